@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{VciBoardInfo, VciCanObj, VciCanStatus, VciErrInfo, VciInitConfig};
+
+/// CAN 函式的來源
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// ControlCAN.dll 與實體裝置
+    ControlCan,
+    /// 不需 DLL 與硬體的虛擬裝置 (virtual_can.rs)
+    Virtual,
+    /// Linux 的 SocketCAN 介面 (socketcan.rs)
+    #[serde(rename = "socketcan")]
+    SocketCan,
+    /// 測試用的 MockCan，不能由前端選擇
+    #[serde(skip_deserializing)]
+    Mock,
+}
+
+/// 所有 CAN 後端的共同介面。參數與回傳值沿用 ControlCAN.dll 的語意，
+/// 接收迴圈、傳送路徑與各協定層只透過這個 trait 存取裝置
+pub trait CanInterface: Send + Sync {
+    fn backend(&self) -> Backend;
+    fn open(&self, dev_type: u32, dev_index: u32) -> Result<(), String>;
+    fn close(&self, dev_type: u32, dev_index: u32);
+    fn init_channel(&self, dev_type: u32, dev_index: u32, channel: u32, config: &VciInitConfig) -> Result<(), String>;
+    fn start(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<(), String>;
+    /// 重設 CAN 控制器 (VCI_ResetCAN)，之後需再呼叫 start；用於 bus-off 恢復
+    fn reset(&self, _dev_type: u32, _dev_index: u32, _channel: u32) -> Result<(), String> {
+        Err(not_supported("VCI_ResetCAN"))
+    }
+    /// 回傳實際送出的訊框數；0 表示傳送緩衝暫時已滿，可以重試。
+    /// 驅動回報錯誤 (例如 -1 裝置不存在) 時回傳其錯誤碼，不應重試
+    fn transmit(&self, dev_type: u32, dev_index: u32, channel: u32, frames: &[VciCanObj]) -> Result<u32, i32>;
+    /// 最多讀取 max_frames 個訊框；驅動回報錯誤時回傳其錯誤碼
+    fn receive(&self, dev_type: u32, dev_index: u32, channel: u32, max_frames: u32, wait_ms: i32) -> Result<Vec<VciCanObj>, i32>;
+    /// 通道尚未讀取的訊框數 (VCI_GetReceiveNum)；後端不支援時回傳 Err，接收迴圈改為固定間隔輪詢
+    fn receive_num(&self, _dev_type: u32, _dev_index: u32, _channel: u32) -> Result<u32, String> {
+        Err(not_supported("VCI_GetReceiveNum"))
+    }
+    /// 讀取並清除通道的錯誤資訊 (VCI_ReadErrInfo)
+    fn read_err_info(&self, _dev_type: u32, _dev_index: u32, _channel: u32) -> Result<VciErrInfo, String> {
+        Err(not_supported("VCI_ReadErrInfo"))
+    }
+    /// 讀取控制器狀態與錯誤計數器 (VCI_ReadCANStatus)
+    fn read_can_status(&self, _dev_type: u32, _dev_index: u32, _channel: u32) -> Result<VciCanStatus, String> {
+        Err(not_supported("VCI_ReadCANStatus"))
+    }
+    /// 讀回裝置目前的參數 (VCI_GetReference)，例如自訂鮑率或濾波設定；回傳驅動寫出的原始位元組
+    fn get_reference(&self, _dev_type: u32, _dev_index: u32, _channel: u32, _ref_type: u32) -> Result<Vec<u8>, String> {
+        Err(not_supported("VCI_GetReference"))
+    }
+    /// 重設裝置的 USB 連線 (VCI_UsbDeviceReset)；裝置會重新列舉，之後需重新開啟
+    fn usb_reset(&self, _dev_type: u32, _dev_index: u32) -> Result<(), String> {
+        Err(not_supported("VCI_UsbDeviceReset"))
+    }
+    /// 目前插著的所有裝置
+    fn find_devices(&self) -> Result<Vec<VciBoardInfo>, String>;
+    fn read_board_info(&self, dev_type: u32, dev_index: u32) -> Result<VciBoardInfo, String>;
+
+    /// 從檔案載入的後端 (ControlCAN.dll) 回傳其實際路徑
+    fn library_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// 各 VCI 函式在此後端是否可用；只有 DLL 可能缺少部分函式
+    fn functions(&self) -> Vec<FunctionSupport> {
+        VCI_FUNCTIONS
+            .iter()
+            .map(|&name| FunctionSupport { name, supported: true })
+            .collect()
+    }
+}
+
+/// 後端會用到的 VCI 函式
+pub const VCI_FUNCTIONS: [&str; 14] = [
+    "VCI_OpenDevice",
+    "VCI_CloseDevice",
+    "VCI_InitCAN",
+    "VCI_StartCAN",
+    "VCI_Transmit",
+    "VCI_Receive",
+    "VCI_GetReceiveNum",
+    "VCI_ReadErrInfo",
+    "VCI_ReadCANStatus",
+    "VCI_ResetCAN",
+    "VCI_GetReference",
+    "VCI_UsbDeviceReset",
+    "VCI_FindUsbDevice2",
+    "VCI_ReadBoardInfo",
+];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FunctionSupport {
+    pub name: &'static str,
+    pub supported: bool,
+}
+
+/// 舊版或相容 DLL 沒有匯出某個函式時回傳的錯誤
+pub fn not_supported(function: &str) -> String {
+    format!("{} is not supported by this DLL version", function)
+}
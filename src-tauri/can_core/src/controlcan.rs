@@ -0,0 +1,440 @@
+use std::error::Error as _;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use libloading::Library;
+use serde::Serialize;
+
+use crate::can_interface::{not_supported, FunctionSupport, VCI_FUNCTIONS};
+use crate::{Backend, CanInterface};
+
+/// 各平台廠商提供的函式庫檔名
+#[cfg(windows)]
+pub const DEFAULT_LIBRARY: &str = "ControlCAN.dll";
+#[cfg(not(windows))]
+pub const DEFAULT_LIBRARY: &str = "libcontrolcan.so";
+
+/// VCI 函式指標的型別：Windows 的 ControlCAN.dll 使用 stdcall，Linux 的 libcontrolcan.so 使用 C 呼叫慣例
+#[cfg(windows)]
+macro_rules! vci_fn {
+    (($($arg:ty),*) -> $ret:ty) => { unsafe extern "stdcall" fn($($arg),*) -> $ret };
+}
+#[cfg(not(windows))]
+macro_rules! vci_fn {
+    (($($arg:ty),*) -> $ret:ty) => { unsafe extern "C" fn($($arg),*) -> $ret };
+}
+
+/// VCI_FindUsbDevice2 最多回報 50 個裝置
+const MAX_USB_DEVICES: usize = 50;
+/// VCI_GetReference 的輸出緩衝；各 RefType 的資料 (自訂鮑率 4 位元組、濾波範圍 12 位元組) 都在此範圍內，
+/// 預留較大的空間以免驅動寫出界
+const REFERENCE_BUFFER_LEN: usize = 256;
+/// get_reference 回傳的位元組數
+pub const REFERENCE_DATA_LEN: usize = 16;
+
+#[repr(C)]
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct VciCanObj {
+    pub id: u32,
+    pub time_stamp: u32,
+    pub time_flag: u8,
+    pub send_type: u8,
+    pub remote_flag: u8,
+    pub extern_flag: u8,
+    pub data_len: u8,
+    pub data: [u8; 8],
+    pub reserved: [u8; 3],
+}
+
+#[repr(C)]
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub struct VciInitConfig {
+    pub acc_code: u32,
+    pub acc_mask: u32,
+    pub reserved: u32,
+    pub filter: u8,
+    pub timing0: u8,
+    pub timing1: u8,
+    pub mode: u8,
+}
+
+/// VCI_ERR_INFO.ErrCode 的位元
+pub const ERR_CAN_OVERFLOW: u32 = 0x0001;
+pub const ERR_CAN_BUFFER_OVERFLOW: u32 = 0x0040;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VciErrInfo {
+    pub err_code: u32,
+    pub passive_err_data: [u8; 3],
+    pub ar_lost_err_data: u8,
+}
+
+/// VCI_ReadCANStatus 回傳的 SJA1000 暫存器內容
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VciCanStatus {
+    pub err_interrupt: u8,
+    pub reg_mode: u8,
+    pub reg_status: u8,
+    pub reg_al_capture: u8,
+    pub reg_ec_capture: u8,
+    pub reg_ew_limit: u8,
+    pub reg_re_counter: u8,
+    pub reg_te_counter: u8,
+    pub reserved: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct VciBoardInfo {
+    pub hw_version: u16,
+    pub fw_version: u16,
+    pub dr_version: u16,
+    pub in_version: u16,
+    pub irq_num: u16,
+    pub can_num: u8,
+    pub str_serial_num: [u8; 20],
+    pub str_hw_type: [u8; 40],
+    pub reserved: [u16; 4],
+}
+
+impl Default for VciBoardInfo {
+    fn default() -> Self {
+        Self {
+            hw_version: 0,
+            fw_version: 0,
+            dr_version: 0,
+            in_version: 0,
+            irq_num: 0,
+            can_num: 0,
+            str_serial_num: [0; 20],
+            str_hw_type: [0; 40],
+            reserved: [0; 4],
+        }
+    }
+}
+
+pub struct CanLibrary {
+    _lib: Arc<Library>,
+    /// 依搜尋順序推得的實際檔案位置
+    pub path: Option<PathBuf>,
+    pub vci_open_device: vci_fn!((u32, u32, u32) -> i32),
+    pub vci_close_device: vci_fn!((u32, u32) -> i32),
+    pub vci_init_can: vci_fn!((u32, u32, u32, *const VciInitConfig) -> i32),
+    pub vci_start_can: vci_fn!((u32, u32, u32) -> i32),
+    pub vci_transmit: vci_fn!((u32, u32, u32, *const VciCanObj, u32) -> i32),
+    pub vci_receive: vci_fn!((u32, u32, u32, *mut VciCanObj, u32, i32) -> i32),
+    /// 舊版與部分相容 DLL 沒有匯出以下函式，缺少時相關指令回傳 not_supported 錯誤
+    pub vci_find_usb_device2: Option<vci_fn!((*mut VciBoardInfo) -> i32)>,
+    pub vci_read_board_info: Option<vci_fn!((u32, u32, *mut VciBoardInfo) -> i32)>,
+    pub vci_get_receive_num: Option<vci_fn!((u32, u32, u32) -> i32)>,
+    pub vci_read_err_info: Option<vci_fn!((u32, u32, u32, *mut VciErrInfo) -> i32)>,
+    pub vci_read_can_status: Option<vci_fn!((u32, u32, u32, *mut VciCanStatus) -> i32)>,
+    pub vci_reset_can: Option<vci_fn!((u32, u32, u32) -> i32)>,
+    pub vci_get_reference: Option<vci_fn!((u32, u32, u32, u32, *mut u8) -> i32)>,
+    pub vci_usb_device_reset: Option<vci_fn!((u32, u32, u32) -> i32)>,
+}
+impl CanLibrary {
+    /// 載入 DLL (或 Linux 上的 .so) 並取得函數指標；選用的函式找不到時設為 None，
+    /// 載入失敗時回傳原因
+    pub fn load(name: &str) -> Result<Arc<Self>, LoadFailure> {
+        let path = resolve_library_path(name);
+        let lib = unsafe { Library::new(name) }.map_err(|error| LoadFailure::classify(path.as_deref(), &error))?;
+        let mut missing = Vec::new();
+        macro_rules! required {
+            ($name:literal) => {
+                match unsafe { lib.get($name.as_bytes()) } {
+                    Ok(symbol) => Some(*symbol),
+                    Err(_) => {
+                        missing.push($name);
+                        None
+                    }
+                }
+            };
+        }
+        let (Some(vci_open_device), Some(vci_close_device), Some(vci_init_can), Some(vci_start_can), Some(vci_transmit), Some(vci_receive)) = (
+            required!("VCI_OpenDevice"),
+            required!("VCI_CloseDevice"),
+            required!("VCI_InitCAN"),
+            required!("VCI_StartCAN"),
+            required!("VCI_Transmit"),
+            required!("VCI_Receive"),
+        ) else {
+            return Err(LoadFailure {
+                reason: LoadFailureReason::MissingSymbols,
+                message: format!("missing {}", missing.join(", ")),
+            });
+        };
+        unsafe {
+            Ok(Arc::new(Self {
+                vci_open_device,
+                vci_close_device,
+                vci_init_can,
+                vci_start_can,
+                vci_transmit,
+                vci_receive,
+                vci_find_usb_device2: lib.get(b"VCI_FindUsbDevice2").ok().map(|symbol| *symbol),
+                vci_read_board_info: lib.get(b"VCI_ReadBoardInfo").ok().map(|symbol| *symbol),
+                vci_get_receive_num: lib.get(b"VCI_GetReceiveNum").ok().map(|symbol| *symbol),
+                vci_read_err_info: lib.get(b"VCI_ReadErrInfo").ok().map(|symbol| *symbol),
+                vci_read_can_status: lib.get(b"VCI_ReadCANStatus").ok().map(|symbol| *symbol),
+                vci_reset_can: lib.get(b"VCI_ResetCAN").ok().map(|symbol| *symbol),
+                vci_get_reference: lib.get(b"VCI_GetReference").ok().map(|symbol| *symbol),
+                vci_usb_device_reset: lib.get(b"VCI_UsbDeviceReset").ok().map(|symbol| *symbol),
+                _lib: Arc::new(lib),
+                path,
+            }))
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadFailureReason {
+    NotFound,
+    /// 例如 64 位元的程式載入 32 位元的 DLL
+    WrongArchitecture,
+    /// 檔案存在，但它依賴的其他 DLL 找不到
+    MissingDependency,
+    MissingSymbols,
+    Other,
+}
+
+/// 載入函式庫失敗的原因
+#[derive(Serialize, Clone, Debug)]
+pub struct LoadFailure {
+    pub reason: LoadFailureReason,
+    pub message: String,
+}
+
+impl LoadFailure {
+    fn classify(path: Option<&Path>, error: &libloading::Error) -> Self {
+        let message = match error.source() {
+            Some(source) => format!("{}: {}", error, source),
+            None => error.to_string(),
+        };
+        // Windows: ERROR_MOD_NOT_FOUND = 126, ERROR_BAD_EXE_FORMAT = 193
+        let os_code = error
+            .source()
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .and_then(io::Error::raw_os_error);
+        let wrong_architecture = path
+            .and_then(binary_architecture)
+            .is_some_and(|arch| arch != std::env::consts::ARCH);
+        let reason = if wrong_architecture || os_code == Some(193) || message.contains("wrong ELF class") {
+            LoadFailureReason::WrongArchitecture
+        } else if os_code == Some(126) || message.contains("No such file") {
+            match path {
+                Some(_) => LoadFailureReason::MissingDependency,
+                None => LoadFailureReason::NotFound,
+            }
+        } else {
+            LoadFailureReason::Other
+        };
+        Self { reason, message }
+    }
+}
+
+/// 依系統的搜尋順序 (近似) 找出函式庫會從哪個檔案載入
+pub fn resolve_library_path(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::current_exe().ok().as_deref().and_then(Path::parent) {
+        dirs.push(dir.to_path_buf());
+    }
+    #[cfg(windows)]
+    if let Some(root) = std::env::var_os("SystemRoot") {
+        dirs.push(Path::new(&root).join("System32"));
+    }
+    if let Ok(dir) = std::env::current_dir() {
+        dirs.push(dir);
+    }
+    let search_var = if cfg!(windows) { "PATH" } else { "LD_LIBRARY_PATH" };
+    if let Some(paths) = std::env::var_os(search_var) {
+        dirs.extend(std::env::split_paths(&paths));
+    }
+    #[cfg(not(windows))]
+    dirs.extend(["/usr/local/lib", "/usr/lib", "/lib"].map(PathBuf::from));
+    dirs.into_iter().map(|dir| dir.join(name)).find(|candidate| candidate.is_file())
+}
+
+/// 從 PE 或 ELF 標頭讀出函式庫的 CPU 架構，名稱與 std::env::consts::ARCH 相同
+pub fn binary_architecture(path: &Path) -> Option<&'static str> {
+    let mut header = Vec::new();
+    std::fs::File::open(path).ok()?.take(4096).read_to_end(&mut header).ok()?;
+    let u16_at = |offset: usize| header.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let machine = if header.starts_with(b"MZ") {
+        let pe_offset = header.get(0x3c..0x40).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)?;
+        if header.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+            return None;
+        }
+        match u16_at(pe_offset + 4)? {
+            0x014c => "x86",
+            0x8664 => "x86_64",
+            0xaa64 => "aarch64",
+            0x01c4 => "arm",
+            _ => return None,
+        }
+    } else if header.starts_with(b"\x7fELF") {
+        match u16_at(18)? {
+            3 => "x86",
+            62 => "x86_64",
+            183 => "aarch64",
+            40 => "arm",
+            _ => return None,
+        }
+    } else {
+        return None;
+    };
+    Some(machine)
+}
+
+impl CanInterface for CanLibrary {
+    fn backend(&self) -> Backend {
+        Backend::ControlCan
+    }
+
+    fn open(&self, dev_type: u32, dev_index: u32) -> Result<(), String> {
+        let reserved = 0u32;
+        match unsafe { (self.vci_open_device)(dev_type, dev_index, reserved) } {
+            1 => Ok(()),
+            status => Err(format!("VCI_OpenDevice failed ({})", status)),
+        }
+    }
+
+    fn close(&self, dev_type: u32, dev_index: u32) {
+        unsafe {
+            (self.vci_close_device)(dev_type, dev_index);
+        }
+    }
+
+    fn init_channel(&self, dev_type: u32, dev_index: u32, channel: u32, config: &VciInitConfig) -> Result<(), String> {
+        match unsafe { (self.vci_init_can)(dev_type, dev_index, channel, config) } {
+            1 => Ok(()),
+            status => Err(format!("VCI_InitCAN failed ({})", status)),
+        }
+    }
+
+    fn start(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<(), String> {
+        match unsafe { (self.vci_start_can)(dev_type, dev_index, channel) } {
+            1 => Ok(()),
+            status => Err(format!("VCI_StartCAN failed ({})", status)),
+        }
+    }
+
+    fn reset(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<(), String> {
+        let reset_can = self.vci_reset_can.ok_or_else(|| not_supported("VCI_ResetCAN"))?;
+        match unsafe { reset_can(dev_type, dev_index, channel) } {
+            1 => Ok(()),
+            status => Err(format!("VCI_ResetCAN failed ({})", status)),
+        }
+    }
+
+    fn transmit(&self, dev_type: u32, dev_index: u32, channel: u32, frames: &[VciCanObj]) -> Result<u32, i32> {
+        let sent = unsafe { (self.vci_transmit)(dev_type, dev_index, channel, frames.as_ptr(), frames.len() as u32) };
+        if sent < 0 {
+            return Err(sent);
+        }
+        Ok(sent as u32)
+    }
+
+    fn receive(&self, dev_type: u32, dev_index: u32, channel: u32, max_frames: u32, wait_ms: i32) -> Result<Vec<VciCanObj>, i32> {
+        let mut buffer: Vec<VciCanObj> = (0..max_frames).map(|_| VciCanObj::default()).collect();
+        let received = unsafe { (self.vci_receive)(dev_type, dev_index, channel, buffer.as_mut_ptr(), max_frames, wait_ms) };
+        if received < 0 {
+            return Err(received);
+        }
+        buffer.truncate((received as u32).min(max_frames) as usize);
+        Ok(buffer)
+    }
+
+    fn receive_num(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<u32, String> {
+        let get_receive_num = self.vci_get_receive_num.ok_or_else(|| not_supported("VCI_GetReceiveNum"))?;
+        match unsafe { get_receive_num(dev_type, dev_index, channel) } {
+            pending if pending < 0 => Err(format!("VCI_GetReceiveNum failed ({})", pending)),
+            pending => Ok(pending as u32),
+        }
+    }
+
+    fn read_err_info(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<VciErrInfo, String> {
+        let read_err_info = self.vci_read_err_info.ok_or_else(|| not_supported("VCI_ReadErrInfo"))?;
+        let mut err_info = VciErrInfo::default();
+        match unsafe { read_err_info(dev_type, dev_index, channel, &mut err_info) } {
+            1 => Ok(err_info),
+            status => Err(format!("VCI_ReadErrInfo failed ({})", status)),
+        }
+    }
+
+    fn read_can_status(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<VciCanStatus, String> {
+        let read_can_status = self.vci_read_can_status.ok_or_else(|| not_supported("VCI_ReadCANStatus"))?;
+        let mut can_status = VciCanStatus::default();
+        match unsafe { read_can_status(dev_type, dev_index, channel, &mut can_status) } {
+            1 => Ok(can_status),
+            status => Err(format!("VCI_ReadCANStatus failed ({})", status)),
+        }
+    }
+
+    fn get_reference(&self, dev_type: u32, dev_index: u32, channel: u32, ref_type: u32) -> Result<Vec<u8>, String> {
+        let get_reference = self.vci_get_reference.ok_or_else(|| not_supported("VCI_GetReference"))?;
+        let mut buffer = [0u8; REFERENCE_BUFFER_LEN];
+        match unsafe { get_reference(dev_type, dev_index, channel, ref_type, buffer.as_mut_ptr()) } {
+            1 => Ok(buffer[..REFERENCE_DATA_LEN].to_vec()),
+            status => Err(format!("VCI_GetReference failed ({})", status)),
+        }
+    }
+
+    fn usb_reset(&self, dev_type: u32, dev_index: u32) -> Result<(), String> {
+        let usb_device_reset = self.vci_usb_device_reset.ok_or_else(|| not_supported("VCI_UsbDeviceReset"))?;
+        let reserved = 0u32;
+        match unsafe { usb_device_reset(dev_type, dev_index, reserved) } {
+            1 => Ok(()),
+            status => Err(format!("VCI_UsbDeviceReset failed ({})", status)),
+        }
+    }
+
+    fn find_devices(&self) -> Result<Vec<VciBoardInfo>, String> {
+        let find_usb_device2 = self.vci_find_usb_device2.ok_or_else(|| not_supported("VCI_FindUsbDevice2"))?;
+        let mut board_infos: Vec<VciBoardInfo> = (0..MAX_USB_DEVICES).map(|_| VciBoardInfo::default()).collect();
+        let found = unsafe { find_usb_device2(board_infos.as_mut_ptr()) };
+        board_infos.truncate(found.clamp(0, MAX_USB_DEVICES as i32) as usize);
+        Ok(board_infos)
+    }
+
+    fn read_board_info(&self, dev_type: u32, dev_index: u32) -> Result<VciBoardInfo, String> {
+        let read_board_info = self.vci_read_board_info.ok_or_else(|| not_supported("VCI_ReadBoardInfo"))?;
+        let mut board_info = VciBoardInfo::default();
+        match unsafe { read_board_info(dev_type, dev_index, &mut board_info) } {
+            1 => Ok(board_info),
+            _ => Err("Failed to read board info".into()),
+        }
+    }
+
+    fn library_path(&self) -> Option<PathBuf> {
+        self.path.clone()
+    }
+
+    fn functions(&self) -> Vec<FunctionSupport> {
+        VCI_FUNCTIONS
+            .iter()
+            .map(|&name| FunctionSupport {
+                name,
+                supported: match name {
+                    "VCI_FindUsbDevice2" => self.vci_find_usb_device2.is_some(),
+                    "VCI_ReadBoardInfo" => self.vci_read_board_info.is_some(),
+                    "VCI_GetReceiveNum" => self.vci_get_receive_num.is_some(),
+                    "VCI_ReadErrInfo" => self.vci_read_err_info.is_some(),
+                    "VCI_ReadCANStatus" => self.vci_read_can_status.is_some(),
+                    "VCI_ResetCAN" => self.vci_reset_can.is_some(),
+                    "VCI_GetReference" => self.vci_get_reference.is_some(),
+                    "VCI_UsbDeviceReset" => self.vci_usb_device_reset.is_some(),
+                    _ => true,
+                },
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::CanInterface;
+
+/// 擁有一個已開啟的裝置。drop 時先停止並等待登記的執行緒，再呼叫一次 VCI_CloseDevice，
+/// 錯誤提早返回或 panic 時也會釋放裝置，不會留下下次無法開啟的裝置。
+/// 有登記執行緒時不可在持有 state 鎖的情況下 drop (執行緒需要取得鎖才會發現旗標已清除)
+pub struct CanDevice {
+    can_lib: Arc<dyn CanInterface>,
+    dev_type: u32,
+    dev_index: u32,
+    /// VCI_OpenDevice 成功且尚未關閉
+    open: bool,
+    /// 各通道執行緒的執行旗標與 handle
+    threads: HashMap<u32, (Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl CanDevice {
+    pub fn open(can_lib: Arc<dyn CanInterface>, dev_type: u32, dev_index: u32) -> Result<Self, String> {
+        can_lib.open(dev_type, dev_index)?;
+        Ok(Self {
+            can_lib,
+            dev_type,
+            dev_index,
+            open: true,
+            threads: HashMap::new(),
+        })
+    }
+
+    pub fn dev_type(&self) -> u32 {
+        self.dev_type
+    }
+
+    pub fn dev_index(&self) -> u32 {
+        self.dev_index
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn interface(&self) -> &dyn CanInterface {
+        self.can_lib.as_ref()
+    }
+
+    pub fn library(&self) -> Arc<dyn CanInterface> {
+        self.can_lib.clone()
+    }
+
+    /// 關閉後在 dev_index 重新開啟 (重新插拔後 index 可能改變)，登記的執行緒保持執行。
+    /// 開啟失敗時保持關閉狀態，drop 時不會再關閉一次
+    pub fn reopen(&mut self, dev_index: u32) -> Result<(), String> {
+        self.close_hardware();
+        self.can_lib.open(self.dev_type, dev_index)?;
+        self.dev_index = dev_index;
+        self.open = true;
+        Ok(())
+    }
+
+    /// 關閉後重設裝置的 USB 連線 (VCI_UsbDeviceReset)；裝置重新列舉後以 reopen 重新開啟
+    pub fn usb_reset(&mut self) -> Result<(), String> {
+        self.close_hardware();
+        self.can_lib.usb_reset(self.dev_type, self.dev_index)
+    }
+
+    /// 登記通道的執行緒；同一通道已有執行緒時舊的 handle 不再等待
+    pub fn register_thread(&mut self, channel: u32, running: Arc<AtomicBool>, handle: JoinHandle<()>) {
+        self.threads.insert(channel, (running, handle));
+    }
+
+    /// 清除一個通道執行緒的旗標並交出 handle，讓呼叫端在鎖外等待結束
+    pub fn take_thread(&mut self, channel: u32) -> Option<JoinHandle<()>> {
+        let (running, handle) = self.threads.remove(&channel)?;
+        running.store(false, Ordering::SeqCst);
+        Some(handle)
+    }
+
+    /// 停止並等待一個通道的執行緒
+    pub fn stop_thread(&mut self, channel: u32) {
+        if let Some(handle) = self.take_thread(channel) {
+            if handle.thread().id() != std::thread::current().id() {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    /// 清除所有登記執行緒的旗標並交出 handle，讓呼叫端在鎖外等待結束
+    pub fn take_threads(&mut self) -> Vec<JoinHandle<()>> {
+        for (running, _) in self.threads.values() {
+            running.store(false, Ordering::SeqCst);
+        }
+        self.threads.drain().map(|(_, (_, handle))| handle).collect()
+    }
+
+    /// 清除所有登記執行緒的旗標並等待結束；由登記的執行緒自己 drop 時不等待自己
+    pub fn stop_threads(&mut self) {
+        let current = std::thread::current().id();
+        for handle in self.take_threads() {
+            if handle.thread().id() != current {
+                let _ = handle.join();
+            }
+        }
+    }
+
+    fn close_hardware(&mut self) {
+        if std::mem::take(&mut self.open) {
+            self.can_lib.close(self.dev_type, self.dev_index);
+        }
+    }
+}
+
+impl Drop for CanDevice {
+    fn drop(&mut self) {
+        self.stop_threads();
+        self.close_hardware();
+    }
+}
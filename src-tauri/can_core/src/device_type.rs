@@ -0,0 +1,142 @@
+use std::fmt;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// ControlCAN.h 中定義的裝置類型代碼 (VCI_USBCAN2 = 4 ...)。
+/// 命令參數可以傳名稱 ("USBCAN2") 或數字代碼；不在表中的數字以 Other 保留，方便使用特殊硬體
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceType {
+    Usbcan1,
+    Usbcan2,
+    Pci9820,
+    Pci9840,
+    Pc104Can2,
+    Pci9820I,
+    Pci5010U,
+    UsbcanEU,
+    Usbcan2EU,
+    Pci5020U,
+    Other(u32),
+}
+
+/// (類型, 顯示名稱, 代碼, 通道數)
+const KNOWN_TYPES: &[(DeviceType, &str, u32, u8)] = &[
+    (DeviceType::Usbcan1, "USBCAN1", 3, 1),
+    (DeviceType::Usbcan2, "USBCAN2", 4, 2),
+    (DeviceType::Pci9820, "PCI9820", 5, 2),
+    (DeviceType::Pci9840, "PCI9840", 14, 4),
+    (DeviceType::Pc104Can2, "PC104CAN2", 15, 2),
+    (DeviceType::Pci9820I, "PCI9820I", 16, 2),
+    (DeviceType::Pci5010U, "PCI5010U", 19, 1),
+    (DeviceType::UsbcanEU, "USBCAN-E-U", 20, 1),
+    (DeviceType::Usbcan2EU, "USBCAN-2E-U", 21, 2),
+    (DeviceType::Pci5020U, "PCI5020U", 22, 2),
+];
+
+impl DeviceType {
+    pub fn code(self) -> u32 {
+        match self {
+            DeviceType::Other(code) => code,
+            known => known.entry().map(|(_, _, code, _)| *code).unwrap_or_default(),
+        }
+    }
+
+    pub fn from_code(code: u32) -> Self {
+        KNOWN_TYPES
+            .iter()
+            .find(|(_, _, c, _)| *c == code)
+            .map(|(t, _, _, _)| *t)
+            .unwrap_or(DeviceType::Other(code))
+    }
+
+    /// 名稱比對不分大小寫，並忽略 '-'、'_' 與空白 (USBCAN-2E-U、usbcan_2e_u 皆可)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let wanted = normalize(name);
+        KNOWN_TYPES
+            .iter()
+            .find(|(_, n, _, _)| normalize(n) == wanted)
+            .map(|(t, _, _, _)| *t)
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        self.entry().map(|(_, name, _, _)| *name)
+    }
+
+    pub fn channel_count(self) -> Option<u8> {
+        self.entry().map(|(_, _, _, channel_count)| *channel_count)
+    }
+
+    fn entry(self) -> Option<&'static (DeviceType, &'static str, u32, u8)> {
+        KNOWN_TYPES.iter().find(|(t, _, _, _)| *t == self)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+impl Serialize for DeviceType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.name() {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_u32(self.code()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DeviceType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DeviceTypeVisitor;
+
+        impl Visitor<'_> for DeviceTypeVisitor {
+            type Value = DeviceType;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a device type name (e.g. \"USBCAN2\") or a numeric dev_type code")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<DeviceType, E> {
+                u32::try_from(v)
+                    .map(DeviceType::from_code)
+                    .map_err(|_| E::custom(format!("dev_type {} out of range", v)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<DeviceType, E> {
+                u32::try_from(v)
+                    .map(DeviceType::from_code)
+                    .map_err(|_| E::custom(format!("dev_type {} out of range", v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<DeviceType, E> {
+                if let Ok(code) = v.trim().parse::<u32>() {
+                    return Ok(DeviceType::from_code(code));
+                }
+                DeviceType::from_name(v).ok_or_else(|| {
+                    let names: Vec<&str> = KNOWN_TYPES.iter().map(|(_, n, _, _)| *n).collect();
+                    E::custom(format!("unknown device type {:?} (known: {})", v, names.join(", ")))
+                })
+            }
+        }
+
+        deserializer.deserialize_any(DeviceTypeVisitor)
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeviceTypeInfo {
+    pub name: &'static str,
+    pub code: u32,
+    pub channel_count: u8,
+}
+
+/// 所有已知的裝置類型
+pub fn known_types() -> Vec<DeviceTypeInfo> {
+    KNOWN_TYPES
+        .iter()
+        .map(|&(_, name, code, channel_count)| DeviceTypeInfo { name, code, channel_count })
+        .collect()
+}
@@ -0,0 +1,22 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod can_interface;
+pub mod controlcan;
+pub mod device;
+pub mod device_type;
+pub mod mock;
+#[cfg(target_os = "linux")]
+pub mod socketcan;
+pub mod virtual_can;
+
+pub use can_interface::{Backend, CanInterface};
+pub use controlcan::{CanLibrary, VciBoardInfo, VciCanObj, VciCanStatus, VciErrInfo, VciInitConfig};
+pub use device::CanDevice;
+pub use device_type::DeviceType;
+
+pub fn host_timestamp_us() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or_default()
+}
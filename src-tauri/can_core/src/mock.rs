@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Backend, CanInterface, VciBoardInfo, VciCanObj, VciCanStatus, VciErrInfo, VciInitConfig};
+
+#[derive(Default)]
+struct MockState {
+    open: HashSet<(u32, u32)>,
+    /// 各裝置成功 open 與 close 的次數
+    opens: HashMap<(u32, u32), u32>,
+    closes: HashMap<(u32, u32), u32>,
+    usb_resets: HashMap<(u32, u32), u32>,
+    /// find_devices 回傳的裝置
+    devices: Vec<VciBoardInfo>,
+    fail_open: bool,
+    /// init_channel 對這個通道回傳失敗
+    fail_init: Option<u32>,
+    initialized: HashMap<(u32, u32, u32), VciInitConfig>,
+    started: HashSet<(u32, u32, u32)>,
+    rx: HashMap<(u32, u32, u32), VecDeque<VciCanObj>>,
+    transmitted: Vec<(u32, VciCanObj)>,
+    fail_transmit: bool,
+    transmit_error: Option<i32>,
+    /// 接下來這麼多次 transmit 回傳 0 (傳送緩衝已滿)
+    busy_transmits: u32,
+    receive_error: Option<i32>,
+    /// 接下來這麼多次 receive / receive_num 直接 panic，模擬驅動程式崩潰
+    panic_receives: u32,
+    /// 下一次 read_err_info 回傳的 ErrCode；讀取後清除，與硬體相同
+    err_code: HashMap<(u32, u32, u32), u32>,
+    /// read_can_status 回傳的狀態，保持到下一次設定為止
+    can_status: HashMap<(u32, u32, u32), VciCanStatus>,
+}
+
+/// 可編排的測試用後端：預先排入要「收到」的訊框，並記錄所有送出的訊框。
+/// 以 mode = 2 (自測) 初始化的通道會把送出的訊框放回自己的接收佇列
+#[derive(Default)]
+pub struct MockCan {
+    state: Mutex<MockState>,
+}
+
+impl MockCan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 排入之後由 receive 依序取出的訊框
+    pub fn queue_receive(&self, dev_type: u32, dev_index: u32, channel: u32, frames: impl IntoIterator<Item = VciCanObj>) {
+        self.state().rx.entry((dev_type, dev_index, channel)).or_default().extend(frames);
+    }
+
+    /// 尚未被讀走的訊框數
+    pub fn pending_receive(&self, dev_type: u32, dev_index: u32, channel: u32) -> usize {
+        self.state().rx.get(&(dev_type, dev_index, channel)).map_or(0, VecDeque::len)
+    }
+
+    /// 目前為止送出的 (通道, 訊框)
+    pub fn transmitted(&self) -> Vec<(u32, VciCanObj)> {
+        self.state().transmitted.clone()
+    }
+
+    pub fn set_transmit_failure(&self, fail: bool) {
+        self.state().fail_transmit = fail;
+    }
+
+    /// 設定後 transmit 一律回傳此錯誤碼 (例如 -1 模擬裝置被拔除)
+    pub fn set_transmit_error(&self, code: Option<i32>) {
+        self.state().transmit_error = code;
+    }
+
+    /// 接下來 count 次 transmit 回傳 0，模擬傳送緩衝暫時已滿
+    pub fn set_busy_transmits(&self, count: u32) {
+        self.state().busy_transmits = count;
+    }
+
+    /// 設定後 receive 一律回傳此錯誤碼 (例如 -1 模擬裝置被拔除)
+    pub fn set_receive_error(&self, code: Option<i32>) {
+        self.state().receive_error = code;
+    }
+
+    /// 接下來 count 次 receive 直接 panic
+    pub fn set_receive_panics(&self, count: u32) {
+        self.state().panic_receives = count;
+    }
+
+    fn check_receive_panic(&self) {
+        let mut state = self.state();
+        if state.panic_receives > 0 {
+            state.panic_receives -= 1;
+            drop(state);
+            panic!("mock receive panic");
+        }
+    }
+
+    /// 設定通道下一次 read_err_info 回傳的 ErrCode (例如 ERR_CAN_OVERFLOW)
+    pub fn set_err_code(&self, dev_type: u32, dev_index: u32, channel: u32, err_code: u32) {
+        self.state().err_code.insert((dev_type, dev_index, channel), err_code);
+    }
+
+    /// 設定通道的控制器狀態 (錯誤計數器、bus-off 位元)
+    pub fn set_can_status(&self, dev_type: u32, dev_index: u32, channel: u32, status: VciCanStatus) {
+        self.state().can_status.insert((dev_type, dev_index, channel), status);
+    }
+
+    /// 設定後 open 一律失敗，模擬裝置被拔除
+    pub fn set_open_failure(&self, fail: bool) {
+        self.state().fail_open = fail;
+    }
+
+    /// 設定後 init_channel 對 channel 回傳失敗
+    pub fn set_init_failure(&self, channel: Option<u32>) {
+        self.state().fail_init = channel;
+    }
+
+    /// 目前為止成功開啟裝置的次數
+    pub fn open_count(&self, dev_type: u32, dev_index: u32) -> u32 {
+        self.state().opens.get(&(dev_type, dev_index)).copied().unwrap_or(0)
+    }
+
+    /// 目前為止呼叫 close 的次數 (包含對未開啟裝置的呼叫)
+    pub fn close_count(&self, dev_type: u32, dev_index: u32) -> u32 {
+        self.state().closes.get(&(dev_type, dev_index)).copied().unwrap_or(0)
+    }
+
+    pub fn usb_reset_count(&self, dev_type: u32, dev_index: u32) -> u32 {
+        self.state().usb_resets.get(&(dev_type, dev_index)).copied().unwrap_or(0)
+    }
+
+    /// 之後 find_devices 回傳這些裝置
+    pub fn set_devices(&self, devices: Vec<VciBoardInfo>) {
+        self.state().devices = devices;
+    }
+
+    pub fn is_open(&self, dev_type: u32, dev_index: u32) -> bool {
+        self.state().open.contains(&(dev_type, dev_index))
+    }
+
+    pub fn is_started(&self, dev_type: u32, dev_index: u32, channel: u32) -> bool {
+        self.state().started.contains(&(dev_type, dev_index, channel))
+    }
+}
+
+impl CanInterface for MockCan {
+    fn backend(&self) -> Backend {
+        Backend::Mock
+    }
+
+    /// 與硬體相同，尚未關閉的裝置不能再次開啟
+    fn open(&self, dev_type: u32, dev_index: u32) -> Result<(), String> {
+        let mut state = self.state();
+        if state.fail_open {
+            return Err("device not present".into());
+        }
+        if !state.open.insert((dev_type, dev_index)) {
+            return Err("device already open".into());
+        }
+        *state.opens.entry((dev_type, dev_index)).or_default() += 1;
+        Ok(())
+    }
+
+    fn close(&self, dev_type: u32, dev_index: u32) {
+        let mut state = self.state();
+        *state.closes.entry((dev_type, dev_index)).or_default() += 1;
+        state.open.remove(&(dev_type, dev_index));
+        state.started.retain(|&(t, i, _)| (t, i) != (dev_type, dev_index));
+    }
+
+    fn init_channel(&self, dev_type: u32, dev_index: u32, channel: u32, config: &VciInitConfig) -> Result<(), String> {
+        let mut state = self.state();
+        if !state.open.contains(&(dev_type, dev_index)) {
+            return Err("device not open".into());
+        }
+        if state.fail_init == Some(channel) {
+            return Err(format!("CAN{} init failed", channel + 1));
+        }
+        state.initialized.insert((dev_type, dev_index, channel), *config);
+        state.started.remove(&(dev_type, dev_index, channel));
+        Ok(())
+    }
+
+    fn start(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<(), String> {
+        let mut state = self.state();
+        if !state.initialized.contains_key(&(dev_type, dev_index, channel)) {
+            return Err(format!("CAN{} not initialized", channel + 1));
+        }
+        state.started.insert((dev_type, dev_index, channel));
+        Ok(())
+    }
+
+    /// 重設後通道停止，控制器狀態回到 error-active
+    fn reset(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<(), String> {
+        let mut state = self.state();
+        state.started.remove(&(dev_type, dev_index, channel));
+        state.can_status.remove(&(dev_type, dev_index, channel));
+        Ok(())
+    }
+
+    fn transmit(&self, dev_type: u32, dev_index: u32, channel: u32, frames: &[VciCanObj]) -> Result<u32, i32> {
+        let mut state = self.state();
+        if let Some(code) = state.transmit_error {
+            return Err(code);
+        }
+        if state.busy_transmits > 0 {
+            state.busy_transmits -= 1;
+            return Ok(0);
+        }
+        if state.fail_transmit || !state.started.contains(&(dev_type, dev_index, channel)) {
+            return Ok(0);
+        }
+        state.transmitted.extend(frames.iter().map(|frame| (channel, frame.clone())));
+        let key = (dev_type, dev_index, channel);
+        if state.initialized.get(&key).is_some_and(|config| config.mode == 2) {
+            state.rx.entry(key).or_default().extend(frames.iter().cloned());
+        }
+        Ok(frames.len() as u32)
+    }
+
+    /// 與驅動相同，佇列為空時最多等待 wait_ms 讓訊框到達
+    fn receive(&self, dev_type: u32, dev_index: u32, channel: u32, max_frames: u32, wait_ms: i32) -> Result<Vec<VciCanObj>, i32> {
+        self.check_receive_panic();
+        let deadline = Instant::now() + Duration::from_millis(wait_ms.max(0) as u64);
+        loop {
+            {
+                let mut state = self.state();
+                if let Some(code) = state.receive_error {
+                    return Err(code);
+                }
+                if let Some(queue) = state.rx.get_mut(&(dev_type, dev_index, channel)).filter(|q| !q.is_empty()) {
+                    let count = queue.len().min(max_frames as usize);
+                    return Ok(queue.drain(..count).collect());
+                }
+            }
+            if Instant::now() >= deadline {
+                return Ok(Vec::new());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// 設定了 receive 錯誤時同樣失敗，讓接收迴圈改呼叫 receive 並回報錯誤
+    fn receive_num(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<u32, String> {
+        self.check_receive_panic();
+        let state = self.state();
+        if let Some(code) = state.receive_error {
+            return Err(format!("VCI_GetReceiveNum failed ({})", code));
+        }
+        Ok(state.rx.get(&(dev_type, dev_index, channel)).map_or(0, VecDeque::len) as u32)
+    }
+
+    fn read_err_info(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<VciErrInfo, String> {
+        Ok(VciErrInfo {
+            err_code: self.state().err_code.remove(&(dev_type, dev_index, channel)).unwrap_or(0),
+            ..Default::default()
+        })
+    }
+
+    fn read_can_status(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<VciCanStatus, String> {
+        Ok(self.state().can_status.get(&(dev_type, dev_index, channel)).copied().unwrap_or_default())
+    }
+
+    /// 與硬體相同，重設後裝置回到未開啟的狀態
+    fn usb_reset(&self, dev_type: u32, dev_index: u32) -> Result<(), String> {
+        let mut state = self.state();
+        *state.usb_resets.entry((dev_type, dev_index)).or_default() += 1;
+        state.open.remove(&(dev_type, dev_index));
+        state.started.retain(|&(t, i, _)| (t, i) != (dev_type, dev_index));
+        Ok(())
+    }
+
+    fn find_devices(&self) -> Result<Vec<VciBoardInfo>, String> {
+        Ok(self.state().devices.clone())
+    }
+
+    fn read_board_info(&self, _dev_type: u32, _dev_index: u32) -> Result<VciBoardInfo, String> {
+        Err("mock devices have no board info".into())
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::can_interface::{FunctionSupport, VCI_FUNCTIONS};
+use crate::{Backend, CanInterface, VciBoardInfo, VciCanObj, VciInitConfig};
+
+/// /sys/class/net/*/type 中 CAN 介面的 ARPHRD_CAN
+const ARPHRD_CAN: &str = "280";
+
+/// 以 raw CAN socket 存取 Linux 的 can0/vcan0 等介面。每個介面視為一個只有通道 0 的裝置，
+/// dev_index 為介面在 list_interfaces() 中的位置，序號欄位即介面名稱。
+/// 位元率由 ip link 設定，init_channel 只套用接收過濾
+#[derive(Default)]
+pub struct SocketCan {
+    sockets: Mutex<HashMap<(u32, u32), OwnedFd>>,
+}
+
+/// 目前系統上所有的 CAN 介面名稱，依名稱排序
+pub fn list_interfaces() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir("/sys/class/net")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            fs::read_to_string(entry.path().join("type")).is_ok_and(|t| t.trim() == ARPHRD_CAN)
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+fn board_info(name: &str) -> VciBoardInfo {
+    let mut info = VciBoardInfo {
+        can_num: 1,
+        ..Default::default()
+    };
+    let serial = name.as_bytes();
+    let len = serial.len().min(info.str_serial_num.len() - 1);
+    info.str_serial_num[..len].copy_from_slice(&serial[..len]);
+    let hw_type = b"SocketCAN";
+    info.str_hw_type[..hw_type.len()].copy_from_slice(hw_type);
+    info
+}
+
+fn open_socket(name: &str) -> io::Result<OwnedFd> {
+    let c_name = CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let ifindex = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if ifindex == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, libc::CAN_RAW) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut addr: libc::sockaddr_can = unsafe { mem::zeroed() };
+    addr.can_family = libc::AF_CAN as libc::sa_family_t;
+    addr.can_ifindex = ifindex as libc::c_int;
+    let status = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            &addr as *const libc::sockaddr_can as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+        )
+    };
+    if status < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
+/// 把 SJA1000 的單一過濾器 (AccCode/AccMask，mask 位元 1 = 不比較) 換成 CAN_RAW_FILTER。
+/// AccMask 全部為 1 時接收所有訊框
+fn raw_filters(config: &VciInitConfig) -> Vec<libc::can_filter> {
+    if config.acc_mask == 0xFFFF_FFFF {
+        return vec![libc::can_filter { can_id: 0, can_mask: 0 }];
+    }
+    vec![
+        libc::can_filter {
+            can_id: (config.acc_code >> 21) & libc::CAN_SFF_MASK,
+            can_mask: (!(config.acc_mask >> 21) & libc::CAN_SFF_MASK) | libc::CAN_EFF_FLAG,
+        },
+        libc::can_filter {
+            can_id: ((config.acc_code >> 3) & libc::CAN_EFF_MASK) | libc::CAN_EFF_FLAG,
+            can_mask: (!(config.acc_mask >> 3) & libc::CAN_EFF_MASK) | libc::CAN_EFF_FLAG,
+        },
+    ]
+}
+
+fn to_can_frame(can_obj: &VciCanObj) -> libc::can_frame {
+    let mut frame: libc::can_frame = unsafe { mem::zeroed() };
+    frame.can_id = match can_obj.extern_flag != 0 {
+        true => (can_obj.id & libc::CAN_EFF_MASK) | libc::CAN_EFF_FLAG,
+        false => can_obj.id & libc::CAN_SFF_MASK,
+    };
+    if can_obj.remote_flag != 0 {
+        frame.can_id |= libc::CAN_RTR_FLAG;
+    }
+    frame.can_dlc = can_obj.data_len.min(8);
+    frame.data = can_obj.data;
+    frame
+}
+
+fn from_can_frame(frame: &libc::can_frame) -> VciCanObj {
+    let extended = frame.can_id & libc::CAN_EFF_FLAG != 0;
+    VciCanObj {
+        id: frame.can_id & if extended { libc::CAN_EFF_MASK } else { libc::CAN_SFF_MASK },
+        extern_flag: extended as u8,
+        remote_flag: (frame.can_id & libc::CAN_RTR_FLAG != 0) as u8,
+        data_len: frame.can_dlc.min(8),
+        data: frame.data,
+        ..Default::default()
+    }
+}
+
+impl SocketCan {
+    fn sockets(&self) -> MutexGuard<'_, HashMap<(u32, u32), OwnedFd>> {
+        self.sockets.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CanInterface for SocketCan {
+    fn backend(&self) -> Backend {
+        Backend::SocketCan
+    }
+
+    fn open(&self, dev_type: u32, dev_index: u32) -> Result<(), String> {
+        let name = list_interfaces()
+            .into_iter()
+            .nth(dev_index as usize)
+            .ok_or_else(|| format!("CAN interface {} not found", dev_index))?;
+        let socket = open_socket(&name).map_err(|e| format!("Failed to open {}: {}", name, e))?;
+        self.sockets().insert((dev_type, dev_index), socket);
+        Ok(())
+    }
+
+    fn close(&self, dev_type: u32, dev_index: u32) {
+        self.sockets().remove(&(dev_type, dev_index));
+    }
+
+    fn init_channel(&self, dev_type: u32, dev_index: u32, channel: u32, config: &VciInitConfig) -> Result<(), String> {
+        if channel != 0 {
+            return Err(format!("SocketCAN interfaces have a single channel (got CAN{})", channel + 1));
+        }
+        let sockets = self.sockets();
+        let socket = sockets.get(&(dev_type, dev_index)).ok_or("device not open")?;
+        let filters = raw_filters(config);
+        let status = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_CAN_RAW,
+                libc::CAN_RAW_FILTER,
+                filters.as_ptr() as *const libc::c_void,
+                mem::size_of_val(filters.as_slice()) as libc::socklen_t,
+            )
+        };
+        if status < 0 {
+            return Err(format!("CAN_RAW_FILTER failed: {}", io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    fn start(&self, dev_type: u32, dev_index: u32, channel: u32) -> Result<(), String> {
+        if channel != 0 {
+            return Err(format!("SocketCAN interfaces have a single channel (got CAN{})", channel + 1));
+        }
+        match self.sockets().contains_key(&(dev_type, dev_index)) {
+            true => Ok(()),
+            false => Err("device not open".into()),
+        }
+    }
+
+    fn transmit(&self, dev_type: u32, dev_index: u32, channel: u32, frames: &[VciCanObj]) -> Result<u32, i32> {
+        let sockets = self.sockets();
+        let Some(socket) = sockets.get(&(dev_type, dev_index)).filter(|_| channel == 0) else {
+            return Err(-1);
+        };
+        let mut sent = 0;
+        for can_obj in frames {
+            let frame = to_can_frame(can_obj);
+            let written = unsafe {
+                libc::write(
+                    socket.as_raw_fd(),
+                    &frame as *const libc::can_frame as *const libc::c_void,
+                    mem::size_of::<libc::can_frame>(),
+                )
+            };
+            // 傳送佇列已滿 (EAGAIN) 時停止，回傳已送出的數量；其他錯誤 (例如介面已關閉) 不可重試
+            if written != mem::size_of::<libc::can_frame>() as isize {
+                let errno = std::io::Error::last_os_error().raw_os_error();
+                if sent == 0 && written < 0 && errno != Some(libc::EAGAIN) {
+                    return Err(-1);
+                }
+                break;
+            }
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// socket 為非阻塞模式，讀到沒有資料為止；wait_ms 不使用
+    fn receive(&self, dev_type: u32, dev_index: u32, channel: u32, max_frames: u32, _wait_ms: i32) -> Result<Vec<VciCanObj>, i32> {
+        let sockets = self.sockets();
+        let socket = sockets.get(&(dev_type, dev_index)).ok_or(-1)?;
+        let mut received = Vec::new();
+        if channel != 0 {
+            return Ok(received);
+        }
+        while received.len() < max_frames as usize {
+            let mut frame: libc::can_frame = unsafe { mem::zeroed() };
+            let read = unsafe {
+                libc::read(
+                    socket.as_raw_fd(),
+                    &mut frame as *mut libc::can_frame as *mut libc::c_void,
+                    mem::size_of::<libc::can_frame>(),
+                )
+            };
+            if read < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::WouldBlock {
+                    break;
+                }
+                // 介面被移除 (ENODEV) 等錯誤比照 DLL 回傳 -1
+                return Err(-1);
+            }
+            if read as usize != mem::size_of::<libc::can_frame>() || frame.can_id & libc::CAN_ERR_FLAG != 0 {
+                continue;
+            }
+            received.push(from_can_frame(&frame));
+        }
+        Ok(received)
+    }
+
+    fn find_devices(&self) -> Result<Vec<VciBoardInfo>, String> {
+        Ok(list_interfaces().iter().map(|name| board_info(name)).collect())
+    }
+
+    fn read_board_info(&self, _dev_type: u32, dev_index: u32) -> Result<VciBoardInfo, String> {
+        list_interfaces()
+            .get(dev_index as usize)
+            .map(|name| board_info(name))
+            .ok_or_else(|| format!("CAN interface {} not found", dev_index))
+    }
+
+    /// socket 無法得知佇列中的訊框數，錯誤與控制器狀態也以錯誤訊框回報，bus-off 由核心的 restart-ms 恢復，沒有以下函式
+    fn functions(&self) -> Vec<FunctionSupport> {
+        VCI_FUNCTIONS
+            .iter()
+            .map(|&name| FunctionSupport {
+                name,
+                supported: !matches!(name, "VCI_GetReceiveNum" | "VCI_ReadErrInfo" | "VCI_ReadCANStatus" | "VCI_ResetCAN" | "VCI_GetReference"),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use crate::{Backend, CanInterface, VciBoardInfo, VciCanObj, VciCanStatus, VciErrInfo, VciInitConfig};
+
+pub mod scenario;
+
+pub use scenario::{Scenario, ScenarioReport};
+use scenario::ScenarioRun;
+
+/// 虛擬裝置的通道數，與 CANalyst-II 相同
+pub const VIRTUAL_CHANNELS: usize = 2;
+/// 每個通道的接收佇列上限，超過時丟棄最舊的訊框 (模擬硬體緩衝溢位)
+const VIRTUAL_RX_CAPACITY: usize = 10_000;
+pub const VIRTUAL_SERIAL: &str = "VIRTUAL0001";
+
+/// 合成流量的資料內容
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SyntheticPayload {
+    /// 前兩個位元組為遞增的計數器 (little endian)，其餘沿用 data
+    #[default]
+    Counter,
+    /// 每次產生新的隨機位元組
+    Random,
+    Constant,
+}
+
+/// 一個週期性出現在虛擬通道上的訊框
+#[derive(Deserialize, Clone, Debug)]
+pub struct SyntheticMessage {
+    pub channel: u32,
+    pub id: u32,
+    #[serde(default)]
+    pub extended: Option<bool>,
+    pub period_ms: u64,
+    #[serde(default)]
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub dlc: Option<u8>,
+    #[serde(default)]
+    pub payload: SyntheticPayload,
+}
+
+struct SyntheticSource {
+    message: SyntheticMessage,
+    /// 距開啟時間的下一次產生時間 (微秒)
+    next_due_us: u64,
+    counter: u16,
+}
+
+#[derive(Default)]
+struct VirtualChannel {
+    started: bool,
+    /// 啟動時距開啟時間的微秒數；與硬體一樣，時間戳記從 VCI_StartCAN 起算
+    started_us: u64,
+    rx: VecDeque<VciCanObj>,
+}
+
+/// 虛擬匯流排：兩個通道互相迴路，送到一個通道的訊框會出現在另一個通道；載入情境時另有模擬 ECU 在各通道上收發
+struct VirtualBus {
+    open: bool,
+    opened_at: Instant,
+    channels: [VirtualChannel; VIRTUAL_CHANNELS],
+    sources: Vec<SyntheticSource>,
+    scenario: Option<ScenarioRun>,
+    random_state: u32,
+}
+
+impl VirtualBus {
+    fn elapsed_us(&self) -> u64 {
+        self.opened_at.elapsed().as_micros() as u64
+    }
+
+    fn channel(&mut self, channel: u32) -> Option<&mut VirtualChannel> {
+        self.channels.get_mut(channel as usize).filter(|c| c.started)
+    }
+
+    fn deliver(&mut self, channel: u32, mut can_obj: VciCanObj) {
+        let elapsed_us = self.elapsed_us();
+        if let Some(target) = self.channel(channel) {
+            // 裝置時間戳記單位 0.1 ms
+            can_obj.time_stamp = (elapsed_us.saturating_sub(target.started_us) / 100) as u32;
+            can_obj.time_flag = 1;
+            if target.rx.len() >= VIRTUAL_RX_CAPACITY {
+                target.rx.pop_front();
+            }
+            target.rx.push_back(can_obj);
+        }
+    }
+
+    fn next_random(&mut self) -> u8 {
+        // xorshift32，只需要看起來會變動的資料
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random_state = x;
+        (x >> 24) as u8
+    }
+
+    /// 產生到目前為止應該出現的合成訊框
+    fn generate(&mut self) {
+        let now_us = self.elapsed_us();
+        for index in 0..self.sources.len() {
+            while self.sources[index].next_due_us <= now_us {
+                let source = &mut self.sources[index];
+                source.next_due_us += source.message.period_ms.max(1) * 1000;
+                source.counter = source.counter.wrapping_add(1);
+                let message = &source.message;
+                let counter = source.counter;
+                let default_dlc = if message.data.is_empty() { 8 } else { message.data.len() as u8 };
+                let dlc = message.dlc.unwrap_or(default_dlc).min(8);
+                let mut can_obj = VciCanObj {
+                    id: message.id,
+                    extern_flag: message.extended.unwrap_or(message.id > 0x7FF) as u8,
+                    data_len: dlc,
+                    ..Default::default()
+                };
+                let len = message.data.len().min(8);
+                can_obj.data[..len].copy_from_slice(&message.data[..len]);
+                let (channel, payload) = (message.channel, message.payload.clone());
+                match payload {
+                    SyntheticPayload::Counter => can_obj.data[..2].copy_from_slice(&counter.to_le_bytes()),
+                    SyntheticPayload::Random => {
+                        for i in 0..dlc as usize {
+                            can_obj.data[i] = self.next_random();
+                        }
+                    }
+                    SyntheticPayload::Constant => {}
+                }
+                self.deliver(channel, can_obj);
+            }
+        }
+        let due = self.scenario.as_mut().map(|run| run.due(now_us)).unwrap_or_default();
+        for (channel, can_obj) in due {
+            self.deliver(channel, can_obj);
+        }
+    }
+
+    fn set_traffic(&mut self, traffic: Vec<SyntheticMessage>) {
+        let now_us = self.elapsed_us();
+        self.sources = traffic
+            .into_iter()
+            .map(|message| SyntheticSource {
+                next_due_us: now_us + message.period_ms.max(1) * 1000,
+                message,
+                counter: 0,
+            })
+            .collect();
+    }
+}
+
+fn board_info() -> VciBoardInfo {
+    let mut info = VciBoardInfo {
+        hw_version: 0x0100,
+        fw_version: 0x0100,
+        dr_version: 0x0100,
+        in_version: 0x0100,
+        can_num: VIRTUAL_CHANNELS as u8,
+        ..Default::default()
+    };
+    info.str_serial_num[..VIRTUAL_SERIAL.len()].copy_from_slice(VIRTUAL_SERIAL.as_bytes());
+    let hw_type = b"Virtual CAN";
+    info.str_hw_type[..hw_type.len()].copy_from_slice(hw_type);
+    info
+}
+
+/// 虛擬後端；與實體裝置相同，開啟後需 init + start 通道才會收發
+pub struct VirtualCan {
+    bus: Mutex<VirtualBus>,
+}
+
+impl VirtualCan {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            bus: Mutex::new(VirtualBus {
+                open: false,
+                opened_at: Instant::now(),
+                channels: Default::default(),
+                sources: Vec::new(),
+                scenario: None,
+                random_state: 0x1234_5678,
+            }),
+        })
+    }
+
+    fn bus(&self) -> MutexGuard<'_, VirtualBus> {
+        self.bus.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn set_traffic(&self, traffic: Vec<SyntheticMessage>) {
+        self.bus().set_traffic(traffic);
+    }
+
+    /// 開始執行情境 (取代先前的情境)，None 時停止；裝置重新開啟時情境從頭開始
+    pub fn set_scenario(&self, scenario: Option<Scenario>) -> Result<(), String> {
+        if let Some(scenario) = &scenario {
+            scenario.validate()?;
+        }
+        let mut bus = self.bus();
+        let now_us = bus.elapsed_us();
+        bus.scenario = scenario.map(|scenario| ScenarioRun::new(scenario, now_us));
+        Ok(())
+    }
+
+    /// 目前情境的執行結果與檢查；沒有情境時為 None
+    pub fn scenario_report(&self) -> Option<ScenarioReport> {
+        let mut bus = self.bus();
+        bus.generate();
+        let now_us = bus.elapsed_us();
+        bus.scenario.as_ref().map(|run| run.report(now_us))
+    }
+}
+
+impl CanInterface for VirtualCan {
+    fn backend(&self) -> Backend {
+        Backend::Virtual
+    }
+
+    fn open(&self, _dev_type: u32, dev_index: u32) -> Result<(), String> {
+        if dev_index != 0 {
+            return Err(format!("virtual device {} does not exist", dev_index));
+        }
+        let mut bus = self.bus();
+        bus.open = true;
+        bus.opened_at = Instant::now();
+        bus.channels = Default::default();
+        let sources = std::mem::take(&mut bus.sources).into_iter().map(|s| s.message).collect();
+        bus.set_traffic(sources);
+        bus.scenario = bus.scenario.take().map(|run| ScenarioRun::new(run.scenario().clone(), 0));
+        Ok(())
+    }
+
+    fn close(&self, _dev_type: u32, _dev_index: u32) {
+        let mut bus = self.bus();
+        bus.open = false;
+        bus.channels = Default::default();
+    }
+
+    fn init_channel(&self, _dev_type: u32, _dev_index: u32, channel: u32, _config: &VciInitConfig) -> Result<(), String> {
+        let mut bus = self.bus();
+        if !bus.open {
+            return Err("virtual device not open".into());
+        }
+        let target = bus.channels.get_mut(channel as usize).ok_or_else(|| format!("CAN{} does not exist", channel + 1))?;
+        *target = VirtualChannel::default();
+        Ok(())
+    }
+
+    fn start(&self, _dev_type: u32, _dev_index: u32, channel: u32) -> Result<(), String> {
+        let mut bus = self.bus();
+        if !bus.open {
+            return Err("virtual device not open".into());
+        }
+        let elapsed_us = bus.elapsed_us();
+        let target = bus.channels.get_mut(channel as usize).ok_or_else(|| format!("CAN{} does not exist", channel + 1))?;
+        target.started = true;
+        target.started_us = elapsed_us;
+        Ok(())
+    }
+
+    fn reset(&self, _dev_type: u32, _dev_index: u32, channel: u32) -> Result<(), String> {
+        let mut bus = self.bus();
+        if !bus.open {
+            return Err("virtual device not open".into());
+        }
+        let target = bus.channels.get_mut(channel as usize).ok_or_else(|| format!("CAN{} does not exist", channel + 1))?;
+        target.started = false;
+        Ok(())
+    }
+
+    fn transmit(&self, _dev_type: u32, _dev_index: u32, channel: u32, frames: &[VciCanObj]) -> Result<u32, i32> {
+        let mut bus = self.bus();
+        if !bus.open {
+            return Err(-1);
+        }
+        if bus.channel(channel).is_none() {
+            return Ok(0);
+        }
+        let peer = (channel + 1) % VIRTUAL_CHANNELS as u32;
+        let now_us = bus.elapsed_us();
+        for frame in frames {
+            if let Some(run) = bus.scenario.as_mut() {
+                run.observe(channel, frame, now_us);
+            }
+            let copy = VciCanObj {
+                id: frame.id,
+                remote_flag: frame.remote_flag,
+                extern_flag: frame.extern_flag,
+                data_len: frame.data_len.min(8),
+                data: frame.data,
+                ..Default::default()
+            };
+            bus.deliver(peer, copy);
+        }
+        Ok(frames.len() as u32)
+    }
+
+    /// wait_ms 與實際的 DLL 一樣不會等待
+    fn receive(&self, _dev_type: u32, _dev_index: u32, channel: u32, max_frames: u32, _wait_ms: i32) -> Result<Vec<VciCanObj>, i32> {
+        let mut bus = self.bus();
+        if !bus.open {
+            return Err(-1);
+        }
+        bus.generate();
+        let Some(source) = bus.channel(channel) else {
+            return Ok(Vec::new());
+        };
+        let count = source.rx.len().min(max_frames as usize);
+        Ok(source.rx.drain(..count).collect())
+    }
+
+    fn receive_num(&self, _dev_type: u32, _dev_index: u32, channel: u32) -> Result<u32, String> {
+        let mut bus = self.bus();
+        if !bus.open {
+            return Err("VCI_GetReceiveNum failed (-1)".into());
+        }
+        bus.generate();
+        Ok(bus.channel(channel).map_or(0, |source| source.rx.len() as u32))
+    }
+
+    /// 虛擬匯流排不會發生錯誤
+    fn read_err_info(&self, _dev_type: u32, _dev_index: u32, _channel: u32) -> Result<VciErrInfo, String> {
+        if !self.bus().open {
+            return Err("VCI_ReadErrInfo failed (0)".into());
+        }
+        Ok(VciErrInfo::default())
+    }
+
+    /// 錯誤計數器恆為 0 (error-active)
+    fn read_can_status(&self, _dev_type: u32, _dev_index: u32, _channel: u32) -> Result<VciCanStatus, String> {
+        if !self.bus().open {
+            return Err("VCI_ReadCANStatus failed (0)".into());
+        }
+        Ok(VciCanStatus::default())
+    }
+
+    fn find_devices(&self) -> Result<Vec<VciBoardInfo>, String> {
+        Ok(vec![board_info()])
+    }
+
+    fn read_board_info(&self, _dev_type: u32, dev_index: u32) -> Result<VciBoardInfo, String> {
+        match dev_index {
+            0 => Ok(board_info()),
+            _ => Err(format!("virtual device {} does not exist", dev_index)),
+        }
+    }
+}
@@ -0,0 +1,893 @@
+use serde::{Deserialize, Serialize};
+
+use super::VIRTUAL_CHANNELS;
+use crate::VciCanObj;
+
+/// 報告中保留的狀態切換數，超過時捨棄最舊的
+const MAX_TRANSITIONS: usize = 256;
+
+/// 模擬 ECU 的情境：週期訊息 (可帶逐次遞增的訊號)、對特定請求的回應、故障注入，
+/// 以及對應用程式送出訊框的檢查。訊框的 channel 為模擬 ECU 連接的通道：
+/// 它看得到應用程式在該通道送出的訊框，它送出的訊框也由該通道收到
+#[derive(Deserialize, Clone, Debug)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 開始時的狀態；回應可以切換狀態，週期訊息與回應可以限定只在某些狀態下動作
+    #[serde(default = "default_state")]
+    pub initial_state: String,
+    #[serde(default)]
+    pub cyclic: Vec<CyclicMessage>,
+    #[serde(default)]
+    pub responses: Vec<Response>,
+    #[serde(default)]
+    pub assertions: Vec<ScenarioAssertion>,
+}
+
+fn default_state() -> String {
+    "initial".into()
+}
+
+fn default_step() -> u64 {
+    1
+}
+
+fn default_xor() -> u8 {
+    0xFF
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CyclicMessage {
+    pub channel: u32,
+    pub id: u32,
+    #[serde(default)]
+    pub extended: Option<bool>,
+    pub period_ms: u64,
+    /// 訊號以外的位元組
+    #[serde(default)]
+    pub data: Vec<u8>,
+    /// 省略時為 data 的長度，data 為空時為 8
+    #[serde(default)]
+    pub dlc: Option<u8>,
+    #[serde(default)]
+    pub signals: Vec<SignalRamp>,
+    /// 只在這些狀態下送出；空陣列表示所有狀態
+    #[serde(default)]
+    pub states: Vec<String>,
+    #[serde(default)]
+    pub fault: Fault,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ByteOrder {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RampShape {
+    /// 到 to 之後回到 from
+    #[default]
+    Sawtooth,
+    /// 在 from 與 to 之間來回
+    Triangle,
+}
+
+/// 每送出一次前進 step 的原始值；位元位置與 DBC 相同 (big_endian 的 start_bit 為最高位元)
+#[derive(Deserialize, Clone, Debug)]
+pub struct SignalRamp {
+    pub start_bit: u32,
+    pub length: u32,
+    #[serde(default)]
+    pub byte_order: ByteOrder,
+    pub from: u64,
+    pub to: u64,
+    #[serde(default = "default_step")]
+    pub step: u64,
+    #[serde(default)]
+    pub shape: RampShape,
+}
+
+/// 故障注入；次數從 1 起算，兩者同時成立時丟棄優先
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Fault {
+    /// 每第 N 次不送出
+    #[serde(default)]
+    pub drop_every: Option<u32>,
+    #[serde(default)]
+    pub corrupt: Option<Corruption>,
+}
+
+/// 把 data[byte] 與 xor 做 XOR；every 省略時每次都破壞
+#[derive(Deserialize, Clone, Debug)]
+pub struct Corruption {
+    pub byte: usize,
+    #[serde(default = "default_xor")]
+    pub xor: u8,
+    #[serde(default)]
+    pub every: Option<u32>,
+}
+
+/// 應用程式在 channel 送出 request_id 且資料以 data_prefix 開頭時，delay_ms 後送出 response_id。
+/// 同一個請求只由第一個符合 (含狀態條件) 的回應處理
+#[derive(Deserialize, Clone, Debug)]
+pub struct Response {
+    pub channel: u32,
+    pub request_id: u32,
+    #[serde(default)]
+    pub data_prefix: Vec<u8>,
+    pub response_id: u32,
+    #[serde(default)]
+    pub extended: Option<bool>,
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub dlc: Option<u8>,
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// 只在這些狀態下回應；空陣列表示所有狀態
+    #[serde(default)]
+    pub states: Vec<String>,
+    /// 收到請求後切換到的狀態；回應被 fault 丟棄時仍會切換
+    #[serde(default)]
+    pub next_state: Option<String>,
+    #[serde(default)]
+    pub fault: Fault,
+}
+
+/// 在 get_virtual_scenario_report 時評估的檢查
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScenarioAssertion {
+    /// 應用程式在 channel 上送出的符合訊框數與相鄰兩個訊框的最大間隔
+    Transmitted {
+        name: String,
+        channel: u32,
+        id: u32,
+        #[serde(default)]
+        data_prefix: Vec<u8>,
+        #[serde(default)]
+        min_count: Option<u64>,
+        #[serde(default)]
+        max_count: Option<u64>,
+        #[serde(default)]
+        max_interval_ms: Option<u64>,
+    },
+    /// 產生報告時的狀態
+    State { name: String, state: String },
+}
+
+fn check_frame(context: &str, channel: u32, data: &[u8], dlc: Option<u8>) -> Result<(), String> {
+    if channel as usize >= VIRTUAL_CHANNELS {
+        return Err(format!("{}: channel {} out of range (virtual device has {} channels)", context, channel, VIRTUAL_CHANNELS));
+    }
+    if data.len() > 8 || dlc.is_some_and(|dlc| dlc > 8) {
+        return Err(format!("{}: data exceeds 8 bytes", context));
+    }
+    Ok(())
+}
+
+fn check_fault(context: &str, fault: &Fault) -> Result<(), String> {
+    if fault.drop_every == Some(0) || fault.corrupt.as_ref().is_some_and(|c| c.every == Some(0)) {
+        return Err(format!("{}: fault intervals must be at least 1", context));
+    }
+    if fault.corrupt.as_ref().is_some_and(|c| c.byte >= 8) {
+        return Err(format!("{}: corrupt.byte must be below 8", context));
+    }
+    Ok(())
+}
+
+/// 訊號在 8 個位元組中佔用的位元 (依序為最低位到最高位)；超出範圍時為 None
+fn signal_bits(signal: &SignalRamp) -> Option<Vec<u32>> {
+    if signal.length == 0 || signal.length > 64 {
+        return None;
+    }
+    let mut bits = Vec::with_capacity(signal.length as usize);
+    let mut bit = signal.start_bit;
+    for _ in 0..signal.length {
+        if bit >= 64 {
+            return None;
+        }
+        bits.push(bit);
+        bit = match signal.byte_order {
+            ByteOrder::LittleEndian => bit + 1,
+            // Motorola 從最高位元往下，跨位元組時跳到下一個位元組的第 7 位元
+            ByteOrder::BigEndian if bit.is_multiple_of(8) => bit + 15,
+            ByteOrder::BigEndian => bit - 1,
+        };
+    }
+    if signal.byte_order == ByteOrder::BigEndian {
+        bits.reverse();
+    }
+    Some(bits)
+}
+
+impl Scenario {
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, message) in self.cyclic.iter().enumerate() {
+            let context = format!("cyclic[{}]", index);
+            check_frame(&context, message.channel, &message.data, message.dlc)?;
+            if message.period_ms == 0 {
+                return Err(format!("{}: period_ms must be greater than 0", context));
+            }
+            check_fault(&context, &message.fault)?;
+            for (signal_index, signal) in message.signals.iter().enumerate() {
+                let context = format!("{}.signals[{}]", context, signal_index);
+                if signal_bits(signal).is_none() {
+                    return Err(format!("{}: bits do not fit in 8 bytes", context));
+                }
+                let max = if signal.length == 64 { u64::MAX } else { (1 << signal.length) - 1 };
+                if signal.from > signal.to || signal.to > max || signal.step == 0 {
+                    return Err(format!("{}: needs from <= to <= {} and step > 0", context, max));
+                }
+            }
+        }
+        for (index, response) in self.responses.iter().enumerate() {
+            let context = format!("responses[{}]", index);
+            check_frame(&context, response.channel, &response.data, response.dlc)?;
+            if response.data_prefix.len() > 8 {
+                return Err(format!("{}: data_prefix exceeds 8 bytes", context));
+            }
+            check_fault(&context, &response.fault)?;
+        }
+        for (index, assertion) in self.assertions.iter().enumerate() {
+            if let ScenarioAssertion::Transmitted { channel, min_count, max_count, .. } = assertion {
+                let context = format!("assertions[{}]", index);
+                check_frame(&context, *channel, &[], None)?;
+                if min_count.zip(*max_count).is_some_and(|(min, max)| min > max) {
+                    return Err(format!("{}: min_count exceeds max_count", context));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn build_frame(id: u32, extended: Option<bool>, data: &[u8], dlc: Option<u8>) -> VciCanObj {
+    let default_dlc = if data.is_empty() { 8 } else { data.len() as u8 };
+    let mut can_obj = VciCanObj {
+        id,
+        extern_flag: extended.unwrap_or(id > 0x7FF) as u8,
+        data_len: dlc.unwrap_or(default_dlc).min(8),
+        ..Default::default()
+    };
+    can_obj.data[..data.len()].copy_from_slice(data);
+    can_obj
+}
+
+fn ramp_value(signal: &SignalRamp, emitted: u64) -> u64 {
+    let steps = ((signal.to - signal.from) / signal.step).saturating_add(1);
+    let index = match signal.shape {
+        RampShape::Sawtooth => emitted % steps,
+        RampShape::Triangle if steps == 1 => 0,
+        RampShape::Triangle => {
+            let period = (steps - 1).saturating_mul(2);
+            let k = emitted % period;
+            if k < steps {
+                k
+            } else {
+                period - k
+            }
+        }
+    };
+    signal.from + index * signal.step
+}
+
+fn write_signal(data: &mut [u8; 8], signal: &SignalRamp, value: u64) {
+    for (i, bit) in signal_bits(signal).unwrap_or_default().into_iter().enumerate() {
+        let mask = 1 << (bit % 8);
+        if value >> i & 1 == 1 {
+            data[bit as usize / 8] |= mask;
+        } else {
+            data[bit as usize / 8] &= !mask;
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FaultOutcome {
+    Intact,
+    Dropped,
+    Corrupted,
+}
+
+/// nth 從 1 起算
+fn apply_fault(fault: &Fault, nth: u64, data: &mut [u8; 8]) -> FaultOutcome {
+    if fault.drop_every.is_some_and(|every| nth.is_multiple_of(every as u64)) {
+        return FaultOutcome::Dropped;
+    }
+    match &fault.corrupt {
+        Some(corrupt) if nth.is_multiple_of(corrupt.every.unwrap_or(1) as u64) => {
+            data[corrupt.byte] ^= corrupt.xor;
+            FaultOutcome::Corrupted
+        }
+        _ => FaultOutcome::Intact,
+    }
+}
+
+fn in_state(states: &[String], state: &str) -> bool {
+    states.is_empty() || states.iter().any(|s| s == state)
+}
+
+fn matches_prefix(frame: &VciCanObj, prefix: &[u8]) -> bool {
+    let len = (frame.data_len as usize).min(8);
+    frame.remote_flag == 0 && frame.data[..len].starts_with(prefix)
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct CyclicReport {
+    pub channel: u32,
+    pub id: u32,
+    pub sent: u64,
+    pub dropped: u64,
+    pub corrupted: u64,
+    #[serde(skip)]
+    next_due_us: u64,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ResponseReport {
+    pub channel: u32,
+    pub request_id: u32,
+    pub response_id: u32,
+    /// 符合條件的請求數
+    pub matched: u64,
+    pub sent: u64,
+    pub dropped: u64,
+    pub corrupted: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StateTransition {
+    /// 從情境開始起算
+    pub at_ms: f64,
+    pub from: String,
+    pub to: String,
+    pub request_id: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AssertionResult {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScenarioReport {
+    pub name: Option<String>,
+    pub elapsed_ms: f64,
+    pub state: String,
+    pub transitions: Vec<StateTransition>,
+    pub cyclic: Vec<CyclicReport>,
+    pub responses: Vec<ResponseReport>,
+    pub assertions: Vec<AssertionResult>,
+    /// 所有檢查都通過
+    pub passed: bool,
+}
+
+/// Transmitted 檢查觀察到的訊框
+#[derive(Default)]
+struct Observation {
+    count: u64,
+    last_us: Option<u64>,
+    max_gap_us: u64,
+}
+
+struct PendingResponse {
+    due_us: u64,
+    response: usize,
+    frame: VciCanObj,
+}
+
+/// 執行中的情境；時間以虛擬匯流排開啟後的微秒數表示
+pub(super) struct ScenarioRun {
+    scenario: Scenario,
+    started_us: u64,
+    state: String,
+    transitions: Vec<StateTransition>,
+    cyclic: Vec<CyclicReport>,
+    responses: Vec<ResponseReport>,
+    observations: Vec<Observation>,
+    pending: Vec<PendingResponse>,
+}
+
+impl ScenarioRun {
+    pub(super) fn new(scenario: Scenario, now_us: u64) -> Self {
+        let cyclic = scenario
+            .cyclic
+            .iter()
+            .map(|message| CyclicReport {
+                channel: message.channel,
+                id: message.id,
+                next_due_us: now_us + message.period_ms * 1000,
+                ..Default::default()
+            })
+            .collect();
+        let responses = scenario
+            .responses
+            .iter()
+            .map(|response| ResponseReport {
+                channel: response.channel,
+                request_id: response.request_id,
+                response_id: response.response_id,
+                ..Default::default()
+            })
+            .collect();
+        Self {
+            started_us: now_us,
+            state: scenario.initial_state.clone(),
+            transitions: Vec::new(),
+            cyclic,
+            responses,
+            observations: scenario.assertions.iter().map(|_| Observation::default()).collect(),
+            pending: Vec::new(),
+            scenario,
+        }
+    }
+
+    pub(super) fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    /// 應用程式在 channel 上送出的訊框
+    pub(super) fn observe(&mut self, channel: u32, frame: &VciCanObj, now_us: u64) {
+        for (assertion, observation) in self.scenario.assertions.iter().zip(&mut self.observations) {
+            if let ScenarioAssertion::Transmitted { channel: c, id, data_prefix, .. } = assertion {
+                if *c == channel && *id == frame.id && matches_prefix(frame, data_prefix) {
+                    observation.count += 1;
+                    if let Some(last_us) = observation.last_us {
+                        observation.max_gap_us = observation.max_gap_us.max(now_us - last_us);
+                    }
+                    observation.last_us = Some(now_us);
+                }
+            }
+        }
+        let state = self.state.clone();
+        let Some(index) = self.scenario.responses.iter().position(|response| {
+            response.channel == channel
+                && response.request_id == frame.id
+                && matches_prefix(frame, &response.data_prefix)
+                && in_state(&response.states, &state)
+        }) else {
+            return;
+        };
+        let response = &self.scenario.responses[index];
+        let report = &mut self.responses[index];
+        report.matched += 1;
+        let mut reply = build_frame(response.response_id, response.extended, &response.data, response.dlc);
+        match apply_fault(&response.fault, report.matched, &mut reply.data) {
+            FaultOutcome::Dropped => report.dropped += 1,
+            outcome => {
+                if outcome == FaultOutcome::Corrupted {
+                    report.corrupted += 1;
+                }
+                self.pending.push(PendingResponse {
+                    due_us: now_us + response.delay_ms * 1000,
+                    response: index,
+                    frame: reply,
+                });
+            }
+        }
+        if let Some(next_state) = response.next_state.clone().filter(|next| *next != state) {
+            if self.transitions.len() >= MAX_TRANSITIONS {
+                self.transitions.remove(0);
+            }
+            self.transitions.push(StateTransition {
+                at_ms: now_us.saturating_sub(self.started_us) as f64 / 1000.0,
+                from: std::mem::replace(&mut self.state, next_state.clone()),
+                to: next_state,
+                request_id: frame.id,
+            });
+        }
+    }
+
+    /// 到 now_us 為止應該出現在匯流排上的訊框與其通道，依時間排序
+    pub(super) fn due(&mut self, now_us: u64) -> Vec<(u32, VciCanObj)> {
+        let mut due: Vec<(u64, u32, VciCanObj)> = Vec::new();
+        for (message, report) in self.scenario.cyclic.iter().zip(&mut self.cyclic) {
+            while report.next_due_us <= now_us {
+                let at_us = report.next_due_us;
+                report.next_due_us += message.period_ms * 1000;
+                if !in_state(&message.states, &self.state) {
+                    continue;
+                }
+                let emitted = report.sent + report.dropped;
+                let mut frame = build_frame(message.id, message.extended, &message.data, message.dlc);
+                for signal in &message.signals {
+                    write_signal(&mut frame.data, signal, ramp_value(signal, emitted));
+                }
+                match apply_fault(&message.fault, emitted + 1, &mut frame.data) {
+                    FaultOutcome::Dropped => {
+                        report.dropped += 1;
+                        continue;
+                    }
+                    FaultOutcome::Corrupted => report.corrupted += 1,
+                    FaultOutcome::Intact => {}
+                }
+                report.sent += 1;
+                due.push((at_us, message.channel, frame));
+            }
+        }
+        let responses = &self.scenario.responses;
+        let reports = &mut self.responses;
+        self.pending.retain(|pending| {
+            if pending.due_us > now_us {
+                return true;
+            }
+            reports[pending.response].sent += 1;
+            due.push((pending.due_us, responses[pending.response].channel, pending.frame.clone()));
+            false
+        });
+        due.sort_by_key(|&(at_us, _, _)| at_us);
+        due.into_iter().map(|(_, channel, frame)| (channel, frame)).collect()
+    }
+
+    pub(super) fn report(&self, now_us: u64) -> ScenarioReport {
+        let assertions: Vec<AssertionResult> = self
+            .scenario
+            .assertions
+            .iter()
+            .zip(&self.observations)
+            .map(|(assertion, observation)| match assertion {
+                ScenarioAssertion::Transmitted {
+                    name,
+                    min_count,
+                    max_count,
+                    max_interval_ms,
+                    ..
+                } => {
+                    let mut failures = Vec::new();
+                    if min_count.is_some_and(|min| observation.count < min) {
+                        failures.push(format!("expected at least {}", min_count.unwrap_or_default()));
+                    }
+                    if max_count.is_some_and(|max| observation.count > max) {
+                        failures.push(format!("expected at most {}", max_count.unwrap_or_default()));
+                    }
+                    if let Some(max_interval_ms) = max_interval_ms.filter(|&max| observation.max_gap_us > max * 1000) {
+                        failures.push(format!("gap of {:.1} ms exceeds {} ms", observation.max_gap_us as f64 / 1000.0, max_interval_ms));
+                    }
+                    AssertionResult {
+                        name: name.clone(),
+                        passed: failures.is_empty(),
+                        detail: format!(
+                            "{} frames, max gap {:.1} ms{}",
+                            observation.count,
+                            observation.max_gap_us as f64 / 1000.0,
+                            failures.iter().map(|f| format!("; {}", f)).collect::<String>()
+                        ),
+                    }
+                }
+                ScenarioAssertion::State { name, state } => AssertionResult {
+                    name: name.clone(),
+                    passed: self.state == *state,
+                    detail: format!("state is {}, expected {}", self.state, state),
+                },
+            })
+            .collect();
+        ScenarioReport {
+            name: self.scenario.name.clone(),
+            elapsed_ms: now_us.saturating_sub(self.started_us) as f64 / 1000.0,
+            state: self.state.clone(),
+            transitions: self.transitions.clone(),
+            cyclic: self.cyclic.clone(),
+            responses: self.responses.clone(),
+            passed: assertions.iter().all(|a| a.passed),
+            assertions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario() -> Scenario {
+        Scenario {
+            name: Some("unit".into()),
+            initial_state: default_state(),
+            cyclic: Vec::new(),
+            responses: Vec::new(),
+            assertions: Vec::new(),
+        }
+    }
+
+    fn cyclic(id: u32, period_ms: u64) -> CyclicMessage {
+        CyclicMessage {
+            channel: 0,
+            id,
+            extended: None,
+            period_ms,
+            data: vec![0xAA, 0, 0],
+            dlc: None,
+            signals: Vec::new(),
+            states: Vec::new(),
+            fault: Fault::default(),
+        }
+    }
+
+    fn response(request_id: u32, response_id: u32) -> Response {
+        Response {
+            channel: 1,
+            request_id,
+            data_prefix: vec![0x10],
+            response_id,
+            extended: None,
+            data: vec![0x50, 0x01],
+            dlc: None,
+            delay_ms: 5,
+            states: Vec::new(),
+            next_state: None,
+            fault: Fault::default(),
+        }
+    }
+
+    fn ramp(start_bit: u32, length: u32, byte_order: ByteOrder) -> SignalRamp {
+        SignalRamp {
+            start_bit,
+            length,
+            byte_order,
+            from: 0,
+            to: 3,
+            step: 1,
+            shape: RampShape::Sawtooth,
+        }
+    }
+
+    fn request(id: u32, data: &[u8]) -> VciCanObj {
+        build_frame(id, None, data, None)
+    }
+
+    fn rejection(edit: impl FnOnce(&mut Scenario)) -> String {
+        let mut scenario = scenario();
+        edit(&mut scenario);
+        scenario.validate().unwrap_err()
+    }
+
+    #[test]
+    fn malformed_frames_and_faults_are_rejected_with_their_location() {
+        assert_eq!(
+            rejection(|s| s.cyclic.push(CyclicMessage {
+                channel: 2,
+                ..cyclic(0x100, 10)
+            })),
+            "cyclic[0]: channel 2 out of range (virtual device has 2 channels)"
+        );
+        assert_eq!(
+            rejection(|s| s.cyclic.push(CyclicMessage {
+                dlc: Some(9),
+                ..cyclic(0x100, 10)
+            })),
+            "cyclic[0]: data exceeds 8 bytes"
+        );
+        assert_eq!(
+            rejection(|s| s.cyclic.push(cyclic(0x100, 0))),
+            "cyclic[0]: period_ms must be greater than 0"
+        );
+        let every_zero = Fault {
+            drop_every: Some(0),
+            corrupt: None,
+        };
+        assert_eq!(
+            rejection(|s| s.responses.push(Response {
+                fault: every_zero,
+                ..response(0x7E0, 0x7E8)
+            })),
+            "responses[0]: fault intervals must be at least 1"
+        );
+        let past_the_end = Fault {
+            drop_every: None,
+            corrupt: Some(Corruption {
+                byte: 8,
+                xor: 0xFF,
+                every: None,
+            }),
+        };
+        assert_eq!(
+            rejection(|s| s.cyclic.extend([
+                cyclic(0x100, 10),
+                CyclicMessage {
+                    fault: past_the_end,
+                    ..cyclic(0x101, 10)
+                }
+            ])),
+            "cyclic[1]: corrupt.byte must be below 8"
+        );
+        assert_eq!(
+            rejection(|s| s.responses.push(Response {
+                data: vec![0; 9],
+                ..response(0x7E0, 0x7E8)
+            })),
+            "responses[0]: data exceeds 8 bytes"
+        );
+        assert_eq!(
+            rejection(|s| s.responses.push(Response {
+                data_prefix: vec![0; 9],
+                ..response(0x7E0, 0x7E8)
+            })),
+            "responses[0]: data_prefix exceeds 8 bytes"
+        );
+    }
+
+    #[test]
+    fn signals_must_fit_the_frame_and_their_range() {
+        let with_signal = |signal: SignalRamp| {
+            rejection(|s| {
+                s.cyclic.push(CyclicMessage {
+                    signals: vec![signal],
+                    ..cyclic(0x100, 10)
+                })
+            })
+        };
+        assert_eq!(
+            with_signal(ramp(60, 8, ByteOrder::LittleEndian)),
+            "cyclic[0].signals[0]: bits do not fit in 8 bytes"
+        );
+        assert_eq!(
+            with_signal(ramp(0, 0, ByteOrder::LittleEndian)),
+            "cyclic[0].signals[0]: bits do not fit in 8 bytes"
+        );
+        // Motorola 從 byte 7 的最高位元往下會超出訊框
+        assert_eq!(
+            with_signal(ramp(63, 16, ByteOrder::BigEndian)),
+            "cyclic[0].signals[0]: bits do not fit in 8 bytes"
+        );
+        let too_wide = SignalRamp {
+            to: 4,
+            ..ramp(0, 2, ByteOrder::LittleEndian)
+        };
+        assert_eq!(with_signal(too_wide), "cyclic[0].signals[0]: needs from <= to <= 3 and step > 0");
+        let no_step = SignalRamp {
+            step: 0,
+            ..ramp(0, 8, ByteOrder::LittleEndian)
+        };
+        assert!(with_signal(no_step).ends_with("step > 0"));
+        let reversed = SignalRamp {
+            from: 3,
+            to: 1,
+            ..ramp(0, 8, ByteOrder::LittleEndian)
+        };
+        assert!(with_signal(reversed).starts_with("cyclic[0].signals[0]: needs from <= to"));
+    }
+
+    #[test]
+    fn assertions_need_a_valid_channel_and_count_range() {
+        let transmitted = |channel, min_count, max_count| ScenarioAssertion::Transmitted {
+            name: "tester present".into(),
+            channel,
+            id: 0x7DF,
+            data_prefix: Vec::new(),
+            min_count,
+            max_count,
+            max_interval_ms: None,
+        };
+        assert_eq!(
+            rejection(|s| s.assertions.push(transmitted(0, Some(3), Some(2)))),
+            "assertions[0]: min_count exceeds max_count"
+        );
+        assert!(rejection(|s| s.assertions.push(transmitted(5, None, None))).starts_with("assertions[0]: channel 5 out of range"));
+        let mut valid = scenario();
+        valid.assertions.push(transmitted(1, Some(2), Some(2)));
+        valid.assertions.push(ScenarioAssertion::State {
+            name: "done".into(),
+            state: "x".into(),
+        });
+        assert!(valid.validate().is_ok());
+    }
+
+    #[test]
+    fn signal_bits_follow_the_dbc_layout() {
+        let mut data = [0; 8];
+        write_signal(&mut data, &ramp(7, 16, ByteOrder::BigEndian), 0x1234);
+        write_signal(&mut data, &ramp(20, 12, ByteOrder::LittleEndian), 0xABC);
+        assert_eq!(data, [0x12, 0x34, 0xC0, 0xAB, 0, 0, 0, 0]);
+        // 寫入會清除原本的位元
+        write_signal(&mut data, &ramp(7, 16, ByteOrder::BigEndian), 0);
+        assert_eq!(data[..2], [0, 0]);
+    }
+
+    #[test]
+    fn ramps_wrap_or_bounce_between_from_and_to() {
+        let sawtooth = ramp(0, 8, ByteOrder::LittleEndian);
+        let values: Vec<u64> = (0..6).map(|emitted| ramp_value(&sawtooth, emitted)).collect();
+        assert_eq!(values, [0, 1, 2, 3, 0, 1]);
+        let triangle = SignalRamp {
+            shape: RampShape::Triangle,
+            ..sawtooth.clone()
+        };
+        let values: Vec<u64> = (0..8).map(|emitted| ramp_value(&triangle, emitted)).collect();
+        assert_eq!(values, [0, 1, 2, 3, 2, 1, 0, 1]);
+        let constant = SignalRamp {
+            to: 0,
+            shape: RampShape::Triangle,
+            ..sawtooth
+        };
+        assert_eq!(ramp_value(&constant, 5), 0);
+    }
+
+    #[test]
+    fn cyclic_messages_count_dropped_and_corrupted_frames() {
+        let mut scenario = scenario();
+        scenario.cyclic.push(CyclicMessage {
+            fault: Fault {
+                drop_every: Some(3),
+                corrupt: Some(Corruption {
+                    byte: 0,
+                    xor: 0x0F,
+                    every: Some(2),
+                }),
+            },
+            ..cyclic(0x100, 10)
+        });
+        let mut run = ScenarioRun::new(scenario, 1_000);
+        assert!(run.due(10_999).is_empty());
+        let frames = run.due(61_000);
+        // 第 3、6 次丟棄，第 2、4 次破壞
+        let first_bytes: Vec<u8> = frames.iter().map(|(_, frame)| frame.data[0]).collect();
+        assert_eq!(first_bytes, [0xAA, 0xA5, 0xA5, 0xAA]);
+        let report = &run.report(61_000).cyclic[0];
+        assert_eq!((report.sent, report.dropped, report.corrupted), (4, 2, 2));
+    }
+
+    #[test]
+    fn a_response_is_delayed_switches_state_and_stops_matching_in_the_new_state() {
+        let mut scenario = scenario();
+        scenario.responses.push(Response {
+            states: vec!["initial".into()],
+            next_state: Some("unlocked".into()),
+            ..response(0x7E0, 0x7E8)
+        });
+        scenario.assertions.push(ScenarioAssertion::State {
+            name: "unlocked".into(),
+            state: "unlocked".into(),
+        });
+        let mut run = ScenarioRun::new(scenario, 0);
+
+        // 通道、ID 或資料開頭不符的請求不回應
+        run.observe(0, &request(0x7E0, &[0x10]), 1_000);
+        run.observe(1, &request(0x7E0, &[0x11]), 1_000);
+        run.observe(1, &request(0x7E0, &[0x10, 0x03]), 2_000);
+        assert!(run.due(6_999).is_empty());
+        let due = run.due(7_000);
+        assert_eq!(due.len(), 1);
+        assert_eq!(
+            (due[0].0, due[0].1.id, due[0].1.data_len, due[0].1.data[..2].to_vec()),
+            (1, 0x7E8, 2, vec![0x50, 0x01])
+        );
+        run.observe(1, &request(0x7E0, &[0x10]), 8_000);
+
+        let report = run.report(10_000);
+        assert_eq!(report.state, "unlocked");
+        assert_eq!(report.transitions.len(), 1);
+        assert_eq!((report.transitions[0].at_ms, report.transitions[0].from.as_str()), (2.0, "initial"));
+        assert_eq!((report.responses[0].matched, report.responses[0].sent), (1, 1));
+        assert!(report.passed);
+    }
+
+    #[test]
+    fn transmitted_assertions_report_counts_and_gaps() {
+        let mut scenario = scenario();
+        scenario.assertions.push(ScenarioAssertion::Transmitted {
+            name: "heartbeat".into(),
+            channel: 0,
+            id: 0x700,
+            data_prefix: Vec::new(),
+            min_count: Some(3),
+            max_count: None,
+            max_interval_ms: Some(100),
+        });
+        let mut run = ScenarioRun::new(scenario, 0);
+        run.observe(0, &request(0x700, &[0x05]), 0);
+        run.observe(0, &request(0x700, &[0x05]), 150_000);
+        run.observe(1, &request(0x700, &[0x05]), 160_000);
+
+        let report = run.report(200_000);
+        assert!(!report.passed);
+        assert_eq!(
+            report.assertions[0].detail,
+            "2 frames, max gap 150.0 ms; expected at least 3; gap of 150.0 ms exceeds 100 ms"
+        );
+    }
+}
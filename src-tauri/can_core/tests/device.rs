@@ -0,0 +1,185 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use can_core::mock::MockCan;
+use can_core::virtual_can::scenario::{ByteOrder, Corruption, CyclicMessage, Fault, RampShape, Response, ScenarioAssertion, SignalRamp};
+use can_core::virtual_can::{Scenario, VirtualCan};
+use can_core::{CanDevice, CanInterface, DeviceType, VciCanObj, VciInitConfig};
+
+fn dev_type() -> u32 {
+    DeviceType::Usbcan2.code()
+}
+
+fn frame(id: u32, data: &[u8]) -> VciCanObj {
+    let mut can_obj = VciCanObj {
+        id,
+        data_len: data.len() as u8,
+        ..Default::default()
+    };
+    can_obj.data[..data.len()].copy_from_slice(data);
+    can_obj
+}
+
+#[test]
+fn can_device_stops_registered_threads_before_closing() {
+    let mock = Arc::new(MockCan::new());
+    let mut device = CanDevice::open(mock.clone(), dev_type(), 0).unwrap();
+    let running = Arc::new(AtomicBool::new(true));
+    let saw_closed = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let (mock, running, saw_closed) = (mock.clone(), running.clone(), saw_closed.clone());
+        std::thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                if !mock.is_open(dev_type(), 0) {
+                    saw_closed.store(true, Ordering::SeqCst);
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        })
+    };
+    device.register_thread(0, running, handle);
+    std::thread::sleep(Duration::from_millis(20));
+    drop(device);
+
+    assert!(!saw_closed.load(Ordering::SeqCst));
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+}
+
+#[test]
+fn can_device_closes_when_a_command_panics() {
+    let mock = Arc::new(MockCan::new());
+    let can_lib: Arc<dyn CanInterface> = mock.clone();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _device = CanDevice::open(can_lib, dev_type(), 0).unwrap();
+        panic!("command failed while holding the device");
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+    // 關閉後可以再次開啟
+    assert!(CanDevice::open(mock.clone(), dev_type(), 0).is_ok());
+}
+
+#[test]
+fn a_failed_reopen_is_not_closed_again() {
+    let mock = Arc::new(MockCan::new());
+    let mut device = CanDevice::open(mock.clone(), dev_type(), 0).unwrap();
+    mock.set_open_failure(true);
+    assert!(device.reopen(0).is_err());
+    assert!(!device.is_open());
+    drop(device);
+
+    assert_eq!(mock.open_count(dev_type(), 0), 1);
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+}
+
+#[test]
+fn virtual_scenario_answers_requests_by_state_and_injects_faults() {
+    let bus = VirtualCan::new();
+    bus.open(dev_type(), 0).unwrap();
+    for channel in [0, 1] {
+        bus.init_channel(dev_type(), 0, channel, &VciInitConfig::default()).unwrap();
+        bus.start(dev_type(), 0, channel).unwrap();
+    }
+    let response = |prefix: &[u8], data: &[u8]| Response {
+        channel: 0,
+        request_id: 0x7E0,
+        data_prefix: prefix.to_vec(),
+        response_id: 0x7E8,
+        extended: None,
+        data: data.to_vec(),
+        dlc: None,
+        delay_ms: 0,
+        states: Vec::new(),
+        next_state: None,
+        fault: Fault::default(),
+    };
+    let scenario = Scenario {
+        name: Some("ecu".into()),
+        initial_state: "idle".into(),
+        cyclic: vec![CyclicMessage {
+            channel: 0,
+            id: 0x100,
+            extended: None,
+            period_ms: 5,
+            data: vec![0; 8],
+            dlc: None,
+            signals: vec![SignalRamp {
+                start_bit: 0,
+                length: 16,
+                byte_order: ByteOrder::LittleEndian,
+                from: 0,
+                to: 20,
+                step: 10,
+                shape: RampShape::Sawtooth,
+            }],
+            states: vec!["running".into()],
+            fault: Fault::default(),
+        }],
+        responses: vec![
+            Response {
+                delay_ms: 20,
+                next_state: Some("running".into()),
+                ..response(&[0x02, 0x10], &[0x06, 0x50, 0x03])
+            },
+            Response {
+                states: vec!["running".into()],
+                fault: Fault {
+                    drop_every: Some(2),
+                    corrupt: Some(Corruption { byte: 2, xor: 0xFF, every: None }),
+                },
+                ..response(&[0x02, 0x3E], &[0x02, 0x7E, 0x00])
+            },
+        ],
+        assertions: vec![
+            ScenarioAssertion::Transmitted {
+                name: "requests".into(),
+                channel: 0,
+                id: 0x7E0,
+                data_prefix: Vec::new(),
+                min_count: Some(4),
+                max_count: None,
+                max_interval_ms: None,
+            },
+            ScenarioAssertion::State { name: "started".into(), state: "running".into() },
+        ],
+    };
+    assert!(bus.set_scenario(Some(Scenario { cyclic: vec![CyclicMessage { period_ms: 0, ..scenario.cyclic[0].clone() }], ..scenario.clone() })).is_err());
+    bus.set_scenario(Some(scenario)).unwrap();
+    let receive = || bus.receive(dev_type(), 0, 0, 1000, 0).unwrap();
+    let responses = |frames: &[VciCanObj]| -> Vec<Vec<u8>> {
+        frames.iter().filter(|f| f.id == 0x7E8).map(|f| f.data[..f.data_len as usize].to_vec()).collect()
+    };
+
+    // 在 idle 狀態：沒有週期訊息，TesterPresent 不回應
+    bus.transmit(dev_type(), 0, 0, &[frame(0x7E0, &[0x02, 0x3E, 0x00])]).unwrap();
+    std::thread::sleep(Duration::from_millis(15));
+    assert!(receive().is_empty());
+    assert!(!bus.scenario_report().unwrap().passed);
+
+    bus.transmit(dev_type(), 0, 0, &[frame(0x7E0, &[0x02, 0x10, 0x03])]).unwrap();
+    assert!(responses(&receive()).is_empty());
+    std::thread::sleep(Duration::from_millis(40));
+    let frames = receive();
+    assert_eq!(responses(&frames), [vec![0x06, 0x50, 0x03]]);
+    // 收到請求時即切換狀態，週期訊息從 ramp 的起點開始
+    let ramp: Vec<u16> = frames.iter().filter(|f| f.id == 0x100).map(|f| u16::from_le_bytes([f.data[0], f.data[1]])).collect();
+    assert!(ramp.len() >= 3, "{:?}", ramp);
+    assert_eq!(ramp[..3], [0, 10, 20]);
+
+    // 第一次回應被破壞，第二次被丟棄
+    let tester_present = frame(0x7E0, &[0x02, 0x3E, 0x00]);
+    bus.transmit(dev_type(), 0, 0, &[tester_present.clone(), tester_present]).unwrap();
+    assert_eq!(responses(&receive()), [vec![0x02, 0x7E, 0xFF]]);
+
+    let report = bus.scenario_report().unwrap();
+    assert!(report.passed, "{:?}", report.assertions);
+    assert_eq!(report.state, "running");
+    assert_eq!(report.transitions.len(), 1);
+    let tester = &report.responses[1];
+    assert_eq!((tester.matched, tester.sent, tester.dropped, tester.corrupted), (2, 1, 1, 1));
+    assert_eq!(report.responses[0].sent, 1);
+    bus.set_scenario(None).unwrap();
+    assert!(bus.scenario_report().is_none());
+}
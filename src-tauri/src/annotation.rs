@@ -0,0 +1,279 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::host_timestamp_us;
+use crate::logging::LoggedFrame;
+use crate::receive::EventSink;
+use crate::{invalid_argument, AppState, StateMutex};
+
+/// 最多保留的註記數；超過時移除時間最早的一筆
+pub const MAX_ANNOTATIONS: usize = 10_000;
+
+/// 使用者在時間軸上加的文字註記
+#[derive(Serialize, Clone, Debug)]
+pub struct Annotation {
+    pub id: u32,
+    /// 主機時間 (UNIX epoch 起算的微秒)，與訊框的 host_timestamp_us 同一時基
+    pub timestamp_us: u64,
+    pub text: String,
+}
+
+impl Annotation {
+    /// 寫進記錄檔註解列的文字；換行等控制字元換成空白
+    pub fn comment_text(&self) -> String {
+        self.text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+    }
+}
+
+/// get_recent_frames 一併回傳的註記
+#[derive(Serialize, Clone, Debug)]
+pub struct AnnotationMarker {
+    #[serde(flatten)]
+    pub annotation: Annotation,
+    /// 時間上在註記之前的最後一個回傳訊框；註記早於所有回傳的訊框時為 None
+    pub after_seq: Option<u64>,
+}
+
+#[derive(Serialize, Clone)]
+struct AnnotationDeleted {
+    id: u32,
+}
+
+/// 跟環形緩衝存在一起的註記，依時間排序；清空緩衝時一併清除
+#[derive(Default)]
+pub struct Annotations {
+    next_id: u32,
+    entries: Vec<Annotation>,
+}
+
+impl Annotations {
+    pub fn add(&mut self, text: String, timestamp_us: u64) -> Annotation {
+        self.next_id += 1;
+        let annotation = Annotation {
+            id: self.next_id,
+            timestamp_us,
+            text,
+        };
+        // 同一時間的註記依加入的順序排列
+        let index = self.entries.partition_point(|a| a.timestamp_us <= timestamp_us);
+        self.entries.insert(index, annotation.clone());
+        if self.entries.len() > MAX_ANNOTATIONS {
+            self.entries.remove(0);
+        }
+        annotation
+    }
+
+    pub fn delete(&mut self, id: u32) -> Option<Annotation> {
+        let index = self.entries.iter().position(|a| a.id == id)?;
+        Some(self.entries.remove(index))
+    }
+
+    pub fn list(&self) -> Vec<Annotation> {
+        self.entries.clone()
+    }
+
+    /// 時間在 from_us 到 to_us 之間 (包含兩端) 的註記
+    pub fn between(&self, from_us: u64, to_us: u64) -> Vec<Annotation> {
+        let start = self.entries.partition_point(|a| a.timestamp_us < from_us);
+        self.entries[start..].iter().take_while(|a| a.timestamp_us <= to_us).cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl AppState {
+    /// 記下註記並送出 annotation-added，讓其他視窗同步。timestamp 為 UNIX epoch 起算的秒數
+    /// (與 CSV 記錄的時間欄相同)，省略時為現在。記錄中時同時寫進記錄檔，位置是寫入當下而非 timestamp
+    pub fn add_annotation(&self, events: &impl EventSink, text: String, timestamp: Option<f64>) -> Result<Annotation, String> {
+        if text.trim().is_empty() {
+            return Err(invalid_argument("text", "must not be empty"));
+        }
+        let timestamp_us = match timestamp {
+            Some(seconds) if !seconds.is_finite() || seconds < 0.0 => {
+                return Err(invalid_argument("timestamp", "must be a non-negative number of seconds"));
+            }
+            Some(seconds) => (seconds * 1_000_000.0).round() as u64,
+            None => host_timestamp_us(),
+        };
+        let annotation = self
+            .frame_buffer
+            .lock()
+            .map_err(|_| "Failed to lock frame buffer")?
+            .annotations_mut()
+            .add(text, timestamp_us);
+        if let Some(sink) = self.log_sink.lock().map_err(|_| "Failed to lock log sink")?.as_ref() {
+            sink.annotate(&annotation);
+        }
+        events.emit_event("annotation-added", annotation.clone());
+        Ok(annotation)
+    }
+
+    /// 依時間排序的所有註記
+    pub fn list_annotations(&self) -> Result<Vec<Annotation>, String> {
+        Ok(self.frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?.annotations().list())
+    }
+
+    /// 刪除註記並送出 annotation-deleted；已寫進執行中記錄檔的註解列不受影響
+    pub fn delete_annotation(&self, events: &impl EventSink, id: u32) -> Result<Annotation, String> {
+        let deleted = self
+            .frame_buffer
+            .lock()
+            .map_err(|_| "Failed to lock frame buffer")?
+            .annotations_mut()
+            .delete(id)
+            .ok_or_else(|| format!("annotation {} not found", id))?;
+        events.emit_event("annotation-deleted", AnnotationDeleted { id });
+        Ok(deleted)
+    }
+
+    /// 時間落在 frames 第一個到最後一個訊框之間的註記，供匯出時穿插在訊框之間
+    pub(crate) fn annotations_within(&self, frames: &[LoggedFrame]) -> Vec<Annotation> {
+        let (Some(first), Some(last)) = (frames.first(), frames.last()) else {
+            return Vec::new();
+        };
+        self.frame_buffer
+            .lock()
+            .map(|ring| ring.annotations().between(first.frame.host_timestamp_us, last.frame.host_timestamp_us))
+            .unwrap_or_default()
+    }
+}
+
+/// 在 timestamp (UNIX epoch 起算的秒數，省略時為現在) 加上文字註記
+#[tauri::command]
+pub fn add_annotation(
+    text: String,
+    timestamp: Option<f64>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<Annotation, String> {
+    state.lock().map_err(|_| "Failed to lock state")?.add_annotation(&app_handle, text, timestamp)
+}
+
+#[tauri::command]
+pub fn list_annotations(state: State<Arc<StateMutex>>) -> Result<Vec<Annotation>, String> {
+    state.lock().map_err(|_| "Failed to lock state")?.list_annotations()
+}
+
+#[tauri::command]
+pub fn delete_annotation(id: u32, app_handle: tauri::AppHandle, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    state.lock().map_err(|_| "Failed to lock state")?.delete_annotation(&app_handle, id)?;
+    Ok(format!("Annotation {} deleted", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::frame::{CanFrameEvent, Direction};
+    use crate::VciCanObj;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, serde_json::Value)>>>);
+
+    impl EventSink for Recorder {
+        fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) -> bool {
+            self.0.lock().unwrap().push((event.to_string(), serde_json::to_value(payload).unwrap()));
+            true
+        }
+    }
+
+    fn texts(annotations: &[Annotation]) -> Vec<&str> {
+        annotations.iter().map(|a| a.text.as_str()).collect()
+    }
+
+    #[test]
+    fn annotations_stay_in_time_order_with_ties_in_insertion_order() {
+        let mut annotations = Annotations::default();
+        annotations.add("b".into(), 200);
+        annotations.add("a".into(), 100);
+        annotations.add("b2".into(), 200);
+        annotations.add("c".into(), 300);
+        assert_eq!(texts(&annotations.list()), ["a", "b", "b2", "c"]);
+        assert_eq!(texts(&annotations.between(200, 300)), ["b", "b2", "c"]);
+        assert!(annotations.between(201, 299).is_empty());
+
+        assert_eq!(annotations.delete(1).map(|a| a.text), Some("b".into()));
+        assert!(annotations.delete(1).is_none());
+        // 刪除後不重複使用 ID
+        assert_eq!(annotations.add("d".into(), 0).id, 5);
+    }
+
+    #[test]
+    fn the_earliest_annotation_is_dropped_past_the_cap() {
+        let mut annotations = Annotations::default();
+        for timestamp_us in 1..=MAX_ANNOTATIONS as u64 {
+            annotations.add("note".into(), timestamp_us);
+        }
+        // 比所有註記都早的註記加入後立即被移除
+        annotations.add("earliest".into(), 0);
+        assert_eq!(annotations.list().len(), MAX_ANNOTATIONS);
+        assert_eq!(annotations.list()[0].id, 1);
+        annotations.add("newest".into(), MAX_ANNOTATIONS as u64 + 1);
+        let list = annotations.list();
+        assert_eq!((list.len(), list[0].id, list[0].timestamp_us), (MAX_ANNOTATIONS, 2, 2));
+        assert_eq!(list.last().unwrap().text, "newest");
+    }
+
+    #[test]
+    fn empty_text_or_a_bad_timestamp_is_rejected_without_an_event() {
+        let app_state = AppState::default();
+        let events = Recorder::default();
+        for (text, timestamp, field) in [
+            ("", None, "text"),
+            (" \n", Some(1.0), "text"),
+            ("note", Some(-1.0), "timestamp"),
+            ("note", Some(f64::NAN), "timestamp"),
+            ("note", Some(f64::INFINITY), "timestamp"),
+        ] {
+            let error = app_state.add_annotation(&events, text.into(), timestamp).unwrap_err();
+            assert!(error.contains(field), "{}", error);
+        }
+        assert_eq!(app_state.delete_annotation(&events, 7).unwrap_err(), "annotation 7 not found");
+        assert!(events.0.lock().unwrap().is_empty());
+        assert!(app_state.list_annotations().unwrap().is_empty());
+    }
+
+    #[test]
+    fn adding_and_deleting_announce_the_change() {
+        let app_state = AppState::default();
+        let events = Recorder::default();
+        let added = app_state.add_annotation(&events, "ignition on".into(), Some(1.5)).unwrap();
+        assert_eq!(added.timestamp_us, 1_500_000);
+        app_state.delete_annotation(&events, added.id).unwrap();
+        let events = events.0.lock().unwrap();
+        assert_eq!(events[0].0, "annotation-added");
+        assert_eq!(events[0].1, serde_json::json!({ "id": 1, "timestamp_us": 1_500_000, "text": "ignition on" }));
+        assert_eq!(events[1], ("annotation-deleted".to_string(), serde_json::json!({ "id": 1 })));
+    }
+
+    #[test]
+    fn exports_get_the_annotations_between_their_first_and_last_frame() {
+        let app_state = AppState::default();
+        let events = Recorder::default();
+        for (text, seconds) in [("before", 0.9), ("start", 1.0), ("inside", 1.5), ("end", 2.0), ("after", 2.1)] {
+            app_state.add_annotation(&events, text.into(), Some(seconds)).unwrap();
+        }
+        let logged = |host_timestamp_us| LoggedFrame {
+            direction: Direction::Rx,
+            frame: CanFrameEvent::from_raw((4, 0), 0, &VciCanObj::default(), host_timestamp_us),
+        };
+        let within = app_state.annotations_within(&[logged(1_000_000), logged(2_000_000)]);
+        assert_eq!(texts(&within), ["start", "inside", "end"]);
+        assert!(app_state.annotations_within(&[]).is_empty());
+    }
+
+    #[test]
+    fn comment_text_replaces_control_characters() {
+        let annotation = Annotation {
+            id: 1,
+            timestamp_us: 0,
+            text: "line 1\nline 2\tend".into(),
+        };
+        assert_eq!(annotation.comment_text(), "line 1 line 2 end");
+    }
+}
@@ -0,0 +1,388 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::receive::{spawn_receive_loop, EventSink, ReceiveOptions};
+use crate::settings::{self, locate_saved_device, SavedChannel, SavedSettings};
+use crate::{invalid_argument, supervisor, Backend, DeviceType, StateMutex, DEFAULT_DEV_TYPE};
+
+const AUTO_CONNECT_FILE: &str = "auto_connect.json";
+const DEFAULT_RETRY_INTERVAL_MS: u64 = 2000;
+const MIN_RETRY_INTERVAL_MS: u64 = 100;
+
+/// 程式啟動時自動連線的設定；存在 app_data_dir/auto_connect.json
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AutoConnectSettings {
+    /// 省略時沿用目前的後端 (預設為 ControlCAN)
+    #[serde(default)]
+    pub backend: Option<Backend>,
+    #[serde(default)]
+    pub dev_type: Option<DeviceType>,
+    /// 有序號時以序號找回裝置，否則使用 dev_index
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub dev_index: u32,
+    pub channels: Vec<SavedChannel>,
+    /// 開啟時先禁止傳送的通道
+    #[serde(default)]
+    pub tx_inhibited: Vec<u32>,
+    /// 為 true 時已啟動的通道以預設選項開始接收
+    #[serde(default)]
+    pub start_receiving: bool,
+    #[serde(default)]
+    pub retry_interval_ms: Option<u64>,
+    /// 省略時一直重試，直到成功或 cancel_auto_connect
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+}
+
+impl AutoConnectSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == Some(0) {
+            return Err(invalid_argument("settings.max_attempts", "must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// 最近一次自動連線的結果；前端載入完成前送出的事件可能漏接，可改以 get_auto_connect 查詢
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+pub enum AutoConnectStatus {
+    #[default]
+    Idle,
+    Running {
+        attempts: u32,
+    },
+    Connected(AutoConnectFinished),
+    Failed(AutoConnectFailed),
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AutoConnectFinished {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub serial_number: Option<String>,
+    pub receiving: Vec<u32>,
+    pub attempts: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AutoConnectFailed {
+    pub error: String,
+    pub attempts: u32,
+    pub cancelled: bool,
+}
+
+/// 由 auto-connect 執行緒持有；cancel_auto_connect 清除 running
+#[derive(Default)]
+pub(crate) struct AutoConnect {
+    running: Option<Arc<AtomicBool>>,
+    status: AutoConnectStatus,
+}
+
+fn load(app_handle: &tauri::AppHandle) -> Result<Option<AutoConnectSettings>, String> {
+    let path = settings::data_file(app_handle, AUTO_CONNECT_FILE)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| format!("Auto-connect settings file {} is corrupt: {}", path.display(), e))
+}
+
+fn store(app_handle: &tauri::AppHandle, settings: Option<&AutoConnectSettings>) -> Result<(), String> {
+    let path = settings::data_file(app_handle, AUTO_CONNECT_FILE)?;
+    let Some(settings) = settings else {
+        return match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove {}: {}", path.display(), e)),
+            _ => Ok(()),
+        };
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// 嘗試連線一次；Err 的 bool 表示是否值得重試 (DLL 載入失敗等不會自行恢復的錯誤為 false)
+fn connect_once<E: EventSink + Clone>(
+    state: &Arc<StateMutex>,
+    events: &E,
+    settings: &AutoConnectSettings,
+    attempts: u32,
+) -> Result<AutoConnectFinished, (String, bool)> {
+    let (key, started) = {
+        let mut app_state = state.lock().map_err(|_| ("Failed to lock state".to_string(), true))?;
+        let backend = match settings.backend {
+            Some(backend) => {
+                app_state.select_backend(backend).map_err(|e| (e, false))?;
+                backend
+            }
+            None => app_state.backend().map_or(Backend::ControlCan, |can_lib| can_lib.backend()),
+        };
+        // DLL 不存在時在此回報錯誤，不必重試
+        app_state.try_library().map_err(|e| (e, false))?;
+        let saved = SavedSettings {
+            backend,
+            dev_type: settings.dev_type.unwrap_or(DEFAULT_DEV_TYPE),
+            dev_index: settings.dev_index,
+            serial_number: settings.serial_number.clone(),
+            channels: settings.channels.clone(),
+            tx_inhibited: settings.tx_inhibited.clone(),
+        };
+        let found = app_state.enumerate_devices();
+        let dev_index = locate_saved_device(&saved, found).map_err(|e| (e, true))?;
+        let key = (saved.dev_type.code(), dev_index);
+        if app_state.devices.contains_key(&key) {
+            return Err((format!("device {} is already open", dev_index), false));
+        }
+        app_state.restore_device(&saved, dev_index).map_err(|e| (e, true))?;
+        let started: Vec<u32> = saved.channels.iter().filter(|c| c.started).map(|c| c.channel).collect();
+        (key, started)
+    };
+    let mut receiving = Vec::new();
+    for channel in started.into_iter().filter(|_| settings.start_receiving) {
+        let (_, handle) = spawn_receive_loop(state, events.clone(), Some(key.0), Some(key.1), channel, ReceiveOptions::default())
+            .map_err(|e| (format!("device opened but starting CAN{} receive failed: {}", channel + 1, e), false))?;
+        if let Some(device) = state.lock().map_err(|_| ("Failed to lock state".to_string(), false))?.devices.get_mut(&key) {
+            device.register_receive_thread(channel, handle);
+        }
+        receiving.push(channel);
+    }
+    Ok(AutoConnectFinished {
+        dev_type: key.0,
+        dev_index: key.1,
+        serial_number: settings.serial_number.clone(),
+        receiving,
+        attempts,
+    })
+}
+
+fn set_status(state: &StateMutex, status: AutoConnectStatus) {
+    if let Ok(mut app_state) = state.lock() {
+        app_state.auto_connect.status = status;
+    }
+}
+
+/// 依設定連線，失敗時每 retry_interval_ms 重試，直到成功、用完 max_attempts 或 running 被清除。
+/// 開始時送出 auto-connect-started，結束時送出 auto-connect-finished 或 auto-connect-failed
+pub fn run_auto_connect<E: EventSink + Clone>(
+    state: &Arc<StateMutex>,
+    events: E,
+    settings: &AutoConnectSettings,
+    running: &AtomicBool,
+) -> AutoConnectStatus {
+    let interval = Duration::from_millis(settings.retry_interval_ms.unwrap_or(DEFAULT_RETRY_INTERVAL_MS).max(MIN_RETRY_INTERVAL_MS));
+    events.emit_event("auto-connect-started", settings.clone());
+    let mut attempts = 0;
+    let status = loop {
+        attempts += 1;
+        set_status(state, AutoConnectStatus::Running { attempts });
+        let error = match connect_once(state, &events, settings, attempts) {
+            Ok(finished) => {
+                events.emit_event("auto-connect-finished", finished.clone());
+                break AutoConnectStatus::Connected(finished);
+            }
+            Err((error, retry)) if retry && settings.max_attempts.is_none_or(|max| attempts < max) => error,
+            Err((error, _)) => break AutoConnectStatus::Failed(AutoConnectFailed { error, attempts, cancelled: false }),
+        };
+        println!("Auto-connect attempt {} failed: {}", attempts, error);
+        let deadline = std::time::Instant::now() + interval;
+        while running.load(Ordering::SeqCst) && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        if !running.load(Ordering::SeqCst) {
+            break AutoConnectStatus::Failed(AutoConnectFailed { error, attempts, cancelled: true });
+        }
+    };
+    if let AutoConnectStatus::Failed(failed) = &status {
+        events.emit_event("auto-connect-failed", failed.clone());
+    }
+    set_status(state, status.clone());
+    status
+}
+
+/// 在 setup 中呼叫：有儲存的設定時在背景執行緒自動連線。任何失敗 (包含 panic) 都只送出
+/// auto-connect-failed，程式仍可手動連線
+pub(crate) fn start(app_handle: &tauri::AppHandle) {
+    let settings = match load(app_handle) {
+        Ok(Some(settings)) => settings,
+        Ok(None) => return,
+        Err(error_message) => {
+            supervisor::record_diagnostic(&format!("auto-connect disabled: {}", error_message));
+            return;
+        }
+    };
+    let state = app_handle.state::<Arc<StateMutex>>().inner().clone();
+    let running = Arc::new(AtomicBool::new(true));
+    if let Ok(mut app_state) = state.lock() {
+        app_state.auto_connect.running = Some(running.clone());
+    }
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || {
+        let finished = supervisor::guard("auto-connect", String::new(), || {
+            run_auto_connect(&state, app_handle.clone(), &settings, &running)
+        });
+        if finished.is_none() {
+            let failed = AutoConnectFailed {
+                error: "auto-connect panicked".into(),
+                attempts: 0,
+                cancelled: false,
+            };
+            app_handle.emit_event("auto-connect-failed", failed.clone());
+            set_status(&state, AutoConnectStatus::Failed(failed));
+        }
+        if let Ok(mut app_state) = state.lock() {
+            if app_state.auto_connect.running.as_ref().is_some_and(|r| Arc::ptr_eq(r, &running)) {
+                app_state.auto_connect.running = None;
+            }
+        }
+    });
+}
+
+#[derive(Serialize)]
+pub struct AutoConnectInfo {
+    pub settings: Option<AutoConnectSettings>,
+    pub status: AutoConnectStatus,
+}
+
+#[tauri::command]
+pub fn get_auto_connect(app_handle: tauri::AppHandle, state: State<Arc<StateMutex>>) -> Result<AutoConnectInfo, String> {
+    let settings = load(&app_handle)?;
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    Ok(AutoConnectInfo {
+        settings,
+        status: app_state.auto_connect.status.clone(),
+    })
+}
+
+/// 儲存下次啟動時的自動連線設定；settings 為 None 時關閉自動連線
+#[tauri::command]
+pub fn set_auto_connect(settings: Option<AutoConnectSettings>, app_handle: tauri::AppHandle) -> Result<String, String> {
+    if let Some(settings) = &settings {
+        settings.validate()?;
+    }
+    store(&app_handle, settings.as_ref())?;
+    Ok(match settings {
+        Some(_) => "Auto-connect enabled for the next start".into(),
+        None => "Auto-connect disabled".into(),
+    })
+}
+
+/// 停止重試中的自動連線；已連線的裝置不受影響
+#[tauri::command]
+pub fn cancel_auto_connect(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let running = app_state.auto_connect.running.take().ok_or("Auto-connect is not running")?;
+    running.store(false, Ordering::SeqCst);
+    Ok("Auto-connect cancelled".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::{AppState, VciBoardInfo};
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl EventSink for Recorder {
+        fn emit_event<S: Serialize + Clone>(&self, event: &str, _payload: S) -> bool {
+            self.0.lock().unwrap().push(event.to_string());
+            true
+        }
+    }
+
+    fn settings(value: serde_json::Value) -> AutoConnectSettings {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// 列舉中有序號 MOCK0001 的裝置
+    fn listed_state() -> (Arc<MockCan>, Arc<StateMutex>) {
+        let mock = Arc::new(MockCan::new());
+        let mut board_info = VciBoardInfo::default();
+        board_info.str_serial_num[..8].copy_from_slice(b"MOCK0001");
+        mock.set_devices(vec![board_info]);
+        let state = Arc::new(StateMutex::new(AppState::with_interface(mock.clone())));
+        (mock, state)
+    }
+
+    fn saved_channel() -> serde_json::Value {
+        serde_json::json!({
+            "channel": 0, "timing0": 0, "timing1": 0x1C, "mode": 0,
+            "acc_code": 0, "acc_mask": 0xFFFF_FFFFu32, "filter": 1, "started": true,
+        })
+    }
+
+    #[test]
+    fn a_listed_serial_is_opened_with_its_saved_channels() {
+        let (mock, state) = listed_state();
+        let events = Recorder::default();
+        let settings = settings(serde_json::json!({ "serial_number": "MOCK0001", "channels": [saved_channel()] }));
+        let status = run_auto_connect(&state, events.clone(), &settings, &AtomicBool::new(true));
+        let AutoConnectStatus::Connected(finished) = status else {
+            panic!("not connected: {:?}", status);
+        };
+        assert_eq!((finished.dev_type, finished.dev_index, finished.attempts), (4, 0, 1));
+        assert!(finished.receiving.is_empty());
+        assert!(mock.is_started(4, 0, 0));
+        assert_eq!(*events.0.lock().unwrap(), ["auto-connect-started", "auto-connect-finished"]);
+        assert!(matches!(state.lock().unwrap().auto_connect.status, AutoConnectStatus::Connected(_)));
+    }
+
+    #[test]
+    fn a_missing_serial_is_retried_until_max_attempts() {
+        let (mock, state) = listed_state();
+        let events = Recorder::default();
+        let settings = settings(serde_json::json!({
+            "serial_number": "MOCK9999", "channels": [], "retry_interval_ms": 0, "max_attempts": 2,
+        }));
+        let AutoConnectStatus::Failed(failed) = run_auto_connect(&state, events.clone(), &settings, &AtomicBool::new(true)) else {
+            panic!("connected to a missing device");
+        };
+        assert_eq!((failed.attempts, failed.cancelled), (2, false));
+        assert_eq!(failed.error, "saved device MOCK9999 not present (available: MOCK0001)");
+        assert_eq!(mock.open_count(4, 0), 0);
+        assert_eq!(*events.0.lock().unwrap(), ["auto-connect-started", "auto-connect-failed"]);
+    }
+
+    #[test]
+    fn clearing_running_cancels_the_retry_wait() {
+        let (_mock, state) = listed_state();
+        let settings = settings(serde_json::json!({ "serial_number": "MOCK9999", "channels": [] }));
+        let AutoConnectStatus::Failed(failed) = run_auto_connect(&state, Recorder::default(), &settings, &AtomicBool::new(false)) else {
+            panic!("connected to a missing device");
+        };
+        assert_eq!((failed.attempts, failed.cancelled), (1, true));
+    }
+
+    #[test]
+    fn a_device_already_open_here_fails_without_retrying() {
+        let (_mock, state) = listed_state();
+        state.lock().unwrap().open_device(4, 0, None).unwrap();
+        let settings = settings(serde_json::json!({ "dev_index": 0, "channels": [] }));
+        let AutoConnectStatus::Failed(failed) = run_auto_connect(&state, Recorder::default(), &settings, &AtomicBool::new(true)) else {
+            panic!("opened the device twice");
+        };
+        assert_eq!((failed.error.as_str(), failed.attempts), ("device 0 is already open", 1));
+    }
+
+    #[test]
+    fn zero_max_attempts_and_missing_channels_are_rejected() {
+        let error = settings(serde_json::json!({ "channels": [], "max_attempts": 0 })).validate().unwrap_err();
+        assert!(error.contains("settings.max_attempts"), "{}", error);
+        assert!(settings(serde_json::json!({ "channels": [], "max_attempts": 1 })).validate().is_ok());
+        assert!(serde_json::from_value::<AutoConnectSettings>(serde_json::json!({ "dev_index": 0 })).is_err());
+    }
+}
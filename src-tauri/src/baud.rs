@@ -0,0 +1,97 @@
+use serde::Serialize;
+
+/// CANalyst-II 的 SJA1000 相容控制器時脈
+pub const SJA1000_CLOCK_HZ: u32 = 16_000_000;
+
+/// ControlCAN 文件中的標準 Timing0/Timing1 (bit/s, Timing0, Timing1)
+pub const STANDARD_TIMINGS: &[(u32, u8, u8)] = &[
+    (10_000, 0x31, 0x1C),
+    (20_000, 0x18, 0x1C),
+    (40_000, 0x87, 0xFF),
+    (50_000, 0x09, 0x1C),
+    (80_000, 0x83, 0xFF),
+    (100_000, 0x04, 0x1C),
+    (125_000, 0x03, 0x1C),
+    (200_000, 0x81, 0xFA),
+    (250_000, 0x01, 0x1C),
+    (400_000, 0x80, 0xFA),
+    (500_000, 0x00, 0x1C),
+    (666_000, 0x80, 0xB6),
+    (800_000, 0x00, 0x16),
+    (1_000_000, 0x00, 0x14),
+];
+
+/// 標準位元率對應的 Timing0/Timing1
+pub fn timing_for_bitrate(bitrate: u32) -> Option<(u8, u8)> {
+    STANDARD_TIMINGS
+        .iter()
+        .find(|&&(b, _, _)| b == bitrate)
+        .map(|&(_, timing0, timing1)| (timing0, timing1))
+}
+
+/// 由 Timing0/Timing1 (SJA1000 BTR0/BTR1) 計算位元率：
+/// tq = 2 × (BRP + 1) / f_clk，一個位元 = 1 + TSEG1 + TSEG2 個 tq
+pub fn bitrate_from_timing(timing0: u8, timing1: u8) -> u32 {
+    let brp = (timing0 & 0x3F) as u32 + 1;
+    let tseg1 = (timing1 & 0x0F) as u32 + 1;
+    let tseg2 = ((timing1 >> 4) & 0x07) as u32 + 1;
+    SJA1000_CLOCK_HZ / (2 * brp * (1 + tseg1 + tseg2))
+}
+
+/// 取樣點位置：同步段加 TSEG1 佔整個位元的比例 (%)
+pub fn sample_point_percent(timing1: u8) -> f64 {
+    let tseg1 = (timing1 & 0x0F) as f64 + 1.0;
+    let tseg2 = ((timing1 >> 4) & 0x07) as f64 + 1.0;
+    (1.0 + tseg1) / (1.0 + tseg1 + tseg2) * 100.0
+}
+
+#[derive(Serialize)]
+pub struct BaudPreset {
+    pub name: String,
+    pub bitrate: u32,
+    pub timing0: u8,
+    pub timing1: u8,
+    /// 以控制器時脈實際得到的位元率
+    pub actual_bitrate: u32,
+    pub sample_point_percent: f64,
+    /// actual_bitrate 與 bitrate 完全相同
+    pub exact: bool,
+}
+
+fn preset_name(bitrate: u32) -> String {
+    if bitrate >= 1_000_000 && bitrate.is_multiple_of(1_000_000) {
+        format!("{} Mbit/s", bitrate / 1_000_000)
+    } else {
+        format!("{} kbit/s", bitrate as f64 / 1000.0)
+    }
+}
+
+/// 前端的鮑率選單：STANDARD_TIMINGS 的每一項與其暫存器值、取樣點
+#[tauri::command]
+pub fn list_baud_presets() -> Vec<BaudPreset> {
+    STANDARD_TIMINGS
+        .iter()
+        .map(|&(bitrate, timing0, timing1)| {
+            let actual_bitrate = bitrate_from_timing(timing0, timing1);
+            BaudPreset {
+                name: preset_name(bitrate),
+                bitrate,
+                timing0,
+                timing1,
+                actual_bitrate,
+                sample_point_percent: sample_point_percent(timing1),
+                exact: actual_bitrate == bitrate,
+            }
+        })
+        .collect()
+}
+
+/// 估算一個經典 CAN 訊框在匯流排上佔用的位元數 (含 IFS 與約略的填充位元)
+pub fn frame_bits(extended: bool, remote: bool, dlc: u8) -> u32 {
+    let data_bits = if remote { 0 } else { 8 * dlc.min(8) as u32 };
+    // SOF 到 CRC 之間會被填充的位元；其後 CRC 分隔、ACK、EOF、IFS 共 13 位元不填充
+    let stuffable = if extended { 54 } else { 34 } + data_bits;
+    // 最壞情況每 4 位元插入 1 位，實際流量約為一半
+    let stuff_bits = (stuffable - 1) / 8;
+    stuffable + stuff_bits + 13
+}
@@ -0,0 +1,195 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::operation::OperationKind;
+use crate::supervisor;
+use crate::tap::TapReceiver;
+use crate::{run_blocking, DeviceType, StateMutex, VciCanObj};
+
+const BENCHMARK_ID: u32 = 0x100;
+/// 批次模式每次 VCI_Transmit 送出的訊框數
+const BATCH_FRAMES: usize = 100;
+/// 連續這麼多次一個都送不出去就放棄
+const MAX_CONSECUTIVE_FAILURES: u32 = 100;
+/// 傳送結束後再等這麼久沒有收到訊框就結束接收端的量測
+const RX_IDLE_TIMEOUT: Duration = Duration::from_millis(200);
+/// VCI_InitConfig.mode 的自發自收模式
+const MODE_SELF_TEST: u8 = 2;
+
+#[derive(Serialize)]
+pub struct RxBenchmark {
+    pub channel: u32,
+    pub frames_received: u64,
+    pub duration_ms: f64,
+    pub frames_per_sec: f64,
+}
+
+#[derive(Serialize)]
+pub struct BenchmarkResult {
+    pub frames_requested: u32,
+    pub frames_sent: u64,
+    pub duration_ms: f64,
+    pub frames_per_sec: f64,
+    pub transmit_calls: u64,
+    /// VCI_Transmit 只送出部分訊框的次數
+    pub partial_sends: u64,
+    /// VCI_Transmit 一個都沒送出的次數
+    pub failed_sends: u64,
+    /// 連續失敗過多或被 cancel_operation 取消而提前結束
+    pub aborted: bool,
+    /// 通道為自發自收模式或指定 rx_channel 時的接收端量測
+    pub rx: Option<RxBenchmark>,
+}
+
+fn per_sec(frames: u64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => frames as f64 / secs,
+        _ => 0.0,
+    }
+}
+
+fn benchmark_frame(sequence: u32, payload_len: usize) -> VciCanObj {
+    let mut can_obj = VciCanObj {
+        id: BENCHMARK_ID,
+        data_len: payload_len as u8,
+        ..Default::default()
+    };
+    let bytes = sequence.to_le_bytes();
+    for (i, byte) in can_obj.data[..payload_len].iter_mut().enumerate() {
+        *byte = bytes.get(i).copied().unwrap_or_default();
+    }
+    can_obj
+}
+
+/// 在背景計算接收端收到的測試訊框，直到 done 後閒置 RX_IDLE_TIMEOUT
+fn spawn_rx_counter(tap: TapReceiver, expected: u64, done: Arc<AtomicBool>) -> std::thread::JoinHandle<(u64, Duration)> {
+    std::thread::spawn(move || {
+        let mut received = 0;
+        let mut first: Option<Instant> = None;
+        let mut last = Instant::now();
+        supervisor::guard("benchmark-rx", String::new(), || {
+            while received < expected {
+                let deadline = Instant::now() + Duration::from_millis(10);
+                match tap.recv_until(deadline) {
+                    Some(frame) if frame.id == BENCHMARK_ID => {
+                        last = Instant::now();
+                        first.get_or_insert(last);
+                        received += 1;
+                    }
+                    Some(_) => {}
+                    None if done.load(Ordering::SeqCst) && last.elapsed() > RX_IDLE_TIMEOUT => break,
+                    None => {}
+                }
+            }
+        });
+        (received, first.map_or(Duration::ZERO, |first| last.duration_since(first)))
+    })
+}
+
+/// 以驅動程式能接受的最快速度送出 frame_count 個訊框 (逐一或批次呼叫 VCI_Transmit)，回報達成的速率。
+/// 送出的訊框不回送到前端事件流。開始時送出 operation-started，可用 cancel_operation 在兩次傳送之間取消
+#[tauri::command]
+pub async fn run_throughput_benchmark(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    frame_count: u32,
+    payload_len: u8,
+    use_batch: bool,
+    rx_channel: Option<u32>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<BenchmarkResult, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        if frame_count == 0 {
+            return Err("frame_count must be greater than 0".into());
+        }
+        if payload_len > 8 {
+            return Err(format!("payload_len {} exceeds 8 bytes", payload_len));
+        }
+        let (key, self_test, operation) = {
+            let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+            let self_test = device.channels.get(&channel).is_some_and(|c| c.config.mode == MODE_SELF_TEST);
+            let key = device.key();
+            (key, self_test, app_state.start_operation(OperationKind::Benchmark, key, channel, Some(frame_count as u64)))
+        };
+        operation.announce(&state, &app_handle);
+        let rx_channel = rx_channel.or(self_test.then_some(channel));
+        let done = Arc::new(AtomicBool::new(false));
+        let tap = match rx_channel.map(|rx_channel| TapReceiver::open(&state, key, rx_channel)).transpose() {
+            Ok(tap) => tap,
+            Err(error_message) => {
+                operation.finish(&state, &app_handle, Some(error_message.clone()));
+                return Err(error_message);
+            }
+        };
+        let rx_counter = rx_channel.zip(tap).map(|(rx_channel, tap)| (rx_channel, spawn_rx_counter(tap, frame_count as u64, done.clone())));
+
+        let frames: Vec<VciCanObj> = (0..frame_count).map(|seq| benchmark_frame(seq, payload_len as usize)).collect();
+        let batch = if use_batch { BATCH_FRAMES } else { 1 };
+        let (mut sent_total, mut transmit_calls, mut partial_sends, mut failed_sends) = (0usize, 0u64, 0u64, 0u64);
+        let mut consecutive_failures = 0;
+        let started = Instant::now();
+        let running = operation.running();
+        while sent_total < frames.len() && consecutive_failures < MAX_CONSECUTIVE_FAILURES && running.load(Ordering::SeqCst) {
+            let chunk = &frames[sent_total..(sent_total + batch).min(frames.len())];
+            transmit_calls += 1;
+            let Ok(mut app_state) = state.lock() else {
+                break;
+            };
+            let tx_path = app_state.tx_path(key, channel);
+            drop(app_state);
+            let result = tx_path.and_then(|tx_path| tx_path.transmit(chunk, false));
+            match result {
+                Ok(sent) => {
+                    consecutive_failures = 0;
+                    if (sent as usize) < chunk.len() {
+                        partial_sends += 1;
+                    }
+                    sent_total += sent as usize;
+                    operation.set_progress(sent_total as u64);
+                }
+                Err(_) => {
+                    failed_sends += 1;
+                    consecutive_failures += 1;
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+        let elapsed = started.elapsed();
+        done.store(true, Ordering::SeqCst);
+        let gave_up = consecutive_failures >= MAX_CONSECUTIVE_FAILURES;
+        let cancelled = !running.load(Ordering::SeqCst);
+
+        let rx = rx_counter.and_then(|(channel, handle)| {
+            let (frames_received, duration) = handle.join().ok()?;
+            Some(RxBenchmark {
+                channel,
+                frames_received,
+                duration_ms: duration.as_secs_f64() * 1000.0,
+                frames_per_sec: per_sec(frames_received, duration),
+            })
+        });
+        let result = BenchmarkResult {
+            frames_requested: frame_count,
+            frames_sent: sent_total as u64,
+            duration_ms: elapsed.as_secs_f64() * 1000.0,
+            frames_per_sec: per_sec(sent_total as u64, elapsed),
+            transmit_calls,
+            partial_sends,
+            failed_sends,
+            aborted: gave_up || cancelled,
+            rx,
+        };
+        let error = gave_up.then(|| format!("{} consecutive transmit failures", MAX_CONSECUTIVE_FAILURES));
+        operation.finish(&state, &app_handle, error);
+        Ok(result)
+    })
+    .await
+}
@@ -0,0 +1,145 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use crate::frame::FrameInput;
+use crate::operation::OperationKind;
+use crate::sequence::wait_until;
+use crate::tx_limit::transmit_paced;
+use crate::{invalid_argument, run_blocking, DeviceType, StateMutex};
+
+/// 超過任一門檻時必須帶 confirm_large，避免把誤植的參數當成近乎無限的傳送
+const CONFIRM_FRAMES: u32 = 1000;
+const CONFIRM_DURATION: Duration = Duration::from_secs(10);
+/// 即使確認也不接受的上限；更長的傳送應使用週期任務
+const MAX_BURST_FRAMES: u32 = 100_000;
+
+#[derive(Serialize, Clone)]
+struct BurstStarted {
+    burst_id: u32,
+    channel: u32,
+    count: u32,
+    interval_ms: u64,
+}
+
+#[derive(Serialize)]
+pub struct BurstResult {
+    pub burst_id: u32,
+    pub requested: u32,
+    /// 驅動實際接受的訊框數
+    pub sent: u32,
+    pub completed: bool,
+    pub aborted: bool,
+    /// 第一個訊框送出到最後一個訊框送出的實測時間
+    pub duration_ms: f64,
+    pub error: Option<String>,
+}
+
+/// 以 interval_ms 的間隔送出同一個訊框 count 次，完成後才回傳。
+/// 開始時送出 burst-started 事件帶 burst_id (即 operation_id)，可在完成前以 abort_burst 或 cancel_operation 中止；
+/// 傳送經過通道的速率限制
+#[tauri::command]
+pub async fn transmit_burst(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    frame: FrameInput,
+    count: u32,
+    interval_ms: u64,
+    confirm_large: Option<bool>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<BurstResult, String> {
+    let can_obj = frame.checked("frame")?;
+    if count == 0 {
+        return Err(invalid_argument("count", "must be at least 1"));
+    }
+    if count > MAX_BURST_FRAMES {
+        return Err(invalid_argument(
+            "count",
+            format!("{} exceeds the {}-frame burst limit; use a periodic task", count, MAX_BURST_FRAMES),
+        ));
+    }
+    let interval = Duration::from_millis(interval_ms);
+    let planned = interval * (count - 1);
+    if (count > CONFIRM_FRAMES || planned > CONFIRM_DURATION) && confirm_large != Some(true) {
+        return Err(invalid_argument(
+            "confirm_large",
+            format!(
+                "{} frames over {:.1} s requires confirm_large: true",
+                count,
+                planned.as_secs_f64()
+            ),
+        ));
+    }
+    let (key, operation) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+        device.check_channel(channel)?;
+        let key = device.key();
+        (key, app_state.start_operation(OperationKind::Burst, key, channel, Some(count as u64)))
+    };
+    let burst_id = operation.id();
+    operation.announce(&state, &app_handle);
+    let _ = app_handle.emit(
+        "burst-started",
+        BurstStarted {
+            burst_id,
+            channel,
+            count,
+            interval_ms,
+        },
+    );
+
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let running = operation.running().clone();
+        let mut sent = 0;
+        let mut error = None;
+        let mut first_sent = None;
+        let mut last_sent = Instant::now();
+        let mut next = Instant::now();
+        for _ in 0..count {
+            if !wait_until(next, &running) {
+                break;
+            }
+            if let Err(error_message) = transmit_paced(&state, key, channel, std::slice::from_ref(&can_obj), true) {
+                error = Some(error_message);
+                break;
+            }
+            last_sent = Instant::now();
+            first_sent.get_or_insert(last_sent);
+            sent += 1;
+            operation.set_progress(sent as u64);
+            // 間隔從預定的傳送時間起算，傳送耗時不會累積；落後時從現在重新起算
+            next += interval;
+            if next < last_sent {
+                next = last_sent;
+            }
+        }
+        let aborted = !running.load(Ordering::SeqCst);
+        operation.finish(&state, &app_handle, error.clone());
+        Ok(BurstResult {
+            burst_id,
+            requested: count,
+            sent,
+            completed: sent == count,
+            aborted,
+            duration_ms: first_sent.map_or(0.0, |first| (last_sent - first).as_secs_f64() * 1000.0),
+            error,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn abort_burst(burst_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state
+        .cancel_operation(burst_id, Some(OperationKind::Burst))
+        .ok_or_else(|| format!("burst {} not found", burst_id))?;
+    Ok(format!("burst {} aborted", burst_id))
+}
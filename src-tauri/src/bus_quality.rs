@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::stats::ChannelCounters;
+use crate::{invalid_argument, DeviceType, StateMutex};
+
+/// 視窗以這個長度的時間桶滑動
+const BUCKET: Duration = Duration::from_secs(1);
+const DEFAULT_WINDOW_S: u32 = 10;
+const MAX_WINDOW_S: u32 = 3600;
+
+/// 通道的錯誤率視窗與警告門檻；兩個門檻任一超過即視為品質下降
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct BusQualityConfig {
+    pub window_s: u32,
+    pub max_errors_per_sec: Option<f64>,
+    pub max_errors_per_1000_frames: Option<f64>,
+}
+
+impl Default for BusQualityConfig {
+    fn default() -> Self {
+        Self {
+            window_s: DEFAULT_WINDOW_S,
+            max_errors_per_sec: None,
+            max_errors_per_1000_frames: None,
+        }
+    }
+}
+
+/// 最近 window_s 秒內的錯誤事件 (VCI_ReadErrInfo 回報的錯誤與 VCI_Receive 失敗)
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct ErrorRate {
+    pub errors: u64,
+    pub frames: u64,
+    pub errors_per_sec: f64,
+    /// 視窗內沒有任何訊框時為 None
+    pub errors_per_1000_frames: Option<f64>,
+}
+
+/// bus-quality-degraded / bus-quality-recovered 事件
+#[derive(Serialize, Clone)]
+pub struct BusQualityEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    #[serde(flatten)]
+    pub rate: ErrorRate,
+    #[serde(flatten)]
+    pub config: BusQualityConfig,
+}
+
+/// 由接收執行緒持有，每一輪以通道計數器的增量更新時間桶
+pub struct BusQualityMonitor {
+    key: (u32, u32),
+    channel: u32,
+    counters: Arc<ChannelCounters>,
+    config: Arc<Mutex<BusQualityConfig>>,
+    /// 每個時間桶的 (錯誤數, 訊框數)，最後一個是目前的桶
+    buckets: VecDeque<(u64, u64)>,
+    bucket_start: Instant,
+    last_errors: u64,
+    last_frames: u64,
+    degraded: bool,
+    rate: ErrorRate,
+}
+
+impl BusQualityMonitor {
+    pub fn new(key: (u32, u32), channel: u32, counters: Arc<ChannelCounters>, config: Arc<Mutex<BusQualityConfig>>) -> Self {
+        let (last_errors, last_frames) = totals(&counters);
+        Self {
+            key,
+            channel,
+            counters,
+            config,
+            buckets: VecDeque::from([(0, 0)]),
+            bucket_start: Instant::now(),
+            last_errors,
+            last_frames,
+            degraded: false,
+            rate: ErrorRate::default(),
+        }
+    }
+
+    pub fn rate(&self) -> ErrorRate {
+        self.rate
+    }
+
+    /// 跨過門檻時回傳事件名稱與內容
+    pub fn poll(&mut self) -> Option<(&'static str, BusQualityEvent)> {
+        let config = *self.config.lock().unwrap_or_else(PoisonError::into_inner);
+        let (errors, frames) = totals(&self.counters);
+        // 計數器被歸零時 total 會小於上次的值
+        let error_delta = errors.checked_sub(self.last_errors).unwrap_or(errors);
+        let frame_delta = frames.checked_sub(self.last_frames).unwrap_or(frames);
+        self.last_errors = errors;
+        self.last_frames = frames;
+        if self.bucket_start.elapsed() >= BUCKET {
+            self.buckets.push_back((0, 0));
+            self.bucket_start = Instant::now();
+        }
+        if let Some(current) = self.buckets.back_mut() {
+            current.0 += error_delta;
+            current.1 += frame_delta;
+        }
+        while self.buckets.len() > config.window_s as usize {
+            self.buckets.pop_front();
+        }
+
+        let (errors, frames) = self.buckets.iter().fold((0, 0), |(e, f), &(be, bf)| (e + be, f + bf));
+        let seconds = ((self.buckets.len() - 1) as f64 + self.bucket_start.elapsed().as_secs_f64()).max(BUCKET.as_secs_f64());
+        self.rate = ErrorRate {
+            errors,
+            frames,
+            errors_per_sec: errors as f64 / seconds,
+            errors_per_1000_frames: (frames > 0).then(|| errors as f64 * 1000.0 / frames as f64),
+        };
+        let exceeded = config.max_errors_per_sec.is_some_and(|max| self.rate.errors_per_sec > max)
+            || config
+                .max_errors_per_1000_frames
+                .zip(self.rate.errors_per_1000_frames)
+                .is_some_and(|(max, rate)| rate > max);
+        if exceeded == self.degraded {
+            return None;
+        }
+        self.degraded = exceeded;
+        let event = BusQualityEvent {
+            dev_type: self.key.0,
+            dev_index: self.key.1,
+            channel: self.channel,
+            rate: self.rate,
+            config,
+        };
+        Some((if exceeded { "bus-quality-degraded" } else { "bus-quality-recovered" }, event))
+    }
+}
+
+fn totals(counters: &ChannelCounters) -> (u64, u64) {
+    (
+        counters.bus_errors.load(Ordering::Relaxed),
+        counters.rx_frames.load(Ordering::Relaxed) + counters.tx_frames.load(Ordering::Relaxed),
+    )
+}
+
+/// 設定錯誤率視窗與門檻；平均錯誤率超過任一門檻時送出 bus-quality-degraded，回到門檻以下時送出 bus-quality-recovered。
+/// 需要接收執行緒在運作，視窗內的錯誤率也附在 can-stats 事件中
+#[tauri::command]
+pub fn set_bus_quality_threshold(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    config: BusQualityConfig,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    if !(1..=MAX_WINDOW_S).contains(&config.window_s) {
+        return Err(invalid_argument("config.window_s", format!("must be between 1 and {}", MAX_WINDOW_S)));
+    }
+    for (field, threshold) in [
+        ("config.max_errors_per_sec", config.max_errors_per_sec),
+        ("config.max_errors_per_1000_frames", config.max_errors_per_1000_frames),
+    ] {
+        if threshold.is_some_and(|max| !max.is_finite() || max < 0.0) {
+            return Err(invalid_argument(field, "must be a non-negative number"));
+        }
+    }
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let key = device.key();
+    let runtime = app_state.channel_runtime(key, channel);
+    *runtime.bus_quality.lock().map_err(|_| "Failed to lock bus quality config")? = config;
+    Ok(format!("CAN{} bus quality window set to {} s", channel + 1, config.window_s))
+}
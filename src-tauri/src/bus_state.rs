@@ -0,0 +1,103 @@
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::stats::ChannelCounters;
+use crate::{CanInterface, StateMutex, VciCanStatus};
+
+/// SJA1000 狀態暫存器的 bus-off 位元
+const STATUS_BUS_OFF: u8 = 0x80;
+/// 任一錯誤計數器達到此值即進入 error-passive
+const ERROR_PASSIVE_LIMIT: u8 = 128;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BusState {
+    ErrorActive,
+    ErrorPassive,
+    BusOff,
+}
+
+impl BusState {
+    pub fn from_status(status: &VciCanStatus) -> Self {
+        if status.reg_status & STATUS_BUS_OFF != 0 {
+            BusState::BusOff
+        } else if status.reg_re_counter >= ERROR_PASSIVE_LIMIT || status.reg_te_counter >= ERROR_PASSIVE_LIMIT {
+            BusState::ErrorPassive
+        } else {
+            BusState::ErrorActive
+        }
+    }
+}
+
+/// 最近一次讀到的控制器狀態，附在 can-stats 事件中
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ControllerStatus {
+    pub rx_err_counter: u8,
+    pub tx_err_counter: u8,
+    pub bus_state: BusState,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BusStateChanged {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    /// 通道開始接收後第一次讀取時為 None
+    pub previous: Option<BusState>,
+    #[serde(flatten)]
+    pub status: ControllerStatus,
+}
+
+/// 由接收執行緒定期呼叫 VCI_ReadCANStatus，與 VCI_Receive 在同一執行緒依序呼叫 DLL；
+/// 接收停止時輪詢也隨之停止
+pub struct BusStateMonitor {
+    key: (u32, u32),
+    channel: u32,
+    counters: Arc<ChannelCounters>,
+    /// Duration::ZERO 表示不輪詢
+    interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl BusStateMonitor {
+    pub fn new(key: (u32, u32), channel: u32, counters: Arc<ChannelCounters>, interval: Duration) -> Self {
+        Self {
+            key,
+            channel,
+            counters,
+            interval,
+            last_poll: None,
+        }
+    }
+
+    /// 狀態改變時回傳 bus-state-changed 事件；後端不支援或讀取失敗時略過
+    pub fn poll(&mut self, state: &Arc<StateMutex>) -> Option<BusStateChanged> {
+        if self.interval.is_zero() || self.last_poll.is_some_and(|t| t.elapsed() < self.interval) {
+            return None;
+        }
+        self.last_poll = Some(Instant::now());
+        let can_lib: Arc<dyn CanInterface> = state.lock().ok()?.backend()?;
+        let raw = can_lib.read_can_status(self.key.0, self.key.1, self.channel).ok()?;
+        let status = ControllerStatus {
+            rx_err_counter: raw.reg_re_counter,
+            tx_err_counter: raw.reg_te_counter,
+            bus_state: BusState::from_status(&raw),
+        };
+        let previous = self
+            .counters
+            .controller
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(status)
+            .map(|s| s.bus_state);
+        (previous != Some(status.bus_state)).then_some(BusStateChanged {
+            dev_type: self.key.0,
+            dev_index: self.key.1,
+            channel: self.channel,
+            previous,
+            status,
+        })
+    }
+}
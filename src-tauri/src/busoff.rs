@@ -0,0 +1,216 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::bus_state::BusState;
+use crate::receive::EventSink;
+use crate::timestamp;
+use crate::{invalid_argument, DeviceType, StateMutex};
+
+/// 恢復後維持這麼久沒有再 bus-off，嘗試次數才重新計算
+const STABLE_AFTER_RECOVERY: Duration = Duration::from_secs(10);
+const STOP_POLL: Duration = Duration::from_millis(50);
+
+/// 通道 bus-off 時的處理方式
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum BusOffRecovery {
+    /// 只送出 bus-state-changed 事件
+    #[default]
+    Manual,
+    /// 等待 delay_ms 後呼叫 VCI_ResetCAN 與 VCI_StartCAN，最多嘗試 max_attempts 次
+    Auto { delay_ms: u64, max_attempts: u32 },
+    /// 關閉並重新開啟裝置 (與斷線重連相同的流程)
+    Reopen,
+}
+
+/// ChannelRuntime::recovery_phase 的值
+pub const PHASE_IDLE: u8 = 0;
+pub const PHASE_RECOVERING: u8 = 1;
+/// 已用盡嘗試次數，通道停止
+pub const PHASE_GAVE_UP: u8 = 2;
+
+#[derive(Serialize, Clone)]
+struct RecoveryAttempt {
+    dev_type: u32,
+    dev_index: u32,
+    channel: u32,
+    attempt: u32,
+    max_attempts: u32,
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct RecoveryFailed {
+    dev_type: u32,
+    dev_index: u32,
+    channel: u32,
+    attempts: u32,
+}
+
+/// 接收執行緒偵測到 bus-off 後要做的事
+pub enum BusOffAction {
+    None,
+    /// 走斷線重連的流程重新開啟裝置
+    Reopen,
+    /// 恢復失敗，通道已停止
+    Stopped,
+}
+
+/// 由接收執行緒持有；嘗試次數跨越短時間內連續發生的 bus-off 累計
+pub struct BusOffRecoverer {
+    key: (u32, u32),
+    channel: u32,
+    attempts: u32,
+    last_recovery: Option<Instant>,
+}
+
+impl BusOffRecoverer {
+    pub fn new(key: (u32, u32), channel: u32) -> Self {
+        Self {
+            key,
+            channel,
+            attempts: 0,
+            last_recovery: None,
+        }
+    }
+
+    /// 依通道的恢復策略處理 bus-off；自動恢復會在此阻塞直到成功、用盡次數或 receiving 被清除
+    pub fn on_bus_off<E: EventSink>(&mut self, state: &Arc<StateMutex>, receiving: &AtomicBool, events: &E) -> BusOffAction {
+        let Ok(runtime) = state.lock().map(|mut s| s.channel_runtime(self.key, self.channel)) else {
+            return BusOffAction::None;
+        };
+        let policy = *runtime.busoff_recovery.lock().unwrap_or_else(PoisonError::into_inner);
+        let (delay, max_attempts) = match policy {
+            BusOffRecovery::Manual => return BusOffAction::None,
+            BusOffRecovery::Reopen => {
+                println!("CAN{} bus-off, reopening device", self.channel + 1);
+                return BusOffAction::Reopen;
+            }
+            BusOffRecovery::Auto { delay_ms, max_attempts } => (Duration::from_millis(delay_ms), max_attempts),
+        };
+        if self.last_recovery.is_none_or(|t| t.elapsed() >= STABLE_AFTER_RECOVERY) {
+            self.attempts = 0;
+        }
+        runtime.recovery_phase.store(PHASE_RECOVERING, Ordering::SeqCst);
+        while self.attempts < max_attempts {
+            if !wait(delay, receiving) {
+                runtime.recovery_phase.store(PHASE_IDLE, Ordering::SeqCst);
+                return BusOffAction::None;
+            }
+            self.attempts += 1;
+            let result = self.restart(state);
+            match &result {
+                Ok(()) => println!("CAN{} bus-off recovery attempt {} succeeded", self.channel + 1, self.attempts),
+                Err(e) => println!("CAN{} bus-off recovery attempt {} failed: {}", self.channel + 1, self.attempts, e),
+            }
+            events.emit_event(
+                "busoff-recovery",
+                RecoveryAttempt {
+                    dev_type: self.key.0,
+                    dev_index: self.key.1,
+                    channel: self.channel,
+                    attempt: self.attempts,
+                    max_attempts,
+                    success: result.is_ok(),
+                    error: result.as_ref().err().cloned(),
+                },
+            );
+            if result.is_ok() {
+                self.last_recovery = Some(Instant::now());
+                runtime.recovery_phase.store(PHASE_IDLE, Ordering::SeqCst);
+                return BusOffAction::None;
+            }
+        }
+        runtime.recovery_phase.store(PHASE_GAVE_UP, Ordering::SeqCst);
+        receiving.store(false, Ordering::SeqCst);
+        if let Ok(mut app_state) = state.lock() {
+            if let Some(channel_state) = app_state.devices.get_mut(&self.key).and_then(|d| d.channels.get_mut(&self.channel)) {
+                channel_state.started = false;
+            }
+        }
+        events.emit_event(
+            "busoff-recovery-failed",
+            RecoveryFailed {
+                dev_type: self.key.0,
+                dev_index: self.key.1,
+                channel: self.channel,
+                attempts: self.attempts,
+            },
+        );
+        BusOffAction::Stopped
+    }
+
+    /// VCI_ResetCAN 後重新啟動，並確認控制器已離開 bus-off
+    fn restart(&self, state: &Arc<StateMutex>) -> Result<(), String> {
+        let (can_lib, clock) = {
+            let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let can_lib = app_state.backend().ok_or("CAN 裝置尚未初始化")?;
+            (can_lib, app_state.channel_runtime(self.key, self.channel).clock.clone())
+        };
+        let (dev_type, dev_index) = self.key;
+        can_lib.reset(dev_type, dev_index, self.channel)?;
+        timestamp::start_and_mark(can_lib.as_ref(), &clock, self.key, self.channel)?;
+        match can_lib.read_can_status(dev_type, dev_index, self.channel) {
+            Ok(status) if BusState::from_status(&status) == BusState::BusOff => Err("controller is still bus-off".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn wait(duration: Duration, receiving: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while receiving.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep((deadline - now).min(STOP_POLL));
+    }
+    false
+}
+
+/// 週期傳送失敗時呼叫：通道正在 (或即將) 自動恢復 bus-off 時回傳 true，
+/// 任務保留排程並在恢復後繼續送出，而不是結束
+pub(crate) fn transmit_on_hold(state: &Arc<StateMutex>, key: (u32, u32), channel: u32) -> bool {
+    let Ok((runtime, can_lib)) = state.lock().map(|mut s| (s.channel_runtime(key, channel), s.backend())) else {
+        return false;
+    };
+    match runtime.recovery_phase.load(Ordering::SeqCst) {
+        PHASE_RECOVERING => true,
+        PHASE_IDLE => {
+            // 接收執行緒每秒才讀一次狀態，這裡直接讀取以免在偵測到 bus-off 之前就結束任務
+            *runtime.busoff_recovery.lock().unwrap_or_else(PoisonError::into_inner) != BusOffRecovery::Manual
+                && can_lib.is_some_and(|lib| {
+                    lib.read_can_status(key.0, key.1, channel)
+                        .is_ok_and(|status| BusState::from_status(&status) == BusState::BusOff)
+                })
+        }
+        _ => false,
+    }
+}
+
+/// 設定通道的 bus-off 恢復策略；需要接收執行緒在運作 (由它讀取匯流排狀態)
+#[tauri::command]
+pub fn set_busoff_recovery(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    policy: BusOffRecovery,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    if let BusOffRecovery::Auto { max_attempts: 0, .. } = policy {
+        return Err(invalid_argument("policy.max_attempts", "must be at least 1"));
+    }
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let key = device.key();
+    let runtime = app_state.channel_runtime(key, channel);
+    *runtime.busoff_recovery.lock().map_err(|_| "Failed to lock recovery policy")? = policy;
+    Ok(format!("CAN{} bus-off recovery set to {:?}", channel + 1, policy))
+}
@@ -0,0 +1,2 @@
+pub mod nmt;
+pub mod sdo;
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::supervisor;
+use crate::tap::TapReceiver;
+use crate::{run_blocking, DeviceType, StateMutex, VciCanObj};
+
+const NMT_ID: u32 = 0x000;
+const HEARTBEAT_BASE: u32 = 0x700;
+const DEFAULT_HEARTBEAT_TIMEOUT_MS: u64 = 3000;
+/// 監看執行緒等待訊框的最長時間，也是檢查逾時的間隔
+const MONITOR_POLL_MS: u64 = 100;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeState {
+    BootUp,
+    Stopped,
+    Operational,
+    PreOperational,
+    Unknown,
+}
+
+impl NodeState {
+    fn from_heartbeat(value: u8) -> Self {
+        match value & 0x7F {
+            0x00 => NodeState::BootUp,
+            0x04 => NodeState::Stopped,
+            0x05 => NodeState::Operational,
+            0x7F => NodeState::PreOperational,
+            _ => NodeState::Unknown,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct NodeStatus {
+    pub node_id: u8,
+    pub state: NodeState,
+    /// 最後一次收到心跳的主機時間 (UNIX epoch 微秒)
+    pub last_seen_us: u64,
+    pub timed_out: bool,
+    #[serde(skip)]
+    last_seen: Instant,
+}
+
+#[derive(Serialize, Clone)]
+struct NodeStateEvent {
+    device_type: u32,
+    device_index: u32,
+    channel: u32,
+    node_id: u8,
+    state: NodeState,
+    previous: Option<NodeState>,
+}
+
+#[derive(Serialize, Clone)]
+struct NodeTimeoutEvent {
+    device_type: u32,
+    device_index: u32,
+    channel: u32,
+    node_id: u8,
+    last_seen_us: u64,
+}
+
+pub struct CanopenMonitor {
+    running: Arc<AtomicBool>,
+    nodes: Arc<Mutex<HashMap<u8, NodeStatus>>>,
+}
+
+/// 開始監看 0x700+node 的心跳；同一裝置的同一通道重複呼叫會先停掉舊的監看
+#[tauri::command]
+pub fn start_canopen_monitor(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    heartbeat_timeout_ms: Option<u64>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let key = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+        device.check_channel(channel)?;
+        device.key()
+    };
+    let slot = (key.0, key.1, channel);
+    let tap = TapReceiver::open(state.inner(), key, channel)?;
+    let running = Arc::new(AtomicBool::new(true));
+    let nodes: Arc<Mutex<HashMap<u8, NodeStatus>>> = Arc::default();
+    {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let monitor = CanopenMonitor {
+            running: running.clone(),
+            nodes: nodes.clone(),
+        };
+        if let Some(previous) = app_state.canopen_monitors.insert(slot, monitor) {
+            previous.running.store(false, Ordering::SeqCst);
+        }
+    }
+    let timeout = Duration::from_millis(heartbeat_timeout_ms.unwrap_or(DEFAULT_HEARTBEAT_TIMEOUT_MS));
+    let state = state.inner().clone();
+    std::thread::spawn(move || {
+        let finished = supervisor::guard("canopen-monitor", format!("CAN{}", channel + 1), || {
+            while running.load(Ordering::SeqCst) {
+                let frame = tap.recv_until(Instant::now() + Duration::from_millis(MONITOR_POLL_MS));
+                let Ok(mut nodes) = nodes.lock() else {
+                    break;
+                };
+                if let Some(frame) = frame.filter(|f| {
+                    !f.extended && !f.remote && f.data.len() == 1 && (HEARTBEAT_BASE + 1..HEARTBEAT_BASE + 128).contains(&f.id)
+                }) {
+                    let node_id = (frame.id - HEARTBEAT_BASE) as u8;
+                    let node_state = NodeState::from_heartbeat(frame.data[0]);
+                    let status = nodes.entry(node_id).or_insert(NodeStatus {
+                        node_id,
+                        state: NodeState::Unknown,
+                        last_seen_us: 0,
+                        timed_out: false,
+                        last_seen: Instant::now(),
+                    });
+                    let previous = (status.last_seen_us != 0).then_some(status.state);
+                    status.last_seen_us = frame.host_timestamp_us;
+                    status.last_seen = Instant::now();
+                    status.timed_out = false;
+                    // 開機訊息每次都通知，即使前一個狀態也是 boot-up (節點重新啟動)
+                    if previous != Some(node_state) || node_state == NodeState::BootUp {
+                        status.state = node_state;
+                        let _ = app_handle.emit(
+                            "canopen-node-state",
+                            NodeStateEvent {
+                                device_type: key.0,
+                                device_index: key.1,
+                                channel,
+                                node_id,
+                                state: node_state,
+                                previous,
+                            },
+                        );
+                    }
+                }
+                for status in nodes.values_mut().filter(|s| !s.timed_out && s.last_seen.elapsed() > timeout) {
+                    status.timed_out = true;
+                    let _ = app_handle.emit(
+                        "node-timeout",
+                        NodeTimeoutEvent {
+                            device_type: key.0,
+                            device_index: key.1,
+                            channel,
+                            node_id: status.node_id,
+                            last_seen_us: status.last_seen_us,
+                        },
+                    );
+                }
+            }
+        });
+        if finished.is_none() {
+            nodes.clear_poison();
+            if let Ok(mut app_state) = state.lock() {
+                if app_state.canopen_monitors.get(&slot).is_some_and(|m| Arc::ptr_eq(&m.running, &running)) {
+                    app_state.canopen_monitors.remove(&slot);
+                }
+            }
+        }
+    });
+    Ok(format!("CANopen monitor started on CAN{}", channel + 1))
+}
+
+#[tauri::command]
+pub fn stop_canopen_monitor(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let key = app_state.device(dev_type.map(DeviceType::code), dev_index)?.key();
+    let monitor = app_state
+        .canopen_monitors
+        .remove(&(key.0, key.1, channel))
+        .ok_or_else(|| format!("No CANopen monitor on CAN{}", channel + 1))?;
+    monitor.running.store(false, Ordering::SeqCst);
+    Ok(format!("CANopen monitor stopped on CAN{}", channel + 1))
+}
+
+#[tauri::command]
+pub fn get_canopen_nodes(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<Vec<NodeStatus>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let key = app_state.device(dev_type.map(DeviceType::code), dev_index)?.key();
+    let monitor = app_state
+        .canopen_monitors
+        .get(&(key.0, key.1, channel))
+        .ok_or_else(|| format!("No CANopen monitor on CAN{}", channel + 1))?;
+    let nodes = monitor.nodes.lock().map_err(|_| "Failed to lock node table")?;
+    let mut table: Vec<NodeStatus> = nodes.values().cloned().collect();
+    table.sort_by_key(|s| s.node_id);
+    Ok(table)
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NmtCommand {
+    Start,
+    Stop,
+    EnterPreOperational,
+    ResetNode,
+    ResetCommunication,
+}
+
+impl NmtCommand {
+    fn specifier(self) -> u8 {
+        match self {
+            NmtCommand::Start => 0x01,
+            NmtCommand::Stop => 0x02,
+            NmtCommand::EnterPreOperational => 0x80,
+            NmtCommand::ResetNode => 0x81,
+            NmtCommand::ResetCommunication => 0x82,
+        }
+    }
+}
+
+/// 送出 NMT 指令；node_id 為 0 時對所有節點廣播
+#[tauri::command]
+pub async fn nmt_command(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    node_id: u8,
+    command: NmtCommand,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<String, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        if node_id > 127 {
+            return Err(format!("node_id {} out of range 0-127", node_id));
+        }
+        let mut can_obj = VciCanObj {
+            id: NMT_ID,
+            data_len: 2,
+            ..Default::default()
+        };
+        can_obj.data[..2].copy_from_slice(&[command.specifier(), node_id]);
+        let tx_path = {
+            let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+            app_state.tx_path(key, channel)?
+        };
+        tx_path.transmit(&[can_obj], true)?;
+        Ok(format!("NMT {:?} sent to node {}", command, node_id))
+    })
+    .await
+}
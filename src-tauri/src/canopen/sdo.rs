@@ -0,0 +1,287 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::tap::TapReceiver;
+use crate::{run_blocking, DeviceType, StateMutex, VciCanObj};
+
+const SDO_REQUEST_BASE: u32 = 0x600;
+const SDO_RESPONSE_BASE: u32 = 0x580;
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+/// 指令位元組的高 3 位元
+const CCS_DOWNLOAD_SEGMENT: u8 = 0;
+const CCS_INITIATE_DOWNLOAD: u8 = 1;
+const CCS_INITIATE_UPLOAD: u8 = 2;
+const CCS_UPLOAD_SEGMENT: u8 = 3;
+const SCS_UPLOAD_SEGMENT: u8 = 0;
+const SCS_DOWNLOAD_SEGMENT: u8 = 1;
+const SCS_INITIATE_UPLOAD: u8 = 2;
+const SCS_INITIATE_DOWNLOAD: u8 = 3;
+const CS_ABORT: u8 = 4;
+
+/// CiA 301 中止碼說明
+fn abort_description(code: u32) -> &'static str {
+    match code {
+        0x0503_0000 => "Toggle bit not alternated",
+        0x0504_0000 => "SDO protocol timed out",
+        0x0504_0001 => "Client/server command specifier not valid or unknown",
+        0x0504_0005 => "Out of memory",
+        0x0601_0000 => "Unsupported access to an object",
+        0x0601_0001 => "Attempt to read a write only object",
+        0x0601_0002 => "Attempt to write a read only object",
+        0x0602_0000 => "Object does not exist in the object dictionary",
+        0x0604_0041 => "Object cannot be mapped to the PDO",
+        0x0604_0042 => "The number and length of the objects to be mapped would exceed PDO length",
+        0x0604_0043 => "General parameter incompatibility reason",
+        0x0604_0047 => "General internal incompatibility in the device",
+        0x0606_0000 => "Access failed due to a hardware error",
+        0x0607_0010 => "Data type does not match, length of service parameter does not match",
+        0x0607_0012 => "Data type does not match, length of service parameter too high",
+        0x0607_0013 => "Data type does not match, length of service parameter too low",
+        0x0609_0011 => "Sub-index does not exist",
+        0x0609_0030 => "Invalid value for parameter",
+        0x0609_0031 => "Value of parameter written too high",
+        0x0609_0032 => "Value of parameter written too low",
+        0x0609_0036 => "Maximum value is less than minimum value",
+        0x060A_0023 => "Resource not available: SDO connection",
+        0x0800_0000 => "General error",
+        0x0800_0020 => "Data cannot be transferred or stored to the application",
+        0x0800_0021 => "Data cannot be transferred or stored to the application because of local control",
+        0x0800_0022 => "Data cannot be transferred or stored to the application because of the present device state",
+        0x0800_0023 => "Object dictionary dynamic generation fails or no object dictionary is present",
+        0x0800_0024 => "No data available",
+        _ => "Unknown abort code",
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SdoError {
+    Abort { code: u32, description: &'static str },
+    Timeout,
+    ToggleMismatch,
+    UnexpectedResponse { data: Vec<u8> },
+    Other { message: String },
+}
+
+impl fmt::Display for SdoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SdoError::Abort { code, description } => write!(f, "SDO abort 0x{:08X}: {}", code, description),
+            SdoError::Timeout => write!(f, "SDO response timed out"),
+            SdoError::ToggleMismatch => write!(f, "SDO toggle bit mismatch"),
+            SdoError::UnexpectedResponse { data } => write!(f, "unexpected SDO response {:02X?}", data),
+            SdoError::Other { message } => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for SdoError {
+    fn from(message: String) -> Self {
+        SdoError::Other { message }
+    }
+}
+
+impl From<&str> for SdoError {
+    fn from(message: &str) -> Self {
+        SdoError::Other { message: message.to_string() }
+    }
+}
+
+/// 與單一節點的 SDO 用戶端連線
+struct SdoClient {
+    state: Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    node_id: u8,
+    index: u16,
+    subindex: u8,
+    timeout: Duration,
+    tap: TapReceiver,
+}
+
+impl SdoClient {
+    fn open(
+        state: &Arc<StateMutex>,
+        dev_type: Option<DeviceType>,
+        dev_index: Option<u32>,
+        channel: u32,
+        node_id: u8,
+        index: u16,
+        subindex: u8,
+        timeout_ms: Option<u64>,
+    ) -> Result<Self, SdoError> {
+        if !(1..=127).contains(&node_id) {
+            return Err(format!("node_id {} out of range 1-127", node_id).into());
+        }
+        let key = {
+            let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key()
+        };
+        Ok(Self {
+            tap: TapReceiver::open(state, key, channel)?,
+            state: state.clone(),
+            key,
+            channel,
+            node_id,
+            index,
+            subindex,
+            timeout: Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS)),
+        })
+    }
+
+    fn multiplexer(&self) -> [u8; 3] {
+        let [low, high] = self.index.to_le_bytes();
+        [low, high, self.subindex]
+    }
+
+    /// 送出請求並等待節點回應；中止回應轉為 SdoError::Abort
+    fn exchange(&self, request: [u8; 8]) -> Result<[u8; 8], SdoError> {
+        let can_obj = VciCanObj {
+            id: SDO_REQUEST_BASE + self.node_id as u32,
+            data_len: 8,
+            data: request,
+            ..Default::default()
+        };
+        crate::transmit(&self.state, self.key, self.channel, &[can_obj])?;
+        let deadline = Instant::now() + self.timeout;
+        let response_id = SDO_RESPONSE_BASE + self.node_id as u32;
+        loop {
+            let frame: CanFrameEvent = self.tap.recv_until(deadline).ok_or(SdoError::Timeout)?;
+            if frame.id != response_id || frame.extended || frame.remote || frame.data.len() != 8 {
+                continue;
+            }
+            let mut response = [0u8; 8];
+            response.copy_from_slice(&frame.data);
+            if response[0] >> 5 == CS_ABORT {
+                let code = u32::from_le_bytes([response[4], response[5], response[6], response[7]]);
+                return Err(SdoError::Abort {
+                    code,
+                    description: abort_description(code),
+                });
+            }
+            return Ok(response);
+        }
+    }
+
+    fn expect_initiate(&self, response: &[u8; 8], scs: u8) -> Result<(), SdoError> {
+        if response[0] >> 5 != scs || response[1..4] != self.multiplexer() {
+            return Err(SdoError::UnexpectedResponse { data: response.to_vec() });
+        }
+        Ok(())
+    }
+
+    fn upload(&self) -> Result<Vec<u8>, SdoError> {
+        let [low, high, sub] = self.multiplexer();
+        let response = self.exchange([CCS_INITIATE_UPLOAD << 5, low, high, sub, 0, 0, 0, 0])?;
+        self.expect_initiate(&response, SCS_INITIATE_UPLOAD)?;
+        let expedited = response[0] & 0x02 != 0;
+        let size_indicated = response[0] & 0x01 != 0;
+        if expedited {
+            let unused = if size_indicated { ((response[0] >> 2) & 0x03) as usize } else { 0 };
+            return Ok(response[4..8 - unused].to_vec());
+        }
+
+        let size = size_indicated.then(|| u32::from_le_bytes([response[4], response[5], response[6], response[7]]) as usize);
+        let mut data = Vec::with_capacity(size.unwrap_or_default());
+        let mut toggle = 0u8;
+        loop {
+            let response = self.exchange([CCS_UPLOAD_SEGMENT << 5 | toggle << 4, 0, 0, 0, 0, 0, 0, 0])?;
+            if response[0] >> 5 != SCS_UPLOAD_SEGMENT {
+                return Err(SdoError::UnexpectedResponse { data: response.to_vec() });
+            }
+            if (response[0] >> 4) & 0x01 != toggle {
+                return Err(SdoError::ToggleMismatch);
+            }
+            let unused = ((response[0] >> 1) & 0x07) as usize;
+            data.extend_from_slice(&response[1..8 - unused]);
+            if response[0] & 0x01 != 0 {
+                break;
+            }
+            toggle ^= 1;
+        }
+        if let Some(size) = size {
+            data.truncate(size);
+        }
+        Ok(data)
+    }
+
+    fn download(&self, data: &[u8]) -> Result<(), SdoError> {
+        let [low, high, sub] = self.multiplexer();
+        if data.len() <= 4 {
+            // 快速傳輸：e = 1、s = 1，n 為未使用的位元組數
+            let mut request = [CCS_INITIATE_DOWNLOAD << 5 | ((4 - data.len() as u8) << 2) | 0x03, low, high, sub, 0, 0, 0, 0];
+            request[4..4 + data.len()].copy_from_slice(data);
+            let response = self.exchange(request)?;
+            return self.expect_initiate(&response, SCS_INITIATE_DOWNLOAD);
+        }
+
+        let size = u32::try_from(data.len()).map_err(|_| "data too large")?.to_le_bytes();
+        let response = self.exchange([CCS_INITIATE_DOWNLOAD << 5 | 0x01, low, high, sub, size[0], size[1], size[2], size[3]])?;
+        self.expect_initiate(&response, SCS_INITIATE_DOWNLOAD)?;
+        let mut toggle = 0u8;
+        let mut segments = data.chunks(7).peekable();
+        while let Some(segment) = segments.next() {
+            let last = segments.peek().is_none() as u8;
+            let unused = 7 - segment.len() as u8;
+            let mut request = [CCS_DOWNLOAD_SEGMENT << 5 | toggle << 4 | unused << 1 | last, 0, 0, 0, 0, 0, 0, 0];
+            request[1..1 + segment.len()].copy_from_slice(segment);
+            let response = self.exchange(request)?;
+            if response[0] >> 5 != SCS_DOWNLOAD_SEGMENT {
+                return Err(SdoError::UnexpectedResponse { data: response.to_vec() });
+            }
+            if (response[0] >> 4) & 0x01 != toggle {
+                return Err(SdoError::ToggleMismatch);
+            }
+            toggle ^= 1;
+        }
+        Ok(())
+    }
+}
+
+/// 讀取節點物件字典；大於 4 位元組時自動使用分段傳輸
+#[tauri::command]
+pub async fn sdo_upload(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    node_id: u8,
+    index: u16,
+    subindex: u8,
+    timeout_ms: Option<u64>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<u8>, SdoError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        SdoClient::open(&state, dev_type, dev_index, channel, node_id, index, subindex, timeout_ms)?.upload()
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn sdo_download(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    node_id: u8,
+    index: u16,
+    subindex: u8,
+    data: Vec<u8>,
+    timeout_ms: Option<u64>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<String, SdoError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        if data.is_empty() {
+            return Err("data must not be empty".into());
+        }
+        SdoClient::open(&state, dev_type, dev_index, channel, node_id, index, subindex, timeout_ms)?.download(&data)?;
+        Ok(format!("Wrote {} bytes to 0x{:04X}:{:02X} on node {}", data.len(), index, subindex, node_id))
+    })
+    .await
+}
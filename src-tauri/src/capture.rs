@@ -0,0 +1,342 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::{CanFrameEvent, Direction};
+use crate::logging::{self, LogFormat, LoggedFrame};
+use crate::memory::{self, CapWarning, SubsystemMemory};
+use crate::trigger::TriggerEvent;
+use crate::StateMutex;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureState {
+    /// 等待觸發，持續更新觸發前緩衝
+    Armed,
+    /// 已觸發，正在收集觸發後的訊框
+    Recording,
+    Complete,
+}
+
+/// 一次觸發擷取：觸發前的訊框、觸發訊框本身，以及其後 post_frames 個訊框
+pub struct Capture {
+    capture_id: u32,
+    trigger_id: u32,
+    channel: u32,
+    pre_frames: usize,
+    /// 觸發前緩衝只保留這段時間內的訊框
+    pre_ms: Option<u64>,
+    post_frames: usize,
+    state: CaptureState,
+    pre: VecDeque<CanFrameEvent>,
+    trigger_frame: Option<CanFrameEvent>,
+    post: Vec<CanFrameEvent>,
+    /// 保存的訊框以 memory::frame_bytes 估計的大小
+    bytes: usize,
+    /// 因擷取儲存空間上限而捨棄了觸發前的訊框或提早結束
+    truncated: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CaptureInfo {
+    pub capture_id: u32,
+    pub trigger_id: u32,
+    pub channel: u32,
+    pub state: CaptureState,
+    pub pre_count: usize,
+    pub post_count: usize,
+    pub truncated: bool,
+}
+
+#[derive(Serialize)]
+pub struct CaptureData {
+    #[serde(flatten)]
+    pub info: CaptureInfo,
+    /// 觸發訊框在 frames 中的索引
+    pub trigger_index: Option<usize>,
+    pub frames: Vec<CanFrameEvent>,
+}
+
+impl Capture {
+    fn info(&self) -> CaptureInfo {
+        CaptureInfo {
+            capture_id: self.capture_id,
+            trigger_id: self.trigger_id,
+            channel: self.channel,
+            state: self.state,
+            pre_count: self.pre.len(),
+            post_count: self.post.len(),
+            truncated: self.truncated,
+        }
+    }
+
+    fn frames(&self) -> Vec<CanFrameEvent> {
+        self.pre
+            .iter()
+            .chain(self.trigger_frame.iter())
+            .chain(self.post.iter())
+            .cloned()
+            .collect()
+    }
+
+    fn push_pre(&mut self, frame: &CanFrameEvent) {
+        if self.pre_frames == 0 {
+            return;
+        }
+        while self.pre.len() >= self.pre_frames {
+            self.pop_pre();
+        }
+        self.bytes += memory::frame_bytes(frame);
+        self.pre.push_back(frame.clone());
+        if let Some(pre_ms) = self.pre_ms {
+            let oldest_us = frame.host_timestamp_us.saturating_sub(pre_ms * 1000);
+            while self.pre.front().is_some_and(|f| f.host_timestamp_us < oldest_us) {
+                self.pop_pre();
+            }
+        }
+    }
+
+    fn pop_pre(&mut self) -> bool {
+        let Some(frame) = self.pre.pop_front() else {
+            return false;
+        };
+        self.bytes = self.bytes.saturating_sub(memory::frame_bytes(&frame));
+        true
+    }
+
+    /// 依序處理一批訊框；剛完成時回傳 true
+    fn process(&mut self, frames: &[CanFrameEvent], trigger_events: &[TriggerEvent]) -> bool {
+        for (index, frame) in frames.iter().enumerate() {
+            match self.state {
+                CaptureState::Armed => {
+                    let fired = trigger_events
+                        .iter()
+                        .any(|e| e.trigger_id == self.trigger_id && e.frame_index == index);
+                    if fired {
+                        self.bytes += memory::frame_bytes(frame);
+                        self.trigger_frame = Some(frame.clone());
+                        self.state = CaptureState::Recording;
+                    } else {
+                        self.push_pre(frame);
+                    }
+                }
+                CaptureState::Recording => {
+                    self.bytes += memory::frame_bytes(frame);
+                    self.post.push(frame.clone());
+                }
+                CaptureState::Complete => return false,
+            }
+            if self.state == CaptureState::Recording && self.post.len() >= self.post_frames {
+                self.state = CaptureState::Complete;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// 所有觸發擷取；完成的擷取保留到 discard_capture 為止。
+/// 合計超過 max_bytes 時先捨棄待觸發擷取最舊的觸發前訊框，仍超過時提早結束錄製中的擷取
+pub struct Captures {
+    captures: Vec<Capture>,
+    next_id: u32,
+    max_bytes: usize,
+    warning: CapWarning,
+    /// 調降上限時提早結束的擷取，在下一批訊框送出 capture-complete
+    truncated: Vec<CaptureInfo>,
+}
+
+impl Default for Captures {
+    fn default() -> Self {
+        Self {
+            captures: Vec::new(),
+            next_id: 0,
+            max_bytes: memory::DEFAULT_MAX_CAPTURE_BYTES,
+            warning: CapWarning::default(),
+            truncated: Vec::new(),
+        }
+    }
+}
+
+impl Captures {
+    /// 在接收迴圈中呼叫 (觸發器求值之後)，回傳本批次完成的擷取
+    pub fn process(&mut self, channel: u32, frames: &[CanFrameEvent], trigger_events: &[TriggerEvent]) -> Vec<CaptureInfo> {
+        let mut completed: Vec<CaptureInfo> = self
+            .captures
+            .iter_mut()
+            .filter(|c| c.channel == channel && c.state != CaptureState::Complete)
+            .filter_map(|c| c.process(frames, trigger_events).then(|| c.info()))
+            .collect();
+        completed.append(&mut self.truncated);
+        completed.extend(self.enforce_max_bytes());
+        completed
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        let mut truncated = self.enforce_max_bytes();
+        self.truncated.append(&mut truncated);
+    }
+
+    fn bytes(&self) -> usize {
+        self.captures.iter().map(|c| c.bytes).sum()
+    }
+
+    /// 回傳因超過上限而提早結束的擷取
+    fn enforce_max_bytes(&mut self) -> Vec<CaptureInfo> {
+        let mut over = self.bytes().saturating_sub(self.max_bytes);
+        if over == 0 {
+            return Vec::new();
+        }
+        let mut evicted = 0u64;
+        for capture in self.captures.iter_mut().filter(|c| c.state == CaptureState::Armed) {
+            while over > 0 {
+                let before = capture.bytes;
+                if !capture.pop_pre() {
+                    break;
+                }
+                over = over.saturating_sub(before - capture.bytes);
+                capture.truncated = true;
+                evicted += 1;
+            }
+        }
+        let mut completed = Vec::new();
+        for capture in self.captures.iter_mut().filter(|c| c.state == CaptureState::Recording) {
+            if over == 0 {
+                break;
+            }
+            capture.state = CaptureState::Complete;
+            capture.truncated = true;
+            over = over.saturating_sub(capture.bytes);
+            completed.push(capture.info());
+        }
+        self.warning.evicted("captures", evicted + completed.len() as u64, self.max_bytes);
+        completed
+    }
+
+    pub fn memory(&self) -> SubsystemMemory {
+        SubsystemMemory {
+            entries: self.captures.iter().map(|c| c.pre.len() + c.trigger_frame.iter().count() + c.post.len()).sum(),
+            bytes: self.bytes(),
+            limit: self.max_bytes,
+            evictions: self.warning.evictions(),
+        }
+    }
+
+    fn get(&self, capture_id: u32) -> Result<&Capture, String> {
+        self.captures
+            .iter()
+            .find(|c| c.capture_id == capture_id)
+            .ok_or_else(|| format!("capture {} not found", capture_id))
+    }
+
+    /// 擷取目前收集到的訊框；仍在進行中的擷取也可讀取
+    pub(crate) fn frames_of(&self, capture_id: u32) -> Result<Vec<CanFrameEvent>, String> {
+        self.get(capture_id).map(Capture::frames)
+    }
+}
+
+fn captures(state: &State<Arc<StateMutex>>) -> Result<Arc<Mutex<Captures>>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    Ok(app_state.captures.clone())
+}
+
+/// 針對觸發器建立擷取；觸發時凍結前 pre_frames 個訊框並再收集 post_frames 個，完成後發出 capture-complete
+#[tauri::command]
+pub fn arm_capture(
+    trigger_id: u32,
+    pre_frames: usize,
+    post_frames: usize,
+    pre_ms: Option<u64>,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    let (triggers, captures) = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        (app_state.triggers.clone(), app_state.captures.clone())
+    };
+    let channel = triggers
+        .lock()
+        .map_err(|_| "Failed to lock triggers")?
+        .channel_of(trigger_id)
+        .ok_or_else(|| format!("trigger {} not found", trigger_id))?;
+    let mut captures = captures.lock().map_err(|_| "Failed to lock captures")?;
+    captures.next_id += 1;
+    let capture_id = captures.next_id;
+    captures.captures.push(Capture {
+        capture_id,
+        trigger_id,
+        channel,
+        pre_frames,
+        pre_ms,
+        post_frames,
+        state: CaptureState::Armed,
+        pre: VecDeque::new(),
+        trigger_frame: None,
+        post: Vec::new(),
+        bytes: 0,
+        truncated: false,
+    });
+    Ok(capture_id)
+}
+
+#[tauri::command]
+pub fn get_capture(capture_id: u32, state: State<Arc<StateMutex>>) -> Result<CaptureData, String> {
+    let captures = captures(&state)?;
+    let captures = captures.lock().map_err(|_| "Failed to lock captures")?;
+    let capture = captures.get(capture_id)?;
+    Ok(CaptureData {
+        info: capture.info(),
+        trigger_index: capture.trigger_frame.as_ref().map(|_| capture.pre.len()),
+        frames: capture.frames(),
+    })
+}
+
+#[tauri::command]
+pub fn list_captures(state: State<Arc<StateMutex>>) -> Result<Vec<CaptureInfo>, String> {
+    let captures = captures(&state)?;
+    let captures = captures.lock().map_err(|_| "Failed to lock captures")?;
+    Ok(captures.captures.iter().map(Capture::info).collect())
+}
+
+/// 以記錄格式匯出擷取內容，回傳寫入的訊框數
+#[tauri::command]
+pub fn export_capture(
+    capture_id: u32,
+    path: String,
+    format: Option<LogFormat>,
+    state: State<Arc<StateMutex>>,
+) -> Result<u64, String> {
+    let frames: Vec<LoggedFrame> = {
+        let captures = captures(&state)?;
+        let captures = captures.lock().map_err(|_| "Failed to lock captures")?;
+        captures
+            .get(capture_id)?
+            .frames()
+            .into_iter()
+            .map(|frame| LoggedFrame { direction: Direction::Rx, frame })
+            .collect()
+    };
+    let (configs, annotations) = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        (app_state.configs_of(&frames), app_state.annotations_within(&frames))
+    };
+    let path = PathBuf::from(path);
+    logging::write_log_file(&path, format.unwrap_or(LogFormat::Csv), &frames, &annotations, &configs)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[tauri::command]
+pub fn discard_capture(capture_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let captures = captures(&state)?;
+    let mut captures = captures.lock().map_err(|_| "Failed to lock captures")?;
+    captures.get(capture_id)?;
+    captures.captures.retain(|c| c.capture_id != capture_id);
+    Ok(format!("capture {} discarded", capture_id))
+}
@@ -0,0 +1,328 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tauri::Emitter;
+
+use crate::bus_quality::BusQualityConfig;
+use crate::busoff::BusOffRecovery;
+use crate::config_history::ConfigHistory;
+use crate::dbc::Dbc;
+use crate::dedupe::Dedupe;
+use crate::frame::{self, CanFrameEvent, Direction, Provenance, TxTiming};
+use crate::id_names::{self, IdNames};
+use crate::logging::LogSink;
+use crate::receive::{self, EmissionControl};
+use crate::ring_buffer::{BufferedFrame, FrameRing};
+use crate::stats::{ChannelCounters, IdStatistics};
+use crate::timestamp::ClockStatus;
+use crate::tx_limit::{TxRateLimiter, DEFAULT_CHUNK_FRAMES};
+use crate::mqtt::MqttFeed;
+use crate::ws_bridge::WsHub;
+use crate::{AppState, CanInterface, StateMutex, VciCanObj};
+
+/// 單一通道的執行期狀態。計數器為 atomic，速率限制各自加鎖，
+/// 傳送與接收路徑各持一份 Arc，讀取統計或調整設定不必等待 AppState 鎖
+#[derive(Default)]
+pub struct ChannelRuntime {
+    pub counters: Arc<ChannelCounters>,
+    pub emission: Arc<EmissionControl>,
+    pub tx_limit: Mutex<Option<TxRateLimiter>>,
+    pub busoff_recovery: Mutex<BusOffRecovery>,
+    /// 接收執行緒每一輪讀取，set_bus_quality_threshold 的設定不必重新啟動接收
+    pub bus_quality: Arc<Mutex<BusQualityConfig>>,
+    /// busoff::PHASE_* 之一
+    pub recovery_phase: AtomicU8,
+    /// 每次 VCI_Transmit 的訊框數上限，0 表示預設值
+    pub tx_chunk_frames: AtomicU32,
+    /// set_timestamp_mode 的設定與接收執行緒估計的時鐘偏移
+    pub clock: Arc<Mutex<ClockStatus>>,
+    /// 下一個送出訊框的傳送序號
+    pub tx_seq: AtomicU64,
+    /// set_dedupe 的設定，None 表示不抑制重複訊框
+    pub dedupe: Arc<Mutex<Option<Dedupe>>>,
+    /// set_tx_inhibit 的設定；為 true 時 try_transmit 拒絕所有傳送
+    pub tx_inhibited: Arc<AtomicBool>,
+    /// apply_channel_change 重新初始化通道期間為 true，週期訊框略過這段時間的週期
+    pub reconfiguring: AtomicBool,
+    /// 每次初始化通道時開始新的世代，收發的訊框標記當時的世代
+    pub config_history: Arc<ConfigHistory>,
+}
+
+impl ChannelRuntime {
+    pub fn chunk_frames(&self) -> usize {
+        match self.tx_chunk_frames.load(Ordering::Relaxed) {
+            0 => DEFAULT_CHUNK_FRAMES as usize,
+            frames => frames as usize,
+        }
+    }
+}
+
+/// 傳送一個通道所需的共用狀態。在 state 鎖內取出後即可放開鎖，呼叫 VCI_Transmit 期間不阻擋其他命令
+pub(crate) struct TxPath {
+    key: (u32, u32),
+    channel: u32,
+    can_lib: Arc<dyn CanInterface>,
+    pub runtime: Arc<ChannelRuntime>,
+    id_names: Arc<Mutex<Option<Arc<IdNames>>>>,
+    log_sink: Arc<Mutex<Option<LogSink>>>,
+    dbc: Arc<Mutex<Option<Arc<Dbc>>>>,
+    id_statistics: Arc<Mutex<IdStatistics>>,
+    frame_buffer: Arc<Mutex<FrameRing>>,
+    ws_hub: Arc<WsHub>,
+    mqtt_feed: Arc<MqttFeed>,
+    app_handle: Option<tauri::AppHandle>,
+    /// 送出的訊框標記的來源，預設為 tx-manual
+    provenance: Provenance,
+}
+
+impl AppState {
+    pub(crate) fn channel_runtime(&mut self, key: (u32, u32), channel: u32) -> Arc<ChannelRuntime> {
+        self.channel_runtime.entry((key.0, key.1, channel)).or_default().clone()
+    }
+
+    /// 裝置重新開啟在另一個 index 時，把各通道的執行期狀態 (禁止傳送、速率限制、dedupe 等) 搬到新的 key。
+    /// 新 key 上殘留的是之前在那裡的另一個轉接器，一併丟棄
+    pub(crate) fn move_channel_runtime(&mut self, from: (u32, u32), to: (u32, u32)) {
+        if from == to {
+            return;
+        }
+        self.channel_runtime.retain(|&(dev_type, dev_index, _), _| (dev_type, dev_index) != to);
+        let moved: Vec<(u32, u32, u32)> = self
+            .channel_runtime
+            .keys()
+            .filter(|&&(dev_type, dev_index, _)| (dev_type, dev_index) == from)
+            .copied()
+            .collect();
+        for entry in moved {
+            if let Some(runtime) = self.channel_runtime.remove(&entry) {
+                self.channel_runtime.insert((to.0, to.1, entry.2), runtime);
+            }
+        }
+    }
+
+    /// 檢查通道後取出傳送路徑
+    pub(crate) fn tx_path(&mut self, key: (u32, u32), channel: u32) -> Result<TxPath, String> {
+        let can_lib = self.backend().ok_or("CAN 裝置尚未初始化")?;
+        if let Some(device) = self.devices.get(&key) {
+            device.check_channel(channel)?;
+        }
+        Ok(TxPath {
+            key,
+            channel,
+            can_lib,
+            runtime: self.channel_runtime(key, channel),
+            id_names: self.id_names.clone(),
+            log_sink: self.log_sink.clone(),
+            dbc: self.dbc.clone(),
+            id_statistics: self.id_statistics.clone(),
+            frame_buffer: self.frame_buffer.clone(),
+            ws_hub: self.ws_hub.clone(),
+            mqtt_feed: self.mqtt_feed.clone(),
+            app_handle: self.app_handle.clone(),
+            provenance: Provenance::TxManual,
+        })
+    }
+}
+
+/// 在 state 鎖下取出傳送路徑，放開鎖後才呼叫 VCI_Transmit，等待驅動期間不阻擋其他命令。回傳實際送出的訊框數
+pub fn transmit(state: &StateMutex, key: (u32, u32), channel: u32, frames: &[VciCanObj]) -> Result<u32, String> {
+    let tx_path = state.lock().map_err(|_| "Failed to lock state")?.tx_path(key, channel)?;
+    tx_path.transmit(frames, true)
+}
+
+impl TxPath {
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// 呼叫一次 VCI_Transmit，回傳每個送出訊框的傳送序號與呼叫時間。回傳空陣列表示傳送緩衝暫時已滿、
+    /// 呼叫端可以重試；驅動回報的錯誤碼 (例如 -1) 直接回傳 Err，不應重試
+    pub fn try_transmit(&self, frames: &[VciCanObj], echo: bool) -> Result<Vec<TxTiming>, String> {
+        for can_obj in frames {
+            frame::check_id(can_obj.id, can_obj.extern_flag != 0)?;
+        }
+        let (key, channel) = (self.key, self.channel);
+        let counters = &self.runtime.counters;
+        // 所有傳送路徑都經過這裡，禁止傳送只在此檢查
+        if self.runtime.tx_inhibited.load(Ordering::SeqCst) {
+            counters.tx_refused.fetch_add(frames.len() as u64, Ordering::Relaxed);
+            return Err(crate::tx_inhibited(channel));
+        }
+        let call_start_us = frame::monotonic_us();
+        let result = self.can_lib.transmit(key.0, key.1, channel, frames);
+        let call_end_us = frame::monotonic_us();
+        let sent = match result {
+            Ok(0) => return Ok(Vec::new()),
+            Ok(sent) => sent,
+            Err(code) => {
+                counters.errors.fetch_add(1, Ordering::Relaxed);
+                return Err(format!("VCI_Transmit failed ({})", code));
+            }
+        };
+        let sent = (sent as usize).min(frames.len());
+        let first_seq = self.runtime.tx_seq.fetch_add(sent as u64, Ordering::Relaxed);
+        let timings: Vec<TxTiming> = (0..sent as u64)
+            .map(|offset| TxTiming {
+                tx_seq: first_seq + offset,
+                call_start_us,
+                call_end_us,
+            })
+            .collect();
+        counters.tx_frames.fetch_add(sent as u64, Ordering::Relaxed);
+        if let Some(limiter) = self.runtime.tx_limit.lock().map_err(|_| "Failed to lock rate limiter")?.as_mut() {
+            limiter.consume(sent);
+        }
+        let host_timestamp_us = frame::host_timestamp_us();
+        let mut tx_frames: Vec<CanFrameEvent> = frames[..sent]
+            .iter()
+            .zip(&timings)
+            .map(|(can_obj, &timing)| {
+                counters.add_bus_frame(can_obj.extern_flag != 0, can_obj.remote_flag != 0, can_obj.data_len);
+                CanFrameEvent {
+                    provenance: self.provenance,
+                    config_generation: self.runtime.config_history.current(),
+                    tx_timing: Some(timing),
+                    ..CanFrameEvent::from_raw(key, channel, can_obj, host_timestamp_us).with_direction(Direction::Tx)
+                }
+            })
+            .collect();
+        id_names::annotate(&self.id_names, &mut tx_frames);
+        if let Some(sink) = self.log_sink.lock().map_err(|_| "Failed to lock log sink")?.as_ref() {
+            for frame in &tx_frames {
+                sink.log(Direction::Tx, frame);
+            }
+        }
+        if echo {
+            self.echo(tx_frames);
+        }
+        Ok(timings)
+    }
+
+    /// 同 try_transmit()，傳送緩衝已滿也視為失敗
+    pub fn transmit_timed(&self, frames: &[VciCanObj], echo: bool) -> Result<Vec<TxTiming>, String> {
+        let timings = self.try_transmit(frames, echo)?;
+        if timings.is_empty() {
+            self.runtime.counters.errors.fetch_add(1, Ordering::Relaxed);
+            return Err("傳送 CAN 數據失敗".to_string());
+        }
+        Ok(timings)
+    }
+
+    /// 同 transmit_timed()，只回傳送出的訊框數
+    pub fn transmit(&self, frames: &[VciCanObj], echo: bool) -> Result<u32, String> {
+        self.transmit_timed(frames, echo).map(|timings| timings.len() as u32)
+    }
+
+    /// 把送出的訊框放進與接收相同的環形緩衝、ID 統計與 can-data 事件流
+    fn echo(&self, mut frames: Vec<CanFrameEvent>) {
+        let emission = &self.runtime.emission;
+        if !emission.tx_echo_enabled() {
+            return;
+        }
+        receive::decode_frames(&self.dbc, &mut frames);
+        if let Ok(mut stats) = self.id_statistics.lock() {
+            for frame in &frames {
+                stats.record(frame);
+            }
+        }
+        let buffered: Vec<BufferedFrame> = {
+            let Ok(mut ring) = self.frame_buffer.lock() else {
+                return;
+            };
+            frames
+                .into_iter()
+                .map(|frame| BufferedFrame {
+                    seq: ring.push(frame.clone()),
+                    frame,
+                })
+                .collect()
+        };
+        self.ws_hub.broadcast(&buffered);
+        self.mqtt_feed.publish(&buffered);
+        if let Some(app_handle) = self.app_handle.as_ref().filter(|_| !emission.is_paused()) {
+            for frame in buffered {
+                let _ = app_handle.emit("can-data", frame);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::VciInitConfig;
+
+    const KEY: (u32, u32) = (4, 0);
+
+    fn started() -> (Arc<MockCan>, AppState) {
+        let mock = Arc::new(MockCan::new());
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(KEY.0, KEY.1, None).unwrap();
+        app_state.devices.get_mut(&KEY).unwrap().channel_count = Some(2);
+        let config = VciInitConfig {
+            acc_mask: 0xFFFF_FFFF,
+            timing1: 0x1C,
+            ..Default::default()
+        };
+        app_state.start_channel(KEY, 0, config).unwrap();
+        (mock, app_state)
+    }
+
+    fn can_obj(id: u32) -> VciCanObj {
+        VciCanObj {
+            id,
+            data_len: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn each_sent_frame_gets_the_next_sequence_number_and_the_call_time() {
+        let (mock, mut app_state) = started();
+        let tx_path = app_state.tx_path(KEY, 0).unwrap();
+        let first = tx_path.try_transmit(&[can_obj(0x100), can_obj(0x101)], false).unwrap();
+        let second = tx_path.transmit_timed(&[can_obj(0x102)], false).unwrap();
+
+        let seqs: Vec<u64> = first.iter().chain(&second).map(|timing| timing.tx_seq).collect();
+        assert_eq!(seqs, [0, 1, 2]);
+        assert_eq!((first[0].call_start_us, first[0].call_end_us), (first[1].call_start_us, first[1].call_end_us));
+        assert!(first[0].call_start_us <= first[0].call_end_us && first[0].call_end_us <= second[0].call_start_us);
+        assert_eq!(mock.transmitted().len(), 3);
+        assert_eq!(tx_path.runtime.counters.tx_frames.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn a_full_buffer_or_driver_error_sends_nothing_and_uses_no_sequence_number() {
+        let (mock, mut app_state) = started();
+        let tx_path = app_state.tx_path(KEY, 0).unwrap();
+        mock.set_busy_transmits(2);
+        assert!(tx_path.try_transmit(&[can_obj(0x100)], false).unwrap().is_empty());
+        assert!(tx_path.transmit_timed(&[can_obj(0x100)], false).is_err());
+        mock.set_transmit_error(Some(-1));
+        assert!(tx_path.try_transmit(&[can_obj(0x100)], false).is_err());
+        mock.set_transmit_error(None);
+
+        assert_eq!(tx_path.runtime.counters.errors.load(Ordering::Relaxed), 2);
+        assert_eq!(tx_path.runtime.tx_seq.load(Ordering::Relaxed), 0);
+        assert_eq!(tx_path.try_transmit(&[can_obj(0x100)], false).unwrap()[0].tx_seq, 0);
+    }
+
+    #[test]
+    fn invalid_ids_and_channels_are_rejected_before_the_driver_is_called() {
+        let (mock, mut app_state) = started();
+        let tx_path = app_state.tx_path(KEY, 0).unwrap();
+        assert!(tx_path.try_transmit(&[can_obj(0x100), can_obj(0x800)], false).is_err());
+        let extended = VciCanObj {
+            extern_flag: 1,
+            ..can_obj(0x2000_0000)
+        };
+        assert!(tx_path.try_transmit(&[extended], false).is_err());
+        assert!(mock.transmitted().is_empty());
+
+        let error = app_state.tx_path(KEY, 2).err().unwrap();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        assert!(AppState::default().tx_path(KEY, 0).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{baud, run_blocking, spawn_receive_loop, AppState, ChannelConfig, DeviceType, EventSink, StateMutex, VciInitConfig};
+
+/// 一個設定欄位目前與要求的值
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigChange {
+    pub field: &'static str,
+    /// 通道尚未初始化時為 None
+    pub current: Option<u32>,
+    pub requested: u32,
+}
+
+/// plan_channel_change 的結果：設定差異與套用時的副作用
+#[derive(Serialize, Clone, Debug)]
+pub struct ChannelChangePlan {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub changes: Vec<ConfigChange>,
+    /// 沒有任何變更時 apply_channel_change 不會重新初始化通道
+    pub reinit_required: bool,
+    /// 通道正在接收，套用時接收執行緒會停止並以相同選項重新啟動
+    pub stream_restart_required: bool,
+    /// 重新初始化期間暫停、之後依原本時間表繼續的週期任務
+    pub paused_periodic_tasks: Vec<u32>,
+    /// 通道上進行中的重播會被停止
+    pub replay_aborts: bool,
+}
+
+fn config_fields(config: &VciInitConfig, started: bool) -> [(&'static str, u32); 8] {
+    [
+        ("timing0", config.timing0 as u32),
+        ("timing1", config.timing1 as u32),
+        ("bitrate", baud::bitrate_from_timing(config.timing0, config.timing1)),
+        ("mode", config.mode as u32),
+        ("acc_code", config.acc_code),
+        ("acc_mask", config.acc_mask),
+        ("filter", config.filter as u32),
+        ("started", started as u32),
+    ]
+}
+
+impl AppState {
+    /// 比較通道目前的設定與 config，列出套用時會受影響的接收串流、週期任務與重播
+    pub fn plan_channel_change(
+        &mut self,
+        dev_type: Option<u32>,
+        dev_index: Option<u32>,
+        config: &ChannelConfig,
+    ) -> Result<ChannelChangePlan, String> {
+        let requested = config_fields(&config.init_config()?, config.start.unwrap_or(true));
+        let channel = config.channel;
+        let device = self.connected_device(dev_type, dev_index)?;
+        device.check_channel(channel)?;
+        let key = device.key();
+        let current = device.channels.get(&channel).map(|state| config_fields(&state.config, state.started));
+        let changes: Vec<ConfigChange> = requested
+            .iter()
+            .enumerate()
+            .filter(|&(i, &(_, value))| current.is_none_or(|current| current[i].1 != value))
+            .map(|(i, &(field, requested))| ConfigChange {
+                field,
+                current: current.map(|current| current[i].1),
+                requested,
+            })
+            .collect();
+        let reinit_required = !changes.is_empty();
+        let stream_restart_required =
+            reinit_required && device.receiving.get(&channel).is_some_and(|receiving| receiving.load(Ordering::SeqCst));
+        let mut paused_periodic_tasks: Vec<u32> = self
+            .periodic_tasks
+            .iter()
+            .filter(|(_, task)| reinit_required && task.key() == key && task.channel() == channel)
+            .map(|(&task_id, _)| task_id)
+            .collect();
+        paused_periodic_tasks.sort_unstable();
+        Ok(ChannelChangePlan {
+            dev_type: key.0,
+            dev_index: key.1,
+            channel,
+            changes,
+            reinit_required,
+            stream_restart_required,
+            paused_periodic_tasks,
+            replay_aborts: reinit_required
+                && self.replay.as_ref().is_some_and(|replay| replay.key == key && replay.channel == channel),
+        })
+    }
+}
+
+/// 依 plan_channel_change 的計畫套用設定：暫停週期任務、停止重播與接收串流、重新 init/start，
+/// 再以原本的選項重新啟動接收並恢復週期任務，最後送出 channel-changed。沒有變更時不做任何事
+pub fn change_channel<E: EventSink + Clone>(
+    state: &Arc<StateMutex>,
+    events: E,
+    dev_type: Option<u32>,
+    dev_index: Option<u32>,
+    config: &ChannelConfig,
+) -> Result<ChannelChangePlan, String> {
+    let requested = config.init_config()?;
+    let channel = config.channel;
+    let (plan, runtime, previous, restart) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let plan = app_state.plan_channel_change(dev_type, dev_index, config)?;
+        if !plan.reinit_required {
+            return Ok(plan);
+        }
+        let key = (plan.dev_type, plan.dev_index);
+        let runtime = app_state.channel_runtime(key, channel);
+        runtime.reconfiguring.store(true, Ordering::SeqCst);
+        if plan.replay_aborts {
+            if let Some(replay) = app_state.replay.take() {
+                replay.running.store(false, Ordering::SeqCst);
+            }
+        }
+        let device = app_state.devices.get_mut(&key).expect("device key resolved by the plan");
+        let restart = plan
+            .stream_restart_required
+            .then(|| device.receive_options.get(&channel).copied())
+            .flatten();
+        if let Some(receiving) = device.receiving.get(&channel) {
+            receiving.store(false, Ordering::SeqCst);
+        }
+        (plan, runtime, device.handle.take_thread(channel), restart)
+    };
+    // 在鎖外等接收執行緒結束，重新初始化時沒有執行緒在讀取這個通道
+    if let Some(previous) = previous {
+        let _ = previous.join();
+    }
+    let key = (plan.dev_type, plan.dev_index);
+    let result = state.lock().map_err(|_| "Failed to lock state".to_string()).and_then(|mut app_state| {
+        let result = match config.start.unwrap_or(true) {
+            true => app_state.start_channel(key, channel, requested),
+            false => app_state.init_channel(key, channel, requested),
+        };
+        app_state.save_settings(key);
+        result
+    });
+    let result = result.and_then(|_| match restart {
+        Some(options) => {
+            let (_, handle) = spawn_receive_loop(state, events.clone(), Some(key.0), Some(key.1), channel, options)
+                .map_err(|error_message| format!("CAN{} reinitialized but restarting its receive stream failed: {}", channel + 1, error_message))?;
+            if let Some(device) = state.lock().map_err(|_| "Failed to lock state")?.devices.get_mut(&key) {
+                device.register_receive_thread(channel, handle);
+            }
+            Ok(())
+        }
+        None => Ok(()),
+    });
+    runtime.reconfiguring.store(false, Ordering::SeqCst);
+    match result {
+        Ok(()) => {
+            events.emit_event("channel-changed", plan.clone());
+            Ok(plan)
+        }
+        Err(error_message) if plan.stream_restart_required => {
+            Err(format!("{}; the CAN{} receive stream is stopped", error_message, channel + 1))
+        }
+        Err(error_message) => Err(error_message),
+    }
+}
+
+/// 列出把通道改成 config 的設定差異與副作用 (接收重新啟動、暫停的週期任務、中止的重播)，不做任何變更
+#[tauri::command]
+pub fn plan_channel_change(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    config: ChannelConfig,
+    state: State<Arc<StateMutex>>,
+) -> Result<ChannelChangePlan, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state.plan_channel_change(dev_type.map(DeviceType::code), dev_index, &config)
+}
+
+/// 依序暫停週期任務、停止接收、重新 init/start 並恢復，見 change_channel
+#[tauri::command]
+pub async fn apply_channel_change(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    config: ChannelConfig,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<ChannelChangePlan, String> {
+    let state = state.inner().clone();
+    run_blocking(move || change_channel(&state, app_handle, dev_type.map(DeviceType::code), dev_index, &config)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::mock::MockCan;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl EventSink for Recorder {
+        fn emit_event<S: Serialize + Clone>(&self, event: &str, _payload: S) -> bool {
+            self.0.lock().unwrap().push(event.to_string());
+            true
+        }
+    }
+
+    fn config(value: serde_json::Value) -> ChannelConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn opened() -> (Arc<MockCan>, Arc<StateMutex>) {
+        let mock = Arc::new(MockCan::new());
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(4, 0, None).unwrap();
+        app_state.devices.get_mut(&(4, 0)).unwrap().channel_count = Some(2);
+        (mock, Arc::new(StateMutex::new(app_state)))
+    }
+
+    #[test]
+    fn a_new_channel_differs_in_every_field_and_a_repeat_in_none() {
+        let (mock, state) = opened();
+        let events = Recorder::default();
+        let requested = config(serde_json::json!({ "channel": 1, "baud": 500000 }));
+        let plan = state.lock().unwrap().plan_channel_change(None, None, &requested).unwrap();
+        assert!(plan.reinit_required);
+        assert_eq!(plan.changes.len(), 8);
+        assert!(plan.changes.iter().all(|change| change.current.is_none()));
+        assert_eq!(plan.changes.iter().find(|change| change.field == "bitrate").unwrap().requested, 500000);
+
+        change_channel(&state, events.clone(), None, None, &requested).unwrap();
+        assert!(mock.is_started(4, 0, 1));
+        let repeat = change_channel(&state, events.clone(), None, None, &requested).unwrap();
+        assert!(!repeat.reinit_required && repeat.changes.is_empty());
+        // 沒有變更時不重新初始化，也不送出 channel-changed
+        assert_eq!(*events.0.lock().unwrap(), ["channel-changed"]);
+    }
+
+    #[test]
+    fn changing_one_filter_bit_lists_only_that_field() {
+        let (_mock, state) = opened();
+        let requested = config(serde_json::json!({ "channel": 0, "baud": 250000 }));
+        change_channel(&state, Recorder::default(), None, None, &requested).unwrap();
+        let filtered = config(serde_json::json!({
+            "channel": 0,
+            "baud": 250000,
+            "filter": { "acc_code": 0, "acc_mask": 0xFFFF_FFFEu32, "filter": 1 },
+        }));
+        let plan = state.lock().unwrap().plan_channel_change(None, None, &filtered).unwrap();
+        let changes: Vec<_> = plan.changes.iter().map(|change| (change.field, change.current, change.requested)).collect();
+        assert_eq!(changes, [("acc_mask", Some(0xFFFF_FFFF), 0xFFFF_FFFE)]);
+        assert!(!plan.stream_restart_required && !plan.replay_aborts);
+        assert!(plan.paused_periodic_tasks.is_empty());
+    }
+
+    #[test]
+    fn plans_for_a_missing_channel_device_or_malformed_config_are_rejected() {
+        let (_mock, state) = opened();
+        let mut app_state = state.lock().unwrap();
+        let error = app_state.plan_channel_change(None, None, &config(serde_json::json!({ "channel": 2, "baud": 500000 }))).unwrap_err();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+
+        let valid = config(serde_json::json!({ "channel": 0, "baud": 500000 }));
+        let error = app_state.plan_channel_change(Some(4), Some(1), &valid).unwrap_err();
+        assert_eq!(error, "device 1 is not the open device (0)");
+        app_state.devices.get_mut(&(4, 0)).unwrap().disconnected = true;
+        assert_eq!(app_state.plan_channel_change(None, None, &valid).unwrap_err(), "device 0 disconnected");
+
+        let error = app_state.plan_channel_change(None, None, &config(serde_json::json!({ "channel": 0, "baud": 123456 }))).unwrap_err();
+        assert!(error.contains("not a standard baud rate"), "{}", error);
+        let error = app_state
+            .plan_channel_change(None, None, &config(serde_json::json!({ "channel": 0, "baud": 500000, "timing0": 0 })))
+            .unwrap_err();
+        assert_eq!(error, "CAN1: give either baud or both timing0 and timing1");
+    }
+}
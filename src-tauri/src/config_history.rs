@@ -0,0 +1,342 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use crate::frame::host_timestamp_us;
+use crate::logging::LoggedFrame;
+use crate::{baud, AppState, DeviceType, StateMutex, VciInitConfig};
+
+/// 每個通道保留的設定世代數
+const MAX_GENERATIONS: usize = 64;
+
+/// 通道的一次 (重新) 初始化；訊框的 config_generation 指向收發當時的世代
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigGeneration {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub generation: u64,
+    pub config: VciInitConfig,
+    pub mode: &'static str,
+    pub bitrate: u32,
+    /// 初始化成功時的主機時間 (UNIX epoch 起算的微秒)
+    pub applied_at_us: u64,
+    /// 被下一個世代取代的時間；目前的設定為 None
+    pub superseded_at_us: Option<u64>,
+}
+
+impl ConfigGeneration {
+    /// 記錄檔檔頭中的一行說明
+    pub fn describe(&self) -> String {
+        format!(
+            "device {}/{} CAN{} generation {}: {} bit/s mode={} timing0=0x{:02X} timing1=0x{:02X} acc_code=0x{:08X} acc_mask=0x{:08X} filter={} applied_at_us={}",
+            self.dev_type,
+            self.dev_index,
+            self.channel + 1,
+            self.generation,
+            self.bitrate,
+            self.mode,
+            self.config.timing0,
+            self.config.timing1,
+            self.config.acc_code,
+            self.config.acc_mask,
+            self.config.filter,
+            self.applied_at_us
+        )
+    }
+}
+
+/// 通道 (重新) 初始化後送出；之後該通道的訊框事件 config_generation 都等於 stream_generation。
+/// 新世代先送出此事件才開始標記訊框，因此前端收到此事件前不會看到新世代的訊框，
+/// 可以直接丟棄 config_generation 較舊的訊框而不必重新註冊監聽
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigChangedEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub stream_generation: u64,
+    pub config: VciInitConfig,
+    pub mode: &'static str,
+    pub bitrate: u32,
+    pub applied_at_us: u64,
+}
+
+impl From<&ConfigGeneration> for ConfigChangedEvent {
+    fn from(generation: &ConfigGeneration) -> Self {
+        Self {
+            dev_type: generation.dev_type,
+            dev_index: generation.dev_index,
+            channel: generation.channel,
+            stream_generation: generation.generation,
+            config: generation.config,
+            mode: generation.mode,
+            bitrate: generation.bitrate,
+            applied_at_us: generation.applied_at_us,
+        }
+    }
+}
+
+/// VCI_INIT_CONFIG.Mode 的名稱
+fn mode_name(mode: u8) -> &'static str {
+    match mode {
+        0 => "normal",
+        1 => "listen-only",
+        2 => "self-test",
+        _ => "unknown",
+    }
+}
+
+/// 通道的設定世代；世代號碼只增不減，通道尚未初始化時為 0
+#[derive(Default)]
+pub struct ConfigHistory {
+    current: AtomicU64,
+    generations: Mutex<VecDeque<ConfigGeneration>>,
+}
+
+impl ConfigHistory {
+    pub fn current(&self) -> u64 {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    pub fn list(&self) -> Vec<ConfigGeneration> {
+        self.generations.lock().map(|generations| generations.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// 加入新世代；announce 在接收執行緒開始以新世代標記訊框之前呼叫
+    fn record(&self, key: (u32, u32), channel: u32, config: VciInitConfig, announce: impl FnOnce(&ConfigGeneration)) -> u64 {
+        let Ok(mut generations) = self.generations.lock() else {
+            return self.current();
+        };
+        let now = host_timestamp_us();
+        if let Some(previous) = generations.back_mut() {
+            previous.superseded_at_us = Some(now);
+        }
+        let generation = self.current() + 1;
+        generations.push_back(ConfigGeneration {
+            dev_type: key.0,
+            dev_index: key.1,
+            channel,
+            generation,
+            config,
+            mode: mode_name(config.mode),
+            bitrate: baud::bitrate_from_timing(config.timing0, config.timing1),
+            applied_at_us: now,
+            superseded_at_us: None,
+        });
+        if generations.len() > MAX_GENERATIONS {
+            generations.pop_front();
+        }
+        if let Some(recorded) = generations.back() {
+            announce(recorded);
+        }
+        self.current.store(generation, Ordering::SeqCst);
+        generation
+    }
+}
+
+impl AppState {
+    /// 通道初始化成功後呼叫，開始新的設定世代並送出 config-changed
+    pub(crate) fn record_channel_config(&mut self, key: (u32, u32), channel: u32, config: VciInitConfig) -> u64 {
+        let app_handle = self.app_handle.clone();
+        self.channel_runtime(key, channel).config_history.record(key, channel, config, |recorded| {
+            if let Some(app_handle) = app_handle {
+                let _ = app_handle.emit("config-changed", ConfigChangedEvent::from(recorded));
+            }
+        })
+    }
+
+    /// 開啟中的裝置上各通道目前的設定；channel 指定時只取該通道
+    pub(crate) fn active_configs(&self, channel: Option<u32>) -> Vec<ConfigGeneration> {
+        let mut active: Vec<ConfigGeneration> = self
+            .channel_runtime
+            .iter()
+            .filter(|(&(dev_type, dev_index, c), _)| {
+                self.devices.contains_key(&(dev_type, dev_index)) && channel.is_none_or(|channel| channel == c)
+            })
+            .filter_map(|(_, runtime)| runtime.config_history.list().pop())
+            .collect();
+        active.sort_by_key(|config| (config.dev_type, config.dev_index, config.channel));
+        active
+    }
+
+    /// frames 中出現過的設定世代，匯出記錄檔時寫進檔頭
+    pub(crate) fn configs_of(&self, frames: &[LoggedFrame]) -> Vec<ConfigGeneration> {
+        let used: HashSet<(u32, u32, u32, u64)> = frames
+            .iter()
+            .map(|logged| (logged.frame.device_type, logged.frame.device_index, logged.frame.channel, logged.frame.config_generation))
+            .collect();
+        let mut configs: Vec<ConfigGeneration> = self
+            .channel_runtime
+            .values()
+            .flat_map(|runtime| runtime.config_history.list())
+            .filter(|config| used.contains(&(config.dev_type, config.dev_index, config.channel, config.generation)))
+            .collect();
+        configs.sort_by_key(|config| (config.dev_type, config.dev_index, config.channel, config.generation));
+        configs
+    }
+
+    pub fn config_history(&mut self, key: (u32, u32), channel: u32) -> Vec<ConfigGeneration> {
+        self.channel_runtime(key, channel).config_history.list()
+    }
+
+    /// 檢查裝置與通道後回傳 config_history()
+    pub fn checked_config_history(
+        &mut self,
+        dev_type: Option<u32>,
+        dev_index: Option<u32>,
+        channel: u32,
+    ) -> Result<Vec<ConfigGeneration>, String> {
+        let device = self.device(dev_type, dev_index)?;
+        device.check_channel(channel)?;
+        let key = device.key();
+        Ok(self.config_history(key, channel))
+    }
+}
+
+/// 通道歷次初始化所用的設定 (由舊到新，最多保留 64 個世代)，
+/// 用訊框的 config_generation 查出收到時的硬體過濾與模式
+#[tauri::command]
+pub fn get_config_history(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<Vec<ConfigGeneration>, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state.checked_config_history(dev_type.map(DeviceType::code), dev_index, channel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{CanFrameEvent, Direction};
+    use crate::mock::MockCan;
+    use crate::VciCanObj;
+
+    const KEY: (u32, u32) = (4, 0);
+
+    fn config(timing1: u8, mode: u8) -> VciInitConfig {
+        VciInitConfig {
+            acc_mask: 0xFFFF_FFFF,
+            filter: 1,
+            timing1,
+            mode,
+            ..Default::default()
+        }
+    }
+
+    fn two_channel_state() -> AppState {
+        let mut app_state = AppState::with_interface(Arc::new(MockCan::new()));
+        app_state.open_device(KEY.0, KEY.1, None).unwrap();
+        app_state.devices.get_mut(&KEY).unwrap().channel_count = Some(2);
+        app_state
+    }
+
+    #[test]
+    fn each_init_starts_a_generation_that_supersedes_the_previous_one() {
+        let mut app_state = two_channel_state();
+        app_state.start_channel(KEY, 0, config(0x1C, 0)).unwrap();
+        app_state.init_channel(KEY, 0, config(0x14, 1)).unwrap();
+
+        let history = app_state.config_history(KEY, 0);
+        let summary: Vec<_> = history.iter().map(|g| (g.generation, g.bitrate, g.mode)).collect();
+        assert_eq!(summary, [(1, 500_000, "normal"), (2, 1_000_000, "listen-only")]);
+        assert!(history[0].superseded_at_us.is_some_and(|superseded| superseded >= history[0].applied_at_us));
+        assert!(history[1].superseded_at_us.is_none());
+        assert_eq!(app_state.channel_runtime(KEY, 0).config_history.current(), 2);
+        // 另一個通道從 0 開始
+        assert!(app_state.config_history(KEY, 1).is_empty());
+        assert_eq!(app_state.channel_runtime(KEY, 1).config_history.current(), 0);
+    }
+
+    #[test]
+    fn only_the_newest_generations_are_kept_and_numbers_keep_growing() {
+        let history = ConfigHistory::default();
+        for _ in 0..MAX_GENERATIONS + 6 {
+            history.record(KEY, 0, config(0x1C, 0), |_| {});
+        }
+        let generations = history.list();
+        assert_eq!(generations.len(), MAX_GENERATIONS);
+        assert_eq!(generations[0].generation, 7);
+        assert_eq!(history.current(), MAX_GENERATIONS as u64 + 6);
+    }
+
+    #[test]
+    fn the_new_generation_is_announced_before_frames_can_carry_it() {
+        let history = ConfigHistory::default();
+        history.record(KEY, 0, config(0x1C, 0), |_| {});
+        let mut announced = None;
+        history.record(KEY, 0, config(0x14, 0), |recorded| {
+            // 接收執行緒以 current() 標記訊框；送出事件時仍是舊世代
+            announced = Some((ConfigChangedEvent::from(recorded), history.current()));
+        });
+        let (event, current_while_announcing) = announced.unwrap();
+        assert_eq!((event.stream_generation, current_while_announcing, history.current()), (2, 1, 2));
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!((json["stream_generation"].as_u64(), json["bitrate"].as_u64()), (Some(2), Some(1_000_000)));
+    }
+
+    #[test]
+    fn the_log_header_line_names_every_field() {
+        let generation = ConfigGeneration {
+            dev_type: 4,
+            dev_index: 0,
+            channel: 1,
+            generation: 3,
+            config: VciInitConfig {
+                acc_code: 0x2000_0000,
+                acc_mask: 0x001F_FFFF,
+                filter: 2,
+                ..config(0x1C, 2)
+            },
+            mode: mode_name(2),
+            bitrate: 500_000,
+            applied_at_us: 1_700_000_000_000_000,
+            superseded_at_us: None,
+        };
+        assert_eq!(
+            generation.describe(),
+            "device 4/0 CAN2 generation 3: 500000 bit/s mode=self-test timing0=0x00 timing1=0x1C \
+             acc_code=0x20000000 acc_mask=0x001FFFFF filter=2 applied_at_us=1700000000000000"
+        );
+        assert_eq!(mode_name(7), "unknown");
+    }
+
+    #[test]
+    fn exports_list_only_the_generations_their_frames_used() {
+        let mut app_state = two_channel_state();
+        app_state.start_channel(KEY, 0, config(0x1C, 0)).unwrap();
+        app_state.start_channel(KEY, 0, config(0x14, 0)).unwrap();
+        app_state.start_channel(KEY, 1, config(0x1C, 0)).unwrap();
+        let logged = LoggedFrame {
+            direction: Direction::Rx,
+            frame: CanFrameEvent {
+                config_generation: 1,
+                ..CanFrameEvent::from_raw(KEY, 0, &VciCanObj::default(), 0)
+            },
+        };
+        let used: Vec<_> = app_state.configs_of(&[logged]).iter().map(|g| (g.channel, g.generation)).collect();
+        assert_eq!(used, [(0, 1)]);
+
+        let active: Vec<_> = app_state.active_configs(None).iter().map(|g| (g.channel, g.generation)).collect();
+        assert_eq!(active, [(0, 2), (1, 1)]);
+        assert_eq!(app_state.active_configs(Some(1)).len(), 1);
+        // 關閉的裝置不算在目前的設定中
+        app_state.devices.clear();
+        assert!(app_state.active_configs(None).is_empty());
+    }
+
+    #[test]
+    fn history_of_a_missing_channel_or_unopened_device_is_rejected() {
+        let mut app_state = two_channel_state();
+        app_state.start_channel(KEY, 0, config(0x1C, 0)).unwrap();
+        assert_eq!(app_state.checked_config_history(None, None, 0).unwrap().len(), 1);
+        let error = app_state.checked_config_history(None, None, 2).unwrap_err();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        assert_eq!(app_state.checked_config_history(Some(4), Some(1), 0).unwrap_err(), "device 1 is not the open device (0)");
+        assert!(!app_state.channel_runtime.contains_key(&(4, 0, 2)));
+    }
+}
@@ -0,0 +1,236 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use super::Dbc;
+use crate::frame::CanFrameEvent;
+use crate::{invalid_argument, run_blocking, StateMutex};
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SignalExportOptions {
+    /// 匯出觸發擷取的內容 (進行中的擷取為目前收集到的部分)；省略時匯出環形緩衝
+    pub capture_id: Option<u32>,
+    pub channel: Option<u32>,
+    /// 沒有新值的欄位沿用該訊號上一個值，而不是留空
+    pub forward_fill: bool,
+    /// 以固定間隔輸出一列，而不是每個訊框一列
+    pub resample_ms: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SignalExportSummary {
+    pub path: String,
+    pub rows: u64,
+    pub columns: Vec<String>,
+    /// 所屬訊息從未出現、整欄為空的訊號
+    pub missing: Vec<String>,
+}
+
+/// 一個匯出欄位；signals 可寫 "Message.Signal" 或只寫訊號名稱 (需在 DBC 中唯一)
+struct Column {
+    header: String,
+    id: u32,
+    extended: bool,
+    signal: String,
+}
+
+fn resolve_columns(dbc: &Dbc, signals: &[String]) -> Result<Vec<Column>, String> {
+    signals
+        .iter()
+        .map(|spec| {
+            let (message, signal) = match spec.split_once('.') {
+                Some((message, signal)) => {
+                    let message = dbc.message_by_name(message)?;
+                    if message.signal(signal).is_none() {
+                        return Err(format!("message {} has no signal {}", message.name, signal));
+                    }
+                    (message, signal)
+                }
+                None => {
+                    let mut owners = dbc.messages.iter().filter(|m| m.signal(spec).is_some());
+                    let message = owners.next().ok_or_else(|| format!("No DBC signal named {}", spec))?;
+                    if owners.next().is_some() {
+                        return Err(invalid_argument("signals", format!("{} is in several messages; use Message.Signal", spec)));
+                    }
+                    (message, spec.as_str())
+                }
+            };
+            Ok(Column {
+                header: format!("{}.{}", message.name, signal),
+                id: message.id,
+                extended: message.extended,
+                signal: signal.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// 逐訊框更新各欄位的值並依模式輸出列
+struct SignalTable<W: Write> {
+    out: W,
+    columns: Vec<Column>,
+    forward_fill: bool,
+    latest: Vec<Option<f64>>,
+    /// 上一列之後是否有新值
+    fresh: Vec<bool>,
+    rows: u64,
+}
+
+impl<W: Write> SignalTable<W> {
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let headers: Vec<&str> = self.columns.iter().map(|c| c.header.as_str()).collect();
+        writeln!(self.out, "timestamp,{}", headers.join(","))
+    }
+
+    /// 套用一個訊框；回傳是否有任何選取的訊號
+    fn apply(&mut self, dbc: &Dbc, frame: &CanFrameEvent) -> bool {
+        let Some(decoded) = dbc.decode(frame) else {
+            return false;
+        };
+        let mut matched = false;
+        for (index, column) in self.columns.iter().enumerate() {
+            if column.id != frame.id || column.extended != frame.extended {
+                continue;
+            }
+            if let Some(&value) = decoded.signals.get(&column.signal) {
+                self.latest[index] = Some(value);
+                self.fresh[index] = true;
+                matched = true;
+            }
+        }
+        matched
+    }
+
+    fn write_row(&mut self, timestamp_us: u64) -> std::io::Result<()> {
+        let cells: Vec<String> = self
+            .latest
+            .iter()
+            .zip(&self.fresh)
+            .map(|(value, &fresh)| match value {
+                Some(value) if fresh || self.forward_fill => value.to_string(),
+                _ => String::new(),
+            })
+            .collect();
+        writeln!(
+            self.out,
+            "{}.{:06},{}",
+            timestamp_us / 1_000_000,
+            timestamp_us % 1_000_000,
+            cells.join(",")
+        )?;
+        self.fresh.iter_mut().for_each(|fresh| *fresh = false);
+        self.rows += 1;
+        Ok(())
+    }
+}
+
+fn write_signals<W: Write>(
+    out: W,
+    dbc: &Dbc,
+    columns: Vec<Column>,
+    frames: &[CanFrameEvent],
+    options: &SignalExportOptions,
+) -> std::io::Result<(u64, Vec<bool>)> {
+    let mut table = SignalTable {
+        out,
+        forward_fill: options.forward_fill,
+        latest: vec![None; columns.len()],
+        fresh: vec![false; columns.len()],
+        columns,
+        rows: 0,
+    };
+    table.write_header()?;
+    let period_us = options.resample_ms.map(|ms| ((ms * 1000.0) as u64).max(1));
+    let mut next_tick = None;
+    let mut last_us = 0;
+    for frame in frames {
+        let timestamp_us = frame.host_timestamp_us;
+        if let (Some(period), Some(tick)) = (period_us, next_tick.as_mut()) {
+            // 先輸出這個訊框之前的取樣點，訊框的值從下一個取樣點開始生效
+            while *tick < timestamp_us {
+                table.write_row(*tick)?;
+                *tick += period;
+            }
+        }
+        if !table.apply(dbc, frame) {
+            continue;
+        }
+        last_us = timestamp_us;
+        match period_us {
+            Some(_) => {
+                next_tick.get_or_insert(timestamp_us);
+            }
+            None => table.write_row(timestamp_us)?,
+        }
+    }
+    if let (Some(period), Some(mut tick)) = (period_us, next_tick) {
+        while tick <= last_us {
+            table.write_row(tick)?;
+            tick += period;
+        }
+    }
+    table.out.flush()?;
+    let seen = table.latest.iter().map(Option::is_some).collect();
+    Ok((table.rows, seen))
+}
+
+/// 以 DBC 解碼環形緩衝或觸發擷取中的訊框，把選取的訊號以物理值寫成時間序列 CSV。
+/// 預設每個含選取訊號的訊框一列；resample_ms 時改為固定間隔，該時間點取最新的值
+#[tauri::command]
+pub async fn export_signals_csv(
+    path: String,
+    signals: Vec<String>,
+    options: Option<SignalExportOptions>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<SignalExportSummary, String> {
+    let options = options.unwrap_or_default();
+    if signals.is_empty() {
+        return Err(invalid_argument("signals", "select at least one signal"));
+    }
+    if options.resample_ms.is_some_and(|ms| !ms.is_finite() || ms <= 0.0) {
+        return Err(invalid_argument("options.resample_ms", "must be greater than 0"));
+    }
+    let (dbc, frame_buffer, captures) = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        (app_state.loaded_dbc()?, app_state.frame_buffer.clone(), app_state.captures.clone())
+    };
+    let columns = resolve_columns(&dbc, &signals)?;
+    run_blocking(move || {
+        let mut frames = match options.capture_id {
+            Some(capture_id) => captures
+                .lock()
+                .map_err(|_| "Failed to lock captures")?
+                .frames_of(capture_id)?,
+            None => frame_buffer
+                .lock()
+                .map_err(|_| "Failed to lock frame buffer")?
+                .snapshot(options.channel, None)
+                .into_iter()
+                .map(|buffered| buffered.frame)
+                .collect(),
+        };
+        if let Some(channel) = options.channel {
+            frames.retain(|frame| frame.channel == channel);
+        }
+        let headers: Vec<String> = columns.iter().map(|c| c.header.clone()).collect();
+        let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let (rows, seen) = write_signals(BufWriter::new(file), &dbc, columns, &frames, &options)
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        Ok(SignalExportSummary {
+            path,
+            rows,
+            missing: headers
+                .iter()
+                .zip(seen)
+                .filter(|(_, seen)| !seen)
+                .map(|(header, _)| header.clone())
+                .collect(),
+            columns: headers,
+        })
+    })
+    .await
+}
@@ -0,0 +1,291 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use super::{parser, read_dbc_file, Dbc, DbcSummary};
+use crate::supervisor;
+use crate::{AppState, StateMutex};
+
+/// 監看的 DBC 檔案檢查修改時間的間隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 一個已載入的 DBC 檔案
+struct DbcLayer {
+    id: u32,
+    path: Option<String>,
+    dbc: Dbc,
+    watch: bool,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LoadedDbc {
+    pub id: u32,
+    /// 直接傳內容載入時為 None
+    pub path: Option<String>,
+    #[serde(flatten)]
+    pub summary: DbcSummary,
+    pub watch: bool,
+}
+
+/// dbc-reloaded 事件的內容；列出合併結果中變動的訊息名稱
+#[derive(Serialize, Clone, Debug)]
+pub struct DbcReloaded {
+    pub id: u32,
+    pub path: String,
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    /// 檔案無法解析時保留原本的定義並回報錯誤
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 依優先順序排列的 DBC，後面的檔案以相同 ID 覆寫前面的訊息定義
+#[derive(Default)]
+pub struct DbcLayers {
+    layers: Vec<DbcLayer>,
+    next_id: u32,
+    watcher: Option<Arc<AtomicBool>>,
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn summary(dbc: &Dbc) -> DbcSummary {
+    DbcSummary {
+        messages: dbc.messages.len(),
+        signals: dbc.messages.iter().map(|m| m.signals.len()).sum(),
+    }
+}
+
+impl DbcLayers {
+    fn push(&mut self, dbc: Dbc, path: Option<String>, watch: bool) -> u32 {
+        self.next_id += 1;
+        self.layers.push(DbcLayer {
+            id: self.next_id,
+            modified: path.as_deref().and_then(modified_time),
+            path,
+            dbc,
+            watch,
+        });
+        self.next_id
+    }
+
+    pub(crate) fn replace(&mut self, dbc: Dbc, path: Option<String>) {
+        self.layers.clear();
+        self.push(dbc, path, false);
+    }
+
+    /// 優先順序最低 (最先載入) 的檔案路徑
+    pub(crate) fn base_path(&self) -> Option<String> {
+        self.layers.first().and_then(|layer| layer.path.clone())
+    }
+
+    fn merged(&self) -> Option<Dbc> {
+        if self.layers.is_empty() {
+            return None;
+        }
+        let mut merged = Dbc::default();
+        for layer in &self.layers {
+            for message in &layer.dbc.messages {
+                match merged
+                    .messages
+                    .iter_mut()
+                    .find(|m| m.id == message.id && m.extended == message.extended)
+                {
+                    Some(existing) => *existing = message.clone(),
+                    None => merged.messages.push(message.clone()),
+                }
+            }
+        }
+        Some(merged)
+    }
+
+    fn info(&self) -> Vec<LoadedDbc> {
+        self.layers
+            .iter()
+            .map(|layer| LoadedDbc {
+                id: layer.id,
+                path: layer.path.clone(),
+                summary: summary(&layer.dbc),
+                watch: layer.watch,
+            })
+            .collect()
+    }
+
+    /// 修改時間與上次載入時不同的監看檔案
+    fn changed_files(&self) -> Vec<(u32, String, Option<SystemTime>)> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.watch)
+            .filter_map(|layer| {
+                let path = layer.path.clone()?;
+                let modified = modified_time(&path);
+                (modified.is_some() && modified != layer.modified).then_some((layer.id, path, modified))
+            })
+            .collect()
+    }
+
+    fn has_watched(&self) -> bool {
+        self.layers.iter().any(|layer| layer.watch)
+    }
+}
+
+/// 比較合併前後的定義
+fn diff(before: Option<&Dbc>, after: Option<&Dbc>) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let empty = Dbc::default();
+    let (before, after) = (before.unwrap_or(&empty), after.unwrap_or(&empty));
+    let find = |dbc: &Dbc, id: u32, extended: bool| dbc.message(id, extended).cloned();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for message in &after.messages {
+        match find(before, message.id, message.extended) {
+            None => added.push(message.name.clone()),
+            Some(old) if old != *message => changed.push(message.name.clone()),
+            Some(_) => {}
+        }
+    }
+    let removed = before
+        .messages
+        .iter()
+        .filter(|m| after.message(m.id, m.extended).is_none())
+        .map(|m| m.name.clone())
+        .collect();
+    (added, changed, removed)
+}
+
+impl AppState {
+    /// 重新合併後一次替換解碼用的 DBC；接收迴圈每批次取一次 Arc，不會用到一半更新的定義
+    pub(crate) fn publish_dbc(&mut self) -> Result<(), String> {
+        *self.dbc.lock().map_err(|_| "Failed to lock DBC")? = self.dbc_layers.merged().map(Arc::new);
+        Ok(())
+    }
+
+    /// 還沒有監看執行緒且有需要監看的檔案時啟動
+    fn ensure_dbc_watcher(&mut self, state: Arc<StateMutex>, app_handle: tauri::AppHandle) {
+        if self.dbc_layers.watcher.is_some() || !self.dbc_layers.has_watched() {
+            return;
+        }
+        let running = Arc::new(AtomicBool::new(true));
+        self.dbc_layers.watcher = Some(running.clone());
+        std::thread::spawn(move || {
+            let finished = supervisor::guard("dbc-watcher", String::new(), || {
+                while running.load(Ordering::SeqCst) {
+                    std::thread::sleep(WATCH_INTERVAL);
+                    if !reload_changed(&state, &app_handle, &running) {
+                        break;
+                    }
+                }
+            });
+            if finished.is_none() {
+                if let Ok(mut app_state) = state.lock() {
+                    if app_state.dbc_layers.watcher.as_ref().is_some_and(|w| Arc::ptr_eq(w, &running)) {
+                        app_state.dbc_layers.watcher = None;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// 重新載入修改過的監看檔案並送出 dbc-reloaded；沒有需要監看的檔案時回傳 false 讓執行緒結束
+fn reload_changed(state: &Arc<StateMutex>, app_handle: &tauri::AppHandle, running: &Arc<AtomicBool>) -> bool {
+    let changed = match state.lock() {
+        Ok(mut app_state) => {
+            if !app_state.dbc_layers.has_watched() {
+                app_state.dbc_layers.watcher = None;
+                return false;
+            }
+            app_state.dbc_layers.changed_files()
+        }
+        Err(_) => return false,
+    };
+    for (id, path, modified) in changed {
+        // 在鎖外解析，大檔案也不會阻擋其他命令
+        let parsed = read_dbc_file(&path);
+        let Ok(mut app_state) = state.lock() else {
+            return false;
+        };
+        if !running.load(Ordering::SeqCst) {
+            return false;
+        }
+        let before = app_state.dbc_layers.merged();
+        let Some(layer) = app_state.dbc_layers.layers.iter_mut().find(|layer| layer.id == id) else {
+            continue;
+        };
+        layer.modified = modified;
+        let error = match parsed {
+            Ok(dbc) => {
+                layer.dbc = dbc;
+                app_state.publish_dbc().err()
+            }
+            Err(e) => Some(e),
+        };
+        let (added, changed, removed) = match error {
+            None => diff(before.as_ref(), app_state.dbc_layers.merged().as_ref()),
+            Some(_) => Default::default(),
+        };
+        let _ = app_handle.emit(
+            "dbc-reloaded",
+            DbcReloaded {
+                id,
+                path,
+                added,
+                changed,
+                removed,
+                error,
+            },
+        );
+    }
+    true
+}
+
+/// 以最高優先順序加入一個 DBC，相同 ID 的訊息覆寫之前載入的定義。
+/// watch 時檔案修改後自動重新載入並送出 dbc-reloaded
+#[tauri::command]
+pub fn add_dbc(
+    path: Option<String>,
+    content: Option<String>,
+    watch: Option<bool>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<LoadedDbc, String> {
+    let watch = watch.unwrap_or(false);
+    let (dbc, path) = match (path, content) {
+        (_, Some(_)) if watch => return Err("watch requires a path".into()),
+        (_, Some(content)) => (parser::parse(&content)?, None),
+        (Some(path), None) => (read_dbc_file(&path)?, Some(path)),
+        (None, None) => return Err("Either path or content is required".into()),
+    };
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let summary = summary(&dbc);
+    let id = app_state.dbc_layers.push(dbc, path.clone(), watch);
+    app_state.publish_dbc()?;
+    app_state.ensure_dbc_watcher(state.inner().clone(), app_handle);
+    Ok(LoadedDbc { id, path, summary, watch })
+}
+
+/// 依優先順序 (低到高) 列出已載入的 DBC
+#[tauri::command]
+pub fn list_loaded_dbcs(state: State<Arc<StateMutex>>) -> Result<Vec<LoadedDbc>, String> {
+    Ok(state.lock().map_err(|_| "Failed to lock state")?.dbc_layers.info())
+}
+
+/// 移除一個 DBC；被它覆寫的訊息恢復成較低優先順序的定義
+#[tauri::command]
+pub fn unload_dbc(id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let layers = &mut app_state.dbc_layers.layers;
+    let index = layers
+        .iter()
+        .position(|layer| layer.id == id)
+        .ok_or_else(|| format!("DBC {} is not loaded", id))?;
+    layers.remove(index);
+    app_state.publish_dbc()?;
+    Ok(format!("DBC {} unloaded", id))
+}
@@ -0,0 +1,559 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::e2e::E2eSpec;
+use crate::frame::CanFrameEvent;
+use crate::{run_blocking, AppState, DeviceType, StateMutex, VciCanObj};
+
+pub mod export;
+pub mod layers;
+mod parser;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ByteOrder {
+    /// Intel (@1)
+    LittleEndian,
+    /// Motorola (@0)；start_bit 為最高位元的位置
+    BigEndian,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Signal {
+    pub name: String,
+    pub start_bit: u32,
+    pub length: u32,
+    pub byte_order: ByteOrder,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub unit: String,
+    /// GenSigStartValue 屬性定義的初始原始值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_raw: Option<f64>,
+    /// 此訊號是多工切換訊號 (M 或 m<n>M)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_multiplexor: bool,
+    /// SG_ 行上的 m<n>
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplexor_value: Option<u64>,
+    /// 此訊號有效的條件；None 表示永遠有效
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplexed_by: Option<MuxCondition>,
+}
+
+/// 切換訊號的原始值落在任一範圍內時，被多工的訊號才有效
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MuxCondition {
+    pub switch: String,
+    pub ranges: Vec<(u64, u64)>,
+}
+
+impl MuxCondition {
+    fn contains(&self, value: u64) -> bool {
+        self.ranges.iter().any(|&(low, high)| (low..=high).contains(&value))
+    }
+}
+
+/// 切換訊號本身也可以被多工 (擴展多工)，限制巢狀深度以免定義循環
+const MAX_MUX_DEPTH: usize = 8;
+
+/// 編碼時數值超出 [minimum, maximum] 的處理方式
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutOfRange {
+    #[default]
+    Error,
+    Clamp,
+}
+
+impl Signal {
+    /// 原始值每個位元 (由最低位元起) 在訊框中的位置，位置 n 為第 n/8 個位元組的第 n%8 位元
+    fn bit_positions(&self) -> Vec<u32> {
+        match self.byte_order {
+            ByteOrder::LittleEndian => (self.start_bit..self.start_bit + self.length).collect(),
+            ByteOrder::BigEndian => {
+                // Motorola 從 start_bit 往低位元走，跨位元組時跳到下一個位元組的第 7 位元
+                let mut positions = Vec::with_capacity(self.length as usize);
+                let mut position = self.start_bit;
+                for _ in 0..self.length {
+                    positions.push(position);
+                    position = if position.is_multiple_of(8) { position + 15 } else { position - 1 };
+                }
+                positions.reverse();
+                positions
+            }
+        }
+    }
+
+    /// 取出原始值；訊號超出資料長度時回傳 None
+    pub fn raw_value(&self, data: &[u8]) -> Option<u64> {
+        let mut raw = 0u64;
+        for (bit, position) in self.bit_positions().into_iter().enumerate() {
+            let byte = *data.get((position / 8) as usize)?;
+            raw |= (((byte >> (position % 8)) & 1) as u64) << bit;
+        }
+        Some(raw)
+    }
+
+    fn set_raw_value(&self, data: &mut [u8], raw: u64) -> Result<(), String> {
+        for (bit, position) in self.bit_positions().into_iter().enumerate() {
+            let byte = data
+                .get_mut((position / 8) as usize)
+                .ok_or_else(|| format!("signal {} does not fit in the message DLC", self.name))?;
+            let mask = 1 << (position % 8);
+            if (raw >> bit) & 1 != 0 {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+        Ok(())
+    }
+
+    /// 物理值轉原始值：raw = (value - offset) / factor。min 與 max 皆為 0 時視為未定義範圍
+    pub fn encode(&self, value: f64, out_of_range: OutOfRange) -> Result<u64, String> {
+        let mut value = value;
+        if (self.minimum != 0.0 || self.maximum != 0.0) && !(self.minimum..=self.maximum).contains(&value) {
+            match out_of_range {
+                OutOfRange::Error => {
+                    return Err(format!(
+                        "{} = {} is out of range [{}, {}]",
+                        self.name, value, self.minimum, self.maximum
+                    ))
+                }
+                OutOfRange::Clamp => value = value.clamp(self.minimum, self.maximum),
+            }
+        }
+        let factor = if self.factor == 0.0 { 1.0 } else { self.factor };
+        self.raw_from_f64(((value - self.offset) / factor).round(), out_of_range)
+    }
+
+    fn raw_from_f64(&self, raw: f64, out_of_range: OutOfRange) -> Result<u64, String> {
+        let (low, high) = if self.signed {
+            (-(2f64.powi(self.length as i32 - 1)), 2f64.powi(self.length as i32 - 1) - 1.0)
+        } else {
+            (0.0, 2f64.powi(self.length as i32) - 1.0)
+        };
+        let raw = if (low..=high).contains(&raw) {
+            raw
+        } else if out_of_range == OutOfRange::Clamp {
+            raw.clamp(low, high)
+        } else {
+            return Err(format!("{} raw value {} does not fit in {} bits", self.name, raw, self.length));
+        };
+        let raw = if self.signed { raw as i64 as u64 } else { raw as u64 };
+        Ok(if self.length < 64 { raw & ((1 << self.length) - 1) } else { raw })
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Option<f64> {
+        let raw = self.raw_value(data)?;
+        let value = if self.signed && self.length < 64 && raw & (1 << (self.length - 1)) != 0 {
+            (raw | (u64::MAX << self.length)) as i64 as f64
+        } else if self.signed {
+            raw as i64 as f64
+        } else {
+            raw as f64
+        };
+        Some(value * self.factor + self.offset)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub id: u32,
+    pub extended: bool,
+    pub name: String,
+    pub dlc: u8,
+    pub sender: String,
+    pub signals: Vec<Signal>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DecodedMessage {
+    pub message: String,
+    pub signals: BTreeMap<String, f64>,
+    /// 最上層多工切換訊號的值；不在目前切換值下有效的訊號不會出現在 signals 中
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplexor: Option<u64>,
+    /// 因超出收到的 DLC 而未解碼的訊號
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+impl Message {
+    fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter().find(|s| s.name == name)
+    }
+
+    /// 依目前資料中的切換訊號值判斷訊號是否有效
+    fn is_active(&self, signal: &Signal, data: &[u8], depth: usize) -> bool {
+        let Some(condition) = &signal.multiplexed_by else {
+            return true;
+        };
+        let Some(switch) = self.signal(&condition.switch) else {
+            return false;
+        };
+        depth < MAX_MUX_DEPTH
+            && self.is_active(switch, data, depth + 1)
+            && switch.raw_value(data).is_some_and(|value| condition.contains(value))
+    }
+
+    fn mux_depth(&self, signal: &Signal, depth: usize) -> usize {
+        match signal.multiplexed_by.as_ref().and_then(|c| self.signal(&c.switch)) {
+            Some(switch) if depth < MAX_MUX_DEPTH => self.mux_depth(switch, depth + 1),
+            _ => depth,
+        }
+    }
+
+    pub fn decode(&self, data: &[u8]) -> DecodedMessage {
+        let mut decoded = DecodedMessage {
+            message: self.name.clone(),
+            signals: BTreeMap::new(),
+            multiplexor: self
+                .signals
+                .iter()
+                .find(|s| s.is_multiplexor && s.multiplexed_by.is_none())
+                .and_then(|s| s.raw_value(data)),
+            skipped: Vec::new(),
+        };
+        for signal in &self.signals {
+            if !self.is_active(signal, data, 0) {
+                continue;
+            }
+            match signal.decode(data) {
+                Some(value) => {
+                    decoded.signals.insert(signal.name.clone(), value);
+                }
+                None => decoded.skipped.push(signal.name.clone()),
+            }
+        }
+        decoded
+    }
+
+    /// 依訊號值組出資料；未指定的訊號使用初始值，沒有初始值則為 0
+    pub fn encode(&self, values: &HashMap<String, f64>, out_of_range: OutOfRange) -> Result<Vec<u8>, String> {
+        if let Some(unknown) = values.keys().find(|name| !self.signals.iter().any(|s| &s.name == *name)) {
+            return Err(format!("message {} has no signal {}", self.name, unknown));
+        }
+        let mut data = vec![0u8; self.dlc.min(8) as usize];
+        // 先寫入切換訊號，被多工的訊號才能依已寫入的切換值判斷是否有效
+        let mut signals: Vec<&Signal> = self.signals.iter().collect();
+        signals.sort_by_key(|s| self.mux_depth(s, 0));
+        for signal in signals {
+            if !self.is_active(signal, &data, 0) {
+                if values.contains_key(&signal.name) {
+                    return Err(format!("signal {} is not active for the selected multiplexor value", signal.name));
+                }
+                continue;
+            }
+            let raw = match values.get(&signal.name) {
+                Some(&value) => signal.encode(value, out_of_range)?,
+                None => signal.raw_from_f64(signal.initial_raw.unwrap_or_default(), OutOfRange::Clamp)?,
+            };
+            signal.set_raw_value(&mut data, raw)?;
+        }
+        Ok(data)
+    }
+
+    pub fn to_can_obj(&self, data: &[u8]) -> VciCanObj {
+        let mut can_obj = VciCanObj {
+            id: self.id,
+            extern_flag: self.extended as u8,
+            data_len: data.len() as u8,
+            ..Default::default()
+        };
+        can_obj.data[..data.len()].copy_from_slice(data);
+        can_obj
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Dbc {
+    messages: Vec<Message>,
+}
+
+impl Dbc {
+    pub fn message_by_name(&self, name: &str) -> Result<&Message, String> {
+        self.messages
+            .iter()
+            .find(|m| m.name == name)
+            .ok_or_else(|| format!("No DBC message named {}", name))
+    }
+
+    pub fn message(&self, id: u32, extended: bool) -> Option<&Message> {
+        self.messages.iter().find(|m| m.id == id && m.extended == extended)
+    }
+
+    pub fn decode(&self, frame: &CanFrameEvent) -> Option<DecodedMessage> {
+        if frame.remote {
+            return None;
+        }
+        self.message(frame.id, frame.extended).map(|m| m.decode(&frame.data))
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DbcSummary {
+    pub messages: usize,
+    pub signals: usize,
+}
+
+fn loaded_dbc(state: &State<Arc<StateMutex>>) -> Result<Arc<Dbc>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state.loaded_dbc()
+}
+
+impl AppState {
+    pub(crate) fn loaded_dbc(&self) -> Result<Arc<Dbc>, String> {
+        let dbc = self.dbc.lock().map_err(|_| "Failed to lock DBC")?;
+        dbc.clone().ok_or_else(|| "No DBC loaded".to_string())
+    }
+
+    /// 以單一 DBC 取代所有已載入的 DBC；path 為從檔案載入時的路徑，供設定檔記錄
+    pub(crate) fn set_dbc(&mut self, dbc: Dbc, path: Option<String>) -> Result<(), String> {
+        self.dbc_layers.replace(dbc, path);
+        self.publish_dbc()
+    }
+}
+
+pub(crate) fn read_dbc_file(path: &str) -> Result<Dbc, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    parser::parse(&text)
+}
+
+/// 載入 DBC 並取代所有已載入的 DBC；可傳檔案路徑或直接傳檔案內容。疊加多個檔案請用 add_dbc
+#[tauri::command]
+pub fn load_dbc(path: Option<String>, content: Option<String>, state: State<Arc<StateMutex>>) -> Result<DbcSummary, String> {
+    let (dbc, path) = match (path, content) {
+        (_, Some(content)) => (parser::parse(&content)?, None),
+        (Some(path), None) => (read_dbc_file(&path)?, Some(path)),
+        (None, None) => return Err("Either path or content is required".into()),
+    };
+    let summary = DbcSummary {
+        messages: dbc.messages.len(),
+        signals: dbc.messages.iter().map(|m| m.signals.len()).sum(),
+    };
+    state.lock().map_err(|_| "Failed to lock state")?.set_dbc(dbc, path)?;
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn decode_frame(id: u32, extended: Option<bool>, data: Vec<u8>, state: State<Arc<StateMutex>>) -> Result<DecodedMessage, String> {
+    let dbc = loaded_dbc(&state)?;
+    let message = dbc
+        .message(id, extended.unwrap_or(id > 0x7FF))
+        .ok_or_else(|| format!("No DBC message for ID 0x{:X}", id))?;
+    Ok(message.decode(&data))
+}
+
+#[tauri::command]
+pub fn get_dbc_messages(state: State<Arc<StateMutex>>) -> Result<Vec<Message>, String> {
+    Ok(loaded_dbc(&state)?.messages.clone())
+}
+
+#[derive(Serialize)]
+pub struct EncodedFrame {
+    pub id: u32,
+    pub extended: bool,
+    pub data: Vec<u8>,
+}
+
+/// 依 DBC 訊號值編碼並送出一個訊框
+#[tauri::command]
+pub async fn transmit_signals(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    message_name: String,
+    signals: HashMap<String, f64>,
+    out_of_range: Option<OutOfRange>,
+    e2e: Option<E2eSpec>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<EncodedFrame, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+        let dbc = app_state.loaded_dbc()?;
+        let message = dbc.message_by_name(&message_name)?;
+        let mut data = message.encode(&signals, out_of_range.unwrap_or_default())?;
+        if let Some(e2e) = &e2e {
+            e2e.check_fits(data.len())?;
+            // 每次呼叫遞增同一訊息的計數器
+            let counter = app_state.e2e_counters.entry((key.0, key.1, channel, message.id)).or_default();
+            e2e.apply(&mut data, *counter);
+            *counter = e2e.next_counter(*counter);
+        }
+        let tx_path = app_state.tx_path(key, channel)?;
+        drop(app_state);
+        tx_path.transmit(&[message.to_can_obj(&data)], true)?;
+        Ok(EncodedFrame {
+            id: message.id,
+            extended: message.extended,
+            data,
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(start_bit: u32, length: u32, byte_order: ByteOrder, signed: bool) -> Signal {
+        Signal {
+            name: "Sig".into(),
+            start_bit,
+            length,
+            byte_order,
+            signed,
+            factor: 1.0,
+            offset: 0.0,
+            minimum: 0.0,
+            maximum: 0.0,
+            unit: String::new(),
+            initial_raw: None,
+            is_multiplexor: false,
+            multiplexor_value: None,
+            multiplexed_by: None,
+        }
+    }
+
+    fn values(pairs: &[(&str, f64)]) -> HashMap<String, f64> {
+        pairs.iter().map(|&(name, value)| (name.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn intel_signals_read_upward_across_byte_boundaries() {
+        let data = [0xA0, 0xBC, 0x12, 0x34];
+        assert_eq!(signal(0, 8, ByteOrder::LittleEndian, false).raw_value(&data), Some(0xA0));
+        // 第 0 個位元組的高 4 位元是最低位
+        assert_eq!(signal(4, 12, ByteOrder::LittleEndian, false).raw_value(&data), Some(0xBCA));
+        assert_eq!(signal(8, 16, ByteOrder::LittleEndian, false).raw_value(&data), Some(0x12BC));
+        assert_eq!(signal(0, 32, ByteOrder::LittleEndian, false).raw_value(&data), Some(0x3412_BCA0));
+        assert_eq!(signal(24, 16, ByteOrder::LittleEndian, false).raw_value(&data), None);
+    }
+
+    #[test]
+    fn motorola_signals_read_downward_across_byte_boundaries() {
+        let data = [0x12, 0x34, 0xAB, 0xCD];
+        assert_eq!(signal(7, 8, ByteOrder::BigEndian, false).raw_value(&data), Some(0x12));
+        assert_eq!(signal(7, 16, ByteOrder::BigEndian, false).raw_value(&data), Some(0x1234));
+        // start_bit 3 為最高位：第 0 個位元組的低 4 位元接第 1 個位元組的高 4 位元
+        assert_eq!(signal(3, 8, ByteOrder::BigEndian, false).raw_value(&data), Some(0x23));
+        assert_eq!(signal(23, 12, ByteOrder::BigEndian, false).raw_value(&data), Some(0xABC));
+        assert_eq!(signal(15, 24, ByteOrder::BigEndian, false).raw_value(&data), Some(0x34ABCD));
+        assert_eq!(signal(31, 16, ByteOrder::BigEndian, false).raw_value(&data), None);
+    }
+
+    #[test]
+    fn signed_and_scaled_signals_decode_to_physical_values() {
+        let mut intel = signal(0, 8, ByteOrder::LittleEndian, true);
+        intel.factor = 0.5;
+        intel.offset = -10.0;
+        assert_eq!(intel.decode(&[0xFE]), Some(-11.0));
+        assert_eq!(intel.decode(&[0x7F]), Some(53.5));
+
+        let mut motorola = signal(7, 12, ByteOrder::BigEndian, true);
+        motorola.factor = 0.25;
+        assert_eq!(motorola.decode(&[0x80, 0x00]), Some(-512.0));
+        assert_eq!(motorola.decode(&[0x7F, 0xF0]), Some(511.75));
+
+        let mut unsigned = signal(4, 12, ByteOrder::LittleEndian, false);
+        unsigned.factor = 0.125;
+        unsigned.offset = 100.0;
+        assert_eq!(unsigned.decode(&[0xF0, 0xFF]), Some(4095.0 * 0.125 + 100.0));
+    }
+
+    #[test]
+    fn encoding_scales_checks_the_range_and_packs_both_byte_orders() {
+        let mut speed = signal(24, 16, ByteOrder::LittleEndian, false);
+        speed.factor = 0.125;
+        speed.maximum = 8031.875;
+        assert_eq!(speed.encode(2500.0, OutOfRange::Error), Ok(20_000));
+        assert!(speed.encode(9000.0, OutOfRange::Error).is_err());
+        assert_eq!(speed.encode(9000.0, OutOfRange::Clamp), Ok(64_255));
+
+        let mut torque = signal(7, 12, ByteOrder::BigEndian, true);
+        torque.factor = 0.25;
+        assert_eq!(torque.encode(-512.0, OutOfRange::Error), Ok(0x800));
+        // 未定義範圍時以位元數為界
+        assert!(torque.encode(512.0, OutOfRange::Error).is_err());
+        assert_eq!(torque.encode(512.0, OutOfRange::Clamp), Ok(0x7FF));
+
+        let message = Message {
+            id: 0x100,
+            extended: false,
+            name: "Msg".into(),
+            dlc: 8,
+            sender: String::new(),
+            signals: vec![
+                Signal {
+                    name: "Speed".into(),
+                    ..speed
+                },
+                Signal {
+                    name: "Torque".into(),
+                    ..torque
+                },
+            ],
+        };
+        let data = message.encode(&values(&[("Speed", 2500.0), ("Torque", -1.25)]), OutOfRange::Error).unwrap();
+        // -1.25 / 0.25 = -5 → 12 位元 0xFFB
+        assert_eq!(data, [0xFF, 0xB0, 0, 0x20, 0x4E, 0, 0, 0]);
+        let decoded = message.decode(&data);
+        assert_eq!(decoded.signals["Speed"], 2500.0);
+        assert_eq!(decoded.signals["Torque"], -1.25);
+        assert!(message.encode(&values(&[("Unknown", 1.0)]), OutOfRange::Error).is_err());
+    }
+
+    #[test]
+    fn unspecified_signals_take_their_initial_value() {
+        let dbc = parser::parse(
+            "BO_ 256 Msg: 2 X\n SG_ A : 0|8@1+ (1,0) [0|0] \"\" X\n SG_ B : 15|8@0+ (1,0) [0|0] \"\" X\nBA_ \"GenSigStartValue\" SG_ 256 B 66;",
+        )
+        .unwrap();
+        let message = dbc.message_by_name("Msg").unwrap();
+        assert_eq!(message.encode(&HashMap::new(), OutOfRange::Error).unwrap(), [0, 66]);
+        assert_eq!(message.encode(&values(&[("A", 7.0)]), OutOfRange::Error).unwrap(), [7, 66]);
+    }
+
+    #[test]
+    fn only_signals_of_the_active_multiplexor_value_are_decoded_and_encoded() {
+        let dbc = parser::parse(
+            "BO_ 512 Mux: 4 X\n\
+             SG_ Switch M : 0|8@1+ (1,0) [0|0] \"\" X\n\
+             SG_ SigA m1 : 8|8@1+ (1,0) [0|0] \"\" X\n\
+             SG_ SubSwitch m2M : 8|8@1+ (1,0) [0|0] \"\" X\n\
+             SG_ SigC m0 : 16|16@1+ (1,0) [0|0] \"\" X\n\
+             SG_MUL_VAL_ 512 SigC SubSwitch 3-4;",
+        )
+        .unwrap();
+        let message = dbc.message_by_name("Mux").unwrap();
+
+        let decoded = message.decode(&[1, 0x10, 0x20, 0x30]);
+        assert_eq!(decoded.multiplexor, Some(1));
+        assert_eq!(decoded.signals.keys().collect::<Vec<_>>(), ["SigA", "Switch"]);
+        assert_eq!(decoded.signals["SigA"], 16.0);
+
+        let decoded = message.decode(&[2, 4, 0x34, 0x12]);
+        assert_eq!(decoded.multiplexor, Some(2));
+        assert_eq!(decoded.signals.keys().collect::<Vec<_>>(), ["SigC", "SubSwitch", "Switch"]);
+        assert_eq!(decoded.signals["SigC"], 0x1234 as f64);
+        // 子切換值不在範圍內，或上層切換值不選中子切換訊號時，SigC 都無效
+        assert!(!message.decode(&[2, 5, 0x34, 0x12]).signals.contains_key("SigC"));
+        assert!(!message.decode(&[0, 3, 0x34, 0x12]).signals.contains_key("SigC"));
+
+        let data = message
+            .encode(&values(&[("Switch", 2.0), ("SubSwitch", 3.0), ("SigC", 0xBEEF as f64)]), OutOfRange::Error)
+            .unwrap();
+        assert_eq!(data, [2, 3, 0xEF, 0xBE]);
+        assert!(message.encode(&values(&[("Switch", 2.0), ("SigA", 1.0)]), OutOfRange::Error).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use super::{ByteOrder, Dbc, Message, MuxCondition, Signal};
+
+/// BO_ 行的 ID 最高位元為 1 時代表擴展幀
+const EXTENDED_ID_FLAG: u32 = 0x8000_0000;
+/// 用來收容不屬於任何訊息之訊號的虛擬訊息
+const INDEPENDENT_SIG_MSG: &str = "VECTOR__INDEPENDENT_SIG_MSG";
+
+/// 解析 DBC 內容；只處理編解碼需要的 BO_/SG_、多工定義與訊號初始值，其他區段略過
+pub fn parse(text: &str) -> Result<Dbc, String> {
+    let mut messages: Vec<Message> = Vec::new();
+    let mut start_values: Vec<(u32, String, f64)> = Vec::new();
+    let mut mux_values: Vec<(u32, String, MuxCondition)> = Vec::new();
+    let mut skipping = false;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let result = if let Some(rest) = line.strip_prefix("BO_ ") {
+            parse_message(rest).map(|message| {
+                skipping = message.name == INDEPENDENT_SIG_MSG;
+                if !skipping {
+                    messages.push(message);
+                }
+            })
+        } else if let Some(rest) = line.strip_prefix("SG_ ") {
+            match messages.last_mut() {
+                _ if skipping => Ok(()),
+                Some(message) => parse_signal(rest).map(|signal| message.signals.push(signal)),
+                None => Err("SG_ outside of a BO_ block".to_string()),
+            }
+        } else if let Some(rest) = line.strip_prefix("BA_ \"GenSigStartValue\" SG_ ") {
+            parse_start_value(rest).map(|value| start_values.push(value))
+        } else if let Some(rest) = line.strip_prefix("SG_MUL_VAL_ ") {
+            parse_mux_value(rest).map(|value| mux_values.push(value))
+        } else {
+            Ok(())
+        };
+        result.map_err(|e| format!("line {}: {}", index + 1, e))?;
+    }
+    for (raw_id, signal_name, raw_value) in start_values {
+        if let Some(signal) = find_signal(&mut messages, raw_id, &signal_name) {
+            signal.initial_raw = Some(raw_value);
+        }
+    }
+    for message in &mut messages {
+        resolve_simple_multiplexing(message);
+    }
+    // SG_MUL_VAL_ (擴展多工) 明確指定切換訊號與數值範圍，覆蓋 m<n> 的預設解讀
+    for (raw_id, signal_name, condition) in mux_values {
+        if let Some(signal) = find_signal(&mut messages, raw_id, &signal_name) {
+            signal.multiplexed_by = Some(condition);
+        }
+    }
+    Ok(Dbc { messages })
+}
+
+fn find_signal<'a>(messages: &'a mut [Message], raw_id: u32, name: &str) -> Option<&'a mut Signal> {
+    messages
+        .iter_mut()
+        .filter(|m| m.id == raw_id & !EXTENDED_ID_FLAG && m.extended == (raw_id & EXTENDED_ID_FLAG != 0))
+        .flat_map(|m| m.signals.iter_mut())
+        .find(|s| s.name == name)
+}
+
+/// 一般多工：m<n> 的訊號在訊息中唯一的 M 訊號等於 n 時有效
+fn resolve_simple_multiplexing(message: &mut Message) {
+    let switches: Vec<&Signal> = message
+        .signals
+        .iter()
+        .filter(|s| s.is_multiplexor && s.multiplexor_value.is_none())
+        .collect();
+    let [switch] = switches[..] else {
+        return;
+    };
+    let switch = switch.name.clone();
+    for signal in &mut message.signals {
+        if let Some(value) = signal.multiplexor_value {
+            signal.multiplexed_by = Some(MuxCondition {
+                switch: switch.clone(),
+                ranges: vec![(value, value)],
+            });
+        }
+    }
+}
+
+/// `2024 S1 MuxA 0-0, 3-5;`
+fn parse_mux_value(text: &str) -> Result<(u32, String, MuxCondition), String> {
+    let mut fields = text.trim().trim_end_matches(';').splitn(4, char::is_whitespace);
+    let raw_id = fields.next().and_then(|id| id.parse().ok()).ok_or("invalid message ID")?;
+    let name = fields.next().ok_or("missing signal name")?.to_string();
+    let switch = fields.next().ok_or("missing multiplexor name")?.to_string();
+    let ranges = fields
+        .next()
+        .ok_or("missing multiplexor ranges")?
+        .split(',')
+        .map(|range| {
+            let (low, high) = range.trim().split_once('-')?;
+            Some((low.trim().parse().ok()?, high.trim().parse().ok()?))
+        })
+        .collect::<Option<Vec<(u64, u64)>>>()
+        .ok_or("invalid multiplexor range")?;
+    Ok((raw_id, name, MuxCondition { switch, ranges }))
+}
+
+/// `2364540158 EngineSpeed 0;` (GenSigStartValue 為原始值)
+fn parse_start_value(text: &str) -> Result<(u32, String, f64), String> {
+    let mut fields = text.trim_end_matches(';').split_whitespace();
+    let raw_id = fields.next().and_then(|id| id.parse().ok()).ok_or("invalid message ID")?;
+    let name = fields.next().ok_or("missing signal name")?.to_string();
+    let value = fields.next().and_then(|v| v.parse().ok()).ok_or("invalid start value")?;
+    Ok((raw_id, name, value))
+}
+
+/// `2364540158 EEC1: 8 Engine`
+fn parse_message(text: &str) -> Result<Message, String> {
+    let (head, tail) = text.split_once(':').ok_or("expected ':' in BO_")?;
+    let mut head = head.split_whitespace();
+    let raw_id: u32 = head
+        .next()
+        .and_then(|id| id.parse().ok())
+        .ok_or("invalid message ID")?;
+    let name = head.next().ok_or("missing message name")?.to_string();
+    let mut tail = tail.split_whitespace();
+    let dlc: u8 = tail.next().and_then(|d| d.parse().ok()).ok_or("invalid message DLC")?;
+    Ok(Message {
+        id: raw_id & !EXTENDED_ID_FLAG,
+        extended: raw_id & EXTENDED_ID_FLAG != 0,
+        name,
+        dlc,
+        sender: tail.next().unwrap_or_default().to_string(),
+        signals: Vec::new(),
+    })
+}
+
+/// `EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Vector__XXX`
+fn parse_signal(text: &str) -> Result<Signal, String> {
+    let (head, tail) = text.split_once(':').ok_or("expected ':' in SG_")?;
+    let mut head = head.split_whitespace();
+    let name = head.next().ok_or("missing signal name")?.to_string();
+    // 多工標記：M 為切換訊號，m<n> 表示在切換值為 n 時有效，m<n>M 兩者皆是
+    let (is_multiplexor, multiplexor_value) = match head.next() {
+        None => (false, None),
+        Some("M") => (true, None),
+        Some(mark) => {
+            let value = mark.strip_prefix('m').ok_or_else(|| format!("invalid multiplexer indicator {:?}", mark))?;
+            let (value, is_multiplexor) = match value.strip_suffix('M') {
+                Some(value) => (value, true),
+                None => (value, false),
+            };
+            let value = value
+                .parse()
+                .map_err(|_| format!("invalid multiplexer indicator {:?}", mark))?;
+            (is_multiplexor, Some(value))
+        }
+    };
+
+    let (layout, tail) = tail.trim_start().split_once(char::is_whitespace).ok_or("missing signal layout")?;
+    let (start_bit, rest) = layout.split_once('|').ok_or("expected start|length")?;
+    let (length, format) = rest.split_once('@').ok_or("expected @ after length")?;
+    let start_bit: u32 = start_bit.parse().map_err(|_| "invalid start bit")?;
+    let length: u32 = length.parse().map_err(|_| "invalid signal length")?;
+    if length == 0 || length > 64 {
+        return Err(format!("unsupported signal length {}", length));
+    }
+    let byte_order = match format.chars().next() {
+        Some('0') => ByteOrder::BigEndian,
+        Some('1') => ByteOrder::LittleEndian,
+        _ => return Err("invalid byte order".into()),
+    };
+    let signed = match format.chars().nth(1) {
+        Some('+') => false,
+        Some('-') => true,
+        _ => return Err("invalid value type".into()),
+    };
+
+    let (factor, offset) = pair(delimited(tail, '(', ')')?, ',').ok_or("invalid (factor,offset)")?;
+    let (minimum, maximum) = pair(delimited(tail, '[', ']')?, '|').ok_or("invalid [min|max]")?;
+    let unit = delimited(tail, '"', '"')?.to_string();
+
+    Ok(Signal {
+        name,
+        start_bit,
+        length,
+        byte_order,
+        signed,
+        factor,
+        offset,
+        minimum,
+        maximum,
+        unit,
+        initial_raw: None,
+        is_multiplexor,
+        multiplexor_value,
+        multiplexed_by: None,
+    })
+}
+
+fn delimited(text: &str, open: char, close: char) -> Result<&str, String> {
+    let start = text.find(open).ok_or_else(|| format!("missing '{}'", open))? + 1;
+    let len = text[start..].find(close).ok_or_else(|| format!("missing '{}'", close))?;
+    Ok(&text[start..start + len])
+}
+
+fn pair(text: &str, separator: char) -> Option<(f64, f64)> {
+    let (a, b) = text.split_once(separator)?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DBC: &str = r#"VERSION ""
+
+BU_: Engine Gateway
+
+BO_ 2364540158 EEC1: 8 Engine
+ SG_ EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Gateway
+ SG_ Torque : 7|12@0- (0.5,-100) [-1124|923.5] "Nm" Gateway
+
+BO_ 512 Mux: 4 Gateway
+ SG_ Switch M : 0|8@1+ (1,0) [0|0] "" Gateway
+ SG_ SigA m1 : 8|8@1+ (1,0) [0|0] "" Gateway
+ SG_ SubSwitch m2M : 8|8@1+ (1,0) [0|0] "" Gateway
+ SG_ SigC m0 : 16|8@1+ (1,0) [0|0] "" Gateway
+
+BO_ 3221225472 VECTOR__INDEPENDENT_SIG_MSG: 0 Vector__XXX
+ SG_ Orphan : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+
+BA_ "GenSigStartValue" SG_ 2364540158 EngineSpeed 6400;
+SG_MUL_VAL_ 512 SigC SubSwitch 3-4, 7-7;
+"#;
+
+    #[test]
+    fn messages_and_signals_are_parsed() {
+        let dbc = parse(DBC).unwrap();
+        assert_eq!(dbc.messages.len(), 2);
+
+        let eec1 = dbc.message(0x0CF0_04FE, true).unwrap();
+        assert_eq!((eec1.name.as_str(), eec1.dlc, eec1.sender.as_str()), ("EEC1", 8, "Engine"));
+        let speed = eec1.signal("EngineSpeed").unwrap();
+        assert_eq!(
+            (speed.start_bit, speed.length, speed.byte_order, speed.signed),
+            (24, 16, ByteOrder::LittleEndian, false)
+        );
+        assert_eq!((speed.factor, speed.offset, speed.minimum, speed.maximum), (0.125, 0.0, 0.0, 8031.875));
+        assert_eq!(speed.unit, "rpm");
+        assert_eq!(speed.initial_raw, Some(6400.0));
+        let torque = eec1.signal("Torque").unwrap();
+        assert_eq!((torque.byte_order, torque.signed, torque.offset, torque.minimum), (ByteOrder::BigEndian, true, -100.0, -1124.0));
+        assert_eq!(torque.initial_raw, None);
+
+        // 標準 ID 的訊息不會被同名數值的擴展 ID 找到
+        assert!(dbc.message(512, true).is_none());
+        assert!(dbc.message(512, false).is_some());
+        assert!(dbc.message_by_name("VECTOR__INDEPENDENT_SIG_MSG").is_err());
+    }
+
+    #[test]
+    fn multiplexer_indicators_and_extended_multiplexing_are_resolved() {
+        let dbc = parse(DBC).unwrap();
+        let mux = dbc.message_by_name("Mux").unwrap();
+        let switch = mux.signal("Switch").unwrap();
+        assert!(switch.is_multiplexor && switch.multiplexed_by.is_none());
+
+        let sig_a = mux.signal("SigA").unwrap();
+        assert_eq!(sig_a.multiplexor_value, Some(1));
+        assert_eq!(
+            sig_a.multiplexed_by,
+            Some(MuxCondition {
+                switch: "Switch".into(),
+                ranges: vec![(1, 1)],
+            })
+        );
+        let sub_switch = mux.signal("SubSwitch").unwrap();
+        assert!(sub_switch.is_multiplexor);
+        assert_eq!(sub_switch.multiplexed_by.as_ref().map(|c| c.ranges.clone()), Some(vec![(2, 2)]));
+        // SG_MUL_VAL_ 取代 m0 的預設解讀
+        assert_eq!(
+            mux.signal("SigC").unwrap().multiplexed_by,
+            Some(MuxCondition {
+                switch: "SubSwitch".into(),
+                ranges: vec![(3, 4), (7, 7)],
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_lines_report_their_line_number() {
+        let cases = [
+            (" SG_ Lost : 0|8@1+ (1,0) [0|0] \"\" X", "line 1: SG_ outside of a BO_ block"),
+            ("BO_ 100 NoColon 8 X", "line 1: expected ':' in BO_"),
+            ("BO_ abc Bad: 8 X", "line 1: invalid message ID"),
+            ("BO_ 100 Msg: 8 X\n SG_ Zero : 0|0@1+ (1,0) [0|0] \"\" X", "line 2: unsupported signal length 0"),
+            ("BO_ 100 Msg: 8 X\n SG_ Long : 0|65@1+ (1,0) [0|0] \"\" X", "line 2: unsupported signal length 65"),
+            ("BO_ 100 Msg: 8 X\n SG_ Order : 0|8@2+ (1,0) [0|0] \"\" X", "line 2: invalid byte order"),
+            ("BO_ 100 Msg: 8 X\n SG_ Sign : 0|8@1* (1,0) [0|0] \"\" X", "line 2: invalid value type"),
+            ("BO_ 100 Msg: 8 X\n SG_ Mark x1 : 0|8@1+ (1,0) [0|0] \"\" X", "line 2: invalid multiplexer indicator \"x1\""),
+            ("BO_ 100 Msg: 8 X\n SG_ Scale : 0|8@1+ (one,0) [0|0] \"\" X", "line 2: invalid (factor,offset)"),
+            ("SG_MUL_VAL_ 100 Sig Switch 3;", "line 1: invalid multiplexor range"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse(text).unwrap_err(), expected, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::{invalid_argument, AppState, DeviceType, StateMutex};
+
+const MAX_WINDOW_MS: f64 = 10_000.0;
+/// 記住的訊框超過這個數量時清掉視窗外的項目
+const PRUNE_AT: usize = 4096;
+
+/// 兩個訊框視為相同的條件
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupeCompare {
+    #[serde(rename = "id")]
+    Id,
+    #[default]
+    #[serde(rename = "id+data")]
+    IdData,
+}
+
+/// 抑制視窗內重複的訊框 (例如閘道在 1 ms 內重送多次)。
+/// 被抑制的訊框仍計入統計，但不進入環形緩衝與事件流；記錄檔預設不受影響
+pub struct Dedupe {
+    window_us: u64,
+    compare: DedupeCompare,
+    pub apply_to_log: bool,
+    /// 最後一個通過的訊框的 timestamp_us
+    last_passed: HashMap<(u32, bool, bool, Vec<u8>), u64>,
+}
+
+impl Dedupe {
+    pub fn new(window_us: u64, compare: DedupeCompare, apply_to_log: bool) -> Self {
+        Self {
+            window_us,
+            compare,
+            apply_to_log,
+            last_passed: HashMap::new(),
+        }
+    }
+
+    /// 依 set_dedupe 的 window_ms (毫秒) 建立；視窗必須大於 0 且不超過 10 秒
+    pub fn with_window_ms(window_ms: f64, compare: DedupeCompare, apply_to_log: bool) -> Result<Self, String> {
+        if !window_ms.is_finite() || window_ms <= 0.0 || window_ms > MAX_WINDOW_MS {
+            return Err(invalid_argument("window_ms", format!("must be greater than 0 and at most {}", MAX_WINDOW_MS)));
+        }
+        Ok(Self::new((window_ms * 1000.0).round() as u64, compare, apply_to_log))
+    }
+
+    /// 回傳每個訊框是否被抑制；距同一訊框上一次通過不到視窗時間的訊框被抑制
+    pub fn apply(&mut self, frames: &[CanFrameEvent]) -> Vec<bool> {
+        let suppressed = frames
+            .iter()
+            .map(|frame| {
+                let data = match self.compare {
+                    DedupeCompare::Id => Vec::new(),
+                    DedupeCompare::IdData => frame.data.clone(),
+                };
+                let key = (frame.id, frame.extended, frame.remote, data);
+                match self.last_passed.get(&key) {
+                    Some(&passed) if frame.timestamp_us.saturating_sub(passed) < self.window_us => true,
+                    _ => {
+                        self.last_passed.insert(key, frame.timestamp_us);
+                        false
+                    }
+                }
+            })
+            .collect();
+        if self.last_passed.len() > PRUNE_AT {
+            if let Some(newest) = frames.iter().map(|frame| frame.timestamp_us).max() {
+                let window_us = self.window_us;
+                self.last_passed.retain(|_, passed| newest.saturating_sub(*passed) < window_us);
+            }
+        }
+        suppressed
+    }
+}
+
+impl AppState {
+    /// dedupe 為 None 時關閉；接收執行緒在下一批訊框套用
+    pub fn set_dedupe(&mut self, key: (u32, u32), channel: u32, dedupe: Option<Dedupe>) -> Result<(), String> {
+        if let Some(device) = self.devices.get(&key) {
+            device.check_channel(channel)?;
+        }
+        *self.channel_runtime(key, channel).dedupe.lock().map_err(|_| "Failed to lock dedupe filter")? = dedupe;
+        Ok(())
+    }
+}
+
+/// 設定通道的重複訊框抑制；window_ms 省略時關閉。可在接收中調整，下一批訊框即生效。
+/// apply_to_log 為 true 時記錄檔也略過被抑制的訊框 (預設保留，記錄檔維持完整)
+#[tauri::command]
+pub fn set_dedupe(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    window_ms: Option<f64>,
+    compare: Option<DedupeCompare>,
+    apply_to_log: Option<bool>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let dedupe = window_ms
+        .map(|window| Dedupe::with_window_ms(window, compare.unwrap_or_default(), apply_to_log.unwrap_or(false)))
+        .transpose()?;
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let key = app_state.device(dev_type.map(DeviceType::code), dev_index)?.key();
+    app_state.set_dedupe(key, channel, dedupe)?;
+    Ok(match window_ms {
+        Some(window) => format!("CAN{} duplicate frames within {} ms suppressed", channel + 1, window),
+        None => format!("CAN{} duplicate suppression disabled", channel + 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::VciCanObj;
+
+    fn frame(id: u32, data: &[u8], timestamp_us: u64) -> CanFrameEvent {
+        let mut can_obj = VciCanObj {
+            id,
+            data_len: data.len() as u8,
+            ..Default::default()
+        };
+        can_obj.data[..data.len()].copy_from_slice(data);
+        CanFrameEvent {
+            timestamp_us,
+            ..CanFrameEvent::from_raw((4, 0), 0, &can_obj, timestamp_us)
+        }
+    }
+
+    #[test]
+    fn repeats_inside_the_window_are_suppressed_from_the_last_passed_frame() {
+        let mut dedupe = Dedupe::new(1000, DedupeCompare::IdData, false);
+        let frames = [
+            frame(0x100, &[1], 0),
+            frame(0x100, &[1], 400),
+            // 被抑制的訊框不延長視窗
+            frame(0x100, &[1], 1000),
+            frame(0x100, &[1], 1500),
+            frame(0x100, &[2], 1600),
+            frame(0x101, &[1], 1700),
+        ];
+        assert_eq!(dedupe.apply(&frames), [false, true, false, true, false, false]);
+        // 視窗跨越批次
+        assert_eq!(dedupe.apply(&[frame(0x100, &[1], 1999), frame(0x100, &[1], 2000)]), [true, false]);
+    }
+
+    #[test]
+    fn comparing_by_id_ignores_the_data_but_not_the_frame_format() {
+        let mut dedupe = Dedupe::new(1000, DedupeCompare::Id, false);
+        let extended = CanFrameEvent {
+            extended: true,
+            ..frame(0x100, &[3], 200)
+        };
+        assert_eq!(dedupe.apply(&[frame(0x100, &[1], 0), frame(0x100, &[2], 100), extended]), [false, true, false]);
+    }
+
+    #[test]
+    fn window_ms_outside_the_allowed_range_is_rejected() {
+        for window_ms in [0.0, -1.0, f64::NAN, f64::INFINITY, MAX_WINDOW_MS + 1.0] {
+            let error = Dedupe::with_window_ms(window_ms, DedupeCompare::Id, false).err().unwrap();
+            assert!(error.contains("window_ms"), "{}", error);
+        }
+        assert_eq!(Dedupe::with_window_ms(0.5, DedupeCompare::Id, false).unwrap().window_us, 500);
+        assert_eq!(Dedupe::with_window_ms(MAX_WINDOW_MS, DedupeCompare::Id, false).unwrap().window_us, 10_000_000);
+    }
+
+    #[test]
+    fn compare_modes_use_the_documented_names() {
+        assert_eq!(serde_json::from_str::<DedupeCompare>("\"id+data\"").unwrap(), DedupeCompare::IdData);
+        assert_eq!(serde_json::from_str::<DedupeCompare>("\"id\"").unwrap(), DedupeCompare::Id);
+        assert!(serde_json::from_str::<DedupeCompare>("\"data\"").is_err());
+    }
+
+    #[test]
+    fn set_dedupe_rejects_a_channel_the_device_does_not_have() {
+        let mut app_state = AppState::with_interface(Arc::new(MockCan::new()));
+        app_state.open_device(4, 0, None).unwrap();
+        app_state.devices.get_mut(&(4, 0)).unwrap().channel_count = Some(2);
+        let error = app_state.set_dedupe((4, 0), 2, Some(Dedupe::new(1000, DedupeCompare::Id, false))).unwrap_err();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        assert!(!app_state.channel_runtime.contains_key(&(4, 0, 2)));
+        app_state.set_dedupe((4, 0), 1, None).unwrap();
+    }
+}
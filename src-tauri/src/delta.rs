@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use crate::frame::CanFrameEvent;
+use crate::memory;
+
+#[derive(Clone, Copy)]
+struct FrameTime {
+    device: Option<u32>,
+    host_us: u64,
+}
+
+impl FrameTime {
+    fn of(frame: &CanFrameEvent) -> Self {
+        Self {
+            device: frame.device_timestamp,
+            host_us: frame.host_timestamp_us,
+        }
+    }
+
+    /// 兩者都有裝置時間戳記 (0.1 ms，會回繞) 時以它計算，否則用主機時間；主機時間同一批次相同，只能當退路
+    fn ms_since(&self, earlier: &FrameTime) -> f64 {
+        match (self.device, earlier.device) {
+            (Some(now), Some(then)) => now.wrapping_sub(then) as f64 / 10.0,
+            _ => self.host_us.saturating_sub(earlier.host_us) as f64 / 1000.0,
+        }
+    }
+}
+
+/// 接收串流的 Δt：距前一個訊框與距同一 ID 前一個訊框的時間。
+/// 跟著接收執行緒存活，串流重新啟動時重新計算；環形緩衝被清空後 (epoch 改變) 也從頭計算。
+/// 最多記住 max_ids 個 ID，被移除的 ID 下一個訊框的 delta_same_id_ms 為 None
+#[derive(Default)]
+pub struct DeltaTimes {
+    epoch: u64,
+    last: Option<FrameTime>,
+    last_by_id: HashMap<(u32, bool), FrameTime>,
+}
+
+impl DeltaTimes {
+    pub fn annotate(&mut self, epoch: u64, max_ids: usize, frames: &mut [CanFrameEvent]) {
+        if epoch != self.epoch {
+            self.epoch = epoch;
+            self.last = None;
+            self.last_by_id.clear();
+        }
+        for frame in frames {
+            let time = FrameTime::of(frame);
+            frame.delta_ms = self.last.map(|last| time.ms_since(&last));
+            frame.delta_same_id_ms = self
+                .last_by_id
+                .insert((frame.id, frame.extended), time)
+                .map(|last| time.ms_since(&last));
+            self.last = Some(time);
+        }
+        memory::evict_least_recent(&mut self.last_by_id, max_ids, |time| time.host_us);
+    }
+}
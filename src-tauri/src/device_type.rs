@@ -0,0 +1,6 @@
+use can_core::device_type::{known_types, DeviceTypeInfo};
+
+#[tauri::command]
+pub fn list_device_types() -> Vec<DeviceTypeInfo> {
+    known_types()
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::StateMutex;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Nibble {
+    Low,
+    High,
+}
+
+/// 存活計數器的位置；nibble 省略時佔整個位元組
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CounterSpec {
+    pub byte: usize,
+    #[serde(default)]
+    pub nibble: Option<Nibble>,
+    /// 省略時 nibble 為 16、整個位元組為 256
+    #[serde(default)]
+    pub modulo: Option<u16>,
+}
+
+impl CounterSpec {
+    fn modulo(&self) -> u16 {
+        let max = if self.nibble.is_some() { 16 } else { 256 };
+        self.modulo.filter(|&m| m > 0).unwrap_or(max).min(max)
+    }
+
+    fn write(&self, data: &mut [u8], value: u16) {
+        let value = (value % self.modulo()) as u8;
+        data[self.byte] = match self.nibble {
+            Some(Nibble::Low) => (data[self.byte] & 0xF0) | (value & 0x0F),
+            Some(Nibble::High) => (data[self.byte] & 0x0F) | (value << 4),
+            None => value,
+        };
+    }
+}
+
+/// CRC-8，預設為 SAE J1850 (poly 0x1D、init 0xFF、xor_out 0xFF)
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CrcSpec {
+    pub byte: usize,
+    #[serde(default = "default_poly")]
+    pub poly: u8,
+    #[serde(default = "default_ff")]
+    pub init: u8,
+    #[serde(default = "default_ff")]
+    pub xor_out: u8,
+    /// 計算範圍 [start, end)；省略時為 CRC 位元組以外的所有位元組
+    #[serde(default)]
+    pub range: Option<[usize; 2]>,
+}
+
+fn default_poly() -> u8 {
+    0x1D
+}
+
+fn default_ff() -> u8 {
+    0xFF
+}
+
+impl CrcSpec {
+    fn compute(&self, data: &[u8]) -> u8 {
+        let bytes: Vec<u8> = match self.range {
+            Some([start, end]) => data[start.min(data.len())..end.min(data.len())].to_vec(),
+            None => data
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| i != self.byte)
+                .map(|(_, &b)| b)
+                .collect(),
+        };
+        let mut crc = self.init;
+        for byte in bytes {
+            crc ^= byte;
+            for _ in 0..8 {
+                crc = if crc & 0x80 != 0 { (crc << 1) ^ self.poly } else { crc << 1 };
+            }
+        }
+        crc ^ self.xor_out
+    }
+}
+
+/// 週期訊框與 transmit_signals 的 E2E 保護；先寫入計數器再計算 CRC
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct E2eSpec {
+    pub counter: Option<CounterSpec>,
+    pub crc: Option<CrcSpec>,
+}
+
+impl E2eSpec {
+    /// 確認指定的位元組都在 len 之內
+    pub fn check_fits(&self, len: usize) -> Result<(), String> {
+        let bytes = self.counter.iter().map(|c| c.byte).chain(self.crc.iter().map(|c| c.byte));
+        let range_end = self.crc.as_ref().and_then(|c| c.range).map(|[start, end]| (start, end));
+        for byte in bytes {
+            if byte >= len {
+                return Err(format!("E2E byte {} is outside the {}-byte payload", byte, len));
+            }
+        }
+        match range_end {
+            Some((start, end)) if start > end || end > len => Err(format!("invalid CRC range {}..{}", start, end)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, data: &mut [u8], counter: u16) {
+        if self.check_fits(data.len()).is_err() {
+            return;
+        }
+        if let Some(spec) = &self.counter {
+            spec.write(data, counter);
+        }
+        if let Some(spec) = &self.crc {
+            data[spec.byte] = spec.compute(data);
+        }
+    }
+
+    /// 計數器下一個值
+    pub fn next_counter(&self, counter: u16) -> u16 {
+        self.counter.as_ref().map_or(0, |spec| (counter + 1) % spec.modulo())
+    }
+
+    /// 收到的訊框 CRC 是否正確；沒有 CRC 設定或長度不足時為 None
+    pub fn crc_valid(&self, data: &[u8]) -> Option<bool> {
+        let spec = self.crc.as_ref()?;
+        self.check_fits(data.len()).ok()?;
+        Some(data[spec.byte] == spec.compute(data))
+    }
+}
+
+/// 接收端要驗證的 ID 與其 E2E 設定
+#[derive(Default)]
+pub struct E2eChecks {
+    checks: HashMap<(u32, bool), E2eSpec>,
+}
+
+impl E2eChecks {
+    pub fn annotate(&self, frames: &mut [CanFrameEvent]) {
+        if self.checks.is_empty() {
+            return;
+        }
+        for frame in frames {
+            if let Some(spec) = self.checks.get(&(frame.id, frame.extended)) {
+                frame.crc_valid = spec.crc_valid(&frame.data);
+            }
+        }
+    }
+}
+
+/// 設定收到此 ID 時以 spec 驗證 CRC，結果放在訊框事件的 crc_valid；spec 為 None 時取消
+#[tauri::command]
+pub fn set_e2e_check(
+    id: u32,
+    extended: Option<bool>,
+    spec: Option<E2eSpec>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let checks = state.lock().map_err(|_| "Failed to lock state")?.e2e_checks.clone();
+    let mut checks = checks.lock().map_err(|_| "Failed to lock E2E checks")?;
+    let key = (id, extended.unwrap_or(id > 0x7FF));
+    match spec {
+        Some(spec) => {
+            spec.check_fits(8)?;
+            checks.checks.insert(key, spec);
+            Ok(format!("E2E check enabled for 0x{:X}", id))
+        }
+        None => {
+            checks.checks.remove(&key);
+            Ok(format!("E2E check removed for 0x{:X}", id))
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::ring_buffer::BufferedFrame;
+use crate::stats::ChannelCounters;
+use crate::{invalid_argument, DeviceType, StateMutex};
+
+const DEFAULT_QUEUE_CAPACITY: usize = 5000;
+/// 每秒最多送出的 can-data 事件數；超過時訊框在佇列中等待
+const DEFAULT_MAX_RATE: u32 = 5000;
+/// 最後一次丟棄後維持這麼久沒有再丟棄，才視為壓力解除
+const PRESSURE_HOLD: Duration = Duration::from_secs(1);
+/// 累積的送出額度上限，避免安靜一段時間後一次送出大量事件
+const MAX_BURST: Duration = Duration::from_millis(100);
+
+/// 佇列已滿時的處理方式
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    #[default]
+    DropOldest,
+    DropNewest,
+    /// 改為每個 ID 只保留最新一筆，直到壓力解除
+    Coalesce,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct BackpressureConfig {
+    pub capacity: usize,
+    pub max_rate: u32,
+    pub policy: DropPolicy,
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            max_rate: DEFAULT_MAX_RATE,
+            policy: DropPolicy::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct BackpressureEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    /// true 表示開始丟棄訊框，false 表示壓力已解除
+    pub active: bool,
+    pub policy: DropPolicy,
+    pub queue_len: usize,
+    pub capacity: usize,
+    /// 此通道累計丟棄的訊框數
+    pub frames_dropped: u64,
+}
+
+/// 接收迴圈與 can-data 事件之間的有界佇列：以 max_rate 送出，佇列滿時依 policy 丟棄，
+/// 丟棄的數量計入 ChannelCounters::frames_dropped
+pub struct EmitQueue {
+    key: (u32, u32),
+    channel: u32,
+    counters: Arc<ChannelCounters>,
+    queue: VecDeque<BufferedFrame>,
+    /// Coalesce 模式下每個 ID 最新的一筆
+    latest: HashMap<(u32, bool), BufferedFrame>,
+    coalescing: bool,
+    pressure: bool,
+    last_drop: Option<Instant>,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl EmitQueue {
+    pub fn new(key: (u32, u32), channel: u32, counters: Arc<ChannelCounters>) -> Self {
+        Self {
+            key,
+            channel,
+            counters,
+            queue: VecDeque::new(),
+            latest: HashMap::new(),
+            coalescing: false,
+            pressure: false,
+            last_drop: None,
+            tokens: 0.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty() && self.latest.is_empty()
+    }
+
+    /// 暫停送出時清空；暫停期間的訊框在恢復時由環形緩衝補送
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.latest.clear();
+        self.coalescing = false;
+    }
+
+    /// 放入一批訊框；佇列因此開始丟棄時回傳 backpressure 事件
+    pub fn push(&mut self, frames: Vec<BufferedFrame>, config: &BackpressureConfig) -> Option<BackpressureEvent> {
+        let mut dropped = 0;
+        let mut overflowed = false;
+        for frame in frames {
+            if self.coalescing {
+                if self.latest.insert((frame.frame.id, frame.frame.extended), frame).is_some() {
+                    dropped += 1;
+                }
+                continue;
+            }
+            if self.queue.len() < config.capacity {
+                self.queue.push_back(frame);
+                continue;
+            }
+            overflowed = true;
+            match config.policy {
+                DropPolicy::DropOldest => {
+                    self.queue.pop_front();
+                    self.queue.push_back(frame);
+                    dropped += 1;
+                }
+                DropPolicy::DropNewest => dropped += 1,
+                DropPolicy::Coalesce => {
+                    self.coalescing = true;
+                    let backlog = self.queue.len();
+                    for queued in self.queue.drain(..).chain(std::iter::once(frame)) {
+                        self.latest.insert((queued.frame.id, queued.frame.extended), queued);
+                    }
+                    dropped += (backlog + 1 - self.latest.len()) as u64;
+                }
+            }
+        }
+        if dropped == 0 && !overflowed {
+            return None;
+        }
+        self.last_drop = Some(Instant::now());
+        self.counters.frames_dropped.fetch_add(dropped, Ordering::Relaxed);
+        if self.pressure {
+            return None;
+        }
+        self.pressure = true;
+        Some(self.event(true, config))
+    }
+
+    /// 依送出速率取出這一輪可以送出的訊框；壓力解除時一併回傳 backpressure 事件
+    pub fn take_ready(&mut self, config: &BackpressureConfig) -> (Vec<BufferedFrame>, Option<BackpressureEvent>) {
+        let elapsed = self.last_refill.elapsed().min(MAX_BURST);
+        self.last_refill = Instant::now();
+        let max_tokens = (config.max_rate as f64 * MAX_BURST.as_secs_f64()).max(1.0);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * config.max_rate as f64).min(max_tokens);
+        let budget = self.tokens.floor() as usize;
+        let mut ready: Vec<BufferedFrame> = self.queue.drain(..budget.min(self.queue.len())).collect();
+        if self.coalescing && ready.len() < budget {
+            let mut latest: Vec<BufferedFrame> = self.latest.drain().map(|(_, frame)| frame).collect();
+            latest.sort_by_key(|f| f.seq);
+            let rest = latest.split_off((budget - ready.len()).min(latest.len()));
+            ready.extend(latest);
+            self.latest = rest.into_iter().map(|f| ((f.frame.id, f.frame.extended), f)).collect();
+        }
+        self.tokens = (self.tokens - ready.len() as f64).max(0.0);
+        let calm = self.last_drop.is_none_or(|t| t.elapsed() >= PRESSURE_HOLD);
+        if self.coalescing && self.latest.is_empty() && calm {
+            self.coalescing = false;
+        }
+        let relieved = self.pressure && calm && !self.coalescing && self.queue.len() < config.capacity / 2;
+        if relieved {
+            self.pressure = false;
+        }
+        (ready, relieved.then(|| self.event(false, config)))
+    }
+
+    fn event(&self, active: bool, config: &BackpressureConfig) -> BackpressureEvent {
+        BackpressureEvent {
+            dev_type: self.key.0,
+            dev_index: self.key.1,
+            channel: self.channel,
+            active,
+            policy: config.policy,
+            queue_len: self.queue.len() + self.latest.len(),
+            capacity: config.capacity,
+            frames_dropped: self.counters.frames_dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 設定通道 can-data 事件的佇列大小、每秒最多送出的事件數與佇列滿時的處理方式；省略的參數維持原值
+#[tauri::command]
+pub fn set_event_backpressure(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    capacity: Option<usize>,
+    max_rate: Option<u32>,
+    policy: Option<DropPolicy>,
+    state: State<Arc<StateMutex>>,
+) -> Result<BackpressureConfig, String> {
+    if capacity == Some(0) {
+        return Err(invalid_argument("capacity", "must be at least 1"));
+    }
+    if max_rate == Some(0) {
+        return Err(invalid_argument("max_rate", "must be at least 1"));
+    }
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let key = device.key();
+    let emission = app_state.emission_control(key, channel);
+    let mut config = emission.backpressure.lock().map_err(|_| "Failed to lock backpressure config")?;
+    config.capacity = capacity.unwrap_or(config.capacity);
+    config.max_rate = max_rate.unwrap_or(config.max_rate);
+    config.policy = policy.unwrap_or(config.policy);
+    Ok(*config)
+}
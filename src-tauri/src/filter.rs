@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::frame::CanFrameEvent;
+use crate::{reinit_channel, run_blocking, DeviceType, StateMutex};
+
+const PRESET_FILE: &str = "filter_presets.json";
+
+/// SJA1000 驗收濾波器，寫入 VCI_InitConfig
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct HardwareFilter {
+    pub acc_code: u32,
+    pub acc_mask: u32,
+    /// 1 = 雙濾波，2 = 單濾波
+    pub filter: u8,
+}
+
+/// 接收迴圈在其他處理之前套用的軟體 ID 過濾
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SoftwareFilter {
+    /// 只保留這些 ID；省略時全部保留
+    pub allow_ids: Option<HashSet<u32>>,
+    pub block_ids: HashSet<u32>,
+}
+
+impl SoftwareFilter {
+    fn accepts(&self, frame: &CanFrameEvent) -> bool {
+        self.allow_ids.as_ref().is_none_or(|ids| ids.contains(&frame.id)) && !self.block_ids.contains(&frame.id)
+    }
+}
+
+/// 設定檔中一個通道的軟體過濾
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SavedSoftwareFilter {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub filter: SoftwareFilter,
+}
+
+/// 各 (dev_type, dev_index, channel) 的軟體過濾
+#[derive(Default)]
+pub struct SoftwareFilters {
+    channels: HashMap<(u32, u32, u32), SoftwareFilter>,
+}
+
+impl SoftwareFilters {
+    pub fn snapshot(&self) -> Vec<SavedSoftwareFilter> {
+        let mut saved: Vec<SavedSoftwareFilter> = self
+            .channels
+            .iter()
+            .map(|(&(dev_type, dev_index, channel), filter)| SavedSoftwareFilter {
+                dev_type,
+                dev_index,
+                channel,
+                filter: filter.clone(),
+            })
+            .collect();
+        saved.sort_by_key(|f| (f.dev_type, f.dev_index, f.channel));
+        saved
+    }
+
+    /// 以 filters 取代所有通道的軟體過濾
+    pub fn replace(&mut self, filters: Vec<SavedSoftwareFilter>) {
+        self.channels = filters.into_iter().map(|f| ((f.dev_type, f.dev_index, f.channel), f.filter)).collect();
+    }
+
+    pub fn apply(&self, key: (u32, u32), channel: u32, frames: &mut Vec<CanFrameEvent>) {
+        if let Some(filter) = self.channels.get(&(key.0, key.1, channel)) {
+            frames.retain(|frame| filter.accepts(frame));
+        }
+    }
+}
+
+/// 同時包含硬體與軟體兩層過濾；套用時兩層一起設定
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct FilterPreset {
+    pub hardware: Option<HardwareFilter>,
+    pub software: Option<SoftwareFilter>,
+}
+
+fn preset_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))?;
+    Ok(dir.join(PRESET_FILE))
+}
+
+/// 讀取預設集檔案；檔案不存在時為空，內容損毀時回報錯誤而不是覆寫
+fn load_presets(app_handle: &tauri::AppHandle) -> Result<BTreeMap<String, FilterPreset>, String> {
+    let path = preset_path(app_handle)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&text).map_err(|e| format!("Filter preset file {} is corrupt: {}", path.display(), e))
+}
+
+fn store_presets(app_handle: &tauri::AppHandle, presets: &BTreeMap<String, FilterPreset>) -> Result<(), String> {
+    let path = preset_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(presets).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// 直接設定通道的軟體過濾；filter 為 None 時取消
+#[tauri::command]
+pub fn set_software_filter(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    filter: Option<SoftwareFilter>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let (slot, filters) = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+        device.check_channel(channel)?;
+        ((device.dev_type, device.dev_index, channel), app_state.software_filters.clone())
+    };
+    let mut filters = filters.lock().map_err(|_| "Failed to lock filters")?;
+    match filter {
+        Some(filter) => {
+            filters.channels.insert(slot, filter);
+            Ok(format!("Software filter on CAN{} set", channel + 1))
+        }
+        None => {
+            filters.channels.remove(&slot);
+            Ok(format!("Software filter on CAN{} cleared", channel + 1))
+        }
+    }
+}
+
+#[tauri::command]
+pub fn save_filter_preset(name: String, filter_definition: FilterPreset, app_handle: tauri::AppHandle) -> Result<String, String> {
+    let mut presets = load_presets(&app_handle)?;
+    presets.insert(name.clone(), filter_definition);
+    store_presets(&app_handle, &presets)?;
+    Ok(format!("Filter preset '{}' saved", name))
+}
+
+#[tauri::command]
+pub fn list_filter_presets(app_handle: tauri::AppHandle) -> Result<BTreeMap<String, FilterPreset>, String> {
+    load_presets(&app_handle)
+}
+
+#[tauri::command]
+pub fn delete_filter_preset(name: String, app_handle: tauri::AppHandle) -> Result<String, String> {
+    let mut presets = load_presets(&app_handle)?;
+    presets
+        .remove(&name)
+        .ok_or_else(|| format!("filter preset '{}' not found", name))?;
+    store_presets(&app_handle, &presets)?;
+    Ok(format!("Filter preset '{}' deleted", name))
+}
+
+/// 套用預設集：有硬體過濾時以原本的鮑率與模式重新初始化通道 (原本已啟動則重新啟動)，並設定軟體過濾
+#[tauri::command]
+pub async fn apply_filter_preset(
+    name: String,
+    channel: u32,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<String, String> {
+    let preset = load_presets(&app_handle)?
+        .remove(&name)
+        .ok_or_else(|| format!("filter preset '{}' not found", name))?;
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let (key, channel_state) = {
+            let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+            device.check_channel(channel)?;
+            (device.key(), device.channels.get(&channel).copied())
+        };
+        if let Some(hardware) = preset.hardware {
+            let channel_state = channel_state.ok_or_else(|| format!("CAN{} is not initialized", channel + 1))?;
+            let mut config = channel_state.config;
+            config.acc_code = hardware.acc_code;
+            config.acc_mask = hardware.acc_mask;
+            config.filter = hardware.filter;
+            reinit_channel(&state, key, channel, config, channel_state.started)?;
+        }
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        if preset.hardware.is_some() {
+            app_state.save_settings(key);
+        }
+        let mut filters = app_state.software_filters.lock().map_err(|_| "Failed to lock filters")?;
+        match preset.software {
+            Some(software) => filters.channels.insert((key.0, key.1, channel), software),
+            None => filters.channels.remove(&(key.0, key.1, channel)),
+        };
+        Ok(format!("Filter preset '{}' applied to CAN{}", name, channel + 1))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VciCanObj;
+
+    fn frames(key: (u32, u32), ids: &[u32]) -> Vec<CanFrameEvent> {
+        ids.iter()
+            .map(|&id| CanFrameEvent::from_raw(key, 0, &VciCanObj { id, ..Default::default() }, 0))
+            .collect()
+    }
+
+    #[test]
+    fn a_software_filter_applies_only_to_its_own_device() {
+        let mut filters = SoftwareFilters::default();
+        filters.replace(vec![SavedSoftwareFilter {
+            dev_type: 4,
+            dev_index: 0,
+            channel: 0,
+            filter: SoftwareFilter {
+                allow_ids: None,
+                block_ids: HashSet::from([0x100]),
+            },
+        }]);
+
+        let mut first = frames((4, 0), &[0x100, 0x200]);
+        filters.apply((4, 0), 0, &mut first);
+        assert_eq!(first.iter().map(|f| f.id).collect::<Vec<_>>(), [0x200]);
+        let mut second = frames((4, 1), &[0x100, 0x200]);
+        filters.apply((4, 1), 0, &mut second);
+        assert_eq!(second.len(), 2);
+        assert_eq!(filters.snapshot()[0].dev_index, 0);
+    }
+}
@@ -0,0 +1,258 @@
+pub use can_core::host_timestamp_us;
+
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dbc::DecodedMessage;
+use crate::gateway::GatewayHop;
+use crate::j1939::J1939Info;
+use crate::timestamp::TimestampSource;
+use crate::{invalid_argument, VciCanObj};
+
+/// 程式啟動後的單調時鐘 (微秒)，不受系統時間調整影響；用於量測傳送時間
+pub fn monotonic_us() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
+
+/// 送出訊框的 VCI_Transmit 呼叫時間 (monotonic_us)；同一次呼叫送出的訊框共用呼叫時間
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct TxTiming {
+    /// 通道的傳送序號，傳送命令的回傳值與 TX 回送的訊框相同，可用來對應
+    pub tx_seq: u64,
+    pub call_start_us: u64,
+    pub call_end_us: u64,
+}
+
+/// 傳給前端的 CAN 訊框
+#[derive(Serialize, Clone, Debug)]
+pub struct CanFrameEvent {
+    /// 收到或送出此訊框的裝置，多裝置、多通道同時串流時用來區分來源
+    pub device_type: u32,
+    pub device_index: u32,
+    pub channel: u32,
+    pub id: u32,
+    pub extended: bool,
+    pub remote: bool,
+    pub dlc: u8,
+    pub data: Vec<u8>,
+    /// 裝置時間戳記 (單位 0.1 ms)，僅在 time_flag = 1 時有效
+    pub device_timestamp: Option<u32>,
+    /// 收到訊框時的主機時間 (UNIX epoch 起算的微秒)
+    pub host_timestamp_us: u64,
+    /// 依通道的 timestamp_mode 選擇的時間 (UNIX epoch 起算的微秒)；硬體時間戳記以接收開始時對齊主機時間
+    pub timestamp_us: u64,
+    pub timestamp_source: TimestampSource,
+    /// 原始的 time_stamp，不論 time_flag；hardware 模式由此換算
+    #[serde(skip)]
+    pub device_counter: u32,
+    /// 送出的訊框回送到事件流時為 tx
+    pub direction: Direction,
+    /// 訊框的來源：接收、手動傳送、週期、重播、自動回應或閘道轉送
+    pub provenance: Provenance,
+    /// 收發當時通道的設定世代，以 get_config_history 查出硬體過濾與模式；0 表示通道未經本程式初始化
+    pub config_generation: u64,
+    /// 距同一接收串流前一個訊框的時間；串流的第一個訊框與 TX 回送為 None
+    pub delta_ms: Option<f64>,
+    /// 距同一 ID 前一個訊框的時間
+    pub delta_same_id_ms: Option<f64>,
+    /// 載入 ID 名稱對照表且 ID 在表中時的名稱
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 載入 DBC 且 ID 符合時的訊號解碼結果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<DecodedMessage>,
+    /// 通道啟用 J1939 模式時，擴展 ID 拆解後的欄位
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub j1939: Option<J1939Info>,
+    /// 由閘道轉送到此通道的訊框
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<GatewayHop>,
+    /// 此 ID 設定了 E2E 驗證時，CRC 是否正確
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crc_valid: Option<bool>,
+    /// 接收啟用 debug_mode 時，DLL 回傳的原始 VCI_CAN_OBJ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<VciCanObj>,
+    /// 送出的訊框的傳送序號與 VCI_Transmit 呼叫時間
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_timing: Option<TxTiming>,
+}
+
+impl CanFrameEvent {
+    pub fn from_raw(key: (u32, u32), channel: u32, can_obj: &VciCanObj, host_timestamp_us: u64) -> Self {
+        // 遙控幀的 DLC 只是請求的長度，沒有資料；DLC 0 的資料幀同樣回報空陣列
+        let len = if can_obj.remote_flag != 0 {
+            0
+        } else {
+            (can_obj.data_len as usize).min(can_obj.data.len())
+        };
+        Self {
+            device_type: key.0,
+            device_index: key.1,
+            channel,
+            id: can_obj.id,
+            extended: can_obj.extern_flag != 0,
+            remote: can_obj.remote_flag != 0,
+            dlc: can_obj.data_len,
+            data: can_obj.data[..len].to_vec(),
+            device_timestamp: (can_obj.time_flag != 0).then_some(can_obj.time_stamp),
+            host_timestamp_us,
+            timestamp_us: host_timestamp_us,
+            timestamp_source: TimestampSource::Host,
+            device_counter: can_obj.time_stamp,
+            direction: Direction::Rx,
+            provenance: Provenance::Rx,
+            config_generation: 0,
+            delta_ms: None,
+            delta_same_id_ms: None,
+            name: None,
+            decoded: None,
+            j1939: None,
+            gateway: None,
+            crc_valid: None,
+            raw: None,
+            tx_timing: None,
+        }
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn to_can_obj(&self) -> VciCanObj {
+        let mut can_obj = VciCanObj {
+            id: self.id,
+            remote_flag: self.remote as u8,
+            extern_flag: self.extended as u8,
+            data_len: self.dlc,
+            ..Default::default()
+        };
+        can_obj.data[..self.data.len()].copy_from_slice(&self.data);
+        can_obj
+    }
+}
+
+/// 前端指定要送出的訊框；extended 省略時 ID 大於 0x7FF 即視為擴展幀。
+/// 所有傳送路徑都經由 checked() 轉成 VciCanObj，不合法的內容不會送到 DLL
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FrameInput {
+    pub id: u32,
+    #[serde(default)]
+    pub extended: Option<bool>,
+    #[serde(default)]
+    pub remote: bool,
+    /// 省略時資料幀為 data 的長度、遙控幀為 0。指定時可以大於 data 的長度 (不足的位元組以 padding 補齊)，
+    /// 但不能小於；DLC 0 的資料幀 data 為空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dlc: Option<u8>,
+    #[serde(default)]
+    pub data: Vec<u8>,
+    /// dlc 大於 data 長度時補上的位元組，省略時為 0x00
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<u8>,
+}
+
+/// 標準幀 ID 最大 0x7FF，擴展幀最大 0x1FFFFFFF
+pub fn check_id(id: u32, extended: bool) -> Result<(), String> {
+    if id > 0x1FFF_FFFF {
+        return Err(format!("CAN ID 0x{:X} exceeds 29 bits", id));
+    }
+    if !extended && id > 0x7FF {
+        return Err(format!("CAN ID 0x{:X} exceeds 11 bits; set extended for 29-bit IDs", id));
+    }
+    Ok(())
+}
+
+impl FrameInput {
+    pub fn to_can_obj(&self) -> Result<VciCanObj, String> {
+        self.checked("frame")
+    }
+
+    /// 驗證並轉成 VciCanObj；field 為錯誤訊息中此訊框的名稱 (例如 "frames[3]")
+    pub fn checked(&self, field: &str) -> Result<VciCanObj, String> {
+        if self.data.len() > 8 {
+            return Err(invalid_argument(
+                &format!("{}.data", field),
+                format!("{} bytes exceeds the 8-byte maximum", self.data.len()),
+            ));
+        }
+        let extended = self.extended.unwrap_or(self.id > 0x7FF);
+        check_id(self.id, extended).map_err(|reason| invalid_argument(&format!("{}.id", field), reason))?;
+        let dlc = match self.dlc {
+            Some(dlc) if dlc > 8 => {
+                return Err(invalid_argument(&format!("{}.dlc", field), format!("{} exceeds 8", dlc)));
+            }
+            Some(_) if self.remote && !self.data.is_empty() => {
+                return Err(invalid_argument(&format!("{}.data", field), "remote frames carry no data"));
+            }
+            Some(dlc) if !self.remote && (dlc as usize) < self.data.len() => {
+                return Err(invalid_argument(
+                    &format!("{}.dlc", field),
+                    format!("{} is shorter than the {} data bytes", dlc, self.data.len()),
+                ));
+            }
+            Some(dlc) => dlc,
+            None if self.remote => 0,
+            None => self.data.len() as u8,
+        };
+        let mut data = [0u8; 8];
+        if !self.remote {
+            data[self.data.len()..dlc as usize].fill(self.padding.unwrap_or(0x00));
+            data[..self.data.len()].copy_from_slice(&self.data);
+        }
+        Ok(VciCanObj {
+            id: self.id,
+            remote_flag: self.remote as u8,
+            extern_flag: extended as u8,
+            data_len: dlc,
+            data,
+            ..Default::default()
+        })
+    }
+}
+
+/// 批次傳送時驗證每個訊框，錯誤訊息標出是第幾個
+pub fn checked_frames(field: &str, frames: &[FrameInput]) -> Result<Vec<VciCanObj>, String> {
+    frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| frame.checked(&format!("{}[{}]", field, index)))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+/// 環形緩衝與記錄檔中的訊框來源
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Provenance {
+    Rx,
+    TxManual,
+    TxPeriodic,
+    TxReplay,
+    TxAutoresponse,
+    Gateway,
+}
+
+impl Provenance {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Provenance::Rx => "rx",
+            Provenance::TxManual => "tx-manual",
+            Provenance::TxPeriodic => "tx-periodic",
+            Provenance::TxReplay => "tx-replay",
+            Provenance::TxAutoresponse => "tx-autoresponse",
+            Provenance::Gateway => "gateway",
+        }
+    }
+}
+
@@ -0,0 +1,260 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::frame::{host_timestamp_us, FrameInput};
+use crate::supervisor;
+use crate::tx_limit::transmit_paced;
+use crate::{DeviceType, StateMutex};
+
+const DEFAULT_FRAMES_PER_SEC: u32 = 100;
+const DEFAULT_DRY_RUN_FRAMES: usize = 20;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+const STOP_POLL: Duration = Duration::from_millis(10);
+
+/// 可重現的亂數產生器 (xorshift64*)；相同的 seed 產生相同的訊框序列
+struct FuzzRng(u64);
+
+impl FuzzRng {
+    fn new(seed: u64) -> Self {
+        // splitmix64 打散 seed，避免 0 或相近的 seed 產生相似的序列
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Self((z ^ (z >> 31)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// [low, high] 內的整數
+    fn range(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next() % (high - low + 1)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum FuzzIds {
+    List { ids: Vec<u32> },
+    /// 包含 start 與 end
+    Range { start: u32, end: u32 },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct FuzzOptions {
+    pub ids: FuzzIds,
+    /// 省略時依 ID 是否大於 0x7FF 判斷
+    #[serde(default)]
+    pub extended: Option<bool>,
+    #[serde(default)]
+    pub dlc_min: Option<u8>,
+    #[serde(default)]
+    pub dlc_max: Option<u8>,
+    /// 第 i 個位元組為 Some 時固定為該值，None 或超出長度時隨機
+    #[serde(default)]
+    pub fixed_bytes: Vec<Option<u8>>,
+    #[serde(default)]
+    pub frames_per_sec: Option<u32>,
+    /// count 與 duration_ms 先達到者結束；都省略時持續到 stop_fuzzing
+    #[serde(default)]
+    pub count: Option<u64>,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// 省略時以目前時間產生，並在結果中回報以便重現
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// 只產生並回傳前 dry_run_frames 個訊框，不送出
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub dry_run_frames: Option<usize>,
+}
+
+struct FrameGenerator {
+    rng: FuzzRng,
+    ids: FuzzIds,
+    extended: Option<bool>,
+    dlc_min: u8,
+    dlc_max: u8,
+    fixed_bytes: Vec<Option<u8>>,
+}
+
+impl FrameGenerator {
+    fn new(options: &FuzzOptions, seed: u64) -> Result<Self, String> {
+        let (dlc_min, dlc_max) = (options.dlc_min.unwrap_or(0), options.dlc_max.unwrap_or(8));
+        if dlc_min > dlc_max || dlc_max > 8 {
+            return Err(format!("invalid DLC range {}..={}", dlc_min, dlc_max));
+        }
+        match &options.ids {
+            FuzzIds::List { ids } if ids.is_empty() => return Err("ID list is empty".into()),
+            FuzzIds::Range { start, end } if start > end => return Err(format!("invalid ID range 0x{:X}..=0x{:X}", start, end)),
+            _ => {}
+        }
+        Ok(Self {
+            rng: FuzzRng::new(seed),
+            ids: options.ids.clone(),
+            extended: options.extended,
+            dlc_min,
+            dlc_max,
+            fixed_bytes: options.fixed_bytes.clone(),
+        })
+    }
+
+    fn next_frame(&mut self) -> FrameInput {
+        let id = match &self.ids {
+            FuzzIds::List { ids } => ids[self.rng.range(0, ids.len() as u64 - 1) as usize],
+            FuzzIds::Range { start, end } => self.rng.range(*start as u64, *end as u64) as u32,
+        };
+        let dlc = self.rng.range(self.dlc_min as u64, self.dlc_max as u64) as usize;
+        let mut data = vec![0u8; dlc];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = match self.fixed_bytes.get(i) {
+                Some(Some(fixed)) => *fixed,
+                _ => self.rng.next() as u8,
+            };
+        }
+        FrameInput {
+            id,
+            extended: self.extended,
+            remote: false,
+            dlc: None,
+            data,
+            padding: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FuzzStart {
+    pub seed: u64,
+    /// dry_run 時產生的訊框
+    pub preview: Vec<FrameInput>,
+}
+
+#[derive(Serialize, Clone)]
+struct FuzzProgress {
+    frames_sent: u64,
+    elapsed_ms: u64,
+    total_frames: Option<u64>,
+}
+
+#[derive(Serialize, Clone)]
+struct FuzzFinished {
+    seed: u64,
+    frames_sent: u64,
+    elapsed_ms: u64,
+    error: Option<String>,
+}
+
+/// 依限制條件產生半隨機訊框並經由傳送路徑 (含速率限制與 TX 回送) 送出
+#[tauri::command]
+pub fn start_fuzzing(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    options: FuzzOptions,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<FuzzStart, String> {
+    let seed = options.seed.unwrap_or_else(host_timestamp_us);
+    let mut generator = FrameGenerator::new(&options, seed)?;
+    if options.dry_run {
+        let preview = (0..options.dry_run_frames.unwrap_or(DEFAULT_DRY_RUN_FRAMES))
+            .map(|_| generator.next_frame())
+            .collect();
+        return Ok(FuzzStart { seed, preview });
+    }
+    let frames_per_sec = options.frames_per_sec.unwrap_or(DEFAULT_FRAMES_PER_SEC);
+    if frames_per_sec == 0 {
+        return Err("frames_per_sec must be greater than 0".into());
+    }
+    let running = Arc::new(AtomicBool::new(true));
+    let key = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        if app_state.fuzzer.is_some() {
+            return Err("Fuzzing already running".into());
+        }
+        let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+        app_state.fuzzer = Some(running.clone());
+        key
+    };
+
+    let state = state.inner().clone();
+    let interval = Duration::from_secs_f64(1.0 / frames_per_sec as f64);
+    let duration = options.duration_ms.map(Duration::from_millis);
+    let total_frames = options.count;
+    std::thread::spawn(move || {
+        let started = Instant::now();
+        let mut next = started;
+        let mut last_progress = started;
+        let mut frames_sent = 0u64;
+        let mut error = None;
+        let finished = supervisor::guard("fuzz", format!("device {}:{} CAN{}", key.0, key.1, channel + 1), || {
+            while running.load(Ordering::SeqCst)
+                && total_frames.is_none_or(|total| frames_sent < total)
+                && duration.is_none_or(|duration| started.elapsed() < duration)
+            {
+                let now = Instant::now();
+                if now < next {
+                    std::thread::sleep((next - now).min(STOP_POLL));
+                    continue;
+                }
+                let result = generator
+                    .next_frame()
+                    .to_can_obj()
+                    .and_then(|can_obj| transmit_paced(&state, key, channel, &[can_obj], true));
+                if let Err(error_message) = result {
+                    error = Some(error_message);
+                    break;
+                }
+                frames_sent += 1;
+                next = (next + interval).max(now);
+                if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                    last_progress = Instant::now();
+                    let _ = app_handle.emit(
+                        "fuzz-progress",
+                        FuzzProgress {
+                            frames_sent,
+                            elapsed_ms: started.elapsed().as_millis() as u64,
+                            total_frames,
+                        },
+                    );
+                }
+            }
+        });
+        if finished.is_none() {
+            error = Some("fuzzer thread panicked".to_string());
+        }
+        if let Ok(mut app_state) = state.lock() {
+            if app_state.fuzzer.as_ref().is_some_and(|r| Arc::ptr_eq(r, &running)) {
+                app_state.fuzzer = None;
+            }
+        }
+        let _ = app_handle.emit(
+            "fuzz-finished",
+            FuzzFinished {
+                seed,
+                frames_sent,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                error,
+            },
+        );
+    });
+    Ok(FuzzStart { seed, preview: Vec::new() })
+}
+
+#[tauri::command]
+pub fn stop_fuzzing(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let running = app_state.fuzzer.take().ok_or("Fuzzing is not running")?;
+    running.store(false, Ordering::SeqCst);
+    Ok("Fuzzing stopped".into())
+}
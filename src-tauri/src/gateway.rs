@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::frame::{host_timestamp_us, CanFrameEvent, Direction, Provenance};
+use crate::ring_buffer::BufferedFrame;
+use crate::supervisor;
+use crate::tap::TapReceiver;
+use crate::{DeviceType, StateMutex};
+
+/// 雙向轉送時，在這段時間內從目的通道收到與剛轉送出去相同的訊框視為迴圈，不再轉回
+const LOOP_WINDOW: Duration = Duration::from_millis(100);
+/// 兩個通道輪流等待的時間片
+const POLL_SLICE: Duration = Duration::from_millis(1);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GatewayHop {
+    pub from_channel: u32,
+    pub to_channel: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdRemap {
+    pub match_id: u32,
+    pub new_id: u32,
+}
+
+/// 單一方向的轉送規則
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DirectionOptions {
+    /// 只轉送這些 ID；省略時全部轉送
+    pub allow_ids: Option<Vec<u32>>,
+    pub block_ids: Vec<u32>,
+    pub remap: Vec<IdRemap>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct GatewayOptions {
+    /// from_channel → to_channel
+    pub forward: DirectionOptions,
+    /// to_channel → from_channel (bidirectional 時)
+    pub reverse: DirectionOptions,
+}
+
+#[derive(Default)]
+struct DirectionCounters {
+    forwarded: AtomicU64,
+    filtered: AtomicU64,
+    loops_suppressed: AtomicU64,
+    errors: AtomicU64,
+}
+
+#[derive(Serialize)]
+pub struct DirectionStats {
+    pub from_channel: u32,
+    pub to_channel: u32,
+    pub forwarded: u64,
+    /// 被過濾或判定為迴圈而未轉送的訊框
+    pub dropped: u64,
+    pub loops_suppressed: u64,
+    pub errors: u64,
+}
+
+impl DirectionCounters {
+    fn stats(&self, from_channel: u32, to_channel: u32) -> DirectionStats {
+        let loops_suppressed = self.loops_suppressed.load(Ordering::Relaxed);
+        DirectionStats {
+            from_channel,
+            to_channel,
+            forwarded: self.forwarded.load(Ordering::Relaxed),
+            dropped: self.filtered.load(Ordering::Relaxed) + loops_suppressed,
+            loops_suppressed,
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub struct Gateway {
+    running: Arc<AtomicBool>,
+    from_channel: u32,
+    to_channel: u32,
+    bidirectional: bool,
+    forward: Arc<DirectionCounters>,
+    reverse: Arc<DirectionCounters>,
+}
+
+/// 一個轉送方向：來源通道的讀取端、規則，以及最近轉送出去的訊框 (用於偵測迴圈)
+struct Route {
+    from_channel: u32,
+    to_channel: u32,
+    tap: TapReceiver,
+    options: DirectionOptions,
+    counters: Arc<DirectionCounters>,
+    sent: VecDeque<(u32, bool, Vec<u8>, Instant)>,
+}
+
+impl Route {
+    fn forwarded_id(&self, frame: &CanFrameEvent) -> Option<u32> {
+        if self.options.allow_ids.as_ref().is_some_and(|ids| !ids.contains(&frame.id)) || self.options.block_ids.contains(&frame.id) {
+            return None;
+        }
+        Some(
+            self.options
+                .remap
+                .iter()
+                .find(|r| r.match_id == frame.id)
+                .map_or(frame.id, |r| r.new_id),
+        )
+    }
+
+    fn remember(&mut self, frame: &CanFrameEvent) {
+        self.sent.push_back((frame.id, frame.extended, frame.data.clone(), Instant::now()));
+    }
+
+    /// 這個訊框是否就是本方向剛送到該通道的訊框 (被接回來)
+    fn is_echo(&mut self, frame: &CanFrameEvent) -> bool {
+        while self.sent.front().is_some_and(|(_, _, _, at)| at.elapsed() > LOOP_WINDOW) {
+            self.sent.pop_front();
+        }
+        match self
+            .sent
+            .iter()
+            .position(|(id, extended, data, _)| *id == frame.id && *extended == frame.extended && *data == frame.data)
+        {
+            Some(index) => {
+                self.sent.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn forward(
+    state: &Arc<StateMutex>,
+    app_handle: &tauri::AppHandle,
+    key: (u32, u32),
+    route: &Route,
+    frame: &CanFrameEvent,
+    new_id: u32,
+) -> Option<CanFrameEvent> {
+    let mut forwarded = frame.clone();
+    forwarded.id = new_id;
+    forwarded.channel = route.to_channel;
+    forwarded.host_timestamp_us = host_timestamp_us();
+    forwarded.direction = Direction::Tx;
+    forwarded.provenance = Provenance::Gateway;
+    forwarded.device_timestamp = None;
+    forwarded.name = None;
+    forwarded.decoded = None;
+    forwarded.j1939 = None;
+    forwarded.gateway = Some(GatewayHop {
+        from_channel: route.from_channel,
+        to_channel: route.to_channel,
+    });
+    let result = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())
+        .and_then(|mut app_state| Ok((app_state.tx_path(key, route.to_channel)?, app_state.frame_buffer.clone())))
+        .and_then(|(tx_path, frame_buffer)| {
+            // 由下方帶著 gateway 標記送進事件流，不使用一般的 TX 回送
+            tx_path.transmit(&[forwarded.to_can_obj()], false)?;
+            Ok(frame_buffer)
+        });
+    match result {
+        Ok(frame_buffer) => {
+            route.counters.forwarded.fetch_add(1, Ordering::Relaxed);
+            // 轉送出去的訊框也放進環形緩衝區並送進一般的 can-data 事件流
+            if let Ok(mut ring) = frame_buffer.lock() {
+                let seq = ring.push(forwarded.clone());
+                let _ = app_handle.emit(
+                    "can-data",
+                    BufferedFrame {
+                        seq,
+                        frame: forwarded.clone(),
+                    },
+                );
+            }
+            Some(forwarded)
+        }
+        Err(_) => {
+            route.counters.errors.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub fn start_gateway(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    from_channel: u32,
+    to_channel: u32,
+    bidirectional: bool,
+    options: Option<GatewayOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    if from_channel == to_channel {
+        return Err("from_channel and to_channel must differ".into());
+    }
+    let options = options.unwrap_or_default();
+    let key = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        if app_state.gateway.is_some() {
+            return Err("Gateway already running".into());
+        }
+        app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key()
+    };
+    let forward_counters: Arc<DirectionCounters> = Arc::default();
+    let reverse_counters: Arc<DirectionCounters> = Arc::default();
+    let mut routes = vec![Route {
+        from_channel,
+        to_channel,
+        tap: TapReceiver::open(state.inner(), key, from_channel)?,
+        options: options.forward,
+        counters: forward_counters.clone(),
+        sent: VecDeque::new(),
+    }];
+    if bidirectional {
+        routes.push(Route {
+            from_channel: to_channel,
+            to_channel: from_channel,
+            tap: TapReceiver::open(state.inner(), key, to_channel)?,
+            options: options.reverse,
+            counters: reverse_counters.clone(),
+            sent: VecDeque::new(),
+        });
+    }
+    let running = Arc::new(AtomicBool::new(true));
+    state.lock().map_err(|_| "Failed to lock state")?.gateway = Some(Gateway {
+        running: running.clone(),
+        from_channel,
+        to_channel,
+        bidirectional,
+        forward: forward_counters,
+        reverse: reverse_counters,
+    });
+
+    let state = state.inner().clone();
+    std::thread::spawn(move || {
+        let finished = supervisor::guard("gateway", format!("CAN{} -> CAN{}", from_channel + 1, to_channel + 1), || {
+            while running.load(Ordering::SeqCst) {
+                for index in 0..routes.len() {
+                    let Some(frame) = routes[index].tap.recv_until(Instant::now() + POLL_SLICE) else {
+                        continue;
+                    };
+                    // 反方向剛送到這個通道的訊框不再轉回去
+                    if let Some(other) = routes.get_mut(1 - index) {
+                        if other.is_echo(&frame) {
+                            routes[index].counters.loops_suppressed.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                    }
+                    let route = &routes[index];
+                    let Some(new_id) = route.forwarded_id(&frame) else {
+                        route.counters.filtered.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    };
+                    if let Some(forwarded) = forward(&state, &app_handle, key, route, &frame, new_id) {
+                        routes[index].remember(&forwarded);
+                    }
+                }
+            }
+        });
+        if finished.is_none() {
+            if let Ok(mut app_state) = state.lock() {
+                if app_state.gateway.as_ref().is_some_and(|g| Arc::ptr_eq(&g.running, &running)) {
+                    app_state.gateway = None;
+                }
+            }
+        }
+    });
+    Ok(format!(
+        "Gateway CAN{} {} CAN{} started",
+        from_channel + 1,
+        if bidirectional { "<->" } else { "->" },
+        to_channel + 1
+    ))
+}
+
+#[tauri::command]
+pub fn stop_gateway(state: State<Arc<StateMutex>>) -> Result<Vec<DirectionStats>, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let gateway = app_state.gateway.take().ok_or("Gateway is not running")?;
+    gateway.running.store(false, Ordering::SeqCst);
+    Ok(gateway.stats())
+}
+
+#[tauri::command]
+pub fn get_gateway_stats(state: State<Arc<StateMutex>>) -> Result<Vec<DirectionStats>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    Ok(app_state.gateway.as_ref().ok_or("Gateway is not running")?.stats())
+}
+
+impl Gateway {
+    fn stats(&self) -> Vec<DirectionStats> {
+        let mut stats = vec![self.forward.stats(self.from_channel, self.to_channel)];
+        if self.bidirectional {
+            stats.push(self.reverse.stats(self.to_channel, self.from_channel));
+        }
+        stats
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::frame::{host_timestamp_us, monotonic_us};
+use crate::operation::OperationKind;
+use crate::receive::EventSink;
+use crate::{supervisor, AppState, StateMutex};
+
+/// 工作執行緒超過其正常週期再加上這段時間沒有完成一輪，即視為停滯 (例如 VCI_Receive 沒有返回)
+pub const STALL_MARGIN: Duration = Duration::from_secs(2);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// 等候 state 鎖的上限；超過表示持有鎖的命令或執行緒卡住
+const STATE_LOCK_WAIT: Duration = Duration::from_millis(100);
+/// last_alive_us 為此值時不檢查 (例如等待重新連線)
+const PAUSED: u64 = u64::MAX;
+
+struct PulseEntry {
+    role: &'static str,
+    context: String,
+    threshold_us: u64,
+    /// monotonic_us；每完成一輪更新
+    last_alive_us: AtomicU64,
+}
+
+/// 登錄中的工作執行緒；heartbeat 執行緒不經過 state 鎖也能檢查
+#[derive(Default)]
+pub struct Pulses {
+    next_id: AtomicU64,
+    entries: Mutex<HashMap<u64, Arc<PulseEntry>>>,
+}
+
+impl Pulses {
+    /// 登錄工作執行緒；threshold 為兩次 beat() 之間允許的最長時間。回傳值被 drop 時取消登錄
+    pub fn register(self: &Arc<Self>, role: &'static str, context: impl Into<String>, threshold: Duration) -> WorkerPulse {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let entry = Arc::new(PulseEntry {
+            role,
+            context: context.into(),
+            threshold_us: threshold.as_micros() as u64,
+            last_alive_us: AtomicU64::new(monotonic_us()),
+        });
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner).insert(id, entry.clone());
+        WorkerPulse {
+            pulses: self.clone(),
+            id,
+            entry,
+        }
+    }
+
+    /// 超過 threshold 沒有 beat() 的工作執行緒
+    pub fn stalled(&self) -> Vec<StalledWorker> {
+        let now = monotonic_us();
+        let mut stalled: Vec<StalledWorker> = self
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .filter_map(|entry| {
+                let last = entry.last_alive_us.load(Ordering::Relaxed);
+                let silent_us = now.saturating_sub(last);
+                (last != PAUSED && silent_us > entry.threshold_us).then(|| StalledWorker {
+                    role: entry.role,
+                    context: entry.context.clone(),
+                    silent_ms: silent_us / 1000,
+                    threshold_ms: entry.threshold_us / 1000,
+                })
+            })
+            .collect();
+        stalled.sort_by(|a, b| (a.role, &a.context).cmp(&(b.role, &b.context)));
+        stalled
+    }
+}
+
+/// 工作執行緒持有的一端；每完成一輪呼叫 beat()
+pub struct WorkerPulse {
+    pulses: Arc<Pulses>,
+    id: u64,
+    entry: Arc<PulseEntry>,
+}
+
+impl WorkerPulse {
+    pub fn beat(&self) {
+        self.entry.last_alive_us.store(monotonic_us(), Ordering::Relaxed);
+    }
+
+    /// 預期會長時間等待 (例如重新連線的退避) 時暫停檢查，下一次 beat() 恢復
+    pub fn pause(&self) {
+        self.entry.last_alive_us.store(PAUSED, Ordering::Relaxed);
+    }
+}
+
+impl Drop for WorkerPulse {
+    fn drop(&mut self) {
+        self.pulses.entries.lock().unwrap_or_else(PoisonError::into_inner).remove(&self.id);
+    }
+}
+
+impl AppState {
+    /// 工作執行緒在啟動時以此登錄
+    pub fn worker_pulses(&self) -> Arc<Pulses> {
+        self.pulses.clone()
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StalledWorker {
+    pub role: &'static str,
+    pub context: String,
+    /// 距離上一次完成一輪的時間
+    pub silent_ms: u64,
+    pub threshold_ms: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ActiveChannel {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ActiveOperation {
+    pub operation_id: u32,
+    pub kind: OperationKind,
+}
+
+/// backend-heartbeat 事件，每秒一次；前端超過數秒沒收到即可判定後端沒有回應
+#[derive(Serialize, Clone, Debug)]
+pub struct BackendHeartbeat {
+    /// 從 1 起單調遞增；前端重新載入後看到較小的值表示後端重新啟動
+    pub tick: u64,
+    pub host_timestamp_us: u64,
+    /// 接收中的通道
+    pub channels: Vec<ActiveChannel>,
+    pub operations: Vec<ActiveOperation>,
+    /// 等候 100 ms 仍取不到 state 鎖；此時 channels 與 operations 為空
+    pub state_busy: bool,
+    pub stalled_workers: Vec<StalledWorker>,
+    /// 有工作執行緒停滯或 state 鎖取不到
+    pub stalled: bool,
+}
+
+/// 組出第 tick 次的 heartbeat；state 鎖被佔用時最多等候 100 ms，不會卡住 heartbeat
+pub fn backend_heartbeat(state: &StateMutex, pulses: &Pulses, tick: u64) -> BackendHeartbeat {
+    let snapshot = state.lock_timeout(STATE_LOCK_WAIT).map(|app_state| {
+        let mut channels: Vec<ActiveChannel> = app_state
+            .devices
+            .values()
+            .flat_map(|device| {
+                device
+                    .receiving
+                    .iter()
+                    .filter(|(_, receiving)| receiving.load(Ordering::SeqCst))
+                    .map(|(&channel, _)| ActiveChannel {
+                        dev_type: device.dev_type,
+                        dev_index: device.dev_index,
+                        channel,
+                    })
+            })
+            .collect();
+        channels.sort_by_key(|c| (c.dev_type, c.dev_index, c.channel));
+        let operations = app_state
+            .list_operations()
+            .into_iter()
+            .map(|op| ActiveOperation {
+                operation_id: op.operation_id,
+                kind: op.kind,
+            })
+            .collect();
+        (channels, operations)
+    });
+    let state_busy = snapshot.is_none();
+    let (channels, operations) = snapshot.unwrap_or_default();
+    let stalled_workers = pulses.stalled();
+    BackendHeartbeat {
+        tick,
+        host_timestamp_us: host_timestamp_us(),
+        channels,
+        operations,
+        state_busy,
+        stalled: state_busy || !stalled_workers.is_empty(),
+        stalled_workers,
+    }
+}
+
+/// 在 setup 中呼叫：啟動每秒送出 backend-heartbeat 的執行緒。單次組裝 panic 只略過那一次
+pub(crate) fn start(app_handle: &tauri::AppHandle) {
+    let state = app_handle.state::<Arc<StateMutex>>().inner().clone();
+    let Some(pulses) = state.lock().ok().map(|app_state| app_state.worker_pulses()) else {
+        return;
+    };
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || {
+        let mut was_stalled = false;
+        for tick in 1.. {
+            std::thread::sleep(HEARTBEAT_INTERVAL);
+            let Some(heartbeat) = supervisor::guard("heartbeat", String::new(), || backend_heartbeat(&state, &pulses, tick)) else {
+                continue;
+            };
+            if heartbeat.stalled && !was_stalled {
+                let workers: Vec<String> = heartbeat
+                    .stalled_workers
+                    .iter()
+                    .map(|w| format!("{} [{}] silent {} ms", w.role, w.context, w.silent_ms))
+                    .collect();
+                supervisor::record_diagnostic(&format!(
+                    "backend stalled: {}{}",
+                    if heartbeat.state_busy { "state lock busy; " } else { "" },
+                    workers.join(", ")
+                ));
+            }
+            was_stalled = heartbeat.stalled;
+            app_handle.emit_event("backend-heartbeat", heartbeat);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::mock::MockCan;
+
+    const THRESHOLD: Duration = Duration::from_millis(20);
+
+    fn silent_for_longer_than_the_threshold() {
+        std::thread::sleep(THRESHOLD * 2);
+    }
+
+    #[test]
+    fn a_worker_is_stalled_only_while_it_has_not_beaten_within_its_threshold() {
+        let pulses = Arc::new(Pulses::default());
+        let worker = pulses.register("receive", "4/0 CAN1", THRESHOLD);
+        assert!(pulses.stalled().is_empty());
+        silent_for_longer_than_the_threshold();
+        let stalled = pulses.stalled();
+        assert_eq!((stalled[0].role, stalled[0].context.as_str(), stalled[0].threshold_ms), ("receive", "4/0 CAN1", 20));
+        assert!(stalled[0].silent_ms >= 40);
+
+        worker.beat();
+        assert!(pulses.stalled().is_empty());
+        // 暫停期間不檢查，下一次 beat() 恢復
+        worker.pause();
+        silent_for_longer_than_the_threshold();
+        assert!(pulses.stalled().is_empty());
+        worker.beat();
+        silent_for_longer_than_the_threshold();
+        assert_eq!(pulses.stalled().len(), 1);
+        drop(worker);
+        assert!(pulses.stalled().is_empty());
+    }
+
+    #[test]
+    fn stalled_workers_are_sorted_by_role_and_context() {
+        let pulses = Arc::new(Pulses::default());
+        let _workers = [
+            pulses.register("receive", "4/0 CAN2", THRESHOLD),
+            pulses.register("periodic", "task 1", THRESHOLD),
+            pulses.register("receive", "4/0 CAN1", THRESHOLD),
+        ];
+        silent_for_longer_than_the_threshold();
+        let stalled: Vec<_> = pulses.stalled().into_iter().map(|w| (w.role, w.context)).collect();
+        assert_eq!(
+            stalled,
+            [("periodic", "task 1".to_string()), ("receive", "4/0 CAN1".to_string()), ("receive", "4/0 CAN2".to_string())]
+        );
+    }
+
+    #[test]
+    fn the_heartbeat_lists_receiving_channels_and_running_operations() {
+        let mut app_state = AppState::with_interface(Arc::new(MockCan::new()));
+        app_state.open_device(4, 0, None).unwrap();
+        let device = app_state.devices.get_mut(&(4, 0)).unwrap();
+        device.receiving.insert(1, Arc::new(AtomicBool::new(true)));
+        device.receiving.insert(0, Arc::new(AtomicBool::new(false)));
+        let operation = app_state.start_operation(OperationKind::Benchmark, (4, 0), 1, None);
+        let state = StateMutex::new(app_state);
+
+        let heartbeat = backend_heartbeat(&state, &Pulses::default(), 7);
+        assert_eq!(heartbeat.tick, 7);
+        let channels: Vec<_> = heartbeat.channels.iter().map(|c| (c.dev_type, c.dev_index, c.channel)).collect();
+        assert_eq!(channels, [(4, 0, 1)]);
+        assert_eq!((heartbeat.operations[0].operation_id, heartbeat.operations[0].kind), (operation.id(), OperationKind::Benchmark));
+        assert!(!heartbeat.state_busy && !heartbeat.stalled);
+    }
+
+    #[test]
+    fn a_held_state_lock_marks_the_backend_stalled_instead_of_blocking() {
+        let state = StateMutex::default();
+        let _held = state.lock().unwrap();
+        let heartbeat = backend_heartbeat(&state, &Pulses::default(), 1);
+        assert!(heartbeat.state_busy && heartbeat.stalled);
+        assert!(heartbeat.channels.is_empty() && heartbeat.operations.is_empty() && heartbeat.stalled_workers.is_empty());
+    }
+}
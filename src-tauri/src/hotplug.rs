@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use crate::supervisor;
+use crate::{list_devices, run_blocking, AppState, DeviceInfo, StateMutex};
+
+const DEFAULT_WATCH_INTERVAL_MS: u64 = 2000;
+
+#[derive(Serialize, Clone)]
+pub struct LostDevice {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub serial_number: Option<String>,
+}
+
+/// 背景輪詢 VCI_FindUsbDevice2，比對序號集合並發出 device-attached / device-detached 事件
+#[tauri::command]
+pub async fn start_device_watch(
+    interval_ms: Option<u64>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<String, String> {
+    let interval = Duration::from_millis(interval_ms.unwrap_or(DEFAULT_WATCH_INTERVAL_MS).max(100));
+    let state = state.inner().clone();
+    run_blocking(move || start_watch(state, interval, app_handle)).await
+}
+
+/// 在 state 鎖外列舉裝置；VCI_FindUsbDevice2 可能需要數百毫秒
+fn enumerate(state: &StateMutex) -> Result<HashMap<String, DeviceInfo>, String> {
+    let can_lib = state.lock().map_err(|_| "Failed to lock state")?.try_library()?;
+    list_devices(can_lib.as_ref()).map(by_serial)
+}
+
+fn start_watch(state: Arc<StateMutex>, interval: Duration, app_handle: tauri::AppHandle) -> Result<String, String> {
+    let watching = Arc::new(AtomicBool::new(true));
+    {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        if app_state.device_watch.is_some() {
+            return Err("Device watch already running".into());
+        }
+        app_state.device_watch = Some(watching.clone());
+    }
+    let mut known = match enumerate(&state) {
+        Ok(known) => known,
+        Err(error_message) => {
+            if let Ok(mut app_state) = state.lock() {
+                if app_state.device_watch.as_ref().is_some_and(|w| Arc::ptr_eq(w, &watching)) {
+                    app_state.device_watch = None;
+                }
+            }
+            return Err(error_message);
+        }
+    };
+    std::thread::spawn(move || {
+        let finished = supervisor::guard("device-watch", String::new(), || {
+            let mut next_poll = Instant::now() + interval;
+            while watching.load(Ordering::SeqCst) {
+                if Instant::now() < next_poll {
+                    std::thread::sleep(Duration::from_millis(50));
+                    continue;
+                }
+                next_poll = Instant::now() + interval;
+
+                let Ok(current) = enumerate(&state) else {
+                    continue;
+                };
+                let lost = match state.lock() {
+                    Ok(mut app_state) => mark_lost_devices(&mut app_state, &current),
+                    Err(_) => continue,
+                };
+                for (serial, info) in &current {
+                    if !known.contains_key(serial) {
+                        let _ = app_handle.emit("device-attached", info.clone());
+                    }
+                }
+                for (serial, info) in &known {
+                    if !current.contains_key(serial) {
+                        let _ = app_handle.emit("device-detached", info.clone());
+                    }
+                }
+                for device in lost {
+                    let _ = app_handle.emit("device-lost", device);
+                }
+                known = current;
+            }
+        });
+        if finished.is_none() {
+            if let Ok(mut app_state) = state.lock() {
+                if app_state.device_watch.as_ref().is_some_and(|w| Arc::ptr_eq(w, &watching)) {
+                    app_state.device_watch = None;
+                }
+            }
+        }
+    });
+    Ok("Device watch started".into())
+}
+
+#[tauri::command]
+pub fn stop_device_watch(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    match app_state.device_watch.take() {
+        Some(watching) => {
+            watching.store(false, Ordering::SeqCst);
+            Ok("Device watch stopped".into())
+        }
+        None => Err("Device watch is not running".into()),
+    }
+}
+
+fn by_serial(devices: Vec<DeviceInfo>) -> HashMap<String, DeviceInfo> {
+    devices.into_iter().map(|d| (d.serial_number.clone(), d)).collect()
+}
+
+/// 開啟中的裝置若已不在列舉結果內，標記為 disconnected 並停止其接收
+fn mark_lost_devices(app_state: &mut AppState, current: &HashMap<String, DeviceInfo>) -> Vec<LostDevice> {
+    let mut lost = Vec::new();
+    for device in app_state.devices.values_mut() {
+        if device.disconnected {
+            continue;
+        }
+        let present = match &device.serial_number {
+            Some(serial) => current.contains_key(serial),
+            None => (device.dev_index as usize) < current.len(),
+        };
+        if !present {
+            device.disconnected = true;
+            for receiving in device.receiving.values() {
+                receiving.store(false, Ordering::SeqCst);
+            }
+            lost.push(LostDevice {
+                dev_type: device.dev_type,
+                dev_index: device.dev_index,
+                serial_number: device.serial_number.clone(),
+            });
+        }
+    }
+    lost
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::StateMutex;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct IdName {
+    pub name: String,
+    pub color: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonEntry {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(default)]
+        category: Option<String>,
+    },
+}
+
+/// 仲裁 ID 對應的名稱；不需要完整 DBC 就能在事件、ID 統計與 CSV 記錄中顯示
+#[derive(Default)]
+pub struct IdNames {
+    names: HashMap<u32, IdName>,
+}
+
+impl IdNames {
+    pub fn get(&self, id: u32) -> Option<&IdName> {
+        self.names.get(&id)
+    }
+
+    /// JSON 物件 ({"0x0CF00400": "EEC1"} 或 {"0x100": {"name", "color", "category"}})
+    /// 或 CSV (id,name[,color[,category]]，可有標題列)
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut names = HashMap::new();
+        if content.trim_start().starts_with('{') {
+            let entries: HashMap<String, JsonEntry> =
+                serde_json::from_str(content).map_err(|e| format!("Invalid ID name JSON: {}", e))?;
+            for (key, entry) in entries {
+                let id = parse_id(&key).ok_or_else(|| format!("Invalid CAN ID '{}'", key))?;
+                let entry = match entry {
+                    JsonEntry::Name(name) => IdName { name, color: None, category: None },
+                    JsonEntry::Full { name, color, category } => IdName { name, color, category },
+                };
+                names.insert(id, entry);
+            }
+        } else {
+            for (line_no, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                let Some(id) = parse_id(fields[0]) else {
+                    // 第一列可以是標題
+                    if line_no == 0 {
+                        continue;
+                    }
+                    return Err(format!("line {}: invalid CAN ID '{}'", line_no + 1, fields[0]));
+                };
+                let field = |i: usize| fields.get(i).filter(|f| !f.is_empty()).map(|f| f.to_string());
+                let name = field(1).ok_or_else(|| format!("line {}: missing name", line_no + 1))?;
+                names.insert(id, IdName { name, color: field(2), category: field(3) });
+            }
+        }
+        Ok(Self { names })
+    }
+}
+
+fn parse_id(text: &str) -> Option<u32> {
+    let text = text.trim().trim_matches('"');
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// 在訊框事件中填入名稱；沒有載入對照表或 ID 不在表中時不設定
+pub(crate) fn annotate(id_names: &Mutex<Option<Arc<IdNames>>>, frames: &mut [CanFrameEvent]) {
+    let Some(names) = id_names.lock().ok().and_then(|n| n.clone()) else {
+        return;
+    };
+    for frame in frames {
+        frame.name = names.get(frame.id).map(|n| n.name.clone());
+    }
+}
+
+#[derive(Serialize)]
+pub struct IdNamesSummary {
+    pub count: usize,
+}
+
+/// 載入 ID 名稱對照表；參數可以是檔案路徑或檔案內容本身。重新載入後立即套用到之後的訊框
+#[tauri::command]
+pub fn load_id_names(path_or_content: String, state: State<Arc<StateMutex>>) -> Result<IdNamesSummary, String> {
+    let content = if path_or_content.contains('\n') || path_or_content.trim_start().starts_with('{') {
+        path_or_content
+    } else {
+        std::fs::read_to_string(&path_or_content).map_err(|e| format!("Failed to read {}: {}", path_or_content, e))?
+    };
+    let names = IdNames::parse(&content)?;
+    let summary = IdNamesSummary { count: names.names.len() };
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    *app_state.id_names.lock().map_err(|_| "Failed to lock ID names")? = Some(Arc::new(names));
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn clear_id_names(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    *app_state.id_names.lock().map_err(|_| "Failed to lock ID names")? = None;
+    Ok("ID names cleared".into())
+}
+
+/// 目前的對照表，前端可用來顯示顏色與分類
+#[tauri::command]
+pub fn get_id_names(state: State<Arc<StateMutex>>) -> Result<HashMap<u32, IdName>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let names = app_state.id_names.lock().map_err(|_| "Failed to lock ID names")?;
+    Ok(names.as_ref().map(|n| n.names.clone()).unwrap_or_default())
+}
@@ -0,0 +1,728 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::frame::{host_timestamp_us, CanFrameEvent};
+use crate::operation::{OperationHandle, OperationKind};
+use crate::supervisor;
+use crate::tap::TapReceiver;
+use crate::{invalid_argument, run_blocking, AppState, DeviceType, StateMutex, VciCanObj};
+
+/// 12 位元長度欄位可表示的最大長度；更長的訊息使用 32 位元長度的 first frame
+const MAX_SHORT_LENGTH: usize = 0xFFF;
+/// 監聽器等待 first frame 時的檢查間隔
+const LISTENER_POLL_MS: u64 = 100;
+
+const FC_CONTINUE: u8 = 0;
+const FC_WAIT: u8 = 1;
+const FC_OVERFLOW: u8 = 2;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct IsoTpOptions {
+    /// 填充位元組；None 表示不填充，訊框長度依實際資料
+    pub padding: Option<u8>,
+    /// 省略時依 ID 是否大於 0x7FF 判斷
+    pub extended_id: Option<bool>,
+    /// 接收時回覆給對方的 flow control 參數
+    pub block_size: u8,
+    pub st_min: u8,
+    /// 送出時取代對方 flow control 要求的 STmin (同樣的編碼)；設為 0 即忽略對方的要求
+    pub tx_st_min_override: Option<u8>,
+    /// 一個訊框交給驅動送出的時間上限
+    pub n_as_ms: u64,
+    pub n_bs_ms: u64,
+    pub n_cr_ms: u64,
+    /// 可接受的連續 FC WAIT 次數 (N_WFTmax)
+    pub max_wait_frames: u32,
+}
+
+impl Default for IsoTpOptions {
+    fn default() -> Self {
+        Self {
+            padding: Some(0xCC),
+            extended_id: None,
+            block_size: 0,
+            st_min: 0,
+            tx_st_min_override: None,
+            n_as_ms: 1000,
+            n_bs_ms: 1000,
+            n_cr_ms: 1000,
+            max_wait_frames: 10,
+        }
+    }
+}
+
+impl IsoTpOptions {
+    fn validate(&self, field: &str) -> Result<(), String> {
+        let reserved = |st_min: u8| st_min_duration(st_min).is_none();
+        if reserved(self.st_min) {
+            return Err(invalid_argument(&format!("{}.st_min", field), format!("0x{:02X} is a reserved STmin value", self.st_min)));
+        }
+        if let Some(st_min) = self.tx_st_min_override.filter(|&st_min| reserved(st_min)) {
+            return Err(invalid_argument(
+                &format!("{}.tx_st_min_override", field),
+                format!("0x{:02X} is a reserved STmin value", st_min),
+            ));
+        }
+        for (name, value) in [("n_as_ms", self.n_as_ms), ("n_bs_ms", self.n_bs_ms), ("n_cr_ms", self.n_cr_ms)] {
+            if value == 0 {
+                return Err(invalid_argument(&format!("{}.{}", field, name), "must be greater than 0"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IsoTpError {
+    /// 驅動沒有在 N_As 內送出訊框
+    NAsTimeout { elapsed_ms: u64 },
+    /// 送出 first frame 或一個區塊後，等不到 flow control
+    NBsTimeout,
+    /// 收到 first frame 後，等不到下一個 consecutive frame
+    NCrTimeout,
+    /// 指定時間內沒有收到任何訊息
+    ReceiveTimeout,
+    FlowControlOverflow,
+    TooManyWaits { waits: u32 },
+    InvalidFlowStatus { status: u8 },
+    SequenceError { expected: u8, received: u8 },
+    UnexpectedFrame { pci: u8 },
+    PayloadTooLarge { len: usize },
+    /// 對方的 flow control 帶有保留的 STmin 值
+    InvalidStMin { st_min: u8 },
+    /// 對方在我們送出下一個 flow control 前就送出超過 block_size 個 consecutive frame
+    BlockSizeExceeded { block_size: u8 },
+    /// 依裝置時間戳記，對方的 consecutive frame 間隔小於我們要求的 STmin
+    StMinViolation { st_min_us: u64, gap_us: u64 },
+    /// 傳輸中被 cancel_operation 取消
+    Cancelled,
+    Other { message: String },
+}
+
+impl fmt::Display for IsoTpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IsoTpError::NAsTimeout { elapsed_ms } => write!(f, "frame took {} ms to transmit (N_As)", elapsed_ms),
+            IsoTpError::NBsTimeout => write!(f, "timed out waiting for flow control (N_Bs)"),
+            IsoTpError::NCrTimeout => write!(f, "timed out waiting for consecutive frame (N_Cr)"),
+            IsoTpError::ReceiveTimeout => write!(f, "no ISO-TP message received"),
+            IsoTpError::FlowControlOverflow => write!(f, "receiver reported overflow"),
+            IsoTpError::TooManyWaits { waits } => write!(f, "receiver sent {} flow control WAIT frames", waits),
+            IsoTpError::InvalidFlowStatus { status } => write!(f, "invalid flow status {}", status),
+            IsoTpError::SequenceError { expected, received } => {
+                write!(f, "sequence number {} received, expected {}", received, expected)
+            }
+            IsoTpError::UnexpectedFrame { pci } => write!(f, "unexpected frame with PCI 0x{:02X}", pci),
+            IsoTpError::PayloadTooLarge { len } => write!(f, "payload of {} bytes is too large", len),
+            IsoTpError::InvalidStMin { st_min } => write!(f, "flow control carries reserved STmin 0x{:02X}", st_min),
+            IsoTpError::BlockSizeExceeded { block_size } => {
+                write!(f, "sender exceeded block size {} without waiting for flow control", block_size)
+            }
+            IsoTpError::StMinViolation { st_min_us, gap_us } => {
+                write!(f, "consecutive frames {} us apart, STmin is {} us", gap_us, st_min_us)
+            }
+            IsoTpError::Cancelled => write!(f, "transfer cancelled"),
+            IsoTpError::Other { message } => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for IsoTpError {
+    fn from(message: String) -> Self {
+        IsoTpError::Other { message }
+    }
+}
+
+impl From<&str> for IsoTpError {
+    fn from(message: &str) -> Self {
+        IsoTpError::Other { message: message.to_string() }
+    }
+}
+
+/// STmin 編碼：0x00–0x7F 為毫秒，0xF1–0xF9 為 100–900 微秒，其餘為保留值
+fn st_min_duration(st_min: u8) -> Option<Duration> {
+    match st_min {
+        0x00..=0x7F => Some(Duration::from_millis(st_min as u64)),
+        0xF1..=0xF9 => Some(Duration::from_micros((st_min - 0xF0) as u64 * 100)),
+        _ => None,
+    }
+}
+
+/// 一組 tx_id/rx_id 上的 ISO-TP 連線；建立時即開始收集 rx_id 的訊框，避免漏掉對方的回覆
+pub struct IsoTpLink {
+    state: Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    extended: bool,
+    options: IsoTpOptions,
+    tap: TapReceiver,
+    /// 同一組 ID 上進行中的傳輸數，TesterPresent 據此暫停
+    activity: Arc<AtomicUsize>,
+}
+
+/// 存在期間把連線標記為傳輸中
+pub struct TransferGuard(Arc<AtomicUsize>);
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl AppState {
+    /// 一組 tx_id 上進行中的 ISO-TP 傳輸計數，由該組 ID 的所有連線共用
+    pub(crate) fn isotp_activity(&mut self, key: (u32, u32), channel: u32, tx_id: u32) -> Arc<AtomicUsize> {
+        self.isotp_activity.entry((key.0, key.1, channel, tx_id)).or_default().clone()
+    }
+}
+
+impl IsoTpLink {
+    pub fn open(
+        state: &Arc<StateMutex>,
+        key: (u32, u32),
+        channel: u32,
+        tx_id: u32,
+        rx_id: u32,
+        options: IsoTpOptions,
+    ) -> Result<Self, String> {
+        let activity = state
+            .lock()
+            .map_err(|_| "Failed to lock state")?
+            .isotp_activity(key, channel, tx_id);
+        Ok(Self {
+            tap: TapReceiver::open(state, key, channel)?,
+            activity,
+            state: state.clone(),
+            key,
+            channel,
+            tx_id,
+            rx_id,
+            extended: options.extended_id.unwrap_or(tx_id > 0x7FF || rx_id > 0x7FF),
+            options,
+        })
+    }
+
+    pub fn rx_id(&self) -> u32 {
+        self.rx_id
+    }
+
+    pub fn key(&self) -> (u32, u32) {
+        self.key
+    }
+
+    /// 標記一段不可被其他訊框插入的傳輸，例如 UDS 請求到收到回應為止
+    pub fn hold(&self) -> TransferGuard {
+        self.activity.fetch_add(1, Ordering::SeqCst);
+        TransferGuard(self.activity.clone())
+    }
+
+    fn send_frame(&self, bytes: &[u8]) -> Result<(), IsoTpError> {
+        let mut can_obj = VciCanObj {
+            id: self.tx_id,
+            extern_flag: self.extended as u8,
+            data_len: bytes.len() as u8,
+            ..Default::default()
+        };
+        can_obj.data[..bytes.len()].copy_from_slice(bytes);
+        if let Some(padding) = self.options.padding {
+            can_obj.data[bytes.len()..].fill(padding);
+            can_obj.data_len = 8;
+        }
+        let started = Instant::now();
+        crate::transmit(&self.state, self.key, self.channel, &[can_obj])?;
+        let elapsed = started.elapsed();
+        if elapsed > Duration::from_millis(self.options.n_as_ms) {
+            return Err(IsoTpError::NAsTimeout {
+                elapsed_ms: elapsed.as_millis() as u64,
+            });
+        }
+        Ok(())
+    }
+
+    fn next_frame(&self, deadline: Instant) -> Option<CanFrameEvent> {
+        while let Some(frame) = self.tap.recv_until(deadline) {
+            if frame.id == self.rx_id && frame.extended == self.extended && !frame.remote && !frame.data.is_empty() {
+                return Some(frame);
+            }
+        }
+        None
+    }
+
+    /// 等待 flow control；WAIT 會重新計時，超過 max_wait_frames 次視為錯誤。回傳 (block_size, st_min)，
+    /// 設定 tx_st_min_override 時以它取代對方要求的 STmin
+    fn wait_flow_control(&self) -> Result<(u8, Duration), IsoTpError> {
+        let mut waits = 0;
+        loop {
+            let deadline = Instant::now() + Duration::from_millis(self.options.n_bs_ms);
+            let frame = loop {
+                let frame = self.next_frame(deadline).ok_or(IsoTpError::NBsTimeout)?;
+                if frame.data[0] >> 4 == 3 {
+                    break frame;
+                }
+            };
+            match frame.data[0] & 0x0F {
+                FC_CONTINUE => {
+                    let block_size = frame.data.get(1).copied().unwrap_or(0);
+                    let requested = frame.data.get(2).copied().unwrap_or(0);
+                    let st_min = st_min_duration(self.options.tx_st_min_override.unwrap_or(requested))
+                        .ok_or(IsoTpError::InvalidStMin { st_min: requested })?;
+                    return Ok((block_size, st_min));
+                }
+                FC_WAIT => {
+                    waits += 1;
+                    if waits > self.options.max_wait_frames {
+                        return Err(IsoTpError::TooManyWaits { waits });
+                    }
+                }
+                FC_OVERFLOW => return Err(IsoTpError::FlowControlOverflow),
+                status => return Err(IsoTpError::InvalidFlowStatus { status }),
+            }
+        }
+    }
+
+    /// 送出一則訊息；回傳使用的訊框數
+    pub fn send(&self, data: &[u8]) -> Result<usize, IsoTpError> {
+        self.send_cancellable(data, &AtomicBool::new(true), |_| {})
+    }
+
+    /// 同 send，但在每個 consecutive frame 之前檢查 running；progress 收到已送出的位元組數
+    pub fn send_cancellable(&self, data: &[u8], running: &AtomicBool, mut progress: impl FnMut(u64)) -> Result<usize, IsoTpError> {
+        // 長度 0 的 single frame 不合法，對方 (包含本模組的接收端) 會拒收
+        if data.is_empty() {
+            return Err(invalid_argument("data", "must not be empty").into());
+        }
+        let _hold = self.hold();
+        if data.len() <= 7 {
+            let mut frame = vec![data.len() as u8];
+            frame.extend_from_slice(data);
+            self.send_frame(&frame)?;
+            return Ok(1);
+        }
+        if u32::try_from(data.len()).is_err() {
+            return Err(IsoTpError::PayloadTooLarge { len: data.len() });
+        }
+        let mut frame = if data.len() <= MAX_SHORT_LENGTH {
+            vec![0x10 | (data.len() >> 8) as u8, data.len() as u8]
+        } else {
+            let mut frame = vec![0x10, 0x00];
+            frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            frame
+        };
+        let first_len = 8 - frame.len();
+        frame.extend_from_slice(&data[..first_len]);
+        self.send_frame(&frame)?;
+        let mut frames = 1;
+        let mut bytes_sent = first_len as u64;
+        progress(bytes_sent);
+
+        let mut sequence = 1u8;
+        let mut chunks = data[first_len..].chunks(7).peekable();
+        while chunks.peek().is_some() {
+            let (block_size, st_min) = self.wait_flow_control()?;
+            let mut sent_in_block = 0;
+            while let Some(chunk) = chunks.next() {
+                if !running.load(Ordering::SeqCst) {
+                    return Err(IsoTpError::Cancelled);
+                }
+                let mut frame = vec![0x20 | sequence];
+                frame.extend_from_slice(chunk);
+                self.send_frame(&frame)?;
+                frames += 1;
+                bytes_sent += chunk.len() as u64;
+                progress(bytes_sent);
+                sequence = (sequence + 1) & 0x0F;
+                sent_in_block += 1;
+                if block_size != 0 && sent_in_block == block_size {
+                    break;
+                }
+                if chunks.peek().is_some() {
+                    std::thread::sleep(st_min);
+                }
+            }
+        }
+        Ok(frames)
+    }
+
+    /// 回傳開始送出前的主機時間；在此之前讀到的 consecutive frame 都是對方沒等 flow control 就送出的
+    fn send_flow_control(&self) -> Result<u64, IsoTpError> {
+        let sent_at = host_timestamp_us();
+        self.send_frame(&[0x30 | FC_CONTINUE, self.options.block_size, self.options.st_min])?;
+        Ok(sent_at)
+    }
+
+    /// 依裝置時間戳記檢查 consecutive frame 的間隔；沒有時間戳記時無法判斷，不檢查
+    fn check_st_min(&self, previous: Option<u32>, frame: &CanFrameEvent) -> Result<(), IsoTpError> {
+        let st_min_us = st_min_duration(self.options.st_min).unwrap_or_default().as_micros() as u64;
+        let (Some(previous), Some(current)) = (previous, frame.device_timestamp) else {
+            return Ok(());
+        };
+        // 時間戳記單位為 0.1 ms，容許一個單位的誤差
+        let gap_us = u64::from(current.wrapping_sub(previous)) * 100;
+        if st_min_us > 0 && gap_us + 100 < st_min_us {
+            return Err(IsoTpError::StMinViolation { st_min_us, gap_us });
+        }
+        Ok(())
+    }
+
+    /// 等待一則訊息直到 deadline；逾時且沒有收到 first frame 時回傳 None
+    pub fn receive(&self, deadline: Instant) -> Result<Option<Vec<u8>>, IsoTpError> {
+        self.receive_cancellable(deadline, &AtomicBool::new(true), |_, _| {})
+    }
+
+    /// 同 receive，但等待 first frame 時每 LISTENER_POLL_MS、收到每個 consecutive frame 後檢查 running；
+    /// progress 收到已重組的位元組數與訊息總長度
+    pub fn receive_cancellable(
+        &self,
+        deadline: Instant,
+        running: &AtomicBool,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<Option<Vec<u8>>, IsoTpError> {
+        let frame = loop {
+            if !running.load(Ordering::SeqCst) {
+                return Err(IsoTpError::Cancelled);
+            }
+            let poll_deadline = deadline.min(Instant::now() + Duration::from_millis(LISTENER_POLL_MS));
+            let Some(frame) = self.next_frame(poll_deadline) else {
+                if poll_deadline < deadline {
+                    continue;
+                }
+                return Ok(None);
+            };
+            // 閒置時收到的 CF/FC 不屬於任何進行中的訊息，略過
+            if frame.data[0] >> 4 <= 1 {
+                break frame;
+            }
+        };
+        let pci = frame.data[0];
+        if pci >> 4 == 0 {
+            let len = (pci & 0x0F) as usize;
+            if len == 0 || len > frame.data.len() - 1 {
+                return Err(IsoTpError::UnexpectedFrame { pci });
+            }
+            return Ok(Some(frame.data[1..1 + len].to_vec()));
+        }
+
+        let short_len = (((pci & 0x0F) as usize) << 8) | frame.data.get(1).copied().unwrap_or(0) as usize;
+        let (total, header) = if short_len == 0 {
+            let bytes: [u8; 4] = frame.data.get(2..6).and_then(|b| b.try_into().ok()).ok_or(IsoTpError::UnexpectedFrame { pci })?;
+            (u32::from_be_bytes(bytes) as usize, 6)
+        } else {
+            (short_len, 2)
+        };
+        // 7 位元組以內的訊息應以 single frame 送出
+        if total <= 7 {
+            return Err(IsoTpError::UnexpectedFrame { pci });
+        }
+        let _hold = self.hold();
+        let mut data = frame.data[header.min(frame.data.len())..].to_vec();
+        data.truncate(total);
+        progress(data.len() as u64, total as u64);
+        let mut flow_control_at = self.send_flow_control()?;
+
+        let mut expected = 1u8;
+        let mut received_in_block = 0;
+        let mut previous_timestamp = None;
+        while data.len() < total {
+            let frame = self
+                .next_frame(Instant::now() + Duration::from_millis(self.options.n_cr_ms))
+                .ok_or(IsoTpError::NCrTimeout)?;
+            let pci = frame.data[0];
+            if pci >> 4 != 2 {
+                return Err(IsoTpError::UnexpectedFrame { pci });
+            }
+            if pci & 0x0F != expected {
+                return Err(IsoTpError::SequenceError {
+                    expected,
+                    received: pci & 0x0F,
+                });
+            }
+            if received_in_block == 0 && frame.host_timestamp_us < flow_control_at {
+                return Err(IsoTpError::BlockSizeExceeded {
+                    block_size: self.options.block_size,
+                });
+            }
+            self.check_st_min(previous_timestamp, &frame)?;
+            previous_timestamp = frame.device_timestamp;
+            expected = (expected + 1) & 0x0F;
+            let remaining = total - data.len();
+            data.extend_from_slice(&frame.data[1..frame.data.len().min(1 + remaining)]);
+            progress(data.len() as u64, total as u64);
+            if !running.load(Ordering::SeqCst) {
+                return Err(IsoTpError::Cancelled);
+            }
+            received_in_block += 1;
+            if self.options.block_size != 0 && received_in_block == self.options.block_size && data.len() < total {
+                received_in_block = 0;
+                previous_timestamp = None;
+                flow_control_at = self.send_flow_control()?;
+            }
+        }
+        Ok(Some(data))
+    }
+}
+
+/// configure_isotp 的連線設定
+#[derive(Deserialize)]
+pub struct IsoTpLinkSpec {
+    pub dev_type: Option<DeviceType>,
+    pub dev_index: Option<u32>,
+    pub channel: u32,
+    pub tx_id: u32,
+    pub rx_id: u32,
+    #[serde(default)]
+    pub params: IsoTpOptions,
+}
+
+/// 一組已設定參數的 tx_id/rx_id；對這組 ID 呼叫 ISO-TP 命令且不帶 options 時使用這些參數
+#[derive(Serialize, Clone, Debug)]
+pub struct IsoTpLinkConfig {
+    pub link_id: String,
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub tx_id: u32,
+    pub rx_id: u32,
+    pub params: IsoTpOptions,
+}
+
+impl AppState {
+    fn isotp_params(&self, key: (u32, u32), channel: u32, tx_id: u32, rx_id: u32) -> Option<IsoTpOptions> {
+        self.isotp_links
+            .values()
+            .find(|link| (link.dev_type, link.dev_index) == key && link.channel == channel && link.tx_id == tx_id && link.rx_id == rx_id)
+            .map(|link| link.params.clone())
+    }
+}
+
+fn open_link(
+    state: &Arc<StateMutex>,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    options: Option<IsoTpOptions>,
+) -> Result<IsoTpLink, IsoTpError> {
+    if let Some(options) = &options {
+        options.validate("options")?;
+    }
+    let (key, options) = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+        let options = options.or_else(|| app_state.isotp_params(key, channel, tx_id, rx_id));
+        (key, options.unwrap_or_default())
+    };
+    Ok(IsoTpLink::open(state, key, channel, tx_id, rx_id, options)?)
+}
+
+/// 設定一組 tx_id/rx_id 的 flow control、填充與逾時參數；同一 link_id 再次設定時取代原本的內容
+#[tauri::command]
+pub fn configure_isotp(link_id: String, link: IsoTpLinkSpec, state: State<Arc<StateMutex>>) -> Result<IsoTpLinkConfig, String> {
+    if link_id.trim().is_empty() {
+        return Err(invalid_argument("link_id", "must not be empty"));
+    }
+    link.params.validate("link.params")?;
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.connected_device(link.dev_type.map(DeviceType::code), link.dev_index)?;
+    device.check_channel(link.channel)?;
+    let (dev_type, dev_index) = device.key();
+    let config = IsoTpLinkConfig {
+        link_id: link_id.clone(),
+        dev_type,
+        dev_index,
+        channel: link.channel,
+        tx_id: link.tx_id,
+        rx_id: link.rx_id,
+        params: link.params,
+    };
+    if let Some(other) = app_state.isotp_links.values().find(|other| {
+        other.link_id != link_id
+            && (other.dev_type, other.dev_index, other.channel, other.tx_id, other.rx_id) == (dev_type, dev_index, config.channel, config.tx_id, config.rx_id)
+    }) {
+        return Err(format!("link {} already configures these IDs", other.link_id));
+    }
+    app_state.isotp_links.insert(link_id, config.clone());
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn get_isotp_config(link_id: String, state: State<Arc<StateMutex>>) -> Result<IsoTpLinkConfig, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state
+        .isotp_links
+        .get(&link_id)
+        .cloned()
+        .ok_or_else(|| format!("ISO-TP link {} is not configured", link_id))
+}
+
+#[tauri::command]
+pub fn list_isotp_links(state: State<Arc<StateMutex>>) -> Result<Vec<IsoTpLinkConfig>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let mut links: Vec<IsoTpLinkConfig> = app_state.isotp_links.values().cloned().collect();
+    links.sort_by(|a, b| a.link_id.cmp(&b.link_id));
+    Ok(links)
+}
+
+/// 登錄一個 ISO-TP 操作並送出 operation-started
+fn start_operation(
+    state: &Arc<StateMutex>,
+    app_handle: &tauri::AppHandle,
+    kind: OperationKind,
+    link: &IsoTpLink,
+    total: Option<u64>,
+) -> Result<OperationHandle, IsoTpError> {
+    let operation = state
+        .lock()
+        .map_err(|_| "Failed to lock state")?
+        .start_operation(kind, link.key, link.channel, total);
+    operation.announce(state, app_handle);
+    Ok(operation)
+}
+
+/// 以 ISO-TP 分段送出資料，回傳使用的 CAN 訊框數。
+/// 開始時送出 operation-started，可用 cancel_operation 在 consecutive frame 之間取消
+#[tauri::command]
+pub async fn isotp_send(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    data: Vec<u8>,
+    options: Option<IsoTpOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<usize, IsoTpError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let link = open_link(&state, dev_type, dev_index, channel, tx_id, rx_id, options)?;
+        let operation = start_operation(&state, &app_handle, OperationKind::IsotpSend, &link, Some(data.len() as u64))?;
+        let result = link.send_cancellable(&data, operation.running(), |bytes_sent| operation.set_progress(bytes_sent));
+        operation.finish(&state, &app_handle, result.as_ref().err().map(IsoTpError::to_string));
+        result
+    })
+    .await
+}
+
+/// 等待 rx_id 上的一則完整訊息，flow control 由 tx_id 送出。
+/// 開始時送出 operation-started，等待中或 consecutive frame 之間可用 cancel_operation 取消
+#[tauri::command]
+pub async fn isotp_receive(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    timeout_ms: u64,
+    options: Option<IsoTpOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<u8>, IsoTpError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let link = open_link(&state, dev_type, dev_index, channel, tx_id, rx_id, options)?;
+        let operation = start_operation(&state, &app_handle, OperationKind::IsotpReceive, &link, None)?;
+        let result = link
+            .receive_cancellable(Instant::now() + Duration::from_millis(timeout_ms), operation.running(), |received, total| {
+                operation.set_total(total);
+                operation.set_progress(received);
+            })
+            .and_then(|data| data.ok_or(IsoTpError::ReceiveTimeout));
+        operation.finish(&state, &app_handle, result.as_ref().err().map(IsoTpError::to_string));
+        result
+    })
+    .await
+}
+
+#[derive(Serialize, Clone)]
+struct IsoTpMessageEvent {
+    listener_id: u32,
+    channel: u32,
+    rx_id: u32,
+    data: Vec<u8>,
+}
+
+#[derive(Serialize, Clone)]
+struct IsoTpErrorEvent {
+    listener_id: u32,
+    channel: u32,
+    rx_id: u32,
+    error: IsoTpError,
+    message: String,
+}
+
+/// 在背景持續重組 rx_id 上的訊息，以 isotp-message 事件送出；回傳 listener_id (即 operation_id)，
+/// 可用 stop_isotp_listener 或 cancel_operation 停止
+#[tauri::command]
+pub fn start_isotp_listener(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    options: Option<IsoTpOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, IsoTpError> {
+    let link = open_link(state.inner(), dev_type, dev_index, channel, tx_id, rx_id, options)?;
+    let operation = start_operation(state.inner(), &app_handle, OperationKind::IsotpListener, &link, None)?;
+    let listener_id = operation.id();
+    let running = operation.running().clone();
+    let state = state.inner().clone();
+    std::thread::spawn(move || {
+        let mut messages = 0;
+        let finished = supervisor::guard("isotp-listener", format!("listener {}", listener_id), || {
+            while running.load(Ordering::SeqCst) {
+                match link.receive_cancellable(Instant::now() + Duration::from_millis(LISTENER_POLL_MS), &running, |_, _| {}) {
+                    Ok(Some(data)) => {
+                        messages += 1;
+                        operation.set_progress(messages);
+                        let _ = app_handle.emit(
+                            "isotp-message",
+                            IsoTpMessageEvent {
+                                listener_id,
+                                channel,
+                                rx_id: link.rx_id(),
+                                data,
+                            },
+                        );
+                    }
+                    Ok(None) | Err(IsoTpError::Cancelled) => {}
+                    Err(error) => {
+                        let _ = app_handle.emit(
+                            "isotp-error",
+                            IsoTpErrorEvent {
+                                listener_id,
+                                channel,
+                                rx_id: link.rx_id(),
+                                message: error.to_string(),
+                                error,
+                            },
+                        );
+                    }
+                }
+            }
+        });
+        let error = finished.is_none().then(|| "ISO-TP listener thread panicked".to_string());
+        operation.finish(&state, &app_handle, error);
+    });
+    Ok(listener_id)
+}
+
+#[tauri::command]
+pub fn stop_isotp_listener(listener_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state
+        .cancel_operation(listener_id, Some(OperationKind::IsotpListener))
+        .ok_or_else(|| format!("ISO-TP listener {} not found", listener_id))?;
+    Ok(format!("ISO-TP listener {} stopped", listener_id))
+}
@@ -0,0 +1,316 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::{run_blocking, DeviceType, StateMutex, VciCanObj};
+
+const PGN_REQUEST: u32 = 0xEA00;
+const PGN_TP_CM: u32 = 0xEC00;
+const PGN_TP_DT: u32 = 0xEB00;
+const PGN_EEC1: u32 = 0xF004;
+const PGN_CCVS: u32 = 0xFEF1;
+const PGN_DM1: u32 = 0xFECA;
+
+const TP_CM_RTS: u8 = 16;
+const TP_CM_BAM: u8 = 32;
+const TP_CM_ABORT: u8 = 255;
+
+const GLOBAL_ADDRESS: u8 = 0xFF;
+/// 預設來源位址：off-board diagnostic-service tool #1
+const DEFAULT_SOURCE_ADDRESS: u8 = 0xF9;
+const REQUEST_PRIORITY: u32 = 6;
+/// 封包之間超過 T1/T2 (取較寬鬆的 1250 ms) 視為傳輸中斷
+const TP_TIMEOUT_US: u64 = 1_250_000;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Dtc {
+    pub spn: u32,
+    pub fmi: u8,
+    pub occurrence_count: u8,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct J1939Decoded {
+    pub name: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<&'static str, f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dtcs: Vec<Dtc>,
+}
+
+/// 29 位元 ID 拆解後的欄位；PDU2 (廣播) 訊息的 destination_address 為 0xFF
+#[derive(Serialize, Clone, Debug)]
+pub struct J1939Info {
+    pub priority: u8,
+    pub pgn: u32,
+    pub source_address: u8,
+    pub destination_address: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<J1939Decoded>,
+}
+
+impl J1939Info {
+    pub fn from_id(id: u32) -> Self {
+        let pdu_format = (id >> 16) & 0xFF;
+        let pdu_specific = (id >> 8) & 0xFF;
+        let data_page = (id >> 24) & 0x03;
+        let (pgn, destination_address) = if pdu_format < 240 {
+            ((data_page << 16) | (pdu_format << 8), pdu_specific as u8)
+        } else {
+            ((data_page << 16) | (pdu_format << 8) | pdu_specific, GLOBAL_ADDRESS)
+        };
+        Self {
+            priority: ((id >> 26) & 0x07) as u8,
+            pgn,
+            source_address: id as u8,
+            destination_address,
+            decoded: None,
+        }
+    }
+}
+
+/// 內建的常見 PGN 解碼
+pub fn decode_pgn(pgn: u32, data: &[u8]) -> Option<J1939Decoded> {
+    let mut values = BTreeMap::new();
+    let u16_at = |i: usize| Some(u16::from_le_bytes([*data.get(i)?, *data.get(i + 1)?]) as f64);
+    let name = match pgn {
+        PGN_EEC1 => {
+            if let Some(&torque) = data.get(2) {
+                values.insert("actual_engine_torque_percent", torque as f64 - 125.0);
+            }
+            if let Some(speed) = u16_at(3) {
+                values.insert("engine_speed_rpm", speed * 0.125);
+            }
+            "EEC1"
+        }
+        PGN_CCVS => {
+            if let Some(speed) = u16_at(1) {
+                values.insert("wheel_based_vehicle_speed_kmh", speed / 256.0);
+            }
+            "CCVS"
+        }
+        PGN_DM1 => {
+            // 前兩個位元組為燈號狀態，之後每 4 個位元組一個 DTC；全 0 表示沒有故障
+            let dtcs = data
+                .get(2..)
+                .unwrap_or_default()
+                .chunks_exact(4)
+                .map(|dtc| Dtc {
+                    spn: dtc[0] as u32 | (dtc[1] as u32) << 8 | ((dtc[2] as u32 & 0xE0) << 11),
+                    fmi: dtc[2] & 0x1F,
+                    occurrence_count: dtc[3] & 0x7F,
+                })
+                .filter(|dtc| dtc.spn != 0)
+                .collect();
+            return Some(J1939Decoded {
+                name: "DM1",
+                values,
+                dtcs,
+            });
+        }
+        _ => return None,
+    };
+    Some(J1939Decoded {
+        name,
+        values,
+        dtcs: Vec::new(),
+    })
+}
+
+/// TP.CM/TP.DT 重組完成的多封包訊息
+#[derive(Serialize, Clone, Debug)]
+pub struct J1939Message {
+    pub device_type: u32,
+    pub device_index: u32,
+    pub channel: u32,
+    pub pgn: u32,
+    pub source_address: u8,
+    pub destination_address: u8,
+    pub data: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<J1939Decoded>,
+}
+
+struct TransportSession {
+    pgn: u32,
+    size: usize,
+    packets: u8,
+    data: Vec<u8>,
+    next_sequence: u8,
+    last_us: u64,
+}
+
+/// 單一通道的傳輸協定重組；同時旁聽 BAM 與 RTS/CTS (只收不回 CTS)
+#[derive(Default)]
+struct Reassembler {
+    /// key 為 (來源位址, 目的位址)
+    sessions: HashMap<(u8, u8), TransportSession>,
+}
+
+impl Reassembler {
+    fn feed(&mut self, info: &J1939Info, frame: &CanFrameEvent) -> Option<J1939Message> {
+        let session_key = (info.source_address, info.destination_address);
+        match info.pgn {
+            PGN_TP_CM => {
+                let data = &frame.data;
+                match data.first().copied() {
+                    Some(TP_CM_RTS | TP_CM_BAM) if data.len() >= 8 => {
+                        self.sessions.insert(
+                            session_key,
+                            TransportSession {
+                                pgn: data[5] as u32 | (data[6] as u32) << 8 | (data[7] as u32) << 16,
+                                size: u16::from_le_bytes([data[1], data[2]]) as usize,
+                                packets: data[3],
+                                data: Vec::new(),
+                                next_sequence: 1,
+                                last_us: frame.host_timestamp_us,
+                            },
+                        );
+                    }
+                    Some(TP_CM_ABORT) => {
+                        self.sessions.remove(&session_key);
+                    }
+                    _ => {}
+                }
+                None
+            }
+            PGN_TP_DT => {
+                let session = self.sessions.get_mut(&session_key)?;
+                let sequence = *frame.data.first()?;
+                if sequence != session.next_sequence
+                    || frame.host_timestamp_us.saturating_sub(session.last_us) > TP_TIMEOUT_US
+                {
+                    self.sessions.remove(&session_key);
+                    return None;
+                }
+                session.data.extend_from_slice(&frame.data[1..]);
+                session.next_sequence = session.next_sequence.wrapping_add(1);
+                session.last_us = frame.host_timestamp_us;
+                if sequence < session.packets {
+                    return None;
+                }
+                let mut session = self.sessions.remove(&session_key)?;
+                session.data.truncate(session.size);
+                Some(J1939Message {
+                    device_type: frame.device_type,
+                    device_index: frame.device_index,
+                    channel: frame.channel,
+                    pgn: session.pgn,
+                    source_address: info.source_address,
+                    destination_address: info.destination_address,
+                    decoded: decode_pgn(session.pgn, &session.data),
+                    data: session.data,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 啟用 J1939 模式的 (dev_type, dev_index, channel) 與各自的重組狀態
+#[derive(Default)]
+pub struct J1939State {
+    channels: HashMap<(u32, u32, u32), Reassembler>,
+}
+
+impl J1939State {
+    /// 為擴展幀加上 J1939 欄位，回傳重組完成的多封包訊息
+    pub fn process(&mut self, key: (u32, u32), channel: u32, frames: &mut [CanFrameEvent]) -> Vec<J1939Message> {
+        let Some(reassembler) = self.channels.get_mut(&(key.0, key.1, channel)) else {
+            return Vec::new();
+        };
+        let mut completed = Vec::new();
+        for frame in frames.iter_mut().filter(|f| f.extended && !f.remote) {
+            let mut info = J1939Info::from_id(frame.id);
+            completed.extend(reassembler.feed(&info, frame));
+            info.decoded = decode_pgn(info.pgn, &frame.data);
+            frame.j1939 = Some(info);
+        }
+        completed
+    }
+}
+
+#[tauri::command]
+pub fn set_j1939_mode(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    enabled: bool,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let slot = (device.dev_type, device.dev_index, channel);
+    let mut j1939 = app_state.j1939.lock().map_err(|_| "Failed to lock J1939 state")?;
+    if enabled {
+        j1939.channels.entry(slot).or_default();
+    } else {
+        j1939.channels.remove(&slot);
+    }
+    Ok(format!("J1939 mode {} on CAN{}", if enabled { "enabled" } else { "disabled" }, channel + 1))
+}
+
+/// 送出 Request PGN (59904)；destination 省略時為全域位址
+#[tauri::command]
+pub async fn j1939_request_pgn(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    pgn: u32,
+    destination: Option<u8>,
+    source_address: Option<u8>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<String, String> {
+    if pgn > 0x3FFFF {
+        return Err(format!("PGN {} is out of range", pgn));
+    }
+    let destination = destination.unwrap_or(GLOBAL_ADDRESS);
+    let source_address = source_address.unwrap_or(DEFAULT_SOURCE_ADDRESS);
+    let mut can_obj = VciCanObj {
+        id: REQUEST_PRIORITY << 26 | PGN_REQUEST << 8 | (destination as u32) << 8 | source_address as u32,
+        extern_flag: 1,
+        data_len: 3,
+        ..Default::default()
+    };
+    can_obj.data[..3].copy_from_slice(&pgn.to_le_bytes()[..3]);
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let tx_path = {
+            let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+            app_state.tx_path(key, channel)?
+        };
+        tx_path.transmit(&[can_obj], true)?;
+        Ok(format!("Requested PGN {} from 0x{:02X}", pgn, destination))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn j1939_mode_is_enabled_per_device() {
+        let mut j1939 = J1939State::default();
+        j1939.channels.entry((4, 0, 0)).or_default();
+        let can_obj = VciCanObj {
+            id: 0x18FE_F100,
+            extern_flag: 1,
+            data_len: 8,
+            ..Default::default()
+        };
+        let mut enabled = vec![CanFrameEvent::from_raw((4, 0), 0, &can_obj, 0)];
+        let mut disabled = vec![CanFrameEvent::from_raw((4, 1), 0, &can_obj, 0)];
+
+        j1939.process((4, 0), 0, &mut enabled);
+        j1939.process((4, 1), 0, &mut disabled);
+
+        assert_eq!(enabled[0].j1939.as_ref().map(|info| info.pgn), Some(PGN_CCVS));
+        assert!(disabled[0].j1939.is_none());
+    }
+}
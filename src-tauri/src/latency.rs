@@ -0,0 +1,134 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::{host_timestamp_us, FrameInput};
+use crate::tap::TapReceiver;
+use crate::{run_blocking, DeviceType, StateMutex};
+
+/// 單次量測的原始時間
+struct Sample {
+    tx_at: Instant,
+    rx_at: Instant,
+    tx_host_us: u64,
+    rx_host_us: u64,
+    /// 回應訊框的裝置時間戳記 (0.1 ms)
+    device_timestamp: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampSource {
+    Device,
+    Host,
+}
+
+#[derive(Serialize)]
+pub struct LatencyResult {
+    pub iterations: u32,
+    pub responses: u32,
+    pub timeouts: u32,
+    pub min_us: Option<u64>,
+    pub mean_us: Option<u64>,
+    pub max_us: Option<u64>,
+    pub p95_us: Option<u64>,
+    pub timestamp_source: TimestampSource,
+}
+
+/// 所有回應都帶裝置時間戳記時，以「主機收到時間 − 裝置時間」的最小值估計兩個時鐘的差，
+/// 再把回應的裝置時間換算成主機時間，去掉 USB 與輪詢造成的延遲；否則使用主機的單調時鐘
+fn latencies(samples: &[Sample]) -> (Vec<u64>, TimestampSource) {
+    if !samples.is_empty() && samples.iter().all(|s| s.device_timestamp.is_some()) {
+        let device_us = |s: &Sample| s.device_timestamp.unwrap_or_default() as i64 * 100;
+        let offset = samples
+            .iter()
+            .map(|s| s.rx_host_us as i64 - device_us(s))
+            .min()
+            .unwrap_or_default();
+        let values = samples
+            .iter()
+            .map(|s| (device_us(s) + offset - s.tx_host_us as i64).max(0) as u64)
+            .collect();
+        return (values, TimestampSource::Device);
+    }
+    let values = samples
+        .iter()
+        .map(|s| s.rx_at.duration_since(s.tx_at).as_micros() as u64)
+        .collect();
+    (values, TimestampSource::Host)
+}
+
+/// 重複送出 request_frame 並等待 ID 為 response_id 的訊框，回報往返延遲 (微秒)。
+/// 經由 TapReceiver 讀取，不影響進行中的接收串流
+#[tauri::command]
+pub async fn measure_latency(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    request_frame: FrameInput,
+    response_id: u32,
+    iterations: u32,
+    interval_ms: u64,
+    timeout_ms: u64,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<LatencyResult, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        if iterations == 0 {
+            return Err("iterations must be greater than 0".into());
+        }
+        let request = request_frame.checked("request_frame")?;
+        let key = {
+            let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key()
+        };
+        let tap = TapReceiver::open(&state, key, channel)?;
+        let timeout = Duration::from_millis(timeout_ms);
+        let mut samples = Vec::new();
+        let mut timeouts = 0;
+        for iteration in 0..iterations {
+            if iteration > 0 {
+                std::thread::sleep(Duration::from_millis(interval_ms));
+            }
+            // 丟掉上一輪逾時後才到的回應
+            while tap.recv_until(Instant::now()).is_some() {}
+            let tx_host_us = host_timestamp_us();
+            let tx_at = Instant::now();
+            crate::transmit(&state, key, channel, std::slice::from_ref(&request))?;
+            let deadline = tx_at + timeout;
+            let response = loop {
+                match tap.recv_until(deadline) {
+                    Some(frame) if frame.id == response_id && !frame.remote => break Some(frame),
+                    Some(_) => continue,
+                    None => break None,
+                }
+            };
+            match response {
+                Some(frame) => samples.push(Sample {
+                    tx_at,
+                    rx_at: Instant::now(),
+                    tx_host_us,
+                    rx_host_us: frame.host_timestamp_us,
+                    device_timestamp: frame.device_timestamp,
+                }),
+                None => timeouts += 1,
+            }
+        }
+        let (mut values, timestamp_source) = latencies(&samples);
+        values.sort_unstable();
+        let p95_index = (values.len() * 95).div_ceil(100).saturating_sub(1);
+        Ok(LatencyResult {
+            iterations,
+            responses: values.len() as u32,
+            timeouts,
+            min_us: values.first().copied(),
+            mean_us: (!values.is_empty()).then(|| values.iter().sum::<u64>() / values.len() as u64),
+            max_us: values.last().copied(),
+            p95_us: values.get(p95_index).copied(),
+            timestamp_source,
+        })
+    })
+    .await
+}
@@ -1,269 +1,1001 @@
-use libloading::Library;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
 use tauri::Emitter;
-use tauri::State;
-use serde::Serialize;
-
-#[repr(C)]
-#[derive(Debug, Default)]
-pub struct VciCanObj {
-    pub id: u32,
-    pub time_stamp: u32,
-    pub time_flag: u8,
-    pub send_type: u8,
-    pub remote_flag: u8,
-    pub extern_flag: u8,
-    pub data_len: u8,
-    pub data: [u8; 8],
-    pub reserved: [u8; 3],
-}
-
-#[repr(C)]
-#[derive(Debug, Default)]
-pub struct VciInitConfig {
-    pub acc_code: u32,
-    pub acc_mask: u32,
-    pub reserved: u32,
-    pub filter: u8,
-    pub timing0: u8,
-    pub timing1: u8,
-    pub mode: u8,
-}
-
-#[repr(C)]
-#[derive(Debug)]
-pub struct VciBoardInfo {
-    pub hw_version: u16,
-    pub fw_version: u16,
-    pub dr_version: u16,
-    pub in_version: u16,
-    pub irq_num: u16,
-    pub can_num: u8,
-    pub str_serial_num: [u8; 20],
-    pub str_hw_type: [u8; 40],
-    pub reserved: [u16; 4],
-}
+use tauri::{Manager, RunEvent, State};
+use serde::{Deserialize, Serialize};
 
-impl Default for VciBoardInfo {
-    fn default() -> Self {
-        Self {
-            hw_version: 0,
-            fw_version: 0,
-            dr_version: 0,
-            in_version: 0,
-            irq_num: 0,
-            can_num: 0,
-            str_serial_num: [0; 20], 
-            str_hw_type: [0; 40],    
-            reserved: [0; 4],
-        }
-    }
-}
+mod annotation;
+mod auto_connect;
+mod baud;
+mod benchmark;
+mod burst;
+mod bus_quality;
+mod bus_state;
+mod busoff;
+mod capture;
+mod channel;
+mod channel_change;
+mod config_history;
+mod canopen;
+mod dbc;
+mod dedupe;
+mod emit_queue;
+mod delta;
+mod e2e;
+mod filter;
+mod device_type;
+mod frame;
+mod fuzz;
+mod gateway;
+mod heartbeat;
+mod hotplug;
+mod id_names;
+mod isotp;
+mod j1939;
+mod latency;
+mod logging;
+mod memory;
+mod obd;
+mod operation;
+mod overflow;
+mod periodic;
+mod probe;
+mod profile;
+mod receive;
+mod replay;
+mod reply;
+mod responder;
+mod ring_buffer;
+mod selftest;
+mod sequence;
+mod settings;
+mod mqtt;
+mod socketcand;
+mod state_lock;
+mod stats;
+mod status;
+mod subscription;
+mod supervisor;
+mod tap;
+mod timed_capture;
+mod timestamp;
+mod trigger;
+mod tx_limit;
+mod uds;
+mod usb_reset;
+mod virtual_can;
+mod watchdog;
+mod ws_bridge;
 
+pub use can_core::{can_interface, mock};
+pub use can_core::{Backend, CanDevice, CanInterface, DeviceType};
+pub use can_core::{CanLibrary, VciBoardInfo, VciCanObj, VciCanStatus, VciErrInfo, VciInitConfig};
+#[cfg(target_os = "linux")]
+use can_core::socketcan;
+use can_core::controlcan;
+pub use annotation::{Annotation, AnnotationMarker};
+pub use auto_connect::{run_auto_connect, AutoConnectSettings, AutoConnectStatus};
+pub use channel::transmit;
+pub use channel_change::{change_channel, ChannelChangePlan};
+pub use config_history::ConfigGeneration;
+pub use dedupe::{Dedupe, DedupeCompare};
+pub use frame::{FrameInput, Provenance};
+pub use heartbeat::{backend_heartbeat, BackendHeartbeat, Pulses, WorkerPulse};
+pub use isotp::{IsoTpError, IsoTpLink, IsoTpOptions};
+pub use memory::{MemoryLimits, MemoryStats};
+pub use operation::{OperationFinished, OperationInfo, OperationKind, OperationStatus};
+pub use probe::{ProbeResult, ProbeStatus};
+pub use replay::{parse_log, LogDialect, ParsedLog};
+pub use receive::{receive_once, spawn_receive_loop, EventSink, ReceiveOptions};
+pub use ring_buffer::{query_recent_frames, FrameQuery};
+pub use sequence::{spawn_tx_sequence, SequenceStep};
+pub use usb_reset::{force_usb_reset_device, UsbResetResult};
+pub use state_lock::{StateMutex, StateRecoveredEvent};
+pub use status::{app_status, AppStatus};
+pub use tx_limit::{transmit_paced_as, transmit_tracked, TxOutcome, TxRetry};
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct DeviceInfo {
     pub index: i32,
     pub serial_number: String,
+    pub hardware_type: String,
+    pub channel_count: u8,
+    pub hardware_version: u16,
     pub firmware_version: u16,
+    pub driver_version: u16,
+    pub interface_version: u16,
+    pub irq_num: u16,
+    pub hardware_version_text: String,
+    pub firmware_version_text: String,
+    pub driver_version_text: String,
+    pub interface_version_text: String,
 }
 
-pub struct CanLibrary {
-    _lib: Arc<Library>,
-    pub vci_open_device: unsafe extern "stdcall" fn(u32, u32, u32) -> i32,
-    pub vci_close_device: unsafe extern "stdcall" fn(u32, u32) -> i32,
-    pub vci_init_can: unsafe extern "stdcall" fn(u32, u32, u32, *const VciInitConfig) -> i32,
-    pub vci_start_can: unsafe extern "stdcall" fn(u32, u32, u32) -> i32,
-    pub vci_transmit: unsafe extern "stdcall" fn(u32, u32, u32, *const VciCanObj, u32) -> i32,
-    pub vci_receive: unsafe extern "stdcall" fn(u32, u32, u32, *mut VciCanObj, u32, i32) -> i32,
-    pub vci_find_usb_device2: unsafe extern "stdcall" fn(*mut VciBoardInfo) -> i32,
-    pub vci_read_board_info: unsafe extern "stdcall" fn(u32, u32, *mut VciBoardInfo) -> i32,
-}
-impl CanLibrary {
-    /// 載入 DLL 並取得所有所需的函數指標
-    pub fn new(_dll_name: &str) -> Arc<Self> {
-        let lib = Arc::new(unsafe { Library::new(_dll_name) }.expect("DLL load failed"));
-        unsafe {
-            Arc::new(Self {
-                _lib: lib.clone(),
-                vci_open_device: *lib.get(b"VCI_OpenDevice").expect("Failed to get VCI_OpenDevice"),
-                vci_close_device: *lib.get(b"VCI_CloseDevice").expect("Failed to get VCI_CloseDevice"),
-                vci_init_can: *lib.get(b"VCI_InitCAN").expect("Failed to get VCI_InitCAN"),
-                vci_start_can: *lib.get(b"VCI_StartCAN").expect("Failed to get VCI_StartCAN"),
-                vci_transmit: *lib.get(b"VCI_Transmit").expect("Failed to get VCI_Transmit"),
-                vci_receive: *lib.get(b"VCI_Receive").expect("Failed to get VCI_Receive"),
-                vci_find_usb_device2: *lib.get(b"VCI_FindUsbDevice2").expect("Failed to get VCI_FindUsbDevice2"),
-                vci_read_board_info: *lib.get(b"VCI_ReadBoardInfo").expect("Failed to get VCI_ReadBoardInfo"), // ✅ 新增 VCI_ReadBoardInfo
-            })
+impl DeviceInfo {
+    fn from_board_info(index: u32, board_info: &VciBoardInfo) -> Self {
+        Self {
+            index: index as i32,
+            serial_number: fixed_str(&board_info.str_serial_num),
+            hardware_type: fixed_str(&board_info.str_hw_type),
+            channel_count: board_info.can_num,
+            hardware_version: board_info.hw_version,
+            firmware_version: board_info.fw_version,
+            driver_version: board_info.dr_version,
+            interface_version: board_info.in_version,
+            irq_num: board_info.irq_num,
+            hardware_version_text: version_text(board_info.hw_version),
+            firmware_version_text: version_text(board_info.fw_version),
+            driver_version_text: version_text(board_info.dr_version),
+            interface_version_text: version_text(board_info.in_version),
         }
     }
 }
 
-struct AppState {
-    can_library: Option<Arc<CanLibrary>>,
-    receiving: Arc<AtomicBool>,
+/// 固定長度、以 NUL 結尾的字串欄位
+fn fixed_str(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
 }
 
-#[tauri::command]
-fn open_can_device(
+/// 版本號高位元組為主版號、低位元組為次版號：0x0205 → "2.05"
+fn version_text(version: u16) -> String {
+    format!("{:x}.{:02x}", version >> 8, version & 0xFF)
+}
+
+/// 未指定 dev_type 時預設為 USBCAN2 (CANalyst-II)
+const DEFAULT_DEV_TYPE: DeviceType = DeviceType::Usbcan2;
+
+#[derive(Serialize)]
+pub struct EnumeratedDevice {
+    #[serde(flatten)]
+    pub info: DeviceInfo,
+    pub is_open: bool,
+}
+
+/// 通道最後一次初始化所用的設定與啟動狀態
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    config: VciInitConfig,
+    started: bool,
+}
+
+/// 一個已開啟的裝置；每個裝置有自己的通道狀態與接收旗標，關閉時不影響其他裝置
+struct OpenDevice {
     dev_type: u32,
     dev_index: u32,
-    app_handle: tauri::AppHandle,
-    state: State<Arc<Mutex<AppState>>>,
-) -> Result<String, String> {
-    let can_lib = CanLibrary::new("ControlCAN.dll");
-    let reserved = 0u32;
+    serial_number: Option<String>,
+    /// 開啟時由 VCI_ReadBoardInfo 讀到的通道數；不支援時為 None，不檢查通道編號
+    channel_count: Option<u8>,
+    /// 開啟或重新連線時讀到的板卡資訊，read_board_info 預設回傳這份
+    board_info: Option<DeviceInfo>,
+    channels: HashMap<u32, ChannelState>,
+    /// 各通道接收執行緒的執行旗標，執行緒結束時移除自己的項目
+    receiving: HashMap<u32, Arc<AtomicBool>>,
+    /// 各通道接收執行緒啟動時的選項，重新連線後以相同選項重新啟動
+    receive_options: HashMap<u32, ReceiveOptions>,
+    /// 設定了自動停止上限的接收執行緒的進度
+    captures: HashMap<u32, Arc<timed_capture::CaptureProgress>>,
+    /// 由熱插拔監看偵測到裝置已被拔除
+    disconnected: bool,
+    /// 自動重連連續失敗這麼多次後重設 USB (見 set_usb_reset_fallback)
+    usb_reset_after: Option<u32>,
+    /// 移除 OpenDevice 時由它停止接收執行緒並關閉裝置
+    handle: CanDevice,
+}
 
-    unsafe {
-        if (can_lib.vci_open_device)(dev_type, dev_index, reserved) != 1 {
-            let error_message = "開啟 CAN 裝置失敗".to_string();
-            app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
-            return Err(error_message);
+impl OpenDevice {
+    fn new(handle: CanDevice) -> Self {
+        Self {
+            dev_type: handle.dev_type(),
+            dev_index: handle.dev_index(),
+            serial_number: None,
+            channel_count: None,
+            board_info: None,
+            channels: HashMap::new(),
+            receiving: HashMap::new(),
+            receive_options: HashMap::new(),
+            captures: HashMap::new(),
+            disconnected: false,
+            usb_reset_after: None,
+            handle,
         }
     }
 
-    println!("Device opened successfully");
+    fn key(&self) -> (u32, u32) {
+        (self.dev_type, self.dev_index)
+    }
 
-    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
-    app_state.can_library = Some(can_lib);
-    drop(app_state);
+    /// 讓 CanDevice 在關閉前等待通道的接收執行緒；執行緒已結束時以已清除的旗標登記
+    fn register_receive_thread(&mut self, channel: u32, handle: JoinHandle<()>) {
+        let running = self.receiving.get(&channel).cloned().unwrap_or_default();
+        self.handle.register_thread(channel, running, handle);
+    }
+
+    /// 通道編號必須小於板卡的 can_num
+    fn check_channel(&self, channel: u32) -> Result<(), String> {
+        match self.channel_count {
+            Some(count) if channel >= count as u32 => Err(invalid_argument(
+                "can_channel",
+                format!("device has {} channels (0..={})", count, count.saturating_sub(1)),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
 
-    Ok("CAN device opened and started successfully".into())
+/// 在阻塞執行緒池上執行會呼叫 DLL 或等待匯流排回應的命令，不佔用 IPC 處理。
+/// 工作本身只在需要時短暫取得 state 鎖，等待 DLL 期間不可持有
+pub(crate) async fn run_blocking<T, E>(work: impl FnOnce() -> Result<T, E> + Send + 'static) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: From<String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| E::from(format!("command worker failed: {}", e)))?
 }
 
-#[tauri::command]
-fn stop_can_device(
-    dev_type: u32,
-    dev_index: u32,
-    app_handle: tauri::AppHandle,
-    state: State<Arc<Mutex<AppState>>>,
-) -> Result<String, String> {
-    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
-    if let Some(ref can_lib) = app_state.can_library {
-        unsafe {
-            (can_lib.vci_close_device)(dev_type, dev_index);
+/// 命令參數不合法時的錯誤訊息
+pub(crate) fn invalid_argument(field: &str, reason: impl std::fmt::Display) -> String {
+    format!("InvalidArgument {{ field: \"{}\", reason: \"{}\" }}", field, reason)
+}
+
+/// set_tx_inhibit 禁止傳送的通道拒絕傳送時的錯誤訊息
+pub(crate) fn tx_inhibited(channel: u32) -> String {
+    format!("TxInhibited {{ channel: {} }}", channel)
+}
+
+/// 整個程式共用的狀態；以 Arc<StateMutex> 交給 Tauri 管理
+#[derive(Default)]
+pub struct AppState {
+    /// 目前使用的後端；可能是 ControlCAN.dll、虛擬裝置或測試用的 MockCan。
+    /// 只有切換後端時寫入，其餘路徑複製 Arc 後即放開
+    can_library: Arc<RwLock<Option<Arc<dyn CanInterface>>>>,
+    /// 選用虛擬後端時與 can_library 指向同一個物件，用來設定合成流量
+    virtual_can: Option<Arc<can_core::virtual_can::VirtualCan>>,
+    devices: HashMap<(u32, u32), OpenDevice>,
+    device_watch: Option<Arc<AtomicBool>>,
+    frame_buffer: Arc<Mutex<ring_buffer::FrameRing>>,
+    id_statistics: Arc<Mutex<stats::IdStatistics>>,
+    channel_runtime: HashMap<(u32, u32, u32), Arc<channel::ChannelRuntime>>,
+    log_sink: Arc<Mutex<Option<logging::LogSink>>>,
+    logger: Option<logging::ActiveLogger>,
+    replay_log: Option<Arc<replay::LoadedLog>>,
+    replay: Option<replay::ActiveReplay>,
+    auto_connect: auto_connect::AutoConnect,
+    dbc: Arc<Mutex<Option<Arc<dbc::Dbc>>>>,
+    /// 依優先順序載入的 DBC；合併結果放在 dbc
+    dbc_layers: dbc::layers::DbcLayers,
+    id_names: Arc<Mutex<Option<Arc<id_names::IdNames>>>>,
+    periodic_tasks: HashMap<u32, periodic::PeriodicTask>,
+    next_periodic_id: u32,
+    /// 重播、ISO-TP、UDS 下載、效能測試、burst 與序列等可以 cancel_operation 取消的操作
+    operations: operation::Operations,
+    /// 超過一個分段、可以中止的傳送，值為 (通道, 進度)
+    tx_operations: HashMap<u32, (u32, Arc<tx_limit::TxControl>)>,
+    next_tx_operation_id: u32,
+    fuzzer: Option<Arc<AtomicBool>>,
+    e2e_checks: Arc<Mutex<e2e::E2eChecks>>,
+    software_filters: Arc<Mutex<filter::SoftwareFilters>>,
+    subscriptions: Arc<Mutex<subscription::Subscriptions>>,
+    /// transmit_signals 的 E2E 計數器，以 (dev_type, dev_index, channel, id) 區分
+    e2e_counters: HashMap<(u32, u32, u32, u32), u16>,
+    frame_taps: Arc<Mutex<tap::FrameTaps>>,
+    /// configure_isotp 設定的連線參數，以 link_id 區分
+    isotp_links: HashMap<String, isotp::IsoTpLinkConfig>,
+    /// 以 (dev_type, dev_index, channel, tx_id) 區分的進行中 ISO-TP 傳輸數
+    isotp_activity: HashMap<(u32, u32, u32, u32), Arc<AtomicUsize>>,
+    j1939: Arc<Mutex<j1939::J1939State>>,
+    /// 以 (dev_type, dev_index, channel) 區分的 CANopen 心跳監看
+    canopen_monitors: HashMap<(u32, u32, u32), canopen::nmt::CanopenMonitor>,
+    gateway: Option<gateway::Gateway>,
+    /// WebSocket 橋接的用戶端；沒有橋接時為空
+    ws_hub: Arc<ws_bridge::WsHub>,
+    ws_bridge: Option<ws_bridge::WsBridge>,
+    /// 各 port 上 socketcand 服務的執行旗標
+    socketcand_servers: HashMap<u16, Arc<AtomicBool>>,
+    /// MQTT 發佈執行中時把訊框交給發佈執行緒
+    mqtt_feed: Arc<mqtt::MqttFeed>,
+    mqtt_publisher: Option<mqtt::MqttPublisher>,
+    auto_responder: Arc<Mutex<responder::AutoResponder>>,
+    triggers: Arc<Mutex<trigger::TriggerTable>>,
+    captures: Arc<Mutex<capture::Captures>>,
+    watchdogs: Arc<Mutex<watchdog::BusWatchdogs>>,
+    /// 接收與週期傳送等工作執行緒的存活時間，由 backend-heartbeat 檢查
+    pulses: Arc<heartbeat::Pulses>,
+    /// 在 setup 時設定，讓不帶 AppHandle 的傳送路徑也能送出 TX 回送事件
+    app_handle: Option<tauri::AppHandle>,
+}
+
+impl AppState {
+    /// 使用指定後端的狀態 (例如測試時的 MockCan)
+    pub fn with_interface(interface: Arc<dyn CanInterface>) -> Self {
+        Self {
+            can_library: Arc::new(RwLock::new(Some(interface))),
+            ..Default::default()
         }
-        app_state.can_library = None;
-        return Ok("CAN device stopped successfully".into());
     }
-    let error_message = "CAN 裝置尚未初始化".to_string();
-    app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
-    Err(error_message)
-}
 
-#[tauri::command]
-fn start_receiving_data(
-    app_handle: tauri::AppHandle,
-    dev_type: u32,
-    dev_index: u32,
-    can_channel: u32,
-    state: State<Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
-    let state_clone = state.inner().clone();
-    let receiving_flag = {
-        let state_guard = state.lock().map_err(|_| "Failed to lock state")?;
-        state_guard.receiving.clone()
-    };
-    receiving_flag.store(true, Ordering::SeqCst);
-    std::thread::spawn(move || {
-        while receiving_flag.load(Ordering::SeqCst) {
-            let message_opt = match state_clone.lock() {
-                Ok(state_guard) => {
-                    if let Some(ref can_lib) = state_guard.can_library {
-                        let mut can_obj = VciCanObj::default();
-                        let received_frames = unsafe {
-                            (can_lib.vci_receive)(dev_type, dev_index, can_channel, &mut can_obj, 1, 500)
-                        };
-                        if received_frames > 0 {
-                            let data = &can_obj.data[..(can_obj.data_len as usize)];
-                            Some(format!("Received CAN message: ID=0x{:X}, Data={:?}", can_obj.id, data))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
+    /// 取得目前的後端；尚未選擇時載入 DLL，之後所有裝置共用。DLL 不存在或缺少函式時回傳錯誤
+    fn try_library(&mut self) -> Result<Arc<dyn CanInterface>, String> {
+        if let Some(can_lib) = self.backend() {
+            return Ok(can_lib);
+        }
+        let can_lib: Arc<dyn CanInterface> = CanLibrary::load(controlcan::DEFAULT_LIBRARY).map_err(|failure| failure.message)?;
+        *self.can_library.write().unwrap_or_else(PoisonError::into_inner) = Some(can_lib.clone());
+        Ok(can_lib)
+    }
+
+    /// 目前的後端；尚未選擇時為 None，不會載入 DLL
+    fn backend(&self) -> Option<Arc<dyn CanInterface>> {
+        self.can_library.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// 切換實體/虛擬後端；仍有裝置開啟時不能切換
+    fn select_backend(&mut self, backend: Backend) -> Result<(), String> {
+        let current = self.backend().map_or(Backend::ControlCan, |lib| lib.backend());
+        if current == backend {
+            return Ok(());
+        }
+        if !self.devices.is_empty() {
+            return Err(format!(
+                "close all devices before switching to the {} backend",
+                match backend {
+                    Backend::ControlCan => "ControlCAN",
+                    Backend::Virtual => "virtual",
+                    Backend::SocketCan => "SocketCAN",
+                    Backend::Mock => "mock",
+                }
+            ));
+        }
+        self.virtual_can = None;
+        let selected: Option<Arc<dyn CanInterface>> = match backend {
+            Backend::Virtual => {
+                let virtual_can = can_core::virtual_can::VirtualCan::new();
+                self.virtual_can = Some(virtual_can.clone());
+                Some(virtual_can)
+            }
+            #[cfg(target_os = "linux")]
+            Backend::SocketCan => Some(Arc::new(socketcan::SocketCan::default())),
+            #[cfg(not(target_os = "linux"))]
+            Backend::SocketCan => return Err("SocketCAN is only available on Linux".into()),
+            // 實體 DLL 延後到 try_library() 第一次使用時才載入
+            Backend::ControlCan | Backend::Mock => None,
+        };
+        *self.can_library.write().unwrap_or_else(PoisonError::into_inner) = selected;
+        Ok(())
+    }
+
+    /// 依 dev_type/dev_index 找出已開啟的裝置；只開啟一個裝置時兩者皆可省略
+    fn device(&self, dev_type: Option<u32>, dev_index: Option<u32>) -> Result<&OpenDevice, String> {
+        if self.devices.is_empty() {
+            return Err("CAN 裝置尚未初始化".to_string());
+        }
+        let mut candidates: Vec<&OpenDevice> = self
+            .devices
+            .values()
+            .filter(|d| dev_type.is_none_or(|t| t == d.dev_type) && dev_index.is_none_or(|i| i == d.dev_index))
+            .collect();
+        match candidates.len() {
+            1 => Ok(candidates.remove(0)),
+            0 => {
+                if let [open] = self.devices.values().collect::<Vec<_>>()[..] {
+                    if let Some(index) = dev_index.filter(|&i| i != open.dev_index) {
+                        return Err(format!("device {} is not the open device ({})", index, open.dev_index));
                     }
+                    return Err(format!(
+                        "device type {} is not the open device type ({})",
+                        dev_type.unwrap_or_default(),
+                        open.dev_type
+                    ));
                 }
-                Err(_) => None,
-            };
-            if let Some(msg) = message_opt {
-                let _ = app_handle.emit("can-data", msg);
+                Err(format!(
+                    "device {}:{} is not open (open devices: {})",
+                    dev_type.map_or("*".to_string(), |t| t.to_string()),
+                    dev_index.map_or("*".to_string(), |i| i.to_string()),
+                    self.open_device_list()
+                ))
             }
-            std::thread::sleep(std::time::Duration::from_millis(10));
+            _ => Err(format!(
+                "multiple devices are open ({}); specify dev_type and dev_index",
+                self.open_device_list()
+            )),
         }
-    });
-    Ok(())
+    }
+
+    /// 同 device()，但裝置已被拔除時回傳明確的錯誤，避免拿到難以理解的 DLL 錯誤碼
+    fn connected_device(&self, dev_type: Option<u32>, dev_index: Option<u32>) -> Result<&OpenDevice, String> {
+        let device = self.device(dev_type, dev_index)?;
+        if device.disconnected {
+            return Err(format!("device {} disconnected", device.dev_index));
+        }
+        Ok(device)
+    }
+
+    fn device_mut(&mut self, dev_type: Option<u32>, dev_index: Option<u32>) -> Result<&mut OpenDevice, String> {
+        let key = self.device(dev_type, dev_index)?.key();
+        Ok(self.devices.get_mut(&key).expect("device key resolved above"))
+    }
+
+    fn channel_counters(&mut self, key: (u32, u32), channel: u32) -> Arc<stats::ChannelCounters> {
+        self.channel_runtime(key, channel).counters.clone()
+    }
+
+    /// 初始化並啟動通道，記下設定供重新連線時還原
+    pub fn start_channel(&mut self, key: (u32, u32), channel: u32, config: VciInitConfig) -> Result<(), String> {
+        self.init_channel(key, channel, config)?;
+        self.start_initialized_channel(key, channel, config)
+    }
+
+    /// 啟動已初始化的通道並記下啟動時間；同時啟動多個通道時先全部 init 再連續呼叫
+    pub(crate) fn start_initialized_channel(&mut self, key: (u32, u32), channel: u32, config: VciInitConfig) -> Result<(), String> {
+        let can_lib = self.backend().ok_or("CAN 裝置尚未初始化")?;
+        timestamp::start_and_mark(can_lib.as_ref(), &self.channel_runtime(key, channel).clock, key, channel)
+            .map_err(|_| format!("Failed to start CAN{}", channel + 1))?;
+        let device = self.device_mut(Some(key.0), Some(key.1))?;
+        device.channels.insert(channel, ChannelState { config, started: true });
+        Ok(())
+    }
+
+    /// 只初始化通道、不啟動，記下設定
+    pub fn init_channel(&mut self, key: (u32, u32), channel: u32, config: VciInitConfig) -> Result<(), String> {
+        let can_lib = self.backend().ok_or("CAN 裝置尚未初始化")?;
+        self.device(Some(key.0), Some(key.1))?.check_channel(channel)?;
+        can_lib
+            .init_channel(key.0, key.1, channel, &config)
+            .map_err(|_| format!("Failed to initialize CAN{}", channel + 1))?;
+        let device = self.device_mut(Some(key.0), Some(key.1))?;
+        device.channels.insert(channel, ChannelState { config, started: false });
+        self.reset_channel_counters(key, channel, &config);
+        self.record_channel_config(key, channel, config);
+        Ok(())
+    }
+
+    /// 通道 (重新) 初始化後歸零計數器並記下位元率，供負載估算使用
+    fn reset_channel_counters(&mut self, key: (u32, u32), channel: u32, config: &VciInitConfig) {
+        let counters = self.channel_counters(key, channel);
+        counters.reset();
+        counters
+            .bitrate
+            .store(baud::bitrate_from_timing(config.timing0, config.timing1), Ordering::Relaxed);
+    }
+
+    /// 呼叫 VCI_FindUsbDevice2 取得目前插著的所有裝置
+    fn enumerate_devices(&mut self) -> Result<Vec<DeviceInfo>, String> {
+        list_devices(self.try_library()?.as_ref())
+    }
+
+    /// 列舉 backend 上的裝置而不切換目前的後端，讓套用設定檔前能先確認裝置都在
+    fn enumerate_with(&mut self, backend: Backend) -> Result<Vec<DeviceInfo>, String> {
+        if self.backend().map_or(Backend::ControlCan, |lib| lib.backend()) == backend {
+            return self.enumerate_devices();
+        }
+        let probe: Arc<dyn CanInterface> = match backend {
+            Backend::Virtual => can_core::virtual_can::VirtualCan::new(),
+            #[cfg(target_os = "linux")]
+            Backend::SocketCan => Arc::new(socketcan::SocketCan::default()),
+            #[cfg(not(target_os = "linux"))]
+            Backend::SocketCan => return Err("SocketCAN is only available on Linux".into()),
+            Backend::ControlCan | Backend::Mock => CanLibrary::load(controlcan::DEFAULT_LIBRARY).map_err(|failure| failure.message)?,
+        };
+        list_devices(probe.as_ref())
+    }
+
+    /// 開啟裝置並登記到 devices，送出 device-opened；回傳讀到的板卡資訊 (後端不支援時為 None)
+    pub fn open_device(&mut self, dev_type: u32, dev_index: u32, serial_number: Option<String>) -> Result<Option<DeviceInfo>, String> {
+        if self.devices.contains_key(&(dev_type, dev_index)) {
+            return Err(format!("device {} is already open", dev_index));
+        }
+        let handle = CanDevice::open(self.try_library()?, dev_type, dev_index).map_err(|_| "開啟 CAN 裝置失敗".to_string())?;
+        let board_info = read_device_info(handle.interface(), dev_type, dev_index);
+        let mut device = OpenDevice::new(handle);
+        device.channel_count = board_info.as_ref().map(|b| b.channel_count).filter(|&n| n > 0);
+        device.serial_number = serial_number.or_else(|| Some(board_info.as_ref()?.serial_number.clone()));
+        device.board_info = board_info.clone();
+        self.devices.insert((dev_type, dev_index), device);
+        self.save_settings((dev_type, dev_index));
+        self.emit_device_opened((dev_type, dev_index));
+        Ok(board_info)
+    }
+
+    fn emit_device_opened(&self, key: (u32, u32)) {
+        let (Some(app_handle), Some(device)) = (&self.app_handle, self.devices.get(&key)) else {
+            return;
+        };
+        let _ = app_handle.emit(
+            "device-opened",
+            DeviceOpened {
+                dev_type: key.0,
+                dev_index: key.1,
+                serial_number: device.serial_number.clone(),
+                board_info: device.board_info.clone(),
+            },
+        );
+    }
+
+    /// 裝置斷線後重新開啟：依序號找回目前的 index，再以先前儲存的通道設定重新 init/start。
+    /// 成功時回傳裝置新的 key (index 可能因重新插拔而改變)
+    fn reopen_device(&mut self, key: (u32, u32)) -> Result<(u32, u32), String> {
+        let serial_number = self.devices.get(&key).ok_or("device closed")?.serial_number.clone();
+        let dev_index = match self.enumerate_devices() {
+            Ok(found) => match &serial_number {
+                Some(serial) => found.iter().find(|d| &d.serial_number == serial).map(|d| d.index as u32),
+                None => ((key.1 as usize) < found.len()).then_some(key.1),
+            },
+            // DLL 不支援列舉時只能假設裝置仍在原本的 index
+            Err(_) => Some(key.1),
+        }
+        .ok_or("device not present")?;
+
+        let can_lib = self.try_library()?;
+        let mut device = self.devices.remove(&key).ok_or("device closed")?;
+        let (dev_type, _) = key;
+        // 在初始化、啟動前搬移，禁止傳送等設定在新的 index 上立即生效
+        self.move_channel_runtime(key, (dev_type, dev_index));
+        // 由呼叫的接收執行緒持有 state 鎖，只重新開啟硬體，執行緒保持登記在裝置上
+        let result = device
+            .handle
+            .reopen(dev_index)
+            .map_err(|_| "Failed to open device".to_string())
+            .and_then(|_| {
+                for (&channel, channel_state) in &device.channels {
+                    can_lib
+                        .init_channel(dev_type, dev_index, channel, &channel_state.config)
+                        .map_err(|_| format!("Failed to initialize CAN{}", channel + 1))?;
+                    self.record_channel_config((dev_type, dev_index), channel, channel_state.config);
+                }
+                // 全部初始化後才連續啟動，讓各通道的時間戳記起點接近
+                for (&channel, channel_state) in &device.channels {
+                    if channel_state.started {
+                        let clock = self.channel_runtime((dev_type, dev_index), channel).clock.clone();
+                        timestamp::start_and_mark(can_lib.as_ref(), &clock, (dev_type, dev_index), channel)
+                            .map_err(|_| format!("Failed to start CAN{}", channel + 1))?;
+                    }
+                }
+                Ok(())
+            });
+        if let Err(error_message) = result {
+            self.move_channel_runtime((dev_type, dev_index), key);
+            self.devices.insert(key, device);
+            return Err(error_message);
+        }
+        device.dev_index = dev_index;
+        device.disconnected = false;
+        if let Some(board_info) = read_device_info(can_lib.as_ref(), dev_type, dev_index) {
+            device.board_info = Some(board_info);
+        }
+        let new_key = device.key();
+        for (&channel, channel_state) in &device.channels {
+            self.reset_channel_counters(new_key, channel, &channel_state.config);
+        }
+        self.devices.insert(new_key, device);
+        self.save_settings(new_key);
+        self.emit_device_opened(new_key);
+        Ok(new_key)
+    }
+
+    fn open_device_list(&self) -> String {
+        let mut keys: Vec<_> = self.devices.keys().collect();
+        keys.sort();
+        keys.iter()
+            .map(|(dev_type, dev_index)| format!("{}:{}", dev_type, dev_index))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// 讀取板卡資訊；後端不支援時為 None
+fn read_device_info(can_lib: &dyn CanInterface, dev_type: u32, dev_index: u32) -> Option<DeviceInfo> {
+    let board_info = can_lib.read_board_info(dev_type, dev_index).ok()?;
+    Some(DeviceInfo::from_board_info(dev_index, &board_info))
+}
+
+/// device-opened 事件；開啟與重新連線後都會送出
+#[derive(Serialize, Clone)]
+struct DeviceOpened {
+    dev_type: u32,
+    dev_index: u32,
+    serial_number: Option<String>,
+    board_info: Option<DeviceInfo>,
 }
 
+#[derive(Serialize)]
+struct OpenedDevice {
+    message: String,
+    /// 開啟後立即讀取的板卡資訊，不必再呼叫 read_board_info
+    board_info: Option<DeviceInfo>,
+}
 
 #[tauri::command]
-fn stop_receiving_data(state: State<Arc<Mutex<AppState>>>) -> Result<String, String> {
-    let state_guard = state.lock().map_err(|_| "Failed to lock state")?;
-    state_guard.receiving.store(false, Ordering::SeqCst);
-    Ok("Stopped receiving CAN data".into())
+async fn open_can_device(
+    dev_type: DeviceType,
+    dev_index: u32,
+    backend: Option<Backend>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<OpenedDevice, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let board_info = match app_state
+            .select_backend(backend.unwrap_or(Backend::ControlCan))
+            .and_then(|_| app_state.open_device(dev_type.code(), dev_index, None))
+        {
+            Ok(board_info) => board_info,
+            Err(error_message) => {
+                app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
+                return Err(error_message);
+            }
+        };
+        drop(app_state);
+
+        println!("Device opened successfully");
+
+        Ok(OpenedDevice {
+            message: "CAN device opened and started successfully".into(),
+            board_info,
+        })
+    })
+    .await
 }
 
+#[derive(Serialize)]
+pub struct OpenedBySerial {
+    pub dev_index: u32,
+    pub board_info: DeviceInfo,
+}
 
+/// 依序號開啟裝置；USB 重新插拔後 index 可能改變，序號則固定不變
+#[tauri::command]
+async fn open_device_by_serial(
+    serial: String,
+    dev_type: Option<DeviceType>,
+    backend: Option<Backend>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<OpenedBySerial, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        app_state.select_backend(backend.unwrap_or(Backend::ControlCan))?;
+        let found = app_state.enumerate_devices()?;
+        let Some(board_info) = found.iter().find(|d| d.serial_number == serial) else {
+            let serials: Vec<&str> = found.iter().map(|d| d.serial_number.as_str()).collect();
+            return Err(format!(
+                "serial {} not found (found: {})",
+                serial,
+                if serials.is_empty() { "none".to_string() } else { serials.join(", ") }
+            ));
+        };
+        let board_info = board_info.clone();
+        let dev_index = board_info.index as u32;
+        if let Err(error_message) = app_state.open_device(dev_type.unwrap_or(DEFAULT_DEV_TYPE).code(), dev_index, Some(serial.clone())) {
+            app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
+            return Err(error_message);
+        }
+        println!("Device {} opened at index {}", serial, dev_index);
+        Ok(OpenedBySerial {
+            dev_index,
+            board_info,
+        })
+    })
+    .await
+}
 
 #[tauri::command]
-fn transmit_can_data(
+async fn stop_can_device(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<String, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let key = match app_state.device(dev_type.map(DeviceType::code), dev_index) {
+            Ok(device) => device.key(),
+            Err(error_message) => {
+                app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
+                return Err(error_message);
+            }
+        };
+        let device = app_state.devices.remove(&key);
+        if let Ok(mut taps) = app_state.frame_taps.lock() {
+            taps.disconnect_device(key);
+        }
+        // 先釋放鎖再 drop，CanDevice 才能等待接收執行緒結束
+        drop(app_state);
+        drop(device);
+        Ok("CAN device stopped successfully".into())
+    })
+    .await
+}
+
+/// retries/retry_interval_ms/timeout_ms 省略時傳送緩衝已滿即失敗
+#[tauri::command]
+async fn transmit_can_data(
     data: u8,
-    dev_type: u32,
-    dev_index: u32,
+    dlc: Option<u8>,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
     can_channel: u32,
+    retries: Option<u32>,
+    retry_interval_ms: Option<u64>,
+    timeout_ms: Option<u64>,
     app_handle: tauri::AppHandle,
-    state: State<Arc<Mutex<AppState>>>,
+    state: State<'_, Arc<StateMutex>>,
 ) -> Result<String, String> {
-    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
-    if let Some(ref can_lib) = app_state.can_library {
-        let can_obj = VciCanObj {
-            id: 0x1,
-            data_len: 1,
-            data: [data, 0, 0, 0, 0, 0, 0, 0],
-            ..Default::default()
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let key = match state
+            .lock()
+            .map_err(|_| "Failed to lock state".to_string())
+            .and_then(|app_state| Ok(app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key()))
+        {
+            Ok(key) => key,
+            Err(error_message) => {
+                app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
+                return Err(error_message);
+            }
         };
-        unsafe {
-            let sent_frames = (can_lib.vci_transmit)(dev_type, dev_index, can_channel, &can_obj, 1);
-            if sent_frames > 0 {
-                return Ok(format!("Sent data: {}", data));
-            } else {
-                let error_message = "傳送 CAN 數據失敗".to_string();
+        // dlc 省略時為 1；0 表示不帶資料，大於 1 時其餘位元組補 0x00
+        let can_obj = match (FrameInput {
+            id: 0x1,
+            extended: None,
+            remote: false,
+            dlc,
+            data: if dlc == Some(0) { Vec::new() } else { vec![data] },
+            padding: None,
+        })
+        .checked("frame")
+        {
+            Ok(can_obj) => can_obj,
+            Err(error_message) => {
                 app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
                 return Err(error_message);
             }
+        };
+        let retry = tx_limit::TxRetry::from_params(retries, retry_interval_ms, timeout_ms);
+        match tx_limit::transmit_paced_with_retry(&state, key, can_channel, std::slice::from_ref(&can_obj), true, retry) {
+            Ok(outcome) if outcome.attempts > 1 => Ok(format!("Sent data: {} ({} attempts)", data, outcome.attempts)),
+            Ok(_) => Ok(format!("Sent data: {}", data)),
+            Err(error_message) => {
+                app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
+                Err(error_message)
+            }
         }
-    }
-    let error_message = "CAN 裝置尚未初始化".to_string();
-    app_handle.emit("error-message", error_message.clone()).unwrap_or_default();
-    Err(error_message)
+    })
+    .await
 }
 
+/// 一次送出多個訊框；任何一個不合法時整批不送，錯誤訊息標出是第幾個。
+/// 傳送緩衝已滿時依 retries/retry_interval_ms/timeout_ms 重試。
+/// 超過通道分段大小 (set_tx_chunk_size) 時分次交給驅動，送出 transmit-started/transmit-progress，可用 abort_transmit 中止
 #[tauri::command]
-fn read_board_info(dev_type: u32, dev_index: u32, state: State<Arc<Mutex<AppState>>>) -> Result<DeviceInfo, String> {
-    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
-    if let Some(ref can_lib) = app_state.can_library {
-        let mut board_info = VciBoardInfo::default();
-        unsafe {
-            let status = (can_lib.vci_read_board_info)(dev_type, dev_index, &mut board_info);
-            if status != 1 {
-                return Err("Failed to read board info".to_string());
-            }
+async fn transmit_frames(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    can_channel: u32,
+    frames: Vec<frame::FrameInput>,
+    retries: Option<u32>,
+    retry_interval_ms: Option<u64>,
+    timeout_ms: Option<u64>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<tx_limit::TxOutcome, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let can_objs = frame::checked_frames("frames", &frames)?;
+        if can_objs.is_empty() {
+            return Err(invalid_argument("frames", "no frames given"));
         }
-        Ok(DeviceInfo {
-            index: dev_index as i32,
-            serial_number: String::from_utf8_lossy(&board_info.str_serial_num).trim_matches('\0').to_string(),
-            firmware_version: board_info.fw_version,
+        let key = {
+            let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key()
+        };
+        let retry = tx_limit::TxRetry::from_params(retries, retry_interval_ms, timeout_ms);
+        tx_limit::transmit_tracked(&state, key, can_channel, &can_objs, true, retry)
+    })
+    .await
+}
+
+/// 回傳開啟時讀到的板卡資訊；refresh 為 true 或沒有快取時重新呼叫 VCI_ReadBoardInfo 並更新快取
+#[tauri::command]
+async fn read_board_info(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    refresh: Option<bool>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<DeviceInfo, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+        if let Some(board_info) = device.board_info.clone().filter(|_| refresh != Some(true)) {
+            return Ok(board_info);
+        }
+        let key = device.key();
+        let can_lib = app_state.backend().ok_or("CAN library not initialized")?;
+        let board_info = DeviceInfo::from_board_info(key.1, &can_lib.read_board_info(key.0, key.1)?);
+        if let Some(device) = app_state.devices.get_mut(&key) {
+            device.board_info = Some(board_info.clone());
+        }
+        Ok(board_info)
+    })
+    .await
+}
+
+#[derive(Serialize)]
+struct ReferenceValue {
+    ref_type: u32,
+    /// data 前 4 個位元組 (little-endian)，自訂鮑率等單一數值的參數即為此值
+    value: u32,
+    data: Vec<u8>,
+}
+
+/// 以 VCI_GetReference 讀回裝置目前的參數，用來確認設定是否生效。
+/// 舊版 DLL 沒有此函式時回傳 not_supported 錯誤
+#[tauri::command]
+async fn get_reference(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    can_channel: u32,
+    ref_type: u32,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<ReferenceValue, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let (key, can_lib) = {
+            let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+            device.check_channel(can_channel)?;
+            (device.key(), app_state.backend().ok_or("CAN library not initialized")?)
+        };
+        let data = can_lib.get_reference(key.0, key.1, can_channel, ref_type)?;
+        let value = data.get(..4).map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        Ok(ReferenceValue { ref_type, value, data })
+    })
+    .await
+}
+
+#[derive(Serialize)]
+struct LibraryCapabilities {
+    backend: Backend,
+    functions: Vec<can_interface::FunctionSupport>,
+}
+
+/// 列出目前後端可用的 VCI 函式，前端據此隱藏舊版 DLL 不支援的功能
+#[tauri::command]
+async fn get_library_capabilities(state: State<'_, Arc<StateMutex>>) -> Result<LibraryCapabilities, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let can_lib = app_state.try_library()?;
+        Ok(LibraryCapabilities {
+            backend: can_lib.backend(),
+            functions: can_lib.functions(),
         })
-    } else {
-        Err("CAN library not initialized".to_string())
-    }
+    })
+    .await
+}
+
+#[derive(Serialize)]
+struct LibraryInfo {
+    backend: Backend,
+    /// 要求載入的檔名
+    name: &'static str,
+    path: Option<String>,
+    size: Option<u64>,
+    modified: Option<String>,
+    /// 函式庫檔案的 CPU 架構，與 app_architecture 不同時無法載入
+    file_architecture: Option<&'static str>,
+    app_architecture: &'static str,
+    loaded: bool,
+    /// 尚未載入時試載一次，失敗的原因
+    load_error: Option<controlcan::LoadFailure>,
+    resolved_symbols: Vec<&'static str>,
+    missing_symbols: Vec<&'static str>,
+    /// 已開啟裝置的驅動程式/介面版本
+    devices: Vec<DeviceInfo>,
+}
+
+/// 回報使用中的函式庫檔案、符號與版本，供使用者回報問題時附上
+#[tauri::command]
+async fn get_library_info(state: State<'_, Arc<StateMutex>>) -> Result<LibraryInfo, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let loaded = app_state.backend().is_some();
+        // 尚未載入時只試載，不保留，避免在使用者選擇後端前就固定使用 DLL
+        let (can_lib, load_error) = match app_state.backend() {
+            Some(can_lib) => (Some(can_lib), None),
+            None => match CanLibrary::load(controlcan::DEFAULT_LIBRARY) {
+                Ok(can_lib) => (Some(can_lib as Arc<dyn CanInterface>), None),
+                Err(failure) => (None, Some(failure)),
+            },
+        };
+        let path = match &can_lib {
+            Some(can_lib) => can_lib.library_path(),
+            None => controlcan::resolve_library_path(controlcan::DEFAULT_LIBRARY),
+        };
+        let metadata = path.as_ref().and_then(|path| std::fs::metadata(path).ok());
+        let functions = can_lib.as_ref().map(|can_lib| can_lib.functions()).unwrap_or_default();
+        let devices = match &can_lib {
+            Some(can_lib) if loaded => app_state
+                .devices
+                .values()
+                .filter_map(|device| {
+                    let board_info = can_lib.read_board_info(device.dev_type, device.dev_index).ok()?;
+                    Some(DeviceInfo::from_board_info(device.dev_index, &board_info))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(LibraryInfo {
+            backend: can_lib.as_ref().map_or(Backend::ControlCan, |can_lib| can_lib.backend()),
+            name: controlcan::DEFAULT_LIBRARY,
+            path: path.as_ref().map(|path| path.display().to_string()),
+            size: metadata.as_ref().map(|metadata| metadata.len()),
+            modified: metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .map(|modified| chrono::DateTime::<chrono::Local>::from(modified).to_rfc3339()),
+            file_architecture: path.as_deref().and_then(controlcan::binary_architecture),
+            app_architecture: std::env::consts::ARCH,
+            loaded,
+            load_error,
+            resolved_symbols: functions.iter().filter(|f| f.supported).map(|f| f.name).collect(),
+            missing_symbols: functions.iter().filter(|f| !f.supported).map(|f| f.name).collect(),
+            devices,
+        })
+    })
+    .await
+}
+
+/// 列舉所有插著的 USB 裝置，並標示哪些已被本程式開啟。
+/// 指定 backend 時先切換後端，例如 socketcan 會列出 can0/vcan0 等介面 (序號即介面名稱)；
+/// dev_type 決定以哪個裝置類型判斷是否已開啟
+#[tauri::command]
+async fn find_usb_devices2(
+    backend: Option<Backend>,
+    dev_type: Option<DeviceType>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<EnumeratedDevice>, String> {
+    let state = state.inner().clone();
+    let dev_type = dev_type.unwrap_or(DEFAULT_DEV_TYPE).code();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        if let Some(backend) = backend {
+            app_state.select_backend(backend)?;
+        }
+        let found = app_state.enumerate_devices()?;
+        Ok(found
+            .into_iter()
+            .map(|info| {
+                let is_open = app_state.devices.contains_key(&(dev_type, info.index as u32));
+                EnumeratedDevice { info, is_open }
+            })
+            .collect())
+    })
+    .await
 }
 
 #[tauri::command]
-fn set_baud_rate(dev_type: u32, dev_index: u32, can_channel: u32, timing0: u8, timing1: u8, state: State<Arc<Mutex<AppState>>>) -> Result<String, String> {
-    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
-    if let Some(ref can_lib) = app_state.can_library {
+async fn set_baud_rate(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    can_channel: u32,
+    timing0: u8,
+    timing1: u8,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<String, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+        if app_state.backend().is_none() {
+            return Err("CAN library not initialized".to_string());
+        }
         let config = VciInitConfig {
             acc_code: 0,
             acc_mask: 0xFFFFFFFF,
@@ -273,95 +1005,673 @@ fn set_baud_rate(dev_type: u32, dev_index: u32, can_channel: u32, timing0: u8, t
             timing1,
             mode: 0,
         };
-        unsafe {
-            if (can_lib.vci_init_can)(dev_type, dev_index, can_channel, &config) != 1 {
-                return Err("Failed to set baud rate".to_string());
-            }
-        }
+        app_state
+            .init_channel(key, can_channel, config)
+            .map_err(|_| "Failed to set baud rate".to_string())?;
+        app_state.save_settings(key);
         Ok("Baud rate set successfully".to_string())
-    } else {
-        Err("CAN library not initialized".to_string())
+    })
+    .await
+}
+
+/// configure_channels 中一個通道的設定；baud 與 timing0/timing1 擇一
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChannelConfig {
+    channel: u32,
+    /// 標準位元率 (bit/s)，例如 500000
+    #[serde(default)]
+    baud: Option<u32>,
+    #[serde(default)]
+    timing0: Option<u8>,
+    #[serde(default)]
+    timing1: Option<u8>,
+    /// 省略時接收所有訊框
+    #[serde(default)]
+    filter: Option<filter::HardwareFilter>,
+    /// 0 = 正常，1 = 只聽，2 = 自測
+    #[serde(default)]
+    mode: u8,
+    /// 省略時為 true；false 時只初始化
+    #[serde(default)]
+    start: Option<bool>,
+}
+
+impl ChannelConfig {
+    fn init_config(&self) -> Result<VciInitConfig, String> {
+        let (timing0, timing1) = match (self.baud, self.timing0, self.timing1) {
+            (None, Some(timing0), Some(timing1)) => (timing0, timing1),
+            (Some(baud), None, None) => baud::timing_for_bitrate(baud)
+                .ok_or_else(|| format!("CAN{}: {} bit/s is not a standard baud rate; give timing0/timing1", self.channel + 1, baud))?,
+            _ => return Err(format!("CAN{}: give either baud or both timing0 and timing1", self.channel + 1)),
+        };
+        let filter = self.filter.unwrap_or(filter::HardwareFilter {
+            acc_code: 0,
+            acc_mask: 0xFFFFFFFF,
+            filter: 1,
+        });
+        Ok(VciInitConfig {
+            acc_code: filter.acc_code,
+            acc_mask: filter.acc_mask,
+            reserved: 0,
+            filter: filter.filter,
+            timing0,
+            timing1,
+            mode: self.mode,
+        })
     }
 }
 
-#[tauri::command]
-fn reconnect_can_device(
-    dev_type: u32,
-    dev_index: u32,
-    can1: u32,
-    can2: u32,
+#[derive(Serialize)]
+struct ChannelApplied {
+    channel: u32,
     timing0: u8,
     timing1: u8,
-    state: State<Arc<Mutex<AppState>>>,
-) -> Result<String, String> {
-    {
+    bitrate: u32,
+    mode: u8,
+    acc_code: u32,
+    acc_mask: u32,
+    filter: u8,
+    started: bool,
+}
+
+/// 依各通道自己的鮑率、濾波與模式初始化 (並啟動) 列出的通道；未列出的通道維持原狀
+#[tauri::command]
+async fn configure_channels(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channels: Vec<ChannelConfig>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<ChannelApplied>, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let configs = channels
+            .iter()
+            .map(|channel| Ok((channel.channel, channel.init_config()?, channel.start.unwrap_or(true))))
+            .collect::<Result<Vec<_>, String>>()?;
         let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
-        if let Some(ref can_lib) = app_state.can_library {
-            unsafe {
-                (can_lib.vci_close_device)(dev_type, dev_index);
+        let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+        // 先初始化全部通道再連續啟動，讓同時擷取的通道時間戳記起點接近
+        let mut done: Vec<String> = Vec::new();
+        let mut result = Ok(());
+        for &(channel, config, _) in &configs {
+            result = app_state.init_channel(key, channel, config);
+            if result.is_err() {
+                break;
+            }
+            done.push(format!("CAN{} initialized", channel + 1));
+        }
+        if result.is_ok() {
+            for &(channel, config, _) in configs.iter().filter(|&&(_, _, start)| start) {
+                result = app_state.start_initialized_channel(key, channel, config);
+                if result.is_err() {
+                    break;
+                }
+                done.push(format!("CAN{} started", channel + 1));
             }
         }
-        app_state.can_library = None;
+        if let Err(error_message) = result {
+            app_state.save_settings(key);
+            return Err(format!(
+                "{} (already applied: {})",
+                error_message,
+                if done.is_empty() { "none".to_string() } else { done.join(", ") }
+            ));
+        }
+        let mut applied = Vec::new();
+        for (channel, config, start) in configs {
+            applied.push(ChannelApplied {
+                channel,
+                timing0: config.timing0,
+                timing1: config.timing1,
+                bitrate: baud::bitrate_from_timing(config.timing0, config.timing1),
+                mode: config.mode,
+                acc_code: config.acc_code,
+                acc_mask: config.acc_mask,
+                filter: config.filter,
+                started: start,
+            });
+        }
+        app_state.save_settings(key);
+        Ok(applied)
+    })
+    .await
+}
+
+/// 列舉 can_lib 上的裝置；不需 state 鎖，可在鎖外等待 VCI_FindUsbDevice2
+fn list_devices(can_lib: &dyn CanInterface) -> Result<Vec<DeviceInfo>, String> {
+    Ok(can_lib
+        .find_devices()?
+        .iter()
+        .enumerate()
+        .map(|(index, board_info)| DeviceInfo::from_board_info(index as u32, board_info))
+        .collect())
+}
+
+/// 以 config 重新初始化通道 (start 為 true 時接著啟動)；在 state 鎖外呼叫 VCI_InitCAN/VCI_StartCAN，
+/// 等待驅動期間不阻擋其他命令，完成後才記下設定
+pub(crate) fn reinit_channel(state: &StateMutex, key: (u32, u32), channel: u32, config: VciInitConfig, start: bool) -> Result<(), String> {
+    let (can_lib, clock) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        app_state.connected_device(Some(key.0), Some(key.1))?.check_channel(channel)?;
+        let can_lib = app_state.backend().ok_or("CAN 裝置尚未初始化")?;
+        (can_lib, app_state.channel_runtime(key, channel).clock.clone())
+    };
+    can_lib
+        .init_channel(key.0, key.1, channel, &config)
+        .map_err(|_| format!("Failed to initialize CAN{}", channel + 1))?;
+    if start {
+        timestamp::start_and_mark(can_lib.as_ref(), &clock, key, channel).map_err(|_| format!("Failed to start CAN{}", channel + 1))?;
     }
-    let can_lib = CanLibrary::new("ControlCAN.dll");
-    let reserved = 0u32;
-    unsafe {
-        if (can_lib.vci_open_device)(dev_type, dev_index, reserved) != 1 {
-            return Err("Failed to open device".into());
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.device_mut(Some(key.0), Some(key.1))?;
+    device.channels.insert(channel, ChannelState { config, started: start });
+    app_state.reset_channel_counters(key, channel, &config);
+    app_state.record_channel_config(key, channel, config);
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+struct StreamRestartedEvent {
+    dev_type: u32,
+    dev_index: u32,
+    channels: Vec<u32>,
+}
+
+/// 停止並等待裝置的接收執行緒，以 config 重新開啟並 init/start channels (其他已設定的通道以原本的設定還原)，再以相同選項重新啟動原本在接收的通道，
+/// 並送出 stream-restarted 事件。各通道在重新啟動接收前先送出 config-changed，之後的訊框帶新的 config_generation。任何一步失敗時回傳錯誤並說明之後的狀態；提早返回時裝置由 CanDevice 關閉
+pub fn reconnect_device<E: EventSink + Clone>(
+    state: &Arc<StateMutex>,
+    events: E,
+    dev_type: Option<u32>,
+    dev_index: Option<u32>,
+    channels: [u32; 2],
+    config: VciInitConfig,
+) -> Result<Option<DeviceInfo>, String> {
+    let (key, mut device, clocks, others) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.device(dev_type, dev_index)?;
+        for channel in channels {
+            device.check_channel(channel)?;
         }
+        let key = device.key();
+        let others: Vec<(u32, ChannelState)> = device
+            .channels
+            .iter()
+            .filter(|(channel, _)| !channels.contains(channel))
+            .map(|(&channel, &channel_state)| (channel, channel_state))
+            .collect();
+        let clocks = channels.map(|channel| app_state.channel_runtime(key, channel).clock.clone());
+        let others: Vec<_> = others
+            .into_iter()
+            .map(|(channel, channel_state)| (channel, channel_state, app_state.channel_runtime(key, channel).clock.clone()))
+            .collect();
+        (key, app_state.devices.remove(&key).expect("device key resolved above"), clocks, others)
+    };
+    let mut restart: Vec<(u32, ReceiveOptions)> = device
+        .receiving
+        .iter()
+        .filter(|(_, receiving)| receiving.load(Ordering::SeqCst))
+        .filter_map(|(&channel, _)| Some((channel, *device.receive_options.get(&channel)?)))
+        .collect();
+    restart.sort_by_key(|&(channel, _)| channel);
+    // 裝置已從 devices 移除，執行緒在下一輪就會結束，不會在關閉期間呼叫 DLL
+    for receiving in device.receiving.values() {
+        receiving.store(false, Ordering::SeqCst);
     }
-    println!("Device reopened successfully");
-    let config = VciInitConfig {
-        acc_code: 0,
-        acc_mask: 0xFFFFFFFF,
-        reserved: 0,
-        filter: 1,
-        timing0,
-        timing1,
-        mode: 0,
+    device.handle.stop_threads();
+    let channel_list = |channels: &mut dyn Iterator<Item = u32>| {
+        let names: Vec<String> = channels.map(|channel| format!("CAN{}", channel + 1)).collect();
+        if names.is_empty() { "none".to_string() } else { names.join(", ") }
     };
-    unsafe {
-        if (can_lib.vci_init_can)(dev_type, dev_index, can1, &config) != 1 {
-            return Err("Failed to initialize CAN1 with new baud".into());
-        }
-        if (can_lib.vci_init_can)(dev_type, dev_index, can2, &config) != 1 {
-            return Err("Failed to initialize CAN2 with new baud".into());
+    let stopped = channel_list(&mut restart.iter().map(|&(channel, _)| channel));
+    let (dev_type, dev_index) = key;
+    let result = device
+        .handle
+        .reopen(dev_index)
+        .map_err(|_| "Failed to open device".to_string())
+        .and_then(|_| {
+            println!("Device reopened successfully");
+            let can_lib = device.handle.interface();
+            for channel in channels {
+                can_lib
+                    .init_channel(dev_type, dev_index, channel, &config)
+                    .map_err(|_| format!("Failed to initialize CAN{} with new baud", channel + 1))?;
+            }
+            for (channel, channel_state, _) in &others {
+                can_lib
+                    .init_channel(dev_type, dev_index, *channel, &channel_state.config)
+                    .map_err(|_| format!("Failed to initialize CAN{}", channel + 1))?;
+            }
+            for (channel, clock) in channels.into_iter().zip(&clocks) {
+                timestamp::start_and_mark(can_lib, clock, key, channel)
+                    .map_err(|_| format!("Failed to start CAN{} after reconnect", channel + 1))?;
+            }
+            for (channel, _, clock) in others.iter().filter(|(_, channel_state, _)| channel_state.started) {
+                timestamp::start_and_mark(can_lib, clock, key, *channel)
+                    .map_err(|_| format!("Failed to start CAN{} after reconnect", channel + 1))?;
+            }
+            Ok(())
+        });
+    if let Err(error_message) = result {
+        return Err(format!(
+            "{}; device {} is closed and its receive streams ({}) were stopped",
+            error_message, dev_index, stopped
+        ));
+    }
+    println!("CAN channels reinitialized and started with new baud");
+    let board_info = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let board_info = read_device_info(device.handle.interface(), dev_type, dev_index).or(device.board_info.take());
+        // 沿用原本的 OpenDevice，保留序號、usb_reset_after 與其他通道的設定；接收執行緒已停止，稍後重新啟動
+        device.board_info = board_info.clone();
+        device.disconnected = false;
+        device.captures.clear();
+        for channel in channels {
+            device.channels.insert(channel, ChannelState { config, started: true });
+            app_state.reset_channel_counters(key, channel, &config);
+            app_state.record_channel_config(key, channel, config);
         }
-        if (can_lib.vci_start_can)(dev_type, dev_index, can1) != 1 {
-            return Err("Failed to start CAN1 after reconnect".into());
+        for (channel, channel_state, _) in &others {
+            app_state.reset_channel_counters(key, *channel, &channel_state.config);
+            app_state.record_channel_config(key, *channel, channel_state.config);
         }
-        if (can_lib.vci_start_can)(dev_type, dev_index, can2) != 1 {
-            return Err("Failed to start CAN2 after reconnect".into());
+        app_state.devices.insert(key, device);
+        app_state.save_settings(key);
+        app_state.emit_device_opened(key);
+        board_info
+    };
+    let mut restarted = Vec::new();
+    for (channel, options) in restart {
+        let spawned = spawn_receive_loop(state, events.clone(), Some(dev_type), Some(dev_index), channel, options);
+        let handle = match spawned {
+            Ok((_, handle)) => handle,
+            Err(error_message) => {
+                return Err(format!(
+                    "device reconnected but restarting CAN{} failed: {} (restarted: {})",
+                    channel + 1,
+                    error_message,
+                    channel_list(&mut restarted.iter().copied())
+                ));
+            }
+        };
+        if let Some(device) = state.lock().map_err(|_| "Failed to lock state")?.devices.get_mut(&key) {
+            device.register_receive_thread(channel, handle);
         }
+        restarted.push(channel);
     }
-    println!("CAN channels reinitialized and started with new baud");
-    {
-        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
-        app_state.can_library = Some(can_lib);
+    if !restarted.is_empty() {
+        events.emit_event(
+            "stream-restarted",
+            StreamRestartedEvent {
+                dev_type,
+                dev_index,
+                channels: restarted,
+            },
+        );
+    }
+    Ok(board_info)
+}
+
+/// 以新的鮑率重新開啟裝置 (見 reconnect_device)
+#[tauri::command]
+async fn reconnect_can_device(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    can1: u32,
+    can2: u32,
+    timing0: u8,
+    timing1: u8,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<OpenedDevice, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let config = VciInitConfig {
+            acc_code: 0,
+            acc_mask: 0xFFFFFFFF,
+            reserved: 0,
+            filter: 1,
+            timing0,
+            timing1,
+            mode: 0,
+        };
+        let board_info = reconnect_device(&state, app_handle, dev_type.map(DeviceType::code), dev_index, [can1, can2], config)?;
+        Ok(OpenedDevice {
+            message: format!(
+                "Device reconnected with new baud: Timing0 = 0x{:X}, Timing1 = 0x{:X}",
+                timing0, timing1
+            ),
+            board_info,
+        })
+    })
+    .await
+}
+
+/// 程式結束前關閉所有開啟中的裝置，否則裝置常會停在下次 VCI_OpenDevice 失敗的狀態
+fn close_all_devices(state: &StateMutex) {
+    let devices = {
+        let Ok(mut app_state) = state.lock() else {
+            return;
+        };
+        if let Some(watching) = app_state.device_watch.take() {
+            watching.store(false, Ordering::SeqCst);
+        }
+        std::mem::take(&mut app_state.devices)
+    };
+    // 在鎖外 drop，CanDevice 才能等待接收執行緒結束
+    for device in devices.into_values() {
+        println!("Closing device {}:{} before exit", device.dev_type, device.dev_index);
+        drop(device);
     }
-    Ok(format!(
-        "Device reconnected with new baud: Timing0 = 0x{:X}, Timing1 = 0x{:X}",
-        timing0, timing1
-    ))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
-        .manage(Arc::new(Mutex::new(AppState {
-            can_library: None,
-            receiving: Arc::new(AtomicBool::new(false)),
-        })))
+        .manage(Arc::new(StateMutex::default()))
+        .setup(|app| {
+            supervisor::init(app.handle());
+            if let Ok(mut app_state) = app.state::<Arc<StateMutex>>().lock() {
+                app_state.app_handle = Some(app.handle().clone());
+            }
+            auto_connect::start(app.handle());
+            heartbeat::start(app.handle());
+            Ok(())
+        })
+        // 重新載入頁面後舊頁面的訂閱已無人接收
+        .on_page_load(|webview, payload| {
+            if payload.event() == tauri::webview::PageLoadEvent::Started {
+                if let Ok(app_state) = webview.state::<Arc<StateMutex>>().lock() {
+                    if let Ok(mut subscriptions) = app_state.subscriptions.lock() {
+                        subscriptions.clear();
+                    }
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             open_can_device,
             stop_can_device,
             transmit_can_data,
-            start_receiving_data,
-            stop_receiving_data ,
+            transmit_frames,
+            burst::transmit_burst,
+            burst::abort_burst,
+            operation::list_operations,
+            operation::cancel_operation,
+            receive::start_receiving_data,
+            receive::stop_receiving_data,
+            receive::get_receiving_channels,
+            receive::receive_can_data,
+            receive::receive_raw_frames,
+            receive::pause_emission,
+            receive::resume_emission,
+            receive::set_id_table_mode,
+            receive::set_tx_echo,
+            subscription::subscribe,
+            subscription::unsubscribe,
+            subscription::subscribe_signals,
+            subscription::unsubscribe_signals,
+            subscription::list_signal_subscriptions,
+            ring_buffer::get_recent_frames,
+            ring_buffer::get_frame_buffer_status,
+            ring_buffer::set_frame_buffer_capacity,
+            ring_buffer::clear_frame_buffer,
+            ring_buffer::export_buffer,
+            annotation::add_annotation,
+            annotation::list_annotations,
+            annotation::delete_annotation,
+            memory::get_memory_stats,
+            memory::set_memory_limits,
+            stats::get_id_statistics,
+            stats::get_id_table,
+            stats::reset_id_statistics,
+            stats::set_expected_period,
+            stats::reset_statistics,
+            busoff::set_busoff_recovery,
+            baud::list_baud_presets,
+            emit_queue::set_event_backpressure,
+            stats::get_bus_load,
+            logging::start_logging,
+            logging::stop_logging,
+            replay::load_log_file,
+            replay::start_replay,
+            replay::stop_replay,
+            dbc::load_dbc,
+            dbc::decode_frame,
+            dbc::get_dbc_messages,
+            dbc::transmit_signals,
+            dbc::export::export_signals_csv,
+            dbc::layers::add_dbc,
+            dbc::layers::list_loaded_dbcs,
+            dbc::layers::unload_dbc,
+            id_names::load_id_names,
+            id_names::clear_id_names,
+            id_names::get_id_names,
+            periodic::start_periodic,
+            periodic::start_periodic_signals,
+            periodic::update_periodic_signals,
+            periodic::set_periodic_e2e,
+            e2e::set_e2e_check,
+            filter::set_software_filter,
+            filter::save_filter_preset,
+            filter::apply_filter_preset,
+            profile::save_profile,
+            profile::apply_profile,
+            profile::list_profiles,
+            profile::delete_profile,
+            profile::export_profile,
+            profile::import_profile,
+            filter::list_filter_presets,
+            filter::delete_filter_preset,
+            dedupe::set_dedupe,
+            periodic::stop_periodic,
+            periodic::list_periodic_tasks,
+            sequence::run_tx_sequence,
+            sequence::abort_sequence,
+            fuzz::start_fuzzing,
+            fuzz::stop_fuzzing,
+            reply::transmit_and_wait,
+            isotp::isotp_send,
+            isotp::isotp_receive,
+            isotp::start_isotp_listener,
+            isotp::stop_isotp_listener,
+            isotp::configure_isotp,
+            isotp::get_isotp_config,
+            isotp::list_isotp_links,
+            uds::uds_request,
+            uds::uds_read_did,
+            uds::uds_diagnostic_session_control,
+            uds::uds_ecu_reset,
+            uds::start_tester_present,
+            uds::stop_tester_present,
+            uds::uds_download,
+            uds::abort_uds_download,
+            selftest::run_self_test,
+            bus_quality::set_bus_quality_threshold,
+            tx_limit::abort_transmit,
+            tx_limit::get_transmit_progress,
+            tx_limit::set_tx_chunk_size,
+            obd::obd_query,
+            j1939::set_j1939_mode,
+            j1939::j1939_request_pgn,
+            canopen::sdo::sdo_upload,
+            canopen::sdo::sdo_download,
+            canopen::nmt::start_canopen_monitor,
+            canopen::nmt::stop_canopen_monitor,
+            canopen::nmt::get_canopen_nodes,
+            canopen::nmt::nmt_command,
+            gateway::start_gateway,
+            ws_bridge::start_ws_bridge,
+            ws_bridge::stop_ws_bridge,
+            socketcand::start_socketcand_server,
+            socketcand::stop_socketcand_server,
+            mqtt::start_mqtt_publisher,
+            mqtt::stop_mqtt_publisher,
+            gateway::stop_gateway,
+            gateway::get_gateway_stats,
+            responder::add_auto_response,
+            responder::remove_auto_response,
+            responder::clear_auto_responses,
+            responder::list_auto_responses,
+            trigger::add_trigger,
+            trigger::remove_trigger,
+            trigger::list_triggers,
+            watchdog::set_bus_watchdog,
+            watchdog::clear_bus_watchdog,
+            latency::measure_latency,
+            benchmark::run_throughput_benchmark,
+            tx_limit::set_tx_rate_limit,
+            tx_limit::set_tx_inhibit,
+            virtual_can::open_virtual_device,
+            virtual_can::set_virtual_traffic,
+            virtual_can::load_virtual_scenario,
+            virtual_can::clear_virtual_scenario,
+            virtual_can::get_virtual_scenario_report,
+            capture::arm_capture,
+            capture::get_capture,
+            capture::list_captures,
+            capture::export_capture,
+            capture::discard_capture,
             read_board_info,
+            get_reference,
+            get_library_capabilities,
+            get_library_info,
+            status::get_status,
+            find_usb_devices2,
+            open_device_by_serial,
+            probe::probe_device,
+            settings::get_saved_settings,
+            settings::open_with_saved_settings,
+            auto_connect::get_auto_connect,
+            auto_connect::set_auto_connect,
+            auto_connect::cancel_auto_connect,
+            device_type::list_device_types,
+            hotplug::start_device_watch,
+            hotplug::stop_device_watch,
             set_baud_rate,
-            reconnect_can_device
+            configure_channels,
+            channel_change::plan_channel_change,
+            channel_change::apply_channel_change,
+            config_history::get_config_history,
+            reconnect_can_device,
+            usb_reset::force_usb_reset,
+            usb_reset::set_usb_reset_fallback,
+            timestamp::set_timestamp_mode,
+            timestamp::calibrate_channel_offset
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            if let RunEvent::ExitRequested { .. } | RunEvent::Exit = event {
+                close_all_devices(&app_handle.state::<Arc<StateMutex>>());
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct NoEvents;
+
+    impl EventSink for NoEvents {
+        fn emit_event<S: Serialize + Clone>(&self, _event: &str, _payload: S) -> bool {
+            true
+        }
+    }
+
+    fn config() -> VciInitConfig {
+        VciInitConfig {
+            acc_mask: 0xFFFF_FFFF,
+            timing1: 0x1C,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_missing_dll_fails_the_command_instead_of_panicking() {
+        let mut app_state = AppState::default();
+        assert!(app_state.open_device(DEFAULT_DEV_TYPE.code(), 0, None).is_err());
+        assert!(app_state.enumerate_devices().is_err());
+        assert!(app_state.enumerate_with(Backend::ControlCan).is_err());
+        let probe = app_state.probe_device(DEFAULT_DEV_TYPE.code(), Some(0), None);
+        assert_eq!(probe.status, ProbeStatus::OpenFailed);
+        assert!(probe.error.is_some());
+        // 載入失敗時不記住任何後端，放好 DLL 後可再試
+        assert!(app_state.backend().is_none());
+        assert!(app_state.devices.is_empty());
+    }
+
+    #[test]
+    fn reinit_channel_records_the_new_config_and_restarts_a_started_channel() {
+        let mock = Arc::new(mock::MockCan::new());
+        let dev_type = DEFAULT_DEV_TYPE.code();
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(dev_type, 0, None).unwrap();
+        let config = VciInitConfig {
+            acc_mask: 0xFFFF_FFFF,
+            timing1: 0x1C,
+            ..Default::default()
+        };
+        app_state.start_channel((dev_type, 0), 0, config).unwrap();
+        let state = StateMutex::new(app_state);
+
+        let filtered = VciInitConfig {
+            acc_code: 0x100 << 21,
+            acc_mask: 0x001F_FFFF,
+            ..config
+        };
+        reinit_channel(&state, (dev_type, 0), 0, filtered, true).unwrap();
+        let channel_state = state.lock().unwrap().devices[&(dev_type, 0)].channels[&0];
+        assert_eq!((channel_state.config.acc_code, channel_state.started), (filtered.acc_code, true));
+        assert!(mock.is_started(dev_type, 0, 0));
+
+        // 沒有開啟的裝置不會呼叫驅動
+        assert!(reinit_channel(&state, (dev_type, 1), 0, config, false).is_err());
+    }
+
+    #[test]
+    fn reconnecting_starts_a_new_generation_on_every_configured_channel() {
+        let mock = Arc::new(mock::MockCan::new());
+        let dev_type = DEFAULT_DEV_TYPE.code();
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(dev_type, 0, None).unwrap();
+        app_state.devices.get_mut(&(dev_type, 0)).unwrap().channel_count = Some(3);
+        for channel in 0..3 {
+            app_state.start_channel((dev_type, 0), channel, config()).unwrap();
+        }
+        let state = Arc::new(StateMutex::new(app_state));
+
+        let faster = VciInitConfig {
+            timing1: 0x14,
+            ..config()
+        };
+        reconnect_device(&state, NoEvents, None, None, [0, 1], faster).unwrap();
+        let mut app_state = state.lock().unwrap();
+        for channel in 0..3 {
+            let history = app_state.config_history((dev_type, 0), channel);
+            let bitrate = if channel < 2 { 1_000_000 } else { 500_000 };
+            assert_eq!((history.len(), history[1].bitrate), (2, bitrate), "CAN{}", channel + 1);
+            assert!(mock.is_started(dev_type, 0, channel));
+        }
+    }
+
+    #[test]
+    fn reconnect_rejects_an_unopened_device_or_missing_channel_without_closing_anything() {
+        let mock = Arc::new(mock::MockCan::new());
+        let dev_type = DEFAULT_DEV_TYPE.code();
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(dev_type, 0, None).unwrap();
+        app_state.devices.get_mut(&(dev_type, 0)).unwrap().channel_count = Some(2);
+        app_state.start_channel((dev_type, 0), 0, config()).unwrap();
+        let state = Arc::new(StateMutex::new(app_state));
+
+        let error = reconnect_device(&state, NoEvents, None, None, [0, 2], config()).err().unwrap();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        let error = reconnect_device(&state, NoEvents, Some(dev_type), Some(1), [0, 1], config()).err().unwrap();
+        assert_eq!(error, "device 1 is not the open device (0)");
+        assert!(state.lock().unwrap().devices.contains_key(&(dev_type, 0)));
+        assert_eq!((mock.open_count(dev_type, 0), mock.close_count(dev_type, 0)), (1, 0));
+        assert_eq!(state.lock().unwrap().config_history((dev_type, 0), 0).len(), 1);
+    }
 }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use chrono::{DateTime, Local};
+
+use super::{FrameWriter, LoggedFrame};
+use crate::annotation::Annotation;
+use crate::config_history::ConfigGeneration;
+use crate::frame::{Direction, Provenance};
+
+/// ASC 沒有裝置欄位，第 n 個裝置的通道接在前面裝置的通道之後編號
+const CHANNELS_PER_DEVICE: u32 = 2;
+
+/// Vector ASC 格式；時間戳記為相對於記錄開始的秒數，通道從 1 起算
+pub struct AscWriter<W: Write> {
+    out: W,
+    start_us: u64,
+    /// 各通道最後寫出的設定世代；記錄中途重新初始化時加一行註解
+    generations: HashMap<(u32, u32, u32), u64>,
+}
+
+impl<W: Write> AscWriter<W> {
+    pub fn new(out: W, start_us: u64) -> Self {
+        Self {
+            out,
+            start_us,
+            generations: HashMap::new(),
+        }
+    }
+
+    fn start_time(&self) -> String {
+        DateTime::from_timestamp_micros(self.start_us as i64)
+            .map(|t| t.with_timezone(&Local).format("%a %b %d %I:%M:%S%.3f %P %Y").to_string())
+            .unwrap_or_default()
+    }
+}
+
+impl<W: Write + Send> FrameWriter for AscWriter<W> {
+    fn write_header(&mut self, configs: &[ConfigGeneration]) -> io::Result<()> {
+        let start = self.start_time();
+        writeln!(self.out, "date {}", start)?;
+        writeln!(self.out, "base hex  timestamps absolute")?;
+        writeln!(self.out, "internal events logged")?;
+        for config in configs {
+            writeln!(self.out, "// config {}", config.describe())?;
+            self.generations.insert((config.dev_type, config.dev_index, config.channel), config.generation);
+        }
+        writeln!(self.out, "Begin Triggerblock {}", start)?;
+        writeln!(self.out, "{:>11.6} Start of measurement", 0.0)
+    }
+
+    fn write_frame(&mut self, logged: &LoggedFrame) -> io::Result<()> {
+        let frame = &logged.frame;
+        let elapsed_us = frame.host_timestamp_us.saturating_sub(self.start_us);
+        let id = if frame.extended {
+            format!("{:X}x", frame.id)
+        } else {
+            format!("{:X}", frame.id)
+        };
+        let channel = (frame.device_type, frame.device_index, frame.channel);
+        if self.generations.insert(channel, frame.config_generation).is_some_and(|g| g != frame.config_generation) {
+            writeln!(self.out, "// config generation {} on CAN{}", frame.config_generation, frame.channel + 1)?;
+        }
+        // ASC 沒有來源欄位，送出的訊框前加一行註解
+        if frame.provenance != Provenance::Rx {
+            writeln!(self.out, "// provenance {}", frame.provenance.as_str())?;
+        }
+        let direction = match logged.direction {
+            Direction::Rx => "Rx",
+            Direction::Tx => "Tx",
+        };
+        write!(
+            self.out,
+            "{:>4}.{:06} {:<2} {:<15} {:<4} {} {:X}",
+            elapsed_us / 1_000_000,
+            elapsed_us % 1_000_000,
+            frame.device_index * CHANNELS_PER_DEVICE + frame.channel + 1,
+            id,
+            direction,
+            if frame.remote { 'r' } else { 'd' },
+            frame.dlc
+        )?;
+        if !frame.remote {
+            for byte in &frame.data {
+                write!(self.out, " {:02X}", byte)?;
+            }
+        }
+        writeln!(self.out)
+    }
+
+    fn write_annotation(&mut self, annotation: &Annotation) -> io::Result<()> {
+        let elapsed_us = annotation.timestamp_us.saturating_sub(self.start_us);
+        writeln!(
+            self.out,
+            "// annotation {}.{:06} {}",
+            elapsed_us / 1_000_000,
+            elapsed_us % 1_000_000,
+            annotation.comment_text()
+        )
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        writeln!(self.out, "End TriggerBlock")?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::CanFrameEvent;
+    use crate::replay::{parse_log, LogDialect};
+    use crate::VciCanObj;
+
+    const START_US: u64 = 1_700_000_000_000_000;
+
+    fn logged(key: (u32, u32), channel: u32, can_obj: &VciCanObj, elapsed_us: u64, direction: Direction) -> LoggedFrame {
+        let mut frame = CanFrameEvent::from_raw(key, channel, can_obj, START_US + elapsed_us).with_direction(direction);
+        if direction == Direction::Tx {
+            frame.provenance = Provenance::TxManual;
+        }
+        LoggedFrame { direction, frame }
+    }
+
+    fn write_log(frames: &[LoggedFrame]) -> String {
+        let mut out = Vec::new();
+        let mut writer = AscWriter::new(&mut out, START_US);
+        writer.write_header(&[]).unwrap();
+        for frame in frames {
+            writer.write_frame(frame).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn frames_written_as_asc_parse_back_unchanged() {
+        let standard = VciCanObj {
+            id: 0x123,
+            data_len: 3,
+            data: [0xDE, 0xAD, 0xBE, 0, 0, 0, 0, 0],
+            ..Default::default()
+        };
+        let extended = VciCanObj {
+            id: 0x18FE_F100,
+            extern_flag: 1,
+            data_len: 8,
+            data: [1, 2, 3, 4, 5, 6, 7, 0xFF],
+            ..Default::default()
+        };
+        let remote = VciCanObj {
+            id: 0x7FF,
+            remote_flag: 1,
+            data_len: 4,
+            ..Default::default()
+        };
+        let empty = VciCanObj {
+            id: 0x1FFF_FFFF,
+            extern_flag: 1,
+            ..Default::default()
+        };
+        let text = write_log(&[
+            logged((4, 0), 0, &standard, 1_234, Direction::Rx),
+            logged((4, 0), 1, &extended, 1_000_001, Direction::Tx),
+            logged((4, 0), 0, &remote, 2_500_000, Direction::Rx),
+            logged((4, 1), 1, &empty, 12_000_999, Direction::Rx),
+        ]);
+        assert!(text.starts_with("date "), "{}", text);
+        assert!(text.contains("\nbase hex  timestamps absolute\n"), "{}", text);
+        assert!(text.contains("   1.000001 2  18FEF100x       Tx   d 8 01 02 03 04 05 06 07 FF\n"), "{}", text);
+
+        let parsed = parse_log(&text);
+        assert_eq!(parsed.dialect, LogDialect::Asc);
+        assert!(parsed.unparsed.is_empty(), "{:?}", parsed.unparsed);
+        let frames: Vec<_> = parsed
+            .frames
+            .iter()
+            .map(|f| (f.timestamp_us, f.interface.as_str(), f.direction, f.id, f.extended, f.remote, f.dlc, f.data.clone()))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                (1_234, "CAN1", Some(Direction::Rx), 0x123, false, false, 3, vec![0xDE, 0xAD, 0xBE]),
+                (1_000_001, "CAN2", Some(Direction::Tx), 0x18FE_F100, true, false, 8, vec![1, 2, 3, 4, 5, 6, 7, 0xFF]),
+                (2_500_000, "CAN1", Some(Direction::Rx), 0x7FF, false, true, 4, vec![]),
+                (12_000_999, "CAN4", Some(Direction::Rx), 0x1FFF_FFFF, true, false, 0, vec![]),
+            ]
+        );
+    }
+
+    /// 本程式不會寫出錯誤訊框 (VCI 只以 ReadErrInfo 回報錯誤)，但 CANoe 匯出的錯誤訊框行夾在其中時
+    /// 匯入端應略過並計數，不影響前後訊框
+    #[test]
+    fn error_frames_between_written_frames_are_counted_and_skipped() {
+        let standard = VciCanObj {
+            id: 0x100,
+            data_len: 1,
+            data: [0x55, 0, 0, 0, 0, 0, 0, 0],
+            ..Default::default()
+        };
+        let text = write_log(&[
+            logged((4, 0), 0, &standard, 10, Direction::Rx),
+            logged((4, 0), 0, &standard, 30, Direction::Rx),
+        ]);
+        let second = text.find("   0.000030").unwrap();
+        let text = format!("{}   0.000020 1  ErrorFrame\n{}", &text[..second], &text[second..]);
+
+        let parsed = parse_log(&text);
+        assert!(parsed.unparsed.is_empty(), "{:?}", parsed.unparsed);
+        assert_eq!(parsed.error_frames, 1);
+        assert_eq!(parsed.frames.iter().map(|f| f.timestamp_us).collect::<Vec<_>>(), vec![10, 30]);
+    }
+}
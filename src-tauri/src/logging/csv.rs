@@ -0,0 +1,75 @@
+use std::io::{self, Write};
+
+use super::{FrameWriter, LoggedFrame};
+use crate::annotation::Annotation;
+use crate::config_history::ConfigGeneration;
+use crate::frame::Direction;
+
+pub struct CsvWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write + Send> FrameWriter for CsvWriter<W> {
+    fn write_header(&mut self, configs: &[ConfigGeneration]) -> io::Result<()> {
+        // 欄位列前以 # 開頭的註解列記下記錄開始時各通道的設定
+        for config in configs {
+            writeln!(self.out, "# config {}", config.describe())?;
+        }
+        writeln!(
+            self.out,
+            "timestamp,device_type,device_index,channel,direction,provenance,config_generation,id,extended,remote,dlc,data,name,tx_seq,tx_call_start_us,tx_call_end_us"
+        )
+    }
+
+    fn write_frame(&mut self, logged: &LoggedFrame) -> io::Result<()> {
+        let frame = &logged.frame;
+        let data: Vec<String> = frame.data.iter().map(|b| format!("{:02X}", b)).collect();
+        // TX 訊框附上傳送序號與 VCI_Transmit 的呼叫時間 (單調時鐘)，RX 留空
+        let tx_timing = frame.tx_timing.map_or_else(
+            || ",,".to_string(),
+            |timing| format!("{},{},{}", timing.tx_seq, timing.call_start_us, timing.call_end_us),
+        );
+        writeln!(
+            self.out,
+            "{}.{:06},{},{},{},{},{},{},{:X},{},{},{},{},{},{}",
+            frame.host_timestamp_us / 1_000_000,
+            frame.host_timestamp_us % 1_000_000,
+            frame.device_type,
+            frame.device_index,
+            frame.channel,
+            match logged.direction {
+                Direction::Rx => "Rx",
+                Direction::Tx => "Tx",
+            },
+            frame.provenance.as_str(),
+            frame.config_generation,
+            frame.id,
+            frame.extended as u8,
+            frame.remote as u8,
+            frame.dlc,
+            data.join(" "),
+            frame.name.as_deref().unwrap_or_default(),
+            tx_timing
+        )
+    }
+
+    fn write_annotation(&mut self, annotation: &Annotation) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "# annotation {}.{:06} {}",
+            annotation.timestamp_us / 1_000_000,
+            annotation.timestamp_us % 1_000_000,
+            annotation.comment_text()
+        )
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,491 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::annotation::Annotation;
+use crate::config_history::ConfigGeneration;
+use crate::frame::{host_timestamp_us, CanFrameEvent, Direction};
+use crate::supervisor;
+use crate::timed_capture::{CaptureLimit, CaptureProgress, CaptureSource};
+use crate::{invalid_argument, StateMutex};
+
+mod asc;
+mod csv;
+mod pcapng;
+
+/// 整批寫檔時回報進度的間隔 (訊框數)
+pub const PROGRESS_INTERVAL: u64 = 10_000;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Csv,
+    Asc,
+    Pcapng,
+}
+
+/// 記錄器要寫入的方向
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogDirections {
+    Rx,
+    Tx,
+    #[default]
+    Both,
+}
+
+impl LogDirections {
+    fn accepts(self, direction: Direction) -> bool {
+        match self {
+            LogDirections::Rx => direction == Direction::Rx,
+            LogDirections::Tx => direction == Direction::Tx,
+            LogDirections::Both => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoggedFrame {
+    pub direction: Direction,
+    pub frame: CanFrameEvent,
+}
+
+/// 各種記錄格式的共同介面
+pub trait FrameWriter: Send {
+    /// configs 為記錄開始時各通道的設定，讓記錄檔能說明當時的硬體過濾與模式
+    fn write_header(&mut self, configs: &[ConfigGeneration]) -> io::Result<()>;
+    fn write_frame(&mut self, frame: &LoggedFrame) -> io::Result<()>;
+    /// 以註解列寫入使用者的註記；呼叫端負責在時間順序上的正確位置呼叫
+    fn write_annotation(&mut self, annotation: &Annotation) -> io::Result<()>;
+    /// 寫入結尾並清空緩衝
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+enum LogMessage {
+    Frame(Box<LoggedFrame>),
+    Annotation(Annotation),
+    Stop,
+}
+
+/// 接收迴圈與傳送路徑用來把訊框交給記錄執行緒的入口；送進 channel 後立即返回，
+/// 不會因為寫檔而拖慢接收。所有傳送 (手動、週期、重播、序列、自動回應…) 都經過 TxPath，
+/// 送出的訊框由那裡以 Tx 方向交給記錄器
+pub struct LogSink {
+    sender: Sender<LogMessage>,
+    channel: Option<u32>,
+    directions: LogDirections,
+}
+
+impl LogSink {
+    pub fn log(&self, direction: Direction, frame: &CanFrameEvent) {
+        if !self.directions.accepts(direction) {
+            return;
+        }
+        if self.channel.is_some_and(|c| c != frame.channel) {
+            return;
+        }
+        let _ = self.sender.send(LogMessage::Frame(Box::new(LoggedFrame {
+            direction,
+            frame: frame.clone(),
+        })));
+    }
+
+    /// 註記不分通道與方向，一律寫入
+    pub fn annotate(&self, annotation: &Annotation) {
+        let _ = self.sender.send(LogMessage::Annotation(annotation.clone()));
+    }
+}
+
+/// 執行中的記錄器
+pub struct ActiveLogger {
+    path: PathBuf,
+    handle: JoinHandle<io::Result<Vec<LogFileSummary>>>,
+    capture: Option<Arc<CaptureProgress>>,
+}
+
+impl ActiveLogger {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn capture(&self) -> Option<&CaptureProgress> {
+        self.capture.as_deref()
+    }
+}
+
+/// 一個已關閉的記錄檔；同時作為 log-rotated 事件的內容
+#[derive(Serialize, Clone)]
+pub struct LogFileSummary {
+    pub path: String,
+    pub frames_written: u64,
+    pub file_size: u64,
+}
+
+#[derive(Serialize)]
+pub struct LogSummary {
+    /// 第一個記錄檔
+    pub path: String,
+    /// 所有檔案的合計
+    pub frames_written: u64,
+    pub file_size: u64,
+    /// 依序產生的所有檔案；未輪替時只有一個
+    pub files: Vec<LogFileSummary>,
+}
+
+#[derive(Serialize, Clone)]
+struct LogErrorEvent {
+    path: String,
+    message: String,
+}
+
+/// 計算寫入的位元組數；輪替時以此判斷檔案大小，不必等 BufWriter 寫到磁碟
+struct CountingWriter<W> {
+    inner: W,
+    written: Arc<AtomicU64>,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 一個開啟中的記錄檔，建立時即寫入該格式的檔頭
+struct OpenLog {
+    path: PathBuf,
+    writer: Box<dyn FrameWriter>,
+    written: Arc<AtomicU64>,
+    frames: u64,
+    opened: Instant,
+}
+
+impl OpenLog {
+    fn create(path: PathBuf, format: LogFormat, start_us: u64, configs: &[ConfigGeneration]) -> io::Result<Self> {
+        let written = Arc::new(AtomicU64::new(0));
+        let out = CountingWriter {
+            inner: BufWriter::new(File::create(&path)?),
+            written: written.clone(),
+        };
+        let mut writer: Box<dyn FrameWriter> = match format {
+            LogFormat::Csv => Box::new(csv::CsvWriter::new(out)),
+            LogFormat::Asc => Box::new(asc::AscWriter::new(out, start_us)),
+            LogFormat::Pcapng => Box::new(pcapng::PcapngWriter::new(out)),
+        };
+        writer.write_header(configs)?;
+        Ok(Self {
+            path,
+            writer,
+            written,
+            frames: 0,
+            opened: Instant::now(),
+        })
+    }
+
+    fn write(&mut self, frame: &LoggedFrame) -> io::Result<()> {
+        self.writer.write_frame(frame)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> io::Result<()> {
+        self.writer.write_annotation(annotation)
+    }
+
+    fn close(mut self) -> io::Result<LogFileSummary> {
+        self.writer.finish()?;
+        Ok(LogFileSummary {
+            path: self.path.display().to_string(),
+            frames_written: self.frames,
+            file_size: self.written.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// 記錄檔輪替的條件；兩者都設定時先達到的為準
+#[derive(Clone, Copy)]
+struct Rotation {
+    max_bytes: Option<u64>,
+    max_duration: Option<Duration>,
+}
+
+impl Rotation {
+    fn is_due(&self, log: &OpenLog) -> bool {
+        self.max_bytes.is_some_and(|max| log.written.load(Ordering::Relaxed) >= max)
+            || self.max_duration.is_some_and(|max| log.opened.elapsed() >= max)
+    }
+}
+
+/// 輪替時的第 index 個檔名：capture.csv → capture_0001.csv
+fn rotated_path(base: &Path, index: u32) -> PathBuf {
+    let stem = base.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match base.extension() {
+        Some(ext) => format!("{}_{:04}.{}", stem, index, ext.to_string_lossy()),
+        None => format!("{}_{:04}", stem, index),
+    };
+    base.with_file_name(name)
+}
+
+/// 一次把整批訊框寫成記錄檔 (例如匯出觸發擷取)，回傳寫入的訊框數。configs 寫進檔頭；
+/// annotations 依時間排序，以註解列穿插在時間早於它的訊框之後
+pub(crate) fn write_log_file(
+    path: &Path,
+    format: LogFormat,
+    frames: &[LoggedFrame],
+    annotations: &[Annotation],
+    configs: &[ConfigGeneration],
+) -> io::Result<u64> {
+    write_log_file_with_progress(path, format, frames, annotations, configs, |_| {})
+}
+
+/// 同 write_log_file()，每寫入 PROGRESS_INTERVAL 個訊框以已寫入的數量呼叫 progress
+pub(crate) fn write_log_file_with_progress(
+    path: &Path,
+    format: LogFormat,
+    frames: &[LoggedFrame],
+    annotations: &[Annotation],
+    configs: &[ConfigGeneration],
+    mut progress: impl FnMut(u64),
+) -> io::Result<u64> {
+    let start_us = frames.first().map_or_else(host_timestamp_us, |f| f.frame.host_timestamp_us);
+    let mut log = OpenLog::create(path.to_path_buf(), format, start_us, configs)?;
+    let mut annotations = annotations.iter().peekable();
+    for frame in frames {
+        while let Some(annotation) = annotations.next_if(|a| a.timestamp_us < frame.frame.host_timestamp_us) {
+            log.annotate(annotation)?;
+        }
+        log.write(frame)?;
+        if log.frames % PROGRESS_INTERVAL == 0 {
+            progress(log.frames);
+        }
+    }
+    for annotation in annotations {
+        log.annotate(annotation)?;
+    }
+    Ok(log.close()?.frames_written)
+}
+
+struct Logger {
+    log: OpenLog,
+    format: LogFormat,
+    base: PathBuf,
+    rotation: Option<Rotation>,
+    /// 只記錄此通道時，輪替後的檔頭也只寫此通道的設定
+    channel: Option<u32>,
+    index: u32,
+    files: Vec<LogFileSummary>,
+    capture: Option<Arc<CaptureProgress>>,
+    state: Arc<StateMutex>,
+    app_handle: tauri::AppHandle,
+}
+
+impl Logger {
+    /// 輪替只在兩次寫入之間進行，訊框不會在切換時遺失；
+    /// 新檔在下一個訊框到達時才建立，停止記錄時不會留下空檔
+    fn write(&mut self, frame: &LoggedFrame) -> io::Result<()> {
+        if self.rotation.is_some_and(|r| r.is_due(&self.log)) {
+            self.index += 1;
+            let configs = self.state.lock().map(|app_state| app_state.active_configs(self.channel)).unwrap_or_default();
+            let next = OpenLog::create(rotated_path(&self.base, self.index), self.format, frame.frame.host_timestamp_us, &configs)?;
+            let closed = std::mem::replace(&mut self.log, next).close()?;
+            let _ = self.app_handle.emit("log-rotated", closed.clone());
+            self.files.push(closed);
+        }
+        self.log.write(frame)
+    }
+
+    /// 回傳 true 表示達到 capture 的上限而自行結束
+    fn run(&mut self, receiver: Receiver<LogMessage>) -> io::Result<bool> {
+        loop {
+            let message = match self.capture.as_ref().and_then(|c| c.time_left()) {
+                Some(left) => match receiver.recv_timeout(left) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout) => return Ok(true),
+                    Err(RecvTimeoutError::Disconnected) => return Ok(false),
+                },
+                None => match receiver.recv() {
+                    Ok(message) => message,
+                    Err(_) => return Ok(false),
+                },
+            };
+            match message {
+                LogMessage::Frame(frame) => {
+                    // 期限過後才取出的訊框不寫入
+                    if self.capture.as_ref().is_some_and(|c| c.is_reached()) {
+                        return Ok(true);
+                    }
+                    self.write(&frame)?;
+                    if let Some(capture) = &self.capture {
+                        capture.record(1);
+                        if capture.is_reached() {
+                            return Ok(true);
+                        }
+                    }
+                }
+                LogMessage::Annotation(annotation) => self.log.annotate(&annotation)?,
+                LogMessage::Stop => return Ok(false),
+            }
+        }
+    }
+
+    /// 達到上限時把自己從 AppState 移除，之後不再收到訊框
+    fn detach(&self) {
+        detach_current(&self.state);
+    }
+}
+
+/// 把目前執行緒的記錄從 AppState 移除；已被 stop_logging 取走時不動
+fn detach_current(state: &StateMutex) {
+    let Ok(mut app_state) = state.lock() else {
+        return;
+    };
+    let current = std::thread::current().id();
+    if app_state.logger.as_ref().is_some_and(|l| l.handle.thread().id() == current) {
+        app_state.logger = None;
+        if let Ok(mut sink) = app_state.log_sink.lock() {
+            *sink = None;
+        }
+    }
+}
+
+/// 記錄執行緒：寫檔失敗 (例如磁碟已滿) 時發出 log-error 事件並結束
+fn run_logger(mut logger: Logger, receiver: Receiver<LogMessage>) -> io::Result<Vec<LogFileSummary>> {
+    let result = logger.run(receiver);
+    let limit_reached = matches!(result, Ok(true));
+    if limit_reached {
+        logger.detach();
+    }
+    let path = logger.log.path.display().to_string();
+    let result = result.and_then(|_| logger.log.close());
+    match result {
+        Ok(last) => {
+            logger.files.push(last);
+            if let Some(capture) = logger.capture.as_ref().filter(|_| limit_reached) {
+                let first = logger.files.first().map(|f| f.path.clone());
+                let finished = capture.finished(CaptureSource::Logging, None, None, first);
+                let _ = logger.app_handle.emit("capture-finished", finished);
+            }
+            Ok(logger.files)
+        }
+        Err(e) => {
+            let _ = logger.app_handle.emit(
+                "log-error",
+                LogErrorEvent {
+                    path,
+                    message: e.to_string(),
+                },
+            );
+            Err(e)
+        }
+    }
+}
+
+/// 開始記錄，directions 省略時同時記錄 RX 與 TX。設定 max_file_size_mb 或 max_duration_min 時啟用輪替，
+/// 檔名依序加上 _0001、_0002… 後綴，每個檔案都有完整的檔頭。
+/// 設定 limit 時記錄 duration_s 秒或 max_frames 個訊框後自動停止，並送出 capture-finished
+#[tauri::command]
+pub fn start_logging(
+    path: String,
+    channel: Option<u32>,
+    format: Option<LogFormat>,
+    directions: Option<LogDirections>,
+    max_file_size_mb: Option<u64>,
+    max_duration_min: Option<u64>,
+    limit: Option<CaptureLimit>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let capture = limit.map(CaptureLimit::validate).transpose()?.map(|limit| Arc::new(CaptureProgress::new(limit)));
+    if max_file_size_mb == Some(0) {
+        return Err(invalid_argument("max_file_size_mb", "must be greater than 0"));
+    }
+    if max_duration_min == Some(0) {
+        return Err(invalid_argument("max_duration_min", "must be greater than 0"));
+    }
+    let rotation = (max_file_size_mb.is_some() || max_duration_min.is_some()).then_some(Rotation {
+        max_bytes: max_file_size_mb.map(|mb| mb * 1024 * 1024),
+        max_duration: max_duration_min.map(|min| Duration::from_secs(min * 60)),
+    });
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    if app_state.logger.is_some() {
+        return Err("Logging already active".into());
+    }
+    let base = PathBuf::from(path);
+    let path = match rotation {
+        Some(_) => rotated_path(&base, 1),
+        None => base.clone(),
+    };
+    let format = format.unwrap_or(LogFormat::Csv);
+    let log = OpenLog::create(path.clone(), format, host_timestamp_us(), &app_state.active_configs(channel))
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let (sender, receiver) = mpsc::channel();
+    let logger = Logger {
+        log,
+        format,
+        base,
+        rotation,
+        channel,
+        index: 1,
+        files: Vec::new(),
+        capture: capture.clone(),
+        state: state.inner().clone(),
+        app_handle,
+    };
+    let logger_state = state.inner().clone();
+    let context = path.display().to_string();
+    let handle = std::thread::spawn(move || {
+        supervisor::guard("logger", context, || run_logger(logger, receiver)).unwrap_or_else(|| {
+            detach_current(&logger_state);
+            Err(io::Error::other("logger thread panicked"))
+        })
+    });
+    *app_state.log_sink.lock().map_err(|_| "Failed to lock log sink")? = Some(LogSink {
+        sender,
+        channel,
+        directions: directions.unwrap_or_default(),
+    });
+    app_state.logger = Some(ActiveLogger {
+        path: path.clone(),
+        handle,
+        capture,
+    });
+    Ok(format!("Logging to {}", path.display()))
+}
+
+/// 停止記錄並等待緩衝寫完，回傳產生的所有檔案與合計的訊框數、檔案大小
+#[tauri::command]
+pub fn stop_logging(state: State<Arc<StateMutex>>) -> Result<LogSummary, String> {
+    let (logger, sink) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let logger = app_state.logger.take().ok_or("Logging is not active")?;
+        let sink = app_state.log_sink.lock().map_err(|_| "Failed to lock log sink")?.take();
+        (logger, sink)
+    };
+    if let Some(sink) = sink {
+        let _ = sink.sender.send(LogMessage::Stop);
+    }
+    let files = logger
+        .handle
+        .join()
+        .map_err(|_| "Logger thread panicked")?
+        .map_err(|e| format!("Logging failed: {}", e))?;
+    Ok(LogSummary {
+        path: logger.path.display().to_string(),
+        frames_written: files.iter().map(|f| f.frames_written).sum(),
+        file_size: files.iter().map(|f| f.file_size).sum(),
+        files,
+    })
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use super::{FrameWriter, LoggedFrame};
+use crate::annotation::Annotation;
+use crate::config_history::ConfigGeneration;
+use crate::frame::{Direction, Provenance};
+
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const LINKTYPE_CAN_SOCKETCAN: u16 = 227;
+/// struct can_frame 的大小 (4 位元組 ID + 4 位元組長度欄位 + 8 位元組資料)
+const SOCKETCAN_FRAME_LEN: usize = 16;
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+const EPB_FLAG_INBOUND: u32 = 0b01;
+const EPB_FLAG_OUTBOUND: u32 = 0b10;
+
+/// pcapng 格式 (LINKTYPE_CAN_SOCKETCAN)，可直接用 Wireshark 開啟。
+/// 每個裝置的每個 CAN 通道第一次出現時寫入一個介面描述區塊
+pub struct PcapngWriter<W: Write> {
+    out: W,
+    interfaces: HashMap<(u32, u32, u32), u32>,
+    /// 各通道最後寫出的設定世代；改變時在該訊框的註解中註明
+    generations: HashMap<(u32, u32, u32), u64>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            interfaces: HashMap::new(),
+            generations: HashMap::new(),
+        }
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let total_len = (12 + body.len()) as u32;
+        self.out.write_all(&block_type.to_le_bytes())?;
+        self.out.write_all(&total_len.to_le_bytes())?;
+        self.out.write_all(body)?;
+        self.out.write_all(&total_len.to_le_bytes())
+    }
+
+    fn interface_id(&mut self, device_type: u32, device_index: u32, channel: u32) -> io::Result<u32> {
+        let key = (device_type, device_index, channel);
+        if let Some(&id) = self.interfaces.get(&key) {
+            return Ok(id);
+        }
+        let id = self.interfaces.len() as u32;
+        let mut body = Vec::new();
+        body.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(SOCKETCAN_FRAME_LEN as u32).to_le_bytes());
+        let name = format!("CAN{} ({}:{})", channel + 1, device_type, device_index);
+        push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+        // 時間戳記單位 10^-6 秒
+        push_option(&mut body, OPT_IF_TSRESOL, &[6]);
+        push_option(&mut body, OPT_END, &[]);
+        self.write_block(BLOCK_INTERFACE_DESCRIPTION, &body)?;
+        self.interfaces.insert(key, id);
+        Ok(id)
+    }
+}
+
+/// 選項值需補齊到 4 位元組邊界
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+impl<W: Write + Send> FrameWriter for PcapngWriter<W> {
+    fn write_header(&mut self, configs: &[ConfigGeneration]) -> io::Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // 區段長度未知
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        // 記錄開始時各通道的設定寫成區段標頭的註解
+        for config in configs {
+            push_option(&mut body, OPT_COMMENT, format!("config {}", config.describe()).as_bytes());
+            self.generations.insert((config.dev_type, config.dev_index, config.channel), config.generation);
+        }
+        if !configs.is_empty() {
+            push_option(&mut body, OPT_END, &[]);
+        }
+        self.write_block(BLOCK_SECTION_HEADER, &body)
+    }
+
+    fn write_frame(&mut self, logged: &LoggedFrame) -> io::Result<()> {
+        let frame = &logged.frame;
+        let interface_id = self.interface_id(frame.device_type, frame.device_index, frame.channel)?;
+
+        // SocketCAN 標頭中的 can_id 為網路位元組順序
+        let mut can_id = frame.id;
+        if frame.extended {
+            can_id |= CAN_EFF_FLAG;
+        }
+        if frame.remote {
+            can_id |= CAN_RTR_FLAG;
+        }
+        let mut packet = [0u8; SOCKETCAN_FRAME_LEN];
+        packet[..4].copy_from_slice(&can_id.to_be_bytes());
+        packet[4] = frame.dlc.min(8);
+        packet[8..8 + frame.data.len()].copy_from_slice(&frame.data);
+
+        let timestamp = frame.host_timestamp_us;
+        let mut body = Vec::new();
+        body.extend_from_slice(&interface_id.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(SOCKETCAN_FRAME_LEN as u32).to_le_bytes());
+        body.extend_from_slice(&(SOCKETCAN_FRAME_LEN as u32).to_le_bytes());
+        body.extend_from_slice(&packet);
+        let flags = match logged.direction {
+            Direction::Rx => EPB_FLAG_INBOUND,
+            Direction::Tx => EPB_FLAG_OUTBOUND,
+        };
+        push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+        let mut comment = match frame.provenance {
+            Provenance::Rx => String::new(),
+            provenance => format!("provenance={}", provenance.as_str()),
+        };
+        let channel = (frame.device_type, frame.device_index, frame.channel);
+        if self.generations.insert(channel, frame.config_generation).is_some_and(|g| g != frame.config_generation) {
+            comment.push_str(&format!(" config_generation={}", frame.config_generation));
+        }
+        if let Some(timing) = frame.tx_timing {
+            comment.push_str(&format!(
+                " tx_seq={} tx_call_start_us={} tx_call_end_us={}",
+                timing.tx_seq, timing.call_start_us, timing.call_end_us
+            ));
+        }
+        if !comment.is_empty() {
+            push_option(&mut body, OPT_COMMENT, comment.trim_start().as_bytes());
+        }
+        push_option(&mut body, OPT_END, &[]);
+        self.write_block(BLOCK_ENHANCED_PACKET, &body)
+    }
+
+    /// pcapng 的註解只能掛在封包或介面上，註記不寫入
+    fn write_annotation(&mut self, _annotation: &Annotation) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::CanFrameEvent;
+    use crate::VciCanObj;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// 拆出 (區塊類型, 內容)，同時檢查前後長度欄位一致且為 4 的倍數
+    fn blocks(mut bytes: &[u8]) -> Vec<(u32, Vec<u8>)> {
+        let mut blocks = Vec::new();
+        while !bytes.is_empty() {
+            let total_len = u32_at(bytes, 4) as usize;
+            assert_eq!(total_len % 4, 0, "block length {} is not padded", total_len);
+            assert_eq!(u32_at(bytes, total_len - 4) as usize, total_len, "trailing block length");
+            blocks.push((u32_at(bytes, 0), bytes[8..total_len - 4].to_vec()));
+            bytes = &bytes[total_len..];
+        }
+        blocks
+    }
+
+    /// 拆出 (選項代碼, 值)，值之後的補齊位元組必須為 0
+    fn options(mut body: &[u8]) -> Vec<(u16, Vec<u8>)> {
+        let mut options = Vec::new();
+        while !body.is_empty() {
+            let code = u16::from_le_bytes([body[0], body[1]]);
+            let len = u16::from_le_bytes([body[2], body[3]]) as usize;
+            let padded = len.next_multiple_of(4);
+            assert!(body[4 + len..4 + padded].iter().all(|&b| b == 0), "option {} padding", code);
+            options.push((code, body[4..4 + len].to_vec()));
+            body = &body[4 + padded..];
+        }
+        options
+    }
+
+    fn logged(channel: u32, can_obj: &VciCanObj, direction: Direction) -> LoggedFrame {
+        let mut frame = CanFrameEvent::from_raw((4, 0), channel, can_obj, 0x1_0000_0002).with_direction(direction);
+        if direction == Direction::Tx {
+            frame.provenance = Provenance::TxPeriodic;
+        }
+        LoggedFrame { direction, frame }
+    }
+
+    #[test]
+    fn blocks_carry_matching_lengths_and_padded_options() {
+        let mut out = Vec::new();
+        let mut writer = PcapngWriter::new(&mut out);
+        writer.write_header(&[]).unwrap();
+        let extended = VciCanObj {
+            id: 0x18DA_F110,
+            extern_flag: 1,
+            data_len: 3,
+            data: [0xAA, 0xBB, 0xCC, 0, 0, 0, 0, 0],
+            ..Default::default()
+        };
+        let remote = VciCanObj {
+            id: 0x123,
+            remote_flag: 1,
+            data_len: 2,
+            ..Default::default()
+        };
+        writer.write_frame(&logged(0, &extended, Direction::Rx)).unwrap();
+        writer.write_frame(&logged(0, &remote, Direction::Tx)).unwrap();
+        writer.write_frame(&logged(1, &extended, Direction::Rx)).unwrap();
+        writer.finish().unwrap();
+
+        let blocks = blocks(&out);
+        let types: Vec<u32> = blocks.iter().map(|(block_type, _)| *block_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                BLOCK_SECTION_HEADER,
+                BLOCK_INTERFACE_DESCRIPTION,
+                BLOCK_ENHANCED_PACKET,
+                BLOCK_ENHANCED_PACKET,
+                BLOCK_INTERFACE_DESCRIPTION,
+                BLOCK_ENHANCED_PACKET,
+            ]
+        );
+
+        let section = &blocks[0].1;
+        assert_eq!(section.len(), 16);
+        assert_eq!(u32_at(section, 0), BYTE_ORDER_MAGIC);
+
+        // "CAN1 (4:0)" 為 10 個位元組，補齊到 12
+        let interface = &blocks[1].1;
+        assert_eq!(u16::from_le_bytes([interface[0], interface[1]]), LINKTYPE_CAN_SOCKETCAN);
+        assert_eq!(u32_at(interface, 4) as usize, SOCKETCAN_FRAME_LEN);
+        assert_eq!(
+            options(&interface[8..]),
+            vec![(OPT_IF_NAME, b"CAN1 (4:0)".to_vec()), (OPT_IF_TSRESOL, vec![6]), (OPT_END, vec![])]
+        );
+        assert_eq!(interface.len(), 8 + 4 + 12 + 4 + 4 + 4);
+
+        let packet = &blocks[2].1;
+        assert_eq!(u32_at(packet, 0), 0);
+        assert_eq!((u32_at(packet, 4), u32_at(packet, 8)), (1, 2));
+        assert_eq!((u32_at(packet, 12) as usize, u32_at(packet, 16) as usize), (SOCKETCAN_FRAME_LEN, SOCKETCAN_FRAME_LEN));
+        assert_eq!(&packet[20..36], &[0x98, 0xDA, 0xF1, 0x10, 3, 0, 0, 0, 0xAA, 0xBB, 0xCC, 0, 0, 0, 0, 0]);
+        assert_eq!(options(&packet[36..]), vec![(OPT_EPB_FLAGS, EPB_FLAG_INBOUND.to_le_bytes().to_vec()), (OPT_END, vec![])]);
+
+        // "provenance=tx-periodic" 為 22 個位元組，補齊到 24
+        let remote_packet = &blocks[3].1;
+        assert_eq!(&remote_packet[20..28], &[0x40, 0x00, 0x01, 0x23, 2, 0, 0, 0]);
+        let remote_options = options(&remote_packet[36..]);
+        assert_eq!(remote_options[0], (OPT_EPB_FLAGS, EPB_FLAG_OUTBOUND.to_le_bytes().to_vec()));
+        assert_eq!(remote_options[1].0, OPT_COMMENT);
+        assert_eq!(remote_options[1].1, format!("provenance={}", Provenance::TxPeriodic.as_str()).into_bytes());
+        assert_eq!(remote_options[2], (OPT_END, vec![]));
+
+        assert_eq!(options(&blocks[4].1[8..])[0], (OPT_IF_NAME, b"CAN2 (4:0)".to_vec()));
+        assert_eq!(u32_at(&blocks[5].1, 0), 1);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::ring_buffer::BufferedFrame;
+use crate::{invalid_argument, supervisor, AppState, StateMutex};
+
+/// 每個通道的 ID 統計表最多追蹤的 ID 數
+pub const DEFAULT_MAX_TRACKED_IDS: usize = 8192;
+pub const DEFAULT_MAX_BUFFER_BYTES: usize = 256 * 1024 * 1024;
+pub const DEFAULT_MAX_CAPTURE_BYTES: usize = 64 * 1024 * 1024;
+const MIN_TRACKED_IDS: usize = 16;
+const MIN_BYTES: usize = 64 * 1024;
+/// 同一子系統的 memory-cap-reached 最多每這段時間送一次
+const WARN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 訊框在記憶體中大約佔用的位元組數 (結構本身加上字串與解碼結果)
+pub fn frame_bytes(frame: &CanFrameEvent) -> usize {
+    let decoded = frame.decoded.as_ref().map_or(0, |decoded| {
+        decoded.message.len()
+            + decoded.signals.keys().map(|name| name.len() + 48).sum::<usize>()
+            + decoded.skipped.iter().map(|name| name.len() + 24).sum::<usize>()
+    });
+    std::mem::size_of::<BufferedFrame>() + frame.data.capacity() + frame.name.as_ref().map_or(0, String::len) + decoded
+}
+
+/// map 超過 max 個項目時移除最久沒出現的項目，只留下 max 的 15/16，避免每個新 ID 都觸發一次驅逐。回傳移除的數量
+pub fn evict_least_recent<K: Clone + Eq + Hash, V>(map: &mut HashMap<K, V>, max: usize, last_seen: impl Fn(&V) -> u64) -> usize {
+    if map.len() <= max {
+        return 0;
+    }
+    let remove = map.len() - (max - max / 16);
+    let mut by_age: Vec<(u64, K)> = map.iter().map(|(key, value)| (last_seen(value), key.clone())).collect();
+    by_age.select_nth_unstable_by_key(remove - 1, |&(seen, _)| seen);
+    for (_, key) in &by_age[..remove] {
+        map.remove(key);
+    }
+    remove
+}
+
+#[derive(Serialize, Clone)]
+struct MemoryCapEvent {
+    subsystem: &'static str,
+    limit: usize,
+    /// 這次驅逐的項目數
+    evicted: u64,
+    /// 累計驅逐的項目數
+    evictions: u64,
+}
+
+/// 達到上限後的驅逐計數；驅逐時送出 memory-cap-reached，同一子系統每 10 秒最多一次
+#[derive(Default)]
+pub struct CapWarning {
+    evictions: u64,
+    last_warned: Option<Instant>,
+}
+
+impl CapWarning {
+    pub fn evicted(&mut self, subsystem: &'static str, evicted: u64, limit: usize) {
+        if evicted == 0 {
+            return;
+        }
+        self.evictions += evicted;
+        if self.last_warned.is_some_and(|warned| warned.elapsed() < WARN_INTERVAL) {
+            return;
+        }
+        self.last_warned = Some(Instant::now());
+        supervisor::emit(
+            "memory-cap-reached",
+            MemoryCapEvent {
+                subsystem,
+                limit,
+                evicted,
+                evictions: self.evictions,
+            },
+        );
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+}
+
+/// 可在執行中調整的記憶體上限
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct MemoryLimits {
+    /// 每個通道 ID 統計表與 Δt 計算追蹤的 ID 數；超過時移除最久沒出現的 ID
+    pub max_tracked_ids: usize,
+    /// 環形緩衝的位元組上限，與訊框數上限先達到的為準
+    pub max_buffer_bytes: usize,
+    /// 所有觸發擷取合計的位元組上限；超過時先捨棄觸發前緩衝最舊的訊框，再提早結束錄製中的擷取
+    pub max_capture_bytes: usize,
+}
+
+/// 一個子系統目前的用量估計
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct SubsystemMemory {
+    pub entries: usize,
+    pub bytes: usize,
+    pub limit: usize,
+    pub evictions: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MemoryStats {
+    pub limits: MemoryLimits,
+    pub ring_buffer: SubsystemMemory,
+    /// entries 為所有通道追蹤的 ID 數
+    pub id_statistics: SubsystemMemory,
+    /// entries 為所有擷取保存的訊框數
+    pub captures: SubsystemMemory,
+    pub total_bytes: usize,
+}
+
+impl AppState {
+    pub fn memory_limits(&self) -> MemoryLimits {
+        MemoryLimits {
+            max_tracked_ids: self.id_statistics.lock().map_or(DEFAULT_MAX_TRACKED_IDS, |stats| stats.max_ids()),
+            max_buffer_bytes: self.frame_buffer.lock().map_or(DEFAULT_MAX_BUFFER_BYTES, |ring| ring.max_bytes()),
+            max_capture_bytes: self.captures.lock().map_or(DEFAULT_MAX_CAPTURE_BYTES, |captures| captures.max_bytes()),
+        }
+    }
+
+    /// 立即套用新的上限；已超過的部分馬上驅逐
+    pub fn set_memory_limits(&mut self, limits: MemoryLimits) -> Result<(), String> {
+        if limits.max_tracked_ids < MIN_TRACKED_IDS {
+            return Err(invalid_argument("max_tracked_ids", format!("must be at least {}", MIN_TRACKED_IDS)));
+        }
+        for (field, bytes) in [("max_buffer_bytes", limits.max_buffer_bytes), ("max_capture_bytes", limits.max_capture_bytes)] {
+            if bytes < MIN_BYTES {
+                return Err(invalid_argument(field, format!("must be at least {}", MIN_BYTES)));
+            }
+        }
+        self.id_statistics
+            .lock()
+            .map_err(|_| "Failed to lock statistics")?
+            .set_max_ids(limits.max_tracked_ids);
+        self.frame_buffer
+            .lock()
+            .map_err(|_| "Failed to lock frame buffer")?
+            .set_max_bytes(limits.max_buffer_bytes);
+        self.captures
+            .lock()
+            .map_err(|_| "Failed to lock captures")?
+            .set_max_bytes(limits.max_capture_bytes);
+        Ok(())
+    }
+
+    pub fn memory_stats(&self) -> Result<MemoryStats, String> {
+        let ring_buffer = self.frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?.memory();
+        let id_statistics = self.id_statistics.lock().map_err(|_| "Failed to lock statistics")?.memory();
+        let captures = self.captures.lock().map_err(|_| "Failed to lock captures")?.memory();
+        Ok(MemoryStats {
+            limits: self.memory_limits(),
+            ring_buffer,
+            id_statistics,
+            captures,
+            total_bytes: ring_buffer.bytes + id_statistics.bytes + captures.bytes,
+        })
+    }
+}
+
+/// 各子系統估計的記憶體用量與上限
+#[tauri::command]
+pub fn get_memory_stats(state: State<Arc<StateMutex>>) -> Result<MemoryStats, String> {
+    state.lock().map_err(|_| "Failed to lock state")?.memory_stats()
+}
+
+/// 調整記憶體上限；省略的欄位維持目前的值
+#[tauri::command]
+pub fn set_memory_limits(
+    max_tracked_ids: Option<usize>,
+    max_buffer_bytes: Option<usize>,
+    max_capture_bytes: Option<usize>,
+    state: State<Arc<StateMutex>>,
+) -> Result<MemoryLimits, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let current = app_state.memory_limits();
+    let limits = MemoryLimits {
+        max_tracked_ids: max_tracked_ids.unwrap_or(current.max_tracked_ids),
+        max_buffer_bytes: max_buffer_bytes.unwrap_or(current.max_buffer_bytes),
+        max_capture_bytes: max_capture_bytes.unwrap_or(current.max_capture_bytes),
+    };
+    app_state.set_memory_limits(limits)?;
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VciCanObj;
+
+    #[test]
+    fn eviction_removes_the_least_recently_seen_entries_down_to_fifteen_sixteenths() {
+        let mut map: HashMap<u32, u64> = (0..20).map(|id| (id, 100 + id as u64)).collect();
+        assert_eq!(evict_least_recent(&mut map, 20, |&seen| seen), 0);
+        assert_eq!(evict_least_recent(&mut map, 16, |&seen| seen), 5);
+        let mut kept: Vec<u32> = map.into_keys().collect();
+        kept.sort_unstable();
+        assert_eq!(kept, (5..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn warnings_are_rate_limited_but_every_eviction_is_counted() {
+        let mut warning = CapWarning::default();
+        warning.evicted("ring-buffer", 0, 10);
+        assert!(warning.last_warned.is_none());
+        warning.evicted("ring-buffer", 3, 10);
+        let warned = warning.last_warned;
+        warning.evicted("ring-buffer", 4, 10);
+        assert_eq!(warning.evictions(), 7);
+        assert_eq!(warning.last_warned, warned);
+    }
+
+    #[test]
+    fn limits_below_the_minimums_are_rejected_and_change_nothing() {
+        let mut app_state = AppState::default();
+        let defaults = app_state.memory_limits();
+        let too_small = [
+            (
+                "max_tracked_ids",
+                MemoryLimits {
+                    max_tracked_ids: MIN_TRACKED_IDS - 1,
+                    ..defaults
+                },
+            ),
+            (
+                "max_buffer_bytes",
+                MemoryLimits {
+                    max_buffer_bytes: MIN_BYTES - 1,
+                    ..defaults
+                },
+            ),
+            (
+                "max_capture_bytes",
+                MemoryLimits {
+                    max_capture_bytes: 0,
+                    ..defaults
+                },
+            ),
+        ];
+        for (field, limits) in too_small {
+            let error = app_state.set_memory_limits(limits).unwrap_err();
+            assert!(error.contains(field), "{}", error);
+        }
+        let current = app_state.memory_limits();
+        assert_eq!(
+            (current.max_tracked_ids, current.max_buffer_bytes, current.max_capture_bytes),
+            (DEFAULT_MAX_TRACKED_IDS, DEFAULT_MAX_BUFFER_BYTES, DEFAULT_MAX_CAPTURE_BYTES)
+        );
+    }
+
+    #[test]
+    fn lowering_the_buffer_limit_evicts_at_once_and_shows_in_the_stats() {
+        let mut app_state = AppState::default();
+        {
+            let mut ring = app_state.frame_buffer.lock().unwrap();
+            for id in 0..2000 {
+                let can_obj = VciCanObj {
+                    id,
+                    data_len: 8,
+                    ..Default::default()
+                };
+                ring.push(CanFrameEvent::from_raw((4, 0), 0, &can_obj, 0));
+            }
+        }
+        let before = app_state.memory_stats().unwrap().ring_buffer;
+        assert_eq!((before.entries, before.evictions), (2000, 0));
+
+        let limits = MemoryLimits {
+            max_buffer_bytes: MIN_BYTES,
+            ..app_state.memory_limits()
+        };
+        app_state.set_memory_limits(limits).unwrap();
+        let stats = app_state.memory_stats().unwrap();
+        assert!(stats.ring_buffer.bytes <= MIN_BYTES && stats.ring_buffer.entries < 2000);
+        assert_eq!(stats.ring_buffer.evictions, (2000 - stats.ring_buffer.entries) as u64);
+        assert_eq!((stats.ring_buffer.limit, stats.limits.max_buffer_bytes), (MIN_BYTES, MIN_BYTES));
+        assert_eq!(stats.total_bytes, stats.ring_buffer.bytes + stats.id_statistics.bytes + stats.captures.bytes);
+    }
+}
@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use rumqttc::{Client, Connection, Event, Outgoing, Packet, QoS, TlsConfiguration, Transport};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::frame::{CanFrameEvent, Direction};
+use crate::ring_buffer::BufferedFrame;
+use crate::supervisor;
+use crate::{invalid_argument, run_blocking, StateMutex};
+
+/// 接收迴圈交給發佈執行緒的批次上限；發佈來不及時丟棄新的批次，不拖慢接收
+const FEED_QUEUE_BATCHES: usize = 1000;
+/// rumqttc 請求佇列的大小；斷線期間累積超過此數量的發佈會被丟棄
+const REQUEST_QUEUE: usize = 10_000;
+/// 發佈執行緒等待新訊框的間隔，也是速率限制下延後發佈的檢查間隔
+const PUBLISH_TICK: Duration = Duration::from_millis(20);
+const CONNECTION_POLL: Duration = Duration::from_millis(200);
+const RECONNECT_INITIAL_BACKOFF_MS: u64 = 500;
+const RECONNECT_MAX_BACKOFF_MS: u64 = 30_000;
+/// stop_mqtt_publisher 等待剩餘訊息送出的上限
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct MqttPublishOptions {
+    /// 省略時為 tauri-can-<pid>
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// broker_url 為 mqtts:// 或 ssl:// 時自動啟用
+    pub tls: bool,
+    /// PEM 格式的 CA 憑證；省略時使用系統的根憑證
+    pub ca_path: Option<String>,
+    /// 可用 {device}、{channel}、{id}
+    pub frame_topic: String,
+    /// 可用 {device}、{channel}、{id}、{message}、{signal}；None 時不發佈訊號
+    pub signal_topic: Option<String>,
+    /// 每個 topic 每秒最多發佈的次數；期間內的更新只保留最新值，到時再送出
+    pub max_rate_hz: Option<f64>,
+    /// 只在內容與該 topic 上次發佈的不同時發佈
+    pub change_only: bool,
+    /// 0、1 或 2
+    pub qos: u8,
+    pub keep_alive_s: u64,
+}
+
+impl Default for MqttPublishOptions {
+    fn default() -> Self {
+        Self {
+            client_id: None,
+            username: None,
+            password: None,
+            tls: false,
+            ca_path: None,
+            frame_topic: "can/{channel}/{id}".into(),
+            signal_topic: Some("can/{channel}/{message}/{signal}".into()),
+            max_rate_hz: None,
+            change_only: false,
+            qos: 0,
+            keep_alive_s: 30,
+        }
+    }
+}
+
+/// mqtt-status 事件的內容
+#[derive(Serialize, Clone, Debug)]
+pub struct MqttStatusEvent {
+    pub broker: String,
+    /// connecting、connected、disconnected 或 stopped
+    pub state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 斷線後下次重新連線前的等待時間
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_ms: Option<u64>,
+    pub published: u64,
+    pub dropped: u64,
+}
+
+/// 發佈到 frame_topic 的內容
+#[derive(Serialize)]
+struct MqttFrame<'a> {
+    id: u32,
+    extended: bool,
+    remote: bool,
+    dlc: u8,
+    data: &'a [u8],
+    host_timestamp_us: u64,
+    direction: Direction,
+}
+
+/// 接收迴圈與 TX 回送把訊框交給執行中的發佈執行緒；沒有發佈時為空
+#[derive(Default)]
+pub struct MqttFeed {
+    sender: Mutex<Option<SyncSender<Vec<CanFrameEvent>>>>,
+}
+
+impl MqttFeed {
+    pub fn publish(&self, frames: &[BufferedFrame]) {
+        if frames.is_empty() {
+            return;
+        }
+        let Ok(mut sender) = self.sender.lock() else {
+            return;
+        };
+        if let Some(tx) = sender.as_ref() {
+            let batch = frames.iter().map(|buffered| buffered.frame.clone()).collect();
+            if let Err(TrySendError::Disconnected(_)) = tx.try_send(batch) {
+                *sender = None;
+            }
+        }
+    }
+
+    fn attach(&self) -> Receiver<Vec<CanFrameEvent>> {
+        let (tx, rx) = mpsc::sync_channel(FEED_QUEUE_BATCHES);
+        if let Ok(mut sender) = self.sender.lock() {
+            *sender = Some(tx);
+        }
+        rx
+    }
+
+    fn detach(&self) {
+        if let Ok(mut sender) = self.sender.lock() {
+            *sender = None;
+        }
+    }
+}
+
+/// 執行中的 MQTT 發佈
+pub struct MqttPublisher {
+    broker: String,
+    running: Arc<AtomicBool>,
+    publisher: JoinHandle<()>,
+    connection: JoinHandle<()>,
+}
+
+impl MqttPublisher {
+    pub fn broker(&self) -> &str {
+        &self.broker
+    }
+}
+
+/// 解析 mqtt://、tcp://、mqtts://、ssl:// 開頭的位址，回傳 (host, port, 是否為 TLS)
+fn parse_broker_url(url: &str) -> Result<(String, u16, bool), String> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| invalid_argument("broker_url", "expected mqtt://host[:port] or mqtts://host[:port]"))?;
+    let tls = match scheme {
+        "mqtt" | "tcp" => false,
+        "mqtts" | "ssl" => true,
+        other => return Err(invalid_argument("broker_url", format!("unsupported scheme {}", other))),
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse::<u16>()
+                .map_err(|_| invalid_argument("broker_url", format!("invalid port {}", port)))?;
+            (host, port)
+        }
+        None => (authority, if tls { 8883 } else { 1883 }),
+    };
+    if host.is_empty() {
+        return Err(invalid_argument("broker_url", "missing host"));
+    }
+    Ok((host.to_string(), port, tls))
+}
+
+fn mqtt_options(broker_url: &str, options: &MqttPublishOptions) -> Result<rumqttc::MqttOptions, String> {
+    let (host, port, tls_scheme) = parse_broker_url(broker_url)?;
+    let client_id = options
+        .client_id
+        .clone()
+        .unwrap_or_else(|| format!("tauri-can-{}", std::process::id()));
+    let mut mqtt = rumqttc::MqttOptions::new(client_id, host, port);
+    if options.keep_alive_s > 0 {
+        mqtt.set_keep_alive(Duration::from_secs(options.keep_alive_s));
+    }
+    match (&options.username, &options.password) {
+        (Some(username), password) => {
+            mqtt.set_credentials(username.clone(), password.clone().unwrap_or_default());
+        }
+        (None, Some(_)) => return Err(invalid_argument("options.password", "requires a username")),
+        (None, None) => {}
+    }
+    if options.tls || tls_scheme {
+        let config = match &options.ca_path {
+            Some(path) => TlsConfiguration::SimpleNative {
+                ca: std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?,
+                client_auth: None,
+            },
+            None => TlsConfiguration::Native,
+        };
+        mqtt.set_transport(Transport::tls_with_config(config));
+    }
+    Ok(mqtt)
+}
+
+fn check_options(options: &MqttPublishOptions) -> Result<QoS, String> {
+    if options.frame_topic.trim().is_empty() {
+        return Err(invalid_argument("options.frame_topic", "must not be empty"));
+    }
+    if options.signal_topic.as_deref().is_some_and(|topic| topic.trim().is_empty()) {
+        return Err(invalid_argument("options.signal_topic", "must not be empty"));
+    }
+    if options.max_rate_hz.is_some_and(|hz| !hz.is_finite() || hz <= 0.0) {
+        return Err(invalid_argument("options.max_rate_hz", "must be greater than 0"));
+    }
+    if options.keep_alive_s > u64::from(u16::MAX) {
+        return Err(invalid_argument("options.keep_alive_s", "must be at most 65535"));
+    }
+    match options.qos {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        _ => Err(invalid_argument("options.qos", "must be 0, 1 or 2")),
+    }
+}
+
+fn frame_id(frame: &CanFrameEvent) -> String {
+    if frame.extended {
+        format!("{:08X}", frame.id)
+    } else {
+        format!("{:03X}", frame.id)
+    }
+}
+
+/// 代入 topic 樣板；訊號名稱中的 MQTT 萬用字元與分隔符換成底線
+fn render_topic(pattern: &str, frame: &CanFrameEvent, message: &str, signal: &str) -> String {
+    let clean = |name: &str| name.replace(['/', '+', '#'], "_");
+    pattern
+        .replace("{device}", &frame.device_index.to_string())
+        .replace("{channel}", &frame.channel.to_string())
+        .replace("{id}", &frame_id(frame))
+        .replace("{message}", &clean(message))
+        .replace("{signal}", &clean(signal))
+}
+
+/// 每個 topic 上次發佈的內容與時間
+#[derive(Default)]
+struct TopicState {
+    last_sent: Option<Instant>,
+    /// change_only 比較用的值；raw 訊框不含時間戳記
+    last_value: Option<Vec<u8>>,
+    /// 速率限制期間延後的最新內容
+    pending: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+struct Publisher {
+    client: Client,
+    options: MqttPublishOptions,
+    qos: QoS,
+    min_interval: Option<Duration>,
+    topics: HashMap<String, TopicState>,
+    published: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Publisher {
+    fn send(&self, topic: &str, payload: Vec<u8>) {
+        match self.client.try_publish(topic, self.qos, false, payload) {
+            Ok(()) => self.published.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.dropped.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// 依 change_only 與速率限制決定立即發佈、延後或略過
+    fn offer(&mut self, topic: String, value: Vec<u8>, payload: Vec<u8>) {
+        let now = Instant::now();
+        let entry = self.topics.entry(topic.clone()).or_default();
+        if self.options.change_only && entry.pending.is_none() && entry.last_value.as_ref() == Some(&value) {
+            return;
+        }
+        let ready = match (self.min_interval, entry.last_sent) {
+            (Some(interval), Some(last)) => now.duration_since(last) >= interval,
+            _ => true,
+        };
+        if !ready {
+            entry.pending = Some((value, payload));
+            return;
+        }
+        entry.last_sent = Some(now);
+        entry.last_value = Some(value);
+        entry.pending = None;
+        self.send(&topic, payload);
+    }
+
+    fn handle(&mut self, frame: &CanFrameEvent) {
+        let value: Vec<u8> = std::iter::once(u8::from(frame.remote))
+            .chain(std::iter::once(frame.dlc))
+            .chain(frame.data.iter().copied())
+            .collect();
+        let payload = serde_json::to_vec(&MqttFrame {
+            id: frame.id,
+            extended: frame.extended,
+            remote: frame.remote,
+            dlc: frame.dlc,
+            data: &frame.data,
+            host_timestamp_us: frame.host_timestamp_us,
+            direction: frame.direction,
+        })
+        .unwrap_or_default();
+        let topic = render_topic(&self.options.frame_topic, frame, "", "");
+        self.offer(topic, value, payload);
+
+        let (Some(pattern), Some(decoded)) = (self.options.signal_topic.clone(), frame.decoded.as_ref()) else {
+            return;
+        };
+        for (signal, value) in &decoded.signals {
+            let topic = render_topic(&pattern, frame, &decoded.message, signal);
+            let text = value.to_string().into_bytes();
+            self.offer(topic, text.clone(), text);
+        }
+    }
+
+    /// 送出速率限制期間已到的延後內容；force 時不論期間全部送出
+    fn flush_pending(&mut self, force: bool) {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        for (topic, entry) in &mut self.topics {
+            let ready = force
+                || match (self.min_interval, entry.last_sent) {
+                    (Some(interval), Some(last)) => now.duration_since(last) >= interval,
+                    _ => true,
+                };
+            if !ready {
+                continue;
+            }
+            if let Some((value, payload)) = entry.pending.take() {
+                entry.last_sent = Some(now);
+                entry.last_value = Some(value);
+                due.push((topic.clone(), payload));
+            }
+        }
+        for (topic, payload) in due {
+            self.send(&topic, payload);
+        }
+    }
+
+    /// 停止時先處理佇列中剩餘的訊框與延後的內容，再排入 DISCONNECT，讓之前的發佈先送出
+    fn run(mut self, frames: Receiver<Vec<CanFrameEvent>>, running: Arc<AtomicBool>) {
+        while running.load(Ordering::SeqCst) {
+            match frames.recv_timeout(PUBLISH_TICK) {
+                Ok(batch) => batch.iter().for_each(|frame| self.handle(frame)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            self.flush_pending(false);
+        }
+        while let Ok(batch) = frames.try_recv() {
+            batch.iter().for_each(|frame| self.handle(frame));
+        }
+        self.flush_pending(true);
+        let _ = self.client.try_disconnect();
+    }
+}
+
+/// 推動 rumqttc 的連線，在狀態改變時送出 mqtt-status。
+/// 斷線後以倍增的間隔重試；停止後等 DISCONNECT 送出或逾時就結束
+struct ConnectionMonitor {
+    broker: String,
+    app_handle: tauri::AppHandle,
+    published: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl ConnectionMonitor {
+    fn emit(&self, state: &'static str, error: Option<String>, retry_in_ms: Option<u64>) {
+        let _ = self.app_handle.emit(
+            "mqtt-status",
+            MqttStatusEvent {
+                broker: self.broker.clone(),
+                state,
+                error,
+                retry_in_ms,
+                published: self.published.load(Ordering::Relaxed),
+                dropped: self.dropped.load(Ordering::Relaxed),
+            },
+        );
+    }
+
+    fn run(self, mut connection: Connection, running: Arc<AtomicBool>) {
+        self.emit("connecting", None, None);
+        let mut connected = false;
+        let mut backoff_ms = RECONNECT_INITIAL_BACKOFF_MS;
+        let mut drain_deadline = None;
+        loop {
+            if drain_deadline.is_none() && !running.load(Ordering::SeqCst) {
+                drain_deadline = Some(Instant::now() + DRAIN_TIMEOUT);
+            }
+            if drain_deadline.is_some_and(|deadline| !connected || Instant::now() >= deadline) {
+                break;
+            }
+            match connection.recv_timeout(CONNECTION_POLL) {
+                Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => {
+                    connected = true;
+                    backoff_ms = RECONNECT_INITIAL_BACKOFF_MS;
+                    self.emit("connected", None, None);
+                }
+                Ok(Ok(Event::Outgoing(Outgoing::Disconnect))) => break,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => {
+                    connected = false;
+                    if drain_deadline.is_some() {
+                        break;
+                    }
+                    self.emit("disconnected", Some(e.to_string()), Some(backoff_ms));
+                    let retry_at = Instant::now() + Duration::from_millis(backoff_ms);
+                    while running.load(Ordering::SeqCst) && Instant::now() < retry_at {
+                        std::thread::sleep(CONNECTION_POLL.min(retry_at.saturating_duration_since(Instant::now())));
+                    }
+                    backoff_ms = (backoff_ms * 2).min(RECONNECT_MAX_BACKOFF_MS);
+                    if running.load(Ordering::SeqCst) {
+                        self.emit("connecting", None, None);
+                    }
+                }
+                Err(rumqttc::RecvTimeoutError::Timeout) => {}
+                Err(rumqttc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.emit("stopped", None, None);
+    }
+}
+
+/// 連線到 broker_url，把所有通道收到與送出的訊框發佈到 frame_topic (JSON)；
+/// 載入 DBC 時另外把每個訊號的數值以文字發佈到 signal_topic。
+/// 斷線時自動重新連線，連線狀態改變時送出 mqtt-status
+#[tauri::command]
+pub fn start_mqtt_publisher(
+    broker_url: String,
+    options: Option<MqttPublishOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let qos = check_options(&options)?;
+    let mqtt = mqtt_options(&broker_url, &options)?;
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    if app_state.mqtt_publisher.is_some() {
+        return Err("MQTT publisher already running".into());
+    }
+    let (client, connection) = Client::new(mqtt, REQUEST_QUEUE);
+    let running = Arc::new(AtomicBool::new(true));
+    let published = Arc::new(AtomicU64::new(0));
+    let dropped = Arc::new(AtomicU64::new(0));
+    let publisher = Publisher {
+        client,
+        min_interval: options.max_rate_hz.map(|hz| Duration::from_secs_f64(1.0 / hz)),
+        options,
+        qos,
+        topics: HashMap::new(),
+        published: published.clone(),
+        dropped: dropped.clone(),
+    };
+    let monitor = ConnectionMonitor {
+        broker: broker_url.clone(),
+        app_handle,
+        published,
+        dropped,
+    };
+    let frames = app_state.mqtt_feed.attach();
+    let publisher = {
+        let (state, running, broker) = (state.inner().clone(), running.clone(), broker_url.clone());
+        std::thread::spawn(move || {
+            let finished = supervisor::guard("mqtt-publisher", broker, || publisher.run(frames, running.clone()));
+            if finished.is_none() {
+                stop_after_panic(&state, &running);
+            }
+        })
+    };
+    let connection = {
+        let (state, running, broker) = (state.inner().clone(), running.clone(), broker_url.clone());
+        std::thread::spawn(move || {
+            let finished = supervisor::guard("mqtt-connection", broker, || monitor.run(connection, running.clone()));
+            if finished.is_none() {
+                stop_after_panic(&state, &running);
+            }
+        })
+    };
+    app_state.mqtt_publisher = Some(MqttPublisher {
+        broker: broker_url.clone(),
+        running,
+        publisher,
+        connection,
+    });
+    Ok(format!("MQTT publisher started for {}", broker_url))
+}
+
+/// 其中一個執行緒 panic 時停止另一個並移除發佈器，讓前端可以重新啟動
+fn stop_after_panic(state: &StateMutex, running: &Arc<AtomicBool>) {
+    running.store(false, Ordering::SeqCst);
+    if let Ok(mut app_state) = state.lock() {
+        if app_state.mqtt_publisher.as_ref().is_some_and(|p| Arc::ptr_eq(&p.running, running)) {
+            app_state.mqtt_publisher = None;
+            app_state.mqtt_feed.detach();
+        }
+    }
+}
+
+/// 停止接收新的訊框，送出已排入的發佈後中斷連線；最多等待 DRAIN_TIMEOUT
+#[tauri::command]
+pub async fn stop_mqtt_publisher(state: State<'_, Arc<StateMutex>>) -> Result<String, String> {
+    let publisher = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let publisher = app_state.mqtt_publisher.take().ok_or("MQTT publisher is not running")?;
+        app_state.mqtt_feed.detach();
+        publisher
+    };
+    run_blocking(move || {
+        publisher.running.store(false, Ordering::SeqCst);
+        let _ = publisher.publisher.join();
+        let _ = publisher.connection.join();
+        Ok(format!("MQTT publisher for {} stopped", publisher.broker))
+    })
+    .await
+}
@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::isotp::{IsoTpError, IsoTpLink, IsoTpOptions};
+use crate::{run_blocking, DeviceType, StateMutex};
+
+/// OBD-II 功能定址 (廣播) 請求 ID
+const FUNCTIONAL_REQUEST_ID: u32 = 0x7DF;
+/// ECU 回應 ID 0x7E8–0x7EF，對應的實體請求 ID 為回應 ID - 8
+const FIRST_RESPONSE_ID: u32 = 0x7E8;
+const RESPONSE_ID_COUNT: u32 = 8;
+const PHYSICAL_TO_RESPONSE_OFFSET: u32 = 8;
+const DEFAULT_TIMEOUT_MS: u64 = 200;
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+
+const MODE_CURRENT_DATA: u8 = 0x01;
+const MODE_VEHICLE_INFO: u8 = 0x09;
+const PID_VIN: u8 = 0x02;
+
+#[derive(Serialize)]
+pub struct ObdResponse {
+    pub responder_id: u32,
+    pub mode: u8,
+    pub pid: u8,
+    /// 回應中 PID 之後的資料位元組
+    pub raw: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<&'static str>,
+    /// 文字型資料 (例如 VIN)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// ECU 回覆負面回應時的 NRC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nrc: Option<u8>,
+}
+
+/// 常用 mode 01 PID 的名稱、換算與單位
+fn decode_current_data(pid: u8, data: &[u8]) -> Option<(&'static str, f64, &'static str)> {
+    let a = *data.first()? as f64;
+    let ab = || Some(a * 256.0 + *data.get(1)? as f64);
+    Some(match pid {
+        0x04 => ("engine_load", a * 100.0 / 255.0, "%"),
+        0x05 => ("coolant_temperature", a - 40.0, "°C"),
+        0x0C => ("engine_rpm", ab()? / 4.0, "rpm"),
+        0x0D => ("vehicle_speed", a, "km/h"),
+        0x0F => ("intake_air_temperature", a - 40.0, "°C"),
+        0x10 => ("maf_air_flow", ab()? / 100.0, "g/s"),
+        0x11 => ("throttle_position", a * 100.0 / 255.0, "%"),
+        _ => return None,
+    })
+}
+
+fn parse_response(responder_id: u32, mode: u8, pid: u8, response: &[u8]) -> Option<ObdResponse> {
+    let mut parsed = ObdResponse {
+        responder_id,
+        mode,
+        pid,
+        raw: Vec::new(),
+        name: None,
+        value: None,
+        unit: None,
+        text: None,
+        nrc: None,
+    };
+    match response {
+        [NEGATIVE_RESPONSE, sid, nrc, ..] if *sid == mode => parsed.nrc = Some(*nrc),
+        [sid, response_pid, data @ ..] if *sid == mode.wrapping_add(POSITIVE_RESPONSE_OFFSET) && *response_pid == pid => {
+            parsed.raw = data.to_vec();
+            if mode == MODE_CURRENT_DATA {
+                if let Some((name, value, unit)) = decode_current_data(pid, data) {
+                    parsed.name = Some(name);
+                    parsed.value = Some(value);
+                    parsed.unit = Some(unit);
+                }
+            } else if mode == MODE_VEHICLE_INFO && pid == PID_VIN {
+                // 第一個位元組為資料項數
+                parsed.name = Some("vin");
+                parsed.text = data.get(1..).map(|vin| String::from_utf8_lossy(vin).trim_matches('\0').to_string());
+            }
+        }
+        _ => return None,
+    }
+    Some(parsed)
+}
+
+/// 查詢 OBD-II PID。預設以 0x7DF 廣播並收集 0x7E8–0x7EF 的回應；
+/// request_id 指定實體位址 (例如 0x7E0) 時只等待對應的 ECU
+#[tauri::command]
+pub async fn obd_query(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    pid: u8,
+    mode: u8,
+    request_id: Option<u32>,
+    timeout_ms: Option<u64>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<ObdResponse>, IsoTpError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let key = {
+            let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key()
+        };
+        let request_id = request_id.unwrap_or(FUNCTIONAL_REQUEST_ID);
+        let response_ids: Vec<u32> = if request_id == FUNCTIONAL_REQUEST_ID {
+            (FIRST_RESPONSE_ID..FIRST_RESPONSE_ID + RESPONSE_ID_COUNT).collect()
+        } else {
+            vec![request_id + PHYSICAL_TO_RESPONSE_OFFSET]
+        };
+        // 先建立所有回應 ID 的連線再送出請求，避免漏掉回得很快的 ECU；
+        // 多訊框回應 (例如 VIN) 的 flow control 送往該 ECU 的實體請求 ID
+        let links = response_ids
+            .iter()
+            .map(|&rx_id| IsoTpLink::open(&state, key, channel, rx_id - PHYSICAL_TO_RESPONSE_OFFSET, rx_id, IsoTpOptions::default()))
+            .collect::<Result<Vec<_>, String>>()?;
+        let request = IsoTpLink::open(&state, key, channel, request_id, request_id, IsoTpOptions::default())?;
+        request.send(&[mode, pid])?;
+
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+        let mut responses: Vec<ObdResponse> = std::thread::scope(|scope| {
+            let handles: Vec<_> = links
+                .into_iter()
+                .map(|link| scope.spawn(move || link.receive(deadline).ok().flatten().map(|r| (link.rx_id(), r))))
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.join().ok().flatten())
+                .filter_map(|(responder_id, response)| parse_response(responder_id, mode, pid, &response))
+                .collect()
+        });
+        responses.sort_by_key(|r| r.responder_id);
+        Ok(responses)
+    })
+    .await
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::host_timestamp_us;
+use crate::receive::EventSink;
+use crate::{AppState, StateMutex};
+
+/// 長時間操作的種類；progress 的單位依種類而定
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationKind {
+    /// 已送出的訊框數；無限循環時沒有 total
+    Replay,
+    /// 已送出的位元組數
+    IsotpSend,
+    /// 已收到的位元組數；收到 first frame 後才有 total
+    IsotpReceive,
+    /// 已重組的訊息數，沒有 total
+    IsotpListener,
+    /// 已下載的位元組數
+    UdsDownload,
+    /// 已送出的訊框數
+    Benchmark,
+    /// 已送出的訊框數
+    Burst,
+    /// 已送出的訊框數
+    Sequence,
+}
+
+impl OperationKind {
+    fn name(self) -> &'static str {
+        match self {
+            OperationKind::Replay => "replay",
+            OperationKind::IsotpSend => "ISO-TP send",
+            OperationKind::IsotpReceive => "ISO-TP receive",
+            OperationKind::IsotpListener => "ISO-TP listener",
+            OperationKind::UdsDownload => "UDS download",
+            OperationKind::Benchmark => "benchmark",
+            OperationKind::Burst => "burst",
+            OperationKind::Sequence => "sequence",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct OperationProgress {
+    pub done: u64,
+    pub total: Option<u64>,
+}
+
+/// 由操作本身更新的進度；total 為 0 表示未知
+#[derive(Default)]
+struct Counters {
+    done: AtomicU64,
+    total: AtomicU64,
+}
+
+impl Counters {
+    fn progress(&self) -> OperationProgress {
+        OperationProgress {
+            done: self.done.load(Ordering::Relaxed),
+            total: Some(self.total.load(Ordering::Relaxed)).filter(|&total| total > 0),
+        }
+    }
+}
+
+struct Operation {
+    kind: OperationKind,
+    key: (u32, u32),
+    channel: u32,
+    started_at_us: u64,
+    running: Arc<AtomicBool>,
+    counters: Arc<Counters>,
+}
+
+impl Operation {
+    fn info(&self, operation_id: u32) -> OperationInfo {
+        OperationInfo {
+            operation_id,
+            kind: self.kind,
+            dev_type: self.key.0,
+            dev_index: self.key.1,
+            channel: self.channel,
+            started_at_us: self.started_at_us,
+            progress: self.counters.progress(),
+            cancel_requested: !self.running.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// 執行中的長時間操作；操作結束時自行移除，取消後到真正停止之前仍會列出
+#[derive(Default)]
+pub(crate) struct Operations {
+    next_id: u32,
+    active: HashMap<u32, Operation>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OperationInfo {
+    pub operation_id: u32,
+    pub kind: OperationKind,
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    /// 開始時的主機時間 (UNIX epoch 起算的微秒)
+    pub started_at_us: u64,
+    pub progress: OperationProgress,
+    /// 已要求取消，操作會在下一個安全點停止
+    pub cancel_requested: bool,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationStatus {
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// operation-finished 事件；每個操作剛好送出一次
+#[derive(Serialize, Clone, Debug)]
+pub struct OperationFinished {
+    pub operation_id: u32,
+    pub kind: OperationKind,
+    pub status: OperationStatus,
+    pub error: Option<String>,
+    pub progress: OperationProgress,
+    pub duration_ms: f64,
+}
+
+/// 操作持有的一端。running 即取消權杖：cancel_operation 清除後，操作在下一個安全點
+/// (訊框、區塊或步驟之間) 停止，最後以 finish 回報結果
+pub struct OperationHandle {
+    id: u32,
+    kind: OperationKind,
+    running: Arc<AtomicBool>,
+    counters: Arc<Counters>,
+    started: Instant,
+}
+
+impl OperationHandle {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn running(&self) -> &Arc<AtomicBool> {
+        &self.running
+    }
+
+    pub fn set_progress(&self, done: u64) {
+        self.counters.done.store(done, Ordering::Relaxed);
+    }
+
+    pub fn set_total(&self, total: u64) {
+        self.counters.total.store(total, Ordering::Relaxed);
+    }
+
+    /// 送出 operation-started；命令要到操作結束才回傳時，前端由此取得 operation_id
+    pub fn announce(&self, state: &StateMutex, events: &impl EventSink) {
+        let info = state.lock().ok().and_then(|app_state| app_state.operations.active.get(&self.id).map(|op| op.info(self.id)));
+        if let Some(info) = info {
+            events.emit_event("operation-started", info);
+        }
+    }
+
+    /// 從登錄表移除並送出 operation-finished。error 為 Some 時為 failed，否則權杖被清除時為 cancelled。
+    /// 呼叫時不可持有 state 鎖
+    pub fn finish(self, state: &StateMutex, events: &impl EventSink, error: Option<String>) -> OperationStatus {
+        if let Ok(mut app_state) = state.lock() {
+            if app_state.operations.active.get(&self.id).is_some_and(|op| Arc::ptr_eq(&op.running, &self.running)) {
+                app_state.operations.active.remove(&self.id);
+            }
+        }
+        let status = match (&error, self.running.load(Ordering::SeqCst)) {
+            (Some(_), _) => OperationStatus::Failed,
+            (None, false) => OperationStatus::Cancelled,
+            (None, true) => OperationStatus::Completed,
+        };
+        events.emit_event(
+            "operation-finished",
+            OperationFinished {
+                operation_id: self.id,
+                kind: self.kind,
+                status,
+                error,
+                progress: self.counters.progress(),
+                duration_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+            },
+        );
+        status
+    }
+}
+
+impl AppState {
+    /// 登錄新的操作；回傳的 operation_id 同時是各操作原有的 ID (burst_id、sequence_id 等)
+    pub(crate) fn start_operation(&mut self, kind: OperationKind, key: (u32, u32), channel: u32, total: Option<u64>) -> OperationHandle {
+        self.operations.next_id += 1;
+        let id = self.operations.next_id;
+        let handle = OperationHandle {
+            id,
+            kind,
+            running: Arc::new(AtomicBool::new(true)),
+            counters: Arc::new(Counters::default()),
+            started: Instant::now(),
+        };
+        handle.set_total(total.unwrap_or(0));
+        self.operations.active.insert(
+            id,
+            Operation {
+                kind,
+                key,
+                channel,
+                started_at_us: host_timestamp_us(),
+                running: handle.running.clone(),
+                counters: handle.counters.clone(),
+            },
+        );
+        handle
+    }
+
+    pub fn list_operations(&self) -> Vec<OperationInfo> {
+        let mut operations: Vec<OperationInfo> = self.operations.active.iter().map(|(&id, op)| op.info(id)).collect();
+        operations.sort_by_key(|op| op.operation_id);
+        operations
+    }
+
+    /// 清除操作的取消權杖；kind 指定時只取消該種類的操作。回傳被取消的種類
+    pub fn cancel_operation(&self, operation_id: u32, kind: Option<OperationKind>) -> Option<OperationKind> {
+        let op = self.operations.active.get(&operation_id).filter(|op| kind.is_none_or(|kind| kind == op.kind))?;
+        op.running.store(false, Ordering::SeqCst);
+        Some(op.kind)
+    }
+
+    pub(crate) fn operations_running(&self, kind: OperationKind) -> usize {
+        self.operations.active.values().filter(|op| op.kind == kind).count()
+    }
+
+    /// 取消所有操作，回傳說明文字 (state 鎖恢復時使用)
+    pub(crate) fn cancel_all_operations(&self) -> Vec<String> {
+        self.list_operations()
+            .into_iter()
+            .filter_map(|op| self.cancel_operation(op.operation_id, None).map(|kind| format!("{} {}", kind.name(), op.operation_id)))
+            .collect()
+    }
+}
+
+/// 執行中的長時間操作，依 operation_id 排序
+#[tauri::command]
+pub fn list_operations(state: State<Arc<StateMutex>>) -> Result<Vec<OperationInfo>, String> {
+    Ok(state.lock().map_err(|_| "Failed to lock state")?.list_operations())
+}
+
+/// 要求操作在下一個安全點停止；停止後送出 status 為 cancelled 的 operation-finished
+#[tauri::command]
+pub fn cancel_operation(operation_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let kind = app_state
+        .cancel_operation(operation_id, None)
+        .ok_or_else(|| format!("operation {} not found", operation_id))?;
+    Ok(format!("{} {} cancelled", kind.name(), operation_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, serde_json::Value)>>>);
+
+    impl EventSink for Recorder {
+        fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) -> bool {
+            self.0.lock().unwrap().push((event.to_string(), serde_json::to_value(payload).unwrap()));
+            true
+        }
+    }
+
+    #[test]
+    fn operations_are_listed_in_id_order_with_their_progress() {
+        let mut app_state = AppState::default();
+        let burst = app_state.start_operation(OperationKind::Burst, (4, 0), 1, Some(100));
+        let listener = app_state.start_operation(OperationKind::IsotpListener, (4, 0), 0, None);
+        burst.set_progress(40);
+        listener.set_progress(3);
+
+        let listed: Vec<_> = app_state
+            .list_operations()
+            .iter()
+            .map(|op| (op.operation_id, op.kind, op.channel, op.progress.done, op.progress.total, op.cancel_requested))
+            .collect();
+        assert_eq!(
+            listed,
+            [(1, OperationKind::Burst, 1, 40, Some(100), false), (2, OperationKind::IsotpListener, 0, 3, None, false)]
+        );
+        assert_eq!(app_state.operations_running(OperationKind::Burst), 1);
+        assert_eq!(serde_json::to_value(OperationKind::IsotpSend).unwrap(), "isotp-send");
+    }
+
+    #[test]
+    fn unknown_ids_and_other_kinds_are_not_cancelled() {
+        let mut app_state = AppState::default();
+        let burst = app_state.start_operation(OperationKind::Burst, (4, 0), 0, None);
+        assert_eq!(app_state.cancel_operation(burst.id() + 1, None), None);
+        assert_eq!(app_state.cancel_operation(burst.id(), Some(OperationKind::Replay)), None);
+        assert!(burst.running().load(Ordering::SeqCst));
+
+        assert_eq!(app_state.cancel_operation(burst.id(), Some(OperationKind::Burst)), Some(OperationKind::Burst));
+        assert!(!burst.running().load(Ordering::SeqCst));
+        // 取消後到操作真正結束之前仍會列出
+        assert!(app_state.list_operations()[0].cancel_requested);
+    }
+
+    #[test]
+    fn cancelling_everything_names_each_operation() {
+        let mut app_state = AppState::default();
+        app_state.start_operation(OperationKind::Burst, (4, 0), 0, None);
+        app_state.start_operation(OperationKind::UdsDownload, (4, 0), 1, Some(4096));
+        assert_eq!(app_state.cancel_all_operations(), ["burst 1", "UDS download 2"]);
+        assert!(app_state.list_operations().iter().all(|op| op.cancel_requested));
+        assert!(AppState::default().cancel_all_operations().is_empty());
+    }
+
+    #[test]
+    fn finish_reports_completed_cancelled_or_failed_once_and_unregisters() {
+        let state = StateMutex::default();
+        let events = Recorder::default();
+        let start = |kind| state.lock().unwrap().start_operation(kind, (4, 0), 0, None);
+        let completed = start(OperationKind::Sequence);
+        completed.announce(&state, &events);
+        let cancelled = start(OperationKind::Replay);
+        let failed = start(OperationKind::IsotpSend);
+        state.lock().unwrap().cancel_operation(cancelled.id(), None);
+
+        assert_eq!(completed.finish(&state, &events, None), OperationStatus::Completed);
+        assert_eq!(cancelled.finish(&state, &events, None), OperationStatus::Cancelled);
+        assert_eq!(failed.finish(&state, &events, Some("no flow control".into())), OperationStatus::Failed);
+        assert!(state.lock().unwrap().list_operations().is_empty());
+
+        let events = events.0.lock().unwrap();
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["operation-started", "operation-finished", "operation-finished", "operation-finished"]);
+        assert_eq!((events[0].1["operation_id"].as_u64(), events[0].1["kind"].as_str()), (Some(1), Some("sequence")));
+        let statuses: Vec<_> = events[1..].iter().map(|(_, payload)| (payload["status"].clone(), payload["error"].clone())).collect();
+        assert_eq!(
+            statuses,
+            [
+                (serde_json::json!("completed"), serde_json::Value::Null),
+                (serde_json::json!("cancelled"), serde_json::Value::Null),
+                (serde_json::json!("failed"), serde_json::json!("no flow control")),
+            ]
+        );
+    }
+}
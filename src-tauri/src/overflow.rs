@@ -0,0 +1,153 @@
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::controlcan::{ERR_CAN_BUFFER_OVERFLOW, ERR_CAN_OVERFLOW};
+use crate::frame::host_timestamp_us;
+use crate::ring_buffer::FrameRing;
+use crate::stats::ChannelCounters;
+use crate::{CanInterface, StateMutex};
+
+/// 連續這麼多次讀滿整批時視為驅動緩衝已溢出
+const FULL_READS_THRESHOLD: u32 = 3;
+/// 讀取 VCI_ReadErrInfo 的間隔；讀取會清除錯誤，間隔即為遺失範圍的上限
+const ERR_INFO_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowSource {
+    /// CAN 控制器內部 FIFO 溢出 (ERR_CAN_OVERFLOW)
+    ControllerFifo,
+    /// 驅動程式接收緩衝溢出 (ERR_CAN_BUFFER_OVERFLOW)
+    DriverBuffer,
+    /// 連續多次 VCI_Receive 讀滿整批，讀取速度跟不上
+    FullReads,
+}
+
+/// can-overflow 事件；window_start_us 到 window_end_us (主機時間) 之間可能有訊框遺失
+#[derive(Serialize, Clone)]
+pub struct OverflowEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub source: OverflowSource,
+    pub window_start_us: u64,
+    pub window_end_us: u64,
+    pub loss_window_ms: f64,
+    pub rx_per_sec: f64,
+    pub buffer_fill: usize,
+    pub buffer_capacity: usize,
+    /// 此通道累計的溢出次數
+    pub overflows: u64,
+}
+
+/// 由接收執行緒呼叫，從錯誤暫存器與讀取批次兩方面偵測接收溢出
+pub struct OverflowDetector {
+    key: (u32, u32),
+    channel: u32,
+    counters: Arc<ChannelCounters>,
+    full_reads: u32,
+    /// 最後一次讀到未滿批次 (已追上驅動緩衝) 的時間
+    caught_up_us: u64,
+    last_err_check: Instant,
+    last_err_check_us: u64,
+    /// 估算接收速率用的上一次 rx_frames 與時間
+    rate_rx: u64,
+    rate_since: Instant,
+    rx_per_sec: f64,
+}
+
+impl OverflowDetector {
+    pub fn new(key: (u32, u32), channel: u32, counters: Arc<ChannelCounters>) -> Self {
+        let now_us = host_timestamp_us();
+        Self {
+            key,
+            channel,
+            full_reads: 0,
+            caught_up_us: now_us,
+            last_err_check: Instant::now(),
+            last_err_check_us: now_us,
+            rate_rx: counters.rx_frames.load(Ordering::Relaxed),
+            rate_since: Instant::now(),
+            rx_per_sec: 0.0,
+            counters,
+        }
+    }
+
+    /// 每次讀到訊框後呼叫；full 表示這次讀滿了整批
+    pub fn record_read(&mut self, full: bool, frame_buffer: &Mutex<FrameRing>) -> Option<OverflowEvent> {
+        if !full {
+            self.full_reads = 0;
+            self.caught_up_us = host_timestamp_us();
+            return None;
+        }
+        self.full_reads += 1;
+        if self.full_reads != FULL_READS_THRESHOLD {
+            return None;
+        }
+        let since = self.caught_up_us;
+        Some(self.report(OverflowSource::FullReads, since, frame_buffer))
+    }
+
+    /// 每隔 ERR_INFO_INTERVAL 讀取一次錯誤資訊；後端不支援或讀取失敗時略過
+    pub fn poll_err_info(&mut self, state: &Arc<StateMutex>, frame_buffer: &Mutex<FrameRing>) -> Option<OverflowEvent> {
+        if self.last_err_check.elapsed() < ERR_INFO_INTERVAL {
+            return None;
+        }
+        let since = self.last_err_check_us;
+        self.last_err_check = Instant::now();
+        self.last_err_check_us = host_timestamp_us();
+        self.update_rate();
+        // 只在取出後端時短暫持有 state 鎖
+        let can_lib: Arc<dyn CanInterface> = state.lock().ok()?.backend()?;
+        let err_code = can_lib.read_err_info(self.key.0, self.key.1, self.channel).ok()?.err_code;
+        if err_code != 0 {
+            self.counters.bus_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        let source = if err_code & ERR_CAN_BUFFER_OVERFLOW != 0 {
+            OverflowSource::DriverBuffer
+        } else if err_code & ERR_CAN_OVERFLOW != 0 {
+            OverflowSource::ControllerFifo
+        } else {
+            return None;
+        };
+        Some(self.report(source, since, frame_buffer))
+    }
+
+    fn update_rate(&mut self) {
+        let rx = self.counters.rx_frames.load(Ordering::Relaxed);
+        let seconds = self.rate_since.elapsed().as_secs_f64();
+        if seconds > 0.0 {
+            self.rx_per_sec = rx.saturating_sub(self.rate_rx) as f64 / seconds;
+        }
+        self.rate_rx = rx;
+        self.rate_since = Instant::now();
+    }
+
+    fn report(&mut self, source: OverflowSource, since_us: u64, frame_buffer: &Mutex<FrameRing>) -> OverflowEvent {
+        let overflows = self.counters.overflows.fetch_add(1, Ordering::Relaxed) + 1;
+        let now_us = host_timestamp_us();
+        let (buffer_fill, buffer_capacity) = frame_buffer
+            .lock()
+            .map(|ring| {
+                let status = ring.status();
+                (status.len, status.capacity)
+            })
+            .unwrap_or_default();
+        OverflowEvent {
+            dev_type: self.key.0,
+            dev_index: self.key.1,
+            channel: self.channel,
+            source,
+            window_start_us: since_us,
+            window_end_us: now_us,
+            loss_window_ms: now_us.saturating_sub(since_us) as f64 / 1000.0,
+            rx_per_sec: self.rx_per_sec,
+            buffer_fill,
+            buffer_capacity,
+            overflows,
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::busoff;
+use crate::dbc::{Dbc, OutOfRange};
+use crate::e2e::E2eSpec;
+use crate::frame::{FrameInput, Provenance};
+use crate::supervisor;
+use crate::{heartbeat, AppState, DeviceType, StateMutex, VciCanObj};
+
+/// 等待下一次傳送時的分段睡眠長度，讓停止能即時生效
+const STOP_POLL_MS: u64 = 10;
+/// list_periodic_tasks 回報的實際週期數
+const RECENT_PERIODS: usize = 32;
+
+/// 週期傳送的內容；Signals 每次傳送時依目前的 DBC 重新編碼，更新訊號值會在下個週期生效
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PeriodicPayload {
+    Raw { id: u32, extended: bool, data: Vec<u8> },
+    Signals { message_name: String, values: HashMap<String, f64>, out_of_range: OutOfRange },
+}
+
+impl PeriodicPayload {
+    /// 不需要 AppState 的檢查：Raw 能組成訊框、Signals 能以 dbc 編碼
+    pub(crate) fn check(&self, dbc: Option<&Dbc>) -> Result<(), String> {
+        match self {
+            PeriodicPayload::Raw { id, extended, data } => FrameInput {
+                id: *id,
+                extended: Some(*extended),
+                remote: false,
+                dlc: None,
+                data: data.clone(),
+                padding: None,
+            }
+            .checked("frame")
+            .map(|_| ()),
+            PeriodicPayload::Signals { message_name, values, out_of_range } => {
+                let dbc = dbc.ok_or("No DBC loaded")?;
+                dbc.message_by_name(message_name)?.encode(values, *out_of_range).map(|_| ())
+            }
+        }
+    }
+
+    fn build(&self, app_state: &AppState) -> Result<VciCanObj, String> {
+        match self {
+            PeriodicPayload::Raw { id, extended, data } => FrameInput {
+                id: *id,
+                extended: Some(*extended),
+                remote: false,
+                dlc: None,
+                data: data.clone(),
+                padding: None,
+            }
+            .checked("frame"),
+            PeriodicPayload::Signals { message_name, values, out_of_range } => {
+                let dbc = app_state.loaded_dbc()?;
+                let message = dbc.message_by_name(message_name)?;
+                let data = message.encode(values, *out_of_range)?;
+                Ok(message.to_can_obj(&data))
+            }
+        }
+    }
+}
+
+/// 週期任務的用途。TesterPresent 在同一組 ISO-TP ID 有傳輸進行時略過該週期，不存入設定檔，
+/// 傳送失敗時改以 tester-present-stopped 事件通知
+#[derive(Clone, Debug)]
+pub(crate) enum TaskRole {
+    User,
+    TesterPresent { tx_id: u32, activity: Arc<AtomicUsize> },
+}
+
+impl TaskRole {
+    pub(crate) fn on_hold(&self) -> bool {
+        match self {
+            TaskRole::User => false,
+            TaskRole::TesterPresent { activity, .. } => activity.load(Ordering::SeqCst) > 0,
+        }
+    }
+}
+
+pub struct PeriodicTask {
+    key: (u32, u32),
+    channel: u32,
+    interval_ms: u64,
+    payload: Arc<Mutex<PeriodicPayload>>,
+    /// 送出的訊框是否回送到 can-data 事件流
+    echo: bool,
+    /// 每個週期寫入的 E2E 計數器與 CRC，可在執行中更新
+    e2e: Arc<Mutex<Option<E2eSpec>>>,
+    running: Arc<AtomicBool>,
+    role: TaskRole,
+    /// 最近幾次相鄰 VCI_Transmit 呼叫開始時間的間隔 (微秒)，最新的在最後
+    periods: Arc<Mutex<VecDeque<u64>>>,
+}
+
+impl PeriodicTask {
+    pub(crate) fn key(&self) -> (u32, u32) {
+        self.key
+    }
+
+    pub(crate) fn channel(&self) -> u32 {
+        self.channel
+    }
+
+    pub(crate) fn interval_ms(&self) -> u64 {
+        self.interval_ms
+    }
+
+    pub(crate) fn role(&self) -> &TaskRole {
+        &self.role
+    }
+
+    pub(crate) fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Serialize)]
+pub struct PeriodicTaskInfo {
+    pub task_id: u32,
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub echo: bool,
+    pub e2e: Option<E2eSpec>,
+    pub interval_ms: u64,
+    pub id: Option<u32>,
+    pub message_name: Option<String>,
+    /// 最近幾個週期實際量到的間隔 (微秒)，最新的在最後
+    pub recent_periods_us: Vec<u64>,
+    /// recent_periods_us 與 interval_ms 的最大差距；尚未送出兩次時為 None
+    pub max_jitter_us: Option<u64>,
+}
+
+/// 設定檔中的一個週期訊框，重新建立時傳給 spawn_task
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SavedPeriodic {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub interval_ms: u64,
+    pub echo: bool,
+    pub e2e: Option<E2eSpec>,
+    pub payload: PeriodicPayload,
+}
+
+#[derive(Serialize, Clone)]
+struct PeriodicErrorEvent {
+    task_id: u32,
+    message: String,
+}
+
+/// 驗證內容能組成訊框後登記並啟動傳送執行緒，回傳 task_id
+pub(crate) fn spawn_task(
+    app_handle: tauri::AppHandle,
+    state: &Arc<StateMutex>,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    interval_ms: u64,
+    payload: PeriodicPayload,
+    echo: Option<bool>,
+    e2e: Option<E2eSpec>,
+    role: TaskRole,
+) -> Result<u32, String> {
+    if interval_ms == 0 {
+        return Err("interval_ms must be greater than 0".into());
+    }
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+    let can_obj = payload.build(&app_state)?;
+    if let Some(e2e) = &e2e {
+        e2e.check_fits(can_obj.data_len as usize)?;
+    }
+    app_state.next_periodic_id += 1;
+    let task_id = app_state.next_periodic_id;
+    let payload = Arc::new(Mutex::new(payload));
+    let running = Arc::new(AtomicBool::new(true));
+    let echo = echo.unwrap_or(true);
+    let e2e = Arc::new(Mutex::new(e2e));
+    let periods = Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_PERIODS)));
+    let runtime = app_state.channel_runtime(key, channel);
+    app_state.periodic_tasks.insert(
+        task_id,
+        PeriodicTask {
+            key,
+            channel,
+            interval_ms,
+            payload: payload.clone(),
+            echo,
+            e2e: e2e.clone(),
+            running: running.clone(),
+            role: role.clone(),
+            periods: periods.clone(),
+        },
+    );
+    let interval = Duration::from_millis(interval_ms);
+    let pulse = app_state.pulses.register("periodic", format!("task {}", task_id), heartbeat::STALL_MARGIN + interval);
+    drop(app_state);
+
+    let state = state.clone();
+    std::thread::spawn(move || {
+        let finished = supervisor::guard("periodic", format!("task {}", task_id), || {
+            let mut next = Instant::now();
+            let mut counter = 0u16;
+            let mut last_call_us = None;
+            while running.load(Ordering::SeqCst) {
+                pulse.beat();
+                if role.on_hold() || runtime.reconfiguring.load(Ordering::SeqCst) {
+                    next += interval;
+                    while running.load(Ordering::SeqCst) && Instant::now() < next {
+                        std::thread::sleep((next - Instant::now()).min(Duration::from_millis(STOP_POLL_MS)));
+                    }
+                    continue;
+                }
+                let e2e = e2e.lock().ok().and_then(|e2e| e2e.clone());
+                // 只在組訊框時持有 state 鎖，VCI_Transmit 在鎖外呼叫
+                let prepared = match (state.lock(), payload.lock()) {
+                    (Ok(mut app_state), Ok(payload)) => payload
+                        .build(&app_state)
+                        .and_then(|can_obj| Ok((can_obj, app_state.tx_path(key, channel)?.with_provenance(Provenance::TxPeriodic)))),
+                    _ => Err("Failed to lock state".to_string()),
+                };
+                let result = prepared.and_then(|(mut can_obj, tx_path)| {
+                    if let Some(e2e) = &e2e {
+                        e2e.apply(&mut can_obj.data[..can_obj.data_len as usize], counter);
+                        counter = e2e.next_counter(counter);
+                    }
+                    tx_path.transmit_timed(&[can_obj], echo)
+                });
+                if let Some(call_start_us) = result.as_ref().ok().and_then(|timings| timings.first()).map(|t| t.call_start_us) {
+                    if let (Some(last), Ok(mut periods)) = (last_call_us, periods.lock()) {
+                        if periods.len() == RECENT_PERIODS {
+                            periods.pop_front();
+                        }
+                        periods.push_back(call_start_us - last);
+                    }
+                    last_call_us = Some(call_start_us);
+                }
+                if let Err(message) = result {
+                    // bus-off 自動恢復或重新初始化期間略過這個週期，恢復後依原本的時間表繼續
+                    if !busoff::transmit_on_hold(&state, key, channel) && !runtime.reconfiguring.load(Ordering::SeqCst) {
+                        let event = match role {
+                            TaskRole::User => "periodic-error",
+                            TaskRole::TesterPresent { .. } => "tester-present-stopped",
+                        };
+                        let _ = app_handle.emit(event, PeriodicErrorEvent { task_id, message });
+                        break;
+                    }
+                }
+                // 以固定的時間表排程，傳送耗時不會累積成漂移；落後太多時從現在重新起算
+                next += interval;
+                let now = Instant::now();
+                if next < now {
+                    next = now;
+                }
+                while running.load(Ordering::SeqCst) && Instant::now() < next {
+                    std::thread::sleep((next - Instant::now()).min(Duration::from_millis(STOP_POLL_MS)));
+                }
+            }
+        });
+        if finished.is_none() {
+            running.store(false, Ordering::SeqCst);
+        }
+        if let Ok(mut app_state) = state.lock() {
+            if app_state.periodic_tasks.get(&task_id).is_some_and(|t| Arc::ptr_eq(&t.running, &running)) {
+                app_state.periodic_tasks.remove(&task_id);
+            }
+        }
+    });
+    Ok(task_id)
+}
+
+#[tauri::command]
+pub fn start_periodic(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    id: u32,
+    extended: Option<bool>,
+    data: Vec<u8>,
+    interval_ms: u64,
+    echo: Option<bool>,
+    e2e: Option<E2eSpec>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    let frame = FrameInput {
+        id,
+        extended,
+        remote: false,
+        dlc: None,
+        data,
+        padding: None,
+    };
+    let extended = frame.checked("frame")?.extern_flag != 0;
+    let payload = PeriodicPayload::Raw { id, extended, data: frame.data };
+    spawn_task(app_handle, state.inner(), dev_type, dev_index, channel, interval_ms, payload, echo, e2e, TaskRole::User)
+}
+
+/// 以 DBC 訊息名稱登記週期訊框；未指定的訊號使用初始值
+#[tauri::command]
+pub fn start_periodic_signals(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    message_name: String,
+    signals: Option<HashMap<String, f64>>,
+    interval_ms: u64,
+    out_of_range: Option<OutOfRange>,
+    echo: Option<bool>,
+    e2e: Option<E2eSpec>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    let payload = PeriodicPayload::Signals {
+        message_name,
+        values: signals.unwrap_or_default(),
+        out_of_range: out_of_range.unwrap_or_default(),
+    };
+    spawn_task(app_handle, state.inner(), dev_type, dev_index, channel, interval_ms, payload, echo, e2e, TaskRole::User)
+}
+
+/// 更新執行中週期訊框的部分訊號，下個週期生效
+#[tauri::command]
+pub fn update_periodic_signals(
+    task_id: u32,
+    signals: HashMap<String, f64>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let task = app_state
+        .periodic_tasks
+        .get(&task_id)
+        .ok_or_else(|| format!("periodic task {} not found", task_id))?;
+    let mut payload = task.payload.lock().map_err(|_| "Failed to lock periodic task")?;
+    let mut updated = payload.clone();
+    let PeriodicPayload::Signals { values, .. } = &mut updated else {
+        return Err(format!("periodic task {} is not a DBC message", task_id));
+    };
+    values.extend(signals);
+    updated.build(&app_state)?;
+    *payload = updated;
+    Ok(format!("periodic task {} updated", task_id))
+}
+
+/// 更新或取消執行中週期訊框的 E2E 設定，下個週期生效；計數器沿用目前的值
+#[tauri::command]
+pub fn set_periodic_e2e(task_id: u32, e2e: Option<E2eSpec>, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let task = app_state
+        .periodic_tasks
+        .get(&task_id)
+        .ok_or_else(|| format!("periodic task {} not found", task_id))?;
+    if let Some(e2e) = &e2e {
+        let payload = task.payload.lock().map_err(|_| "Failed to lock periodic task")?;
+        e2e.check_fits(payload.build(&app_state)?.data_len as usize)?;
+    }
+    *task.e2e.lock().map_err(|_| "Failed to lock periodic task")? = e2e;
+    Ok(format!("periodic task {} E2E updated", task_id))
+}
+
+#[tauri::command]
+pub fn stop_periodic(task_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let task = app_state
+        .periodic_tasks
+        .remove(&task_id)
+        .ok_or_else(|| format!("periodic task {} not found", task_id))?;
+    task.running.store(false, Ordering::SeqCst);
+    Ok(format!("periodic task {} stopped", task_id))
+}
+
+#[tauri::command]
+pub fn list_periodic_tasks(state: State<Arc<StateMutex>>) -> Result<Vec<PeriodicTaskInfo>, String> {
+    Ok(state.lock().map_err(|_| "Failed to lock state")?.periodic_task_infos())
+}
+
+impl AppState {
+    /// 依 task_id 排序的所有使用者週期訊框定義
+    pub(crate) fn saved_periodic_tasks(&self) -> Vec<SavedPeriodic> {
+        let mut tasks: Vec<(u32, &PeriodicTask)> = self
+            .periodic_tasks
+            .iter()
+            .filter(|(_, task)| matches!(task.role, TaskRole::User))
+            .map(|(&id, task)| (id, task))
+            .collect();
+        tasks.sort_by_key(|&(id, _)| id);
+        tasks
+            .into_iter()
+            .filter_map(|(_, task)| {
+                Some(SavedPeriodic {
+                    dev_type: task.key.0,
+                    dev_index: task.key.1,
+                    channel: task.channel,
+                    interval_ms: task.interval_ms,
+                    echo: task.echo,
+                    e2e: task.e2e.lock().ok()?.clone(),
+                    payload: task.payload.lock().ok()?.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// 執行中的 TesterPresent 任務
+    pub(crate) fn tester_present_task(&self) -> Option<(u32, &PeriodicTask)> {
+        self.periodic_tasks
+            .iter()
+            .find(|(_, task)| matches!(task.role, TaskRole::TesterPresent { .. }))
+            .map(|(&task_id, task)| (task_id, task))
+    }
+
+    /// 停止所有週期訊框；執行緒在下一次等待時結束
+    pub(crate) fn stop_periodic_tasks(&mut self) {
+        for (_, task) in self.periodic_tasks.drain() {
+            task.running.store(false, Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn periodic_task_infos(&self) -> Vec<PeriodicTaskInfo> {
+        let mut tasks: Vec<PeriodicTaskInfo> = self
+            .periodic_tasks
+            .iter()
+            .map(|(&task_id, task)| {
+                let payload = task.payload.lock().map(|p| p.clone()).ok();
+                let recent_periods_us: Vec<u64> = task.periods.lock().map(|p| p.iter().copied().collect()).unwrap_or_default();
+                let max_jitter_us = recent_periods_us.iter().map(|&period| period.abs_diff(task.interval_ms * 1000)).max();
+                PeriodicTaskInfo {
+                    task_id,
+                    dev_type: task.key.0,
+                    dev_index: task.key.1,
+                    channel: task.channel,
+                    echo: task.echo,
+                    e2e: task.e2e.lock().ok().and_then(|e2e| e2e.clone()),
+                    interval_ms: task.interval_ms,
+                    id: match &payload {
+                        Some(PeriodicPayload::Raw { id, .. }) => Some(*id),
+                        _ => None,
+                    },
+                    message_name: match payload {
+                        Some(PeriodicPayload::Signals { message_name, .. }) => Some(message_name),
+                        _ => None,
+                    },
+                    recent_periods_us,
+                    max_jitter_us,
+                }
+            })
+            .collect();
+        tasks.sort_by_key(|t| t.task_id);
+        tasks
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{invalid_argument, read_device_info, run_blocking, AppState, DeviceInfo, DeviceType, StateMutex, DEFAULT_DEV_TYPE};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProbeStatus {
+    /// 列舉中找不到裝置
+    NotPresent,
+    /// 裝置在列舉中但 VCI_OpenDevice 失敗，通常是 CANPro 等其他程式正在使用
+    OpenFailed,
+    /// 本程式已開啟此裝置；不會再次開啟或關閉
+    AlreadyOpen,
+    Ok,
+}
+
+#[derive(Serialize)]
+pub struct ProbeResult {
+    pub status: ProbeStatus,
+    pub dev_type: u32,
+    pub dev_index: Option<u32>,
+    pub board_info: Option<DeviceInfo>,
+    /// 驅動回報的錯誤
+    pub error: Option<String>,
+}
+
+impl AppState {
+    /// 暫時開啟裝置、讀取板卡資訊後立即關閉，不登記到 devices
+    pub fn probe_device(&mut self, dev_type: u32, dev_index: Option<u32>, serial: Option<&str>) -> ProbeResult {
+        let found = self.enumerate_devices();
+        let listed = |dev_index: u32| found.as_ref().ok()?.iter().find(|d| d.index as u32 == dev_index).cloned();
+        let dev_index = match (dev_index, serial) {
+            (Some(dev_index), _) => Some(dev_index),
+            (None, Some(serial)) => found
+                .as_ref()
+                .ok()
+                .and_then(|found| found.iter().find(|d| d.serial_number == serial))
+                .map(|d| d.index as u32),
+            (None, None) => None,
+        };
+        let result = |status, board_info, error| ProbeResult {
+            status,
+            dev_type,
+            dev_index,
+            board_info,
+            error,
+        };
+        let Some(dev_index) = dev_index else {
+            return result(ProbeStatus::NotPresent, None, found.err());
+        };
+        if let Some(device) = self.devices.get(&(dev_type, dev_index)) {
+            return result(ProbeStatus::AlreadyOpen, device.board_info.clone(), None);
+        }
+        let can_lib = match self.try_library() {
+            Ok(can_lib) => can_lib,
+            Err(error_message) => return result(ProbeStatus::OpenFailed, None, Some(error_message)),
+        };
+        if let Err(error_message) = can_lib.open(dev_type, dev_index) {
+            // 列舉成功但沒有此 index 時是裝置不存在；否則多半是被其他程式開啟
+            let status = match &found {
+                Ok(_) if listed(dev_index).is_none() => ProbeStatus::NotPresent,
+                _ => ProbeStatus::OpenFailed,
+            };
+            return result(status, listed(dev_index), Some(error_message));
+        }
+        let board_info = read_device_info(can_lib.as_ref(), dev_type, dev_index).or_else(|| listed(dev_index));
+        can_lib.close(dev_type, dev_index);
+        result(ProbeStatus::Ok, board_info, None)
+    }
+}
+
+/// 開啟裝置前確認裝置是否存在、可以開啟而且沒有被其他程式佔用。
+/// 以 dev_index 或 serial 指定；開啟後立即關閉，可以在選擇裝置的對話框中重複呼叫
+#[tauri::command]
+pub async fn probe_device(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    serial: Option<String>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<ProbeResult, String> {
+    if dev_index.is_none() && serial.is_none() {
+        return Err(invalid_argument("dev_index", "dev_index or serial is required"));
+    }
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        Ok(app_state.probe_device(dev_type.unwrap_or(DEFAULT_DEV_TYPE).code(), dev_index, serial.as_deref()))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::{CanInterface, VciBoardInfo};
+
+    const USBCAN2: u32 = 4;
+
+    fn listed(serial: &[u8; 8]) -> (Arc<MockCan>, AppState) {
+        let mock = Arc::new(MockCan::new());
+        let mut board_info = VciBoardInfo::default();
+        board_info.str_serial_num[..8].copy_from_slice(serial);
+        mock.set_devices(vec![board_info]);
+        (mock.clone(), AppState::with_interface(mock))
+    }
+
+    #[test]
+    fn a_device_held_by_another_program_is_reported_and_left_open() {
+        let (mock, mut app_state) = listed(b"MOCK0001");
+        // 模擬其他程式先開啟了裝置
+        mock.open(USBCAN2, 0).unwrap();
+        let probe = app_state.probe_device(USBCAN2, Some(0), None);
+        assert_eq!(probe.status, ProbeStatus::OpenFailed);
+        assert_eq!(probe.board_info.unwrap().serial_number, "MOCK0001");
+        assert!(probe.error.is_some());
+        assert!(mock.is_open(USBCAN2, 0));
+        assert_eq!(mock.close_count(USBCAN2, 0), 0);
+        assert!(app_state.devices.is_empty());
+    }
+
+    #[test]
+    fn an_index_missing_from_the_enumeration_is_not_present() {
+        let (mock, mut app_state) = listed(b"MOCK0001");
+        mock.set_open_failure(true);
+        let probe = app_state.probe_device(USBCAN2, Some(3), None);
+        assert_eq!(probe.status, ProbeStatus::NotPresent);
+        assert_eq!(probe.dev_index, Some(3));
+        assert!(probe.board_info.is_none());
+        assert_eq!(probe.error.as_deref(), Some("device not present"));
+    }
+
+    #[test]
+    fn an_unknown_serial_is_not_present_without_opening_anything() {
+        let (mock, mut app_state) = listed(b"MOCK0001");
+        let probe = app_state.probe_device(USBCAN2, None, Some("MOCK9999"));
+        assert_eq!(probe.status, ProbeStatus::NotPresent);
+        assert_eq!((probe.dev_index, probe.error), (None, None));
+        assert_eq!(mock.open_count(USBCAN2, 0), 0);
+        assert!(app_state.probe_device(USBCAN2, None, None).dev_index.is_none());
+    }
+
+    #[test]
+    fn a_device_open_here_is_found_by_serial_and_not_reopened() {
+        let (mock, mut app_state) = listed(b"MOCK0001");
+        app_state.open_device(USBCAN2, 0, None).unwrap();
+        let probe = app_state.probe_device(USBCAN2, None, Some("MOCK0001"));
+        assert_eq!((probe.status, probe.dev_index), (ProbeStatus::AlreadyOpen, Some(0)));
+        assert_eq!((mock.open_count(USBCAN2, 0), mock.close_count(USBCAN2, 0)), (1, 0));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::filter::SavedSoftwareFilter;
+use crate::periodic::{self, SavedPeriodic};
+use crate::settings::{self, SavedSettings};
+use crate::{dbc, invalid_argument, run_blocking, AppState, Backend, DeviceType, StateMutex};
+
+const PROFILE_FILE: &str = "profiles.json";
+
+/// 一個測試台的完整設定：裝置與通道、軟體過濾、週期訊框與 DBC 路徑
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Profile {
+    pub backend: Backend,
+    pub devices: Vec<SavedSettings>,
+    #[serde(default)]
+    pub software_filters: Vec<SavedSoftwareFilter>,
+    #[serde(default)]
+    pub periodic: Vec<SavedPeriodic>,
+    /// 省略時保留目前載入的 DBC；載入多個 DBC 時只記錄優先順序最低的檔案
+    #[serde(default)]
+    pub dbc_path: Option<String>,
+}
+
+fn profile_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve config directory: {}", e))?;
+    Ok(dir.join(PROFILE_FILE))
+}
+
+/// 讀取設定檔集合；檔案不存在時為空，內容損毀時回報錯誤而不是覆寫
+fn load_profiles(app_handle: &tauri::AppHandle) -> Result<BTreeMap<String, Profile>, String> {
+    let path = profile_path(app_handle)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&text).map_err(|e| format!("Profile file {} is corrupt: {}", path.display(), e))
+}
+
+fn store_profiles(app_handle: &tauri::AppHandle, profiles: &BTreeMap<String, Profile>) -> Result<(), String> {
+    let path = profile_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(profiles).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err(invalid_argument("name", "must not be empty"));
+    }
+    Ok(())
+}
+
+impl AppState {
+    fn current_profile(&self) -> Result<Profile, String> {
+        let mut keys: Vec<(u32, u32)> = self.devices.keys().copied().collect();
+        keys.sort();
+        Ok(Profile {
+            backend: self.backend().map_or(Backend::ControlCan, |lib| lib.backend()),
+            devices: keys.into_iter().filter_map(|key| self.current_settings(key)).collect(),
+            software_filters: self.software_filters.lock().map_err(|_| "Failed to lock filters")?.snapshot(),
+            periodic: self.saved_periodic_tasks(),
+            dbc_path: self.dbc_layers.base_path(),
+        })
+    }
+}
+
+/// 先確認 DBC、週期訊框與所有裝置都可用才開始變更；之後關閉目前的裝置與週期訊框，
+/// 依設定檔重新開啟裝置、初始化通道、設定軟體過濾與 DBC，再啟動週期訊框。
+/// 回傳的設定檔帶有裝置實際所在的 index
+fn apply(state: &Arc<StateMutex>, app_handle: &tauri::AppHandle, mut profile: Profile) -> Result<Profile, String> {
+    let dbc = profile.dbc_path.as_deref().map(dbc::read_dbc_file).transpose()?;
+    let (devices, dev_indexes) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let current_dbc = app_state.loaded_dbc().ok();
+        for (index, task) in profile.periodic.iter().enumerate() {
+            task.payload
+                .check(dbc.as_ref().or(current_dbc.as_deref()))
+                .map_err(|e| format!("periodic[{}]: {}", index, e))?;
+        }
+        let found = app_state.enumerate_with(profile.backend);
+        let dev_indexes = profile
+            .devices
+            .iter()
+            .map(|saved| settings::locate_saved_device(saved, found.clone()))
+            .collect::<Result<Vec<u32>, String>>()?;
+        // 以下開始變更目前的設定
+        app_state.stop_periodic_tasks();
+        let devices = std::mem::take(&mut app_state.devices);
+        if let Ok(mut taps) = app_state.frame_taps.lock() {
+            for &key in devices.keys() {
+                taps.disconnect_device(key);
+            }
+        }
+        (devices, dev_indexes)
+    };
+    // 在鎖外 drop，各裝置的 CanDevice 等待接收執行緒結束後關閉裝置
+    drop(devices);
+
+    let mut moved = HashMap::new();
+    {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        app_state.select_backend(profile.backend)?;
+        for (saved, dev_index) in profile.devices.iter_mut().zip(dev_indexes) {
+            app_state.restore_device(saved, dev_index)?;
+            moved.insert((saved.dev_type.code(), saved.dev_index), dev_index);
+            saved.dev_index = dev_index;
+        }
+        for filter in &mut profile.software_filters {
+            filter.dev_index = moved.get(&(filter.dev_type, filter.dev_index)).copied().unwrap_or(filter.dev_index);
+        }
+        app_state
+            .software_filters
+            .lock()
+            .map_err(|_| "Failed to lock filters")?
+            .replace(profile.software_filters.clone());
+        if let Some(dbc) = dbc {
+            app_state.set_dbc(dbc, profile.dbc_path.clone())?;
+        }
+    }
+    for task in &mut profile.periodic {
+        task.dev_index = moved.get(&(task.dev_type, task.dev_index)).copied().unwrap_or(task.dev_index);
+        periodic::spawn_task(
+            app_handle.clone(),
+            state,
+            Some(DeviceType::from_code(task.dev_type)),
+            Some(task.dev_index),
+            task.channel,
+            task.interval_ms,
+            task.payload.clone(),
+            Some(task.echo),
+            task.e2e.clone(),
+            periodic::TaskRole::User,
+        )?;
+    }
+    Ok(profile)
+}
+
+/// 以 name 儲存目前的完整設定，同名時覆寫
+#[tauri::command]
+pub fn save_profile(name: String, app_handle: tauri::AppHandle, state: State<Arc<StateMutex>>) -> Result<Profile, String> {
+    check_name(&name)?;
+    let profile = state.lock().map_err(|_| "Failed to lock state")?.current_profile()?;
+    let mut profiles = load_profiles(&app_handle)?;
+    profiles.insert(name, profile.clone());
+    store_profiles(&app_handle, &profiles)?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub async fn apply_profile(
+    name: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Profile, String> {
+    let profile = load_profiles(&app_handle)?
+        .remove(&name)
+        .ok_or_else(|| format!("profile '{}' not found", name))?;
+    let state = state.inner().clone();
+    run_blocking(move || apply(&state, &app_handle, profile)).await
+}
+
+#[tauri::command]
+pub fn list_profiles(app_handle: tauri::AppHandle) -> Result<BTreeMap<String, Profile>, String> {
+    load_profiles(&app_handle)
+}
+
+#[tauri::command]
+pub fn delete_profile(name: String, app_handle: tauri::AppHandle) -> Result<String, String> {
+    let mut profiles = load_profiles(&app_handle)?;
+    profiles
+        .remove(&name)
+        .ok_or_else(|| format!("profile '{}' not found", name))?;
+    store_profiles(&app_handle, &profiles)?;
+    Ok(format!("Profile '{}' deleted", name))
+}
+
+/// 把一個設定檔寫成獨立的 JSON 檔，供其他電腦以 import_profile 匯入
+#[tauri::command]
+pub fn export_profile(name: String, path: String, app_handle: tauri::AppHandle) -> Result<String, String> {
+    let profile = load_profiles(&app_handle)?
+        .remove(&name)
+        .ok_or_else(|| format!("profile '{}' not found", name))?;
+    let text = serde_json::to_string_pretty(&profile).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(format!("Profile '{}' exported to {}", name, path))
+}
+
+/// 匯入 export_profile 產生的檔案；name 省略時使用檔名，同名時覆寫。回傳儲存的名稱
+#[tauri::command]
+pub fn import_profile(path: String, name: Option<String>, app_handle: tauri::AppHandle) -> Result<String, String> {
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let profile: Profile = serde_json::from_str(&text).map_err(|e| format!("{} is not a valid profile: {}", path, e))?;
+    let name = name.unwrap_or_else(|| {
+        Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    check_name(&name)?;
+    let mut profiles = load_profiles(&app_handle)?;
+    profiles.insert(name.clone(), profile);
+    store_profiles(&app_handle, &profiles)?;
+    Ok(name)
+}
@@ -0,0 +1,1276 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::capture::{CaptureInfo, Captures};
+use crate::config_history::ConfigHistory;
+use crate::dbc::Dbc;
+use crate::dedupe::Dedupe;
+use crate::delta::DeltaTimes;
+use crate::e2e::E2eChecks;
+use crate::filter::SoftwareFilters;
+use crate::id_names::{self, IdNames};
+use crate::frame::{host_timestamp_us, CanFrameEvent, Direction};
+use crate::j1939::{J1939Message, J1939State};
+use crate::logging::LogSink;
+use crate::memory;
+use crate::mqtt::MqttFeed;
+use crate::bus_state::{BusState, BusStateMonitor};
+use crate::busoff::{self, BusOffAction, BusOffRecoverer};
+use crate::emit_queue::{BackpressureConfig, EmitQueue};
+use crate::bus_quality::{BusQualityConfig, BusQualityMonitor};
+use crate::overflow::OverflowDetector;
+use crate::responder::{self, AutoResponder, AutoResponseRule};
+use crate::ring_buffer::{BufferedFrame, FrameQuery, FrameRing};
+use crate::stats::{ChannelCounters, CycleMissedEvent, IdStatistics, IdTableReporter, StatsReporter};
+use crate::subscription::Subscriptions;
+use crate::supervisor;
+use crate::tap::{FrameTaps, StreamGuard};
+use crate::timed_capture::{CaptureLimit, CaptureProgress, CaptureSource};
+use crate::timestamp::TimestampClock;
+use crate::trigger::{TriggerEvent, TriggerTable};
+use crate::usb_reset;
+use crate::watchdog::{BusActivityEvent, BusWatchdogs};
+use crate::ws_bridge::WsHub;
+use crate::{heartbeat, invalid_argument, run_blocking, AppState, CanInterface, DeviceType, StateMutex, VciCanObj};
+
+/// 連續多少次 VCI_Receive 回傳 -1 視為裝置斷線
+const DISCONNECT_ERROR_THRESHOLD: u32 = 10;
+const RECONNECT_INITIAL_BACKOFF_MS: u64 = 500;
+const RECONNECT_MAX_BACKOFF_MS: u64 = 10_000;
+/// 接收執行緒每次呼叫 VCI_Receive 最多讀取的訊框數
+const STREAM_BATCH_FRAMES: u32 = 100;
+/// VCI_Receive 建議的單次最大讀取數
+const MAX_RECEIVE_FRAMES: u32 = 2500;
+const DEFAULT_STATS_INTERVAL_MS: u64 = 1000;
+/// 讀取錯誤計數器的預設間隔
+const DEFAULT_BUS_STATE_INTERVAL_MS: u64 = 1000;
+/// 恢復事件時補送的最大訊框數
+const DEFAULT_CATCH_UP_LIMIT: usize = 5000;
+const DEFAULT_ID_TABLE_RATE_HZ: f64 = 5.0;
+/// 待讀訊框數達到此值時視為積壓，連續讀取不休眠
+const DEFAULT_BUSY_THRESHOLD: u32 = STREAM_BATCH_FRAMES;
+/// 有流量時每輪之間的休眠
+const DEFAULT_ACTIVE_POLL_MS: u64 = 10;
+/// 匯流排安靜時休眠逐步加倍到此上限
+const DEFAULT_IDLE_POLL_MAX_MS: u64 = 200;
+/// 休眠時檢查停止旗標的間隔
+const STOP_POLL_MS: u64 = 50;
+/// 低延遲模式預設的 VCI_Receive WaitTime；停止接收最多延遲這麼久
+const DEFAULT_BLOCKING_WAIT_MS: u64 = 100;
+const MAX_BLOCKING_WAIT_MS: u64 = 1000;
+
+/// 接收執行緒結束的原因
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEndReason {
+    /// stop_receiving_data 或同一通道重新開始接收
+    Stopped,
+    DeviceClosed,
+    /// 後端已被卸載或切換
+    LibraryUnloaded,
+    /// 裝置斷線且未開啟 auto_reconnect，或重連時被停止
+    Disconnected,
+    /// bus-off 恢復次數用盡
+    BusOff,
+    CaptureFinished,
+    /// state 鎖已失效，無法再存取裝置
+    StateUnavailable,
+    /// 接收執行緒 panic，已另外送出 backend-panic
+    Panicked,
+}
+
+/// stream-ended 事件；每個接收執行緒結束時送出一次
+#[derive(Serialize, Clone)]
+pub struct StreamEndedEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub reason: StreamEndReason,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ConnectionEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub attempts: u32,
+}
+
+/// 通道送往前端的事件設定。暫停時接收迴圈照常處理訊框 (緩衝、統計、記錄、觸發器)，只是不送出 can-data
+#[derive(Default)]
+pub struct EmissionControl {
+    paused: AtomicBool,
+    /// 暫停當下環形緩衝的下一個 seq，恢復時由此補送
+    paused_at_seq: AtomicU64,
+    /// can-id-table 事件的間隔，0 表示未啟用
+    id_table_interval_ms: AtomicU64,
+    /// 關閉送出訊框的回送 (預設開啟)
+    tx_echo_disabled: AtomicBool,
+    /// can-data 事件佇列的大小、速率與丟棄方式
+    pub backpressure: Mutex<BackpressureConfig>,
+}
+
+impl EmissionControl {
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn tx_echo_enabled(&self) -> bool {
+        !self.tx_echo_disabled.load(Ordering::Relaxed)
+    }
+}
+
+impl AppState {
+    /// 清除通道的 receiving 旗標，讓該通道的接收執行緒在下一輪結束；channel 為 None 時停止所有通道
+    pub fn stop_receiving(&self, dev_type: Option<u32>, dev_index: Option<u32>, channel: Option<u32>) -> Result<(), String> {
+        let device = self.device(dev_type, dev_index)?;
+        match channel {
+            Some(channel) => device
+                .receiving
+                .get(&channel)
+                .ok_or_else(|| format!("CAN{} is not receiving", channel + 1))?
+                .store(false, Ordering::SeqCst),
+            None => {
+                for receiving in device.receiving.values() {
+                    receiving.store(false, Ordering::SeqCst);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 目前有接收執行緒在執行的通道
+    pub fn receiving_channels(&self, dev_type: Option<u32>, dev_index: Option<u32>) -> Result<Vec<u32>, String> {
+        let device = self.device(dev_type, dev_index)?;
+        let mut channels: Vec<u32> = device
+            .receiving
+            .iter()
+            .filter(|(_, receiving)| receiving.load(Ordering::SeqCst))
+            .map(|(&channel, _)| channel)
+            .collect();
+        channels.sort_unstable();
+        Ok(channels)
+    }
+
+    pub(crate) fn emission_control(&mut self, key: (u32, u32), channel: u32) -> Arc<EmissionControl> {
+        self.channel_runtime(key, channel).emission.clone()
+    }
+
+    /// 檢查裝置已連線且通道存在後才取得 emission 設定，避免為不存在的通道建立項目
+    fn checked_emission_control(&mut self, dev_type: Option<u32>, dev_index: Option<u32>, channel: u32) -> Result<Arc<EmissionControl>, String> {
+        let device = self.connected_device(dev_type, dev_index)?;
+        device.check_channel(channel)?;
+        let key = device.key();
+        Ok(self.emission_control(key, channel))
+    }
+}
+
+enum ReceiveOutcome {
+    /// backlog 表示仍有積壓，應立即再讀；full 表示讀滿了整批
+    Frames { frames: Vec<CanFrameEvent>, backlog: bool, full: bool },
+    Empty,
+    Error,
+    Ended(StreamEndReason),
+}
+
+/// 接收迴圈送出事件的對象；程式中是 tauri::AppHandle，測試時可換成記錄事件的實作
+pub trait EventSink: Send + 'static {
+    /// 回傳 false 表示事件沒有送達 (計入 events_dropped)
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) -> bool;
+}
+
+impl EventSink for tauri::AppHandle {
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) -> bool {
+        self.emit(event, payload).is_ok()
+    }
+}
+
+/// 各通道的讀取方式；監看大量流量適合輪詢，互動式的請求/回應除錯適合阻塞
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReceiveMode {
+    /// 依 active_poll_ms/idle_poll_max_ms 休眠後查詢待讀數；安靜的匯流排上每個訊框最多延遲 idle_poll_max_ms
+    #[default]
+    Polling,
+    /// 以 wait_ms (預設 100) 阻塞呼叫 VCI_Receive 且不休眠，訊框約 1 ms 內送出；
+    /// 執行緒一直在等待驅動，stop_receiving_data 最多在一個 wait_ms 後生效
+    Blocking { wait_ms: Option<u64> },
+}
+
+impl ReceiveMode {
+    /// 阻塞模式的 VCI_Receive WaitTime；輪詢模式為 None
+    fn blocking_wait(self) -> Result<Option<Duration>, String> {
+        match self {
+            ReceiveMode::Polling => Ok(None),
+            ReceiveMode::Blocking { wait_ms } => {
+                let wait_ms = wait_ms.unwrap_or(DEFAULT_BLOCKING_WAIT_MS);
+                if !(1..=MAX_BLOCKING_WAIT_MS).contains(&wait_ms) {
+                    return Err(invalid_argument("receive_mode.wait_ms", format!("must be between 1 and {}", MAX_BLOCKING_WAIT_MS)));
+                }
+                Ok(Some(Duration::from_millis(wait_ms)))
+            }
+        }
+    }
+}
+
+/// start_receiving_data 的選項
+#[derive(Clone, Copy, Debug)]
+pub struct ReceiveOptions {
+    pub auto_reconnect: bool,
+    pub stats_interval: Duration,
+    /// 讀取錯誤計數器與匯流排狀態的間隔，Duration::ZERO 表示不讀取
+    pub bus_state_interval: Duration,
+    pub busy_threshold: u32,
+    pub active_poll: Duration,
+    pub idle_poll_max: Duration,
+    /// 達到時自動停止接收並送出 capture-finished
+    pub limit: Option<CaptureLimit>,
+    /// 事件附上原始的 VCI_CAN_OBJ (raw)，用於診斷驅動問題
+    pub debug_mode: bool,
+    /// 低延遲模式：以這個 WaitTime 阻塞呼叫 VCI_Receive 且不休眠，訊框一到就送出，代價是執行緒一直在等待驅動
+    pub blocking_wait: Option<Duration>,
+}
+
+impl Default for ReceiveOptions {
+    fn default() -> Self {
+        Self {
+            auto_reconnect: false,
+            stats_interval: Duration::from_millis(DEFAULT_STATS_INTERVAL_MS),
+            bus_state_interval: Duration::from_millis(DEFAULT_BUS_STATE_INTERVAL_MS),
+            busy_threshold: DEFAULT_BUSY_THRESHOLD,
+            active_poll: Duration::from_millis(DEFAULT_ACTIVE_POLL_MS),
+            idle_poll_max: Duration::from_millis(DEFAULT_IDLE_POLL_MAX_MS),
+            limit: None,
+            debug_mode: false,
+            blocking_wait: None,
+        }
+    }
+}
+
+/// busy_threshold、active_poll_ms、idle_poll_max_ms 調整輪詢：待讀訊框數達到 busy_threshold 時連續讀取；
+/// 沒有資料時休眠從 active_poll_ms 逐步加倍到 idle_poll_max_ms。
+/// bus_state_interval_ms 為讀取錯誤計數器的間隔 (預設 1000，0 表示不讀取)，狀態改變時送出 bus-state-changed。
+/// 設定 limit 時接收 duration_s 秒或 max_frames 個訊框後自動停止，並送出 capture-finished。
+/// debug_mode 為 true 時每個事件附上 DLL 回傳的原始欄位 (raw)。
+/// receive_mode 為 {"kind": "blocking", "wait_ms": 100} 時改以阻塞讀取取代輪詢休眠 (見 ReceiveMode)
+#[tauri::command]
+pub async fn start_receiving_data(
+    app_handle: tauri::AppHandle,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    can_channel: u32,
+    auto_reconnect: Option<bool>,
+    stats_interval_ms: Option<u64>,
+    busy_threshold: Option<u32>,
+    active_poll_ms: Option<u64>,
+    idle_poll_max_ms: Option<u64>,
+    bus_state_interval_ms: Option<u64>,
+    limit: Option<CaptureLimit>,
+    debug_mode: Option<bool>,
+    receive_mode: Option<ReceiveMode>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<(), String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let limit = limit.map(CaptureLimit::validate).transpose()?;
+        let blocking_wait = receive_mode.unwrap_or_default().blocking_wait()?;
+        let active_poll_ms = active_poll_ms.unwrap_or(DEFAULT_ACTIVE_POLL_MS);
+        let options = ReceiveOptions {
+            auto_reconnect: auto_reconnect.unwrap_or(false),
+            stats_interval: Duration::from_millis(stats_interval_ms.unwrap_or(DEFAULT_STATS_INTERVAL_MS).max(100)),
+            bus_state_interval: match bus_state_interval_ms.unwrap_or(DEFAULT_BUS_STATE_INTERVAL_MS) {
+                0 => Duration::ZERO,
+                ms => Duration::from_millis(ms.max(100)),
+            },
+            busy_threshold: busy_threshold.unwrap_or(DEFAULT_BUSY_THRESHOLD).clamp(1, MAX_RECEIVE_FRAMES),
+            active_poll: Duration::from_millis(active_poll_ms),
+            idle_poll_max: Duration::from_millis(idle_poll_max_ms.unwrap_or(DEFAULT_IDLE_POLL_MAX_MS).max(active_poll_ms)),
+            limit,
+            debug_mode: debug_mode.unwrap_or(false),
+            blocking_wait,
+        };
+        // 同一通道原本的執行緒在鎖外等它結束，之後只會有一個執行緒在讀取
+        let previous = state
+            .lock()
+            .map_err(|_| "Failed to lock state")?
+            .device_mut(dev_type.map(DeviceType::code), dev_index)?
+            .handle
+            .take_thread(can_channel);
+        if let Some(previous) = previous {
+            let _ = previous.join();
+        }
+        let (key, handle) = spawn_receive_loop(
+            &state,
+            app_handle,
+            dev_type.map(DeviceType::code),
+            dev_index,
+            can_channel,
+            options,
+        )?;
+        let mut state_guard = state.lock().map_err(|_| "Failed to lock state")?;
+        // 裝置若在這期間被關閉，執行緒會在下一輪自行結束
+        if let Some(device) = state_guard.devices.get_mut(&key) {
+            device.register_receive_thread(can_channel, handle);
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// 啟動通道的接收執行緒，回傳裝置的 key 與執行緒 handle。同一通道已有執行緒時先讓舊的結束。
+/// 通道的 receiving 旗標被清除或裝置被關閉後，執行緒會在下一輪結束
+pub fn spawn_receive_loop<E: EventSink>(
+    state: &Arc<StateMutex>,
+    events: E,
+    dev_type: Option<u32>,
+    dev_index: Option<u32>,
+    can_channel: u32,
+    options: ReceiveOptions,
+) -> Result<((u32, u32), JoinHandle<()>), String> {
+    let state_clone = state.clone();
+    let capture = options.limit.map(|limit| Arc::new(CaptureProgress::new(limit)));
+    let (receiving_flag, key, mut pipeline, pulse) = {
+        let mut state_guard = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = state_guard.connected_device(dev_type, dev_index)?;
+        device.check_channel(can_channel)?;
+        let key = device.key();
+        let receiving = Arc::new(AtomicBool::new(true));
+        if let Some(device) = state_guard.devices.get_mut(&key) {
+            if let Some(previous) = device.receiving.insert(can_channel, receiving.clone()) {
+                previous.store(false, Ordering::SeqCst);
+            }
+            device.receive_options.insert(can_channel, options);
+            match &capture {
+                Some(capture) => device.captures.insert(can_channel, capture.clone()),
+                None => device.captures.remove(&can_channel),
+            };
+        }
+        // 先前用盡 bus-off 恢復次數而停止的通道重新開始接收
+        state_guard
+            .channel_runtime(key, can_channel)
+            .recovery_phase
+            .store(busoff::PHASE_IDLE, Ordering::SeqCst);
+        // 一輪最多等候 blocking_wait 或休眠 idle_poll_max
+        let pulse = state_guard.pulses.register(
+            "receive",
+            format!("device {}:{} CAN{}", key.0, key.1, can_channel + 1),
+            heartbeat::STALL_MARGIN + options.idle_poll_max + options.blocking_wait.unwrap_or_default(),
+        );
+        (receiving, key, Pipeline::new(&mut state_guard, key, can_channel), pulse)
+    };
+    let ReceiveOptions {
+        auto_reconnect,
+        stats_interval,
+        bus_state_interval,
+        busy_threshold,
+        active_poll,
+        idle_poll_max,
+        limit: _,
+        debug_mode,
+        blocking_wait,
+    } = options;
+    let mut reporter = pipeline.reporter(key, can_channel, stats_interval);
+    let mut id_table_reporter = IdTableReporter::new(key, can_channel);
+    let mut overflow = OverflowDetector::new(key, can_channel, pipeline.counters.clone());
+    let mut bus_quality = pipeline.quality_monitor(key, can_channel);
+    let mut bus_state = BusStateMonitor::new(key, can_channel, pipeline.counters.clone(), bus_state_interval);
+    let mut busoff_recoverer = BusOffRecoverer::new(key, can_channel);
+    let mut emit_queue = EmitQueue::new(key, can_channel, pipeline.counters.clone());
+    let handle = std::thread::spawn(move || {
+        let mut key = key;
+        let context = format!("device {}:{} CAN{}", key.0, key.1, can_channel + 1);
+        let end_reason = supervisor::guard("receive", context, || {
+            let mut consecutive_errors = 0;
+            let mut idle_poll = active_poll;
+            let mut end_reason = StreamEndReason::Stopped;
+            while receiving_flag.load(Ordering::SeqCst) {
+                pulse.beat();
+                let backpressure = *pipeline.emission.backpressure.lock().unwrap_or_else(PoisonError::into_inner);
+                let fully_idle = idle_poll >= idle_poll_max;
+                let sleep = match receive_one(
+                    &state_clone,
+                    key,
+                    can_channel,
+                    busy_threshold,
+                    fully_idle,
+                    debug_mode,
+                    blocking_wait,
+                    &pipeline.counters,
+                ) {
+                    ReceiveOutcome::Frames { mut frames, backlog, full } => {
+                        consecutive_errors = 0;
+                        if let Some(capture) = &capture {
+                            capture.admit(&mut frames);
+                        }
+                        idle_poll = active_poll;
+                        if let Some(event) = overflow.record_read(full, &pipeline.frame_buffer) {
+                            events.emit_event("can-overflow", event);
+                        }
+                        let buffered = pipeline.process(frames);
+                        for rule in pipeline.auto_responses.drain(..) {
+                            responder::respond(&state_clone, key, rule);
+                        }
+                        // 訂閱、WebSocket 橋接與 MQTT 發佈不受 pause_emission 影響
+                        pipeline.ws_hub.broadcast(&buffered);
+                        pipeline.mqtt_feed.publish(&buffered);
+                        let fanned_out = pipeline
+                            .subscriptions
+                            .lock()
+                            .map(|s| s.fan_out(key, can_channel, &buffered))
+                            .unwrap_or_default();
+                        for (event, frames) in fanned_out {
+                            events.emit_event(&event, frames);
+                        }
+                        let signal_updates = pipeline
+                            .subscriptions
+                            .lock()
+                            .map(|mut s| s.signal_updates(can_channel, &buffered))
+                            .unwrap_or_default();
+                        if !signal_updates.is_empty() {
+                            events.emit_event("signal-update", signal_updates);
+                        }
+                        if pipeline.emission.is_paused() {
+                            emit_queue.clear();
+                        } else if let Some(event) = emit_queue.push(buffered, &backpressure) {
+                            events.emit_event("backpressure", event);
+                        }
+                        for event in pipeline.trigger_events.drain(..) {
+                            events.emit_event("can-trigger", event);
+                        }
+                        for capture in pipeline.completed_captures.drain(..) {
+                            events.emit_event("capture-complete", capture);
+                        }
+                        for message in pipeline.j1939_messages.drain(..) {
+                            events.emit_event("j1939-message", message);
+                        }
+                        if let Some(event) = pipeline.bus_active.take() {
+                            events.emit_event("bus-active", event);
+                        }
+                        for event in pipeline.cycle_misses.drain(..) {
+                            events.emit_event("cycle-missed", event);
+                        }
+                        if backlog || blocking_wait.is_some() {
+                            Duration::ZERO
+                        } else {
+                            active_poll
+                        }
+                    }
+                    // VCI_Receive 已等待過 blocking_wait
+                    ReceiveOutcome::Empty if blocking_wait.is_some() => {
+                        consecutive_errors = 0;
+                        Duration::ZERO
+                    }
+                    ReceiveOutcome::Empty => {
+                        consecutive_errors = 0;
+                        let sleep = idle_poll;
+                        idle_poll = (idle_poll * 2).clamp(Duration::from_millis(1), idle_poll_max);
+                        sleep
+                    }
+                    ReceiveOutcome::Error => {
+                        consecutive_errors += 1;
+                        pipeline.counters.errors.fetch_add(1, Ordering::Relaxed);
+                        pipeline.counters.bus_errors.fetch_add(1, Ordering::Relaxed);
+                        active_poll
+                    }
+                    ReceiveOutcome::Ended(reason) => {
+                        end_reason = reason;
+                        break;
+                    }
+                };
+                let mut reopen = false;
+                if let Some(event) = bus_state.poll(&state_clone) {
+                    let bus_off = event.status.bus_state == BusState::BusOff;
+                    events.emit_event("bus-state-changed", event);
+                    if bus_off {
+                        match busoff_recoverer.on_bus_off(&state_clone, &receiving_flag, &events) {
+                            BusOffAction::None => {}
+                            BusOffAction::Reopen => reopen = true,
+                            BusOffAction::Stopped => {
+                                end_reason = StreamEndReason::BusOff;
+                                break;
+                            }
+                        }
+                    }
+                }
+                if consecutive_errors >= DISCONNECT_ERROR_THRESHOLD || reopen {
+                    consecutive_errors = 0;
+                    mark_disconnected(&state_clone, key);
+                    events.emit_event("can-disconnected", connection_event(key, can_channel, 0));
+                    // bus-off 的 reopen 策略不受 auto_reconnect 影響
+                    if !auto_reconnect && !reopen {
+                        receiving_flag.store(false, Ordering::SeqCst);
+                        end_reason = StreamEndReason::Disconnected;
+                        break;
+                    }
+                    pulse.pause();
+                    match reconnect_with_backoff(&state_clone, key, &receiving_flag) {
+                        Some((new_key, attempts)) => {
+                            key = new_key;
+                            if let Ok(mut app_state) = state_clone.lock() {
+                                pipeline = Pipeline::new(&mut app_state, key, can_channel);
+                                reporter = pipeline.reporter(key, can_channel, stats_interval);
+                                id_table_reporter = IdTableReporter::new(key, can_channel);
+                                overflow = OverflowDetector::new(key, can_channel, pipeline.counters.clone());
+                                bus_quality = pipeline.quality_monitor(key, can_channel);
+                                bus_state = BusStateMonitor::new(key, can_channel, pipeline.counters.clone(), bus_state_interval);
+                                busoff_recoverer = BusOffRecoverer::new(key, can_channel);
+                                emit_queue = EmitQueue::new(key, can_channel, pipeline.counters.clone());
+                            }
+                            events.emit_event("can-reconnected", connection_event(key, can_channel, attempts));
+                        }
+                        None => {
+                            end_reason = StreamEndReason::Disconnected;
+                            break;
+                        }
+                    }
+                }
+                if let Some(event) = overflow.poll_err_info(&state_clone, &pipeline.frame_buffer) {
+                    events.emit_event("can-overflow", event);
+                }
+                if let Some(event) = pipeline.watchdogs.lock().ok().and_then(|mut w| w.poll(pipeline.key, can_channel)) {
+                    events.emit_event("bus-silent", event);
+                }
+                if let Some((event, payload)) = bus_quality.poll() {
+                    events.emit_event(event, payload);
+                }
+                if let Some(stats) = reporter.poll(&pipeline.frame_buffer, bus_quality.rate()) {
+                    events.emit_event("can-stats", stats);
+                }
+                let id_table_interval_ms = pipeline.emission.id_table_interval_ms.load(Ordering::Relaxed);
+                if let Some(table) = id_table_reporter.poll(id_table_interval_ms, &pipeline.id_statistics) {
+                    events.emit_event("can-id-table", table);
+                }
+                let (ready, relieved) = emit_queue.take_ready(&backpressure);
+                for frame in ready {
+                    if !events.emit_event("can-data", frame) {
+                        pipeline.counters.events_dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                if let Some(event) = relieved {
+                    events.emit_event("backpressure", event);
+                }
+                if let Some(capture) = capture.as_ref().filter(|c| c.is_reached()) {
+                    let finished = capture.finished(CaptureSource::Receive, Some(key), Some(can_channel), None);
+                    events.emit_event("capture-finished", finished);
+                    end_reason = StreamEndReason::CaptureFinished;
+                    break;
+                }
+                // 佇列還有訊框時不要進入長時間的閒置休眠
+                let sleep = if emit_queue.is_empty() { sleep } else { sleep.min(active_poll) };
+                let sleep = capture.as_ref().and_then(|c| c.time_left()).map_or(sleep, |left| sleep.min(left));
+                sleep_unless_stopped(&receiving_flag, sleep);
+            }
+            end_reason
+        });
+        let end_reason = end_reason.unwrap_or_else(|| {
+            // panic 時可能正持有訂閱表的鎖；清除 poison 讓下面的清理照常運作 (state 鎖由 StateMutex 自行恢復)
+            pipeline.subscriptions.clear_poison();
+            StreamEndReason::Panicked
+        });
+        receiving_flag.store(false, Ordering::SeqCst);
+        // 只移除自己的旗標與訂閱；同一通道可能已由新的執行緒取代
+        if let Ok(mut app_state) = state_clone.lock() {
+            let replaced = app_state
+                .devices
+                .get(&key)
+                .and_then(|device| device.receiving.get(&can_channel))
+                .is_some_and(|flag| !Arc::ptr_eq(flag, &receiving_flag));
+            if !replaced {
+                if let Some(device) = app_state.devices.get_mut(&key) {
+                    device.receiving.remove(&can_channel);
+                    device.captures.remove(&can_channel);
+                }
+                if let Ok(mut subscriptions) = pipeline.subscriptions.lock() {
+                    subscriptions.remove_channel(key, can_channel);
+                }
+            }
+        }
+        events.emit_event(
+            "stream-ended",
+            StreamEndedEvent {
+                dev_type: key.0,
+                dev_index: key.1,
+                channel: can_channel,
+                reason: end_reason,
+            },
+        );
+    });
+    Ok((key, handle))
+}
+
+/// 停止指定通道的接收；省略 channel 時停止此裝置所有通道
+#[tauri::command]
+pub fn stop_receiving_data(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: Option<u32>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let state_guard = state.lock().map_err(|_| "Failed to lock state")?;
+    state_guard.stop_receiving(dev_type.map(DeviceType::code), dev_index, channel)?;
+    Ok(match channel {
+        Some(channel) => format!("Stopped receiving CAN{} data", channel + 1),
+        None => "Stopped receiving CAN data".into(),
+    })
+}
+
+#[tauri::command]
+pub fn get_receiving_channels(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    state: State<Arc<StateMutex>>,
+) -> Result<Vec<u32>, String> {
+    let state_guard = state.lock().map_err(|_| "Failed to lock state")?;
+    state_guard.receiving_channels(dev_type.map(DeviceType::code), dev_index)
+}
+
+#[derive(Serialize)]
+pub struct EmissionState {
+    pub channel: u32,
+    pub paused: bool,
+    /// 恢復時補送 (can-data-batch) 的訊框數
+    pub caught_up: usize,
+}
+
+/// 暫停送出 can-data 事件；接收執行緒不會停止，暫停期間的訊框仍進入環形緩衝
+#[tauri::command]
+pub fn pause_emission(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<EmissionState, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let emission = app_state.checked_emission_control(dev_type.map(DeviceType::code), dev_index, channel)?;
+    let next_seq = app_state.frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?.status().next_seq;
+    if !emission.paused.swap(true, Ordering::SeqCst) {
+        emission.paused_at_seq.store(next_seq, Ordering::SeqCst);
+    }
+    Ok(EmissionState {
+        channel,
+        paused: true,
+        caught_up: 0,
+    })
+}
+
+/// 恢復送出 can-data；catch_up 為 true 時把暫停期間的訊框以一個 can-data-batch 事件補送 (最多 catch_up_limit 筆)
+#[tauri::command]
+pub fn resume_emission(
+    app_handle: tauri::AppHandle,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    catch_up: Option<bool>,
+    catch_up_limit: Option<usize>,
+    state: State<Arc<StateMutex>>,
+) -> Result<EmissionState, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let emission = app_state.checked_emission_control(dev_type.map(DeviceType::code), dev_index, channel)?;
+    let was_paused = emission.paused.swap(false, Ordering::SeqCst);
+    let mut caught_up = 0;
+    if was_paused && catch_up.unwrap_or(false) {
+        let since_seq = emission.paused_at_seq.load(Ordering::SeqCst).checked_sub(1);
+        let frames = app_state.frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?.query(
+            &FrameQuery::channel(channel),
+            since_seq,
+            catch_up_limit.unwrap_or(DEFAULT_CATCH_UP_LIMIT),
+        );
+        caught_up = frames.len();
+        if !frames.is_empty() {
+            app_handle.emit("can-data-batch", frames).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(EmissionState {
+        channel,
+        paused: false,
+        caught_up,
+    })
+}
+
+/// 開關通道的 ID 表彙總模式：以 rate_hz (預設 5 Hz) 發出只含變動項目的 can-id-table 事件
+#[tauri::command]
+pub fn set_id_table_mode(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    enabled: bool,
+    rate_hz: Option<f64>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let emission = app_state.checked_emission_control(dev_type.map(DeviceType::code), dev_index, channel)?;
+    let rate_hz = rate_hz.unwrap_or(DEFAULT_ID_TABLE_RATE_HZ);
+    if enabled && !(rate_hz > 0.0 && rate_hz <= 100.0) {
+        return Err(format!("rate_hz must be in (0, 100], got {}", rate_hz));
+    }
+    let interval_ms = if enabled { ((1000.0 / rate_hz).round() as u64).max(1) } else { 0 };
+    emission.id_table_interval_ms.store(interval_ms, Ordering::Relaxed);
+    Ok(match enabled {
+        true => format!("ID table for CAN{} at {} Hz", channel + 1, rate_hz),
+        false => format!("ID table for CAN{} disabled", channel + 1),
+    })
+}
+
+/// 開關通道的 TX 回送：開啟時成功送出的訊框以 direction = tx 進入環形緩衝、統計與 can-data 事件流
+#[tauri::command]
+pub fn set_tx_echo(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    enabled: bool,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let key = app_state.device(dev_type.map(DeviceType::code), dev_index)?.key();
+    app_state
+        .emission_control(key, channel)
+        .tx_echo_disabled
+        .store(!enabled, Ordering::Relaxed);
+    Ok(format!("TX echo for CAN{} {}", channel + 1, if enabled { "enabled" } else { "disabled" }))
+}
+
+/// 呼叫一次 VCI_Receive 等待最多 wait_ms；逾時沒有資料時回傳空陣列而非錯誤
+pub fn receive_once(
+    state: &StateMutex,
+    dev_type: Option<u32>,
+    dev_index: Option<u32>,
+    can_channel: u32,
+    max_frames: u32,
+    wait_ms: i32,
+) -> Result<Vec<CanFrameEvent>, String> {
+    let (key, can_lib, dbc) = {
+        let state_guard = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = state_guard.connected_device(dev_type, dev_index)?;
+        device.check_channel(can_channel)?;
+        let can_lib = state_guard.backend().ok_or("CAN library not initialized")?;
+        (device.key(), can_lib, state_guard.dbc.clone())
+    };
+    // wait_ms 期間不持有 state 鎖，其他命令 (例如 get_status) 可以照常回應
+    let mut frames = read_frames(can_lib.as_ref(), key, can_channel, max_frames.clamp(1, MAX_RECEIVE_FRAMES), wait_ms, false)
+        .map_err(|code| format!("VCI_Receive failed ({})", code))?;
+    decode_frames(&dbc, &mut frames);
+    Ok(frames)
+}
+
+/// 單次讀取；適合前端輪詢使用，逾時沒有資料時回傳空陣列而非錯誤
+#[tauri::command]
+pub async fn receive_can_data(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    can_channel: u32,
+    max_frames: u32,
+    wait_ms: i32,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<CanFrameEvent>, String> {
+    let state = state.inner().clone();
+    run_blocking(move || receive_once(&state, dev_type.map(DeviceType::code), dev_index, can_channel, max_frames, wait_ms)).await
+}
+
+/// 除錯用：直接呼叫 VCI_Receive，逐欄位回傳 DLL 填入的 VCI_CAN_OBJ，不經過任何處理
+/// (包含 time_flag、send_type、reserved 與未截斷的 data)。
+/// 讀到的訊框不會進入接收串流；通道有接收執行緒時兩者會互相搶走訊框
+#[tauri::command]
+pub async fn receive_raw_frames(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    max: u32,
+    wait_ms: i32,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<VciCanObj>, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let (key, can_lib) = {
+            let state_guard = state.lock().map_err(|_| "Failed to lock state")?;
+            let device = state_guard.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+            device.check_channel(channel)?;
+            let can_lib = state_guard.backend().ok_or("CAN library not initialized")?;
+            (device.key(), can_lib)
+        };
+        can_lib
+            .receive(key.0, key.1, channel, max.clamp(1, MAX_RECEIVE_FRAMES), wait_ms)
+            .map_err(|code| format!("VCI_Receive failed ({})", code))
+    })
+    .await
+}
+
+pub(crate) fn decode_frames(dbc: &Mutex<Option<Arc<Dbc>>>, frames: &mut [CanFrameEvent]) {
+    let Some(dbc) = dbc.lock().ok().and_then(|d| d.clone()) else {
+        return;
+    };
+    for frame in frames {
+        frame.decoded = dbc.decode(frame);
+    }
+}
+
+/// 每個收到的訊框在送往前端前依序經過的處理
+struct Pipeline {
+    frame_buffer: Arc<Mutex<FrameRing>>,
+    id_statistics: Arc<Mutex<IdStatistics>>,
+    counters: Arc<ChannelCounters>,
+    tx_inhibited: Arc<AtomicBool>,
+    bus_quality: Arc<Mutex<BusQualityConfig>>,
+    log_sink: Arc<Mutex<Option<LogSink>>>,
+    dbc: Arc<Mutex<Option<Arc<Dbc>>>>,
+    id_names: Arc<Mutex<Option<Arc<IdNames>>>>,
+    e2e_checks: Arc<Mutex<E2eChecks>>,
+    software_filters: Arc<Mutex<SoftwareFilters>>,
+    dedupe: Arc<Mutex<Option<Dedupe>>>,
+    config_history: Arc<ConfigHistory>,
+    frame_taps: Arc<Mutex<FrameTaps>>,
+    j1939: Arc<Mutex<J1939State>>,
+    /// 本批次重組完成的 J1939 多封包訊息，由接收迴圈送出
+    j1939_messages: Vec<J1939Message>,
+    auto_responder: Arc<Mutex<AutoResponder>>,
+    /// 本批次命中的自動回應規則，由接收迴圈送出回應
+    auto_responses: Vec<Arc<AutoResponseRule>>,
+    triggers: Arc<Mutex<TriggerTable>>,
+    trigger_events: Vec<TriggerEvent>,
+    captures: Arc<Mutex<Captures>>,
+    /// 本批次完成的觸發擷取，由接收迴圈送出 capture-complete
+    completed_captures: Vec<CaptureInfo>,
+    watchdogs: Arc<Mutex<BusWatchdogs>>,
+    /// 本批次讓沉默的通道恢復流量時的 bus-active 事件
+    bus_active: Option<BusActivityEvent>,
+    cycle_misses: Vec<CycleMissedEvent>,
+    emission: Arc<EmissionControl>,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+    ws_hub: Arc<WsHub>,
+    mqtt_feed: Arc<MqttFeed>,
+    delta_times: DeltaTimes,
+    clock: TimestampClock,
+    key: (u32, u32),
+    channel: u32,
+    _stream_guard: StreamGuard,
+}
+
+impl Pipeline {
+    fn new(app_state: &mut AppState, key: (u32, u32), channel: u32) -> Self {
+        if let Ok(mut watchdogs) = app_state.watchdogs.lock() {
+            watchdogs.restart(key, channel);
+        }
+        Self {
+            frame_buffer: app_state.frame_buffer.clone(),
+            id_statistics: app_state.id_statistics.clone(),
+            counters: app_state.channel_counters(key, channel),
+            tx_inhibited: app_state.channel_runtime(key, channel).tx_inhibited.clone(),
+            bus_quality: app_state.channel_runtime(key, channel).bus_quality.clone(),
+            log_sink: app_state.log_sink.clone(),
+            dbc: app_state.dbc.clone(),
+            id_names: app_state.id_names.clone(),
+            e2e_checks: app_state.e2e_checks.clone(),
+            software_filters: app_state.software_filters.clone(),
+            dedupe: app_state.channel_runtime(key, channel).dedupe.clone(),
+            config_history: app_state.channel_runtime(key, channel).config_history.clone(),
+            frame_taps: app_state.frame_taps.clone(),
+            j1939: app_state.j1939.clone(),
+            j1939_messages: Vec::new(),
+            auto_responder: app_state.auto_responder.clone(),
+            auto_responses: Vec::new(),
+            triggers: app_state.triggers.clone(),
+            trigger_events: Vec::new(),
+            captures: app_state.captures.clone(),
+            completed_captures: Vec::new(),
+            watchdogs: app_state.watchdogs.clone(),
+            bus_active: None,
+            cycle_misses: Vec::new(),
+            emission: app_state.emission_control(key, channel),
+            subscriptions: app_state.subscriptions.clone(),
+            ws_hub: app_state.ws_hub.clone(),
+            mqtt_feed: app_state.mqtt_feed.clone(),
+            delta_times: DeltaTimes::default(),
+            clock: TimestampClock::new(app_state.channel_runtime(key, channel).clock.clone()),
+            key,
+            channel,
+            _stream_guard: StreamGuard::new(app_state.frame_taps.clone(), key, channel),
+        }
+    }
+
+    fn reporter(&self, key: (u32, u32), channel: u32, interval: Duration) -> StatsReporter {
+        StatsReporter::new(key, channel, self.counters.clone(), self.tx_inhibited.clone(), interval)
+    }
+
+    fn quality_monitor(&self, key: (u32, u32), channel: u32) -> BusQualityMonitor {
+        BusQualityMonitor::new(key, channel, self.counters.clone(), self.bus_quality.clone())
+    }
+
+    fn process(&mut self, mut frames: Vec<CanFrameEvent>) -> Vec<BufferedFrame> {
+        let config_generation = self.config_history.current();
+        for frame in &mut frames {
+            frame.config_generation = config_generation;
+        }
+        self.clock.apply(&mut frames);
+        if let Ok(filters) = self.software_filters.lock() {
+            filters.apply(self.key, self.channel, &mut frames);
+        }
+        if let Ok(epoch) = self.frame_buffer.lock().map(|ring| ring.epoch()) {
+            let max_ids = self.id_statistics.lock().map_or(memory::DEFAULT_MAX_TRACKED_IDS, |stats| stats.max_ids());
+            self.delta_times.annotate(epoch, max_ids, &mut frames);
+        }
+        decode_frames(&self.dbc, &mut frames);
+        id_names::annotate(&self.id_names, &mut frames);
+        if let Ok(checks) = self.e2e_checks.lock() {
+            checks.annotate(&mut frames);
+        }
+        if let Ok(mut j1939) = self.j1939.lock() {
+            self.j1939_messages = j1939.process(self.key, self.channel, &mut frames);
+        }
+        if let Ok(responder) = self.auto_responder.lock() {
+            self.auto_responses = responder.matches(self.channel, &frames);
+        }
+        if let Ok(mut triggers) = self.triggers.lock() {
+            self.trigger_events = triggers.evaluate(self.channel, &frames);
+        }
+        if let Ok(mut captures) = self.captures.lock() {
+            self.completed_captures = captures.process(self.channel, &frames, &self.trigger_events);
+        }
+        if let Ok(mut watchdogs) = self.watchdogs.lock() {
+            self.bus_active = watchdogs.observe(self.key, self.channel, &frames);
+        }
+        // 重複的訊框仍計入統計與讀取端，只是不放進環形緩衝與事件流
+        let (suppressed, dedupe_log) = match self.dedupe.lock().as_deref_mut() {
+            Ok(Some(dedupe)) => (dedupe.apply(&frames), dedupe.apply_to_log),
+            _ => (vec![false; frames.len()], false),
+        };
+        let suppressed_count = suppressed.iter().filter(|&&s| s).count() as u64;
+        self.counters.suppressed.fetch_add(suppressed_count, Ordering::Relaxed);
+        self.counters.rx_frames.fetch_add(frames.len() as u64, Ordering::Relaxed);
+        for frame in &frames {
+            self.counters.add_bus_frame(frame.extended, frame.remote, frame.dlc);
+        }
+        if let Ok(mut stats) = self.id_statistics.lock() {
+            self.cycle_misses = frames.iter().filter_map(|frame| stats.record(frame)).collect();
+        }
+        if let Ok(mut taps) = self.frame_taps.lock() {
+            taps.dispatch(self.key, self.channel, &frames);
+        }
+        if let Ok(sink) = self.log_sink.lock() {
+            if let Some(sink) = sink.as_ref() {
+                for (frame, _) in frames.iter().zip(&suppressed).filter(|(_, &s)| !(s && dedupe_log)) {
+                    sink.log(Direction::Rx, frame);
+                }
+            }
+        }
+        match self.frame_buffer.lock() {
+            Ok(mut ring) => frames
+                .into_iter()
+                .zip(suppressed)
+                .filter(|(_, suppressed)| !suppressed)
+                .map(|(frame, _)| BufferedFrame { seq: ring.push(frame.clone()), frame })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// 只在確認裝置仍開啟時短暫持有 state 鎖；VCI_Receive 最多等待 500 ms，期間不可阻擋其他命令。
+/// 後端支援 VCI_GetReceiveNum 時先查詢待讀數量：沒有資料就不呼叫 VCI_Receive (probe_idle 時仍呼叫一次以偵測斷線)，
+/// 有資料時一次讀完
+fn receive_one(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    can_channel: u32,
+    busy_threshold: u32,
+    probe_idle: bool,
+    debug: bool,
+    blocking_wait: Option<Duration>,
+    counters: &ChannelCounters,
+) -> ReceiveOutcome {
+    let can_lib = {
+        let Ok(state_guard) = state.lock() else {
+            return ReceiveOutcome::Ended(StreamEndReason::StateUnavailable);
+        };
+        if !state_guard.devices.contains_key(&key) {
+            return ReceiveOutcome::Ended(StreamEndReason::DeviceClosed);
+        }
+        match state_guard.backend() {
+            Some(can_lib) => can_lib,
+            None => return ReceiveOutcome::Ended(StreamEndReason::LibraryUnloaded),
+        }
+    };
+    counters.receive_polls.fetch_add(1, Ordering::Relaxed);
+    // 不支援查詢時讀滿一批即視為還有積壓
+    // 低延遲模式不查詢待讀數，直接阻塞等待訊框
+    let pending = blocking_wait.is_none().then(|| can_lib.receive_num(key.0, key.1, can_channel));
+    let (max_frames, wait_ms, backlog_at, batch) = match (pending, blocking_wait) {
+        (Some(Ok(0)), _) if !probe_idle => return ReceiveOutcome::Empty,
+        (Some(Ok(pending)), _) => (pending.clamp(1, MAX_RECEIVE_FRAMES), 0, busy_threshold, MAX_RECEIVE_FRAMES),
+        (Some(Err(_)), _) | (None, None) => (STREAM_BATCH_FRAMES, 500, busy_threshold.min(STREAM_BATCH_FRAMES), STREAM_BATCH_FRAMES),
+        (None, Some(wait)) => (MAX_RECEIVE_FRAMES, wait.as_millis() as i32, busy_threshold, MAX_RECEIVE_FRAMES),
+    };
+    match read_frames(can_lib.as_ref(), key, can_channel, max_frames, wait_ms, debug) {
+        Ok(frames) if frames.is_empty() => ReceiveOutcome::Empty,
+        Ok(frames) => {
+            let received = frames.len() as u32;
+            ReceiveOutcome::Frames {
+                frames,
+                backlog: received >= backlog_at,
+                full: received >= batch,
+            }
+        }
+        Err(_) => ReceiveOutcome::Error,
+    }
+}
+
+/// 分段休眠，停止旗標被清除時提早返回
+fn sleep_unless_stopped(receiving: &AtomicBool, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while receiving.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+        std::thread::sleep((deadline - now).min(Duration::from_millis(STOP_POLL_MS)));
+    }
+}
+
+/// 呼叫 VCI_Receive 讀取最多 max_frames 個訊框。逾時沒有資料回傳空 Vec；
+/// DLL 回傳 -1 (裝置錯誤) 時回傳 Err，與「沒有收到資料」區分。keep_raw 時保留原始的 VCI_CAN_OBJ
+pub(crate) fn read_frames(
+    can_lib: &dyn CanInterface,
+    key: (u32, u32),
+    can_channel: u32,
+    max_frames: u32,
+    wait_ms: i32,
+    keep_raw: bool,
+) -> Result<Vec<CanFrameEvent>, i32> {
+    let (dev_type, dev_index) = key;
+    let received = can_lib.receive(dev_type, dev_index, can_channel, max_frames, wait_ms)?;
+    let host_timestamp = host_timestamp_us();
+    Ok(received
+        .into_iter()
+        .map(|can_obj| {
+            let frame = CanFrameEvent::from_raw(key, can_channel, &can_obj, host_timestamp);
+            CanFrameEvent {
+                raw: keep_raw.then_some(can_obj),
+                ..frame
+            }
+        })
+        .collect())
+}
+
+fn mark_disconnected(state: &Arc<StateMutex>, key: (u32, u32)) {
+    if let Ok(mut app_state) = state.lock() {
+        if let Some(device) = app_state.devices.get_mut(&key) {
+            device.disconnected = true;
+        }
+    }
+}
+
+/// 以指數退避反覆嘗試重新開啟裝置；receiving 旗標被清除 (stop_receiving_data) 或裝置被關閉時放棄
+fn reconnect_with_backoff(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    receiving: &AtomicBool,
+) -> Option<((u32, u32), u32)> {
+    let mut backoff = RECONNECT_INITIAL_BACKOFF_MS;
+    let mut attempts = 0;
+    let serial_number = state
+        .lock()
+        .ok()
+        .and_then(|app_state| app_state.devices.get(&key).and_then(|device| device.serial_number.clone()));
+    loop {
+        let mut waited = 0;
+        while waited < backoff {
+            if !receiving.load(Ordering::SeqCst) {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+            waited += 50;
+        }
+        attempts += 1;
+        let reset = state.lock().ok().and_then(|mut app_state| app_state.usb_reset_for_reconnect(key, attempts));
+        if let Some(reset) = reset {
+            // 重設後在鎖外等待裝置重新列舉，再照常重新開啟
+            println!("Reset USB after {} failed reconnect attempts", attempts - 1);
+            reset.wait(usb_reset::RECONNECT_RESET_TIMEOUT, receiving);
+        }
+        let result = match state.lock() {
+            Ok(mut app_state) => {
+                match app_state.devices.get(&key) {
+                    // 同一裝置的另一個通道執行緒已在新的 index 重新開啟；以序號找到後改用新的 key
+                    None => {
+                        return serial_number.as_ref().and_then(|serial| {
+                            app_state
+                                .devices
+                                .values()
+                                .find(|device| device.dev_type == key.0 && device.serial_number.as_ref() == Some(serial) && !device.disconnected)
+                                .map(|device| (device.key(), attempts))
+                        });
+                    }
+                    // 同一裝置的另一個通道執行緒已經完成重連
+                    Some(device) if !device.disconnected => return Some((key, attempts)),
+                    Some(_) => app_state.reopen_device(key),
+                }
+            }
+            Err(_) => return None,
+        };
+        match result {
+            Ok(new_key) => return Some((new_key, attempts)),
+            Err(error_message) => {
+                println!("Reconnect attempt {} failed: {}", attempts, error_message);
+                backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF_MS);
+            }
+        }
+    }
+}
+
+fn connection_event(key: (u32, u32), channel: u32, attempts: u32) -> ConnectionEvent {
+    ConnectionEvent {
+        dev_type: key.0,
+        dev_index: key.1,
+        channel,
+        attempts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+
+    /// 記錄送出的事件
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, serde_json::Value)>>>);
+
+    impl EventSink for Recorder {
+        fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) -> bool {
+            let payload = serde_json::to_value(payload).unwrap();
+            self.0.lock().unwrap().push((event.to_string(), payload));
+            true
+        }
+    }
+
+    impl Recorder {
+        fn named(&self, event: &str) -> Vec<serde_json::Value> {
+            self.0.lock().unwrap().iter().filter(|(name, _)| name == event).map(|(_, payload)| payload.clone()).collect()
+        }
+    }
+
+    /// 開啟一個有兩個通道的裝置
+    fn two_channel_state() -> AppState {
+        let mut app_state = AppState::with_interface(Arc::new(MockCan::new()));
+        app_state.open_device(4, 0, None).unwrap();
+        app_state.devices.get_mut(&(4, 0)).unwrap().channel_count = Some(2);
+        app_state
+    }
+
+    fn runtime_entries(app_state: &AppState) -> usize {
+        app_state.channel_runtime.len()
+    }
+
+    #[test]
+    fn emission_control_rejects_a_channel_the_device_does_not_have() {
+        let mut app_state = two_channel_state();
+        let before = runtime_entries(&app_state);
+
+        let error = app_state.checked_emission_control(Some(4), Some(0), 2).err().unwrap();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        assert_eq!(runtime_entries(&app_state), before);
+        assert!(app_state.checked_emission_control(Some(4), Some(0), 1).is_ok());
+    }
+
+    #[test]
+    fn emission_control_rejects_an_unopened_or_disconnected_device() {
+        let mut app_state = two_channel_state();
+        assert!(app_state.checked_emission_control(Some(4), Some(1), 0).is_err());
+
+        app_state.devices.get_mut(&(4, 0)).unwrap().disconnected = true;
+        let error = app_state.checked_emission_control(Some(4), Some(0), 0).err().unwrap();
+        assert!(error.contains("disconnected"), "{}", error);
+        assert_eq!(runtime_entries(&app_state), 0);
+    }
+
+    fn outcome_reason(outcome: ReceiveOutcome) -> Option<StreamEndReason> {
+        match outcome {
+            ReceiveOutcome::Ended(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn receive_one_ends_the_stream_when_the_device_or_backend_is_gone() {
+        let counters = ChannelCounters::default();
+        let state = Arc::new(StateMutex::new(two_channel_state()));
+        let closed = receive_one(&state, (4, 1), 0, DEFAULT_BUSY_THRESHOLD, true, false, None, &counters);
+        assert_eq!(outcome_reason(closed), Some(StreamEndReason::DeviceClosed));
+
+        *state.lock().unwrap().can_library.write().unwrap() = None;
+        let unloaded = receive_one(&state, (4, 0), 0, DEFAULT_BUSY_THRESHOLD, true, false, None, &counters);
+        assert_eq!(outcome_reason(unloaded), Some(StreamEndReason::LibraryUnloaded));
+        assert_eq!(counters.receive_polls.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn a_receive_loop_is_not_started_for_an_unknown_device_or_channel() {
+        let state = Arc::new(StateMutex::new(two_channel_state()));
+        let events = Recorder::default();
+        assert!(spawn_receive_loop(&state, events.clone(), Some(4), Some(1), 0, ReceiveOptions::default()).is_err());
+        let error = spawn_receive_loop(&state, events.clone(), Some(4), Some(0), 2, ReceiveOptions::default()).err().unwrap();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        assert!(state.lock().unwrap().devices[&(4, 0)].receiving.is_empty());
+        assert!(events.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_stopped_receive_loop_reports_stream_ended_once() {
+        let state = Arc::new(StateMutex::new(two_channel_state()));
+        let events = Recorder::default();
+        let (key, handle) = spawn_receive_loop(&state, events.clone(), Some(4), Some(0), 1, ReceiveOptions::default()).unwrap();
+        assert_eq!(key, (4, 0));
+        state.lock().unwrap().stop_receiving(Some(4), Some(0), Some(1)).unwrap();
+        handle.join().unwrap();
+
+        let ended = events.named("stream-ended");
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0], serde_json::json!({"dev_type": 4, "dev_index": 0, "channel": 1, "reason": "stopped"}));
+    }
+
+    #[test]
+    fn blocking_wait_defaults_to_100_ms_and_rejects_out_of_range_values() {
+        assert_eq!(ReceiveMode::Polling.blocking_wait(), Ok(None));
+        assert_eq!(ReceiveMode::Blocking { wait_ms: None }.blocking_wait(), Ok(Some(Duration::from_millis(100))));
+        assert_eq!(ReceiveMode::Blocking { wait_ms: Some(1000) }.blocking_wait(), Ok(Some(Duration::from_secs(1))));
+        for wait_ms in [0, 1001] {
+            let error = ReceiveMode::Blocking { wait_ms: Some(wait_ms) }.blocking_wait().unwrap_err();
+            assert!(error.starts_with("InvalidArgument { field: \"receive_mode.wait_ms\""), "{}", error);
+        }
+        let mode: ReceiveMode = serde_json::from_value(serde_json::json!({"kind": "blocking", "wait_ms": 20})).unwrap();
+        assert_eq!(mode.blocking_wait(), Ok(Some(Duration::from_millis(20))));
+        assert!(serde_json::from_value::<ReceiveMode>(serde_json::json!({"kind": "busy"})).is_err());
+    }
+
+    #[test]
+    fn blocking_receive_waits_for_a_frame_instead_of_querying_the_pending_count() {
+        let mock = Arc::new(MockCan::new());
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(4, 0, None).unwrap();
+        let state = Arc::new(StateMutex::new(app_state));
+        let counters = ChannelCounters::default();
+        let wait = Some(Duration::from_millis(20));
+
+        let started = Instant::now();
+        let empty = receive_one(&state, (4, 0), 0, DEFAULT_BUSY_THRESHOLD, false, false, wait, &counters);
+        assert!(matches!(empty, ReceiveOutcome::Empty));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+
+        let frame = VciCanObj {
+            id: 0x321,
+            data_len: 1,
+            ..Default::default()
+        };
+        mock.queue_receive(4, 0, 0, [frame]);
+        match receive_one(&state, (4, 0), 0, DEFAULT_BUSY_THRESHOLD, false, false, wait, &counters) {
+            ReceiveOutcome::Frames { frames, backlog, full } => {
+                assert_eq!(frames.iter().map(|f| f.id).collect::<Vec<_>>(), [0x321]);
+                assert!(!backlog && !full);
+            }
+            _ => panic!("expected the queued frame"),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use super::{parse_data_bytes, parse_number, parse_seconds, LineParser, ParsedLine, ReplayFrame};
+use crate::frame::Direction;
+
+/// Vector ASC 格式 (CANoe/CANalyzer 與 export_buffer 產生的檔案)：
+/// `   0.001234 1  18FEF100x       Rx   d 8 01 02 03 04 05 06 07 08`。
+/// 依 base 行使用 hex 或 dec 基底、absolute 或 relative 時間戳記；通道從 1 起算
+pub struct Asc {
+    hex: bool,
+    relative: bool,
+    /// relative 模式下累加的時間
+    elapsed_us: u64,
+}
+
+impl Default for Asc {
+    fn default() -> Self {
+        Self {
+            hex: true,
+            relative: false,
+            elapsed_us: 0,
+        }
+    }
+}
+
+const HEADERS: [&str; 5] = ["date ", "internal events logged", "no internal events logged", "begin triggerblock", "end triggerblock"];
+
+impl LineParser for Asc {
+    fn parse_line(&mut self, line: &str) -> Result<ParsedLine, String> {
+        let lower = line.to_ascii_lowercase();
+        if line.starts_with("//") || HEADERS.iter().any(|header| lower.starts_with(header)) {
+            return Ok(ParsedLine::Ignored);
+        }
+        if lower.starts_with("base ") {
+            self.parse_base(&lower)?;
+            return Ok(ParsedLine::Ignored);
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let time_us = parse_seconds(fields[0]).ok_or_else(|| format!("invalid timestamp {:?}", fields[0]))?;
+        let timestamp_us = if self.relative {
+            self.elapsed_us += time_us;
+            self.elapsed_us
+        } else {
+            time_us
+        };
+        match &fields[1..] {
+            ["Start", "of", "measurement", ..] => Ok(ParsedLine::Ignored),
+            ["CANFD", ..] => Err("CAN FD frames are not supported by the CANalyst-II".into()),
+            [_, event, ..] if event.eq_ignore_ascii_case("ErrorFrame") => Ok(ParsedLine::ErrorFrame),
+            [channel, id, direction, kind, rest @ ..] => self.parse_frame(timestamp_us, channel, id, direction, kind, rest),
+            _ => Err(format!("unsupported event {:?}", line)),
+        }
+    }
+}
+
+impl Asc {
+    /// `base hex  timestamps absolute`
+    fn parse_base(&mut self, lower: &str) -> Result<(), String> {
+        let fields: Vec<&str> = lower.split_whitespace().collect();
+        let ["base", base, "timestamps", timestamps, ..] = fields.as_slice() else {
+            return Err(format!("invalid base line {:?}", lower));
+        };
+        self.hex = match *base {
+            "hex" => true,
+            "dec" => false,
+            _ => return Err(format!("unknown base {:?}", base)),
+        };
+        self.relative = match *timestamps {
+            "absolute" => false,
+            "relative" => true,
+            _ => return Err(format!("unknown timestamp mode {:?}", timestamps)),
+        };
+        Ok(())
+    }
+
+    fn parse_frame(&self, timestamp_us: u64, channel: &str, id: &str, direction: &str, kind: &str, rest: &[&str]) -> Result<ParsedLine, String> {
+        let channel_number: u32 = channel
+            .parse()
+            .ok()
+            .filter(|&c| c > 0)
+            .ok_or_else(|| format!("invalid channel {:?}", channel))?;
+        let (id_text, extended) = match id.strip_suffix(['x', 'X']) {
+            Some(id_text) => (id_text, true),
+            None => (id, false),
+        };
+        let max_id = if extended { 0x1FFF_FFFF } else { 0x7FF };
+        let can_id = parse_number(id_text, self.hex)
+            .filter(|&can_id| can_id <= max_id)
+            .ok_or_else(|| format!("invalid CAN ID {:?}", id))?;
+        let direction = match direction {
+            "Rx" => Direction::Rx,
+            "Tx" => Direction::Tx,
+            _ => return Err(format!("invalid direction {:?}", direction)),
+        };
+        // DLC 在兩種基底都是一位數；遠端訊框的 DLC 可省略
+        let dlc = rest.first().and_then(|dlc| dlc.parse::<u8>().ok());
+        let (remote, dlc, data) = match kind {
+            "d" => {
+                let dlc = dlc.filter(|&dlc| dlc <= 8).ok_or_else(|| format!("invalid DLC {:?}", rest.first().unwrap_or(&"")))?;
+                (false, dlc, parse_data_bytes(&rest[1..], dlc, self.hex)?)
+            }
+            "r" => (true, dlc.filter(|&dlc| dlc <= 8).unwrap_or(0), Vec::new()),
+            _ => return Err(format!("unknown frame type {:?}", kind)),
+        };
+        Ok(ParsedLine::Frame(ReplayFrame {
+            timestamp_us,
+            interface: format!("CAN{}", channel_number),
+            direction: Some(direction),
+            id: can_id,
+            extended,
+            remote,
+            dlc,
+            data,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(parser: &mut Asc, line: &str) -> ReplayFrame {
+        match parser.parse_line(line) {
+            Ok(ParsedLine::Frame(frame)) => frame,
+            Ok(_) => panic!("{:?} is not a frame", line),
+            Err(error) => panic!("{:?}: {}", line, error),
+        }
+    }
+
+    fn error(line: &str) -> String {
+        match Asc::default().parse_line(line) {
+            Err(error) => error,
+            Ok(_) => panic!("{:?} parsed", line),
+        }
+    }
+
+    #[test]
+    fn data_and_remote_frames_parse_with_the_default_hex_absolute_base() {
+        let mut parser = Asc::default();
+        let data = frame(
+            &mut parser,
+            "0.001234 1  18FEF100x       Rx   d 8 01 02 03 04 05 06 07 FF  Length = 272000 BitCount = 141",
+        );
+        assert_eq!(
+            (data.timestamp_us, data.interface.as_str(), data.direction),
+            (1234, "CAN1", Some(Direction::Rx))
+        );
+        assert_eq!((data.id, data.extended, data.remote, data.dlc), (0x18FE_F100, true, false, 8));
+        assert_eq!(data.data, [1, 2, 3, 4, 5, 6, 7, 0xFF]);
+
+        // 遠端訊框的 DLC 可省略
+        let remote = frame(&mut parser, "2.5 2 7FF Tx r");
+        assert_eq!((remote.timestamp_us, remote.interface.as_str(), remote.id), (2_500_000, "CAN2", 0x7FF));
+        assert_eq!((remote.extended, remote.remote, remote.dlc, remote.data.len()), (false, true, 0, 0));
+        assert_eq!(frame(&mut parser, "3.0 1 100 Rx r 4").dlc, 4);
+    }
+
+    #[test]
+    fn a_decimal_relative_base_changes_ids_data_and_timestamps() {
+        let mut parser = Asc::default();
+        assert!(matches!(parser.parse_line("base dec  timestamps relative"), Ok(ParsedLine::Ignored)));
+        let first = frame(&mut parser, "0.5 1 291 Rx d 2 10 255");
+        assert_eq!((first.timestamp_us, first.id, first.data), (500_000, 291, vec![10, 255]));
+        assert_eq!(frame(&mut parser, "0.25 1 2047 Rx d 0").timestamp_us, 750_000);
+    }
+
+    #[test]
+    fn headers_comments_and_error_frames_are_not_frames() {
+        let mut parser = Asc::default();
+        for line in [
+            "// version 13.0.0",
+            "date Mon Oct 14 07:36:11.000 am 2026",
+            "Begin TriggerBlock",
+            "0.000000 Start of measurement",
+            "End TriggerBlock",
+        ] {
+            assert!(matches!(parser.parse_line(line), Ok(ParsedLine::Ignored)), "{:?}", line);
+        }
+        assert!(matches!(parser.parse_line("1.000000 1 ErrorFrame"), Ok(ParsedLine::ErrorFrame)));
+    }
+
+    #[test]
+    fn malformed_lines_say_which_field_is_wrong() {
+        assert_eq!(error("abc 1 123 Rx d 0"), "invalid timestamp \"abc\"");
+        assert_eq!(error("1.0 0 123 Rx d 0"), "invalid channel \"0\"");
+        assert_eq!(error("1.0 1 800 Rx d 0"), "invalid CAN ID \"800\"");
+        assert_eq!(error("1.0 1 20000000x Rx d 0"), "invalid CAN ID \"20000000x\"");
+        assert_eq!(error("1.0 1 123 Up d 0"), "invalid direction \"Up\"");
+        assert_eq!(error("1.0 1 123 Rx d 9"), "invalid DLC \"9\"");
+        assert_eq!(error("1.0 1 123 Rx d"), "invalid DLC \"\"");
+        assert_eq!(error("1.0 1 123 Rx d 8 01 02"), "expected 8 data bytes, got 2");
+        assert_eq!(error("1.0 1 123 Rx d 1 1FF"), "invalid data byte \"1FF\"");
+        assert_eq!(error("1.0 1 123 Rx q 0"), "unknown frame type \"q\"");
+        assert_eq!(error("1.0 1 123"), "unsupported event \"1.0 1 123\"");
+        assert_eq!(
+            error("1.0 CANFD 1 Rx 123 1 0 8 8 00"),
+            "CAN FD frames are not supported by the CANalyst-II"
+        );
+    }
+
+    #[test]
+    fn malformed_base_lines_are_rejected() {
+        assert_eq!(error("base oct timestamps absolute"), "unknown base \"oct\"");
+        assert_eq!(error("base hex timestamps sometimes"), "unknown timestamp mode \"sometimes\"");
+        assert_eq!(error("base hex"), "invalid base line \"base hex\"");
+    }
+}
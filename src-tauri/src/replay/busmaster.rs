@@ -0,0 +1,216 @@
+use super::{parse_data_bytes, parse_fraction_us, parse_number, LineParser, ParsedLine, ReplayFrame};
+use crate::frame::Direction;
+
+const DAY_US: u64 = 24 * 3600 * 1_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TimeMode {
+    /// 電腦的時鐘時間
+    System,
+    /// 從開始記錄起算
+    Absolute,
+    /// 距前一個訊框
+    Relative,
+}
+
+/// BusMaster 記錄檔：`13:43:59:4792 Rx 1 0x0C1 s 8 00 11 22 33 44 55 66 77`。
+/// 依 ***HEX***/***DEC*** 與 ***SYSTEM/ABSOLUTE/RELATIVE MODE*** 檔頭解析；型別為 s/x，
+/// 遠端訊框加 r，ID 欄為 ERR 的錯誤訊框略過
+pub struct BusMaster {
+    hex: bool,
+    mode: TimeMode,
+    elapsed_us: u64,
+    last_time_us: Option<u64>,
+    /// 時鐘時間跨過午夜的次數換算的偏移
+    day_offset_us: u64,
+}
+
+impl Default for BusMaster {
+    fn default() -> Self {
+        Self {
+            hex: true,
+            mode: TimeMode::System,
+            elapsed_us: 0,
+            last_time_us: None,
+            day_offset_us: 0,
+        }
+    }
+}
+
+/// `hh:mm:ss:ffff`，最後一欄為 0.1 ms 單位的秒小數
+pub fn parse_time(text: &str) -> Option<u64> {
+    let mut parts = text.split(':');
+    let (Some(h), Some(m), Some(s), Some(frac), None) = (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+    let field = |part: &str| part.parse::<u64>().ok().filter(|_| part.bytes().all(|b| b.is_ascii_digit()));
+    Some(((field(h)? * 60 + field(m)?) * 60 + field(s)?) * 1_000_000 + parse_fraction_us(frac).filter(|_| !frac.is_empty())?)
+}
+
+impl LineParser for BusMaster {
+    fn parse_line(&mut self, line: &str) -> Result<ParsedLine, String> {
+        if let Some(header) = line.strip_prefix("***") {
+            match header.trim_end_matches('*').to_ascii_uppercase().as_str() {
+                "HEX" => self.hex = true,
+                "DEC" => self.hex = false,
+                "SYSTEM MODE" => self.mode = TimeMode::System,
+                "ABSOLUTE MODE" => self.mode = TimeMode::Absolute,
+                "RELATIVE MODE" => self.mode = TimeMode::Relative,
+                _ => {}
+            }
+            return Ok(ParsedLine::Ignored);
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [time, direction, channel, id, kind, rest @ ..] = fields.as_slice() else {
+            return Err(format!("expected \"time direction channel id type dlc data\", got {:?}", line));
+        };
+        let time_us = parse_time(time).ok_or_else(|| format!("invalid timestamp {:?}", time))?;
+        let timestamp_us = self.timestamp_us(time_us);
+        if id.eq_ignore_ascii_case("ERR") || kind.eq_ignore_ascii_case("ERR") {
+            return Ok(ParsedLine::ErrorFrame);
+        }
+        let direction = match direction.to_ascii_lowercase().as_str() {
+            "rx" => Direction::Rx,
+            "tx" => Direction::Tx,
+            _ => return Err(format!("invalid direction {:?}", direction)),
+        };
+        let channel_number: u32 = channel
+            .parse()
+            .ok()
+            .filter(|&c| c > 0)
+            .ok_or_else(|| format!("invalid channel {:?}", channel))?;
+        let (extended, remote) = match kind.to_ascii_lowercase().as_str() {
+            "s" => (false, false),
+            "x" => (true, false),
+            "sr" => (false, true),
+            "xr" => (true, true),
+            _ => return Err(format!("unknown frame type {:?}", kind)),
+        };
+        let id_text = id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")).unwrap_or(id);
+        let max_id = if extended { 0x1FFF_FFFF } else { 0x7FF };
+        let can_id = parse_number(id_text, self.hex)
+            .filter(|&can_id| can_id <= max_id)
+            .ok_or_else(|| format!("invalid CAN ID {:?}", id))?;
+        let dlc = rest
+            .first()
+            .and_then(|dlc| dlc.parse::<u8>().ok())
+            .filter(|&dlc| dlc <= 8)
+            .ok_or_else(|| format!("invalid DLC {:?}", rest.first().unwrap_or(&"")))?;
+        let data = if remote { Vec::new() } else { parse_data_bytes(&rest[1..], dlc, self.hex)? };
+        Ok(ParsedLine::Frame(ReplayFrame {
+            timestamp_us,
+            interface: format!("CAN{}", channel_number),
+            direction: Some(direction),
+            id: can_id,
+            extended,
+            remote,
+            dlc,
+            data,
+        }))
+    }
+}
+
+impl BusMaster {
+    fn timestamp_us(&mut self, time_us: u64) -> u64 {
+        if self.mode == TimeMode::Relative {
+            self.elapsed_us += time_us;
+            return self.elapsed_us;
+        }
+        // 時間倒退超過半天表示跨過午夜
+        if self.last_time_us.is_some_and(|last| last > time_us + DAY_US / 2) {
+            self.day_offset_us += DAY_US;
+        }
+        self.last_time_us = Some(time_us);
+        time_us + self.day_offset_us
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(parser: &mut BusMaster, line: &str) -> ReplayFrame {
+        match parser.parse_line(line) {
+            Ok(ParsedLine::Frame(frame)) => frame,
+            Ok(_) => panic!("{:?} is not a frame", line),
+            Err(error) => panic!("{:?}: {}", line, error),
+        }
+    }
+
+    fn error(line: &str) -> String {
+        match BusMaster::default().parse_line(line) {
+            Err(error) => error,
+            Ok(_) => panic!("{:?} parsed", line),
+        }
+    }
+
+    #[test]
+    fn times_are_hours_minutes_seconds_and_tenths_of_milliseconds() {
+        assert_eq!(parse_time("13:43:59:4792"), Some(((13 * 60 + 43) * 60 + 59) * 1_000_000 + 479_200));
+        assert_eq!(parse_time("00:00:00:1"), Some(100_000));
+        for text in [
+            "13:43:59",
+            "13:43:59:",
+            "13:43:59:4792:1",
+            "1a:00:00:0000",
+            "+1:00:00:0000",
+            "00:00:00:-1",
+        ] {
+            assert_eq!(parse_time(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn standard_extended_and_remote_frames_parse() {
+        let mut parser = BusMaster::default();
+        let standard = frame(&mut parser, "13:43:59:4792 Rx 1 0x0C1 s 8 00 11 22 33 44 55 66 77");
+        assert_eq!(
+            (standard.interface.as_str(), standard.direction, standard.id, standard.extended),
+            ("CAN1", Some(Direction::Rx), 0xC1, false)
+        );
+        assert_eq!(standard.data, [0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77]);
+        let remote = frame(&mut parser, "13:44:00:0000 TX 2 0x18FEF100 XR 8");
+        assert_eq!(
+            (remote.direction, remote.id, remote.extended, remote.remote, remote.dlc),
+            (Some(Direction::Tx), 0x18FE_F100, true, true, 8)
+        );
+        assert!(remote.data.is_empty());
+        assert!(matches!(parser.parse_line("13:44:00:1000 Rx 1 ERR s 0"), Ok(ParsedLine::ErrorFrame)));
+    }
+
+    #[test]
+    fn headers_switch_the_base_and_time_mode() {
+        let mut parser = BusMaster::default();
+        for header in ["***DEC***", "***RELATIVE MODE***", "***BUSMASTER Ver 3.2.2***"] {
+            assert!(matches!(parser.parse_line(header), Ok(ParsedLine::Ignored)));
+        }
+        let first = frame(&mut parser, "00:00:01:0000 Rx 1 193 s 2 17 255");
+        assert_eq!((first.timestamp_us, first.id, first.data), (1_000_000, 193, vec![17, 255]));
+        assert_eq!(frame(&mut parser, "00:00:00:5000 Rx 1 193 s 0").timestamp_us, 1_500_000);
+    }
+
+    #[test]
+    fn clock_times_continue_past_midnight() {
+        let mut parser = BusMaster::default();
+        assert_eq!(frame(&mut parser, "23:59:59:9000 Rx 1 0x100 s 0").timestamp_us, DAY_US - 100_000);
+        assert_eq!(frame(&mut parser, "00:00:00:1000 Rx 1 0x100 s 0").timestamp_us, DAY_US + 100_000);
+        // 小幅倒退不算跨日
+        assert_eq!(frame(&mut parser, "00:00:00:0500 Rx 1 0x100 s 0").timestamp_us, DAY_US + 50_000);
+    }
+
+    #[test]
+    fn malformed_lines_say_which_field_is_wrong() {
+        assert_eq!(
+            error("13:43:59:4792 Rx 1 0x0C1"),
+            "expected \"time direction channel id type dlc data\", got \"13:43:59:4792 Rx 1 0x0C1\""
+        );
+        assert_eq!(error("13:43:59 Rx 1 0x0C1 s 0"), "invalid timestamp \"13:43:59\"");
+        assert_eq!(error("13:43:59:4792 Up 1 0x0C1 s 0"), "invalid direction \"Up\"");
+        assert_eq!(error("13:43:59:4792 Rx 0 0x0C1 s 0"), "invalid channel \"0\"");
+        assert_eq!(error("13:43:59:4792 Rx 1 0x0C1 q 0"), "unknown frame type \"q\"");
+        assert_eq!(error("13:43:59:4792 Rx 1 0x800 s 0"), "invalid CAN ID \"0x800\"");
+        assert_eq!(error("13:43:59:4792 Rx 1 0x0C1 s 9"), "invalid DLC \"9\"");
+        assert_eq!(error("13:43:59:4792 Rx 1 0x0C1 s"), "invalid DLC \"\"");
+        assert_eq!(error("13:43:59:4792 Rx 1 0x0C1 s 2 00"), "expected 2 data bytes, got 1");
+    }
+}
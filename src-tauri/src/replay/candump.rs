@@ -0,0 +1,77 @@
+use super::{LineParser, ParsedLine, ReplayFrame};
+
+/// `candump -l` 格式：`(1699999999.123456) can0 123#DEADBEEF`
+pub struct Candump;
+
+impl LineParser for Candump {
+    fn parse_line(&mut self, line: &str) -> Result<ParsedLine, String> {
+        parse_line(line)
+    }
+}
+
+fn parse_line(line: &str) -> Result<ParsedLine, String> {
+    let mut fields = line.split_whitespace();
+    let (Some(timestamp), Some(interface), Some(frame), None) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+        return Err(format!("expected \"(timestamp) interface frame\", got {:?}", line));
+    };
+    let timestamp_us = parse_timestamp(timestamp)?;
+    let (id_text, payload) = frame.split_once('#').ok_or_else(|| format!("missing '#' in {:?}", frame))?;
+    if payload.starts_with('#') {
+        return Err("CAN FD frames are not supported by the CANalyst-II".into());
+    }
+    let id = u32::from_str_radix(id_text, 16).map_err(|_| format!("invalid CAN ID {:?}", id_text))?;
+    let extended = match id_text.len() {
+        3 if id <= 0x7FF => false,
+        8 if id & 0x2000_0000 != 0 => return Ok(ParsedLine::ErrorFrame),
+        8 if id <= 0x1FFF_FFFF => true,
+        _ => return Err(format!("invalid CAN ID {:?}", id_text)),
+    };
+    let (remote, dlc, data) = match payload.strip_prefix(['R', 'r']) {
+        Some(len) => {
+            let dlc = if len.is_empty() {
+                0
+            } else {
+                len.parse::<u8>().ok().filter(|&l| l <= 8).ok_or_else(|| format!("invalid RTR length {:?}", len))?
+            };
+            (true, dlc, Vec::new())
+        }
+        None => {
+            let data = parse_data(payload)?;
+            (false, data.len() as u8, data)
+        }
+    };
+    Ok(ParsedLine::Frame(ReplayFrame {
+        timestamp_us,
+        interface: interface.to_string(),
+        direction: None,
+        id,
+        extended,
+        remote,
+        dlc,
+        data,
+    }))
+}
+
+fn parse_timestamp(text: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid timestamp {:?}", text);
+    let inner = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')).ok_or_else(invalid)?;
+    let (secs, frac) = inner.split_once('.').unwrap_or((inner, "0"));
+    if frac.is_empty() || frac.len() > 6 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let secs: u64 = secs.parse().map_err(|_| invalid())?;
+    let micros: u64 = format!("{:0<6}", frac).parse().map_err(|_| invalid())?;
+    Ok(secs * 1_000_000 + micros)
+}
+
+/// 資料為連續的十六進位位元組，允許 cansend 使用的 '.' 分隔
+fn parse_data(text: &str) -> Result<Vec<u8>, String> {
+    let hex: String = text.chars().filter(|&c| c != '.').collect();
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) || hex.len() > 16 {
+        return Err(format!("invalid data {:?}", text));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("invalid data {:?}", text)))
+        .collect()
+}
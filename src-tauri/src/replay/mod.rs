@@ -0,0 +1,439 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::frame::{checked_frames, Direction, FrameInput, Provenance};
+use crate::operation::OperationKind;
+use crate::supervisor;
+use crate::tx_limit;
+use crate::{DeviceType, StateMutex};
+
+mod asc;
+mod busmaster;
+mod candump;
+
+/// 停止旗標的檢查間隔；等待下一個訊框時以此為單位分段睡眠
+const STOP_POLL_MS: u64 = 10;
+/// 載入摘要最多列出的無法解析行數
+const MAX_UNPARSED_LINES: usize = 100;
+
+/// 從記錄檔載入、等待重播的訊框；各種格式都轉成這個表示
+#[derive(Clone, Debug)]
+pub struct ReplayFrame {
+    /// 記錄檔的時間；只有訊框之間的差距有意義
+    pub timestamp_us: u64,
+    /// candump 的介面名稱 (can0)；ASC 與 BusMaster 為 CAN1、CAN2…
+    pub interface: String,
+    /// candump 沒有方向欄位，為 None
+    pub direction: Option<Direction>,
+    pub id: u32,
+    pub extended: bool,
+    pub remote: bool,
+    pub dlc: u8,
+    pub data: Vec<u8>,
+}
+
+impl ReplayFrame {
+    fn to_input(&self) -> FrameInput {
+        FrameInput {
+            id: self.id,
+            extended: Some(self.extended),
+            remote: self.remote,
+            dlc: Some(self.dlc),
+            data: self.data.clone(),
+            padding: None,
+        }
+    }
+}
+
+/// 執行中的重播；記下通道，重新初始化通道前可以先停止
+pub(crate) struct ActiveReplay {
+    pub key: (u32, u32),
+    pub channel: u32,
+    pub running: Arc<AtomicBool>,
+}
+
+/// 記錄檔的格式，載入時由內容判斷
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogDialect {
+    Candump,
+    Asc,
+    Busmaster,
+}
+
+/// 一行的解析結果；檔頭、註解與非訊框事件為 Ignored
+enum ParsedLine {
+    Frame(ReplayFrame),
+    ErrorFrame,
+    Ignored,
+}
+
+/// 各格式的逐行解析器；傳入的行已去除前後空白，空行不會傳入
+trait LineParser {
+    fn parse_line(&mut self, line: &str) -> Result<ParsedLine, String>;
+}
+
+/// 無法解析的一行，line 從 1 起算
+#[derive(Serialize, Clone, Debug)]
+pub struct UnparsedLine {
+    pub line: usize,
+    pub text: String,
+    pub error: String,
+}
+
+/// 記錄檔的解析結果；無法解析的行只會列出，不會讓整個檔案失敗
+#[derive(Debug)]
+pub struct ParsedLog {
+    pub dialect: LogDialect,
+    /// 依時間排序，同一時間的訊框維持檔案中的順序
+    pub frames: Vec<ReplayFrame>,
+    /// 略過的錯誤訊框數
+    pub error_frames: usize,
+    pub unparsed: Vec<UnparsedLine>,
+}
+
+/// 依前幾行內容判斷格式：BusMaster 的 *** 檔頭或 hh:mm:ss:ffff 時間、candump 的 (timestamp)、
+/// ASC 的 date/base 檔頭或以秒數開頭的事件行；都不符合時當作 candump
+pub fn detect_dialect(text: &str) -> LogDialect {
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("//")).take(50) {
+        let first = line.split_whitespace().next().unwrap_or_default();
+        let lower = line.to_ascii_lowercase();
+        if line.starts_with("***") || busmaster::parse_time(first).is_some() {
+            return LogDialect::Busmaster;
+        }
+        if line.starts_with('(') {
+            return LogDialect::Candump;
+        }
+        if ["date ", "base ", "begin triggerblock"].iter().any(|prefix| lower.starts_with(prefix)) || parse_seconds(first).is_some() {
+            return LogDialect::Asc;
+        }
+    }
+    LogDialect::Candump
+}
+
+/// 解析 candump、Vector ASC 或 BusMaster 記錄檔，格式由 detect_dialect() 判斷
+pub fn parse_log(text: &str) -> ParsedLog {
+    let dialect = detect_dialect(text);
+    let mut parser: Box<dyn LineParser> = match dialect {
+        LogDialect::Candump => Box::new(candump::Candump),
+        LogDialect::Asc => Box::new(asc::Asc::default()),
+        LogDialect::Busmaster => Box::new(busmaster::BusMaster::default()),
+    };
+    let mut frames = Vec::new();
+    let mut error_frames = 0;
+    let mut unparsed = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parser.parse_line(line) {
+            Ok(ParsedLine::Frame(frame)) => frames.push(frame),
+            Ok(ParsedLine::ErrorFrame) => error_frames += 1,
+            Ok(ParsedLine::Ignored) => {}
+            Err(error) => unparsed.push(UnparsedLine {
+                line: index + 1,
+                text: line.to_string(),
+                error,
+            }),
+        }
+    }
+    frames.sort_by_key(|f| f.timestamp_us);
+    ParsedLog {
+        dialect,
+        frames,
+        error_frames,
+        unparsed,
+    }
+}
+
+/// "12.345678" 形式的秒數，小數超過 6 位時捨去
+fn parse_seconds(text: &str) -> Option<u64> {
+    let (secs, frac) = text.split_once('.').unwrap_or((text, ""));
+    if secs.is_empty() || !secs.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(secs.parse::<u64>().ok()? * 1_000_000 + parse_fraction_us(frac)?)
+}
+
+/// 秒的小數部分換成微秒
+fn parse_fraction_us(frac: &str) -> Option<u64> {
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let micros: String = frac.chars().chain(std::iter::repeat('0')).take(6).collect();
+    micros.parse().ok()
+}
+
+/// 依檔案宣告的基底解析 ID 或資料位元組
+fn parse_number(text: &str, hex: bool) -> Option<u32> {
+    u32::from_str_radix(text, if hex { 16 } else { 10 }).ok()
+}
+
+/// dlc 個資料位元組；多出的欄位 (例如 ASC 行尾的 Length = …) 不理會
+fn parse_data_bytes(fields: &[&str], dlc: u8, hex: bool) -> Result<Vec<u8>, String> {
+    let data: Vec<u8> = fields
+        .iter()
+        .take(dlc as usize)
+        .map(|field| parse_number(field, hex).and_then(|byte| u8::try_from(byte).ok()).ok_or_else(|| format!("invalid data byte {:?}", field)))
+        .collect::<Result<_, _>>()?;
+    if data.len() < dlc as usize {
+        return Err(format!("expected {} data bytes, got {}", dlc, data.len()));
+    }
+    Ok(data)
+}
+
+pub struct LoadedLog {
+    path: String,
+    parsed: ParsedLog,
+}
+
+#[derive(Serialize)]
+pub struct LoadedLogSummary {
+    pub path: String,
+    pub dialect: LogDialect,
+    pub frame_count: usize,
+    pub duration_ms: f64,
+    pub unique_ids: usize,
+    pub interfaces: Vec<String>,
+    pub error_frames_skipped: usize,
+    /// 無法解析的行，最多列出 100 行
+    pub unparsed_lines: Vec<UnparsedLine>,
+    pub unparsed_count: usize,
+}
+
+impl LoadedLog {
+    fn summary(&self) -> LoadedLogSummary {
+        let frames = &self.parsed.frames;
+        let duration_us = match (frames.first(), frames.last()) {
+            (Some(first), Some(last)) => last.timestamp_us.saturating_sub(first.timestamp_us),
+            _ => 0,
+        };
+        let unique_ids: HashSet<(u32, bool)> = frames.iter().map(|f| (f.id, f.extended)).collect();
+        let mut interfaces: Vec<String> = Vec::new();
+        for frame in frames {
+            if !interfaces.contains(&frame.interface) {
+                interfaces.push(frame.interface.clone());
+            }
+        }
+        LoadedLogSummary {
+            path: self.path.clone(),
+            dialect: self.parsed.dialect,
+            frame_count: frames.len(),
+            duration_ms: duration_us as f64 / 1000.0,
+            unique_ids: unique_ids.len(),
+            interfaces,
+            error_frames_skipped: self.parsed.error_frames,
+            unparsed_lines: self.parsed.unparsed.iter().take(MAX_UNPARSED_LINES).cloned().collect(),
+            unparsed_count: self.parsed.unparsed.len(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ReplayOptions {
+    /// 只重播記錄中指定介面 (例如 "can0"、"CAN1") 的訊框；省略時重播全部
+    pub interface: Option<String>,
+    /// 只重播記錄中指定方向的訊框；沒有方向欄位的 candump 訊框不受影響
+    pub direction: Option<Direction>,
+    pub timing: ReplayTiming,
+    /// original 模式的播放倍速，2.0 表示間隔減半
+    pub speed: Option<f64>,
+    /// 重播次數，0 表示無限循環；預設 1
+    pub loop_count: Option<u32>,
+}
+
+/// 訊框之間的間隔："original" 依記錄時間、{"fixed": ms} 固定間隔、"max" 不等待
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayTiming {
+    #[default]
+    Original,
+    Fixed(f64),
+    Max,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ReplayProgress {
+    pub frames_sent: u64,
+    /// 無限循環時為 None
+    pub total_frames: Option<u64>,
+    pub current_loop: u32,
+    pub remaining_ms: Option<f64>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ReplayFinished {
+    pub frames_sent: u64,
+    pub stopped: bool,
+    pub error: Option<String>,
+}
+
+/// 載入 candump、Vector ASC 或 BusMaster 記錄檔供重播，回傳摘要讓前端預覽。
+/// 摘要包含判斷出的格式、略過的錯誤訊框數與無法解析的行 (附行號)
+#[tauri::command]
+pub fn load_log_file(path: String, state: State<Arc<StateMutex>>) -> Result<LoadedLogSummary, String> {
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let log = LoadedLog {
+        path,
+        parsed: parse_log(&text),
+    };
+    let summary = log.summary();
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state.replay_log = Some(Arc::new(log));
+    Ok(summary)
+}
+
+/// 在背景重播載入的記錄檔，回傳 operation_id；可用 stop_replay 或 cancel_operation 停止
+#[tauri::command]
+pub fn start_replay(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    options: Option<ReplayOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    let options = options.unwrap_or_default();
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    if app_state.replay.is_some() {
+        return Err("Replay already running".into());
+    }
+    let key = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key();
+    let log = app_state.replay_log.clone().ok_or("No log file loaded")?;
+    let frames: Vec<ReplayFrame> = log
+        .parsed
+        .frames
+        .iter()
+        .filter(|f| options.interface.as_ref().is_none_or(|i| i == &f.interface))
+        .filter(|f| options.direction.is_none_or(|d| f.direction.is_none_or(|direction| direction == d)))
+        .cloned()
+        .collect();
+    if frames.is_empty() {
+        return Err("No frames to replay".into());
+    }
+    let inputs: Vec<FrameInput> = frames.iter().map(ReplayFrame::to_input).collect();
+    checked_frames("frames", &inputs)?;
+    let loop_count = options.loop_count.unwrap_or(1);
+    let total_frames = (loop_count > 0).then(|| frames.len() as u64 * loop_count as u64);
+    let operation = app_state.start_operation(OperationKind::Replay, key, channel, total_frames);
+    let running = operation.running().clone();
+    app_state.replay = Some(ActiveReplay {
+        key,
+        channel,
+        running: running.clone(),
+    });
+    drop(app_state);
+
+    let state = state.inner().clone();
+    let operation_id = operation.id();
+    operation.announce(&state, &app_handle);
+    let timing = options.timing;
+    let speed = options.speed.filter(|&s| s > 0.0).unwrap_or(1.0);
+    std::thread::spawn(move || {
+        let mut frames_sent = 0;
+        let finished = supervisor::guard("replay", format!("CAN{}", channel + 1), || {
+            run_replay(&frames, timing, speed, loop_count, &running, |frame| {
+                let can_obj = frame.to_input().to_can_obj()?;
+                tx_limit::transmit_paced_as(&state, key, channel, &[can_obj], true, Provenance::TxReplay)?;
+                frames_sent += 1;
+                operation.set_progress(frames_sent);
+                Ok(())
+            }, |progress| {
+                let _ = app_handle.emit("replay-progress", progress);
+            })
+        });
+        let finished = finished.unwrap_or_else(|| {
+            ReplayFinished {
+                frames_sent: 0,
+                stopped: true,
+                error: Some("replay thread panicked".into()),
+            }
+        });
+        if let Ok(mut app_state) = state.lock() {
+            if app_state.replay.as_ref().is_some_and(|r| Arc::ptr_eq(&r.running, &running)) {
+                app_state.replay = None;
+            }
+        }
+        operation.finish(&state, &app_handle, finished.error.clone());
+        let _ = app_handle.emit("replay-finished", finished);
+    });
+    Ok(operation_id)
+}
+
+/// 依 timing 排程逐一送出訊框；每次最多睡 STOP_POLL_MS，讓停止請求能在一個訊框間隔內生效
+fn run_replay(
+    frames: &[ReplayFrame],
+    timing: ReplayTiming,
+    speed: f64,
+    loop_count: u32,
+    running: &AtomicBool,
+    mut send: impl FnMut(&ReplayFrame) -> Result<(), String>,
+    mut progress: impl FnMut(ReplayProgress),
+) -> ReplayFinished {
+    let first_us = frames[0].timestamp_us;
+    let total_frames = (loop_count > 0).then(|| frames.len() as u64 * loop_count as u64);
+    let started = Instant::now();
+    let mut last_progress = Instant::now();
+    let mut frames_sent = 0u64;
+    let mut current_loop = 0;
+    while loop_count == 0 || current_loop < loop_count {
+        let loop_start = started.elapsed();
+        for frame in frames {
+            let due = match timing {
+                ReplayTiming::Original => loop_start + Duration::from_secs_f64((frame.timestamp_us - first_us) as f64 / 1e6 / speed),
+                // 以整體已送出數計算，跨輪次時間隔也維持固定
+                ReplayTiming::Fixed(interval_ms) => Duration::from_secs_f64(frames_sent as f64 * interval_ms.max(0.0) / 1000.0),
+                ReplayTiming::Max => Duration::ZERO,
+            };
+            while running.load(Ordering::SeqCst) && started.elapsed() < due {
+                std::thread::sleep((due - started.elapsed()).min(Duration::from_millis(STOP_POLL_MS)));
+            }
+            if !running.load(Ordering::SeqCst) {
+                return ReplayFinished {
+                    frames_sent,
+                    stopped: true,
+                    error: None,
+                };
+            }
+            if let Err(error_message) = send(frame) {
+                return ReplayFinished {
+                    frames_sent,
+                    stopped: true,
+                    error: Some(error_message),
+                };
+            }
+            frames_sent += 1;
+            if last_progress.elapsed() >= Duration::from_secs(1) {
+                last_progress = Instant::now();
+                let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+                progress(ReplayProgress {
+                    frames_sent,
+                    total_frames,
+                    current_loop,
+                    remaining_ms: total_frames.map(|total| elapsed_ms * (total - frames_sent) as f64 / frames_sent as f64),
+                });
+            }
+        }
+        current_loop += 1;
+    }
+    ReplayFinished {
+        frames_sent,
+        stopped: false,
+        error: None,
+    }
+}
+
+#[tauri::command]
+pub fn stop_replay(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let replay = app_state.replay.take().ok_or("Replay is not running")?;
+    replay.running.store(false, Ordering::SeqCst);
+    Ok("Replay stopped".into())
+}
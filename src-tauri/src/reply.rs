@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tauri::State;
+
+use crate::frame::{CanFrameEvent, FrameInput};
+use crate::tap::TapReceiver;
+use crate::tx_limit::transmit_paced;
+use crate::{invalid_argument, run_blocking, DeviceType, StateMutex};
+
+/// 送出 frame 後回傳第一個 (id & mask) == (expected_id & mask) 的接收訊框；mask 省略時需完全相符。
+/// 傳送前就登記讀取端，很快的回覆也不會漏掉；多個呼叫可同時等待不同的 ID，
+/// 符合的訊框照常進入環形緩衝與 can-data 事件流。等待期間裝置關閉會立即回傳錯誤
+#[tauri::command]
+pub async fn transmit_and_wait(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    frame: FrameInput,
+    expected_id: u32,
+    mask: Option<u32>,
+    timeout_ms: u64,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<CanFrameEvent, String> {
+    if timeout_ms == 0 {
+        return Err(invalid_argument("timeout_ms", "must be greater than 0"));
+    }
+    let can_obj = frame.checked("frame")?;
+    let mask = mask.unwrap_or(u32::MAX);
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let key = {
+            let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+            device.check_channel(channel)?;
+            device.key()
+        };
+        let tap = TapReceiver::open(&state, key, channel)?;
+        transmit_paced(&state, key, channel, &[can_obj], true)?;
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        while let Some(reply) = tap.recv_until(deadline) {
+            if reply.id & mask == expected_id & mask {
+                return Ok(reply);
+            }
+        }
+        if !tap.is_open() {
+            return Err(format!("device {} closed while waiting for 0x{:X}", key.1, expected_id));
+        }
+        Err(format!("no reply matching 0x{:X} within {} ms", expected_id, timeout_ms))
+    })
+    .await
+}
@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::frame::{CanFrameEvent, FrameInput, Provenance};
+use crate::supervisor;
+use crate::{StateMutex, VciCanObj};
+
+/// 要比對的請求訊框；extended 省略時不區分標準/擴展幀
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FrameMatch {
+    pub id: u32,
+    #[serde(default)]
+    pub extended: Option<bool>,
+    #[serde(default)]
+    pub data_prefix: Option<Vec<u8>>,
+}
+
+impl FrameMatch {
+    pub fn matches(&self, frame: &CanFrameEvent) -> bool {
+        !frame.remote
+            && frame.id == self.id
+            && self.extended.is_none_or(|e| e == frame.extended)
+            && self.data_prefix.as_ref().is_none_or(|prefix| frame.data.starts_with(prefix))
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ResponseFrame {
+    pub id: u32,
+    #[serde(default)]
+    pub extended: Option<bool>,
+    pub data: Vec<u8>,
+}
+
+impl ResponseFrame {
+    fn to_can_obj(&self) -> Result<VciCanObj, String> {
+        FrameInput {
+            id: self.id,
+            extended: self.extended,
+            remote: false,
+            dlc: None,
+            data: self.data.clone(),
+            padding: None,
+        }
+        .checked("response")
+    }
+}
+
+pub struct AutoResponseRule {
+    rule_id: u32,
+    channel: u32,
+    matcher: FrameMatch,
+    response: ResponseFrame,
+    delay_ms: u64,
+    matched: AtomicU64,
+    sent: AtomicU64,
+}
+
+#[derive(Serialize)]
+pub struct AutoResponseInfo {
+    pub rule_id: u32,
+    pub channel: u32,
+    pub matcher: FrameMatch,
+    pub response: ResponseFrame,
+    pub delay_ms: u64,
+    pub matched: u64,
+    pub sent: u64,
+}
+
+/// 自動回應規則表；存放在 AppState 而非裝置上，因此重新連線後仍然有效
+#[derive(Default)]
+pub struct AutoResponder {
+    rules: Vec<Arc<AutoResponseRule>>,
+    next_id: u32,
+}
+
+impl AutoResponder {
+    /// 回傳本批訊框命中的規則 (每命中一次一筆)
+    pub fn matches(&self, channel: u32, frames: &[CanFrameEvent]) -> Vec<Arc<AutoResponseRule>> {
+        let mut hits = Vec::new();
+        for frame in frames {
+            for rule in self.rules.iter().filter(|r| r.channel == channel && r.matcher.matches(frame)) {
+                rule.matched.fetch_add(1, Ordering::Relaxed);
+                hits.push(rule.clone());
+            }
+        }
+        hits
+    }
+}
+
+/// 送出命中規則的回應；有延遲的回應在另一個執行緒等待，不阻塞接收迴圈
+pub fn respond(state: &Arc<StateMutex>, key: (u32, u32), rule: Arc<AutoResponseRule>) {
+    let delay_ms = rule.delay_ms;
+    let channel = rule.channel;
+    let send = move |state: &Arc<StateMutex>| {
+        let tx_path = state
+            .lock()
+            .map_err(|_| "Failed to lock state".to_string())
+            .and_then(|mut app_state| app_state.tx_path(key, rule.channel));
+        if tx_path
+            .and_then(|tx_path| {
+                let can_obj = rule.response.to_can_obj()?;
+                tx_path.with_provenance(Provenance::TxAutoresponse).transmit(&[can_obj], true)
+            })
+            .is_ok()
+        {
+            rule.sent.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+    if delay_ms == 0 {
+        send(state);
+    } else {
+        let state = state.clone();
+        let delay = Duration::from_millis(delay_ms);
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            supervisor::guard("auto-response", format!("CAN{}", channel + 1), || send(&state));
+        });
+    }
+}
+
+fn auto_responder(state: &State<Arc<StateMutex>>) -> Result<Arc<Mutex<AutoResponder>>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    Ok(app_state.auto_responder.clone())
+}
+
+/// 新增自動回應規則 (matcher 即請求的 match 條件)，需有接收執行緒在該通道運作；回傳 rule_id
+#[tauri::command]
+pub fn add_auto_response(
+    channel: u32,
+    matcher: FrameMatch,
+    response: ResponseFrame,
+    delay_ms: Option<u64>,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    response.to_can_obj()?;
+    let auto_responder = auto_responder(&state)?;
+    let mut responder = auto_responder.lock().map_err(|_| "Failed to lock auto responder")?;
+    responder.next_id += 1;
+    let rule_id = responder.next_id;
+    responder.rules.push(Arc::new(AutoResponseRule {
+        rule_id,
+        channel,
+        matcher,
+        response,
+        delay_ms: delay_ms.unwrap_or(0),
+        matched: AtomicU64::new(0),
+        sent: AtomicU64::new(0),
+    }));
+    Ok(rule_id)
+}
+
+#[tauri::command]
+pub fn remove_auto_response(rule_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let auto_responder = auto_responder(&state)?;
+    let mut responder = auto_responder.lock().map_err(|_| "Failed to lock auto responder")?;
+    let before = responder.rules.len();
+    responder.rules.retain(|r| r.rule_id != rule_id);
+    if responder.rules.len() == before {
+        return Err(format!("auto response {} not found", rule_id));
+    }
+    Ok(format!("auto response {} removed", rule_id))
+}
+
+#[tauri::command]
+pub fn clear_auto_responses(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let auto_responder = auto_responder(&state)?;
+    auto_responder.lock().map_err(|_| "Failed to lock auto responder")?.rules.clear();
+    Ok("auto responses cleared".into())
+}
+
+#[tauri::command]
+pub fn list_auto_responses(state: State<Arc<StateMutex>>) -> Result<Vec<AutoResponseInfo>, String> {
+    let auto_responder = auto_responder(&state)?;
+    let responder = auto_responder.lock().map_err(|_| "Failed to lock auto responder")?;
+    Ok(responder
+        .rules
+        .iter()
+        .map(|rule| AutoResponseInfo {
+            rule_id: rule.rule_id,
+            channel: rule.channel,
+            matcher: rule.matcher.clone(),
+            response: rule.response.clone(),
+            delay_ms: rule.delay_ms,
+            matched: rule.matched.load(Ordering::Relaxed),
+            sent: rule.sent.load(Ordering::Relaxed),
+        })
+        .collect())
+}
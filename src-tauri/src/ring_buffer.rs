@@ -0,0 +1,504 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use crate::annotation::{AnnotationMarker, Annotations};
+use crate::frame::{CanFrameEvent, Direction, Provenance};
+use crate::logging::{self, LogFormat, LoggedFrame};
+use crate::memory::{self, CapWarning, SubsystemMemory};
+use crate::{invalid_argument, run_blocking, StateMutex};
+
+pub const DEFAULT_CAPACITY: usize = 100_000;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BufferedFrame {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub frame: CanFrameEvent,
+}
+
+/// 最近收到與送出的訊框，所有裝置與通道依放入的順序存在同一個佇列；超過容量或位元組上限時覆寫最舊的一筆。
+/// seq 單調遞增，前端重新載入或漏掉事件後可用 since_seq 補齊。使用者的註記也存在這裡
+pub struct FrameRing {
+    capacity: usize,
+    max_bytes: usize,
+    /// 目前內容以 memory::frame_bytes 估計的大小
+    bytes: usize,
+    /// 因位元組上限 (而非訊框數) 被覆寫的訊框
+    warning: CapWarning,
+    frames: VecDeque<BufferedFrame>,
+    next_seq: u64,
+    /// 前端已取得的最大 seq；被覆寫的訊框若大於此值就計入 dropped
+    fetched_seq: Option<u64>,
+    dropped: u64,
+    /// 每次 clear() 加一，接收串流據此重新計算 Δt
+    epoch: u64,
+    annotations: Annotations,
+}
+
+/// get_recent_frames 的篩選條件；未指定的欄位不篩選，ID 範圍包含兩端
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameQuery {
+    pub channel: Option<u32>,
+    pub direction: Option<Direction>,
+    pub provenance: Option<Provenance>,
+    pub id_min: Option<u32>,
+    pub id_max: Option<u32>,
+}
+
+impl FrameQuery {
+    pub fn channel(channel: u32) -> Self {
+        Self {
+            channel: Some(channel),
+            ..Self::default()
+        }
+    }
+
+    /// ID 範圍兩端都指定時下限不可大於上限
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.id_min, self.id_max) {
+            (Some(min), Some(max)) if min > max => Err(invalid_argument("id_min", "must not be greater than id_max")),
+            _ => Ok(()),
+        }
+    }
+
+    fn matches(&self, frame: &CanFrameEvent) -> bool {
+        self.channel.is_none_or(|c| c == frame.channel)
+            && self.direction.is_none_or(|d| d == frame.direction)
+            && self.provenance.is_none_or(|p| p == frame.provenance)
+            && self.id_min.is_none_or(|min| frame.id >= min)
+            && self.id_max.is_none_or(|max| frame.id <= max)
+    }
+}
+
+#[derive(Serialize)]
+pub struct FrameBufferStatus {
+    pub capacity: usize,
+    pub len: usize,
+    pub next_seq: u64,
+    pub dropped: u64,
+}
+
+#[derive(Serialize)]
+pub struct RecentFrames {
+    pub frames: Vec<BufferedFrame>,
+    #[serde(flatten)]
+    pub status: FrameBufferStatus,
+    /// include_annotations 時才有：時間落在回傳訊框範圍內的註記
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Vec<AnnotationMarker>>,
+}
+
+impl Default for FrameRing {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl FrameRing {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            max_bytes: memory::DEFAULT_MAX_BUFFER_BYTES,
+            bytes: 0,
+            warning: CapWarning::default(),
+            frames: VecDeque::new(),
+            next_seq: 0,
+            fetched_seq: None,
+            dropped: 0,
+            epoch: 0,
+            annotations: Annotations::default(),
+        }
+    }
+
+    pub fn push(&mut self, frame: CanFrameEvent) -> u64 {
+        while self.frames.len() >= self.capacity {
+            self.evict_oldest();
+        }
+        self.bytes += memory::frame_bytes(&frame);
+        self.enforce_max_bytes();
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.frames.push_back(BufferedFrame { seq, frame });
+        seq
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.frames.len() > self.capacity {
+            self.evict_oldest();
+        }
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.enforce_max_bytes();
+    }
+
+    fn enforce_max_bytes(&mut self) {
+        let mut evicted = 0;
+        while self.bytes > self.max_bytes && !self.frames.is_empty() {
+            self.evict_oldest();
+            evicted += 1;
+        }
+        self.warning.evicted("ring-buffer", evicted, self.max_bytes);
+    }
+
+    pub fn memory(&self) -> SubsystemMemory {
+        SubsystemMemory {
+            entries: self.frames.len(),
+            bytes: self.bytes,
+            limit: self.max_bytes,
+            evictions: self.warning.evictions(),
+        }
+    }
+
+    /// 清空內容與註記但保留 seq，避免前端拿到重複的序號
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.annotations.clear();
+        self.bytes = 0;
+        self.dropped = 0;
+        self.epoch += 1;
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    pub fn annotations_mut(&mut self) -> &mut Annotations {
+        &mut self.annotations
+    }
+
+    /// 時間落在 frames 範圍內的註記，並標出各註記之前的最後一個訊框。frames 包含緩衝最新的訊框時，
+    /// 之後才加上的註記也一併回傳；沒有訊框時只有 newest 為 true (查詢最新內容) 才回傳全部註記
+    fn annotation_markers(&self, frames: &[BufferedFrame], newest: bool) -> Vec<AnnotationMarker> {
+        let annotations = match (frames.first(), frames.last()) {
+            (Some(first), Some(last)) => {
+                let to_us = if last.seq + 1 == self.next_seq {
+                    u64::MAX
+                } else {
+                    last.frame.host_timestamp_us
+                };
+                self.annotations.between(first.frame.host_timestamp_us, to_us)
+            }
+            _ if newest => self.annotations.list(),
+            _ => Vec::new(),
+        };
+        annotations
+            .into_iter()
+            .map(|annotation| {
+                let before = frames.partition_point(|f| f.frame.host_timestamp_us <= annotation.timestamp_us);
+                AnnotationMarker {
+                    after_seq: before.checked_sub(1).map(|i| frames[i].seq),
+                    annotation,
+                }
+            })
+            .collect()
+    }
+
+    /// since_seq 為 None 時回傳最新的 limit 筆；否則回傳 seq 大於 since_seq 的最舊 limit 筆
+    pub fn query(&mut self, filter: &FrameQuery, since_seq: Option<u64>, limit: usize) -> Vec<BufferedFrame> {
+        let matches = |f: &&BufferedFrame| filter.matches(&f.frame) && since_seq.is_none_or(|s| f.seq > s);
+        let frames: Vec<BufferedFrame> = match since_seq {
+            Some(_) => self.frames.iter().filter(matches).take(limit).cloned().collect(),
+            None => {
+                let mut newest: Vec<BufferedFrame> = self.frames.iter().rev().filter(matches).take(limit).cloned().collect();
+                newest.reverse();
+                newest
+            }
+        };
+        if let Some(last) = frames.last() {
+            self.fetched_seq = Some(self.fetched_seq.map_or(last.seq, |s| s.max(last.seq)));
+        }
+        frames
+    }
+
+    /// 複製目前的內容 (最新的 last_n 筆)；不影響 fetched_seq。
+    /// 在一次加鎖內完成，結果是某個時間點的完整內容，不會因為同時寫入而缺漏或重複
+    pub fn snapshot(&self, channel: Option<u32>, last_n: Option<usize>) -> Vec<BufferedFrame> {
+        let mut frames: Vec<BufferedFrame> = self
+            .frames
+            .iter()
+            .rev()
+            .filter(|f| channel.is_none_or(|c| c == f.frame.channel))
+            .take(last_n.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        frames.reverse();
+        frames
+    }
+
+    pub fn status(&self) -> FrameBufferStatus {
+        FrameBufferStatus {
+            capacity: self.capacity,
+            len: self.frames.len(),
+            next_seq: self.next_seq,
+            dropped: self.dropped,
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(evicted) = self.frames.pop_front() {
+            self.bytes = self.bytes.saturating_sub(memory::frame_bytes(&evicted.frame));
+            if self.fetched_seq.is_none_or(|s| evicted.seq > s) {
+                self.dropped += 1;
+            }
+        }
+    }
+}
+
+fn frame_buffer(state: &State<Arc<StateMutex>>) -> Result<Arc<Mutex<FrameRing>>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    Ok(app_state.frame_buffer.clone())
+}
+
+/// 依 seq 排序回傳各通道 RX 與 TX 合併的訊框；可依方向、來源、通道與 ID 範圍篩選。
+/// include_annotations 時一併回傳這段時間的註記，追蹤畫面不必另外查詢
+#[tauri::command]
+pub fn get_recent_frames(
+    channel: Option<u32>,
+    since_seq: Option<u64>,
+    limit: usize,
+    direction: Option<Direction>,
+    provenance: Option<Provenance>,
+    id_min: Option<u32>,
+    id_max: Option<u32>,
+    include_annotations: Option<bool>,
+    state: State<Arc<StateMutex>>,
+) -> Result<RecentFrames, String> {
+    let filter = FrameQuery {
+        channel,
+        direction,
+        provenance,
+        id_min,
+        id_max,
+    };
+    filter.validate()?;
+    query_recent_frames(&state, &filter, since_seq, limit, include_annotations.unwrap_or(false))
+}
+
+/// get_recent_frames 的實作；since_seq 與 limit 的意義同 FrameRing::query()
+pub fn query_recent_frames(
+    state: &StateMutex,
+    filter: &FrameQuery,
+    since_seq: Option<u64>,
+    limit: usize,
+    include_annotations: bool,
+) -> Result<RecentFrames, String> {
+    let frame_buffer = state.lock().map_err(|_| "Failed to lock state")?.frame_buffer.clone();
+    let mut ring = frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?;
+    let frames = ring.query(filter, since_seq, limit);
+    let annotations = include_annotations.then(|| ring.annotation_markers(&frames, since_seq.is_none()));
+    Ok(RecentFrames {
+        frames,
+        status: ring.status(),
+        annotations,
+    })
+}
+
+#[tauri::command]
+pub fn get_frame_buffer_status(state: State<Arc<StateMutex>>) -> Result<FrameBufferStatus, String> {
+    let frame_buffer = frame_buffer(&state)?;
+    let ring = frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?;
+    Ok(ring.status())
+}
+
+#[tauri::command]
+pub fn set_frame_buffer_capacity(capacity: usize, state: State<Arc<StateMutex>>) -> Result<FrameBufferStatus, String> {
+    let frame_buffer = frame_buffer(&state)?;
+    let mut ring = frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?;
+    ring.set_capacity(capacity);
+    Ok(ring.status())
+}
+
+#[tauri::command]
+pub fn clear_frame_buffer(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let frame_buffer = frame_buffer(&state)?;
+    frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?.clear();
+    Ok("Frame buffer cleared".into())
+}
+
+#[derive(Serialize, Clone)]
+struct ExportProgress {
+    path: String,
+    frames_written: u64,
+    total_frames: u64,
+}
+
+/// 把環形緩衝目前的內容寫成記錄檔，回傳寫入的訊框數；第一個訊框之後的註記以註解列穿插在訊框之間。接收不會中斷：
+/// 只在取快照時短暫持有緩衝的鎖，寫檔在阻塞執行緒池進行，大量訊框時送出 export-progress 事件
+#[tauri::command]
+pub async fn export_buffer(
+    path: String,
+    format: Option<LogFormat>,
+    channel: Option<u32>,
+    last_n: Option<usize>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<u64, String> {
+    if last_n == Some(0) {
+        return Err(invalid_argument("last_n", "must be at least 1"));
+    }
+    let frame_buffer = frame_buffer(&state)?;
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let (snapshot, annotations) = {
+            let ring = frame_buffer.lock().map_err(|_| "Failed to lock frame buffer")?;
+            let snapshot = ring.snapshot(channel, last_n);
+            let annotations = snapshot
+                .first()
+                .map(|first| ring.annotations().between(first.frame.host_timestamp_us, u64::MAX))
+                .unwrap_or_default();
+            (snapshot, annotations)
+        };
+        let frames: Vec<LoggedFrame> = snapshot
+            .into_iter()
+            .map(|buffered| LoggedFrame {
+                direction: buffered.frame.direction,
+                frame: buffered.frame,
+            })
+            .collect();
+        let total_frames = frames.len() as u64;
+        let configs = state.lock().map_err(|_| "Failed to lock state")?.configs_of(&frames);
+        let path = PathBuf::from(path);
+        let display = path.display().to_string();
+        logging::write_log_file_with_progress(&path, format.unwrap_or(LogFormat::Csv), &frames, &annotations, &configs, |frames_written| {
+            let _ = app_handle.emit(
+                "export-progress",
+                ExportProgress {
+                    path: display.clone(),
+                    frames_written,
+                    total_frames,
+                },
+            );
+        })
+        .map_err(|e| format!("Failed to write {}: {}", display, e))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VciCanObj;
+
+    fn frame(channel: u32, id: u32, direction: Direction, provenance: Provenance, host_timestamp_us: u64) -> CanFrameEvent {
+        let can_obj = VciCanObj {
+            id,
+            data_len: 1,
+            ..Default::default()
+        };
+        CanFrameEvent {
+            provenance,
+            ..CanFrameEvent::from_raw((4, 0), channel, &can_obj, host_timestamp_us).with_direction(direction)
+        }
+    }
+
+    /// 兩個通道交錯的接收、手動傳送、週期傳送與閘道訊框
+    fn mixed_ring() -> FrameRing {
+        let mut ring = FrameRing::with_capacity(16);
+        ring.push(frame(0, 0x100, Direction::Rx, Provenance::Rx, 10));
+        ring.push(frame(1, 0x200, Direction::Tx, Provenance::TxManual, 20));
+        ring.push(frame(0, 0x300, Direction::Tx, Provenance::TxPeriodic, 30));
+        ring.push(frame(1, 0x100, Direction::Tx, Provenance::Gateway, 40));
+        ring.push(frame(0, 0x7FF, Direction::Rx, Provenance::Rx, 50));
+        ring
+    }
+
+    fn ids(frames: &[BufferedFrame]) -> Vec<u32> {
+        frames.iter().map(|f| f.frame.id).collect()
+    }
+
+    #[test]
+    fn all_channels_share_one_sequence_and_each_filter_narrows_it() {
+        let mut ring = mixed_ring();
+        let all = ring.query(&FrameQuery::default(), None, 100);
+        assert_eq!(all.iter().map(|f| f.seq).collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+
+        let tx = FrameQuery {
+            direction: Some(Direction::Tx),
+            ..Default::default()
+        };
+        assert_eq!(ids(&ring.query(&tx, None, 100)), [0x200, 0x300, 0x100]);
+        let gateway = FrameQuery {
+            provenance: Some(Provenance::Gateway),
+            ..Default::default()
+        };
+        assert_eq!(ids(&ring.query(&gateway, None, 100)), [0x100]);
+        assert_eq!(ids(&ring.query(&FrameQuery::channel(0), None, 100)), [0x100, 0x300, 0x7FF]);
+        // ID 範圍包含兩端
+        let range = FrameQuery {
+            id_min: Some(0x200),
+            id_max: Some(0x300),
+            ..Default::default()
+        };
+        assert_eq!(ids(&ring.query(&range, None, 100)), [0x200, 0x300]);
+        let rx_on_channel_1 = FrameQuery {
+            direction: Some(Direction::Rx),
+            ..FrameQuery::channel(1)
+        };
+        assert!(ring.query(&rx_on_channel_1, None, 100).is_empty());
+    }
+
+    #[test]
+    fn limit_takes_the_newest_frames_or_the_oldest_after_since_seq() {
+        let mut ring = mixed_ring();
+        assert_eq!(ids(&ring.query(&FrameQuery::default(), None, 2)), [0x100, 0x7FF]);
+        assert_eq!(ids(&ring.query(&FrameQuery::default(), Some(0), 2)), [0x200, 0x300]);
+        assert!(ring.query(&FrameQuery::default(), Some(4), 10).is_empty());
+    }
+
+    #[test]
+    fn only_frames_never_fetched_count_as_dropped() {
+        let mut ring = FrameRing::with_capacity(2);
+        for id in 0..3 {
+            ring.push(frame(0, id, Direction::Rx, Provenance::Rx, id as u64));
+        }
+        assert_eq!(ring.status().dropped, 1);
+        ring.query(&FrameQuery::default(), None, 10);
+        ring.push(frame(0, 3, Direction::Rx, Provenance::Rx, 3));
+        ring.push(frame(0, 4, Direction::Rx, Provenance::Rx, 4));
+        let status = ring.status();
+        assert_eq!((status.len, status.next_seq, status.dropped), (2, 5, 1));
+
+        // 清空後 seq 繼續遞增
+        ring.clear();
+        assert_eq!(ring.push(frame(0, 5, Direction::Rx, Provenance::Rx, 5)), 5);
+        assert_eq!(ring.status().dropped, 0);
+    }
+
+    #[test]
+    fn an_inverted_id_range_is_rejected() {
+        let inverted = FrameQuery {
+            id_min: Some(0x300),
+            id_max: Some(0x200),
+            ..Default::default()
+        };
+        let error = inverted.validate().unwrap_err();
+        assert!(error.contains("id_min"), "{}", error);
+        let single_id = FrameQuery {
+            id_min: Some(0x200),
+            id_max: Some(0x200),
+            ..Default::default()
+        };
+        assert!(single_id.validate().is_ok());
+        assert!(FrameQuery {
+            id_min: Some(0x300),
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+    }
+}
@@ -0,0 +1,323 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use crate::bus_state::{BusState, ControllerStatus};
+use crate::channel::TxPath;
+use crate::frame::FrameInput;
+use crate::{baud, run_blocking, CanInterface, DeviceInfo, DeviceType, ReceiveOptions, StateMutex, StreamRestartedEvent, VciCanObj, VciInitConfig};
+
+/// 自測模式 (VCI_INIT_CONFIG.Mode = 2) 與自發自收 (SendType = 2)
+const MODE_SELF_TEST: u8 = 2;
+const SEND_TYPE_SELF_RECEIVE: u8 = 2;
+/// 沒有設定過的通道以此位元率測試；自測模式不會送上匯流排，位元率只影響控制器的時序
+const DEFAULT_TEST_BITRATE: u32 = 500_000;
+/// 等待所有測試訊框回來的時間
+const ECHO_TIMEOUT: Duration = Duration::from_millis(500);
+const ECHO_POLL: Duration = Duration::from_millis(5);
+/// 測試前一次讀掉的殘留訊框上限
+const FLUSH_FRAMES: u32 = 2500;
+
+#[derive(Serialize)]
+pub struct ChannelSelfTest {
+    pub channel: u32,
+    pub passed: bool,
+    pub frames_sent: u32,
+    /// 依序且內容完整地收回的訊框數
+    pub frames_echoed: u32,
+    /// ID 與測試訊框相同但內容或順序不符
+    pub frames_corrupted: u32,
+    /// 測試期間收到的其他訊框 (例如仍在執行的週期任務)
+    pub frames_unexpected: u32,
+    /// 測試後 VCI_ReadErrInfo 的 ErrCode
+    pub err_code: Option<u32>,
+    pub controller: Option<ControllerStatus>,
+    pub errors: Vec<String>,
+    /// 測試前的設定已還原 (沒有設定過的通道為重設)
+    pub restored: bool,
+}
+
+#[derive(Serialize)]
+pub struct SelfTestReport {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub passed: bool,
+    pub board_info: Option<DeviceInfo>,
+    pub board_info_error: Option<String>,
+    pub channels: Vec<ChannelSelfTest>,
+    /// 測試前停止、測試後重新啟動的接收通道
+    pub restarted_streams: Vec<u32>,
+}
+
+/// 涵蓋標準/擴展 ID 的邊界值、0 與 8 位元組、交錯位元與遠端幀
+fn test_frames() -> Result<Vec<VciCanObj>, String> {
+    let frame = |id: u32, extended: bool, remote: bool, dlc: Option<u8>, data: Vec<u8>| FrameInput {
+        id,
+        extended: Some(extended),
+        remote,
+        dlc,
+        data,
+        padding: None,
+    };
+    [
+        frame(0x000, false, false, None, Vec::new()),
+        frame(0x7FF, false, false, None, vec![0xFF; 8]),
+        frame(0x555, false, false, None, vec![0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA]),
+        frame(0x2AA, false, false, None, vec![0x01, 0x02, 0x03]),
+        frame(0x123, false, true, Some(4), Vec::new()),
+        frame(0x1FFF_FFFF, true, false, None, vec![0x00; 8]),
+        frame(0x0AAA_AAAA, true, false, None, (1..=8).collect()),
+        frame(0x1555_5555, true, true, Some(8), Vec::new()),
+    ]
+    .iter()
+    .map(|input| {
+        let mut can_obj = input.checked("frame")?;
+        can_obj.send_type = SEND_TYPE_SELF_RECEIVE;
+        Ok(can_obj)
+    })
+    .collect()
+}
+
+fn same_frame(a: &VciCanObj, b: &VciCanObj) -> bool {
+    let len = (a.data_len as usize).min(8);
+    a.id == b.id
+        && a.extern_flag == b.extern_flag
+        && a.remote_flag == b.remote_flag
+        && a.data_len == b.data_len
+        && (a.remote_flag != 0 || a.data[..len] == b.data[..len])
+}
+
+/// 以自測模式初始化通道、送出測試訊框並檢查收回的內容與錯誤暫存器
+fn test_channel(can_lib: &dyn CanInterface, tx_path: &TxPath, key: (u32, u32), channel: u32, config: VciInitConfig, frames: &[VciCanObj]) -> ChannelSelfTest {
+    let (dev_type, dev_index) = key;
+    let mut result = ChannelSelfTest {
+        channel,
+        passed: false,
+        frames_sent: 0,
+        frames_echoed: 0,
+        frames_corrupted: 0,
+        frames_unexpected: 0,
+        err_code: None,
+        controller: None,
+        errors: Vec::new(),
+        restored: false,
+    };
+    let config = VciInitConfig {
+        mode: MODE_SELF_TEST,
+        ..config
+    };
+    if let Err(e) = can_lib
+        .init_channel(dev_type, dev_index, channel, &config)
+        .and_then(|_| can_lib.start(dev_type, dev_index, channel))
+    {
+        result.errors.push(format!("failed to start in self-test mode: {}", e));
+        return result;
+    }
+    // 清掉之前留下的訊框與錯誤碼
+    let _ = can_lib.receive(dev_type, dev_index, channel, FLUSH_FRAMES, 0);
+    let _ = can_lib.read_err_info(dev_type, dev_index, channel);
+
+    // 與其他傳送相同經過 TxPath，set_tx_inhibit 禁止的通道同樣拒絕
+    match tx_path.try_transmit(frames, false) {
+        Ok(timings) => result.frames_sent = timings.len() as u32,
+        Err(e) => result.errors.push(e),
+    }
+    if (result.frames_sent as usize) < frames.len() {
+        result
+            .errors
+            .push(format!("driver accepted {} of {} frames", result.frames_sent, frames.len()));
+    }
+    let expected = &frames[..result.frames_sent as usize];
+    let deadline = Instant::now() + ECHO_TIMEOUT;
+    while (result.frames_echoed as usize) < expected.len() && Instant::now() < deadline {
+        let received = match can_lib.receive(dev_type, dev_index, channel, frames.len() as u32, 0) {
+            Ok(received) => received,
+            Err(code) => {
+                result.errors.push(format!("VCI_Receive failed ({})", code));
+                break;
+            }
+        };
+        if received.is_empty() {
+            std::thread::sleep(ECHO_POLL);
+        }
+        for can_obj in received {
+            match expected.get(result.frames_echoed as usize) {
+                Some(next) if same_frame(next, &can_obj) => result.frames_echoed += 1,
+                _ if expected.iter().any(|f| f.id == can_obj.id && f.extern_flag == can_obj.extern_flag) => {
+                    result.frames_corrupted += 1
+                }
+                _ => result.frames_unexpected += 1,
+            }
+        }
+    }
+    if (result.frames_echoed as usize) < expected.len() {
+        result.errors.push(format!(
+            "{} of {} frames came back intact",
+            result.frames_echoed,
+            expected.len()
+        ));
+    }
+
+    match can_lib.read_err_info(dev_type, dev_index, channel) {
+        Ok(info) => {
+            if info.err_code != 0 {
+                result.errors.push(format!("ErrCode 0x{:08X}", info.err_code));
+            }
+            result.err_code = Some(info.err_code);
+        }
+        Err(e) => result.errors.push(format!("VCI_ReadErrInfo: {}", e)),
+    }
+    match can_lib.read_can_status(dev_type, dev_index, channel) {
+        Ok(raw) => {
+            let status = ControllerStatus {
+                rx_err_counter: raw.reg_re_counter,
+                tx_err_counter: raw.reg_te_counter,
+                bus_state: BusState::from_status(&raw),
+            };
+            if status.bus_state != BusState::ErrorActive {
+                result.errors.push(format!("controller is {:?}", status.bus_state));
+            }
+            result.controller = Some(status);
+        }
+        Err(e) => result.errors.push(format!("VCI_ReadCANStatus: {}", e)),
+    }
+    result.passed = result.errors.is_empty();
+    result
+}
+
+/// 不需要外部匯流排的端對端自測：每個通道以自測模式重新初始化，用自發自收送出一組測試訊框並檢查是否完整收回，
+/// 讀取板卡資訊與錯誤暫存器，最後還原原本的通道設定。接收中的通道先停止、測試後以相同選項重新啟動並送出 stream-restarted。
+/// 測試訊框直接交給驅動，不經過傳送路徑，不會出現在記錄檔與統計中
+#[tauri::command]
+pub async fn run_self_test(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<SelfTestReport, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let frames = test_frames()?;
+        let (key, can_lib, channels, previous, tx_paths, restart, threads) = {
+            let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            let can_lib = app_state.backend().ok_or("CAN library not initialized")?;
+            let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+            let key = device.key();
+            let channel_count = device
+                .channel_count
+                .or_else(|| DeviceType::from_code(key.0).channel_count())
+                .ok_or("channel count unknown; configure the channels first")?;
+            let channels: Vec<u32> = (0..channel_count as u32).collect();
+            let previous: Vec<_> = channels.iter().map(|channel| device.channels.get(channel).copied()).collect();
+            let tx_paths = channels
+                .iter()
+                .map(|&channel| app_state.tx_path(key, channel))
+                .collect::<Result<Vec<_>, _>>()?;
+            let device = app_state.devices.get_mut(&key).expect("device key resolved above");
+            let mut restart: Vec<(u32, ReceiveOptions)> = device
+                .receiving
+                .iter()
+                .filter(|(_, receiving)| receiving.load(Ordering::SeqCst))
+                .filter_map(|(&channel, _)| Some((channel, *device.receive_options.get(&channel)?)))
+                .collect();
+            restart.sort_by_key(|&(channel, _)| channel);
+            for receiving in device.receiving.values() {
+                receiving.store(false, Ordering::SeqCst);
+            }
+            let threads = device.handle.take_threads();
+            (key, can_lib, channels, previous, tx_paths, restart, threads)
+        };
+        // 接收執行緒需要取得鎖才會發現旗標已清除，等待時不可持有鎖
+        for handle in threads {
+            let _ = handle.join();
+        }
+
+        let (board_info, board_info_error) = match can_lib.read_board_info(key.0, key.1) {
+            Ok(board_info) => (Some(DeviceInfo::from_board_info(key.1, &board_info)), None),
+            Err(e) => (None, Some(e)),
+        };
+        let (timing0, timing1) = baud::timing_for_bitrate(DEFAULT_TEST_BITRATE).ok_or("no timing for the test bitrate")?;
+        let default_config = VciInitConfig {
+            acc_code: 0,
+            acc_mask: 0xFFFFFFFF,
+            reserved: 0,
+            filter: 1,
+            timing0,
+            timing1,
+            mode: 0,
+        };
+        let mut results: Vec<ChannelSelfTest> = channels
+            .iter()
+            .zip(&previous)
+            .zip(&tx_paths)
+            .map(|((&channel, previous), tx_path)| {
+                let config = previous.map_or(default_config, |previous| previous.config);
+                test_channel(can_lib.as_ref(), tx_path, key, channel, config, &frames)
+            })
+            .collect();
+
+        {
+            let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+            for (result, previous) in results.iter_mut().zip(&previous) {
+                let channel = result.channel;
+                let restored = match previous {
+                    Some(previous) if previous.started => app_state.start_channel(key, channel, previous.config),
+                    Some(previous) => app_state.init_channel(key, channel, previous.config),
+                    // 沒有 VCI_ResetCAN 時以一般模式初始化但不啟動，同樣不會收發
+                    None => can_lib
+                        .reset(key.0, key.1, channel)
+                        .or_else(|_| can_lib.init_channel(key.0, key.1, channel, &default_config)),
+                };
+                match restored {
+                    Ok(()) => result.restored = true,
+                    Err(e) => {
+                        result.passed = false;
+                        result.errors.push(format!("failed to restore the channel: {}", e));
+                    }
+                }
+            }
+        }
+
+        let mut restarted_streams = Vec::new();
+        for (channel, options) in restart {
+            let spawned = crate::spawn_receive_loop(&state, app_handle.clone(), Some(key.0), Some(key.1), channel, options);
+            match spawned {
+                Ok((_, handle)) => {
+                    if let Some(device) = state.lock().map_err(|_| "Failed to lock state")?.devices.get_mut(&key) {
+                        device.register_receive_thread(channel, handle);
+                    }
+                    restarted_streams.push(channel);
+                }
+                Err(e) => {
+                    if let Some(result) = results.iter_mut().find(|result| result.channel == channel) {
+                        result.passed = false;
+                        result.errors.push(format!("failed to restart receiving: {}", e));
+                    }
+                }
+            }
+        }
+        if !restarted_streams.is_empty() {
+            let _ = app_handle.emit(
+                "stream-restarted",
+                StreamRestartedEvent {
+                    dev_type: key.0,
+                    dev_index: key.1,
+                    channels: restarted_streams.clone(),
+                },
+            );
+        }
+        Ok(SelfTestReport {
+            dev_type: key.0,
+            dev_index: key.1,
+            passed: board_info_error.is_none() && results.iter().all(|result| result.passed),
+            board_info,
+            board_info_error,
+            channels: results,
+            restarted_streams,
+        })
+    })
+    .await
+}
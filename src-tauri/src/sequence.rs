@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::frame::FrameInput;
+use crate::operation::OperationKind;
+use crate::receive::EventSink;
+use crate::supervisor;
+use crate::tx_limit::transmit_paced;
+use crate::{DeviceType, StateMutex, VciCanObj};
+
+/// 以 sleep 等到距離期限這麼近，剩下的時間以忙等補足 (Windows 的 sleep 精度約 1~15 ms)
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+/// 分段睡眠的長度，讓 abort_sequence 能即時生效
+const STOP_POLL: Duration = Duration::from_millis(10);
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SequenceStep {
+    pub frame: FrameInput,
+    /// 每次送出後等待的時間
+    #[serde(default)]
+    pub delay_after_ms: u64,
+    /// 連續送出的次數，省略時為 1
+    #[serde(default)]
+    pub repeat: Option<u32>,
+}
+
+#[derive(Serialize, Clone)]
+struct SequenceProgress {
+    sequence_id: u32,
+    step_index: usize,
+    repeat_index: u32,
+    frames_sent: u64,
+    total_frames: u64,
+}
+
+#[derive(Serialize, Clone)]
+struct SequenceFinished {
+    sequence_id: u32,
+    completed: bool,
+    frames_sent: u64,
+    /// 傳送失敗而中止時的步驟索引
+    failed_step: Option<usize>,
+    error: Option<String>,
+}
+
+/// 等到 deadline；期間 running 被清除時回傳 false
+pub(crate) fn wait_until(deadline: Instant, running: &AtomicBool) -> bool {
+    loop {
+        if !running.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        let remaining = deadline - now;
+        if remaining > SPIN_THRESHOLD {
+            std::thread::sleep((remaining - SPIN_THRESHOLD).min(STOP_POLL));
+        } else {
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// 在背景執行緒依序送出各步驟，回傳 sequence_id (即 operation_id)。
+/// 與週期傳送各自獨立執行；任何一步傳送失敗即中止，並在 sequence-finished 與 operation-finished 回報失敗的步驟
+pub fn spawn_tx_sequence<E: EventSink>(
+    state: &Arc<StateMutex>,
+    events: E,
+    dev_type: Option<u32>,
+    dev_index: Option<u32>,
+    channel: u32,
+    steps: Vec<SequenceStep>,
+) -> Result<u32, String> {
+    if steps.is_empty() {
+        return Err("sequence has no steps".into());
+    }
+    let frames: Vec<VciCanObj> = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| step.frame.checked(&format!("steps[{}].frame", index)))
+        .collect::<Result<_, _>>()?;
+    let total_frames: u64 = steps.iter().map(|s| s.repeat.unwrap_or(1) as u64).sum();
+    let (key, operation) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let key = app_state.connected_device(dev_type, dev_index)?.key();
+        (key, app_state.start_operation(OperationKind::Sequence, key, channel, Some(total_frames)))
+    };
+    let sequence_id = operation.id();
+    let running = operation.running().clone();
+
+    let state = state.clone();
+    std::thread::spawn(move || {
+        let mut frames_sent = 0;
+        let mut failure = None;
+        let mut next = Instant::now();
+        let mut current_step = 0;
+        let finished = supervisor::guard("sequence", format!("sequence {}", sequence_id), || {
+            'steps: for (step_index, (step, can_obj)) in steps.iter().zip(&frames).enumerate() {
+                current_step = step_index;
+                for repeat_index in 0..step.repeat.unwrap_or(1) {
+                    if !wait_until(next, &running) {
+                        break 'steps;
+                    }
+                    if let Err(error_message) = transmit_paced(&state, key, channel, std::slice::from_ref(can_obj), true) {
+                        failure = Some((step_index, error_message));
+                        break 'steps;
+                    }
+                    frames_sent += 1;
+                    operation.set_progress(frames_sent);
+                    events.emit_event(
+                        "sequence-progress",
+                        SequenceProgress {
+                            sequence_id,
+                            step_index,
+                            repeat_index,
+                            frames_sent,
+                            total_frames,
+                        },
+                    );
+                    // 延遲從預定的傳送時間起算，傳送耗時不會累積；落後時從現在重新起算
+                    next += Duration::from_millis(step.delay_after_ms);
+                    let now = Instant::now();
+                    if next < now {
+                        next = now;
+                    }
+                }
+            }
+        });
+        if finished.is_none() {
+            failure = Some((current_step, "sequence thread panicked".to_string()));
+        }
+        let (failed_step, error) = failure.unzip();
+        operation.finish(&state, &events, error.clone());
+        events.emit_event(
+            "sequence-finished",
+            SequenceFinished {
+                sequence_id,
+                completed: frames_sent == total_frames,
+                frames_sent,
+                failed_step,
+                error,
+            },
+        );
+    });
+    Ok(sequence_id)
+}
+
+#[tauri::command]
+pub fn run_tx_sequence(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    steps: Vec<SequenceStep>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    spawn_tx_sequence(state.inner(), app_handle, dev_type.map(DeviceType::code), dev_index, channel, steps)
+}
+
+#[tauri::command]
+pub fn abort_sequence(sequence_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state
+        .cancel_operation(sequence_id, Some(OperationKind::Sequence))
+        .ok_or_else(|| format!("sequence {} not found", sequence_id))?;
+    Ok(format!("sequence {} aborted", sequence_id))
+}
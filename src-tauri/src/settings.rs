@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, State};
+
+use crate::{run_blocking, AppState, Backend, ChannelState, DeviceInfo, DeviceType, StateMutex, VciInitConfig};
+
+const SETTINGS_FILE: &str = "connection_settings.json";
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SavedChannel {
+    pub channel: u32,
+    pub timing0: u8,
+    pub timing1: u8,
+    pub mode: u8,
+    pub acc_code: u32,
+    pub acc_mask: u32,
+    pub filter: u8,
+    pub started: bool,
+}
+
+impl SavedChannel {
+    fn config(&self) -> VciInitConfig {
+        VciInitConfig {
+            acc_code: self.acc_code,
+            acc_mask: self.acc_mask,
+            reserved: 0,
+            filter: self.filter,
+            timing0: self.timing0,
+            timing1: self.timing1,
+            mode: self.mode,
+        }
+    }
+}
+
+/// 最後一次成功開啟/重新連線時的裝置與通道設定
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SavedSettings {
+    pub backend: Backend,
+    pub dev_type: DeviceType,
+    pub dev_index: u32,
+    /// 有序號時以序號找回裝置，index 只在無法列舉時使用
+    pub serial_number: Option<String>,
+    pub channels: Vec<SavedChannel>,
+    /// 開啟時先禁止傳送 (set_tx_inhibit) 的通道，包含尚未初始化的通道；設定檔可以此讓通道預設處於安全狀態
+    #[serde(default)]
+    pub tx_inhibited: Vec<u32>,
+}
+
+/// app_data_dir 下的設定檔路徑
+pub(crate) fn data_file(app_handle: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve data directory: {}", e))?;
+    Ok(dir.join(name))
+}
+
+fn settings_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    data_file(app_handle, SETTINGS_FILE)
+}
+
+fn load(app_handle: &tauri::AppHandle) -> Result<Option<SavedSettings>, String> {
+    let path = settings_path(app_handle)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| format!("Saved settings file {} is corrupt: {}", path.display(), e))
+}
+
+fn store(app_handle: &tauri::AppHandle, settings: &SavedSettings) -> Result<(), String> {
+    let path = settings_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+impl AppState {
+    /// 裝置目前的設定
+    pub(crate) fn current_settings(&self, key: (u32, u32)) -> Option<SavedSettings> {
+        let device = self.devices.get(&key)?;
+        let mut channels: Vec<SavedChannel> = device
+            .channels
+            .iter()
+            .map(|(&channel, channel_state)| SavedChannel {
+                channel,
+                timing0: channel_state.config.timing0,
+                timing1: channel_state.config.timing1,
+                mode: channel_state.config.mode,
+                acc_code: channel_state.config.acc_code,
+                acc_mask: channel_state.config.acc_mask,
+                filter: channel_state.config.filter,
+                started: channel_state.started,
+            })
+            .collect();
+        channels.sort_by_key(|c| c.channel);
+        let mut tx_inhibited: Vec<u32> = self
+            .channel_runtime
+            .iter()
+            .filter(|(&(dev_type, dev_index, _), runtime)| (dev_type, dev_index) == key && runtime.tx_inhibited.load(Ordering::SeqCst))
+            .map(|(&(_, _, channel), _)| channel)
+            .collect();
+        tx_inhibited.sort();
+        Some(SavedSettings {
+            backend: self.backend().map_or(Backend::ControlCan, |lib| lib.backend()),
+            dev_type: DeviceType::from_code(device.dev_type),
+            dev_index: device.dev_index,
+            serial_number: device.serial_number.clone(),
+            channels,
+            tx_inhibited,
+        })
+    }
+
+    /// 記下裝置目前的設定；沒有 AppHandle (例如測試) 時不做事。寫入失敗只印出，不影響開啟結果
+    pub(crate) fn save_settings(&self, key: (u32, u32)) {
+        let (Some(app_handle), Some(mut settings)) = (self.app_handle.as_ref(), self.current_settings(key)) else {
+            return;
+        };
+        // 剛開啟、尚未設定通道時保留同一裝置先前存下的通道設定
+        if settings.channels.is_empty() {
+            if let Ok(Some(previous)) = load(app_handle) {
+                if previous.serial_number.is_some() && previous.serial_number == settings.serial_number {
+                    settings.channels = previous.channels;
+                    // 開啟後尚未套用設定時同樣保留禁止傳送；寧可多禁止
+                    if settings.tx_inhibited.is_empty() {
+                        settings.tx_inhibited = previous.tx_inhibited;
+                    }
+                }
+            }
+        }
+        if let Err(error_message) = store(app_handle, &settings) {
+            println!("Failed to save connection settings: {}", error_message);
+        }
+    }
+
+    /// 以儲存的設定在 dev_index 開啟裝置，先 init 各通道再連續 start；失敗時關閉裝置
+    pub(crate) fn restore_device(&mut self, saved: &SavedSettings, dev_index: u32) -> Result<(), String> {
+        let dev_type = saved.dev_type.code();
+        self.open_device(dev_type, dev_index, saved.serial_number.clone())?;
+        let key = (dev_type, dev_index);
+        // 在初始化、啟動之前套用，避免其他功能在這之間送出訊框
+        for (&(entry_type, entry_index, channel), runtime) in &self.channel_runtime {
+            if (entry_type, entry_index) == key {
+                runtime.tx_inhibited.store(saved.tx_inhibited.contains(&channel), Ordering::SeqCst);
+            }
+        }
+        for &channel in &saved.tx_inhibited {
+            self.channel_runtime(key, channel).tx_inhibited.store(true, Ordering::SeqCst);
+        }
+        let can_lib = self.try_library()?;
+        let result = saved
+            .channels
+            .iter()
+            .try_for_each(|saved_channel| {
+                let (channel, config) = (saved_channel.channel, saved_channel.config());
+                can_lib
+                    .init_channel(dev_type, dev_index, channel, &config)
+                    .map_err(|_| format!("Failed to initialize CAN{}", channel + 1))?;
+                let device = self.device_mut(Some(dev_type), Some(dev_index))?;
+                device.channels.insert(channel, ChannelState { config, started: false });
+                self.reset_channel_counters(key, channel, &config);
+                self.record_channel_config(key, channel, config);
+                Ok::<(), String>(())
+            })
+            .and_then(|_| {
+                // 全部初始化後才連續啟動，讓各通道的時間戳記起點接近
+                saved
+                    .channels
+                    .iter()
+                    .filter(|saved_channel| saved_channel.started)
+                    .try_for_each(|saved_channel| self.start_initialized_channel(key, saved_channel.channel, saved_channel.config()))
+            });
+        if let Err(error_message) = result {
+            // 還沒有接收執行緒，drop 的 CanDevice 直接關閉裝置
+            self.devices.remove(&key);
+            return Err(error_message);
+        }
+        self.save_settings(key);
+        Ok(())
+    }
+}
+
+/// 找出儲存的裝置目前的 index；有序號時以序號比對列舉結果
+pub(crate) fn locate_saved_device(saved: &SavedSettings, found: Result<Vec<DeviceInfo>, String>) -> Result<u32, String> {
+    match (&saved.serial_number, found) {
+        (Some(serial), Ok(found)) => match found.iter().find(|d| &d.serial_number == serial) {
+            Some(device) => Ok(device.index as u32),
+            None => {
+                let serials: Vec<&str> = found.iter().map(|d| d.serial_number.as_str()).collect();
+                Err(format!(
+                    "saved device {} not present (available: {})",
+                    serial,
+                    if serials.is_empty() { "none".to_string() } else { serials.join(", ") }
+                ))
+            }
+        },
+        // 沒有序號或 DLL 不支援列舉時只能使用原本的 index
+        _ => Ok(saved.dev_index),
+    }
+}
+
+#[tauri::command]
+pub fn get_saved_settings(app_handle: tauri::AppHandle) -> Result<Option<SavedSettings>, String> {
+    load(&app_handle)
+}
+
+/// 依儲存的設定重新開啟裝置，並依序 init/start 各通道
+#[tauri::command]
+pub async fn open_with_saved_settings(app_handle: tauri::AppHandle, state: State<'_, Arc<StateMutex>>) -> Result<SavedSettings, String> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let saved = load(&app_handle)?.ok_or("no saved connection settings")?;
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        app_state.select_backend(saved.backend)?;
+        let found = app_state.enumerate_devices();
+        let dev_index = locate_saved_device(&saved, found)?;
+        app_state.restore_device(&saved, dev_index)?;
+        Ok(SavedSettings { dev_index, ..saved })
+    })
+    .await
+}
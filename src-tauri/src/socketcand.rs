@@ -0,0 +1,400 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tauri::State;
+
+use crate::frame::{CanFrameEvent, FrameInput};
+use crate::supervisor;
+use crate::tap::TapReceiver;
+use crate::tx_limit::transmit_paced;
+use crate::{DeviceType, StateMutex};
+
+/// 沒有新連線時的等待間隔
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+/// 讀取用戶端命令的逾時；rawmode 時與等待訊框的時間交替
+const READ_POLL: Duration = Duration::from_millis(5);
+/// 命令沒有結尾的 '>' 時最多累積的長度，超過即視為錯誤並丟棄
+const MAX_COMMAND_LEN: usize = 256;
+
+/// 連線的協定狀態：先 open 一條匯流排，rawmode 之後才會收到訊框
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    NoBus,
+    Bcm,
+    Raw,
+}
+
+/// 從 buffer 取出完整的 "< ... >" 命令 (去掉角括號與前後空白)，不完整的部分留待下次
+fn take_commands(buffer: &mut String) -> Vec<String> {
+    let mut commands = Vec::new();
+    loop {
+        let Some(start) = buffer.find('<') else {
+            buffer.clear();
+            break;
+        };
+        let Some(end) = buffer[start..].find('>') else {
+            buffer.drain(..start);
+            break;
+        };
+        commands.push(buffer[start + 1..start + end].trim().to_string());
+        buffer.drain(..start + end + 1);
+    }
+    commands
+}
+
+/// "< frame ID SEC.USEC DATA >"：標準 ID 為 3 位、擴展 ID 為 8 位十六進位，資料為連續的十六進位位元組
+fn format_frame(frame: &CanFrameEvent) -> String {
+    let id = if frame.extended {
+        format!("{:08X}", frame.id)
+    } else {
+        format!("{:03X}", frame.id)
+    };
+    let data: String = frame.data.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(
+        "< frame {} {}.{:06} {} >",
+        id,
+        frame.host_timestamp_us / 1_000_000,
+        frame.host_timestamp_us % 1_000_000,
+        data
+    )
+}
+
+/// "send ID DLC BYTE..."；ID 超過 3 位時為擴展幀
+fn parse_send(args: &[&str]) -> Result<FrameInput, String> {
+    let [id, dlc, bytes @ ..] = args else {
+        return Err("send expects an id and a dlc".into());
+    };
+    let can_id = u32::from_str_radix(id, 16).map_err(|_| format!("invalid can id {}", id))?;
+    let dlc = u8::from_str_radix(dlc, 16).map_err(|_| format!("invalid dlc {}", dlc))?;
+    let data = bytes
+        .iter()
+        .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("invalid data byte {}", b)))
+        .collect::<Result<Vec<u8>, String>>()?;
+    if data.len() != dlc as usize {
+        return Err(format!("dlc {} does not match {} data bytes", dlc, data.len()));
+    }
+    Ok(FrameInput {
+        id: can_id,
+        extended: Some(id.len() > 3),
+        remote: false,
+        dlc: None,
+        data,
+        padding: None,
+    })
+}
+
+struct Session {
+    state: Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    mode: Mode,
+    tap: Option<TapReceiver>,
+}
+
+impl Session {
+    /// 處理一個命令，回傳要寫回用戶端的回應 (沒有回應時為 None)
+    fn handle(&mut self, command: &str) -> Option<String> {
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        let result = match (self.mode, parts.as_slice()) {
+            (_, []) => Err("empty command".to_string()),
+            (Mode::NoBus, ["open", _bus]) => {
+                self.mode = Mode::Bcm;
+                Ok(Some("< ok >".to_string()))
+            }
+            (Mode::NoBus, ["open"]) => Err("open expects a bus name".into()),
+            (Mode::NoBus, _) => Err("open a bus first".into()),
+            (_, ["open", ..]) => Err("bus already open".into()),
+            (_, ["rawmode"]) => TapReceiver::open(&self.state, self.key, self.channel).map(|tap| {
+                self.tap = Some(tap);
+                self.mode = Mode::Raw;
+                Some("< ok >".to_string())
+            }),
+            (_, ["bcmmode"]) => {
+                self.tap = None;
+                self.mode = Mode::Bcm;
+                Ok(Some("< ok >".to_string()))
+            }
+            (_, ["echo"]) => Ok(Some("< echo >".to_string())),
+            (_, ["send", args @ ..]) => self.send(args).map(|_| None),
+            (_, [other, ..]) => Err(format!("unknown command {}", other)),
+        };
+        result.unwrap_or_else(|error| Some(format!("< error {} >", error)))
+    }
+
+    fn send(&self, args: &[&str]) -> Result<(), String> {
+        let can_obj = parse_send(args)?.checked("frame")?;
+        transmit_paced(&self.state, self.key, self.channel, &[can_obj], true).map(|_| ())
+    }
+
+    /// rawmode 時把等待中的訊框寫給用戶端
+    fn forward_frames(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let Some(tap) = &self.tap else {
+            return Ok(());
+        };
+        let deadline = Instant::now() + READ_POLL;
+        while let Some(frame) = tap.recv_until(deadline) {
+            stream.write_all(format_frame(&frame).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn serve(mut stream: TcpStream, mut session: Session, running: Arc<AtomicBool>) -> std::io::Result<()> {
+    // Windows 上 accept 出來的連線會沿用 listener 的非阻塞設定
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_POLL))?;
+    stream.write_all(b"< hi >")?;
+    let mut buffer = String::new();
+    let mut chunk = [0u8; 1024];
+    while running.load(Ordering::SeqCst) {
+        session.forward_frames(&mut stream)?;
+        match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => buffer.push_str(&String::from_utf8_lossy(&chunk[..n])),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e),
+        }
+        for command in take_commands(&mut buffer) {
+            if let Some(reply) = session.handle(&command) {
+                stream.write_all(reply.as_bytes())?;
+            }
+        }
+        if buffer.len() > MAX_COMMAND_LEN {
+            buffer.clear();
+            stream.write_all(b"< error command too long >")?;
+        }
+    }
+    Ok(())
+}
+
+/// 在 port 上提供 socketcand 相容的 TCP 服務，把一個通道映射給 python-can、Wireshark 等工具。
+/// 每個用戶端各自收到完整的訊框流；任何匯流排名稱都對應到 channel
+#[tauri::command]
+pub fn start_socketcand_server(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    port: u16,
+    channel: u32,
+    bind_address: Option<String>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let bind_address = bind_address.unwrap_or_else(|| "127.0.0.1".into());
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let key = device.key();
+    let listener = TcpListener::bind((bind_address.as_str(), port))
+        .map_err(|e| format!("Failed to listen on {}:{}: {}", bind_address, port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    let running = Arc::new(AtomicBool::new(true));
+    let port = address.port();
+    app_state.socketcand_servers.insert(port, running.clone());
+    let state = state.inner().clone();
+    std::thread::spawn(move || {
+        let finished = supervisor::guard("socketcand", format!("port {}", port), || {
+            while running.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let session = Session {
+                            state: state.clone(),
+                            key,
+                            channel,
+                            mode: Mode::NoBus,
+                            tap: None,
+                        };
+                        let running = running.clone();
+                        std::thread::spawn(move || {
+                            supervisor::guard("socketcand-session", format!("port {}", port), || serve(stream, session, running));
+                        });
+                    }
+                    Err(_) => std::thread::sleep(ACCEPT_POLL),
+                }
+            }
+        });
+        if finished.is_none() {
+            running.store(false, Ordering::SeqCst);
+            if let Ok(mut app_state) = state.lock() {
+                if app_state.socketcand_servers.get(&port).is_some_and(|r| Arc::ptr_eq(r, &running)) {
+                    app_state.socketcand_servers.remove(&port);
+                }
+            }
+        }
+    });
+    Ok(format!("socketcand server for CAN{} listening on {}", channel + 1, address))
+}
+
+/// 停止 port 上的服務並中斷所有用戶端
+#[tauri::command]
+pub fn stop_socketcand_server(port: u16, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let running = app_state
+        .socketcand_servers
+        .remove(&port)
+        .ok_or_else(|| format!("no socketcand server on port {}", port))?;
+    running.store(false, Ordering::SeqCst);
+    Ok(format!("socketcand server on port {} stopped", port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::{AppState, VciCanObj, VciInitConfig};
+
+    const KEY: (u32, u32) = (4, 0);
+
+    fn session() -> (Arc<MockCan>, Session) {
+        let mock = Arc::new(MockCan::new());
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(KEY.0, KEY.1, None).unwrap();
+        let config = VciInitConfig {
+            acc_mask: 0xFFFF_FFFF,
+            timing1: 0x1C,
+            ..Default::default()
+        };
+        app_state.start_channel(KEY, 0, config).unwrap();
+        let session = Session {
+            state: Arc::new(StateMutex::new(app_state)),
+            key: KEY,
+            channel: 0,
+            mode: Mode::NoBus,
+            tap: None,
+        };
+        (mock, session)
+    }
+
+    fn frame(can_obj: VciCanObj, host_timestamp_us: u64) -> CanFrameEvent {
+        CanFrameEvent::from_raw(KEY, 0, &can_obj, host_timestamp_us)
+    }
+
+    #[test]
+    fn split_and_concatenated_commands_are_taken_whole() {
+        let mut buffer = String::from("< open can0 >< rawmode ><se");
+        assert_eq!(take_commands(&mut buffer), ["open can0", "rawmode"]);
+        assert_eq!(buffer, "<se");
+        buffer.push_str("nd 123 1 FF >  noise < echo");
+        assert_eq!(take_commands(&mut buffer), ["send 123 1 FF"]);
+        assert_eq!(buffer, "< echo");
+        buffer.push('>');
+        assert_eq!(take_commands(&mut buffer), ["echo"]);
+        assert!(buffer.is_empty());
+
+        // '<' 之前的雜訊直接丟棄
+        let mut buffer = String::from("garbage");
+        assert!(take_commands(&mut buffer).is_empty());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn send_arguments_parse_standard_and_extended_ids() {
+        let standard = parse_send(&["123", "2", "01", "FF"]).unwrap();
+        assert_eq!((standard.id, standard.extended, standard.data), (0x123, Some(false), vec![0x01, 0xFF]));
+        let extended = parse_send(&["18DAF110", "0"]).unwrap();
+        assert_eq!((extended.id, extended.extended, extended.data), (0x18DA_F110, Some(true), vec![]));
+        // 補零到 8 位的小 ID 仍是擴展幀
+        assert_eq!(parse_send(&["00000123", "0"]).unwrap().extended, Some(true));
+    }
+
+    #[test]
+    fn malformed_send_arguments_are_rejected() {
+        for args in [
+            &["123"][..],
+            &["12G", "0"],
+            &["123", "Z"],
+            &["123", "1", "1FF"],
+            &["123", "2", "01"],
+            &["123", "0", "01"],
+        ] {
+            assert!(parse_send(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn frames_are_formatted_like_socketcand() {
+        let standard = VciCanObj {
+            id: 0x12,
+            data_len: 3,
+            data: [0xDE, 0xAD, 0x0B, 0, 0, 0, 0, 0],
+            ..Default::default()
+        };
+        assert_eq!(format_frame(&frame(standard, 1_700_000_000_000_042)), "< frame 012 1700000000.000042 DEAD0B >");
+        let extended = VciCanObj {
+            id: 0x1F,
+            extern_flag: 1,
+            data_len: 8,
+            data: [1, 2, 3, 4, 5, 6, 7, 8],
+            ..Default::default()
+        };
+        assert_eq!(format_frame(&frame(extended, 5_123_456)), "< frame 0000001F 5.123456 0102030405060708 >");
+        let empty = VciCanObj {
+            id: 0x7FF,
+            ..Default::default()
+        };
+        assert_eq!(format_frame(&frame(empty, 1_000_000)), "< frame 7FF 1.000000  >");
+    }
+
+    #[test]
+    fn a_bus_must_be_opened_before_rawmode() {
+        let (_, mut session) = session();
+        assert_eq!(session.handle("rawmode").as_deref(), Some("< error open a bus first >"));
+        assert_eq!(session.handle("open").as_deref(), Some("< error open expects a bus name >"));
+        assert_eq!(session.handle("open can0").as_deref(), Some("< ok >"));
+        assert_eq!(session.handle("open can1").as_deref(), Some("< error bus already open >"));
+        assert_eq!(session.handle("rawmode").as_deref(), Some("< ok >"));
+        assert!(session.mode == Mode::Raw && session.tap.is_some());
+        assert_eq!(session.handle("bcmmode").as_deref(), Some("< ok >"));
+        assert!(session.mode == Mode::Bcm && session.tap.is_none());
+        assert_eq!(session.handle("").as_deref(), Some("< error empty command >"));
+        assert_eq!(session.handle("filter 123").as_deref(), Some("< error unknown command filter >"));
+    }
+
+    #[test]
+    fn send_transmits_valid_frames_and_reports_bad_ones() {
+        let (mock, mut session) = session();
+        session.handle("open can0");
+        assert_eq!(session.handle("send 18DAF110 2 AA BB"), None);
+        assert_eq!(session.handle("send 123 2 01").as_deref(), Some("< error dlc 2 does not match 1 data bytes >"));
+        // 超過 29 位的 ID 與 9 個位元組的資料在 checked() 被擋下
+        assert!(session.handle("send 20000000 0").unwrap().starts_with("< error "));
+        assert!(session.handle("send 123 9 1 2 3 4 5 6 7 8 9").unwrap().starts_with("< error "));
+
+        let transmitted = mock.transmitted();
+        assert_eq!(transmitted.len(), 1);
+        let (channel, can_obj) = &transmitted[0];
+        assert_eq!((*channel, can_obj.id, can_obj.extern_flag, can_obj.data_len), (0, 0x18DA_F110, 1, 2));
+        assert_eq!(&can_obj.data[..2], &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn the_server_greets_and_acknowledges_the_rawmode_handshake() {
+        let (_, session) = session();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let running = Arc::new(AtomicBool::new(true));
+        let server = {
+            let running = running.clone();
+            std::thread::spawn(move || serve(stream, session, running))
+        };
+
+        let mut reply = [0u8; 6];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"< hi >");
+        // 命令拆成兩段送出
+        client.write_all(b"< open can0 >< raw").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        client.write_all(b"mode >").unwrap();
+        let mut reply = [0u8; 12];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"< ok >< ok >");
+
+        running.store(false, Ordering::SeqCst);
+        drop(client);
+        server.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::atomic::Ordering;
+use std::sync::{LockResult, Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::{supervisor, AppState};
+
+/// state-recovered 事件：持有 state 鎖的執行緒或命令 panic 後，下一次取得鎖時送出
+#[derive(Serialize, Clone, Debug)]
+pub struct StateRecoveredEvent {
+    /// 因為狀態可能不一致而停止的工作，例如 "periodic task 3"
+    pub stopped: Vec<String>,
+}
+
+/// AppState 的鎖。持有鎖時發生 panic 不會讓後端永久失效：下一次 lock() 取回內容、
+/// 停止可能只做了一半的傳送工作並送出 state-recovered
+#[derive(Default)]
+pub struct StateMutex(Mutex<AppState>);
+
+impl StateMutex {
+    pub fn new(app_state: AppState) -> Self {
+        Self(Mutex::new(app_state))
+    }
+
+    /// 一律回傳 Ok；保留 LockResult 讓呼叫端沿用原本的錯誤處理
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, AppState>> {
+        match self.0.lock() {
+            Ok(app_state) => Ok(app_state),
+            Err(poisoned) => {
+                self.0.clear_poison();
+                let mut app_state = poisoned.into_inner();
+                let stopped = app_state.recover_after_panic();
+                supervisor::record_diagnostic(&format!(
+                    "state lock recovered after a panic; stopped: {}",
+                    if stopped.is_empty() { "nothing".to_string() } else { stopped.join(", ") }
+                ));
+                supervisor::emit("state-recovered", StateRecoveredEvent { stopped });
+                Ok(app_state)
+            }
+        }
+    }
+
+    /// 同 lock()，但鎖被佔用時最多等候 timeout，仍取不到時回傳 None
+    pub fn lock_timeout(&self, timeout: Duration) -> Option<MutexGuard<'_, AppState>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let attempt = self.0.try_lock();
+            match attempt {
+                Ok(app_state) => return Some(app_state),
+                // 交給 lock() 恢復內容並送出 state-recovered
+                Err(TryLockError::Poisoned(poisoned)) => {
+                    drop(poisoned);
+                    return self.lock().ok();
+                }
+                Err(TryLockError::WouldBlock) if Instant::now() >= deadline => return None,
+                Err(TryLockError::WouldBlock) => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    }
+}
+
+impl AppState {
+    /// panic 可能發生在更新傳送工作的途中；停止所有會主動送出訊框的工作，
+    /// 裝置、通道設定與接收串流維持原狀。回傳停止的工作
+    fn recover_after_panic(&mut self) -> Vec<String> {
+        let mut stopped = Vec::new();
+        let mut task_ids: Vec<u32> = self.periodic_tasks.keys().copied().collect();
+        task_ids.sort_unstable();
+        stopped.extend(task_ids.iter().map(|task_id| format!("periodic task {}", task_id)));
+        self.stop_periodic_tasks();
+        // 重播的停止旗標就是它的取消權杖
+        self.replay = None;
+        stopped.extend(self.cancel_all_operations());
+        if let Some(running) = self.fuzzer.take() {
+            running.store(false, Ordering::SeqCst);
+            stopped.push("fuzzer".to_string());
+        }
+        let mut operation_ids: Vec<u32> = self.tx_operations.keys().copied().collect();
+        operation_ids.sort_unstable();
+        for (_, (_, control)) in self.tx_operations.drain() {
+            control.abort.store(true, Ordering::SeqCst);
+        }
+        stopped.extend(operation_ids.iter().map(|id| format!("transmit operation {}", id)));
+        stopped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use crate::mock::MockCan;
+    use crate::operation::OperationKind;
+
+    fn poison(state: &Arc<StateMutex>) {
+        let holder = state.clone();
+        let result = std::thread::spawn(move || {
+            let _app_state = holder.0.lock().unwrap();
+            panic!("panic while holding the state lock");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(state.0.is_poisoned());
+    }
+
+    #[test]
+    fn a_poisoned_lock_stops_transmit_work_and_keeps_the_devices() {
+        let mut app_state = AppState::with_interface(Arc::new(MockCan::new()));
+        app_state.open_device(4, 0, None).unwrap();
+        let operation = app_state.start_operation(OperationKind::Burst, (4, 0), 0, None);
+        let fuzzer = Arc::new(AtomicBool::new(true));
+        app_state.fuzzer = Some(fuzzer.clone());
+        let state = Arc::new(StateMutex::new(app_state));
+        poison(&state);
+
+        let mut app_state = state.lock().unwrap();
+        assert!(!state.0.is_poisoned());
+        assert!(!operation.running().load(Ordering::SeqCst));
+        assert!(!fuzzer.load(Ordering::SeqCst));
+        assert!(app_state.fuzzer.is_none());
+        assert!(app_state.devices.contains_key(&(4, 0)));
+        // 已恢復的內容再次恢復時沒有要停止的工作
+        assert!(app_state.recover_after_panic().is_empty());
+    }
+
+    #[test]
+    fn lock_timeout_gives_up_on_a_held_lock_and_recovers_a_poisoned_one() {
+        let state = Arc::new(StateMutex::default());
+        {
+            let _held = state.lock().unwrap();
+            let started = Instant::now();
+            assert!(state.lock_timeout(Duration::from_millis(20)).is_none());
+            assert!(started.elapsed() >= Duration::from_millis(20));
+        }
+        poison(&state);
+        assert!(state.lock_timeout(Duration::from_millis(20)).is_some());
+        assert!(!state.0.is_poisoned());
+    }
+}
@@ -0,0 +1,894 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::baud::frame_bits;
+use crate::bus_quality::ErrorRate;
+use crate::bus_state::{BusState, ControllerStatus};
+use crate::frame::{host_timestamp_us, CanFrameEvent};
+use crate::memory::{self, CapWarning, SubsystemMemory};
+use crate::ring_buffer::FrameRing;
+use crate::{invalid_argument, DeviceType, StateMutex};
+
+/// 單一仲裁 ID 的統計；標準與擴展 ID 即使數值相同也分開計算
+#[derive(Serialize, Clone, Debug)]
+pub struct IdStats {
+    pub id: u32,
+    pub extended: bool,
+    /// ID 名稱對照表中的名稱
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub count: u64,
+    pub last_dlc: u8,
+    pub last_data: Vec<u8>,
+    /// 最後一個訊框中與前一個訊框不同的位元組索引
+    pub changed_bytes: Vec<u8>,
+    /// changed_bytes 的位元圖，bit i 表示第 i 個位元組有變動
+    pub changed_mask: u8,
+    pub min_period_ms: Option<f64>,
+    pub mean_period_ms: Option<f64>,
+    pub max_period_ms: Option<f64>,
+    /// 最近 PERIOD_WINDOW 個間隔的中位數；收到 MIN_LEARNED_PERIODS 個間隔後才有值
+    pub learned_period_ms: Option<f64>,
+    /// set_expected_period 設定的週期
+    pub configured_period_ms: Option<f64>,
+    /// 間隔的標準差
+    pub jitter_ms: Option<f64>,
+    /// 間隔超過 tolerance 倍預期週期時推算遺漏的週期數；未設定週期時以 learned_period_ms 判斷
+    pub missed_cycles: u64,
+    #[serde(skip)]
+    last_device_timestamp: Option<u32>,
+    #[serde(skip)]
+    last_host_timestamp_us: u64,
+    #[serde(skip)]
+    period_sum_ms: f64,
+    /// Welford 演算法的平方差累計，用於 jitter_ms
+    #[serde(skip)]
+    period_m2: f64,
+    #[serde(skip)]
+    recent_periods: VecDeque<f64>,
+    #[serde(skip)]
+    expected: Option<ExpectedPeriod>,
+}
+
+/// 最近多少個間隔用來學習週期
+const PERIOD_WINDOW: usize = 32;
+const MIN_LEARNED_PERIODS: usize = 8;
+/// 未設定時，間隔超過 1.5 倍週期視為遺漏
+pub const DEFAULT_PERIOD_TOLERANCE: f64 = 1.5;
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ExpectedPeriod {
+    pub period_ms: f64,
+    pub tolerance: f64,
+}
+
+impl ExpectedPeriod {
+    /// tolerance 省略時為 DEFAULT_PERIOD_TOLERANCE
+    pub fn new(period_ms: f64, tolerance: Option<f64>) -> Result<Self, String> {
+        if !period_ms.is_finite() || period_ms <= 0.0 {
+            return Err(invalid_argument("period_ms", "must be greater than 0"));
+        }
+        let tolerance = tolerance.unwrap_or(DEFAULT_PERIOD_TOLERANCE);
+        if !tolerance.is_finite() || tolerance <= 1.0 {
+            return Err(invalid_argument("tolerance", "must be greater than 1"));
+        }
+        Ok(Self { period_ms, tolerance })
+    }
+}
+
+/// cycle-missed 事件；只針對以 set_expected_period 設定週期的 ID 送出
+#[derive(Serialize, Clone, Debug)]
+pub struct CycleMissedEvent {
+    pub device_type: u32,
+    pub device_index: u32,
+    pub channel: u32,
+    pub id: u32,
+    pub extended: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub gap_ms: f64,
+    pub expected_period_ms: f64,
+    /// 這個間隔中遺漏的週期數
+    pub missed: u64,
+    pub missed_total: u64,
+}
+
+impl IdStats {
+    fn new(frame: &CanFrameEvent) -> Self {
+        Self {
+            id: frame.id,
+            extended: frame.extended,
+            name: None,
+            count: 0,
+            last_dlc: frame.dlc,
+            last_data: Vec::new(),
+            changed_bytes: Vec::new(),
+            changed_mask: 0,
+            min_period_ms: None,
+            mean_period_ms: None,
+            max_period_ms: None,
+            learned_period_ms: None,
+            configured_period_ms: None,
+            jitter_ms: None,
+            missed_cycles: 0,
+            last_device_timestamp: None,
+            last_host_timestamp_us: 0,
+            period_sum_ms: 0.0,
+            period_m2: 0.0,
+            recent_periods: VecDeque::new(),
+            expected: None,
+        }
+    }
+
+    fn set_expected(&mut self, expected: Option<ExpectedPeriod>) {
+        self.expected = expected;
+        self.configured_period_ms = expected.map(|e| e.period_ms);
+    }
+
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<((u32, bool), IdStats)>()
+            + self.last_data.capacity()
+            + self.changed_bytes.capacity()
+            + self.recent_periods.capacity() * std::mem::size_of::<f64>()
+            + self.name.as_ref().map_or(0, String::len)
+    }
+
+    /// 回傳設定了週期的 ID 遺漏了幾個週期
+    fn update(&mut self, frame: &CanFrameEvent) -> Option<CycleMissedEvent> {
+        let mut missed_event = None;
+        if self.count > 0 {
+            let period_ms = period_ms(
+                (self.last_device_timestamp, self.last_host_timestamp_us),
+                (frame.device_timestamp, frame.host_timestamp_us),
+            );
+            missed_event = self.check_gap(frame, period_ms);
+            let previous_mean = self.mean_period_ms.unwrap_or(0.0);
+            self.period_sum_ms += period_ms;
+            self.min_period_ms = Some(self.min_period_ms.map_or(period_ms, |m| m.min(period_ms)));
+            self.max_period_ms = Some(self.max_period_ms.map_or(period_ms, |m| m.max(period_ms)));
+            let mean = self.period_sum_ms / self.count as f64;
+            self.mean_period_ms = Some(mean);
+            self.period_m2 += (period_ms - previous_mean) * (period_ms - mean);
+            self.jitter_ms = Some((self.period_m2 / self.count as f64).max(0.0).sqrt());
+            if self.recent_periods.len() == PERIOD_WINDOW {
+                self.recent_periods.pop_front();
+            }
+            self.recent_periods.push_back(period_ms);
+            if self.recent_periods.len() >= MIN_LEARNED_PERIODS {
+                let mut sorted: Vec<f64> = self.recent_periods.iter().copied().collect();
+                sorted.sort_by(f64::total_cmp);
+                self.learned_period_ms = Some(sorted[sorted.len() / 2]);
+            }
+        }
+        let len = self.last_data.len().max(frame.data.len());
+        self.changed_bytes = (0..len)
+            .filter(|&i| self.last_data.get(i) != frame.data.get(i))
+            .map(|i| i as u8)
+            .collect();
+        self.changed_mask = self.changed_bytes.iter().filter(|&&i| i < 8).fold(0, |mask, &i| mask | 1 << i);
+        if self.name != frame.name {
+            self.name = frame.name.clone();
+        }
+        self.count += 1;
+        self.last_dlc = frame.dlc;
+        self.last_data = frame.data.clone();
+        self.last_device_timestamp = frame.device_timestamp;
+        self.last_host_timestamp_us = frame.host_timestamp_us;
+        missed_event
+    }
+
+    fn check_gap(&mut self, frame: &CanFrameEvent, gap_ms: f64) -> Option<CycleMissedEvent> {
+        let expected = self.expected.or_else(|| {
+            Some(ExpectedPeriod {
+                period_ms: self.learned_period_ms?,
+                tolerance: DEFAULT_PERIOD_TOLERANCE,
+            })
+        })?;
+        if expected.period_ms <= 0.0 || gap_ms <= expected.period_ms * expected.tolerance {
+            return None;
+        }
+        let missed = ((gap_ms / expected.period_ms).round() as u64).saturating_sub(1).max(1);
+        self.missed_cycles += missed;
+        self.expected.map(|_| CycleMissedEvent {
+            device_type: frame.device_type,
+            device_index: frame.device_index,
+            channel: frame.channel,
+            id: frame.id,
+            extended: frame.extended,
+            name: frame.name.clone(),
+            gap_ms,
+            expected_period_ms: expected.period_ms,
+            missed,
+            missed_total: self.missed_cycles,
+        })
+    }
+}
+
+/// 兩個訊框的間隔；兩者都有裝置時間戳記時以裝置時間計算 (0.1 ms，允許溢位回繞)，否則用主機時間
+fn period_ms(previous: (Option<u32>, u64), current: (Option<u32>, u64)) -> f64 {
+    match (previous.0, current.0) {
+        (Some(prev), Some(cur)) => cur.wrapping_sub(prev) as f64 / 10.0,
+        _ => current.1.saturating_sub(previous.1) as f64 / 1000.0,
+    }
+}
+
+/// (dev_type, dev_index, channel)
+type ChannelSlot = (u32, u32, u32);
+
+/// 各通道的 ID 統計表；每個通道最多追蹤 max_ids 個 ID，
+/// 隨機 ID 的流量 (例如模糊測試) 不會讓表無限成長
+pub struct IdStatistics {
+    channels: HashMap<ChannelSlot, HashMap<(u32, bool), IdStats>>,
+    /// 上次 take_changed 之後有更新的 ID
+    dirty: HashMap<ChannelSlot, HashSet<(u32, bool)>>,
+    /// 以 set_expected_period 設定的週期；reset 統計時保留
+    expected: HashMap<ChannelSlot, HashMap<(u32, bool), ExpectedPeriod>>,
+    max_ids: usize,
+    warning: CapWarning,
+}
+
+impl Default for IdStatistics {
+    fn default() -> Self {
+        Self {
+            channels: HashMap::new(),
+            dirty: HashMap::new(),
+            expected: HashMap::new(),
+            max_ids: memory::DEFAULT_MAX_TRACKED_IDS,
+            warning: CapWarning::default(),
+        }
+    }
+}
+
+impl IdStatistics {
+    pub fn max_ids(&self) -> usize {
+        self.max_ids
+    }
+
+    pub fn set_max_ids(&mut self, max_ids: usize) {
+        self.max_ids = max_ids;
+        let slots: Vec<ChannelSlot> = self.channels.keys().copied().collect();
+        for slot in slots {
+            self.enforce_cap(slot);
+        }
+    }
+
+    /// 超過上限時移除最久沒出現的 ID
+    fn enforce_cap(&mut self, slot: ChannelSlot) {
+        let Some(ids) = self.channels.get_mut(&slot) else {
+            return;
+        };
+        let evicted = memory::evict_least_recent(ids, self.max_ids, |stats| stats.last_host_timestamp_us);
+        if evicted > 0 {
+            if let Some(dirty) = self.dirty.get_mut(&slot) {
+                dirty.retain(|key| ids.contains_key(key));
+            }
+            self.warning.evicted("id-statistics", evicted as u64, self.max_ids);
+        }
+    }
+
+    pub fn memory(&self) -> SubsystemMemory {
+        let ids = self.channels.values().flat_map(|ids| ids.values());
+        SubsystemMemory {
+            entries: self.channels.values().map(HashMap::len).sum(),
+            bytes: ids.map(IdStats::estimated_bytes).sum(),
+            limit: self.max_ids,
+            evictions: self.warning.evictions(),
+        }
+    }
+
+    /// 設定了週期的 ID 間隔過長時回傳 cycle-missed 事件
+    pub fn record(&mut self, frame: &CanFrameEvent) -> Option<CycleMissedEvent> {
+        let slot = (frame.device_type, frame.device_index, frame.channel);
+        let key = (frame.id, frame.extended);
+        let expected = self.expected.get(&slot).and_then(|ids| ids.get(&key)).copied();
+        let missed = self
+            .channels
+            .entry(slot)
+            .or_default()
+            .entry(key)
+            .or_insert_with(|| {
+                let mut stats = IdStats::new(frame);
+                stats.set_expected(expected);
+                stats
+            })
+            .update(frame);
+        self.dirty.entry(slot).or_default().insert(key);
+        if self.channels.get(&slot).is_some_and(|ids| ids.len() > self.max_ids) {
+            self.enforce_cap(slot);
+        }
+        missed
+    }
+
+    /// 設定或清除 (None) 一個 ID 的預期週期
+    pub fn set_expected_period(&mut self, device: (u32, u32), channel: u32, key: (u32, bool), expected: Option<ExpectedPeriod>) {
+        let slot = (device.0, device.1, channel);
+        match expected {
+            Some(expected) => self.expected.entry(slot).or_default().insert(key, expected),
+            None => self.expected.get_mut(&slot).and_then(|ids| ids.remove(&key)),
+        };
+        if let Some(stats) = self.channels.get_mut(&slot).and_then(|ids| ids.get_mut(&key)) {
+            stats.set_expected(expected);
+            self.dirty.entry(slot).or_default().insert(key);
+        }
+    }
+
+    /// 取出上次呼叫後有變動的項目，供 can-id-table 事件只送差異
+    pub fn take_changed(&mut self, device: (u32, u32), channel: u32) -> Vec<IdStats> {
+        let slot = (device.0, device.1, channel);
+        let Some(dirty) = self.dirty.remove(&slot) else {
+            return Vec::new();
+        };
+        let Some(ids) = self.channels.get(&slot) else {
+            return Vec::new();
+        };
+        let mut changed: Vec<IdStats> = dirty.iter().filter_map(|key| ids.get(key).cloned()).collect();
+        changed.sort_by_key(|s| (s.id, s.extended));
+        changed
+    }
+
+    pub fn table(&self, device: (u32, u32), channel: u32) -> Vec<IdStats> {
+        let mut table: Vec<IdStats> = self
+            .channels
+            .get(&(device.0, device.1, channel))
+            .map(|ids| ids.values().cloned().collect())
+            .unwrap_or_default();
+        table.sort_by_key(|s| (s.id, s.extended));
+        table
+    }
+
+    pub fn reset(&mut self, device: (u32, u32), channel: u32) {
+        let slot = (device.0, device.1, channel);
+        self.channels.remove(&slot);
+        self.dirty.remove(&slot);
+    }
+}
+
+/// 解析指定的裝置與通道後，在 state 鎖外以裝置 key 操作統計表
+fn with_id_statistics<T>(
+    state: &StateMutex,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    f: impl FnOnce(&mut IdStatistics, (u32, u32)) -> T,
+) -> Result<T, String> {
+    let (key, id_statistics) = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+        device.check_channel(channel)?;
+        (device.key(), app_state.id_statistics.clone())
+    };
+    let mut id_statistics = id_statistics.lock().map_err(|_| "Failed to lock statistics")?;
+    Ok(f(&mut id_statistics, key))
+}
+
+#[tauri::command]
+pub fn get_id_statistics(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<Vec<IdStats>, String> {
+    with_id_statistics(&state, dev_type, dev_index, channel, |stats, key| stats.table(key, channel))
+}
+
+/// 固定檢視 (覆寫模式) 初次繪製用的完整 ID 表，之後以 can-id-table 事件增量更新
+#[tauri::command]
+pub fn get_id_table(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<Vec<IdStats>, String> {
+    get_id_statistics(dev_type, dev_index, channel, state)
+}
+
+/// 設定 ID 的預期週期；間隔超過 tolerance (預設 1.5) 倍週期時計入 missed_cycles 並送出 cycle-missed。
+/// 省略 period_ms 時清除設定，改回以觀察到的中位數判斷 (只計數，不送事件)
+#[tauri::command]
+pub fn set_expected_period(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    id: u32,
+    extended: Option<bool>,
+    period_ms: Option<f64>,
+    tolerance: Option<f64>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let extended = extended.unwrap_or(id > 0x7FF);
+    let expected = period_ms.map(|period_ms| ExpectedPeriod::new(period_ms, tolerance)).transpose()?;
+    with_id_statistics(&state, dev_type, dev_index, channel, |stats, key| {
+        stats.set_expected_period(key, channel, (id, extended), expected)
+    })?;
+    Ok(match expected {
+        Some(expected) => format!("CAN{} 0x{:X} expected every {} ms", channel + 1, id, expected.period_ms),
+        None => format!("CAN{} 0x{:X} expected period cleared", channel + 1, id),
+    })
+}
+
+#[tauri::command]
+pub fn reset_id_statistics(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    with_id_statistics(&state, dev_type, dev_index, channel, |stats, key| stats.reset(key, channel))?;
+    Ok(format!("ID statistics for CAN{} reset", channel + 1))
+}
+
+#[derive(Serialize)]
+pub struct StatisticsReset {
+    pub channels: Vec<u32>,
+    /// 歸零的主機時間 (μs)，與之後 can-stats 事件的 since 相同
+    pub since: u64,
+}
+
+/// 歸零通道的統計，不需停止接收。channel 省略時歸零裝置的所有通道，scope 省略時歸零全部統計。
+/// 接收迴圈在同一把鎖下更新計數，歸零後不會被進行中的更新寫回舊數值
+#[tauri::command]
+pub fn reset_statistics(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: Option<u32>,
+    scope: Option<Vec<StatsScope>>,
+    state: State<Arc<StateMutex>>,
+) -> Result<StatisticsReset, String> {
+    let scopes = scope.unwrap_or_else(|| vec![StatsScope::All]);
+    if scopes.is_empty() {
+        return Err(invalid_argument("scope", "must not be empty"));
+    }
+    let (key, channels, counters, id_statistics) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+        let key = device.key();
+        let channels = match channel {
+            Some(channel) => {
+                device.check_channel(channel)?;
+                vec![channel]
+            }
+            None => match device.channel_count {
+                Some(count) => (0..count as u32).collect(),
+                // 尚未讀到板卡資訊時只歸零已有計數器的通道
+                None => {
+                    let mut channels: Vec<u32> = app_state
+                        .channel_runtime
+                        .keys()
+                        .filter(|(t, i, _)| (*t, *i) == key)
+                        .map(|&(_, _, c)| c)
+                        .collect();
+                    channels.sort_unstable();
+                    channels
+                }
+            },
+        };
+        let counters: Vec<Arc<ChannelCounters>> = channels.iter().map(|&c| app_state.channel_counters(key, c)).collect();
+        (key, channels, counters, app_state.id_statistics.clone())
+    };
+    if scopes.iter().any(|s| matches!(s, StatsScope::All | StatsScope::IdTable)) {
+        let mut id_statistics = id_statistics.lock().map_err(|_| "Failed to lock statistics")?;
+        for &channel in &channels {
+            id_statistics.reset(key, channel);
+        }
+    }
+    for counters in &counters {
+        counters.reset_scopes(&scopes);
+    }
+    Ok(StatisticsReset {
+        channels,
+        since: counters.first().map(|c| c.since_us()).unwrap_or_else(host_timestamp_us),
+    })
+}
+
+/// 通道層級的計數器；以原子變數實作，接收/傳送路徑不需取得 state 鎖即可累加
+#[derive(Default)]
+pub struct ChannelCounters {
+    pub rx_frames: AtomicU64,
+    pub tx_frames: AtomicU64,
+    pub errors: AtomicU64,
+    /// 匯流排錯誤事件：VCI_ReadErrInfo 回報的錯誤與 VCI_Receive 失敗，用於計算錯誤率
+    pub bus_errors: AtomicU64,
+    pub events_dropped: AtomicU64,
+    /// 目前統計視窗內 (RX + TX) 訊框估計佔用的位元數
+    pub bus_bits: AtomicU64,
+    /// 通道初始化時設定的位元率，0 表示未知
+    pub bitrate: AtomicU32,
+    /// 最近一個統計視窗的匯流排負載，單位 0.01%
+    bus_load: AtomicU32,
+    /// 進入 transmit_paced 後仍在等待速率限制的訊框數
+    pub tx_pending: AtomicU64,
+    /// set_tx_rate_limit 設定的每秒訊框數，0 表示未限制
+    pub tx_rate_limit: AtomicU32,
+    /// 禁止傳送期間被拒絕的訊框數
+    pub tx_refused: AtomicU64,
+    /// 接收迴圈查詢驅動 (VCI_GetReceiveNum 或 VCI_Receive) 的次數
+    pub receive_polls: AtomicU64,
+    /// 偵測到接收溢出 (可能遺失訊框) 的次數
+    pub overflows: AtomicU64,
+    /// can-data 事件佇列已滿而丟棄的訊框數
+    pub frames_dropped: AtomicU64,
+    /// set_dedupe 抑制的重複訊框數 (仍計入 rx_frames)
+    pub suppressed: AtomicU64,
+    /// 最後一次歸零的主機時間 (μs)，0 表示尚未歸零過
+    since_us: AtomicU64,
+    /// 接收執行緒最近一次讀到的控制器狀態；後端不支援 VCI_ReadCANStatus 時為 None
+    pub controller: Mutex<Option<ControllerStatus>>,
+    /// 歸零與統計視窗結算互斥，避免結算途中被歸零後又寫回舊的負載值
+    window: Mutex<()>,
+}
+
+/// reset_statistics 可以個別歸零的統計
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsScope {
+    All,
+    /// RX/TX 訊框總數與丟棄、抑制的事件、訊框數
+    Frames,
+    /// ID 統計表
+    IdTable,
+    Errors,
+    BusLoad,
+    Overflows,
+}
+
+impl ChannelCounters {
+    /// 通道重新初始化時歸零
+    pub fn reset(&self) {
+        self.reset_scopes(&[StatsScope::All]);
+        *self.controller.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// 歸零指定的計數器並記下歸零時間；ID 統計表不在這裡，由 IdStatistics::reset 處理
+    pub fn reset_scopes(&self, scopes: &[StatsScope]) {
+        let _window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+        let all = scopes.contains(&StatsScope::All);
+        let selected = |scope| all || scopes.contains(&scope);
+        if selected(StatsScope::Frames) {
+            for counter in [&self.rx_frames, &self.tx_frames, &self.tx_refused, &self.events_dropped, &self.frames_dropped, &self.suppressed] {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+        if selected(StatsScope::Errors) {
+            self.errors.store(0, Ordering::Relaxed);
+            self.bus_errors.store(0, Ordering::Relaxed);
+        }
+        if selected(StatsScope::BusLoad) {
+            self.bus_bits.store(0, Ordering::Relaxed);
+            self.bus_load.store(0, Ordering::Relaxed);
+        }
+        if selected(StatsScope::Overflows) {
+            self.overflows.store(0, Ordering::Relaxed);
+        }
+        self.since_us.store(host_timestamp_us(), Ordering::Relaxed);
+    }
+
+    pub fn since_us(&self) -> u64 {
+        self.since_us.load(Ordering::Relaxed)
+    }
+
+    pub fn add_bus_frame(&self, extended: bool, remote: bool, dlc: u8) {
+        self.bus_bits.fetch_add(frame_bits(extended, remote, dlc) as u64, Ordering::Relaxed);
+    }
+
+    pub fn bus_load_percent(&self) -> f64 {
+        self.bus_load.load(Ordering::Relaxed) as f64 / 100.0
+    }
+
+    /// 以這段時間內累積的位元數除以位元率估算負載，並開始新的視窗
+    fn update_bus_load(&self, seconds: f64) -> f64 {
+        let _window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+        let bits = self.bus_bits.swap(0, Ordering::Relaxed);
+        let bitrate = self.bitrate.load(Ordering::Relaxed);
+        if bitrate == 0 || seconds <= 0.0 {
+            return self.bus_load_percent();
+        }
+        let percent = (bits as f64 / (bitrate as f64 * seconds) * 100.0).min(100.0);
+        self.bus_load.store((percent * 100.0).round() as u32, Ordering::Relaxed);
+        percent
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct ChannelStatsEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub rx_per_sec: f64,
+    pub tx_per_sec: f64,
+    pub rx_total: u64,
+    pub tx_total: u64,
+    pub errors: u64,
+    pub events_dropped: u64,
+    /// can-data 事件佇列已滿而沒有送往前端的訊框數 (仍在環形緩衝與記錄檔中)
+    pub frames_dropped: u64,
+    /// set_dedupe 抑制、沒有放進環形緩衝的重複訊框數
+    pub suppressed: u64,
+    pub buffer_fill: usize,
+    pub buffer_capacity: usize,
+    pub bus_load_percent: f64,
+    pub tx_rate_limit: Option<u32>,
+    /// set_tx_inhibit 禁止此通道傳送；所有傳送路徑都會回傳 TxInhibited
+    pub tx_inhibited: bool,
+    pub tx_refused: u64,
+    pub tx_pending: u64,
+    /// 統計視窗內接收迴圈每秒查詢驅動的次數；匯流排安靜時應明顯下降
+    pub poll_rate_hz: f64,
+    pub overflows: u64,
+    pub rx_err_counter: Option<u8>,
+    pub tx_err_counter: Option<u8>,
+    pub bus_state: Option<BusState>,
+    /// 錯誤率視窗 (set_bus_quality_threshold 的 window_s) 內的平均值
+    pub errors_per_sec: f64,
+    pub errors_per_1000_frames: Option<f64>,
+    /// 計數器最後一次歸零的主機時間 (μs)，0 表示尚未歸零過
+    pub since: u64,
+}
+
+/// 由接收執行緒定期呼叫，每隔 interval 產生一次 can-stats 事件
+pub struct StatsReporter {
+    key: (u32, u32),
+    channel: u32,
+    counters: Arc<ChannelCounters>,
+    tx_inhibited: Arc<AtomicBool>,
+    interval: Duration,
+    last_report: Instant,
+    last_rx: u64,
+    last_tx: u64,
+    last_polls: u64,
+    last_since: u64,
+}
+
+impl StatsReporter {
+    pub fn new(key: (u32, u32), channel: u32, counters: Arc<ChannelCounters>, tx_inhibited: Arc<AtomicBool>, interval: Duration) -> Self {
+        Self {
+            key,
+            channel,
+            last_rx: counters.rx_frames.load(Ordering::Relaxed),
+            last_tx: counters.tx_frames.load(Ordering::Relaxed),
+            last_polls: counters.receive_polls.load(Ordering::Relaxed),
+            last_since: counters.since_us(),
+            counters,
+            tx_inhibited,
+            interval,
+            last_report: Instant::now(),
+        }
+    }
+
+    pub fn poll(&mut self, frame_buffer: &Mutex<FrameRing>, error_rate: ErrorRate) -> Option<ChannelStatsEvent> {
+        let elapsed = self.last_report.elapsed();
+        let since = self.counters.since_us();
+        // 歸零後立即送出一次，讓前端馬上看到歸零的數值
+        let was_reset = since != self.last_since;
+        if elapsed < self.interval && !was_reset {
+            return None;
+        }
+        self.last_report = Instant::now();
+        if was_reset {
+            self.last_since = since;
+            self.last_rx = 0;
+            self.last_tx = 0;
+        }
+        let rx_total = self.counters.rx_frames.load(Ordering::Relaxed);
+        let tx_total = self.counters.tx_frames.load(Ordering::Relaxed);
+        // 計數器被歸零時 total 會小於上次的值
+        let rx_delta = rx_total.checked_sub(self.last_rx).unwrap_or(rx_total);
+        let tx_delta = tx_total.checked_sub(self.last_tx).unwrap_or(tx_total);
+        let polls = self.counters.receive_polls.load(Ordering::Relaxed);
+        let polls_delta = polls.saturating_sub(self.last_polls);
+        self.last_rx = rx_total;
+        self.last_tx = tx_total;
+        self.last_polls = polls;
+        let seconds = elapsed.as_secs_f64();
+        let (buffer_fill, buffer_capacity) = frame_buffer
+            .lock()
+            .map(|ring| {
+                let status = ring.status();
+                (status.len, status.capacity)
+            })
+            .unwrap_or_default();
+        let controller = *self.counters.controller.lock().unwrap_or_else(PoisonError::into_inner);
+        Some(ChannelStatsEvent {
+            dev_type: self.key.0,
+            dev_index: self.key.1,
+            channel: self.channel,
+            rx_per_sec: rx_delta as f64 / seconds,
+            tx_per_sec: tx_delta as f64 / seconds,
+            rx_total,
+            tx_total,
+            errors: self.counters.errors.load(Ordering::Relaxed),
+            events_dropped: self.counters.events_dropped.load(Ordering::Relaxed),
+            frames_dropped: self.counters.frames_dropped.load(Ordering::Relaxed),
+            suppressed: self.counters.suppressed.load(Ordering::Relaxed),
+            buffer_fill,
+            buffer_capacity,
+            bus_load_percent: self.counters.update_bus_load(seconds),
+            tx_rate_limit: Some(self.counters.tx_rate_limit.load(Ordering::Relaxed)).filter(|&limit| limit > 0),
+            tx_inhibited: self.tx_inhibited.load(Ordering::SeqCst),
+            tx_refused: self.counters.tx_refused.load(Ordering::Relaxed),
+            tx_pending: self.counters.tx_pending.load(Ordering::Relaxed),
+            poll_rate_hz: polls_delta as f64 / seconds,
+            overflows: self.counters.overflows.load(Ordering::Relaxed),
+            rx_err_counter: controller.map(|c| c.rx_err_counter),
+            tx_err_counter: controller.map(|c| c.tx_err_counter),
+            bus_state: controller.map(|c| c.bus_state),
+            errors_per_sec: error_rate.errors_per_sec,
+            errors_per_1000_frames: error_rate.errors_per_1000_frames,
+            since,
+        })
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct IdTableEvent {
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    /// 只包含上次快照後有變動的 ID
+    pub entries: Vec<IdStats>,
+}
+
+/// 依通道設定的頻率產生 can-id-table 事件；與 can-data 串流各自獨立開關
+pub struct IdTableReporter {
+    key: (u32, u32),
+    channel: u32,
+    last_report: Instant,
+}
+
+impl IdTableReporter {
+    pub fn new(key: (u32, u32), channel: u32) -> Self {
+        Self {
+            key,
+            channel,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// interval_ms 為 0 表示未啟用
+    pub fn poll(&mut self, interval_ms: u64, id_statistics: &Mutex<IdStatistics>) -> Option<IdTableEvent> {
+        if interval_ms == 0 || self.last_report.elapsed() < Duration::from_millis(interval_ms) {
+            return None;
+        }
+        self.last_report = Instant::now();
+        let entries = id_statistics.lock().ok()?.take_changed(self.key, self.channel);
+        (!entries.is_empty()).then_some(IdTableEvent {
+            dev_type: self.key.0,
+            dev_index: self.key.1,
+            channel: self.channel,
+            entries,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct BusLoad {
+    pub channel: u32,
+    pub bitrate: Option<u32>,
+    pub load_percent: f64,
+}
+
+/// 最近一個統計視窗的匯流排負載估計值 (需有接收執行緒在運作)
+#[tauri::command]
+pub fn get_bus_load(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<BusLoad, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let key = app_state.device(dev_type.map(DeviceType::code), dev_index)?.key();
+    let counters = app_state.channel_counters(key, channel);
+    let bitrate = counters.bitrate.load(Ordering::Relaxed);
+    Ok(BusLoad {
+        channel,
+        bitrate: (bitrate != 0).then_some(bitrate),
+        load_percent: counters.bus_load_percent(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AppState, VciCanObj};
+
+    fn frame(key: (u32, u32), channel: u32, id: u32, host_timestamp_us: u64) -> CanFrameEvent {
+        let can_obj = VciCanObj {
+            id,
+            data_len: 1,
+            ..Default::default()
+        };
+        CanFrameEvent::from_raw(key, channel, &can_obj, host_timestamp_us)
+    }
+
+    #[test]
+    fn the_same_channel_on_two_devices_keeps_separate_id_tables() {
+        let mut stats = IdStatistics::default();
+        stats.record(&frame((4, 0), 0, 0x100, 1_000));
+        stats.record(&frame((4, 0), 0, 0x100, 2_000));
+        stats.record(&frame((4, 1), 0, 0x200, 1_000));
+
+        let first = stats.table((4, 0), 0);
+        assert_eq!(first.len(), 1);
+        assert_eq!((first[0].id, first[0].count), (0x100, 2));
+        let second = stats.table((4, 1), 0);
+        assert_eq!(second.len(), 1);
+        assert_eq!((second[0].id, second[0].count), (0x200, 1));
+
+        stats.reset((4, 0), 0);
+        assert!(stats.table((4, 0), 0).is_empty());
+        assert_eq!(stats.table((4, 1), 0).len(), 1);
+        assert!(stats.take_changed((4, 0), 0).is_empty());
+        assert_eq!(stats.take_changed((4, 1), 0).len(), 1);
+    }
+
+    #[test]
+    fn an_expected_period_applies_only_to_its_own_device() {
+        let mut stats = IdStatistics::default();
+        let expected = ExpectedPeriod {
+            period_ms: 10.0,
+            tolerance: DEFAULT_PERIOD_TOLERANCE,
+        };
+        stats.set_expected_period((4, 0), 0, (0x100, false), Some(expected));
+        for key in [(4, 0), (4, 1)] {
+            stats.record(&frame(key, 0, 0x100, 0));
+        }
+        // 間隔 50 ms，只有設定了週期的裝置送出 cycle-missed
+        assert!(stats.record(&frame((4, 0), 0, 0x100, 50_000)).is_some());
+        assert!(stats.record(&frame((4, 1), 0, 0x100, 50_000)).is_none());
+    }
+
+    #[test]
+    fn a_steady_id_learns_its_period_and_counts_missed_cycles() {
+        let mut stats = IdStatistics::default();
+        for i in 0..10 {
+            assert!(stats.record(&frame((4, 0), 0, 0x100, i * 10_000)).is_none());
+        }
+        let steady = &stats.table((4, 0), 0)[0];
+        assert_eq!((steady.learned_period_ms, steady.jitter_ms, steady.missed_cycles), (Some(10.0), Some(0.0), 0));
+
+        // 未設定週期時以學到的週期計數，但不送事件；35 ms 的間隔約遺漏 3 個週期
+        assert!(stats.record(&frame((4, 0), 0, 0x100, 125_000)).is_none());
+        let gapped = &stats.table((4, 0), 0)[0];
+        assert_eq!(gapped.missed_cycles, 3);
+        assert!(gapped.jitter_ms.unwrap() > 0.0);
+        assert_eq!(gapped.max_period_ms, Some(35.0));
+
+        stats.set_expected_period((4, 0), 0, (0x100, false), Some(ExpectedPeriod::new(10.0, None).unwrap()));
+        let event = stats.record(&frame((4, 0), 0, 0x100, 145_000)).unwrap();
+        assert_eq!((event.missed, event.missed_total, event.expected_period_ms), (1, 4, 10.0));
+        // 在 tolerance 內的間隔不算遺漏
+        assert!(stats.record(&frame((4, 0), 0, 0x100, 159_000)).is_none());
+    }
+
+    #[test]
+    fn an_expected_period_must_be_positive_with_a_tolerance_above_one() {
+        assert_eq!(ExpectedPeriod::new(20.0, None).unwrap().tolerance, DEFAULT_PERIOD_TOLERANCE);
+        for (period_ms, tolerance, field) in [
+            (0.0, None, "period_ms"),
+            (-5.0, None, "period_ms"),
+            (f64::NAN, None, "period_ms"),
+            (10.0, Some(1.0), "tolerance"),
+            (10.0, Some(f64::INFINITY), "tolerance"),
+        ] {
+            let error = ExpectedPeriod::new(period_ms, tolerance).unwrap_err();
+            assert!(error.starts_with(&format!("InvalidArgument {{ field: \"{}\"", field)), "{}", error);
+        }
+    }
+
+    #[test]
+    fn id_statistics_commands_reject_an_unopened_device_or_missing_channel() {
+        let mut app_state = AppState::with_interface(Arc::new(crate::mock::MockCan::new()));
+        app_state.open_device(4, 0, None).unwrap();
+        app_state.devices.get_mut(&(4, 0)).unwrap().channel_count = Some(2);
+        let state = StateMutex::new(app_state);
+
+        assert!(with_id_statistics(&state, Some(DeviceType::Usbcan2), Some(1), 0, |_, _| ()).is_err());
+        let error = with_id_statistics(&state, Some(DeviceType::Usbcan2), Some(0), 2, |_, _| ()).unwrap_err();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        assert_eq!(with_id_statistics(&state, None, None, 1, |_, key| key), Ok((4, 0)));
+    }
+}
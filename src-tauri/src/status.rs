@@ -0,0 +1,151 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, PoisonError};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::operation::OperationKind;
+use crate::periodic::PeriodicTaskInfo;
+use crate::timed_capture::CaptureRemaining;
+use crate::timestamp::ClockStatus;
+use crate::uds::TesterPresentInfo;
+use crate::{baud, Backend, DeviceType, StateMutex};
+
+#[derive(Serialize)]
+pub struct ChannelStatus {
+    pub channel: u32,
+    pub initialized: bool,
+    pub started: bool,
+    /// 由 Timing0/Timing1 換算的位元率；尚未初始化時為 None
+    pub baud: Option<u32>,
+    pub mode: Option<u8>,
+    pub receiving: bool,
+    pub frames_rx: u64,
+    pub frames_tx: u64,
+    /// set_tx_inhibit 禁止此通道傳送
+    pub tx_inhibited: bool,
+    /// 接收設定了自動停止上限時的剩餘時間與訊框數
+    pub capture: Option<CaptureRemaining>,
+    /// 時間戳記模式、目前採用的來源與裝置時鐘的偏移/漂移
+    pub timestamp: ClockStatus,
+}
+
+#[derive(Serialize)]
+pub struct DeviceStatus {
+    pub dev_type: DeviceType,
+    pub dev_index: u32,
+    pub serial_number: Option<String>,
+    pub disconnected: bool,
+    pub channels: Vec<ChannelStatus>,
+}
+
+#[derive(Serialize)]
+pub struct AppStatus {
+    pub backend: Option<Backend>,
+    pub library_path: Option<String>,
+    pub devices: Vec<DeviceStatus>,
+    /// 有任何通道被禁止傳送
+    pub tx_inhibited: bool,
+    pub periodic_tasks: Vec<PeriodicTaskInfo>,
+    pub tester_present: Option<TesterPresentInfo>,
+    /// 記錄中的檔案路徑
+    pub loggers: Vec<String>,
+    /// 記錄設定了自動停止上限時的剩餘時間與訊框數
+    pub logging_capture: Option<CaptureRemaining>,
+    pub replay_active: bool,
+    pub sequences_running: usize,
+    pub fuzzing: bool,
+    pub gateway_active: bool,
+    /// WebSocket 橋接的位址
+    pub ws_bridge: Option<String>,
+    pub ws_clients: usize,
+    /// 執行中的 MQTT 發佈所連線的 broker
+    pub mqtt_publisher: Option<String>,
+    pub device_watch: bool,
+}
+
+/// 後端目前狀態的完整快照；前端重新載入後以此還原畫面，而不必自行猜測
+#[tauri::command]
+pub fn get_status(state: State<Arc<StateMutex>>) -> Result<AppStatus, String> {
+    app_status(&state)
+}
+
+/// get_status 的內容；只短暫取得 state 鎖，不呼叫 DLL
+pub fn app_status(state: &StateMutex) -> Result<AppStatus, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let mut devices: Vec<DeviceStatus> = app_state
+        .devices
+        .values()
+        .map(|device| {
+            // 尚未初始化但已禁止傳送的通道也列出
+            let inhibited = app_state
+                .channel_runtime
+                .iter()
+                .filter(|(&(dev_type, dev_index, _), runtime)| {
+                    (dev_type, dev_index) == device.key() && runtime.tx_inhibited.load(Ordering::SeqCst)
+                })
+                .map(|(&(_, _, channel), _)| channel);
+            let channel_ids: BTreeSet<u32> = device
+                .channels
+                .keys()
+                .chain(device.receiving.keys())
+                .copied()
+                .chain(inhibited)
+                .collect();
+            let channels = channel_ids
+                .into_iter()
+                .map(|channel| {
+                    let channel_state = device.channels.get(&channel);
+                    let runtime = app_state.channel_runtime.get(&(device.dev_type, device.dev_index, channel));
+                    let counters = runtime.map(|runtime| &runtime.counters);
+                    ChannelStatus {
+                        channel,
+                        initialized: channel_state.is_some(),
+                        started: channel_state.is_some_and(|c| c.started),
+                        baud: channel_state.map(|c| baud::bitrate_from_timing(c.config.timing0, c.config.timing1)),
+                        mode: channel_state.map(|c| c.config.mode),
+                        receiving: device.receiving.get(&channel).is_some_and(|r| r.load(Ordering::SeqCst)),
+                        frames_rx: counters.map_or(0, |c| c.rx_frames.load(Ordering::Relaxed)),
+                        frames_tx: counters.map_or(0, |c| c.tx_frames.load(Ordering::Relaxed)),
+                        tx_inhibited: runtime.is_some_and(|runtime| runtime.tx_inhibited.load(Ordering::SeqCst)),
+                        capture: device.captures.get(&channel).map(|c| c.remaining()),
+                        timestamp: runtime
+                            .map(|runtime| *runtime.clock.lock().unwrap_or_else(PoisonError::into_inner))
+                            .unwrap_or_default(),
+                    }
+                })
+                .collect();
+            DeviceStatus {
+                dev_type: DeviceType::from_code(device.dev_type),
+                dev_index: device.dev_index,
+                serial_number: device.serial_number.clone(),
+                disconnected: device.disconnected,
+                channels,
+            }
+        })
+        .collect();
+    devices.sort_by_key(|d| (d.dev_type.code(), d.dev_index));
+    let tx_inhibited = devices.iter().flat_map(|d| &d.channels).any(|c| c.tx_inhibited);
+    Ok(AppStatus {
+        backend: app_state.backend().map(|lib| lib.backend()),
+        library_path: app_state
+            .backend()
+            .and_then(|lib| lib.library_path())
+            .map(|path| path.display().to_string()),
+        devices,
+        tx_inhibited,
+        periodic_tasks: app_state.periodic_task_infos(),
+        tester_present: app_state.tester_present_info(),
+        loggers: app_state.logger.iter().map(|logger| logger.path().display().to_string()).collect(),
+        logging_capture: app_state.logger.as_ref().and_then(|logger| logger.capture()).map(|c| c.remaining()),
+        replay_active: app_state.replay.is_some(),
+        sequences_running: app_state.operations_running(OperationKind::Sequence),
+        fuzzing: app_state.fuzzer.is_some(),
+        gateway_active: app_state.gateway.is_some(),
+        ws_bridge: app_state.ws_bridge.as_ref().map(|bridge| bridge.address().to_string()),
+        ws_clients: app_state.ws_hub.client_count(),
+        mqtt_publisher: app_state.mqtt_publisher.as_ref().map(|publisher| publisher.broker().to_string()),
+        device_watch: app_state.device_watch.is_some(),
+    })
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::ring_buffer::BufferedFrame;
+use crate::{invalid_argument, DeviceType, StateMutex};
+
+/// 包含兩端的 ID 範圍
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct IdRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+struct Subscription {
+    key: (u32, u32),
+    channel: u32,
+    /// None 表示所有 ID
+    id_filter: Option<Vec<IdRange>>,
+    event: String,
+}
+
+impl Subscription {
+    fn accepts(&self, frame: &BufferedFrame) -> bool {
+        self.id_filter
+            .as_ref()
+            .is_none_or(|ranges| ranges.iter().any(|r| (r.start..=r.end).contains(&frame.frame.id)))
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SignalSubscriptionOptions {
+    /// 只看此通道；省略時所有通道分別追蹤
+    pub channel: Option<u32>,
+    /// 與上次送出的值相差超過此值才送出
+    pub deadband: Option<f64>,
+    /// 與上次送出的值相差超過其絕對值的此百分比才送出
+    pub deadband_percent: Option<f64>,
+    /// 每個訊號每秒最多送出的次數；期間內的變化保留最新值，於之後的批次送出
+    pub max_rate_hz: Option<f64>,
+}
+
+/// signal-update 事件中的一筆變化
+#[derive(Serialize, Clone, Debug)]
+pub struct SignalUpdate {
+    pub subscription_id: u32,
+    pub channel: u32,
+    pub message: String,
+    pub signal: String,
+    pub value: f64,
+    /// 上次送出的值；第一次收到時為 None
+    pub previous: Option<f64>,
+    pub host_timestamp_us: u64,
+}
+
+#[derive(Default)]
+struct SignalState {
+    last_value: Option<f64>,
+    last_sent: Option<Instant>,
+    pending: Option<SignalUpdate>,
+}
+
+impl SignalState {
+    fn ready(&self, min_interval: Option<Duration>, now: Instant) -> bool {
+        match (min_interval, self.last_sent) {
+            (Some(interval), Some(last)) => now.duration_since(last) >= interval,
+            _ => true,
+        }
+    }
+
+    fn send(&mut self, update: SignalUpdate, now: Instant, updates: &mut Vec<SignalUpdate>) {
+        self.last_value = Some(update.value);
+        self.last_sent = Some(now);
+        self.pending = None;
+        updates.push(update);
+    }
+}
+
+struct SignalSubscription {
+    /// "Message.Signal" 或只有訊號名稱 (任何訊息中的同名訊號)
+    signals: Vec<String>,
+    options: SignalSubscriptionOptions,
+    min_interval: Option<Duration>,
+    /// 以 (通道, 訊息, 訊號) 區分
+    states: HashMap<(u32, String, String), SignalState>,
+}
+
+impl SignalSubscription {
+    fn selects(&self, message: &str, signal: &str) -> bool {
+        self.signals.iter().any(|spec| match spec.split_once('.') {
+            Some((m, s)) => m == message && s == signal,
+            None => spec == signal,
+        })
+    }
+
+    fn exceeds_deadband(&self, previous: f64, value: f64) -> bool {
+        let delta = (value - previous).abs();
+        let absolute = self.options.deadband.is_none_or(|band| delta > band);
+        let percent = self
+            .options
+            .deadband_percent
+            .is_none_or(|percent| delta > previous.abs() * percent / 100.0);
+        delta > 0.0 && absolute && percent
+    }
+
+    fn process(&mut self, subscription_id: u32, channel: u32, frames: &[BufferedFrame], updates: &mut Vec<SignalUpdate>) {
+        if self.options.channel.is_some_and(|c| c != channel) {
+            return;
+        }
+        let now = Instant::now();
+        for buffered in frames {
+            let Some(decoded) = &buffered.frame.decoded else {
+                continue;
+            };
+            for (signal, &value) in &decoded.signals {
+                if !self.selects(&decoded.message, signal) {
+                    continue;
+                }
+                let key = (channel, decoded.message.clone(), signal.clone());
+                let state = self.states.remove(&key).unwrap_or_default();
+                let update = SignalUpdate {
+                    subscription_id,
+                    channel,
+                    message: decoded.message.clone(),
+                    signal: signal.clone(),
+                    value,
+                    previous: None,
+                    host_timestamp_us: buffered.frame.host_timestamp_us,
+                };
+                let state = self.offer(state, now, update, updates);
+                self.states.insert(key, state);
+            }
+        }
+        // 速率限制期間到了的延後變化，即使本批次沒有該訊號也送出
+        for state in self.states.values_mut() {
+            if !state.ready(self.min_interval, now) {
+                continue;
+            }
+            if let Some(update) = state.pending.take() {
+                state.send(update, now, updates);
+            }
+        }
+    }
+
+    fn offer(&self, mut state: SignalState, now: Instant, mut update: SignalUpdate, updates: &mut Vec<SignalUpdate>) -> SignalState {
+        update.previous = state.last_value;
+        if state.last_value.is_some_and(|previous| !self.exceeds_deadband(previous, update.value)) {
+            // 回到上次送出值附近時取消延後的變化
+            state.pending = None;
+            return state;
+        }
+        if state.ready(self.min_interval, now) {
+            state.send(update, now, updates);
+        } else {
+            state.pending = Some(update);
+        }
+        state
+    }
+}
+
+#[derive(Serialize)]
+pub struct SignalSubscriptionInfo {
+    pub subscription_id: u32,
+    pub signals: Vec<String>,
+    pub options: SignalSubscriptionOptions,
+}
+
+/// 前端各面板的訂閱；接收迴圈每批次在 Rust 端過濾一次後分別送出
+#[derive(Default)]
+pub struct Subscriptions {
+    subscriptions: HashMap<u32, Subscription>,
+    signal_subscriptions: HashMap<u32, SignalSubscription>,
+    next_id: u32,
+}
+
+impl Subscriptions {
+    /// 回傳每個符合的訂閱要送出的事件名稱與訊框
+    pub fn fan_out(&self, key: (u32, u32), channel: u32, frames: &[BufferedFrame]) -> Vec<(String, Vec<BufferedFrame>)> {
+        self.subscriptions
+            .values()
+            .filter(|s| s.key == key && s.channel == channel)
+            .filter_map(|s| {
+                let matched: Vec<BufferedFrame> = frames.iter().filter(|f| s.accepts(f)).cloned().collect();
+                (!matched.is_empty()).then(|| (s.event.clone(), matched))
+            })
+            .collect()
+    }
+
+    /// 通道停止接收時移除它的訂閱
+    pub fn remove_channel(&mut self, key: (u32, u32), channel: u32) {
+        self.subscriptions.retain(|_, s| s.key != key || s.channel != channel);
+    }
+
+    /// 本批次超過死區的訊號變化，由接收迴圈以一個 signal-update 事件送出
+    pub fn signal_updates(&mut self, channel: u32, frames: &[BufferedFrame]) -> Vec<SignalUpdate> {
+        let mut updates = Vec::new();
+        for (&subscription_id, subscription) in &mut self.signal_subscriptions {
+            subscription.process(subscription_id, channel, frames, &mut updates);
+        }
+        updates
+    }
+
+    /// 頁面重新載入時舊頁面的訂閱已無人接收，全部移除
+    pub fn clear(&mut self) {
+        self.subscriptions.clear();
+        self.signal_subscriptions.clear();
+    }
+}
+
+#[derive(Serialize)]
+pub struct Subscribed {
+    pub subscription_id: u32,
+    /// 訂閱的訊框以此事件名稱送出，每批次一個陣列
+    pub event: String,
+}
+
+/// 訂閱通道的訊框；id_filter 省略時收到所有 ID。通道停止接收或頁面重新載入後訂閱自動失效
+#[tauri::command]
+pub fn subscribe(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    id_filter: Option<Vec<IdRange>>,
+    state: State<Arc<StateMutex>>,
+) -> Result<Subscribed, String> {
+    if let Some((i, range)) = id_filter.iter().flatten().enumerate().find(|(_, r)| r.start > r.end) {
+        return Err(invalid_argument(
+            &format!("id_filter[{}]", i),
+            format!("start 0x{:X} is greater than end 0x{:X}", range.start, range.end),
+        ));
+    }
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let key = device.key();
+    if !app_state.receiving_channels(Some(key.0), Some(key.1))?.contains(&channel) {
+        return Err(format!("CAN{} is not receiving", channel + 1));
+    }
+    let mut subscriptions = app_state.subscriptions.lock().map_err(|_| "Failed to lock subscriptions")?;
+    subscriptions.next_id += 1;
+    let subscription_id = subscriptions.next_id;
+    let event = format!("can-data-sub-{}", subscription_id);
+    subscriptions.subscriptions.insert(
+        subscription_id,
+        Subscription {
+            key,
+            channel,
+            id_filter,
+            event: event.clone(),
+        },
+    );
+    Ok(Subscribed { subscription_id, event })
+}
+
+#[tauri::command]
+pub fn unsubscribe(subscription_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let subscriptions = state.lock().map_err(|_| "Failed to lock state")?.subscriptions.clone();
+    subscriptions
+        .lock()
+        .map_err(|_| "Failed to lock subscriptions")?
+        .subscriptions
+        .remove(&subscription_id)
+        .ok_or_else(|| format!("subscription {} not found", subscription_id))?;
+    Ok(format!("Subscription {} removed", subscription_id))
+}
+
+/// 訂閱 DBC 訊號的變化：訊號值與上次送出的值相差超過死區時才在 signal-update 事件中送出，
+/// 取代高頻的原始訊框。signals 可寫 "Message.Signal" 或只寫訊號名稱
+#[tauri::command]
+pub fn subscribe_signals(
+    signals: Vec<String>,
+    options: Option<SignalSubscriptionOptions>,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    let options = options.unwrap_or_default();
+    if signals.is_empty() {
+        return Err(invalid_argument("signals", "select at least one signal"));
+    }
+    if options.deadband.is_some_and(|band| !band.is_finite() || band < 0.0) {
+        return Err(invalid_argument("options.deadband", "must be 0 or greater"));
+    }
+    if options.deadband_percent.is_some_and(|percent| !percent.is_finite() || percent < 0.0) {
+        return Err(invalid_argument("options.deadband_percent", "must be 0 or greater"));
+    }
+    if options.max_rate_hz.is_some_and(|hz| !hz.is_finite() || hz <= 0.0) {
+        return Err(invalid_argument("options.max_rate_hz", "must be greater than 0"));
+    }
+    let subscriptions = state.lock().map_err(|_| "Failed to lock state")?.subscriptions.clone();
+    let mut subscriptions = subscriptions.lock().map_err(|_| "Failed to lock subscriptions")?;
+    subscriptions.next_id += 1;
+    let subscription_id = subscriptions.next_id;
+    subscriptions.signal_subscriptions.insert(
+        subscription_id,
+        SignalSubscription {
+            signals,
+            min_interval: options.max_rate_hz.map(|hz| Duration::from_secs_f64(1.0 / hz)),
+            options,
+            states: HashMap::new(),
+        },
+    );
+    Ok(subscription_id)
+}
+
+#[tauri::command]
+pub fn unsubscribe_signals(subscription_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let subscriptions = state.lock().map_err(|_| "Failed to lock state")?.subscriptions.clone();
+    subscriptions
+        .lock()
+        .map_err(|_| "Failed to lock subscriptions")?
+        .signal_subscriptions
+        .remove(&subscription_id)
+        .ok_or_else(|| format!("signal subscription {} not found", subscription_id))?;
+    Ok(format!("Signal subscription {} removed", subscription_id))
+}
+
+#[tauri::command]
+pub fn list_signal_subscriptions(state: State<Arc<StateMutex>>) -> Result<Vec<SignalSubscriptionInfo>, String> {
+    let subscriptions = state.lock().map_err(|_| "Failed to lock state")?.subscriptions.clone();
+    let subscriptions = subscriptions.lock().map_err(|_| "Failed to lock subscriptions")?;
+    let mut infos: Vec<SignalSubscriptionInfo> = subscriptions
+        .signal_subscriptions
+        .iter()
+        .map(|(&subscription_id, subscription)| SignalSubscriptionInfo {
+            subscription_id,
+            signals: subscription.signals.clone(),
+            options: subscription.options.clone(),
+        })
+        .collect();
+    infos.sort_by_key(|info| info.subscription_id);
+    Ok(infos)
+}
@@ -0,0 +1,93 @@
+use std::any::Any;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+/// backend-panic 事件與診斷記錄使用的 AppHandle 及記錄檔路徑，在 setup 時設定
+static APP_HANDLE: OnceLock<tauri::AppHandle> = OnceLock::new();
+static DIAGNOSTICS_LOG: OnceLock<PathBuf> = OnceLock::new();
+
+/// backend-panic 事件；前端可依 role 提供重新啟動
+#[derive(Serialize, Clone, Debug)]
+pub struct BackendPanicEvent {
+    /// 執行緒的角色，例如 receive、periodic、logger
+    pub role: &'static str,
+    /// 執行緒負責的對象，例如裝置與通道
+    pub context: String,
+    pub message: String,
+}
+
+pub(crate) fn init(app_handle: &tauri::AppHandle) {
+    if let Ok(dir) = app_handle.path().app_log_dir() {
+        if std::fs::create_dir_all(&dir).is_ok() {
+            let _ = DIAGNOSTICS_LOG.set(dir.join("diagnostics.log"));
+        }
+    }
+    let _ = APP_HANDLE.set(app_handle.clone());
+}
+
+/// 附上時間寫一行到診斷記錄 (app_log_dir/diagnostics.log)
+pub(crate) fn record_diagnostic(line: &str) {
+    println!("{}", line);
+    let Some(path) = DIAGNOSTICS_LOG.get() else {
+        return;
+    };
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{} {}", chrono::Local::now().to_rfc3339(), line);
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string(),
+    }
+}
+
+/// 以 setup 時記下的 AppHandle 送出事件；尚未設定 (例如測試) 時略過
+pub(crate) fn emit<S: Serialize + Clone>(event: &str, payload: S) {
+    if let Some(app_handle) = APP_HANDLE.get() {
+        let _ = app_handle.emit(event, payload);
+    }
+}
+
+/// 執行背景執行緒的主體；panic 時送出 backend-panic、寫入診斷記錄並回傳 None，
+/// 由呼叫端把對應的狀態標記為停止。panic 時持有的 state 鎖由 StateMutex 在下一次取得時恢復
+pub(crate) fn guard<T>(role: &'static str, context: impl Into<String>, body: impl FnOnce() -> T) -> Option<T> {
+    let payload = catch_unwind(AssertUnwindSafe(body)).err()?;
+    let event = BackendPanicEvent {
+        role,
+        context: context.into(),
+        message: panic_message(payload.as_ref()),
+    };
+    record_diagnostic(&format!("backend-panic {} [{}]: {}", event.role, event.context, event.message));
+    emit("backend-panic", event);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_returns_the_result_or_none_after_a_panic() {
+        assert_eq!(guard("test", "ok", || 42), Some(42));
+        assert_eq!(guard("test", "str", || -> u32 { panic!("boom") }), None);
+        let code = 7;
+        assert_eq!(guard("test", "string", || -> u32 { panic!("failed with {}", code) }), None);
+    }
+
+    #[test]
+    fn panic_payloads_are_turned_into_messages() {
+        let message = |body: fn()| panic_message(catch_unwind(body).unwrap_err().as_ref());
+        assert_eq!(message(|| panic!("plain")), "plain");
+        assert_eq!(message(|| panic!("formatted {}", 1)), "formatted 1");
+        assert_eq!(message(|| std::panic::panic_any(5u8)), "unknown panic");
+    }
+}
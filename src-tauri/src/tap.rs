@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::frame::CanFrameEvent;
+use crate::receive::read_frames;
+use crate::StateMutex;
+
+/// 沒有接收執行緒時自行讀取的批次大小
+const POLL_BATCH_FRAMES: u32 = 100;
+
+struct Tap {
+    key: (u32, u32),
+    channel: u32,
+    sender: Sender<CanFrameEvent>,
+}
+
+/// 讓後端的協定層 (ISO-TP、UDS…) 取得某個通道收到的訊框。
+/// 通道有接收執行緒時由 Pipeline 分發，否則由讀取端自行呼叫 VCI_Receive
+#[derive(Default)]
+pub struct FrameTaps {
+    taps: HashMap<u64, Tap>,
+    next_id: u64,
+    /// 每個 (dev_type, dev_index, channel) 目前有幾個接收執行緒
+    streaming: HashMap<(u32, u32, u32), usize>,
+}
+
+impl FrameTaps {
+    pub fn dispatch(&mut self, key: (u32, u32), channel: u32, frames: &[CanFrameEvent]) {
+        self.taps.retain(|_, tap| {
+            if tap.key != key || tap.channel != channel {
+                return true;
+            }
+            frames.iter().all(|frame| tap.sender.send(frame.clone()).is_ok())
+        });
+    }
+
+    /// 裝置關閉時移除它的所有讀取端，等待中的 recv_until 會立即返回
+    pub fn disconnect_device(&mut self, key: (u32, u32)) {
+        self.taps.retain(|_, tap| tap.key != key);
+    }
+
+    fn is_streaming(&self, key: (u32, u32), channel: u32) -> bool {
+        self.streaming.get(&(key.0, key.1, channel)).is_some_and(|&n| n > 0)
+    }
+}
+
+/// 接收執行緒存活期間持有，表示該通道的訊框會經由 Pipeline 分發
+pub struct StreamGuard {
+    taps: Arc<Mutex<FrameTaps>>,
+    slot: (u32, u32, u32),
+}
+
+impl StreamGuard {
+    pub fn new(taps: Arc<Mutex<FrameTaps>>, key: (u32, u32), channel: u32) -> Self {
+        let slot = (key.0, key.1, channel);
+        if let Ok(mut taps) = taps.lock() {
+            *taps.streaming.entry(slot).or_default() += 1;
+        }
+        Self { taps, slot }
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if let Ok(mut taps) = self.taps.lock() {
+            if let Some(count) = taps.streaming.get_mut(&self.slot) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// 單一讀取端；drop 時自動取消登記
+pub struct TapReceiver {
+    id: u64,
+    key: (u32, u32),
+    channel: u32,
+    taps: Arc<Mutex<FrameTaps>>,
+    state: Arc<StateMutex>,
+    receiver: Receiver<CanFrameEvent>,
+}
+
+impl TapReceiver {
+    pub fn open(state: &Arc<StateMutex>, key: (u32, u32), channel: u32) -> Result<Self, String> {
+        let taps = state.lock().map_err(|_| "Failed to lock state")?.frame_taps.clone();
+        let (sender, receiver) = mpsc::channel();
+        let id = {
+            let mut registry = taps.lock().map_err(|_| "Failed to lock frame taps")?;
+            registry.next_id += 1;
+            let id = registry.next_id;
+            registry.taps.insert(id, Tap { key, channel, sender });
+            id
+        };
+        Ok(Self {
+            id,
+            key,
+            channel,
+            taps,
+            state: state.clone(),
+            receiver,
+        })
+    }
+
+    /// 等待下一個訊框直到 deadline；逾時回傳 None
+    pub fn recv_until(&self, deadline: Instant) -> Option<CanFrameEvent> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(frame) => return Some(frame),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {}
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            if !self.poll_device() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    /// 裝置關閉後讀取端即被移除，recv_until 回傳 None 時可據此區分逾時與關閉
+    pub fn is_open(&self) -> bool {
+        self.taps.lock().map(|t| t.taps.contains_key(&self.id)).unwrap_or(false)
+    }
+
+    /// 通道沒有接收執行緒時直接讀取裝置並分發給所有讀取端；有讀到訊框時回傳 true。
+    /// 這些訊框不會經過 Pipeline，因此不會進入環形緩衝區與統計
+    fn poll_device(&self) -> bool {
+        let streaming = self.taps.lock().map(|t| t.is_streaming(self.key, self.channel)).unwrap_or(true);
+        if streaming {
+            return false;
+        }
+        let Some(can_lib) = self.state.lock().ok().and_then(|s| s.backend()) else {
+            return false;
+        };
+        match read_frames(can_lib.as_ref(), self.key, self.channel, POLL_BATCH_FRAMES, 0, false) {
+            Ok(frames) if !frames.is_empty() => {
+                if let Ok(mut taps) = self.taps.lock() {
+                    taps.dispatch(self.key, self.channel, &frames);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Drop for TapReceiver {
+    fn drop(&mut self) {
+        if let Ok(mut taps) = self.taps.lock() {
+            taps.taps.remove(&self.id);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::invalid_argument;
+
+/// 接收或記錄自動停止的上限；兩者都設定時先達到的為準
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct CaptureLimit {
+    #[serde(default)]
+    pub duration_s: Option<f64>,
+    #[serde(default)]
+    pub max_frames: Option<u64>,
+}
+
+impl CaptureLimit {
+    pub fn validate(self) -> Result<Self, String> {
+        if self.duration_s.is_none() && self.max_frames.is_none() {
+            return Err(invalid_argument("limit", "set duration_s or max_frames"));
+        }
+        if self.duration_s.is_some_and(|s| !s.is_finite() || s <= 0.0) {
+            return Err(invalid_argument("limit.duration_s", "must be greater than 0"));
+        }
+        if self.max_frames == Some(0) {
+            return Err(invalid_argument("limit.max_frames", "must be greater than 0"));
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureSource {
+    Receive,
+    Logging,
+}
+
+/// capture-finished 事件的內容
+#[derive(Serialize, Clone, Debug)]
+pub struct CaptureFinished {
+    pub source: CaptureSource,
+    pub dev_type: Option<u32>,
+    pub dev_index: Option<u32>,
+    pub channel: Option<u32>,
+    pub frames: u64,
+    pub duration_ms: u64,
+    /// 記錄時的第一個檔案
+    pub path: Option<String>,
+}
+
+/// 狀態查詢中的剩餘量；未設定的上限為 None
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct CaptureRemaining {
+    pub remaining_ms: Option<u64>,
+    pub frames_remaining: Option<u64>,
+}
+
+/// 一次定時擷取的進度，由接收或記錄執行緒更新、狀態查詢讀取
+#[derive(Debug)]
+pub struct CaptureProgress {
+    limit: CaptureLimit,
+    started: Instant,
+    frames: AtomicU64,
+}
+
+impl CaptureProgress {
+    pub fn new(limit: CaptureLimit) -> Self {
+        Self {
+            limit,
+            started: Instant::now(),
+            frames: AtomicU64::new(0),
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.limit.duration_s.map(|s| self.started + Duration::from_secs_f64(s))
+    }
+
+    /// 距離時間上限還有多久；沒有時間上限時為 None
+    pub fn time_left(&self) -> Option<Duration> {
+        self.deadline().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// 計入一批訊框，超過 max_frames 的部分截掉，讓結果剛好是 max_frames 個
+    pub fn admit<T>(&self, frames: &mut Vec<T>) {
+        if let Some(max) = self.limit.max_frames {
+            let left = max.saturating_sub(self.frames.load(Ordering::Relaxed));
+            frames.truncate(usize::try_from(left).unwrap_or(usize::MAX));
+        }
+        self.record(frames.len() as u64);
+    }
+
+    pub fn record(&self, frames: u64) {
+        self.frames.fetch_add(frames, Ordering::Relaxed);
+    }
+
+    pub fn is_reached(&self) -> bool {
+        self.limit.max_frames.is_some_and(|max| self.frames.load(Ordering::Relaxed) >= max)
+            || self.time_left().is_some_and(|left| left.is_zero())
+    }
+
+    pub fn remaining(&self) -> CaptureRemaining {
+        CaptureRemaining {
+            remaining_ms: self.time_left().map(|left| left.as_millis() as u64),
+            frames_remaining: self
+                .limit
+                .max_frames
+                .map(|max| max.saturating_sub(self.frames.load(Ordering::Relaxed))),
+        }
+    }
+
+    pub fn finished(
+        &self,
+        source: CaptureSource,
+        key: Option<(u32, u32)>,
+        channel: Option<u32>,
+        path: Option<String>,
+    ) -> CaptureFinished {
+        CaptureFinished {
+            source,
+            dev_type: key.map(|k| k.0),
+            dev_index: key.map(|k| k.1),
+            channel,
+            frames: self.frames.load(Ordering::Relaxed),
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            path,
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::frame::{host_timestamp_us, CanFrameEvent};
+use crate::tap::TapReceiver;
+use crate::{invalid_argument, run_blocking, CanInterface, DeviceType, StateMutex, VciCanObj};
+
+/// 裝置時間戳記的單位 (0.1 ms)
+const TICK_US: u64 = 100;
+/// 每隔這麼久以期間內最小的偏移量更新 offset 與 drift；最小值最接近實際的 USB 傳輸延遲
+const OFFSET_WINDOW: Duration = Duration::from_secs(1);
+/// 以啟動時間換算的時間比收到的時間晚超過這麼多，表示計數器不是從 VCI_StartCAN 起算
+const START_TOLERANCE_US: u64 = 5_000;
+/// 校正訊框的 ID 與資料開頭，用來從匯流排流量中辨認
+const CALIBRATION_ID: u32 = 0x7FF;
+const CALIBRATION_MARKER: u8 = 0xCA;
+const DEFAULT_CALIBRATION_SAMPLES: u32 = 16;
+const MAX_CALIBRATION_SAMPLES: u32 = 1000;
+const CALIBRATION_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// 訊框事件的 timestamp_us 取自哪個時鐘
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampMode {
+    /// 一律使用裝置的 time_stamp，不論 time_flag
+    Hardware,
+    /// 忽略裝置計數器，只用主機接收時間
+    Host,
+    /// time_flag 為 1 時使用硬體時間戳記，否則使用主機時間
+    #[default]
+    Auto,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampSource {
+    Hardware,
+    #[default]
+    Host,
+}
+
+/// 通道的時間戳記設定與最近估計到的裝置時鐘品質，附在 get_status 中
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct ClockStatus {
+    pub mode: TimestampMode,
+    /// 最近一批訊框採用的來源；尚未收到訊框時為 None
+    pub active_source: Option<TimestampSource>,
+    /// 主機接收時間減去換算後的硬體時間 (微秒)，包含 USB 延遲
+    pub offset_us: Option<i64>,
+    /// 裝置時鐘相對主機時鐘的漂移 (ppm)，正值表示裝置較慢
+    pub drift_ppm: Option<f64>,
+    /// 通道最近一次 VCI_StartCAN 的主機時間 (呼叫前後的中點)；硬體計數器由此起算
+    pub started_at_us: Option<u64>,
+    /// calibrate_channel_offset 量到的修正量，加在換算後的硬體時間上；通道重新啟動後歸零
+    pub skew_correction_us: i64,
+}
+
+/// 硬體時鐘的對齊點：計數值 ticks 對應到主機時間 host_us
+struct Anchor {
+    host_us: u64,
+    ticks: u64,
+}
+
+impl Anchor {
+    /// 計數器從 VCI_StartCAN 起算，以啟動時間為對齊點可讓同時啟動的通道共用同一條時間軸；
+    /// 換算出的時間明顯晚於收到的時間時 (計數器不是從啟動起算)，改以這個訊框的接收時間對齊
+    fn new(started_at_us: Option<u64>, ticks: u64, host_us: u64) -> Self {
+        match started_at_us {
+            Some(start) if start.saturating_add(ticks * TICK_US) <= host_us + START_TOLERANCE_US => Self {
+                host_us: start,
+                ticks: 0,
+            },
+            _ => Self { host_us, ticks },
+        }
+    }
+}
+
+/// 由接收執行緒持有，把裝置計數器換算成主機時間軸上的 timestamp_us
+pub struct TimestampClock {
+    status: Arc<Mutex<ClockStatus>>,
+    mode: TimestampMode,
+    started_at_us: Option<u64>,
+    anchor: Option<Anchor>,
+    /// 上一個原始計數值與已經過的溢位次數 (32 位元 0.1 ms 計數器約 5 天溢位一次)
+    last_raw: Option<u32>,
+    wraps: u64,
+    window_start: Instant,
+    window_min_offset: Option<i64>,
+}
+
+impl TimestampClock {
+    pub fn new(status: Arc<Mutex<ClockStatus>>) -> Self {
+        let (mode, started_at_us) = {
+            let status = status.lock().unwrap_or_else(PoisonError::into_inner);
+            (status.mode, status.started_at_us)
+        };
+        Self {
+            status,
+            mode,
+            started_at_us,
+            anchor: None,
+            last_raw: None,
+            wraps: 0,
+            window_start: Instant::now(),
+            window_min_offset: None,
+        }
+    }
+
+    fn reset(&mut self, mode: TimestampMode, started_at_us: Option<u64>) {
+        self.mode = mode;
+        self.started_at_us = started_at_us;
+        self.anchor = None;
+        self.last_raw = None;
+        self.wraps = 0;
+        self.window_start = Instant::now();
+        self.window_min_offset = None;
+    }
+
+    fn extend(&mut self, raw: u32) -> u64 {
+        if let Some(last) = self.last_raw {
+            // 往回跳超過半個範圍視為溢位，小幅倒退 (同一批次的順序抖動) 不算
+            if raw < last && last - raw > u32::MAX / 2 {
+                self.wraps += 1;
+            }
+        }
+        self.last_raw = Some(raw);
+        (self.wraps << 32) + raw as u64
+    }
+
+    /// 依目前的模式設定每個訊框的 timestamp_us 與 timestamp_source；host 模式同時移除 device_timestamp。
+    /// 模式改變或通道重新啟動時重新對齊
+    pub fn apply(&mut self, frames: &mut [CanFrameEvent]) {
+        let shared = self.status.clone();
+        let mut status = shared.lock().unwrap_or_else(PoisonError::into_inner);
+        if status.mode != self.mode || status.started_at_us != self.started_at_us {
+            self.reset(status.mode, status.started_at_us);
+            *status = ClockStatus {
+                mode: status.mode,
+                started_at_us: status.started_at_us,
+                skew_correction_us: status.skew_correction_us,
+                ..Default::default()
+            };
+        }
+        let correction = status.skew_correction_us;
+        for frame in frames.iter_mut() {
+            let use_hardware = match self.mode {
+                TimestampMode::Hardware => true,
+                TimestampMode::Host => false,
+                TimestampMode::Auto => frame.device_timestamp.is_some(),
+            };
+            if !use_hardware {
+                if self.mode == TimestampMode::Host {
+                    frame.device_timestamp = None;
+                }
+                frame.timestamp_us = frame.host_timestamp_us;
+                frame.timestamp_source = TimestampSource::Host;
+                status.active_source = Some(TimestampSource::Host);
+                continue;
+            }
+            let ticks = self.extend(frame.device_counter);
+            let started_at_us = self.started_at_us;
+            let anchor = self
+                .anchor
+                .get_or_insert_with(|| Anchor::new(started_at_us, ticks, frame.host_timestamp_us));
+            let elapsed_us = ticks.saturating_sub(anchor.ticks) * TICK_US;
+            frame.timestamp_us = (anchor.host_us + elapsed_us).saturating_add_signed(correction);
+            frame.timestamp_source = TimestampSource::Hardware;
+            status.active_source = Some(TimestampSource::Hardware);
+            let offset = frame.host_timestamp_us as i64 - frame.timestamp_us as i64;
+            self.window_min_offset = Some(self.window_min_offset.map_or(offset, |min| min.min(offset)));
+            if self.window_start.elapsed() >= OFFSET_WINDOW {
+                let offset = self.window_min_offset.take().unwrap_or(offset);
+                status.offset_us = Some(offset);
+                status.drift_ppm = (elapsed_us > 0).then(|| offset as f64 * 1e6 / elapsed_us as f64);
+                self.window_start = Instant::now();
+            }
+        }
+    }
+}
+
+/// 設定通道的時間戳記來源 (hardware、host 或 auto)；接收中的通道在下一批訊框套用並重新對齊
+#[tauri::command]
+pub fn set_timestamp_mode(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    mode: TimestampMode,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let key = device.key();
+    let runtime = app_state.channel_runtime(key, channel);
+    runtime.clock.lock().map_err(|_| "Failed to lock clock status")?.mode = mode;
+    Ok(format!("CAN{} timestamp mode set to {:?}", channel + 1, mode))
+}
+
+/// 呼叫 VCI_StartCAN 並記下通道的啟動時間，之後的硬體時間戳記以此換算。
+/// 同時啟動多個通道時先全部 init 再連續呼叫，讓各通道的計數器幾乎同時歸零
+pub(crate) fn start_and_mark(
+    can_lib: &dyn CanInterface,
+    clock: &Mutex<ClockStatus>,
+    key: (u32, u32),
+    channel: u32,
+) -> Result<(), String> {
+    let before = host_timestamp_us();
+    can_lib.start(key.0, key.1, channel)?;
+    let after = host_timestamp_us();
+    let mut status = clock.lock().unwrap_or_else(PoisonError::into_inner);
+    status.started_at_us = Some(before + (after - before) / 2);
+    status.skew_correction_us = 0;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+pub struct CalibrationResult {
+    /// 校正前 to_channel 的時間相對 from_channel 超前的量 (微秒)
+    pub skew_us: i64,
+    /// 兩個方向都收到的來回次數
+    pub samples: u32,
+    pub corrected_channel: u32,
+    /// 校正後 corrected_channel 的 skew_correction_us
+    pub correction_us: i64,
+}
+
+/// 從 tx_channel 送出一個校正訊框並等待它出現在 rx 上；回傳接收端時間戳記減去送出前的主機時間
+fn measure_once(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    tx_channel: u32,
+    rx: &TapReceiver,
+    sample: u32,
+) -> Result<Option<i64>, String> {
+    let mut can_obj = VciCanObj {
+        id: CALIBRATION_ID,
+        data_len: 6,
+        ..Default::default()
+    };
+    can_obj.data[0] = CALIBRATION_MARKER;
+    can_obj.data[1] = tx_channel as u8;
+    can_obj.data[2..6].copy_from_slice(&sample.to_le_bytes());
+    // 丟掉上一輪逾時後才到的訊框
+    while rx.recv_until(Instant::now()).is_some() {}
+    let sent_us = host_timestamp_us();
+    let sent_at = Instant::now();
+    let sent = crate::transmit(state, key, tx_channel, std::slice::from_ref(&can_obj))?;
+    if sent == 0 {
+        return Err(format!("CAN{} did not accept the calibration frame", tx_channel + 1));
+    }
+    let deadline = sent_at + CALIBRATION_TIMEOUT;
+    while let Some(frame) = rx.recv_until(deadline) {
+        if frame.id != CALIBRATION_ID || frame.data.get(..6) != Some(&can_obj.data[..6]) {
+            continue;
+        }
+        if frame.timestamp_source != TimestampSource::Hardware {
+            return Err(format!("CAN{} is not using hardware timestamps", frame.channel + 1));
+        }
+        return Ok(Some(frame.timestamp_us as i64 - sent_us as i64));
+    }
+    Ok(None)
+}
+
+/// 兩個通道以迴路接線時，輪流從一端送出校正訊框、在另一端接收，量測兩個通道硬體時間的差並修正 to_channel。
+/// 兩個方向的延遲 (USB 與匯流排傳輸) 視為相同而互相抵銷；各方向取最小值以排除輪詢造成的延遲。
+/// 兩個通道都需要在接收中；沒有迴路接線時回傳錯誤。通道重新啟動後需要重新校正
+pub fn calibrate_offset(
+    state: &Arc<StateMutex>,
+    dev_type: Option<u32>,
+    dev_index: Option<u32>,
+    from_channel: u32,
+    to_channel: u32,
+    samples: u32,
+) -> Result<CalibrationResult, String> {
+    if !(1..=MAX_CALIBRATION_SAMPLES).contains(&samples) {
+        return Err(invalid_argument("samples", format!("must be between 1 and {}", MAX_CALIBRATION_SAMPLES)));
+    }
+    if from_channel == to_channel {
+        return Err(invalid_argument("to_channel", "must differ from from_channel"));
+    }
+    let (key, clock) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.connected_device(dev_type, dev_index)?;
+        for channel in [from_channel, to_channel] {
+            device.check_channel(channel)?;
+            if !device.receiving.get(&channel).is_some_and(|receiving| receiving.load(Ordering::SeqCst)) {
+                return Err(format!("CAN{} is not receiving", channel + 1));
+            }
+        }
+        let key = device.key();
+        (key, app_state.channel_runtime(key, to_channel).clock.clone())
+    };
+    let from_tap = TapReceiver::open(state, key, from_channel)?;
+    let to_tap = TapReceiver::open(state, key, to_channel)?;
+    let mut forward = Vec::new();
+    let mut reverse = Vec::new();
+    for sample in 0..samples {
+        let Some(to_delay) = measure_once(state, key, from_channel, &to_tap, sample)? else {
+            continue;
+        };
+        if let Some(from_delay) = measure_once(state, key, to_channel, &from_tap, sample)? {
+            forward.push(to_delay);
+            reverse.push(from_delay);
+        }
+    }
+    let (Some(&to_delay), Some(&from_delay)) = (forward.iter().min(), reverse.iter().min()) else {
+        return Err(format!(
+            "no calibration frames made it between CAN{} and CAN{}; are the channels wired to each other?",
+            from_channel + 1,
+            to_channel + 1
+        ));
+    };
+    let skew_us = to_delay - from_delay;
+    let mut status = clock.lock().map_err(|_| "Failed to lock clock status")?;
+    status.skew_correction_us -= skew_us;
+    Ok(CalibrationResult {
+        skew_us,
+        samples: forward.len() as u32,
+        corrected_channel: to_channel,
+        correction_us: status.skew_correction_us,
+    })
+}
+
+/// 以迴路接線量測 from_channel 與 to_channel 的時間差並修正 to_channel，讓合併的紀錄共用同一條時間軸。
+/// samples 預設 16
+#[tauri::command]
+pub async fn calibrate_channel_offset(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    from_channel: u32,
+    to_channel: u32,
+    samples: Option<u32>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<CalibrationResult, String> {
+    let samples = samples.unwrap_or(DEFAULT_CALIBRATION_SAMPLES);
+    let state = state.inner().clone();
+    run_blocking(move || {
+        calibrate_offset(&state, dev_type.map(DeviceType::code), dev_index, from_channel, to_channel, samples)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::AppState;
+
+    const HOST_US: u64 = 1_700_000_000_000_000;
+
+    fn clock(mode: TimestampMode) -> (Arc<Mutex<ClockStatus>>, TimestampClock) {
+        let status = Arc::new(Mutex::new(ClockStatus {
+            mode,
+            ..Default::default()
+        }));
+        (status.clone(), TimestampClock::new(status))
+    }
+
+    fn frame(counter: u32, time_flag: bool, host_timestamp_us: u64) -> CanFrameEvent {
+        let can_obj = VciCanObj {
+            id: 0x100,
+            time_stamp: counter,
+            time_flag: time_flag as u8,
+            ..Default::default()
+        };
+        CanFrameEvent::from_raw((4, 0), 0, &can_obj, host_timestamp_us)
+    }
+
+    fn timestamps(clock: &mut TimestampClock, mut frames: Vec<CanFrameEvent>) -> Vec<(u64, TimestampSource)> {
+        clock.apply(&mut frames);
+        frames.iter().map(|f| (f.timestamp_us, f.timestamp_source)).collect()
+    }
+
+    #[test]
+    fn auto_mode_uses_the_device_counter_only_when_time_flag_is_set() {
+        let (status, mut clock) = clock(TimestampMode::Auto);
+        let stamped = timestamps(
+            &mut clock,
+            vec![frame(1_000, true, HOST_US), frame(1_010, true, HOST_US + 5_000), frame(0, false, HOST_US + 6_000)],
+        );
+        assert_eq!(
+            stamped,
+            [
+                (HOST_US, TimestampSource::Hardware),
+                (HOST_US + 1_000, TimestampSource::Hardware),
+                (HOST_US + 6_000, TimestampSource::Host),
+            ]
+        );
+        assert_eq!(status.lock().unwrap().active_source, Some(TimestampSource::Host));
+    }
+
+    #[test]
+    fn the_counter_is_extended_across_wraparound_but_not_on_small_reordering() {
+        let (_, mut clock) = clock(TimestampMode::Hardware);
+        let stamped = timestamps(
+            &mut clock,
+            vec![frame(u32::MAX - 9, false, HOST_US), frame(10, false, HOST_US), frame(5, false, HOST_US)],
+        );
+        // 溢位後多 20 個計數 (2 ms)；同一批中倒退 5 個計數不視為溢位
+        assert_eq!(stamped.iter().map(|&(t, _)| t - HOST_US).collect::<Vec<_>>(), [0, 2_000, 1_500]);
+        assert_eq!(clock.wraps, 1);
+    }
+
+    #[test]
+    fn host_mode_drops_the_device_timestamp_and_a_mode_change_realigns() {
+        let (status, mut clock) = clock(TimestampMode::Host);
+        let mut frames = vec![frame(500, true, HOST_US)];
+        clock.apply(&mut frames);
+        assert_eq!((frames[0].timestamp_us, frames[0].device_timestamp), (HOST_US, None));
+
+        status.lock().unwrap().mode = TimestampMode::Hardware;
+        let stamped = timestamps(&mut clock, vec![frame(500, false, HOST_US + 10_000), frame(600, false, HOST_US + 12_000)]);
+        assert_eq!(stamped, [(HOST_US + 10_000, TimestampSource::Hardware), (HOST_US + 20_000, TimestampSource::Hardware)]);
+        assert_eq!(status.lock().unwrap().mode, TimestampMode::Hardware);
+    }
+
+    #[test]
+    fn unknown_timestamp_modes_are_rejected() {
+        let mode: TimestampMode = serde_json::from_str("\"hardware\"").unwrap();
+        assert_eq!(mode, TimestampMode::Hardware);
+        assert!(serde_json::from_str::<TimestampMode>("\"device\"").is_err());
+        assert!(serde_json::from_str::<TimestampMode>("1").is_err());
+    }
+
+    #[test]
+    fn channels_started_together_share_the_start_time_as_their_anchor() {
+        let started_at_us = HOST_US - 50_000;
+        let mut clocks: Vec<_> = (0..2)
+            .map(|_| {
+                TimestampClock::new(Arc::new(Mutex::new(ClockStatus {
+                    mode: TimestampMode::Hardware,
+                    started_at_us: Some(started_at_us),
+                    ..Default::default()
+                })))
+            })
+            .collect();
+        // 兩個通道在啟動後 40 ms 與 45 ms 收到訊框，各自的 USB 延遲不同
+        let first = timestamps(&mut clocks[0], vec![frame(400, false, HOST_US + 1_000)]);
+        let second = timestamps(&mut clocks[1], vec![frame(450, false, HOST_US + 3_000)]);
+        assert_eq!((first[0].0, second[0].0), (started_at_us + 40_000, started_at_us + 45_000));
+
+        // 計數器不是從啟動起算 (換算出的時間晚於收到的時間) 時改以收到的時間對齊
+        let anchor = Anchor::new(Some(started_at_us), 10_000, HOST_US);
+        assert_eq!((anchor.host_us, anchor.ticks), (HOST_US, 10_000));
+        let anchor = Anchor::new(None, 7, HOST_US);
+        assert_eq!((anchor.host_us, anchor.ticks), (HOST_US, 7));
+    }
+
+    #[test]
+    fn calibration_rejects_bad_arguments_before_sending_anything() {
+        let mock = Arc::new(MockCan::new());
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(4, 0, None).unwrap();
+        app_state.devices.get_mut(&(4, 0)).unwrap().channel_count = Some(2);
+        let state = Arc::new(StateMutex::new(app_state));
+
+        let error = |from: u32, to: u32, samples: u32, dev_index: u32| {
+            calibrate_offset(&state, Some(4), Some(dev_index), from, to, samples).unwrap_err()
+        };
+        assert!(error(0, 1, 0, 0).starts_with("InvalidArgument { field: \"samples\""));
+        assert!(error(0, 1, MAX_CALIBRATION_SAMPLES + 1, 0).starts_with("InvalidArgument { field: \"samples\""));
+        assert!(error(1, 1, 1, 0).starts_with("InvalidArgument { field: \"to_channel\""));
+        assert!(error(0, 2, 1, 0).starts_with("InvalidArgument { field: \"can_channel\""));
+        assert_eq!(error(0, 1, 1, 0), "CAN1 is not receiving");
+        assert_eq!(error(0, 1, 1, 1), "device 1 is not the open device (0)");
+        assert!(mock.transmitted().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::StateMutex;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ByteCondition {
+    pub index: usize,
+    pub value: u8,
+    /// 只比較遮罩為 1 的位元；省略時比較整個位元組
+    #[serde(default)]
+    pub mask: Option<u8>,
+}
+
+/// 所有指定的條件都成立才算命中
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TriggerCondition {
+    pub id: Option<u32>,
+    /// 與 id 搭配使用，只比較遮罩為 1 的位元
+    pub id_mask: Option<u32>,
+    pub extended: Option<bool>,
+    pub dlc: Option<u8>,
+    pub bytes: Vec<ByteCondition>,
+}
+
+impl TriggerCondition {
+    pub fn matches(&self, frame: &CanFrameEvent) -> bool {
+        let id_matches = self.id.is_none_or(|id| {
+            let mask = self.id_mask.unwrap_or(u32::MAX);
+            frame.id & mask == id & mask
+        });
+        id_matches
+            && self.extended.is_none_or(|e| e == frame.extended)
+            && self.dlc.is_none_or(|dlc| dlc == frame.dlc)
+            && self.bytes.iter().all(|c| {
+                let mask = c.mask.unwrap_or(0xFF);
+                frame.data.get(c.index).is_some_and(|b| b & mask == c.value & mask)
+            })
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TriggerInfo {
+    pub trigger_id: u32,
+    pub name: String,
+    pub channel: u32,
+    pub condition: TriggerCondition,
+    pub once: bool,
+    pub armed: bool,
+    pub hits: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TriggerEvent {
+    pub trigger_id: u32,
+    pub name: String,
+    pub frame: CanFrameEvent,
+    /// 命中的訊框在該批次中的位置，供觸發擷取切分前後緩衝
+    #[serde(skip)]
+    pub frame_index: usize,
+}
+
+#[derive(Default)]
+pub struct TriggerTable {
+    triggers: Vec<TriggerInfo>,
+    next_id: u32,
+}
+
+impl TriggerTable {
+    /// 在接收迴圈中對每個訊框求值；once 的觸發器命中後自動解除
+    pub fn evaluate(&mut self, channel: u32, frames: &[CanFrameEvent]) -> Vec<TriggerEvent> {
+        let mut events = Vec::new();
+        for (frame_index, frame) in frames.iter().enumerate() {
+            for trigger in self
+                .triggers
+                .iter_mut()
+                .filter(|t| t.armed && t.channel == channel && t.condition.matches(frame))
+            {
+                trigger.hits += 1;
+                if trigger.once {
+                    trigger.armed = false;
+                }
+                events.push(TriggerEvent {
+                    trigger_id: trigger.trigger_id,
+                    name: trigger.name.clone(),
+                    frame: frame.clone(),
+                    frame_index,
+                });
+            }
+        }
+        events
+    }
+
+    pub fn channel_of(&self, trigger_id: u32) -> Option<u32> {
+        self.triggers.iter().find(|t| t.trigger_id == trigger_id).map(|t| t.channel)
+    }
+}
+
+fn triggers(state: &State<Arc<StateMutex>>) -> Result<Arc<Mutex<TriggerTable>>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    Ok(app_state.triggers.clone())
+}
+
+/// 新增觸發器，命中時發出 can-trigger 事件；回傳 trigger_id
+#[tauri::command]
+pub fn add_trigger(
+    channel: u32,
+    condition: TriggerCondition,
+    name: String,
+    once: Option<bool>,
+    state: State<Arc<StateMutex>>,
+) -> Result<u32, String> {
+    let triggers = triggers(&state)?;
+    let mut table = triggers.lock().map_err(|_| "Failed to lock triggers")?;
+    table.next_id += 1;
+    let trigger_id = table.next_id;
+    table.triggers.push(TriggerInfo {
+        trigger_id,
+        name,
+        channel,
+        condition,
+        once: once.unwrap_or(false),
+        armed: true,
+        hits: 0,
+    });
+    Ok(trigger_id)
+}
+
+#[tauri::command]
+pub fn remove_trigger(trigger_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let triggers = triggers(&state)?;
+    let mut table = triggers.lock().map_err(|_| "Failed to lock triggers")?;
+    let before = table.triggers.len();
+    table.triggers.retain(|t| t.trigger_id != trigger_id);
+    if table.triggers.len() == before {
+        return Err(format!("trigger {} not found", trigger_id));
+    }
+    Ok(format!("trigger {} removed", trigger_id))
+}
+
+#[tauri::command]
+pub fn list_triggers(state: State<Arc<StateMutex>>) -> Result<Vec<TriggerInfo>, String> {
+    let triggers = triggers(&state)?;
+    let table = triggers.lock().map_err(|_| "Failed to lock triggers")?;
+    Ok(table.triggers.clone())
+}
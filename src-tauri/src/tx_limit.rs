@@ -0,0 +1,517 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, State};
+
+use crate::frame::{Provenance, TxTiming};
+use crate::{invalid_argument, AppState, DeviceType, StateMutex, VciCanObj};
+
+/// 每次 VCI_Transmit 最多交給驅動的訊框數；太大的陣列會讓 DLL 失敗或長時間阻塞
+pub const DEFAULT_CHUNK_FRAMES: u32 = 200;
+const MAX_CHUNK_FRAMES: u32 = 2000;
+
+/// 每個通道的 token bucket。容量為 20 ms 份量 (至少 1 個訊框)，
+/// 低於限制的零星單一訊框總是有 token 可用，不會被延遲
+pub struct TxRateLimiter {
+    frames_per_sec: u32,
+    /// 可以為負：不經 transmit_paced 的傳送 (例如週期訊框) 也會扣除
+    tokens: f64,
+    capacity: f64,
+    last_refill: Instant,
+}
+
+impl TxRateLimiter {
+    pub fn new(frames_per_sec: u32) -> Self {
+        let capacity = (frames_per_sec as f64 / 50.0).max(1.0);
+        Self {
+            frames_per_sec,
+            tokens: capacity,
+            capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.frames_per_sec as f64).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// 現在可以立即送出的訊框數
+    pub fn available(&mut self) -> usize {
+        self.refill();
+        self.tokens.max(0.0).floor() as usize
+    }
+
+    pub fn consume(&mut self, frames: usize) {
+        self.refill();
+        self.tokens -= frames as f64;
+    }
+
+    /// 累積到一個 token 還需要等待的時間
+    fn wait_time(&self) -> Duration {
+        Duration::from_secs_f64(((1.0 - self.tokens) / self.frames_per_sec as f64).max(0.0))
+    }
+}
+
+const DEFAULT_RETRY_INTERVAL_MS: u64 = 2;
+
+/// VCI_Transmit 回傳 0 (傳送緩衝暫時已滿) 時的重試設定；驅動回報的錯誤碼一律不重試
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TxRetry {
+    pub retries: u32,
+    pub interval: Duration,
+    /// 從第一次嘗試起算的總時限，超過後不再重試
+    pub timeout: Option<Duration>,
+}
+
+impl TxRetry {
+    /// 只給 timeout_ms 時在時限內不限次數重試
+    pub fn from_params(retries: Option<u32>, retry_interval_ms: Option<u64>, timeout_ms: Option<u64>) -> Self {
+        Self {
+            retries: retries.unwrap_or(if timeout_ms.is_some() { u32::MAX } else { 0 }),
+            interval: Duration::from_millis(retry_interval_ms.unwrap_or(DEFAULT_RETRY_INTERVAL_MS)),
+            timeout: timeout_ms.map(Duration::from_millis),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TxOutcome {
+    pub sent: u32,
+    /// 呼叫 VCI_Transmit 的總次數；明顯大於分段數時表示匯流排接近飽和
+    pub attempts: u32,
+    /// 被 abort_transmit 在分段之間中止
+    pub aborted: bool,
+    /// 每個送出訊框的傳送序號與 VCI_Transmit 呼叫時間，依送出順序
+    pub frames: Vec<TxTiming>,
+}
+
+/// 一次長時間傳送的進度與中止旗標；在分段之間檢查
+#[derive(Default)]
+pub struct TxControl {
+    pub total: u64,
+    pub sent: AtomicU64,
+    pub abort: AtomicBool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TxProgress {
+    pub operation_id: u32,
+    pub channel: u32,
+    pub sent: u64,
+    pub total: u64,
+}
+
+/// 依通道的傳送速率限制分段送出 frames，等待時不持有 state 鎖。
+/// 沒有設定限制時等同 transmit_with_echo；回傳實際送出的訊框數
+pub fn transmit_paced(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    frames: &[VciCanObj],
+    echo: bool,
+) -> Result<u32, String> {
+    transmit_paced_as(state, key, channel, frames, echo, Provenance::TxManual)
+}
+
+/// 同 transmit_paced()，送出的訊框標記為 provenance (例如重播)
+pub fn transmit_paced_as(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    frames: &[VciCanObj],
+    echo: bool,
+    provenance: Provenance,
+) -> Result<u32, String> {
+    transmit_controlled(state, key, channel, frames, echo, TxRetry::default(), provenance, None).map(|outcome| outcome.sent)
+}
+
+/// 同 transmit_paced_with_retry()；超過一個分段時登記為可查詢進度、可中止的傳送，
+/// 開始時送出 transmit-started，每個分段後送出 transmit-progress
+pub fn transmit_tracked(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    frames: &[VciCanObj],
+    echo: bool,
+    retry: TxRetry,
+) -> Result<TxOutcome, String> {
+    let (operation_id, control, app_handle) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let chunk = app_state.channel_runtime(key, channel).chunk_frames();
+        if frames.len() <= chunk {
+            drop(app_state);
+            return transmit_paced_with_retry(state, key, channel, frames, echo, retry);
+        }
+        let control = Arc::new(TxControl {
+            total: frames.len() as u64,
+            ..Default::default()
+        });
+        app_state.next_tx_operation_id += 1;
+        let operation_id = app_state.next_tx_operation_id;
+        app_state.tx_operations.insert(operation_id, (channel, control.clone()));
+        (operation_id, control, app_state.app_handle.clone())
+    };
+    let progress = |sent| {
+        if let Some(app_handle) = &app_handle {
+            let _ = app_handle.emit(
+                "transmit-progress",
+                TxProgress {
+                    operation_id,
+                    channel,
+                    sent,
+                    total: control.total,
+                },
+            );
+        }
+    };
+    if let Some(app_handle) = &app_handle {
+        let _ = app_handle.emit(
+            "transmit-started",
+            TxProgress {
+                operation_id,
+                channel,
+                sent: 0,
+                total: control.total,
+            },
+        );
+    }
+    let result = transmit_controlled(state, key, channel, frames, echo, retry, Provenance::TxManual, Some((&control, &progress)));
+    if let Ok(mut app_state) = state.lock() {
+        app_state.tx_operations.remove(&operation_id);
+    }
+    result
+}
+
+/// 同 transmit_paced()，傳送緩衝已滿時依 retry 等待後重試，用完仍失敗時回傳 TxTimeout。
+/// 只在取出傳送路徑時持有 state 鎖，之後的 VCI_Transmit 與等待只鎖定此通道的速率限制
+pub fn transmit_paced_with_retry(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    frames: &[VciCanObj],
+    echo: bool,
+    retry: TxRetry,
+) -> Result<TxOutcome, String> {
+    transmit_controlled(state, key, channel, frames, echo, retry, Provenance::TxManual, None)
+}
+
+/// 以通道的分段大小分次呼叫 VCI_Transmit；分段被部分接受或緩衝已滿時依 retry 重試。
+/// 中途失敗時錯誤訊息附上已送出的訊框數
+fn transmit_controlled(
+    state: &Arc<StateMutex>,
+    key: (u32, u32),
+    channel: u32,
+    frames: &[VciCanObj],
+    echo: bool,
+    retry: TxRetry,
+    provenance: Provenance,
+    control: Option<(&TxControl, &dyn Fn(u64))>,
+) -> Result<TxOutcome, String> {
+    let tx_path = state
+        .lock()
+        .map_err(|_| "Failed to lock state")?
+        .tx_path(key, channel)?
+        .with_provenance(provenance);
+    let chunk = tx_path.runtime.chunk_frames();
+    let counters = tx_path.runtime.counters.clone();
+    counters.tx_pending.fetch_add(frames.len() as u64, Ordering::Relaxed);
+    let started = Instant::now();
+    let mut sent_total = 0;
+    let mut timings = Vec::new();
+    let mut attempts = 0;
+    let mut retries = 0;
+    let mut aborted = false;
+    let can_retry = |retries: u32| retries < retry.retries && retry.timeout.is_none_or(|t| started.elapsed() + retry.interval <= t);
+    let result = loop {
+        if sent_total >= frames.len() {
+            break Ok(sent_total as u32);
+        }
+        if control.is_some_and(|(control, _)| control.abort.load(Ordering::SeqCst)) {
+            aborted = true;
+            break Ok(sent_total as u32);
+        }
+        let remaining = &frames[sent_total..];
+        let wait = {
+            let allowed = match tx_path.runtime.tx_limit.lock() {
+                Ok(mut limiter) => limiter
+                    .as_mut()
+                    .map_or(remaining.len(), |l| l.available().min(remaining.len()))
+                    .min(chunk),
+                Err(_) => break Err("Failed to lock rate limiter".to_string()),
+            };
+            if allowed > 0 {
+                attempts += 1;
+                match tx_path.try_transmit(&remaining[..allowed], echo).map(|sent| {
+                    let count = sent.len();
+                    timings.extend(sent);
+                    count
+                }) {
+                    Ok(0) if can_retry(retries) => {
+                        retries += 1;
+                        Some(retry.interval)
+                    }
+                    Ok(0) if retry.retries == 0 && retry.timeout.is_none() => {
+                        counters.errors.fetch_add(1, Ordering::Relaxed);
+                        break Err("傳送 CAN 數據失敗".to_string());
+                    }
+                    Ok(0) => {
+                        counters.errors.fetch_add(1, Ordering::Relaxed);
+                        break Err(format!(
+                            "TxTimeout {{ attempts: {}, elapsed_ms: {}, sent: {} }}",
+                            attempts,
+                            started.elapsed().as_millis(),
+                            sent_total
+                        ));
+                    }
+                    Ok(sent) => {
+                        sent_total += sent;
+                        counters.tx_pending.fetch_sub(sent as u64, Ordering::Relaxed);
+                        if let Some((control, progress)) = control {
+                            control.sent.store(sent_total as u64, Ordering::Relaxed);
+                            progress(sent_total as u64);
+                        }
+                        match sent < allowed {
+                            // 驅動程式只接受部分訊框：有重試設定時稍後送出其餘的分段，否則回傳給呼叫端處理
+                            true if can_retry(retries) => {
+                                retries += 1;
+                                Some(retry.interval)
+                            }
+                            true => break Ok(sent_total as u32),
+                            false => None,
+                        }
+                    }
+                    Err(error_message) if sent_total > 0 => {
+                        break Err(format!("{} (sent {} of {} frames)", error_message, sent_total, frames.len()))
+                    }
+                    Err(error_message) => break Err(error_message),
+                }
+            } else {
+                tx_path.runtime.tx_limit.lock().ok().and_then(|l| l.as_ref().map(TxRateLimiter::wait_time))
+            }
+        };
+        if let Some(wait) = wait {
+            std::thread::sleep(wait.max(Duration::from_micros(100)));
+        }
+    };
+    counters
+        .tx_pending
+        .fetch_sub((frames.len() - sent_total) as u64, Ordering::Relaxed);
+    result.map(|sent| TxOutcome {
+        sent,
+        attempts,
+        aborted,
+        frames: timings,
+    })
+}
+
+/// 在目前的分段送出後停止 transmit_frames 等長時間的傳送；已送出的訊框數在該命令的回傳值中
+#[tauri::command]
+pub fn abort_transmit(operation_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let (_, control) = app_state
+        .tx_operations
+        .get(&operation_id)
+        .ok_or_else(|| format!("transmit operation {} not found", operation_id))?;
+    control.abort.store(true, Ordering::SeqCst);
+    Ok(format!("transmit operation {} aborted", operation_id))
+}
+
+/// 進行中的分段傳送
+#[tauri::command]
+pub fn get_transmit_progress(state: State<Arc<StateMutex>>) -> Result<Vec<TxProgress>, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let mut operations: Vec<TxProgress> = app_state
+        .tx_operations
+        .iter()
+        .map(|(&operation_id, (channel, control))| TxProgress {
+            operation_id,
+            channel: *channel,
+            sent: control.sent.load(Ordering::Relaxed),
+            total: control.total,
+        })
+        .collect();
+    operations.sort_by_key(|operation| operation.operation_id);
+    Ok(operations)
+}
+
+/// 設定通道每次 VCI_Transmit 最多送出的訊框數；None 恢復預設值
+#[tauri::command]
+pub fn set_tx_chunk_size(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    frames: Option<u32>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let frames = frames.unwrap_or(DEFAULT_CHUNK_FRAMES);
+    if !(1..=MAX_CHUNK_FRAMES).contains(&frames) {
+        return Err(invalid_argument("frames", format!("must be between 1 and {}", MAX_CHUNK_FRAMES)));
+    }
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+    device.check_channel(channel)?;
+    let key = device.key();
+    app_state.channel_runtime(key, channel).tx_chunk_frames.store(frames, Ordering::Relaxed);
+    Ok(format!("CAN{} transmits at most {} frames per call", channel + 1, frames))
+}
+
+/// 設定通道每秒最多送出的訊框數；None 取消限制
+#[tauri::command]
+pub fn set_tx_rate_limit(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    frames_per_sec: Option<u32>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let runtime = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+        device.check_channel(channel)?;
+        let key = device.key();
+        app_state.channel_runtime(key, channel)
+    };
+    let counters = &runtime.counters;
+    let mut limiter = runtime.tx_limit.lock().map_err(|_| "Failed to lock rate limiter")?;
+    match frames_per_sec {
+        Some(0) => Err("frames_per_sec must be greater than 0".into()),
+        Some(frames_per_sec) => {
+            *limiter = Some(TxRateLimiter::new(frames_per_sec));
+            counters.tx_rate_limit.store(frames_per_sec, Ordering::Relaxed);
+            Ok(format!("CAN{} transmit limited to {} frames/s", channel + 1, frames_per_sec))
+        }
+        None => {
+            *limiter = None;
+            counters.tx_rate_limit.store(0, Ordering::Relaxed);
+            Ok(format!("CAN{} transmit rate limit removed", channel + 1))
+        }
+    }
+}
+
+impl AppState {
+    /// 禁止或恢復通道傳送。禁止期間所有傳送路徑都以 TxInhibited 失敗；設定跟著連線設定保存，
+    /// 關閉後重新開啟同一通道仍維持
+    pub fn set_tx_inhibit(&mut self, dev_type: Option<u32>, dev_index: Option<u32>, channel: u32, inhibited: bool) -> Result<(), String> {
+        let device = self.connected_device(dev_type, dev_index)?;
+        device.check_channel(channel)?;
+        let key = device.key();
+        self.channel_runtime(key, channel).tx_inhibited.store(inhibited, Ordering::SeqCst);
+        self.save_settings(key);
+        Ok(())
+    }
+}
+
+/// 禁止 (inhibited = true) 或恢復通道傳送
+#[tauri::command]
+pub fn set_tx_inhibit(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    inhibited: bool,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    state
+        .lock()
+        .map_err(|_| "Failed to lock state")?
+        .set_tx_inhibit(dev_type.map(DeviceType::code), dev_index, channel, inhibited)?;
+    Ok(match inhibited {
+        true => format!("CAN{} transmit inhibited", channel + 1),
+        false => format!("CAN{} transmit enabled", channel + 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::VciInitConfig;
+
+    const KEY: (u32, u32) = (4, 0);
+
+    fn started() -> (Arc<MockCan>, Arc<StateMutex>) {
+        let mock = Arc::new(MockCan::new());
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(KEY.0, KEY.1, None).unwrap();
+        app_state.devices.get_mut(&KEY).unwrap().channel_count = Some(2);
+        let config = VciInitConfig {
+            acc_mask: 0xFFFF_FFFF,
+            timing1: 0x1C,
+            ..Default::default()
+        };
+        app_state.start_channel(KEY, 0, config).unwrap();
+        (mock, Arc::new(StateMutex::new(app_state)))
+    }
+
+    fn frames(count: u32) -> Vec<VciCanObj> {
+        (0..count)
+            .map(|i| VciCanObj {
+                id: 0x100 + i,
+                data_len: 1,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn an_inhibited_channel_refuses_every_frame_until_enabled_again() {
+        let (mock, state) = started();
+        state.lock().unwrap().set_tx_inhibit(None, None, 0, true).unwrap();
+        assert_eq!(transmit_paced(&state, KEY, 0, &frames(2), false), Err(crate::tx_inhibited(0)));
+        assert!(mock.transmitted().is_empty());
+
+        let mut app_state = state.lock().unwrap();
+        let counters = app_state.channel_runtime(KEY, 0).counters.clone();
+        assert_eq!(counters.tx_refused.load(Ordering::Relaxed), 2);
+        assert_eq!(counters.tx_pending.load(Ordering::Relaxed), 0);
+        assert_eq!(app_state.current_settings(KEY).unwrap().tx_inhibited, [0]);
+        app_state.set_tx_inhibit(None, None, 0, false).unwrap();
+        drop(app_state);
+
+        assert_eq!(transmit_paced(&state, KEY, 0, &frames(2), false), Ok(2));
+        assert_eq!(mock.transmitted().len(), 2);
+        assert_eq!(counters.tx_refused.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn inhibit_rejects_invalid_channels_and_devices_that_are_not_usable() {
+        let (_, state) = started();
+        let mut app_state = state.lock().unwrap();
+        let error = app_state.set_tx_inhibit(None, None, 2, true).unwrap_err();
+        assert!(error.starts_with("InvalidArgument { field: \"can_channel\""), "{}", error);
+        assert_eq!(
+            app_state.set_tx_inhibit(Some(4), Some(1), 0, true).unwrap_err(),
+            "device 1 is not the open device (0)"
+        );
+        app_state.devices.get_mut(&KEY).unwrap().disconnected = true;
+        assert_eq!(app_state.set_tx_inhibit(None, None, 0, true).unwrap_err(), "device 0 disconnected");
+        assert!(!app_state.channel_runtime(KEY, 0).tx_inhibited.load(Ordering::SeqCst));
+        assert!(AppState::default().set_tx_inhibit(None, None, 0, true).is_err());
+    }
+
+    #[test]
+    fn the_rate_limiter_holds_twenty_milliseconds_of_frames_and_can_go_negative() {
+        let mut limiter = TxRateLimiter::new(100);
+        assert_eq!(limiter.available(), 2);
+        limiter.consume(3);
+        assert_eq!(limiter.available(), 0);
+        assert!(limiter.wait_time() > Duration::from_millis(15));
+        assert_eq!(TxRateLimiter::new(10).available(), 1);
+    }
+
+    #[test]
+    fn retry_defaults_to_unlimited_attempts_only_when_a_timeout_is_given() {
+        let retry = TxRetry::from_params(None, None, None);
+        assert_eq!((retry.retries, retry.interval, retry.timeout), (0, Duration::from_millis(2), None));
+        let retry = TxRetry::from_params(None, Some(5), Some(50));
+        assert_eq!(
+            (retry.retries, retry.interval, retry.timeout),
+            (u32::MAX, Duration::from_millis(5), Some(Duration::from_millis(50)))
+        );
+        assert_eq!(TxRetry::from_params(Some(3), None, Some(50)).retries, 3);
+    }
+}
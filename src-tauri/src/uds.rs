@@ -0,0 +1,629 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+
+use crate::isotp::{IsoTpError, IsoTpLink, IsoTpOptions};
+use crate::operation::OperationKind;
+use crate::periodic::{self, PeriodicPayload, TaskRole};
+use crate::{invalid_argument, run_blocking, AppState, DeviceType, StateMutex};
+
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+const NRC_RESPONSE_PENDING: u8 = 0x78;
+/// 收到 0x78 後延長的等待時間 (P2*server_max)
+const RESPONSE_PENDING_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+const SID_DIAGNOSTIC_SESSION_CONTROL: u8 = 0x10;
+const SID_ECU_RESET: u8 = 0x11;
+const SID_READ_DATA_BY_IDENTIFIER: u8 = 0x22;
+const SID_REQUEST_DOWNLOAD: u8 = 0x34;
+const SID_TRANSFER_DATA: u8 = 0x36;
+const SID_REQUEST_TRANSFER_EXIT: u8 = 0x37;
+const SID_TESTER_PRESENT: u8 = 0x3E;
+/// suppressPosRspMsgIndicationBit：ECU 不回覆 TesterPresent
+const SUPPRESS_POSITIVE_RESPONSE: u8 = 0x80;
+/// S3 server 預設 5 秒，每 2 秒送一次留足餘裕
+const DEFAULT_TESTER_PRESENT_INTERVAL_MS: u64 = 2000;
+
+/// ISO 14229-1 負面回應碼名稱
+fn nrc_name(nrc: u8) -> &'static str {
+    match nrc {
+        0x10 => "generalReject",
+        0x11 => "serviceNotSupported",
+        0x12 => "subFunctionNotSupported",
+        0x13 => "incorrectMessageLengthOrInvalidFormat",
+        0x14 => "responseTooLong",
+        0x21 => "busyRepeatRequest",
+        0x22 => "conditionsNotCorrect",
+        0x24 => "requestSequenceError",
+        0x25 => "noResponseFromSubnetComponent",
+        0x26 => "failurePreventsExecutionOfRequestedAction",
+        0x31 => "requestOutOfRange",
+        0x33 => "securityAccessDenied",
+        0x35 => "invalidKey",
+        0x36 => "exceedNumberOfAttempts",
+        0x37 => "requiredTimeDelayNotExpired",
+        0x70 => "uploadDownloadNotAccepted",
+        0x71 => "transferDataSuspended",
+        0x72 => "generalProgrammingFailure",
+        0x73 => "wrongBlockSequenceCounter",
+        0x78 => "requestCorrectlyReceivedResponsePending",
+        0x7E => "subFunctionNotSupportedInActiveSession",
+        0x7F => "serviceNotSupportedInActiveSession",
+        0x81 => "rpmTooHigh",
+        0x82 => "rpmTooLow",
+        0x83 => "engineIsRunning",
+        0x84 => "engineIsNotRunning",
+        0x85 => "engineRunTimeTooLow",
+        0x86 => "temperatureTooHigh",
+        0x87 => "temperatureTooLow",
+        0x88 => "vehicleSpeedTooHigh",
+        0x89 => "vehicleSpeedTooLow",
+        0x8A => "throttlePedalTooHigh",
+        0x8B => "throttlePedalTooLow",
+        0x8C => "transmissionRangeNotInNeutral",
+        0x8D => "transmissionRangeNotInGear",
+        0x8F => "brakeSwitchesNotClosed",
+        0x90 => "shifterLeverNotInPark",
+        0x91 => "torqueConverterClutchLocked",
+        0x92 => "voltageTooHigh",
+        0x93 => "voltageTooLow",
+        _ => "unknown",
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum UdsError {
+    NegativeResponse { service: u8, nrc: u8, name: &'static str },
+    /// 逾時沒有收到回應
+    Timeout,
+    /// 回應的服務 ID 與請求不符
+    UnexpectedResponse { data: Vec<u8> },
+    IsoTp { error: IsoTpError },
+    /// 下載在某個 TransferData 區塊失敗；block_index 從 0 起算
+    TransferFailed {
+        block_index: u32,
+        block_sequence_counter: u8,
+        bytes_sent: u64,
+        error: Box<UdsError>,
+    },
+}
+
+impl fmt::Display for UdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UdsError::NegativeResponse { service, nrc, name } => {
+                write!(f, "service 0x{:02X} rejected: {} (0x{:02X})", service, name, nrc)
+            }
+            UdsError::Timeout => write!(f, "no response from ECU"),
+            UdsError::UnexpectedResponse { data } => write!(f, "unexpected response {:02X?}", data),
+            UdsError::IsoTp { error } => write!(f, "{}", error),
+            UdsError::TransferFailed {
+                block_index,
+                block_sequence_counter,
+                error,
+                ..
+            } => write!(
+                f,
+                "block {} (sequence counter 0x{:02X}) failed: {}",
+                block_index, block_sequence_counter, error
+            ),
+        }
+    }
+}
+
+impl From<IsoTpError> for UdsError {
+    fn from(error: IsoTpError) -> Self {
+        UdsError::IsoTp { error }
+    }
+}
+
+impl From<String> for UdsError {
+    fn from(message: String) -> Self {
+        UdsError::IsoTp {
+            error: IsoTpError::from(message),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct UdsOptions {
+    pub timeout_ms: Option<u64>,
+    pub isotp: IsoTpOptions,
+}
+
+/// 送出請求並等待回應；0x78 (response pending) 會延長等待時間。成功時回傳去掉服務 ID 的正面回應內容
+pub fn request(link: &IsoTpLink, service: u8, payload: &[u8], timeout: Duration) -> Result<Vec<u8>, UdsError> {
+    // 從送出到收到回應都不讓 TesterPresent 插入
+    let _hold = link.hold();
+    let mut request = vec![service];
+    request.extend_from_slice(payload);
+    link.send(&request)?;
+    let mut deadline = Instant::now() + timeout;
+    loop {
+        let response = link.receive(deadline)?.ok_or(UdsError::Timeout)?;
+        match response.as_slice() {
+            [sid, rest @ ..] if *sid == service.wrapping_add(POSITIVE_RESPONSE_OFFSET) => return Ok(rest.to_vec()),
+            [NEGATIVE_RESPONSE, sid, NRC_RESPONSE_PENDING, ..] if *sid == service => {
+                deadline = Instant::now() + Duration::from_millis(RESPONSE_PENDING_TIMEOUT_MS);
+            }
+            [NEGATIVE_RESPONSE, sid, nrc, ..] if *sid == service => {
+                return Err(UdsError::NegativeResponse {
+                    service,
+                    nrc: *nrc,
+                    name: nrc_name(*nrc),
+                })
+            }
+            _ => return Err(UdsError::UnexpectedResponse { data: response }),
+        }
+    }
+}
+
+fn open_link(
+    state: &Arc<StateMutex>,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    options: &UdsOptions,
+) -> Result<IsoTpLink, UdsError> {
+    let key = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state".to_string())?;
+        app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?.key()
+    };
+    Ok(IsoTpLink::open(state, key, channel, tx_id, rx_id, options.isotp.clone())?)
+}
+
+fn timeout(options: &UdsOptions) -> Duration {
+    Duration::from_millis(options.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS))
+}
+
+#[tauri::command]
+pub async fn uds_request(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    service: u8,
+    payload: Vec<u8>,
+    timeout_ms: Option<u64>,
+    options: Option<UdsOptions>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<u8>, UdsError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let mut options = options.unwrap_or_default();
+        options.timeout_ms = timeout_ms.or(options.timeout_ms);
+        let link = open_link(&state, dev_type, dev_index, channel, tx_id, rx_id, &options)?;
+        request(&link, service, &payload, timeout(&options))
+    })
+    .await
+}
+
+#[derive(Serialize)]
+pub struct DidValue {
+    pub did: u16,
+    pub data: Vec<u8>,
+}
+
+/// ReadDataByIdentifier (0x22)
+#[tauri::command]
+pub async fn uds_read_did(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    did: u16,
+    options: Option<UdsOptions>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<DidValue, UdsError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let options = options.unwrap_or_default();
+        let link = open_link(&state, dev_type, dev_index, channel, tx_id, rx_id, &options)?;
+        let response = request(&link, SID_READ_DATA_BY_IDENTIFIER, &did.to_be_bytes(), timeout(&options))?;
+        match response.as_slice() {
+            [high, low, data @ ..] if u16::from_be_bytes([*high, *low]) == did => Ok(DidValue {
+                did,
+                data: data.to_vec(),
+            }),
+            _ => Err(UdsError::UnexpectedResponse { data: response }),
+        }
+    })
+    .await
+}
+
+/// DiagnosticSessionControl (0x10)；回傳 session 之後的參數 (P2/P2* 時間)
+#[tauri::command]
+pub async fn uds_diagnostic_session_control(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    session: u8,
+    options: Option<UdsOptions>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<u8>, UdsError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let options = options.unwrap_or_default();
+        let link = open_link(&state, dev_type, dev_index, channel, tx_id, rx_id, &options)?;
+        let response = request(&link, SID_DIAGNOSTIC_SESSION_CONTROL, &[session], timeout(&options))?;
+        Ok(response.get(1..).unwrap_or_default().to_vec())
+    })
+    .await
+}
+
+/// ECUReset (0x11)
+#[tauri::command]
+pub async fn uds_ecu_reset(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    kind: u8,
+    options: Option<UdsOptions>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<Vec<u8>, UdsError> {
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let options = options.unwrap_or_default();
+        let link = open_link(&state, dev_type, dev_index, channel, tx_id, rx_id, &options)?;
+        let response = request(&link, SID_ECU_RESET, &[kind], timeout(&options))?;
+        Ok(response.get(1..).unwrap_or_default().to_vec())
+    })
+    .await
+}
+
+#[derive(Serialize)]
+pub struct TesterPresentInfo {
+    pub task_id: u32,
+    pub dev_type: u32,
+    pub dev_index: u32,
+    pub channel: u32,
+    pub tx_id: u32,
+    pub interval_ms: u64,
+    /// 同一組 ID 上有 UDS 請求或 ISO-TP 傳輸進行中，暫時不送
+    pub paused: bool,
+}
+
+impl AppState {
+    pub(crate) fn tester_present_info(&self) -> Option<TesterPresentInfo> {
+        let (task_id, task) = self.tester_present_task()?;
+        let TaskRole::TesterPresent { tx_id, .. } = task.role() else {
+            return None;
+        };
+        Some(TesterPresentInfo {
+            task_id,
+            dev_type: task.key().0,
+            dev_index: task.key().1,
+            channel: task.channel(),
+            tx_id: *tx_id,
+            interval_ms: task.interval_ms(),
+            paused: task.role().on_hold(),
+        })
+    }
+}
+
+/// 以週期任務送出 TesterPresent (0x3E 0x80) 維持非預設的診斷 session；
+/// 同一組 tx_id 有 UDS 請求進行時暫停，結束後恢復。傳送失敗 (例如裝置斷線) 時停止並送出 tester-present-stopped
+#[tauri::command]
+pub fn start_tester_present(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    interval_ms: Option<u64>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<TesterPresentInfo, String> {
+    let (activity, padding) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        if let Some(running) = app_state.tester_present_info() {
+            return Err(format!("TesterPresent already running on 0x{:X}", running.tx_id));
+        }
+        let device = app_state.connected_device(dev_type.map(DeviceType::code), dev_index)?;
+        device.check_channel(channel)?;
+        let key = device.key();
+        // 與 configure_isotp 設定的連線使用相同的填充，沒有設定時用預設值
+        let padding = app_state
+            .isotp_links
+            .values()
+            .find(|link| (link.dev_type, link.dev_index) == key && link.channel == channel && link.tx_id == tx_id)
+            .map_or_else(|| IsoTpOptions::default().padding, |link| link.params.padding);
+        (app_state.isotp_activity(key, channel, tx_id), padding)
+    };
+    let mut data = vec![0x02, SID_TESTER_PRESENT, SUPPRESS_POSITIVE_RESPONSE];
+    if let Some(padding) = padding {
+        data.resize(8, padding);
+    }
+    let payload = PeriodicPayload::Raw {
+        id: tx_id,
+        extended: tx_id > 0x7FF,
+        data,
+    };
+    let role = TaskRole::TesterPresent { tx_id, activity };
+    periodic::spawn_task(
+        app_handle,
+        state.inner(),
+        dev_type,
+        dev_index,
+        channel,
+        interval_ms.unwrap_or(DEFAULT_TESTER_PRESENT_INTERVAL_MS),
+        payload,
+        Some(false),
+        None,
+        role,
+    )?;
+    state
+        .lock()
+        .map_err(|_| "Failed to lock state")?
+        .tester_present_info()
+        .ok_or_else(|| "TesterPresent stopped immediately".to_string())
+}
+
+#[tauri::command]
+pub fn stop_tester_present(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let (task_id, task) = app_state.tester_present_task().ok_or("TesterPresent is not running")?;
+    task.stop();
+    app_state.periodic_tasks.remove(&task_id);
+    Ok("TesterPresent stopped".into())
+}
+
+/// transfer-progress 事件最短的間隔；最後一個區塊一定會送出
+const TRANSFER_PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct UdsDownloadOptions {
+    #[serde(flatten)]
+    pub uds: UdsOptions,
+    /// RequestDownload 的 dataFormatIdentifier；0x00 為不壓縮、不加密
+    pub data_format: u8,
+    /// memoryAddress 與 memorySize 的位元組數 (1..=4)
+    pub address_length: u8,
+    pub size_length: u8,
+    /// 比 ECU 回報的 maxNumberOfBlockLength 更小的區塊長度 (含 SID 與序號)
+    pub max_block_length: Option<usize>,
+}
+
+impl Default for UdsDownloadOptions {
+    fn default() -> Self {
+        Self {
+            uds: UdsOptions::default(),
+            data_format: 0x00,
+            address_length: 4,
+            size_length: 4,
+            max_block_length: None,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct TransferStarted {
+    transfer_id: u32,
+    channel: u32,
+    address: u32,
+    total_bytes: u64,
+}
+
+#[derive(Serialize, Clone)]
+struct TransferProgress {
+    transfer_id: u32,
+    bytes_sent: u64,
+    total_bytes: u64,
+    blocks_sent: u32,
+    bytes_per_second: f64,
+}
+
+#[derive(Serialize)]
+pub struct DownloadResult {
+    pub transfer_id: u32,
+    pub bytes_sent: u64,
+    pub blocks_sent: u32,
+    /// 每個 TransferData 區塊實際使用的長度 (含 SID 與序號)
+    pub block_length: usize,
+    pub completed: bool,
+    pub aborted: bool,
+    pub duration_ms: f64,
+    /// RequestTransferExit 正面回應的 transferResponseParameterRecord
+    pub exit_response: Vec<u8>,
+}
+
+/// 取數值的低 length 個位元組 (big-endian)；放不下時回傳 None
+fn be_bytes(value: u32, length: u8) -> Option<Vec<u8>> {
+    let bytes = value.to_be_bytes();
+    let skip = 4 - length as usize;
+    bytes[..skip].iter().all(|&b| b == 0).then(|| bytes[skip..].to_vec())
+}
+
+/// RequestDownload 的回應：lengthFormatIdentifier 高 4 位元為 maxNumberOfBlockLength 的位元組數
+fn max_block_length(response: &[u8]) -> Result<usize, UdsError> {
+    let unexpected = || UdsError::UnexpectedResponse { data: response.to_vec() };
+    let (format, rest) = response.split_first().ok_or_else(unexpected)?;
+    let length = (format >> 4) as usize;
+    let bytes = rest.get(..length).filter(|_| (1..=8).contains(&length)).ok_or_else(unexpected)?;
+    let max = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+    // 至少要能放 SID、序號與一個資料位元組
+    usize::try_from(max).ok().filter(|&max| max > 2).ok_or_else(unexpected)
+}
+
+/// 以 RequestDownload (0x34)、TransferData (0x36) 與 RequestTransferExit (0x37) 把 data 或 file_path 的內容
+/// 下載到 address。區塊長度依 ECU 的 maxNumberOfBlockLength，序號從 1 起算並在 0xFF 後回到 0x00。
+/// 開始時送出 transfer-started 帶 transfer_id (即 operation_id)，傳送中送出 transfer-progress，
+/// 可用 abort_uds_download 或 cancel_operation 在區塊之間中止
+#[tauri::command]
+pub async fn uds_download(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    tx_id: u32,
+    rx_id: u32,
+    address: u32,
+    size: Option<u32>,
+    data: Option<Vec<u8>>,
+    file_path: Option<String>,
+    options: Option<UdsDownloadOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<DownloadResult, UdsError> {
+    let options = options.unwrap_or_default();
+    for (field, length) in [("options.address_length", options.address_length), ("options.size_length", options.size_length)] {
+        if !(1..=4).contains(&length) {
+            return Err(invalid_argument(field, "must be between 1 and 4").into());
+        }
+    }
+    if options.max_block_length.is_some_and(|max| max <= 2) {
+        return Err(invalid_argument("options.max_block_length", "must be greater than 2").into());
+    }
+    let address_bytes = be_bytes(address, options.address_length)
+        .ok_or_else(|| invalid_argument("address", format!("0x{:X} does not fit in {} bytes", address, options.address_length)))?;
+    let state = state.inner().clone();
+    run_blocking(move || {
+        let data = match (data, file_path) {
+            (Some(data), None) => data,
+            (None, Some(path)) => std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?,
+            _ => return Err(invalid_argument("data", "exactly one of data or file_path is required").into()),
+        };
+        if data.is_empty() {
+            return Err(invalid_argument("data", "nothing to download").into());
+        }
+        let total_bytes = data.len() as u64;
+        // 壓縮或加密時 memorySize 是解開後的大小，可以與傳送的資料長度不同
+        let size = match size {
+            Some(size) => size,
+            None => u32::try_from(data.len()).map_err(|_| invalid_argument("data", "larger than 4 GiB"))?,
+        };
+        let size_bytes = be_bytes(size, options.size_length)
+            .ok_or_else(|| invalid_argument("size", format!("{} does not fit in {} bytes", size, options.size_length)))?;
+
+        let link = open_link(&state, dev_type, dev_index, channel, tx_id, rx_id, &options.uds)?;
+        let timeout = timeout(&options.uds);
+        let mut payload = vec![options.data_format, (options.size_length << 4) | options.address_length];
+        payload.extend_from_slice(&address_bytes);
+        payload.extend_from_slice(&size_bytes);
+        let response = request(&link, SID_REQUEST_DOWNLOAD, &payload, timeout)?;
+        let mut block_length = max_block_length(&response)?;
+        if let Some(max) = options.max_block_length {
+            block_length = block_length.min(max);
+        }
+
+        let operation = state
+            .lock()
+            .map_err(|_| "Failed to lock state".to_string())?
+            .start_operation(OperationKind::UdsDownload, link.key(), channel, Some(total_bytes));
+        let transfer_id = operation.id();
+        operation.announce(&state, &app_handle);
+        let _ = app_handle.emit(
+            "transfer-started",
+            TransferStarted {
+                transfer_id,
+                channel,
+                address,
+                total_bytes,
+            },
+        );
+        let started = Instant::now();
+        let result = transfer_blocks(&link, &data, block_length, timeout, operation.running(), |bytes_sent, blocks_sent| {
+            operation.set_progress(bytes_sent);
+            let elapsed = started.elapsed().as_secs_f64();
+            let _ = app_handle.emit(
+                "transfer-progress",
+                TransferProgress {
+                    transfer_id,
+                    bytes_sent,
+                    total_bytes,
+                    blocks_sent,
+                    bytes_per_second: if elapsed > 0.0 { bytes_sent as f64 / elapsed } else { 0.0 },
+                },
+            );
+        });
+        let result = result.and_then(|(bytes_sent, blocks_sent)| {
+            let aborted = bytes_sent < total_bytes;
+            // 中止時不送 RequestTransferExit，ECU 會在下一個請求時回報序列錯誤
+            let exit_response = if aborted {
+                Vec::new()
+            } else {
+                request(&link, SID_REQUEST_TRANSFER_EXIT, &[], timeout)?
+            };
+            Ok((bytes_sent, blocks_sent, aborted, exit_response))
+        });
+        operation.finish(&state, &app_handle, result.as_ref().err().map(UdsError::to_string));
+        let (bytes_sent, blocks_sent, aborted, exit_response) = result?;
+        Ok(DownloadResult {
+            transfer_id,
+            bytes_sent,
+            blocks_sent,
+            block_length,
+            completed: !aborted,
+            aborted,
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+            exit_response,
+        })
+    })
+    .await
+}
+
+/// 依序送出 TransferData 區塊直到送完或被中止；回傳已送出的位元組數與區塊數
+fn transfer_blocks(
+    link: &IsoTpLink,
+    data: &[u8],
+    block_length: usize,
+    timeout: Duration,
+    running: &AtomicBool,
+    mut progress: impl FnMut(u64, u32),
+) -> Result<(u64, u32), UdsError> {
+    let mut bytes_sent = 0u64;
+    let mut blocks_sent = 0u32;
+    let mut last_progress = Instant::now();
+    let chunks = data.chunks(block_length - 2);
+    let block_count = chunks.len();
+    for (index, chunk) in chunks.enumerate() {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        let counter = (index + 1) as u8;
+        let mut payload = vec![counter];
+        payload.extend_from_slice(chunk);
+        let failed = |error| UdsError::TransferFailed {
+            block_index: index as u32,
+            block_sequence_counter: counter,
+            bytes_sent,
+            error: Box::new(error),
+        };
+        let response = request(link, SID_TRANSFER_DATA, &payload, timeout).map_err(failed)?;
+        if response.first() != Some(&counter) {
+            return Err(failed(UdsError::UnexpectedResponse { data: response }));
+        }
+        bytes_sent += chunk.len() as u64;
+        blocks_sent += 1;
+        if last_progress.elapsed() >= TRANSFER_PROGRESS_INTERVAL || index + 1 == block_count {
+            last_progress = Instant::now();
+            progress(bytes_sent, blocks_sent);
+        }
+    }
+    Ok((bytes_sent, blocks_sent))
+}
+
+/// 在目前的區塊完成後停止 uds_download
+#[tauri::command]
+pub fn abort_uds_download(transfer_id: u32, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state
+        .cancel_operation(transfer_id, Some(OperationKind::UdsDownload))
+        .ok_or_else(|| format!("transfer {} not found", transfer_id))?;
+    Ok(format!("transfer {} aborted", transfer_id))
+}
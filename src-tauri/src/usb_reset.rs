@@ -0,0 +1,252 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{fixed_str, invalid_argument, run_blocking, AppState, CanInterface, DeviceType, StateMutex};
+
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+const MAX_TIMEOUT_MS: u64 = 60_000;
+/// 自動重連時等待裝置重新列舉的時間
+pub(crate) const RECONNECT_RESET_TIMEOUT: Duration = Duration::from_millis(DEFAULT_TIMEOUT_MS);
+/// 重設後裝置需要一點時間才會從列舉中消失
+const RESET_SETTLE: Duration = Duration::from_millis(200);
+const ENUMERATE_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Debug)]
+pub struct UsbResetResult {
+    pub serial_number: Option<String>,
+    /// 重設前裝置是否開啟；重設後一律為關閉，需重新開啟
+    pub was_open: bool,
+    pub reappeared: bool,
+    /// 裝置重新出現時的 index (重新列舉後可能改變)
+    pub dev_index: Option<u32>,
+    pub elapsed_ms: u64,
+}
+
+/// VCI_UsbDeviceReset 之後要等待重新列舉的裝置
+pub(crate) struct ResetDevice {
+    can_lib: Arc<dyn CanInterface>,
+    serial_number: Option<String>,
+    dev_index: u32,
+}
+
+impl ResetDevice {
+    /// 輪詢列舉直到序號 (沒有序號時為原本的 index) 再次出現；回傳裝置目前的 index。
+    /// running 被清除時提早放棄
+    pub(crate) fn wait(&self, timeout: Duration, running: &AtomicBool) -> Option<u32> {
+        let deadline = Instant::now() + timeout;
+        std::thread::sleep(RESET_SETTLE.min(timeout));
+        while running.load(Ordering::SeqCst) {
+            if let Ok(found) = self.can_lib.find_devices() {
+                let index = match &self.serial_number {
+                    Some(serial) => found
+                        .iter()
+                        .position(|board_info| &fixed_str(&board_info.str_serial_num) == serial)
+                        .map(|index| index as u32),
+                    None => ((self.dev_index as usize) < found.len()).then_some(self.dev_index),
+                };
+                if index.is_some() {
+                    return index;
+                }
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+            std::thread::sleep(ENUMERATE_POLL);
+        }
+        None
+    }
+}
+
+impl AppState {
+    /// 自動重連第 usb_reset_after 次失敗後重設 USB，作為最後手段；回傳要等待重新列舉的裝置
+    pub(crate) fn usb_reset_for_reconnect(&mut self, key: (u32, u32), attempts: u32) -> Option<ResetDevice> {
+        let device = self.devices.get_mut(&key)?;
+        if !device.disconnected || device.usb_reset_after.and_then(|after| after.checked_add(1)) != Some(attempts) {
+            return None;
+        }
+        if let Err(error_message) = device.handle.usb_reset() {
+            println!("USB reset before reconnect attempt {} failed: {}", attempts, error_message);
+            return None;
+        }
+        Some(ResetDevice {
+            can_lib: device.handle.library(),
+            serial_number: device.serial_number.clone(),
+            dev_index: key.1,
+        })
+    }
+}
+
+/// 關閉裝置 (若已開啟) 後送出 VCI_UsbDeviceReset，並在 timeout 內等待裝置重新列舉。
+/// 裝置未開啟時需指定 dev_type 與 dev_index
+pub fn force_usb_reset_device(
+    state: &Arc<StateMutex>,
+    dev_type: Option<u32>,
+    dev_index: Option<u32>,
+    timeout: Duration,
+) -> Result<UsbResetResult, String> {
+    let started = Instant::now();
+    let (device, target) = {
+        let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        match app_state.device(dev_type, dev_index).map(|device| device.key()) {
+            Ok(key) => {
+                let device = app_state.devices.remove(&key).expect("device key resolved above");
+                if let Ok(mut taps) = app_state.frame_taps.lock() {
+                    taps.disconnect_device(key);
+                }
+                for receiving in device.receiving.values() {
+                    receiving.store(false, Ordering::SeqCst);
+                }
+                (Some(device), key)
+            }
+            Err(error_message) => match (dev_type, dev_index) {
+                (Some(dev_type), Some(dev_index)) => (None, (dev_type, dev_index)),
+                _ => return Err(error_message),
+            },
+        }
+    };
+    let was_open = device.is_some();
+    let reset = match device {
+        // 在鎖外等待接收執行緒結束後才重設
+        Some(mut device) => {
+            device.handle.stop_threads();
+            device.handle.usb_reset().map(|_| ResetDevice {
+                can_lib: device.handle.library(),
+                serial_number: device.serial_number.clone(),
+                dev_index: target.1,
+            })
+        }
+        None => {
+            let can_lib = state.lock().map_err(|_| "Failed to lock state")?.try_library()?;
+            let serial_number = can_lib
+                .find_devices()
+                .ok()
+                .and_then(|found| found.get(target.1 as usize).map(|board_info| fixed_str(&board_info.str_serial_num)));
+            can_lib.usb_reset(target.0, target.1).map(|_| ResetDevice {
+                can_lib,
+                serial_number,
+                dev_index: target.1,
+            })
+        }
+    }?;
+    let dev_index = reset.wait(timeout.saturating_sub(started.elapsed()), &AtomicBool::new(true));
+    Ok(UsbResetResult {
+        serial_number: reset.serial_number,
+        was_open,
+        reappeared: dev_index.is_some(),
+        dev_index,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// 裝置卡在 VCI_OpenDevice 失敗、只能重新插拔時使用；需要 DLL 匯出 VCI_UsbDeviceReset。
+/// 回報裝置是否在 timeout_ms (預設 10000) 內重新出現，但不會自動重新開啟
+#[tauri::command]
+pub async fn force_usb_reset(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    timeout_ms: Option<u64>,
+    state: State<'_, Arc<StateMutex>>,
+) -> Result<UsbResetResult, String> {
+    let timeout_ms = timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS);
+    if !(1..=MAX_TIMEOUT_MS).contains(&timeout_ms) {
+        return Err(invalid_argument("timeout_ms", format!("must be between 1 and {}", MAX_TIMEOUT_MS)));
+    }
+    let state = state.inner().clone();
+    run_blocking(move || {
+        force_usb_reset_device(&state, dev_type.map(DeviceType::code), dev_index, Duration::from_millis(timeout_ms))
+    })
+    .await
+}
+
+/// 設定自動重連連續失敗 after_attempts 次後重設 USB 再繼續重連；None 表示不重設
+#[tauri::command]
+pub fn set_usb_reset_fallback(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    after_attempts: Option<u32>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    if after_attempts == Some(0) {
+        return Err(invalid_argument("after_attempts", "must be at least 1"));
+    }
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let device = app_state.device_mut(dev_type.map(DeviceType::code), dev_index)?;
+    device.usb_reset_after = after_attempts;
+    Ok(match after_attempts {
+        Some(after) => format!("USB reset after {} failed reconnect attempts", after),
+        None => "USB reset fallback disabled".into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockCan;
+    use crate::VciBoardInfo;
+
+    fn open_state() -> (Arc<MockCan>, AppState) {
+        let mock = Arc::new(MockCan::new());
+        let mut app_state = AppState::with_interface(mock.clone());
+        app_state.open_device(4, 0, None).unwrap();
+        (mock, app_state)
+    }
+
+    #[test]
+    fn a_reset_needs_an_open_device_or_an_explicit_target() {
+        let (mock, app_state) = open_state();
+        let state = Arc::new(StateMutex::new(app_state));
+        assert!(force_usb_reset_device(&state, Some(4), Some(3), Duration::from_millis(1)).is_ok());
+        assert_eq!(mock.usb_reset_count(4, 3), 1);
+
+        state.lock().unwrap().devices.clear();
+        assert!(force_usb_reset_device(&state, None, None, Duration::from_millis(1)).is_err());
+        assert!(force_usb_reset_device(&state, None, Some(0), Duration::from_millis(1)).is_err());
+        assert_eq!(mock.usb_reset_count(4, 0), 0);
+    }
+
+    #[test]
+    fn reconnect_resets_only_a_disconnected_device_on_the_configured_attempt() {
+        let (mock, mut app_state) = open_state();
+        app_state.devices.get_mut(&(4, 0)).unwrap().usb_reset_after = Some(2);
+        assert!(app_state.usb_reset_for_reconnect((4, 0), 3).is_none());
+
+        app_state.devices.get_mut(&(4, 0)).unwrap().disconnected = true;
+        assert!(app_state.usb_reset_for_reconnect((4, 0), 2).is_none());
+        assert!(app_state.usb_reset_for_reconnect((4, 1), 3).is_none());
+        assert_eq!(mock.usb_reset_count(4, 0), 0);
+        assert!(app_state.usb_reset_for_reconnect((4, 0), 3).is_some());
+        assert_eq!(mock.usb_reset_count(4, 0), 1);
+
+        app_state.devices.get_mut(&(4, 0)).unwrap().usb_reset_after = None;
+        assert!(app_state.usb_reset_for_reconnect((4, 0), 1).is_none());
+    }
+
+    #[test]
+    fn waiting_finds_the_serial_at_its_new_index_or_gives_up_when_stopped() {
+        let mock = Arc::new(MockCan::new());
+        let mut other = VciBoardInfo::default();
+        other.str_serial_num[..8].copy_from_slice(b"OTHER001");
+        let mut board_info = VciBoardInfo::default();
+        board_info.str_serial_num[..8].copy_from_slice(b"MOCK0001");
+        mock.set_devices(vec![other, board_info]);
+        let reset = ResetDevice {
+            can_lib: mock.clone(),
+            serial_number: Some("MOCK0001".into()),
+            dev_index: 0,
+        };
+        assert_eq!(reset.wait(Duration::from_secs(1), &AtomicBool::new(true)), Some(1));
+
+        let missing = ResetDevice {
+            serial_number: Some("GONE0001".into()),
+            ..reset
+        };
+        let started = Instant::now();
+        assert_eq!(missing.wait(Duration::from_secs(5), &AtomicBool::new(false)), None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(missing.wait(Duration::from_millis(300), &AtomicBool::new(true)), None);
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use can_core::virtual_can::{Scenario, ScenarioReport, SyntheticMessage, VIRTUAL_CHANNELS, VIRTUAL_SERIAL};
+use tauri::State;
+
+use crate::{Backend, StateMutex, DEFAULT_DEV_TYPE};
+
+/// 切換到虛擬後端並開啟虛擬裝置 (dev_index 0，兩個互相迴路的通道)；traffic 為要產生的合成流量
+#[tauri::command]
+pub fn open_virtual_device(traffic: Option<Vec<SyntheticMessage>>, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state.select_backend(Backend::Virtual)?;
+    if let Some(traffic) = traffic {
+        validate_traffic(&traffic)?;
+        app_state.virtual_can.as_ref().ok_or("virtual backend not selected")?.set_traffic(traffic);
+    }
+    app_state.open_device(DEFAULT_DEV_TYPE.code(), 0, Some(VIRTUAL_SERIAL.to_string()))?;
+    Ok("Virtual CAN device opened".into())
+}
+
+/// 更換虛擬裝置的合成流量，傳空陣列即停止產生
+#[tauri::command]
+pub fn set_virtual_traffic(traffic: Vec<SyntheticMessage>, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    validate_traffic(&traffic)?;
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let virtual_can = app_state.virtual_can.as_ref().ok_or("virtual backend not selected")?;
+    let count = traffic.len();
+    virtual_can.set_traffic(traffic);
+    Ok(format!("{} synthetic messages configured", count))
+}
+
+/// 從 JSON 檔載入模擬 ECU 情境並立即在虛擬裝置上執行，取代先前的情境
+#[tauri::command]
+pub fn load_virtual_scenario(path: String, state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let scenario: Scenario = serde_json::from_str(&text).map_err(|e| format!("Scenario file {} is invalid: {}", path, e))?;
+    let summary = format!(
+        "Scenario {} loaded: {} cyclic messages, {} responses, {} assertions",
+        scenario.name.as_deref().unwrap_or(&path),
+        scenario.cyclic.len(),
+        scenario.responses.len(),
+        scenario.assertions.len()
+    );
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state.virtual_can.as_ref().ok_or("virtual backend not selected")?.set_scenario(Some(scenario))?;
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn clear_virtual_scenario(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state.virtual_can.as_ref().ok_or("virtual backend not selected")?.set_scenario(None)?;
+    Ok("Virtual scenario cleared".into())
+}
+
+/// 情境執行到目前為止的統計、狀態切換與各項檢查的結果
+#[tauri::command]
+pub fn get_virtual_scenario_report(state: State<Arc<StateMutex>>) -> Result<ScenarioReport, String> {
+    let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    app_state
+        .virtual_can
+        .as_ref()
+        .ok_or("virtual backend not selected")?
+        .scenario_report()
+        .ok_or_else(|| "no virtual scenario loaded".to_string())
+}
+
+fn validate_traffic(traffic: &[SyntheticMessage]) -> Result<(), String> {
+    for message in traffic {
+        if message.channel as usize >= VIRTUAL_CHANNELS {
+            return Err(format!("channel {} out of range (virtual device has {} channels)", message.channel, VIRTUAL_CHANNELS));
+        }
+        if message.period_ms == 0 {
+            return Err(format!("period_ms of 0x{:X} must be greater than 0", message.id));
+        }
+        if message.data.len() > 8 {
+            return Err(format!("data of 0x{:X} exceeds 8 bytes", message.id));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::frame::CanFrameEvent;
+use crate::{DeviceType, StateMutex};
+
+struct BusWatchdog {
+    timeout: Duration,
+    /// 只有這些 ID 算作流量；None 表示任何訊框
+    id_filter: Option<HashSet<u32>>,
+    last_activity: Instant,
+    silent: bool,
+}
+
+/// bus-silent / bus-active 事件
+#[derive(Serialize, Clone)]
+pub struct BusActivityEvent {
+    pub device_type: u32,
+    pub device_index: u32,
+    pub channel: u32,
+    pub timeout_ms: u64,
+    /// 距離上一個符合條件的訊框的時間
+    pub silent_ms: u64,
+}
+
+impl BusWatchdog {
+    fn event(&self, key: (u32, u32), channel: u32) -> BusActivityEvent {
+        BusActivityEvent {
+            device_type: key.0,
+            device_index: key.1,
+            channel,
+            timeout_ms: self.timeout.as_millis() as u64,
+            silent_ms: self.last_activity.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// 各 (dev_type, dev_index, channel) 的匯流排沉默監看，由接收迴圈求值
+#[derive(Default)]
+pub struct BusWatchdogs {
+    watchdogs: HashMap<(u32, u32, u32), BusWatchdog>,
+}
+
+impl BusWatchdogs {
+    /// 接收執行緒開始時重新計時，未接收的期間不算沉默
+    pub fn restart(&mut self, key: (u32, u32), channel: u32) {
+        if let Some(watchdog) = self.watchdogs.get_mut(&(key.0, key.1, channel)) {
+            watchdog.last_activity = Instant::now();
+            watchdog.silent = false;
+        }
+    }
+
+    /// 本批次有符合條件的訊框就重新計時；原本判定為沉默時回傳 bus-active 事件
+    pub fn observe(&mut self, key: (u32, u32), channel: u32, frames: &[CanFrameEvent]) -> Option<BusActivityEvent> {
+        let watchdog = self.watchdogs.get_mut(&(key.0, key.1, channel))?;
+        let matched = match &watchdog.id_filter {
+            Some(ids) => frames.iter().any(|frame| ids.contains(&frame.id)),
+            None => !frames.is_empty(),
+        };
+        if !matched {
+            return None;
+        }
+        let event = watchdog.silent.then(|| watchdog.event(key, channel));
+        watchdog.last_activity = Instant::now();
+        watchdog.silent = false;
+        event
+    }
+
+    /// 超過 timeout 沒有符合條件的訊框時回傳一次 bus-silent 事件
+    pub fn poll(&mut self, key: (u32, u32), channel: u32) -> Option<BusActivityEvent> {
+        let watchdog = self.watchdogs.get_mut(&(key.0, key.1, channel))?;
+        if watchdog.silent || watchdog.last_activity.elapsed() < watchdog.timeout {
+            return None;
+        }
+        watchdog.silent = true;
+        Some(watchdog.event(key, channel))
+    }
+}
+
+/// 解析指定的裝置與通道後，在 state 鎖外以 (dev_type, dev_index, channel) 操作監看表
+fn with_watchdogs<T>(
+    state: &StateMutex,
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    f: impl FnOnce(&mut HashMap<(u32, u32, u32), BusWatchdog>, (u32, u32, u32)) -> T,
+) -> Result<T, String> {
+    let (slot, watchdogs) = {
+        let app_state = state.lock().map_err(|_| "Failed to lock state")?;
+        let device = app_state.device(dev_type.map(DeviceType::code), dev_index)?;
+        device.check_channel(channel)?;
+        ((device.dev_type, device.dev_index, channel), app_state.watchdogs.clone())
+    };
+    let mut watchdogs = watchdogs.lock().map_err(|_| "Failed to lock watchdogs")?;
+    Ok(f(&mut watchdogs.watchdogs, slot))
+}
+
+/// 接收中的通道超過 timeout_ms 未收到 (符合 id_filter 的) 訊框時發出 bus-silent，流量恢復時發出 bus-active
+#[tauri::command]
+pub fn set_bus_watchdog(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    timeout_ms: u64,
+    id_filter: Option<Vec<u32>>,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    if timeout_ms == 0 {
+        return Err("timeout_ms must be greater than 0".into());
+    }
+    with_watchdogs(&state, dev_type, dev_index, channel, |watchdogs, slot| {
+        watchdogs.insert(
+            slot,
+            BusWatchdog {
+                timeout: Duration::from_millis(timeout_ms),
+                id_filter: id_filter.map(|ids| ids.into_iter().collect()),
+                last_activity: Instant::now(),
+                silent: false,
+            },
+        )
+    })?;
+    Ok(format!("Bus watchdog on CAN{} armed ({} ms)", channel + 1, timeout_ms))
+}
+
+#[tauri::command]
+pub fn clear_bus_watchdog(
+    dev_type: Option<DeviceType>,
+    dev_index: Option<u32>,
+    channel: u32,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    with_watchdogs(&state, dev_type, dev_index, channel, |watchdogs, slot| watchdogs.remove(&slot))?
+        .ok_or_else(|| format!("no bus watchdog on CAN{}", channel + 1))?;
+    Ok(format!("Bus watchdog on CAN{} cleared", channel + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn armed(timeout_ms: u64) -> BusWatchdog {
+        BusWatchdog {
+            timeout: Duration::from_millis(timeout_ms),
+            id_filter: None,
+            last_activity: Instant::now(),
+            silent: false,
+        }
+    }
+
+    #[test]
+    fn a_watchdog_reports_only_its_own_device() {
+        let mut watchdogs = BusWatchdogs::default();
+        watchdogs.watchdogs.insert((4, 0, 0), armed(0));
+        watchdogs.watchdogs.insert((4, 1, 0), armed(60_000));
+
+        let event = watchdogs.poll((4, 0), 0).expect("device 0 is silent");
+        assert_eq!((event.device_type, event.device_index, event.channel), (4, 0, 0));
+        assert!(watchdogs.poll((4, 1), 0).is_none());
+        // 沒有設定監看的裝置不會產生事件
+        assert!(watchdogs.poll((4, 2), 0).is_none());
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, Utf8Bytes, WebSocket};
+
+use crate::frame::FrameInput;
+use crate::ring_buffer::BufferedFrame;
+use crate::supervisor;
+use crate::tx_limit::transmit_paced;
+use crate::{DeviceType, StateMutex};
+
+/// 沒有新連線時的等待間隔
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+/// 用戶端讀取的逾時；每次逾時後送出累積的訊框，也是訊框批次的最長間隔
+const READ_POLL: Duration = Duration::from_millis(20);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// 每個用戶端最多累積的批次數；來不及讀取的用戶端會漏掉之後的批次，不拖慢接收
+const CLIENT_QUEUE_BATCHES: usize = 1000;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WsBridgeOptions {
+    /// 預設只接受本機連線
+    pub bind_address: String,
+    /// 設定時用戶端必須以 ?token= 或 Authorization: Bearer 提供
+    pub token: Option<String>,
+    /// 唯讀時拒絕傳送請求
+    pub read_only: bool,
+}
+
+impl Default for WsBridgeOptions {
+    fn default() -> Self {
+        Self {
+            bind_address: "127.0.0.1".into(),
+            token: None,
+            read_only: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ServerMessage<'a> {
+    Frames { frames: &'a [BufferedFrame] },
+    TransmitResult {
+        request_id: Option<u64>,
+        sent: Option<u32>,
+        error: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ClientMessage {
+    Transmit {
+        request_id: Option<u64>,
+        dev_type: Option<DeviceType>,
+        dev_index: Option<u32>,
+        channel: u32,
+        frame: FrameInput,
+    },
+}
+
+/// ws-client-connected / ws-client-disconnected 事件的內容
+#[derive(Serialize, Clone)]
+struct WsClientEvent {
+    client_id: u64,
+    peer: String,
+}
+
+/// 已連線的用戶端。接收迴圈與 TX 回送每批次只序列化一次，再交給各用戶端的執行緒送出
+#[derive(Default)]
+pub struct WsHub {
+    clients: Mutex<HashMap<u64, SyncSender<Utf8Bytes>>>,
+    next_id: AtomicU64,
+}
+
+impl WsHub {
+    pub fn broadcast(&self, frames: &[BufferedFrame]) {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+        if clients.is_empty() || frames.is_empty() {
+            return;
+        }
+        let Ok(json) = serde_json::to_string(&ServerMessage::Frames { frames }) else {
+            return;
+        };
+        let batch = Utf8Bytes::from(json);
+        clients.retain(|_, sender| !matches!(sender.try_send(batch.clone()), Err(TrySendError::Disconnected(_))));
+    }
+
+    fn register(&self) -> (u64, Receiver<Utf8Bytes>) {
+        let client_id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (sender, receiver) = mpsc::sync_channel(CLIENT_QUEUE_BATCHES);
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(client_id, sender);
+        }
+        (client_id, receiver)
+    }
+
+    fn remove(&self, client_id: u64) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.remove(&client_id);
+        }
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().map_or(0, |clients| clients.len())
+    }
+}
+
+/// 執行中的 WebSocket 橋接
+pub struct WsBridge {
+    running: Arc<AtomicBool>,
+    address: String,
+}
+
+impl WsBridge {
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+/// 用戶端執行緒共用的內容
+#[derive(Clone)]
+struct Bridge {
+    state: Arc<StateMutex>,
+    hub: Arc<WsHub>,
+    running: Arc<AtomicBool>,
+    options: WsBridgeOptions,
+    app_handle: tauri::AppHandle,
+}
+
+impl Bridge {
+    fn authorize(&self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let Some(token) = &self.options.token else {
+            return Ok(response);
+        };
+        let from_query = request
+            .uri()
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter_map(|pair| pair.strip_prefix("token="))
+            .any(|value| value == token);
+        let from_header = request
+            .headers()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|value| value == token);
+        if from_query || from_header {
+            return Ok(response);
+        }
+        let mut rejection = ErrorResponse::new(Some("invalid token".into()));
+        *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+        Err(rejection)
+    }
+
+    fn transmit(&self, dev_type: Option<DeviceType>, dev_index: Option<u32>, channel: u32, frame: &FrameInput) -> Result<u32, String> {
+        if self.options.read_only {
+            return Err("bridge is read-only".into());
+        }
+        let can_obj = frame.checked("frame")?;
+        let key = self
+            .state
+            .lock()
+            .map_err(|_| "Failed to lock state")?
+            .connected_device(dev_type.map(DeviceType::code), dev_index)?
+            .key();
+        transmit_paced(&self.state, key, channel, &[can_obj], true)
+    }
+
+    fn handle_request(&self, text: &str) -> String {
+        let reply = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(ClientMessage::Transmit {
+                request_id,
+                dev_type,
+                dev_index,
+                channel,
+                frame,
+            }) => {
+                let (sent, error) = match self.transmit(dev_type, dev_index, channel, &frame) {
+                    Ok(sent) => (Some(sent), None),
+                    Err(error) => (None, Some(error)),
+                };
+                ServerMessage::TransmitResult { request_id, sent, error }
+            }
+            Err(e) => ServerMessage::TransmitResult {
+                request_id: None,
+                sent: None,
+                error: Some(format!("invalid request: {}", e)),
+            },
+        };
+        serde_json::to_string(&reply).unwrap_or_default()
+    }
+
+    /// 完成握手後輪流送出累積的訊框與處理用戶端的請求，直到任一端關閉或橋接停止
+    fn serve(&self, stream: TcpStream, peer: SocketAddr) {
+        // Windows 上 accept 出來的連線會沿用 listener 的非阻塞設定
+        if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).is_err() {
+            return;
+        }
+        let Ok(mut socket) = tungstenite::accept_hdr(stream, |request: &Request, response| self.authorize(request, response))
+        else {
+            return;
+        };
+        if socket.get_ref().set_read_timeout(Some(READ_POLL)).is_err() {
+            return;
+        }
+        let (client_id, frames) = self.hub.register();
+        let event = WsClientEvent {
+            client_id,
+            peer: peer.to_string(),
+        };
+        let _ = self.app_handle.emit("ws-client-connected", event.clone());
+        self.pump(&mut socket, &frames);
+        self.hub.remove(client_id);
+        let _ = socket.close(None);
+        let _ = socket.flush();
+        let _ = self.app_handle.emit("ws-client-disconnected", event);
+    }
+
+    fn pump(&self, socket: &mut WebSocket<TcpStream>, frames: &Receiver<Utf8Bytes>) {
+        while self.running.load(Ordering::SeqCst) {
+            loop {
+                match frames.try_recv() {
+                    Ok(batch) => {
+                        if socket.send(Message::Text(batch)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            match socket.read() {
+                Ok(Message::Text(text)) => {
+                    let reply = self.handle_request(text.as_str());
+                    if socket.send(Message::text(reply)).is_err() {
+                        return;
+                    }
+                }
+                Ok(Message::Close(_)) => return,
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/// 在 bind_address:port 啟動 WebSocket 伺服器，回傳實際的位址 (port 為 0 時由系統指定)。
+/// 用戶端以 {"type":"frames","frames":[...]} 批次收到與 can-data 相同內容的訊框，
+/// 可送出 {"type":"transmit","channel":0,"frame":{...}} 經一般的驗證與傳送路徑傳送
+#[tauri::command]
+pub fn start_ws_bridge(
+    port: u16,
+    options: Option<WsBridgeOptions>,
+    app_handle: tauri::AppHandle,
+    state: State<Arc<StateMutex>>,
+) -> Result<String, String> {
+    let options = options.unwrap_or_default();
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    if app_state.ws_bridge.is_some() {
+        return Err("WebSocket bridge already running".into());
+    }
+    let listener = TcpListener::bind((options.bind_address.as_str(), port))
+        .map_err(|e| format!("Failed to listen on {}:{}: {}", options.bind_address, port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let address = format!("ws://{}", listener.local_addr().map_err(|e| e.to_string())?);
+    let address_text = address.clone();
+    let running = Arc::new(AtomicBool::new(true));
+    let bridge = Bridge {
+        state: state.inner().clone(),
+        hub: app_state.ws_hub.clone(),
+        running: running.clone(),
+        options,
+        app_handle,
+    };
+    std::thread::spawn(move || {
+        let finished = supervisor::guard("ws-bridge", address_text, || {
+            while bridge.running.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, peer)) => {
+                        let bridge = bridge.clone();
+                        std::thread::spawn(move || {
+                            supervisor::guard("ws-bridge-client", peer.to_string(), || bridge.serve(stream, peer));
+                        });
+                    }
+                    Err(_) => std::thread::sleep(ACCEPT_POLL),
+                }
+            }
+        });
+        if finished.is_none() {
+            bridge.running.store(false, Ordering::SeqCst);
+            if let Ok(mut app_state) = bridge.state.lock() {
+                if app_state.ws_bridge.as_ref().is_some_and(|b| Arc::ptr_eq(&b.running, &bridge.running)) {
+                    app_state.ws_bridge = None;
+                }
+            }
+        }
+    });
+    app_state.ws_bridge = Some(WsBridge {
+        running,
+        address: address.clone(),
+    });
+    Ok(address)
+}
+
+/// 停止接受連線並關閉所有用戶端
+#[tauri::command]
+pub fn stop_ws_bridge(state: State<Arc<StateMutex>>) -> Result<String, String> {
+    let mut app_state = state.lock().map_err(|_| "Failed to lock state")?;
+    let bridge = app_state.ws_bridge.take().ok_or("WebSocket bridge is not running")?;
+    bridge.running.store(false, Ordering::SeqCst);
+    Ok(format!("WebSocket bridge at {} stopped", bridge.address))
+}
@@ -0,0 +1,1293 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use can_app_lib::mock::MockCan;
+use can_app_lib::{
+    app_status, backend_heartbeat, change_channel, force_usb_reset_device, parse_log, query_recent_frames, receive_once, reconnect_device, run_auto_connect, spawn_receive_loop, spawn_tx_sequence, transmit, transmit_paced_as, transmit_tracked,
+    AppState, AutoConnectSettings, AutoConnectStatus, ChannelConfig, Dedupe, DedupeCompare, DeviceType, EventSink, FrameInput, FrameQuery, IsoTpError, IsoTpLink, IsoTpOptions, LogDialect, MemoryLimits, OperationKind, ProbeStatus, Provenance, ReceiveOptions, StateMutex, TxRetry,
+    VciBoardInfo, VciCanObj, VciInitConfig,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// 記錄接收迴圈送出的所有事件
+#[derive(Clone, Default)]
+struct RecordedEvents(Arc<Mutex<Vec<(String, Value)>>>);
+
+impl EventSink for RecordedEvents {
+    fn emit_event<S: Serialize + Clone>(&self, event: &str, payload: S) -> bool {
+        let payload = serde_json::to_value(payload).expect("event payload serializes");
+        self.0.lock().unwrap().push((event.to_string(), payload));
+        true
+    }
+}
+
+impl RecordedEvents {
+    fn named(&self, event: &str) -> Vec<Value> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| name == event)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+
+    /// 等到收到至少 count 個指定事件，逾時則回傳目前收到的
+    fn wait_for(&self, event: &str, count: usize) -> Vec<Value> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            let events = self.named(event);
+            if events.len() >= count || Instant::now() > deadline {
+                return events;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+fn dev_type() -> u32 {
+    DeviceType::Usbcan2.code()
+}
+
+fn config() -> VciInitConfig {
+    VciInitConfig {
+        acc_mask: 0xFFFF_FFFF,
+        filter: 1,
+        timing0: 0x00,
+        timing1: 0x1C,
+        ..Default::default()
+    }
+}
+
+fn frame(id: u32, data: &[u8]) -> VciCanObj {
+    let mut can_obj = VciCanObj {
+        id,
+        extern_flag: (id > 0x7FF) as u8,
+        data_len: data.len() as u8,
+        ..Default::default()
+    };
+    can_obj.data[..data.len()].copy_from_slice(data);
+    can_obj
+}
+
+/// 開啟裝置並啟動兩個通道
+fn setup() -> (Arc<MockCan>, Arc<StateMutex>) {
+    let mock = Arc::new(MockCan::new());
+    let mut app_state = AppState::with_interface(mock.clone());
+    app_state.open_device(dev_type(), 0, Some("MOCK0001".into())).unwrap();
+    for channel in [0, 1] {
+        app_state.start_channel((dev_type(), 0), channel, config()).unwrap();
+    }
+    (mock, Arc::new(StateMutex::new(app_state)))
+}
+
+#[test]
+fn receive_loop_emits_queued_frames_in_order() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x100, &[1]), frame(0x200, &[2, 3]), frame(0x18FEF100, &[4])]);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+
+    let frames = events.wait_for("can-data", 3);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    let ids: Vec<u64> = frames.iter().map(|f| f["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, [0x100, 0x200, 0x18FEF100]);
+    assert_eq!(frames[1]["data"], serde_json::json!([2, 3]));
+    assert_eq!(frames[2]["extended"], true);
+    assert!(frames.iter().all(|f| f["device_type"] == dev_type() && f["device_index"] == 0 && f["channel"] == 0));
+    // seq 依序遞增，前端可據此補齊遺漏的事件
+    let seqs: Vec<u64> = frames.iter().map(|f| f["seq"].as_u64().unwrap()).collect();
+    assert_eq!(seqs, [0, 1, 2]);
+}
+
+#[test]
+fn auto_timestamps_use_the_device_counter_across_wraparound() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let stamped = |id: u32, time_stamp: u32| VciCanObj {
+        time_stamp,
+        time_flag: 1,
+        ..frame(id, &[0])
+    };
+    // 計數器在兩個訊框之間溢位，實際相隔 100 個 0.1 ms
+    mock.queue_receive(dev_type(), 0, 0, [stamped(0x100, u32::MAX - 49), stamped(0x101, 50), frame(0x102, &[0])]);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+
+    let frames = events.wait_for("can-data", 3);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    let timestamp = |frame: &Value| frame["timestamp_us"].as_u64().unwrap();
+    assert_eq!(frames[0]["timestamp_source"], "hardware");
+    assert_eq!(timestamp(&frames[0]), frames[0]["host_timestamp_us"].as_u64().unwrap());
+    assert_eq!(frames[1]["timestamp_source"], "hardware");
+    assert_eq!(timestamp(&frames[1]) - timestamp(&frames[0]), 10_000);
+    // 沒有 time_flag 的訊框退回主機時間
+    assert_eq!(frames[2]["timestamp_source"], "host");
+    assert_eq!(timestamp(&frames[2]), frames[2]["host_timestamp_us"].as_u64().unwrap());
+}
+
+#[test]
+fn hardware_timestamps_of_both_channels_count_from_their_start() {
+    let (mock, state) = setup();
+    // 接收比啟動晚開始；兩個通道在啟動後 1 ms 收到的訊框應落在同一時間點，而不是各自的接收時間
+    std::thread::sleep(Duration::from_millis(50));
+    let stamped = VciCanObj {
+        time_stamp: 10,
+        time_flag: 1,
+        ..frame(0x100, &[0])
+    };
+    let events = RecordedEvents::default();
+    let handles: Vec<_> = [0, 1]
+        .into_iter()
+        .map(|channel| {
+            mock.queue_receive(dev_type(), 0, channel, [stamped.clone()]);
+            spawn_receive_loop(&state, events.clone(), None, None, channel, ReceiveOptions::default()).unwrap().1
+        })
+        .collect();
+
+    let frames = events.wait_for("can-data", 2);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handles.into_iter().for_each(|handle| handle.join().unwrap());
+
+    assert_eq!(frames.len(), 2);
+    let timestamp = |frame: &Value| frame["timestamp_us"].as_u64().unwrap();
+    for frame in &frames {
+        assert_eq!(frame["timestamp_source"], "hardware");
+        assert!(frame["host_timestamp_us"].as_u64().unwrap() - timestamp(frame) >= 40_000);
+    }
+    assert!(timestamp(&frames[0]).abs_diff(timestamp(&frames[1])) < 1_000);
+}
+
+#[test]
+fn receive_loop_only_reads_its_own_channel() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    mock.queue_receive(dev_type(), 0, 1, [frame(0x300, &[9])]);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    assert!(events.named("can-data").is_empty());
+    assert_eq!(mock.pending_receive(dev_type(), 0, 1), 1);
+}
+
+#[test]
+fn stop_receiving_ends_the_thread_and_leaves_later_frames_unread() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x123, &[0xAA])]);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(mock.pending_receive(dev_type(), 0, 0), 1);
+    assert!(events.named("can-data").is_empty());
+}
+
+#[test]
+fn repeated_receive_errors_report_disconnect_and_stop() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    mock.set_receive_error(Some(-1));
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+
+    // 沒有開啟自動重連時，迴圈在回報斷線後自行結束
+    handle.join().unwrap();
+    let disconnected = events.named("can-disconnected");
+    assert_eq!(disconnected.len(), 1);
+    assert_eq!(disconnected[0]["channel"], 0);
+}
+
+#[test]
+fn transmit_sends_frames_through_the_interface() {
+    let (mock, state) = setup();
+    let sent = transmit(&state, (dev_type(), 0), 1, &[frame(0x7DF, &[0x02, 0x01, 0x00]), frame(0x7E0, &[0x3E])]).unwrap();
+
+    assert_eq!(sent, 2);
+    let transmitted = mock.transmitted();
+    assert_eq!(transmitted.len(), 2);
+    assert!(transmitted.iter().all(|(channel, _)| *channel == 1));
+    assert_eq!(transmitted[0].1.id, 0x7DF);
+    assert_eq!(&transmitted[0].1.data[..3], &[0x02, 0x01, 0x00]);
+}
+
+#[test]
+fn transmit_results_carry_a_sequence_number_and_call_time_per_frame() {
+    let (_, state) = setup();
+    let key = (dev_type(), 0);
+    let first = transmit_tracked(&state, key, 0, &[frame(0x100, &[1]), frame(0x101, &[2])], true, TxRetry::default()).unwrap();
+    let second = transmit_tracked(&state, key, 0, &[frame(0x102, &[3])], true, TxRetry::default()).unwrap();
+
+    assert_eq!(first.frames.len(), 2);
+    let seqs: Vec<u64> = first.frames.iter().chain(&second.frames).map(|timing| timing.tx_seq).collect();
+    assert_eq!(seqs, [0, 1, 2]);
+    // 同一次 VCI_Transmit 送出的訊框共用呼叫時間
+    assert_eq!(first.frames[0].call_start_us, first.frames[1].call_start_us);
+    assert!(first.frames[0].call_start_us <= first.frames[0].call_end_us);
+    assert!(first.frames[1].call_end_us <= second.frames[0].call_start_us);
+}
+
+#[test]
+fn a_panic_while_holding_the_state_lock_does_not_break_later_commands() {
+    let (mock, state) = setup();
+    let panicked = std::thread::spawn({
+        let state = state.clone();
+        move || {
+            let _app_state = state.lock().unwrap();
+            panic!("command panicked while holding the state lock");
+        }
+    })
+    .join();
+    assert!(panicked.is_err());
+
+    // 鎖在下一次取得時恢復，裝置與通道設定維持原狀
+    let sent = transmit(&state, (dev_type(), 0), 0, &[frame(0x100, &[1])]).unwrap();
+    assert_eq!(sent, 1);
+    assert_eq!(mock.transmitted().len(), 1);
+    let events = RecordedEvents::default();
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x200, &[2])]);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    let frames = events.wait_for("can-data", 1);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0]["id"], 0x200);
+}
+
+#[test]
+fn transmit_failure_is_reported_as_error() {
+    let (mock, state) = setup();
+    mock.set_transmit_failure(true);
+    let result = transmit(&state, (dev_type(), 0), 0, &[frame(0x100, &[1])]);
+
+    assert!(result.is_err());
+    assert!(mock.transmitted().is_empty());
+}
+
+#[test]
+fn receive_loop_requires_an_open_device() {
+    let mock = Arc::new(MockCan::new());
+    let state = Arc::new(StateMutex::new(AppState::with_interface(mock)));
+    let result = spawn_receive_loop(&state, RecordedEvents::default(), None, None, 0, ReceiveOptions::default());
+    assert!(result.is_err());
+}
+
+#[test]
+fn extended_frames_come_back_marked_extended_in_loopback_mode() {
+    let (_, state) = setup();
+    let loopback = VciInitConfig { mode: 2, ..config() };
+    state.lock().unwrap().start_channel((dev_type(), 0), 0, loopback).unwrap();
+    let standard = frame(0x123, &[1]);
+    let extended = VciCanObj {
+        extern_flag: 1,
+        ..frame(0x123, &[2])
+    };
+    let remote = VciCanObj {
+        remote_flag: 1,
+        data_len: 0,
+        ..frame(0x18FEF100, &[])
+    };
+    transmit(&state, (dev_type(), 0), 0, &[standard, extended, remote]).unwrap();
+
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    let frames = events.wait_for("can-data", 3);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    assert_eq!(frames.len(), 3);
+    // 0x123 的標準幀與擴展幀必須能被區分
+    assert_eq!(frames[0]["id"], 0x123);
+    assert_eq!(frames[0]["extended"], false);
+    assert_eq!(frames[1]["id"], 0x123);
+    assert_eq!(frames[1]["extended"], true);
+    assert_eq!(frames[2]["id"], 0x18FEF100);
+    assert_eq!(frames[2]["extended"], true);
+    assert_eq!(frames[2]["remote"], true);
+}
+
+#[test]
+fn dlc_0_and_dlc_8_frames_come_back_intact_in_loopback_mode() {
+    let (_, state) = setup();
+    let loopback = VciInitConfig { mode: 2, ..config() };
+    state.lock().unwrap().start_channel((dev_type(), 0), 0, loopback).unwrap();
+    let input = |id, dlc, data: &[u8], padding| FrameInput {
+        id,
+        extended: None,
+        remote: false,
+        dlc,
+        data: data.to_vec(),
+        padding,
+    };
+    let empty = input(0x100, Some(0), &[], None).to_can_obj().unwrap();
+    let full = input(0x101, None, &[1, 2, 3, 4, 5, 6, 7, 8], None).to_can_obj().unwrap();
+    let padded = input(0x102, Some(8), &[0x11, 0x22], Some(0xAA)).to_can_obj().unwrap();
+    // 遙控幀的 DLC 是請求的長度，緩衝中殘留的位元組不能被當成資料
+    let remote = VciCanObj {
+        remote_flag: 1,
+        data_len: 4,
+        data: [0xEE; 8],
+        ..frame(0x103, &[])
+    };
+    transmit(&state, (dev_type(), 0), 0, &[empty, full, padded, remote]).unwrap();
+
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    let frames = events.wait_for("can-data", 4);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[0]["dlc"], 0);
+    assert_eq!(frames[0]["data"], json!([]));
+    assert_eq!(frames[1]["dlc"], 8);
+    assert_eq!(frames[1]["data"], json!([1, 2, 3, 4, 5, 6, 7, 8]));
+    assert_eq!(frames[2]["dlc"], 8);
+    assert_eq!(frames[2]["data"], json!([0x11, 0x22, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]));
+    assert_eq!(frames[3]["dlc"], 4);
+    assert_eq!(frames[3]["data"], json!([]));
+}
+
+#[test]
+fn dlc_shorter_than_the_data_is_rejected() {
+    let frame = FrameInput {
+        id: 0x100,
+        extended: None,
+        remote: false,
+        dlc: Some(2),
+        data: vec![1, 2, 3],
+        padding: None,
+    };
+    let error = frame.to_can_obj().unwrap_err();
+    assert!(error.contains("dlc"), "{}", error);
+}
+
+#[test]
+fn transmit_rejects_ids_too_large_for_the_frame_format() {
+    let (mock, state) = setup();
+    let standard_too_large = VciCanObj {
+        extern_flag: 0,
+        ..frame(0x800, &[1])
+    };
+    let extended_too_large = frame(0x2000_0000, &[1]);
+
+    assert!(transmit(&state, (dev_type(), 0), 0, &[standard_too_large]).is_err());
+    assert!(transmit(&state, (dev_type(), 0), 0, &[extended_too_large]).is_err());
+    assert!(mock.transmitted().is_empty());
+    assert!(transmit(&state, (dev_type(), 0), 0, &[frame(0x1FFF_FFFF, &[1])]).is_ok());
+}
+
+#[test]
+fn device_errors_are_reported_separately_from_a_full_tx_buffer() {
+    let (mock, state) = setup();
+    mock.set_busy_transmits(1);
+    let busy = transmit(&state, (dev_type(), 0), 0, &[frame(0x100, &[1])]);
+    mock.set_transmit_error(Some(-1));
+    let gone = transmit(&state, (dev_type(), 0), 0, &[frame(0x100, &[1])]);
+
+    assert!(busy.is_err());
+    assert!(gone.unwrap_err().contains("(-1)"));
+    assert!(mock.transmitted().is_empty());
+}
+
+#[test]
+fn driver_buffer_overflow_is_reported_and_counted() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    // ERR_CAN_BUFFER_OVERFLOW
+    mock.set_err_code(dev_type(), 0, 0, 0x0040);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    let overflows = events.wait_for("can-overflow", 1);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    assert_eq!(overflows.len(), 1);
+    assert_eq!(overflows[0]["source"], "driver_buffer");
+    assert_eq!(overflows[0]["overflows"], 1);
+    assert!(overflows[0]["loss_window_ms"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn dropping_the_state_closes_each_open_device_once() {
+    let (mock, state) = setup();
+    drop(state);
+
+    assert_eq!(mock.open_count(dev_type(), 0), 1);
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+    assert!(!mock.is_open(dev_type(), 0));
+}
+
+#[test]
+fn a_device_that_failed_to_open_is_never_closed() {
+    let mock = Arc::new(MockCan::new());
+    mock.set_open_failure(true);
+    let mut app_state = AppState::with_interface(mock.clone());
+    assert!(app_state.open_device(dev_type(), 0, None).is_err());
+    drop(app_state);
+
+    assert_eq!(mock.close_count(dev_type(), 0), 0);
+}
+
+#[test]
+fn reconnect_closes_the_device_once_when_init_fails() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    mock.set_init_failure(Some(1));
+    let error = reconnect_device(&state, events.clone(), None, None, [0, 1], config()).err().expect("reconnect fails");
+    handle.join().unwrap();
+
+    assert!(error.contains("CAN2"), "{}", error);
+    assert!(error.contains("(CAN1)"), "{}", error);
+    assert!(!mock.is_open(dev_type(), 0));
+    assert_eq!(mock.open_count(dev_type(), 0), 2);
+    assert_eq!(mock.close_count(dev_type(), 0), 2);
+    // 失敗的裝置已從狀態移除，之後不會再被關閉
+    drop(state);
+    assert_eq!(mock.close_count(dev_type(), 0), 2);
+}
+
+#[test]
+fn reconnect_closes_the_device_once_when_reopen_fails() {
+    let (mock, state) = setup();
+    mock.set_open_failure(true);
+    let error = reconnect_device(&state, RecordedEvents::default(), None, None, [0, 1], config()).err().expect("reconnect fails");
+
+    assert!(error.contains("Failed to open device"), "{}", error);
+    assert_eq!(mock.open_count(dev_type(), 0), 1);
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+    drop(state);
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+}
+
+#[test]
+fn reconnect_restarts_receiving_and_keeps_the_device_owned() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    reconnect_device(&state, events.clone(), None, None, [0, 1], config()).unwrap();
+    handle.join().unwrap();
+
+    let restarted = events.wait_for("stream-restarted", 1);
+    assert_eq!(restarted[0]["channels"], json!([0]));
+    assert!(mock.is_started(dev_type(), 0, 1));
+    assert_eq!(mock.open_count(dev_type(), 0), 2);
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+    // 重新啟動的接收執行緒登記在裝置上，停止後等它放開 state 再 drop
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Arc::strong_count(&state) > 1 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    drop(state);
+    assert_eq!(mock.close_count(dev_type(), 0), 2);
+}
+
+#[test]
+fn receive_thread_reports_why_it_ended() {
+    let (_mock, state) = setup();
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 1, ReceiveOptions::default()).unwrap();
+    state.lock().unwrap().stop_receiving(None, None, Some(1)).unwrap();
+    handle.join().unwrap();
+
+    let ended = events.named("stream-ended");
+    assert_eq!(ended.len(), 1);
+    assert_eq!(ended[0]["channel"], 1);
+    assert_eq!(ended[0]["reason"], "stopped");
+    assert!(state.lock().unwrap().receiving_channels(None, None).unwrap().is_empty());
+}
+
+#[test]
+fn receive_thread_panic_ends_stream_and_channel_can_restart() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    mock.set_receive_panics(1);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 1, ReceiveOptions::default()).unwrap();
+    handle.join().unwrap();
+
+    let ended = events.named("stream-ended");
+    assert_eq!(ended.len(), 1);
+    assert_eq!(ended[0]["reason"], "panicked");
+    assert!(state.lock().unwrap().receiving_channels(None, None).unwrap().is_empty());
+
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 1, ReceiveOptions::default()).unwrap();
+    mock.queue_receive(dev_type(), 0, 1, [frame(0x123, &[1])]);
+    assert_eq!(events.wait_for("can-data", 1).len(), 1);
+    state.lock().unwrap().stop_receiving(None, None, Some(1)).unwrap();
+    handle.join().unwrap();
+}
+
+/// 在回送 (mode = 2) 通道上量測安靜匯流排從送出到收到 can-data 的平均延遲
+fn loopback_latency(options: ReceiveOptions) -> Duration {
+    let mock = Arc::new(MockCan::new());
+    let mut app_state = AppState::with_interface(mock);
+    app_state.open_device(dev_type(), 0, None).unwrap();
+    app_state.start_channel((dev_type(), 0), 0, VciInitConfig { mode: 2, ..config() }).unwrap();
+    let state = Arc::new(StateMutex::new(app_state));
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, options).unwrap();
+    let samples = 3;
+    let mut total = Duration::ZERO;
+    for n in 1..=samples {
+        // 讓輪詢退避到 idle_poll_max 才送出
+        std::thread::sleep(Duration::from_millis(250));
+        let sent = Instant::now();
+        transmit(&state, (dev_type(), 0), 0, &[frame(0x100, &[n as u8])]).unwrap();
+        assert_eq!(events.wait_for("can-data", n).len(), n);
+        total += sent.elapsed();
+    }
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+    total / samples as u32
+}
+
+#[test]
+fn blocking_receive_delivers_frames_faster_than_idle_polling() {
+    // 輪詢在安靜的匯流排上休眠 idle_poll_max (200 ms)，平均延遲約為其一半；
+    // 阻塞讀取在訊框到達後約 1 ms 內返回，延遲主要來自 wait_for 的 5 ms 檢查間隔
+    let polling = loopback_latency(ReceiveOptions::default());
+    let blocking = loopback_latency(ReceiveOptions {
+        blocking_wait: Some(Duration::from_millis(150)),
+        ..Default::default()
+    });
+
+    assert!(blocking < Duration::from_millis(30), "blocking latency {:?}", blocking);
+    assert!(blocking < polling, "blocking {:?} vs polling {:?}", blocking, polling);
+}
+
+#[test]
+fn blocking_receive_stops_within_one_wait_interval() {
+    let (_mock, state) = setup();
+    let wait = Duration::from_millis(150);
+    let options = ReceiveOptions {
+        blocking_wait: Some(wait),
+        ..Default::default()
+    };
+    let (_, handle) = spawn_receive_loop(&state, RecordedEvents::default(), None, None, 0, options).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let stopping = Instant::now();
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    assert!(stopping.elapsed() < wait + Duration::from_millis(100), "stopped after {:?}", stopping.elapsed());
+}
+
+#[test]
+fn force_usb_reset_closes_device_and_finds_it_again_by_serial() {
+    let (mock, state) = setup();
+    let mut other = VciBoardInfo::default();
+    other.str_serial_num[..8].copy_from_slice(b"OTHER001");
+    let mut board_info = VciBoardInfo::default();
+    board_info.str_serial_num[..8].copy_from_slice(b"MOCK0001");
+    // 重新列舉後裝置換到 index 1
+    mock.set_devices(vec![other, board_info]);
+    let (_, handle) = spawn_receive_loop(&state, RecordedEvents::default(), None, None, 0, ReceiveOptions::default()).unwrap();
+
+    let result = force_usb_reset_device(&state, None, None, Duration::from_secs(2)).unwrap();
+    handle.join().unwrap();
+
+    assert!(result.was_open);
+    assert!(result.reappeared);
+    assert_eq!(result.dev_index, Some(1));
+    assert_eq!(result.serial_number.as_deref(), Some("MOCK0001"));
+    assert_eq!(mock.usb_reset_count(dev_type(), 0), 1);
+    assert_eq!(mock.close_count(dev_type(), 0), 1);
+    assert!(state.lock().unwrap().receiving_channels(None, None).is_err());
+}
+
+#[test]
+fn force_usb_reset_reports_a_device_that_does_not_come_back() {
+    let (mock, state) = setup();
+    let result = force_usb_reset_device(&state, None, None, Duration::from_millis(300)).unwrap();
+    assert!(!result.reappeared);
+    assert_eq!(result.dev_index, None);
+    assert_eq!(mock.usb_reset_count(dev_type(), 0), 1);
+}
+
+#[test]
+fn recent_frames_merge_rx_and_tx_and_filter_by_provenance_and_id_range() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let key = (dev_type(), 0);
+    mock.queue_receive(dev_type(), 0, 1, [frame(0x150, &[1])]);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 1, ReceiveOptions::default()).unwrap();
+    events.wait_for("can-data", 1);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+    transmit_tracked(&state, key, 0, &[frame(0x100, &[2])], true, TxRetry::default()).unwrap();
+    transmit_paced_as(&state, key, 0, &[frame(0x200, &[3])], true, Provenance::TxReplay).unwrap();
+
+    let all = serde_json::to_value(query_recent_frames(&state, &FrameQuery::default(), None, 10, false).unwrap()).unwrap();
+    let tagged: Vec<(u64, u64, &str)> = all["frames"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["channel"].as_u64().unwrap(), f["id"].as_u64().unwrap(), f["provenance"].as_str().unwrap()))
+        .collect();
+    assert_eq!(tagged, [(1, 0x150, "rx"), (0, 0x100, "tx-manual"), (0, 0x200, "tx-replay")]);
+
+    let replayed = query_recent_frames(
+        &state,
+        &FrameQuery {
+            provenance: Some(Provenance::TxReplay),
+            ..FrameQuery::default()
+        },
+        None,
+        10,
+        false,
+    )
+    .unwrap();
+    assert_eq!(replayed.frames.len(), 1);
+    assert_eq!(replayed.frames[0].frame.id, 0x200);
+    let in_range = query_recent_frames(
+        &state,
+        &FrameQuery {
+            id_min: Some(0x140),
+            id_max: Some(0x1FF),
+            ..FrameQuery::default()
+        },
+        None,
+        10,
+        false,
+    )
+    .unwrap();
+    assert_eq!(in_range.frames.iter().map(|f| f.frame.id).collect::<Vec<_>>(), [0x150]);
+}
+
+#[test]
+fn annotations_are_listed_in_time_order_and_returned_with_recent_frames() {
+    let (_mock, state) = setup();
+    let events = RecordedEvents::default();
+    let key = (dev_type(), 0);
+    transmit_tracked(&state, key, 0, &[frame(0x100, &[1])], true, TxRetry::default()).unwrap();
+    std::thread::sleep(Duration::from_millis(2));
+    transmit_tracked(&state, key, 0, &[frame(0x101, &[2])], true, TxRetry::default()).unwrap();
+    let recent = query_recent_frames(&state, &FrameQuery::default(), None, 10, false).unwrap();
+    assert!(recent.annotations.is_none());
+    let (first, second) = (&recent.frames[0], &recent.frames[1]);
+
+    let between = (first.frame.host_timestamp_us + 1) as f64 / 1_000_000.0;
+    let app_state = state.lock().unwrap();
+    let latest = app_state.add_annotation(&events, "after both".into(), None).unwrap();
+    let middle = app_state.add_annotation(&events, "between".into(), Some(between)).unwrap();
+    assert!(app_state.add_annotation(&events, " ".into(), None).is_err());
+    assert!(app_state.add_annotation(&events, "negative".into(), Some(-1.0)).is_err());
+    let listed: Vec<u32> = app_state.list_annotations().unwrap().iter().map(|a| a.id).collect();
+    assert_eq!(listed, [middle.id, latest.id]);
+    drop(app_state);
+    let added = events.named("annotation-added");
+    assert_eq!(added.len(), 2);
+    assert_eq!(added[1]["text"], "between");
+
+    let recent = query_recent_frames(&state, &FrameQuery::default(), None, 10, true).unwrap();
+    let markers: Vec<(u32, Option<u64>)> = recent.annotations.unwrap().iter().map(|m| (m.annotation.id, m.after_seq)).collect();
+    assert_eq!(markers, [(middle.id, Some(first.seq)), (latest.id, Some(second.seq))]);
+    let older = query_recent_frames(&state, &FrameQuery::default(), None, 1, true).unwrap();
+    assert_eq!(older.annotations.unwrap().len(), 1);
+
+    state.lock().unwrap().delete_annotation(&events, middle.id).unwrap();
+    assert!(state.lock().unwrap().delete_annotation(&events, middle.id).is_err());
+    assert_eq!(events.named("annotation-deleted"), [json!({ "id": middle.id })]);
+    assert_eq!(state.lock().unwrap().list_annotations().unwrap().len(), 1);
+}
+
+#[test]
+fn backend_heartbeat_lists_receiving_channels_and_flags_stalled_workers() {
+    let (_mock, state) = setup();
+    let events = RecordedEvents::default();
+    let pulses = state.lock().unwrap().worker_pulses();
+    let (_, handle) = spawn_receive_loop(&state, events, None, None, 1, ReceiveOptions::default()).unwrap();
+    let heartbeat = backend_heartbeat(&state, &pulses, 1);
+    let channels: Vec<(u32, u32, u32)> = heartbeat.channels.iter().map(|c| (c.dev_type, c.dev_index, c.channel)).collect();
+    assert_eq!(channels, [(dev_type(), 0, 1)]);
+    assert!(!heartbeat.stalled);
+
+    let worker = pulses.register("test", "hung", Duration::from_millis(20));
+    std::thread::sleep(Duration::from_millis(60));
+    let heartbeat = backend_heartbeat(&state, &pulses, 2);
+    assert!(heartbeat.stalled);
+    assert_eq!(heartbeat.stalled_workers.iter().map(|w| w.role).collect::<Vec<_>>(), ["test"]);
+    worker.pause();
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(!backend_heartbeat(&state, &pulses, 3).stalled);
+    drop(worker);
+
+    let app_state = state.lock().unwrap();
+    let heartbeat = backend_heartbeat(&state, &pulses, 4);
+    assert!(heartbeat.state_busy && heartbeat.stalled);
+    assert!(heartbeat.channels.is_empty());
+    drop(app_state);
+
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+    let heartbeat = backend_heartbeat(&state, &pulses, 5);
+    assert!(heartbeat.channels.is_empty() && !heartbeat.stalled);
+}
+
+#[test]
+fn vector_asc_and_busmaster_logs_parse_into_replay_frames_and_report_bad_lines() {
+    let asc = "date Wed Jun 12 10:00:00.000 am 2019\n\
+base hex  timestamps absolute\n\
+internal events logged\n\
+Begin Triggerblock Wed Jun 12 10:00:00.000 am 2019\n\
+   0.000000 Start of measurement\n\
+   0.002500 2  18FEF100x       Tx   d 2 AA BB\n\
+   0.001000 1  123             Rx   d 8 01 02 03 04 05 06 07 08  Length = 0 BitCount = 0\n\
+   0.003000 1  ErrorFrame\n\
+   0.004000 1  7FF             Rx   r 4\n\
+   0.005000 1  GARBAGE\n\
+End TriggerBlock\n";
+    let parsed = parse_log(asc);
+    assert_eq!(parsed.dialect, LogDialect::Asc);
+    let frames: Vec<(u64, &str, u32, bool, bool, u8)> =
+        parsed.frames.iter().map(|f| (f.timestamp_us, f.interface.as_str(), f.id, f.extended, f.remote, f.dlc)).collect();
+    assert_eq!(
+        frames,
+        [(1000, "CAN1", 0x123, false, false, 8), (2500, "CAN2", 0x18FE_F100, true, false, 2), (4000, "CAN1", 0x7FF, false, true, 4)]
+    );
+    assert_eq!(parsed.frames[0].data, [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(parsed.error_frames, 1);
+    assert_eq!(parsed.unparsed.len(), 1);
+    assert_eq!(parsed.unparsed[0].line, 10);
+
+    let relative = parse_log("base dec  timestamps relative\n0.001000 1  291 Rx d 1 255\n0.001000 1  292 Rx d 0\n");
+    assert_eq!(relative.dialect, LogDialect::Asc);
+    assert_eq!(relative.frames.iter().map(|f| (f.timestamp_us, f.id)).collect::<Vec<_>>(), [(1000, 0x123), (2000, 0x124)]);
+    assert_eq!(relative.frames[0].data, [0xFF]);
+
+    let busmaster = "***BUSMASTER Ver 3.2.2***\n\
+***PROTOCOL CAN***\n\
+***[START LOGGING SESSION]***\n\
+***HEX***\n\
+***SYSTEM MODE***\n\
+***<Time><Tx/Rx><Channel><CAN ID><Type><DLC><DataBytes>***\n\
+23:59:59:9990 Rx 1 0x0C1 s 2 01 02\n\
+00:00:00:0010 Tx 2 0x18FEF100 x 1 FF\n\
+00:00:00:0020 Rx 1 0x0C1 s 9 01\n\
+***[STOP LOGGING SESSION]***\n";
+    let parsed = parse_log(busmaster);
+    assert_eq!(parsed.dialect, LogDialect::Busmaster);
+    let frames: Vec<(u64, &str, u32, bool)> = parsed.frames.iter().map(|f| (f.timestamp_us, f.interface.as_str(), f.id, f.extended)).collect();
+    let midnight = 24 * 3600 * 1_000_000;
+    assert_eq!(frames, [(midnight - 1000, "CAN1", 0xC1, false), (midnight + 1000, "CAN2", 0x18FE_F100, true)]);
+    assert_eq!(parsed.unparsed.iter().map(|u| u.line).collect::<Vec<_>>(), [9]);
+
+    let candump = parse_log("(1.000000) can0 123#01\n(1.000100) can0 20000080#0000000000000000\nnot a frame\n");
+    assert_eq!(candump.dialect, LogDialect::Candump);
+    assert_eq!((candump.frames.len(), candump.error_frames, candump.unparsed.len()), (1, 1, 1));
+}
+
+#[test]
+fn probing_a_device_opens_and_closes_it_without_registering_it() {
+    let mock = Arc::new(MockCan::new());
+    let mut app_state = AppState::with_interface(mock.clone());
+    let mut board_info = VciBoardInfo::default();
+    board_info.str_serial_num[..8].copy_from_slice(b"MOCK0001");
+    mock.set_devices(vec![board_info]);
+
+    for _ in 0..2 {
+        let probe = app_state.probe_device(dev_type(), None, Some("MOCK0001"));
+        assert_eq!(probe.status, ProbeStatus::Ok);
+        assert_eq!(probe.dev_index, Some(0));
+        assert_eq!(probe.board_info.unwrap().serial_number, "MOCK0001");
+    }
+    assert_eq!(mock.open_count(dev_type(), 0), 2);
+    assert_eq!(mock.close_count(dev_type(), 0), 2);
+    assert!(!mock.is_open(dev_type(), 0));
+    assert_eq!(app_state.probe_device(dev_type(), Some(1), None).status, ProbeStatus::NotPresent);
+    assert_eq!(app_state.probe_device(dev_type(), None, Some("OTHER001")).status, ProbeStatus::NotPresent);
+
+    // 被其他程式開啟時 VCI_OpenDevice 失敗
+    mock.set_open_failure(true);
+    let probe = app_state.probe_device(dev_type(), Some(0), None);
+    assert_eq!(probe.status, ProbeStatus::OpenFailed);
+    assert!(probe.error.is_some());
+    mock.set_open_failure(false);
+
+    // 本程式已開啟的裝置不會被探測關閉
+    app_state.open_device(dev_type(), 0, None).unwrap();
+    assert_eq!(app_state.probe_device(dev_type(), Some(0), None).status, ProbeStatus::AlreadyOpen);
+    assert!(mock.is_open(dev_type(), 0));
+}
+
+#[test]
+fn duplicate_frames_within_the_dedupe_window_are_counted_but_not_buffered() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let key = (dev_type(), 0);
+    state
+        .lock()
+        .unwrap()
+        .set_dedupe(key, 0, Some(Dedupe::new(1_000_000, DedupeCompare::IdData, false)))
+        .unwrap();
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x100, &[1]), frame(0x100, &[1]), frame(0x100, &[2]), frame(0x100, &[1])]);
+    let options = ReceiveOptions {
+        stats_interval: Duration::from_millis(20),
+        ..ReceiveOptions::default()
+    };
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, options).unwrap();
+    let stats = events.wait_for("can-stats", 1);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    let data: Vec<Value> = events.named("can-data").iter().map(|f| f["data"].clone()).collect();
+    assert_eq!(data, [json!([1]), json!([2])]);
+    assert_eq!(stats[0]["rx_total"], 4);
+    assert_eq!(stats[0]["suppressed"], 2);
+}
+
+#[test]
+fn a_planned_channel_change_lists_the_diff_and_restarts_the_receive_stream() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let unchanged: ChannelConfig = serde_json::from_value(json!({"channel": 0, "timing0": 0x00, "timing1": 0x1C})).unwrap();
+    let plan = state.lock().unwrap().plan_channel_change(None, None, &unchanged).unwrap();
+    assert!(plan.changes.is_empty() && !plan.reinit_required);
+
+    let (_, previous) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    let filtered: ChannelConfig = serde_json::from_value(json!({
+        "channel": 0,
+        "timing0": 0x00,
+        "timing1": 0x1C,
+        "filter": {"acc_code": 0x2000_0000, "acc_mask": 0x1FFF_FFFF, "filter": 1}
+    }))
+    .unwrap();
+    let plan = state.lock().unwrap().plan_channel_change(None, None, &filtered).unwrap();
+    let fields: Vec<&str> = plan.changes.iter().map(|change| change.field).collect();
+    assert_eq!(fields, ["acc_code", "acc_mask"]);
+    assert!(plan.reinit_required && plan.stream_restart_required && !plan.replay_aborts);
+    // 規劃不會改變任何狀態
+    assert_eq!(state.lock().unwrap().receiving_channels(None, None).unwrap(), [0]);
+
+    change_channel(&state, events.clone(), None, None, &filtered).unwrap();
+    previous.join().unwrap();
+    assert!(mock.is_started(dev_type(), 0, 0));
+    assert_eq!(events.named("channel-changed").len(), 1);
+    // 接收以原本的選項重新啟動
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x100, &[1])]);
+    assert_eq!(events.wait_for("can-data", 1).len(), 1);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    let replanned = state.lock().unwrap().plan_channel_change(None, None, &filtered).unwrap();
+    assert!(replanned.changes.is_empty());
+}
+
+#[test]
+fn auto_connect_gives_up_after_max_attempts_and_connects_once_the_device_is_present() {
+    let mock = Arc::new(MockCan::new());
+    let state = Arc::new(StateMutex::new(AppState::with_interface(mock.clone())));
+    let events = RecordedEvents::default();
+    let channel = json!({ "channel": 0, "timing0": 0, "timing1": 0x1C, "mode": 0, "acc_code": 0, "acc_mask": 0xFFFF_FFFFu32, "filter": 1, "started": true });
+    let settings: AutoConnectSettings = serde_json::from_value(json!({
+        "dev_type": "Usbcan2",
+        "serial_number": "MOCK0001",
+        "channels": [channel],
+        "start_receiving": true,
+        "retry_interval_ms": 1,
+        "max_attempts": 2,
+    }))
+    .unwrap();
+    let running = AtomicBool::new(true);
+
+    // 裝置尚未插上：重試到 max_attempts 後放棄，之後仍可手動開啟
+    let status = run_auto_connect(&state, events.clone(), &settings, &running);
+    assert!(matches!(status, AutoConnectStatus::Failed(ref failed) if failed.attempts == 2 && !failed.cancelled));
+    assert_eq!(events.named("auto-connect-started").len(), 1);
+    assert_eq!(events.named("auto-connect-failed").len(), 1);
+    assert!(events.named("auto-connect-finished").is_empty());
+    assert!(state.lock().unwrap().receiving_channels(None, None).is_err());
+
+    let mut board_info = VciBoardInfo::default();
+    board_info.str_serial_num[..8].copy_from_slice(b"MOCK0001");
+    mock.set_devices(vec![board_info]);
+    let status = run_auto_connect(&state, events.clone(), &settings, &running);
+    assert!(matches!(status, AutoConnectStatus::Connected(ref finished) if finished.receiving == [0]));
+    assert_eq!(events.named("auto-connect-finished")[0]["dev_index"], 0);
+    assert!(mock.is_open(dev_type(), 0));
+    assert_eq!(state.lock().unwrap().receiving_channels(None, None).unwrap(), vec![0]);
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x123, &[1])]);
+    assert_eq!(events.wait_for("can-data", 1).len(), 1);
+
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+
+    // cancel_auto_connect 清除 running 後不再重試
+    let missing = AutoConnectSettings {
+        serial_number: Some("OTHER001".into()),
+        ..settings
+    };
+    let cancelled = AtomicBool::new(false);
+    let status = run_auto_connect(&state, events.clone(), &missing, &cancelled);
+    assert!(matches!(status, AutoConnectStatus::Failed(ref failed) if failed.attempts == 1 && failed.cancelled));
+}
+
+#[test]
+fn frames_carry_the_config_generation_of_their_channel() {
+    let (mock, state) = setup();
+    let key = (dev_type(), 0);
+    transmit_tracked(&state, key, 0, &[frame(0x100, &[1])], true, TxRetry::default()).unwrap();
+    let listen_only = VciInitConfig { mode: 1, acc_code: 0x100 << 21, acc_mask: 0x001F_FFFF, ..config() };
+    state.lock().unwrap().start_channel(key, 0, listen_only).unwrap();
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x100, &[2])]);
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    events.wait_for("can-data", 1);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    let recent = query_recent_frames(&state, &FrameQuery::channel(0), None, 10, false).unwrap();
+    let generations: Vec<u64> = recent.frames.iter().map(|f| f.frame.config_generation).collect();
+    assert_eq!(generations, [1, 2]);
+    let history = state.lock().unwrap().config_history(key, 0);
+    assert_eq!(history.iter().map(|g| (g.generation, g.mode)).collect::<Vec<_>>(), [(1, "normal"), (2, "listen-only")]);
+    assert!(history[0].superseded_at_us.is_some());
+    assert_eq!(history[1].config.acc_code, 0x100 << 21);
+    assert!(history[1].superseded_at_us.is_none());
+    // 另一個通道有自己的世代
+    assert_eq!(state.lock().unwrap().config_history(key, 1).len(), 1);
+}
+
+#[test]
+fn frames_after_a_reconnect_carry_the_new_stream_generation() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x100, &[1])]);
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    events.wait_for("can-data", 1);
+    let faster = VciInitConfig { timing0: 0x00, timing1: 0x14, ..config() };
+    reconnect_device(&state, events.clone(), None, None, [0, 1], faster).unwrap();
+    handle.join().unwrap();
+    mock.queue_receive(dev_type(), 0, 0, [frame(0x200, &[2])]);
+
+    let frames = events.wait_for("can-data", 2);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    let generations: Vec<(u64, u64)> = frames.iter().map(|f| (f["id"].as_u64().unwrap(), f["config_generation"].as_u64().unwrap())).collect();
+    assert_eq!(generations, [(0x100, 1), (0x200, 2)]);
+    let history = state.lock().unwrap().config_history((dev_type(), 0), 0);
+    assert_eq!(history.last().unwrap().bitrate, 1_000_000);
+}
+
+#[test]
+fn memory_caps_evict_the_oldest_ids_and_buffered_frames() {
+    let (mock, state) = setup();
+    let limits = MemoryLimits {
+        max_tracked_ids: 16,
+        max_buffer_bytes: 64 * 1024,
+        ..state.lock().unwrap().memory_limits()
+    };
+    state.lock().unwrap().set_memory_limits(limits).unwrap();
+    assert!(state.lock().unwrap().set_memory_limits(MemoryLimits { max_tracked_ids: 1, ..limits }).is_err());
+    let frames: Vec<VciCanObj> = (0..500).map(|i| frame(0x100 + i, &[i as u8])).collect();
+    mock.queue_receive(dev_type(), 0, 0, frames);
+    let events = RecordedEvents::default();
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, ReceiveOptions::default()).unwrap();
+    assert_eq!(events.wait_for("can-data", 500).len(), 500);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+
+    let stats = state.lock().unwrap().memory_stats().unwrap();
+    assert!(stats.id_statistics.entries <= 16, "{:?}", stats.id_statistics);
+    assert!(stats.id_statistics.evictions >= 484);
+    assert!(stats.ring_buffer.bytes <= 64 * 1024);
+    assert!(stats.ring_buffer.entries < 500 && stats.ring_buffer.evictions > 0);
+    assert_eq!(stats.total_bytes, stats.ring_buffer.bytes + stats.id_statistics.bytes + stats.captures.bytes);
+    // 保留的是最新的訊框
+    let recent = query_recent_frames(&state, &FrameQuery::default(), None, 1, false).unwrap();
+    assert_eq!(recent.frames[0].frame.id, 0x100 + 499);
+}
+
+#[test]
+fn cancel_operation_stops_a_sequence_and_reports_it_once() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let steps = serde_json::from_value(json!([
+        { "frame": { "id": 0x100, "data": [1] }, "delay_after_ms": 20, "repeat": 100 },
+    ]))
+    .unwrap();
+    let operation_id = spawn_tx_sequence(&state, events.clone(), None, None, 0, steps).unwrap();
+    events.wait_for("sequence-progress", 2);
+
+    let listed = state.lock().unwrap().list_operations();
+    assert_eq!(listed.len(), 1);
+    assert_eq!((listed[0].operation_id, listed[0].kind, listed[0].channel), (operation_id, OperationKind::Sequence, 0));
+    assert_eq!(listed[0].progress.total, Some(100));
+    assert!(listed[0].progress.done >= 2 && !listed[0].cancel_requested);
+    assert_eq!(state.lock().unwrap().cancel_operation(operation_id, Some(OperationKind::Burst)), None);
+    assert_eq!(state.lock().unwrap().cancel_operation(operation_id, None), Some(OperationKind::Sequence));
+
+    let finished = events.wait_for("operation-finished", 1);
+    assert_eq!(finished.len(), 1);
+    assert_eq!(finished[0]["operation_id"], operation_id);
+    assert_eq!(finished[0]["kind"], "sequence");
+    assert_eq!(finished[0]["status"], "cancelled");
+    let sent = finished[0]["progress"]["done"].as_u64().unwrap();
+    assert!(sent < 100);
+    assert_eq!(events.wait_for("sequence-finished", 1)[0]["frames_sent"], sent);
+    assert!(state.lock().unwrap().list_operations().is_empty());
+    // 取消後不再有訊框送出
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(mock.transmitted().len() as u64, sent);
+}
+
+#[test]
+fn tx_inhibit_refuses_every_transmit_on_the_channel_until_cleared() {
+    let (mock, state) = setup();
+    let key = (dev_type(), 0);
+    state.lock().unwrap().set_tx_inhibit(None, None, 0, true).unwrap();
+
+    let direct = transmit(&state, key, 0, &[frame(0x100, &[1])]);
+    assert_eq!(direct.unwrap_err(), "TxInhibited { channel: 0 }");
+    let tracked = transmit_tracked(&state, key, 0, &[frame(0x101, &[2]), frame(0x102, &[3])], true, TxRetry::default());
+    assert!(tracked.err().is_some_and(|e| e.starts_with("TxInhibited")));
+    assert!(mock.transmitted().is_empty());
+    // 其他通道不受影響
+    assert_eq!(transmit(&state, key, 1, &[frame(0x200, &[4])]).unwrap(), 1);
+
+    state.lock().unwrap().set_tx_inhibit(None, None, 0, false).unwrap();
+    assert_eq!(transmit(&state, key, 0, &[frame(0x103, &[5])]).unwrap(), 1);
+    let transmitted = mock.transmitted();
+    assert_eq!(transmitted.len(), 2);
+    assert_eq!((transmitted[0].0, transmitted[0].1.id), (1, 0x200));
+    assert_eq!((transmitted[1].0, transmitted[1].1.id), (0, 0x103));
+}
+
+#[test]
+fn tx_inhibit_follows_the_device_when_it_reconnects_at_a_new_index() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    state.lock().unwrap().set_tx_inhibit(None, None, 0, true).unwrap();
+    let mut other = VciBoardInfo::default();
+    other.str_serial_num[..8].copy_from_slice(b"OTHER001");
+    let mut board_info = VciBoardInfo::default();
+    board_info.str_serial_num[..8].copy_from_slice(b"MOCK0001");
+    // 重新列舉後裝置換到 index 1
+    mock.set_devices(vec![other, board_info]);
+    mock.set_receive_error(Some(-1));
+    let options = ReceiveOptions {
+        auto_reconnect: true,
+        ..Default::default()
+    };
+    let (_, handle) = spawn_receive_loop(&state, events.clone(), None, None, 0, options).unwrap();
+    events.wait_for("can-disconnected", 1);
+    mock.set_receive_error(None);
+    let reconnected = events.wait_for("can-reconnected", 1);
+    assert_eq!(reconnected[0]["dev_index"], 1);
+
+    let refused = transmit_tracked(&state, (dev_type(), 1), 0, &[frame(0x100, &[1])], true, TxRetry::default());
+    assert_eq!(refused.err().as_deref(), Some("TxInhibited { channel: 0 }"));
+    assert!(transmit_tracked(&state, (dev_type(), 1), 1, &[frame(0x101, &[2])], true, TxRetry::default()).is_ok());
+    assert_eq!(mock.transmitted().len(), 1);
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn get_status_responds_while_a_single_receive_waits_on_the_driver() {
+    let (_mock, state) = setup();
+    let waiting = {
+        let state = state.clone();
+        std::thread::spawn(move || receive_once(&state, None, None, 0, 10, 1000))
+    };
+    std::thread::sleep(Duration::from_millis(50));
+    let asked = Instant::now();
+    let status = app_status(&state).unwrap();
+
+    assert!(asked.elapsed() < Duration::from_millis(100), "get_status took {:?}", asked.elapsed());
+    assert!(!waiting.is_finished());
+    assert_eq!(status.devices.len(), 1);
+    assert!(waiting.join().unwrap().unwrap().is_empty());
+}
+
+#[test]
+fn sibling_streams_follow_a_device_reopened_at_a_new_index() {
+    let (mock, state) = setup();
+    let events = RecordedEvents::default();
+    let mut other = VciBoardInfo::default();
+    other.str_serial_num[..8].copy_from_slice(b"OTHER001");
+    let mut board_info = VciBoardInfo::default();
+    board_info.str_serial_num[..8].copy_from_slice(b"MOCK0001");
+    mock.set_devices(vec![other, board_info]);
+    mock.set_receive_error(Some(-1));
+    let options = ReceiveOptions {
+        auto_reconnect: true,
+        ..Default::default()
+    };
+    let handles: Vec<_> = [0, 1]
+        .into_iter()
+        .map(|channel| spawn_receive_loop(&state, events.clone(), None, None, channel, options).unwrap().1)
+        .collect();
+    events.wait_for("can-disconnected", 2);
+    mock.set_receive_error(None);
+
+    // 先重連的執行緒把裝置搬到 index 1，另一個通道以序號找到它而不是回報斷線
+    let mut reconnected = events.wait_for("can-reconnected", 2);
+    reconnected.sort_by_key(|event| event["channel"].as_u64());
+    assert_eq!(reconnected.len(), 2);
+    assert!(reconnected.iter().all(|event| event["dev_index"] == 1));
+    assert_eq!(state.lock().unwrap().receiving_channels(Some(dev_type()), Some(1)).unwrap(), vec![0, 1]);
+    assert!(events.named("stream-ended").is_empty());
+    state.lock().unwrap().stop_receiving(None, None, None).unwrap();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+const ISOTP_TX: u32 = 0x7E0;
+const ISOTP_RX: u32 = 0x7E8;
+
+fn isotp_link(state: &Arc<StateMutex>, options: IsoTpOptions) -> IsoTpLink {
+    IsoTpLink::open(state, (dev_type(), 0), 0, ISOTP_TX, ISOTP_RX, options).unwrap()
+}
+
+/// 連線送出的 ISO-TP 訊框資料，依送出順序
+fn isotp_sent(mock: &MockCan) -> Vec<Vec<u8>> {
+    mock.transmitted()
+        .into_iter()
+        .filter(|(channel, can_obj)| *channel == 0 && can_obj.id == ISOTP_TX)
+        .map(|(_, can_obj)| can_obj.data[..can_obj.data_len as usize].to_vec())
+        .collect()
+}
+
+/// 等到連線送出至少 count 個訊框，逾時則回傳目前送出的
+fn wait_isotp_sent(mock: &MockCan, count: usize) -> Vec<Vec<u8>> {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    loop {
+        let sent = isotp_sent(mock);
+        if sent.len() >= count || Instant::now() > deadline {
+            return sent;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn isotp_send_segments_a_message_and_wraps_the_sequence_number_after_0xf() {
+    let (mock, state) = setup();
+    let link = isotp_link(&state, IsoTpOptions::default());
+    // first frame 帶 6 位元組，其餘 17 個 consecutive frame 讓序號繞過 0xF
+    let data: Vec<u8> = (0..124).collect();
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x30, 0, 0])]);
+
+    assert_eq!(link.send(&data).unwrap(), 18);
+
+    let sent = isotp_sent(&mock);
+    assert_eq!(sent[0], [0x10, 124, 0, 1, 2, 3, 4, 5]);
+    let pcis: Vec<u8> = sent[1..].iter().map(|f| f[0]).collect();
+    let expected: Vec<u8> = (1..=17).map(|n| 0x20 | (n & 0x0F) as u8).collect();
+    assert_eq!(pcis, expected);
+    assert_eq!(&pcis[14..], [0x2F, 0x20, 0x21]);
+    let reassembled: Vec<u8> = sent[0][2..].iter().chain(sent[1..].iter().flat_map(|f| f[1..].iter())).copied().take(124).collect();
+    assert_eq!(reassembled, data);
+    // 最後一個訊框以填充位元組補滿 8 位元組
+    assert_eq!(sent[17], [0x21, 118, 119, 120, 121, 122, 123, 0xCC]);
+}
+
+#[test]
+fn isotp_send_uses_the_32_bit_first_frame_length_beyond_4095_bytes() {
+    let (mock, state) = setup();
+    let link = isotp_link(&state, IsoTpOptions::default());
+    let data: Vec<u8> = (0..5000).map(|n| n as u8).collect();
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x30, 0, 0])]);
+
+    // 4998 位元組需要 714 個 consecutive frame
+    assert_eq!(link.send(&data).unwrap(), 715);
+
+    let sent = isotp_sent(&mock);
+    assert_eq!(sent[0], [0x10, 0x00, 0x00, 0x00, 0x13, 0x88, 0, 1]);
+    assert_eq!(sent[1], [0x21, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn isotp_send_waits_through_fc_wait_and_gives_up_after_max_wait_frames() {
+    let (mock, state) = setup();
+    let data: Vec<u8> = (0..20).collect();
+    let options = IsoTpOptions {
+        max_wait_frames: 2,
+        ..Default::default()
+    };
+
+    let link = isotp_link(&state, options.clone());
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x31]), frame(ISOTP_RX, &[0x31]), frame(ISOTP_RX, &[0x30, 0, 0])]);
+    assert_eq!(link.send(&data).unwrap(), 3);
+    drop(link);
+
+    let link = isotp_link(&state, options);
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x31]), frame(ISOTP_RX, &[0x31]), frame(ISOTP_RX, &[0x31])]);
+    assert!(matches!(link.send(&data), Err(IsoTpError::TooManyWaits { waits: 3 })));
+    // 第二次只送出 first frame
+    assert_eq!(isotp_sent(&mock).len(), 4);
+}
+
+#[test]
+fn isotp_send_stops_when_the_receiver_reports_overflow() {
+    let (mock, state) = setup();
+    let link = isotp_link(&state, IsoTpOptions::default());
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x32, 0, 0])]);
+
+    assert!(matches!(link.send(&[0; 20]), Err(IsoTpError::FlowControlOverflow)));
+    assert_eq!(isotp_sent(&mock).len(), 1);
+}
+
+#[test]
+fn isotp_send_rejects_an_empty_payload_without_sending() {
+    let (mock, state) = setup();
+    let link = isotp_link(&state, IsoTpOptions::default());
+
+    let err = link.send(&[]).unwrap_err();
+    assert!(err.to_string().starts_with("InvalidArgument"), "{}", err);
+    assert!(isotp_sent(&mock).is_empty());
+}
+
+#[test]
+fn isotp_receive_reassembles_a_message_across_the_sequence_wrap() {
+    let (mock, state) = setup();
+    let link = isotp_link(&state, IsoTpOptions::default());
+    let data: Vec<u8> = (0..125).collect();
+    let mut first = vec![0x10, 125];
+    first.extend_from_slice(&data[..6]);
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &first)]);
+    let receiver = std::thread::spawn(move || link.receive(Instant::now() + Duration::from_secs(2)));
+
+    // 對方等到 flow control 才送出 consecutive frame
+    assert_eq!(wait_isotp_sent(&mock, 1)[0], [0x30, 0, 0, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC]);
+    let consecutive = data[6..].chunks(7).enumerate().map(|(n, chunk)| {
+        let mut bytes = vec![0x20 | ((n + 1) & 0x0F) as u8];
+        bytes.extend_from_slice(chunk);
+        frame(ISOTP_RX, &bytes)
+    });
+    mock.queue_receive(dev_type(), 0, 0, consecutive);
+
+    assert_eq!(receiver.join().unwrap().unwrap(), Some(data));
+}
+
+#[test]
+fn isotp_receive_reads_the_32_bit_first_frame_length_and_checks_the_sequence() {
+    let (mock, state) = setup();
+    let link = isotp_link(&state, IsoTpOptions::default());
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x10, 0x00, 0x00, 0x00, 0x00, 0x10, 0, 1])]);
+    let receiver = std::thread::spawn(move || link.receive(Instant::now() + Duration::from_secs(2)));
+
+    wait_isotp_sent(&mock, 1);
+    // 序號 1 之後跳到 3
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x21, 2, 3, 4, 5, 6, 7, 8]), frame(ISOTP_RX, &[0x23, 9, 10, 11, 12, 13, 14, 15])]);
+
+    assert!(matches!(receiver.join().unwrap(), Err(IsoTpError::SequenceError { expected: 2, received: 3 })));
+}
+
+#[test]
+fn isotp_receive_rejects_a_first_frame_that_fits_in_a_single_frame() {
+    let (mock, state) = setup();
+    let link = isotp_link(&state, IsoTpOptions::default());
+    mock.queue_receive(dev_type(), 0, 0, [frame(ISOTP_RX, &[0x10, 0x07, 1, 2, 3, 4, 5, 6])]);
+
+    let result = link.receive(Instant::now() + Duration::from_secs(2));
+    assert!(matches!(result, Err(IsoTpError::UnexpectedFrame { pci: 0x10 })));
+    // 不回覆 flow control
+    assert!(isotp_sent(&mock).is_empty());
+}